@@ -0,0 +1,41 @@
+// Buttplug Rust Source Code File - See https://buttplug.io for more info.
+//
+// Copyright 2016-2023 Nonpolynomial Labs LLC. All rights reserved.
+//
+// Licensed under the BSD 3-Clause license. See LICENSE file in the project root
+// for full license information.
+
+//! Writes the list of enabled crate features to `$OUT_DIR/features.rs`, for build and status
+//! reports.
+
+use std::{env, fs, path::Path};
+
+fn main() {
+  println!("cargo:rerun-if-changed=Cargo.toml");
+  let manifest_dir = env::var("CARGO_MANIFEST_DIR").expect("Set by cargo");
+  let manifest =
+    fs::read_to_string(Path::new(&manifest_dir).join("Cargo.toml")).expect("Manifest is readable");
+
+  // Cargo only passes features as CARGO_FEATURE_* variables, which lose their dashes, so take the
+  // names from the manifest. This also skips the implicit features of optional dependencies.
+  let features: Vec<&str> = manifest
+    .lines()
+    .skip_while(|line| line.trim() != "[features]")
+    .skip(1)
+    .take_while(|line| !line.starts_with('['))
+    .filter_map(|line| line.split_once('=').map(|(name, _)| name.trim()))
+    .filter(|name| !name.is_empty() && !name.starts_with('#') && *name != "default")
+    .filter(|name| {
+      let var = format!("CARGO_FEATURE_{}", name.to_uppercase().replace('-', "_"));
+      env::var_os(var).is_some()
+    })
+    .collect();
+
+  let out_dir = env::var("OUT_DIR").expect("Set by cargo");
+  let contents = format!(
+    "/// Crate features the library was built with.\npub(crate) const ENABLED_FEATURES: &[&str] = \
+     &{:?};\n",
+    features
+  );
+  fs::write(Path::new(&out_dir).join("features.rs"), contents).expect("OUT_DIR is writable");
+}
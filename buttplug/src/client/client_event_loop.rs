@@ -13,26 +13,40 @@ use super::{
   ButtplugClientEvent,
   ButtplugClientMessageFuturePair,
 };
-use crate::core::{
-  connector::{ButtplugConnector, ButtplugConnectorStateShared},
-  errors::{ButtplugDeviceError, ButtplugError},
-  message::{
-    ButtplugCurrentSpecClientMessage,
-    ButtplugCurrentSpecServerMessage,
-    ButtplugDeviceMessage,
-    ButtplugMessageValidator,
-    DeviceList,
-    DeviceMessageInfo,
+use crate::{
+  core::{
+    connector::{ButtplugConnector, ButtplugConnectorStateShared},
+    errors::{ButtplugDeviceError, ButtplugError, ButtplugUnknownError},
+    message::{
+      ButtplugCurrentSpecClientMessage,
+      ButtplugCurrentSpecServerMessage,
+      ButtplugDeviceMessage,
+      ButtplugMessage,
+      ButtplugMessageValidator,
+      DeviceList,
+      DeviceMessageInfo,
+      Endpoint,
+      RawReading,
+    },
   },
+  util::async_manager,
 };
 use dashmap::DashMap;
-use futures::FutureExt;
-use std::sync::{
-  atomic::{AtomicBool, Ordering},
-  Arc,
+use futures::{future::Either, stream, FutureExt, StreamExt};
+use std::{
+  collections::HashMap,
+  sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc,
+  },
+  time::Duration,
 };
 use tokio::sync::{broadcast, mpsc};
 
+/// Longest the event loop waits between checks for messages that have gone unanswered for longer
+/// than the reply timeout. Shorter timeouts are checked as often as they are long.
+const REPLY_EVICTION_INTERVAL: Duration = Duration::from_secs(5);
+
 /// Enum used for communication from the client to the event loop.
 #[derive(Clone)]
 pub(super) enum ButtplugClientRequest {
@@ -97,6 +111,12 @@ where
   /// Receives incoming messages from client instances.
   from_client_receiver: broadcast::Receiver<ButtplugClientRequest>,
   sorter: ClientMessageSorter,
+  /// How long to wait for a reply to a client message before failing it, if ever. See
+  /// [ButtplugClient::with_reply_timeout](super::ButtplugClient::with_reply_timeout).
+  reply_timeout: Option<Duration>,
+  /// Data of raw readings the server split to fit the connector, by id, device index and
+  /// endpoint, until the last piece arrives.
+  partial_raw_readings: HashMap<(u32, u32, Endpoint), Vec<u8>>,
 }
 
 impl<ConnectorType> ButtplugClientEventLoop<ConnectorType>
@@ -109,6 +129,7 @@ where
   /// Given the [ButtplugClientConnector] object, as well as the channels used
   /// for communicating with the client, creates an event loop structure and
   /// returns it.
+  #[allow(clippy::too_many_arguments)]
   pub fn new(
     connected_status: Arc<AtomicBool>,
     connector: ConnectorType,
@@ -116,8 +137,14 @@ where
     to_client_sender: broadcast::Sender<ButtplugClientEvent>,
     from_client_sender: broadcast::Sender<ButtplugClientRequest>,
     device_map: Arc<DashMap<u32, Arc<ButtplugClientDevice>>>,
+    max_pending_messages: Option<usize>,
+    reply_timeout: Option<Duration>,
   ) -> Self {
     trace!("Creating ButtplugClientEventLoop instance.");
+    let sorter = ClientMessageSorter::for_connection(
+      connector.connection_metrics().unwrap_or_default(),
+      max_pending_messages,
+    );
     Self {
       connected_status,
       device_map,
@@ -126,10 +153,39 @@ where
       to_client_sender,
       from_connector_receiver,
       connector,
-      sorter: ClientMessageSorter::default(),
+      sorter,
+      reply_timeout,
+      partial_raw_readings: HashMap::new(),
     }
   }
 
+  /// Put raw readings split by the server back together. Returns None while pieces are still
+  /// missing.
+  fn reassemble_raw_reading(
+    &mut self,
+    msg: ButtplugCurrentSpecServerMessage,
+  ) -> Option<ButtplugCurrentSpecServerMessage> {
+    let ButtplugCurrentSpecServerMessage::RawReading(reading) = msg else {
+      return Some(msg);
+    };
+    let key = (reading.id(), reading.device_index(), reading.endpoint());
+    if reading.continued() {
+      self
+        .partial_raw_readings
+        .entry(key)
+        .or_default()
+        .extend_from_slice(reading.data());
+      return None;
+    }
+    let Some(mut data) = self.partial_raw_readings.remove(&key) else {
+      return Some(ButtplugCurrentSpecServerMessage::RawReading(reading));
+    };
+    data.extend_from_slice(reading.data());
+    let mut whole = RawReading::new(reading.device_index(), reading.endpoint(), data);
+    whole.set_id(reading.id());
+    Some(ButtplugCurrentSpecServerMessage::RawReading(whole))
+  }
+
   /// Creates a [ButtplugClientDevice] from [DeviceMessageInfo].
   ///
   /// Given a [DeviceMessageInfo] from a [DeviceAdded] or [DeviceList] message,
@@ -200,6 +256,10 @@ where
   /// and update its map accordingly. After that, it will pass the information
   /// on as a [ButtplugClientEvent] to the [ButtplugClient].
   async fn parse_connector_message(&mut self, msg: ButtplugCurrentSpecServerMessage) {
+    let Some(msg) = self.reassemble_raw_reading(msg) else {
+      trace!("Raw reading piece stored until the rest arrives.");
+      return;
+    };
     if self.sorter.maybe_resolve_result(&msg) {
       trace!("Message future found, returning");
       return;
@@ -263,7 +323,7 @@ where
         }
       }
       ButtplugCurrentSpecServerMessage::Error(e) => {
-        self.send_client_event(ButtplugClientEvent::Error(e.into()));
+        self.send_client_event(ButtplugClientEvent::Error(e.original_error()));
       }
       _ => error!("Cannot process message, dropping: {:?}", msg),
     }
@@ -280,7 +340,11 @@ where
     }
 
     trace!("Sending message to connector: {:?}", msg_fut.msg);
-    self.sorter.register_future(&mut msg_fut);
+    if let Err(e) = self.sorter.register_future(&mut msg_fut) {
+      error!("Cannot register message future: {}", e);
+      msg_fut.waker.set_reply(Err(e));
+      return;
+    }
     if self.connector.send(msg_fut.msg).await.is_err() {
       error!("Sending message failed, connector most likely no longer connected.");
     }
@@ -323,8 +387,23 @@ where
   /// Runs the event loop, returning once either the client or connector drops.
   pub async fn run(&mut self) {
     debug!("Running client event loop.");
+    let eviction_ticks = match self.reply_timeout {
+      Some(reply_timeout) => {
+        let check_interval = REPLY_EVICTION_INTERVAL.min(reply_timeout);
+        Either::Left(stream::unfold((), move |()| async move {
+          async_manager::sleep(check_interval).await;
+          Some((reply_timeout, ()))
+        }))
+      }
+      None => Either::Right(stream::pending()),
+    }
+    .fuse();
+    pin_mut!(eviction_ticks);
     loop {
       select! {
+        reply_timeout = eviction_ticks.select_next_some() => {
+          self.sorter.evict_older_than(reply_timeout);
+        },
         event = self.from_connector_receiver.recv().fuse() => match event {
           None => {
             info!("Connector disconnected, exiting loop.");
@@ -356,6 +435,10 @@ where
     device_indexes
       .iter()
       .for_each(|k| self.disconnect_device(*k));
+    // Nothing still waiting on a reply is going to get one now.
+    self
+      .sorter
+      .drain_with_error(ButtplugUnknownError::ConnectorDisconnected.into());
     self.connected_status.store(false, Ordering::SeqCst);
     self.send_client_event(ButtplugClientEvent::ServerDisconnect);
 
@@ -13,13 +13,27 @@ use crate::{
     ButtplugClientMessageFuturePair,
     ButtplugServerMessageStateShared,
   },
-  core::message::{ButtplugCurrentSpecServerMessage, ButtplugMessage, ButtplugMessageValidator},
+  core::{
+    connector::{ConnectionMetrics, MessageSorterStats},
+    errors::{ButtplugError, ButtplugUnknownError},
+    message::{ButtplugCurrentSpecServerMessage, ButtplugMessage, ButtplugMessageValidator},
+  },
 };
 use dashmap::DashMap;
-use std::sync::{
-  atomic::{AtomicU32, Ordering},
-  Arc,
+use std::{
+  sync::{
+    atomic::{AtomicU32, Ordering},
+    Arc,
+  },
+  time::Duration,
 };
+use web_time::Instant;
+
+/// Future waiting on a server response, with when it started waiting.
+struct PendingReply {
+  state: ButtplugServerMessageStateShared,
+  registered_at: Instant,
+}
 
 /// Message sorting and pairing for remote client connectors.
 ///
@@ -58,27 +72,159 @@ pub struct ClientMessageSorter {
   /// This is where we store message `id`s that are waiting for a return from the server. Once we
   /// get back a response with a matching `id`, we remove the entry from this map, and use the waker
   /// to complete the future with the received response message.
-  future_map: DashMap<u32, ButtplugServerMessageStateShared>,
+  future_map: DashMap<u32, PendingReply>,
 
   /// Message `id` counter
   ///
-  /// Every time we add a message to the future_map, we need it to have a unique `id`. We assume
-  /// that unsigned 2^32 will be enough (Buttplug isn't THAT chatty), and use it as a monotonically
-  /// increasing counter for setting `id`s.
+  /// Every time we add a message to the future_map, we need it to have a unique `id`. This is an
+  /// increasing counter that wraps back around to 1 after 2^32 messages, skipping the reserved
+  /// `id` of 0.
   current_id: Arc<AtomicU32>,
+
+  /// Maximum number of futures that can wait on a response at once, if any.
+  ///
+  /// A server that never replies would otherwise grow future_map forever.
+  max_capacity: Option<usize>,
+
+  /// Where [MessageSorterStats] are counted, shared with the connector if it keeps metrics.
+  metrics: ConnectionMetrics,
 }
 
 impl ClientMessageSorter {
+  /// Create a sorter that will hold at most `max_capacity` futures awaiting responses.
+  pub fn with_max_capacity(max_capacity: usize) -> Self {
+    Self {
+      max_capacity: Some(max_capacity),
+      ..Self::default()
+    }
+  }
+
+  /// Create a sorter that counts its [MessageSorterStats] into `metrics`, so they show up in
+  /// [ConnectionMetrics::message_sorter_stats].
+  pub fn with_metrics(metrics: ConnectionMetrics) -> Self {
+    Self {
+      metrics,
+      ..Self::default()
+    }
+  }
+
+  /// Sorter for a client connection, counting into `metrics` and holding at most `max_capacity`
+  /// futures, if set.
+  pub(crate) fn for_connection(metrics: ConnectionMetrics, max_capacity: Option<usize>) -> Self {
+    Self {
+      max_capacity,
+      ..Self::with_metrics(metrics)
+    }
+  }
+
+  /// Counts of messages registered, resolved, expired and failed by id collisions so far, and the
+  /// number still waiting on a reply.
+  pub fn stats(&self) -> MessageSorterStats {
+    self.metrics.message_sorter_stats()
+  }
+
+  fn update_pending(&self) {
+    self
+      .metrics
+      .message_sorter
+      .current_pending
+      .store(self.future_map.len(), Ordering::SeqCst);
+  }
+
   /// Registers a future to be resolved when we receive a response.
   ///
   /// Given a message and its related future, set the message's `id`, and match that id with the
   /// future to be resolved when we get a response back.
-  pub fn register_future(&self, msg_fut: &mut ButtplugClientMessageFuturePair) {
-    let id = self.current_id.load(Ordering::SeqCst);
+  ///
+  /// # Errors
+  ///
+  /// Returns [ButtplugClientError::MessageSorterFullError] if the sorter was created with a
+  /// maximum capacity and that many futures are already waiting on responses.
+  #[allow(clippy::result_large_err)]
+  pub fn register_future(
+    &self,
+    msg_fut: &mut ButtplugClientMessageFuturePair,
+  ) -> Result<(), ButtplugClientError> {
+    if let Some(max_capacity) = self.max_capacity {
+      if self.future_map.len() >= max_capacity {
+        return Err(ButtplugClientError::MessageSorterFullError(max_capacity));
+      }
+    }
+    let id = self
+      .current_id
+      .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |id| {
+        Some(id.wrapping_add(1).max(1))
+      })
+      .expect("Update always returns a value");
     trace!("Setting message id to {}", id);
     msg_fut.msg.set_id(id);
-    self.future_map.insert(id, msg_fut.waker.clone());
-    self.current_id.store(id + 1, Ordering::SeqCst);
+    // Only possible once ids wrap around, with a reply from 2^32 messages ago still outstanding.
+    // That reply is never coming, so fail the old future instead of leaving it waiting forever.
+    let pending = PendingReply {
+      state: msg_fut.waker.clone(),
+      registered_at: Instant::now(),
+    };
+    let counters = &self.metrics.message_sorter;
+    counters.registered.fetch_add(1, Ordering::SeqCst);
+    if let Some(old) = self.future_map.insert(id, pending) {
+      warn!(
+        "Message id {} reused before its reply arrived, failing old message.",
+        id
+      );
+      counters.collisions.fetch_add(1, Ordering::SeqCst);
+      old.state.set_reply(Err(
+        ButtplugError::from(ButtplugUnknownError::MessageIdReused(id)).into(),
+      ));
+    }
+    self.update_pending();
+    Ok(())
+  }
+
+  /// Fail every future still waiting on a response with `err`, and clear them out. Used when the
+  /// connection goes away, since their responses will never arrive.
+  pub fn drain_with_error(&self, err: ButtplugError) {
+    let ids: Vec<u32> = self.future_map.iter().map(|entry| *entry.key()).collect();
+    for id in ids {
+      if let Some((_, pending)) = self.future_map.remove(&id) {
+        pending.state.set_reply(Err(err.clone().into()));
+      }
+    }
+    self.update_pending();
+  }
+
+  /// Fail every future that has been waiting on a response for longer than `ttl` with
+  /// [ButtplugUnknownError::ReplyTimedOut], and clear them out. Returns the number of futures
+  /// evicted.
+  ///
+  /// Keeps messages the server never answers (e.g. a buggy server, or a connection dropping some
+  /// packets) from leaving callers waiting forever.
+  pub fn evict_older_than(&self, ttl: Duration) -> usize {
+    let expired: Vec<u32> = self
+      .future_map
+      .iter()
+      .filter(|entry| entry.value().registered_at.elapsed() >= ttl)
+      .map(|entry| *entry.key())
+      .collect();
+    let mut evicted = 0;
+    for id in expired {
+      if let Some((_, pending)) = self.future_map.remove(&id) {
+        warn!(
+          "No reply to message id {} within {:?}, failing message.",
+          id, ttl
+        );
+        pending.state.set_reply(Err(
+          ButtplugError::from(ButtplugUnknownError::ReplyTimedOut(ttl)).into(),
+        ));
+        evicted += 1;
+      }
+    }
+    self
+      .metrics
+      .message_sorter
+      .expired
+      .fetch_add(evicted as u64, Ordering::SeqCst);
+    self.update_pending();
+    evicted
   }
 
   /// Given a response message from the server, resolve related future if we have one.
@@ -89,8 +235,14 @@ impl ClientMessageSorter {
     let id = msg.id();
     trace!("Trying to resolve message future for id {}.", id);
     match self.future_map.remove(&id) {
-      Some((_, state)) => {
+      Some((_, PendingReply { state, .. })) => {
         trace!("Resolved id {} to a future.", id);
+        self
+          .metrics
+          .message_sorter
+          .resolved
+          .fetch_add(1, Ordering::SeqCst);
+        self.update_pending();
         if let Err(e) = msg.is_valid() {
           error!("Message not valid: {:?} - Error: {}", msg, e);
           state.set_reply(Err(ButtplugClientError::ButtplugError(e.into())));
@@ -118,6 +270,154 @@ impl Default for ClientMessageSorter {
     Self {
       future_map: DashMap::new(),
       current_id: Arc::new(AtomicU32::new(1)),
+      max_capacity: None,
+      metrics: ConnectionMetrics::default(),
+    }
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+  use crate::{
+    client::ButtplugServerMessageFuture,
+    core::message::{self, ButtplugCurrentSpecClientMessage},
+    util::async_manager,
+  };
+  use futures::FutureExt;
+
+  fn future_pair() -> ButtplugClientMessageFuturePair {
+    ButtplugClientMessageFuturePair::new(
+      ButtplugCurrentSpecClientMessage::Ping(message::Ping::default()),
+      ButtplugServerMessageFuture::default().get_state_clone(),
+    )
+  }
+
+  #[test]
+  fn test_sorter_max_capacity() {
+    let sorter = ClientMessageSorter::with_max_capacity(2);
+    assert!(sorter.register_future(&mut future_pair()).is_ok());
+    assert!(sorter.register_future(&mut future_pair()).is_ok());
+    assert!(matches!(
+      sorter.register_future(&mut future_pair()),
+      Err(ButtplugClientError::MessageSorterFullError(2))
+    ));
+    // Resolving a future frees up room for another.
+    assert!(sorter.maybe_resolve_result(&message::Ok::new(1).into()));
+    assert!(sorter.register_future(&mut future_pair()).is_ok());
+  }
+
+  #[test]
+  fn test_sorter_id_wrap_around() {
+    let sorter = ClientMessageSorter {
+      current_id: Arc::new(AtomicU32::new(u32::MAX)),
+      ..ClientMessageSorter::default()
+    };
+    let old_future = ButtplugServerMessageFuture::default();
+    let mut old_pair = ButtplugClientMessageFuturePair::new(
+      ButtplugCurrentSpecClientMessage::Ping(message::Ping::default()),
+      old_future.get_state_clone(),
+    );
+    sorter.future_map.insert(
+      1,
+      PendingReply {
+        state: old_pair.waker.clone(),
+        registered_at: Instant::now(),
+      },
+    );
+    old_pair.msg.set_id(1);
+
+    let mut last_pair = future_pair();
+    sorter.register_future(&mut last_pair).unwrap();
+    assert_eq!(last_pair.msg.id(), u32::MAX);
+    // Id 0 is skipped, and reusing 1 fails the message still waiting on it.
+    let mut wrapped_pair = future_pair();
+    sorter.register_future(&mut wrapped_pair).unwrap();
+    assert_eq!(wrapped_pair.msg.id(), 1);
+    assert!(matches!(
+      async_manager::block_on(old_future),
+      Err(ButtplugClientError::ButtplugError(
+        ButtplugError::ButtplugUnknownError(ButtplugUnknownError::MessageIdReused(1))
+      ))
+    ));
+    assert_eq!(sorter.future_map.len(), 2);
+    assert_eq!(sorter.stats().collisions(), 1);
+  }
+
+  #[test]
+  fn test_sorter_stats() {
+    let metrics = ConnectionMetrics::default();
+    let sorter = ClientMessageSorter::with_metrics(metrics.clone());
+    for _ in 0..3 {
+      sorter.register_future(&mut future_pair()).unwrap();
+    }
+    assert!(sorter.maybe_resolve_result(&message::Ok::new(1).into()));
+    assert!(!sorter.maybe_resolve_result(&message::Ok::new(1).into()));
+    std::thread::sleep(Duration::from_millis(10));
+    assert_eq!(sorter.evict_older_than(Duration::from_millis(5)), 2);
+    sorter.register_future(&mut future_pair()).unwrap();
+    let stats = sorter.stats();
+    assert_eq!(stats.registered(), 4);
+    assert_eq!(stats.resolved(), 1);
+    assert_eq!(stats.expired(), 2);
+    assert_eq!(stats.collisions(), 0);
+    assert_eq!(stats.current_pending(), 1);
+    // The connection's metrics handle sees the same counts.
+    assert_eq!(metrics.message_sorter_stats(), stats);
+  }
+
+  #[test]
+  fn test_sorter_drain_with_error() {
+    let sorter = ClientMessageSorter::default();
+    let future = ButtplugServerMessageFuture::default();
+    let mut pair = ButtplugClientMessageFuturePair::new(
+      ButtplugCurrentSpecClientMessage::Ping(message::Ping::default()),
+      future.get_state_clone(),
+    );
+    sorter.register_future(&mut pair).unwrap();
+    sorter.register_future(&mut future_pair()).unwrap();
+    sorter.drain_with_error(ButtplugUnknownError::ConnectorDisconnected.into());
+    assert!(sorter.future_map.is_empty());
+    assert!(matches!(
+      async_manager::block_on(future),
+      Err(ButtplugClientError::ButtplugError(
+        ButtplugError::ButtplugUnknownError(ButtplugUnknownError::ConnectorDisconnected)
+      ))
+    ));
+  }
+
+  #[test]
+  fn test_sorter_evict_older_than() {
+    let sorter = ClientMessageSorter::default();
+    let stale_futures: Vec<ButtplugServerMessageFuture> = (0..2)
+      .map(|_| {
+        let future = ButtplugServerMessageFuture::default();
+        let mut pair = ButtplugClientMessageFuturePair::new(
+          ButtplugCurrentSpecClientMessage::Ping(message::Ping::default()),
+          future.get_state_clone(),
+        );
+        sorter.register_future(&mut pair).unwrap();
+        future
+      })
+      .collect();
+    std::thread::sleep(Duration::from_millis(50));
+    let fresh_future = ButtplugServerMessageFuture::default();
+    let mut fresh_pair = ButtplugClientMessageFuturePair::new(
+      ButtplugCurrentSpecClientMessage::Ping(message::Ping::default()),
+      fresh_future.get_state_clone(),
+    );
+    sorter.register_future(&mut fresh_pair).unwrap();
+
+    assert_eq!(sorter.evict_older_than(Duration::from_millis(40)), 2);
+    for future in stale_futures {
+      assert!(matches!(
+        async_manager::block_on(future),
+        Err(ButtplugClientError::ButtplugError(
+          ButtplugError::ButtplugUnknownError(ButtplugUnknownError::ReplyTimedOut(_))
+        ))
+      ));
     }
+    assert!(fresh_future.now_or_never().is_none());
+    assert_eq!(sorter.future_map.len(), 1);
   }
 }
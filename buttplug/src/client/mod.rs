@@ -46,9 +46,12 @@ use futures::{
   future::{self, BoxFuture, FutureExt},
   Stream,
 };
-use std::sync::{
-  atomic::{AtomicBool, Ordering},
-  Arc,
+use std::{
+  sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc,
+  },
+  time::Duration,
 };
 use thiserror::Error;
 use tokio::sync::{broadcast, mpsc, Mutex};
@@ -101,12 +104,17 @@ impl ButtplugClientMessageFuturePair {
 
 /// Represents all of the different types of errors a ButtplugClient can return.
 ///
-/// Clients can return two types of errors:
+/// Clients can return three types of errors:
 ///
 /// - [ButtplugConnectorError], which means there was a problem with the connection between the
 /// client and the server, like a network connection issue.
 /// - [ButtplugError], which is an error specific to the Buttplug Protocol.
+/// - MessageSorterFullError, which means too many messages are waiting on replies from the
+/// server. See [ButtplugClient::with_max_pending_messages].
+///
+/// More kinds of errors may be added in the future, so matches need a wildcard arm.
 #[derive(Debug, Error)]
+#[non_exhaustive]
 pub enum ButtplugClientError {
   /// Connector error
   #[error(transparent)]
@@ -114,6 +122,23 @@ pub enum ButtplugClientError {
   /// Protocol error
   #[error(transparent)]
   ButtplugError(#[from] ButtplugError),
+  /// Message sorter is at capacity
+  #[error("Message sorter is full ({0} messages awaiting replies), cannot send more messages.")]
+  MessageSorterFullError(usize),
+}
+
+impl ButtplugClientError {
+  /// Returns a suggestion for how to fix the error, for errors where there's a likely fix. See
+  /// [ButtplugError::hint] and [ButtplugConnectorError::hint].
+  pub fn hint(&self) -> Option<&'static str> {
+    match self {
+      Self::ButtplugConnectorError(err) => err.hint(),
+      Self::ButtplugError(err) => err.hint(),
+      Self::MessageSorterFullError(_) => {
+        Some("Wait for replies to pending messages before sending more")
+      }
+    }
+  }
 }
 
 /// Enum representing different events that can be emitted by a client.
@@ -174,6 +199,10 @@ pub struct ButtplugClient {
   message_sender: broadcast::Sender<ButtplugClientRequest>,
   connected: Arc<AtomicBool>,
   device_map: Arc<DashMap<u32, Arc<ButtplugClientDevice>>>,
+  /// Most messages that can wait on replies from the server at once, if limited.
+  max_pending_messages: Option<usize>,
+  /// How long messages wait on replies from the server before failing, if limited.
+  reply_timeout: Option<Duration>,
 }
 
 impl ButtplugClient {
@@ -187,9 +216,29 @@ impl ButtplugClient {
       message_sender,
       connected: Arc::new(AtomicBool::new(false)),
       device_map: Arc::new(DashMap::new()),
+      max_pending_messages: None,
+      reply_timeout: None,
     }
   }
 
+  /// Limit the number of messages waiting on replies from the server to `max_pending_messages`,
+  /// so a server that never replies can't grow memory use forever. Sends past the limit fail with
+  /// [ButtplugClientError::MessageSorterFullError] until replies arrive. Unlimited by default, and
+  /// only applies to connections made after it's set.
+  pub fn with_max_pending_messages(mut self, max_pending_messages: usize) -> Self {
+    self.max_pending_messages = Some(max_pending_messages);
+    self
+  }
+
+  /// Fail messages that have waited on a reply from the server for longer than `reply_timeout`
+  /// with [ReplyTimedOut](crate::core::errors::ButtplugUnknownError::ReplyTimedOut), so a server
+  /// that never replies can't leave callers waiting forever. Messages wait as long as it takes by
+  /// default, and this only applies to connections made after it's set.
+  pub fn with_reply_timeout(mut self, reply_timeout: Duration) -> Self {
+    self.reply_timeout = Some(reply_timeout);
+    self
+  }
+
   pub async fn connect<ConnectorType>(
     &self,
     mut connector: ConnectorType,
@@ -221,6 +270,8 @@ impl ButtplugClient {
       self.event_stream.clone(),
       self.message_sender.clone(),
       self.device_map.clone(),
+      self.max_pending_messages,
+      self.reply_timeout,
     );
 
     // Start the event loop before we run the handshake.
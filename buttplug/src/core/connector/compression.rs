@@ -0,0 +1,374 @@
+// Buttplug Rust Source Code File - See https://buttplug.io for more info.
+//
+// Copyright 2016-2023 Nonpolynomial Labs LLC. All rights reserved.
+//
+// Licensed under the BSD 3-Clause license. See LICENSE file in the project root
+// for full license information.
+
+//! Compressing messages between remote connectors.
+//!
+//! Clients offer the algorithms they can use in
+//! [RequestServerInfo](crate::core::message::RequestServerInfo), and the server picks one and
+//! returns it in [ServerInfo](crate::core::message::ServerInfo). Every message after ServerInfo,
+//! in both directions, is then compressed. This all happens in
+//! [ButtplugRemoteConnector](super::ButtplugRemoteConnector), so clients and servers don't know
+//! it's going on.
+
+use super::ButtplugConnectorError;
+use crate::core::message::{
+  serializer::ButtplugSerializedMessage,
+  ButtplugClientMessage,
+  ButtplugServerMessage,
+  ButtplugSpecV0ClientMessage,
+  ButtplugSpecV0ServerMessage,
+  ButtplugSpecV1ClientMessage,
+  ButtplugSpecV1ServerMessage,
+  ButtplugSpecV2ClientMessage,
+  ButtplugSpecV2ServerMessage,
+  ButtplugSpecV3ClientMessage,
+  ButtplugSpecV3ServerMessage,
+  CompressionAlgorithm,
+};
+#[cfg(feature = "zstd")]
+use std::io::Read;
+use std::sync::Arc;
+
+/// Largest message the built-in compressors will decompress, so a small message can't expand into
+/// enough data to run the process out of memory.
+#[cfg(any(feature = "zstd", feature = "lz4"))]
+pub const MAX_DECOMPRESSED_MESSAGE_SIZE: usize = 16 * 1024 * 1024;
+
+/// Implementation of a [CompressionAlgorithm], set on a connector with
+/// [ButtplugRemoteConnector::with_compression](super::ButtplugRemoteConnector::with_compression).
+///
+/// The `zstd` and `lz4` features add [ZstdCompressor] and [Lz4Compressor]. Other libraries can be
+/// used by implementing this for a wrapper around them.
+#[allow(clippy::result_large_err)]
+pub trait ButtplugCompressor: Send + Sync {
+  /// Algorithm this compressor implements. For algorithms with settings, these are the settings
+  /// the compressor uses when it's picked by the server.
+  fn algorithm(&self) -> CompressionAlgorithm;
+  fn compress(&self, data: &[u8]) -> Result<Vec<u8>, ButtplugConnectorError>;
+  fn decompress(&self, data: &[u8]) -> Result<Vec<u8>, ButtplugConnectorError>;
+}
+
+/// Zstd compression at the given level, from the `zstd` crate.
+#[cfg(feature = "zstd")]
+#[derive(Debug, Clone, Copy)]
+pub struct ZstdCompressor {
+  level: i32,
+}
+
+#[cfg(feature = "zstd")]
+impl ZstdCompressor {
+  /// Compress at `level`, from 1 (fastest) to 22 (smallest). 0 uses zstd's default level.
+  pub fn new(level: i32) -> Self {
+    Self { level }
+  }
+}
+
+#[cfg(feature = "zstd")]
+impl ButtplugCompressor for ZstdCompressor {
+  fn algorithm(&self) -> CompressionAlgorithm {
+    CompressionAlgorithm::Zstd { level: self.level }
+  }
+
+  fn compress(&self, data: &[u8]) -> Result<Vec<u8>, ButtplugConnectorError> {
+    zstd::bulk::compress(data, self.level).map_err(|err| {
+      ButtplugConnectorError::ConnectorGenericError(format!("Zstd compression failed: {}", err))
+    })
+  }
+
+  fn decompress(&self, data: &[u8]) -> Result<Vec<u8>, ButtplugConnectorError> {
+    let decompress_error = |err: std::io::Error| {
+      ButtplugConnectorError::ConnectorGenericError(format!("Zstd decompression failed: {}", err))
+    };
+    let decoder = zstd::stream::read::Decoder::new(data).map_err(decompress_error)?;
+    let mut decompressed = vec![];
+    // Read one byte past the limit, to tell messages at the limit from ones over it.
+    decoder
+      .take(MAX_DECOMPRESSED_MESSAGE_SIZE as u64 + 1)
+      .read_to_end(&mut decompressed)
+      .map_err(decompress_error)?;
+    if decompressed.len() > MAX_DECOMPRESSED_MESSAGE_SIZE {
+      return Err(ButtplugConnectorError::ConnectorGenericError(format!(
+        "Decompressed message is larger than {} bytes",
+        MAX_DECOMPRESSED_MESSAGE_SIZE
+      )));
+    }
+    Ok(decompressed)
+  }
+}
+
+/// Lz4 compression, from the `lz4_flex` crate. Messages are prefixed with their uncompressed size.
+#[cfg(feature = "lz4")]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Lz4Compressor {}
+
+#[cfg(feature = "lz4")]
+impl ButtplugCompressor for Lz4Compressor {
+  fn algorithm(&self) -> CompressionAlgorithm {
+    CompressionAlgorithm::Lz4
+  }
+
+  fn compress(&self, data: &[u8]) -> Result<Vec<u8>, ButtplugConnectorError> {
+    Ok(lz4_flex::compress_prepend_size(data))
+  }
+
+  fn decompress(&self, data: &[u8]) -> Result<Vec<u8>, ButtplugConnectorError> {
+    // Check the size prefix before lz4_flex allocates a buffer that big.
+    let size = data
+      .get(..4)
+      .map(|prefix| u32::from_le_bytes(prefix.try_into().expect("Slice is 4 bytes")) as usize);
+    if size.is_some_and(|size| size > MAX_DECOMPRESSED_MESSAGE_SIZE) {
+      return Err(ButtplugConnectorError::ConnectorGenericError(format!(
+        "Decompressed message is larger than {} bytes",
+        MAX_DECOMPRESSED_MESSAGE_SIZE
+      )));
+    }
+    lz4_flex::decompress_size_prepended(data).map_err(|err| {
+      ButtplugConnectorError::ConnectorGenericError(format!("Lz4 decompression failed: {}", err))
+    })
+  }
+}
+
+/// Access to the compression fields of the handshake messages in a message union, which
+/// [ButtplugRemoteConnector](super::ButtplugRemoteConnector) uses to negotiate compression.
+/// Message versions from before compression was added use the default methods, so never
+/// negotiate it.
+pub trait CompressionHandshake {
+  /// Algorithms offered by the client, if this is a RequestServerInfo.
+  fn compression_supported(&self) -> Option<&[CompressionAlgorithm]> {
+    None
+  }
+  /// Offers `algorithms` to the server, if this is a RequestServerInfo.
+  fn set_compression_supported(&mut self, _algorithms: Vec<CompressionAlgorithm>) {
+  }
+  /// Algorithm chosen by the server, if this is a ServerInfo that chose one.
+  fn compression_enabled(&self) -> Option<CompressionAlgorithm> {
+    None
+  }
+  /// Tells the client about the chosen algorithm, returning false if this isn't a ServerInfo.
+  fn set_compression_enabled(&mut self, _algorithm: CompressionAlgorithm) -> bool {
+    false
+  }
+}
+
+macro_rules! impl_client_compression_handshake {
+  ($message_type:ty) => {
+    impl CompressionHandshake for $message_type {
+      fn compression_supported(&self) -> Option<&[CompressionAlgorithm]> {
+        if let Self::RequestServerInfo(msg) = self {
+          Some(msg.compression_supported())
+        } else {
+          None
+        }
+      }
+
+      fn set_compression_supported(&mut self, algorithms: Vec<CompressionAlgorithm>) {
+        if let Self::RequestServerInfo(msg) = self {
+          msg.set_compression_supported(algorithms);
+        }
+      }
+    }
+  };
+}
+
+macro_rules! impl_server_compression_handshake {
+  ($message_type:ty) => {
+    impl CompressionHandshake for $message_type {
+      fn compression_enabled(&self) -> Option<CompressionAlgorithm> {
+        if let Self::ServerInfo(msg) = self {
+          msg.compression_enabled()
+        } else {
+          None
+        }
+      }
+
+      fn set_compression_enabled(&mut self, algorithm: CompressionAlgorithm) -> bool {
+        if let Self::ServerInfo(msg) = self {
+          msg.set_compression_enabled(algorithm);
+          true
+        } else {
+          false
+        }
+      }
+    }
+  };
+}
+
+impl_client_compression_handshake!(ButtplugClientMessage);
+impl_client_compression_handshake!(ButtplugSpecV3ClientMessage);
+impl_server_compression_handshake!(ButtplugServerMessage);
+impl_server_compression_handshake!(ButtplugSpecV3ServerMessage);
+impl CompressionHandshake for ButtplugSpecV2ClientMessage {
+}
+impl CompressionHandshake for ButtplugSpecV2ServerMessage {
+}
+impl CompressionHandshake for ButtplugSpecV1ClientMessage {
+}
+impl CompressionHandshake for ButtplugSpecV1ServerMessage {
+}
+impl CompressionHandshake for ButtplugSpecV0ClientMessage {
+}
+impl CompressionHandshake for ButtplugSpecV0ServerMessage {
+}
+
+/// Compression state of a single connection, owned by the remote connector event loop.
+pub(super) struct ConnectionCompression {
+  compressors: Vec<Arc<dyn ButtplugCompressor>>,
+  /// Algorithms of `compressors`, in the same order.
+  supported: Vec<CompressionAlgorithm>,
+  /// Kept by the server side between receiving RequestServerInfo and sending ServerInfo.
+  client_supported: Vec<CompressionAlgorithm>,
+  active: Option<Arc<dyn ButtplugCompressor>>,
+}
+
+#[allow(clippy::result_large_err)]
+impl ConnectionCompression {
+  pub(super) fn new(compressors: Vec<Arc<dyn ButtplugCompressor>>) -> Self {
+    Self {
+      supported: compressors
+        .iter()
+        .map(|compressor| compressor.algorithm())
+        .collect(),
+      compressors,
+      client_supported: vec![],
+      active: None,
+    }
+  }
+
+  fn compressor_for(
+    &self,
+    algorithm: &CompressionAlgorithm,
+  ) -> Option<Arc<dyn ButtplugCompressor>> {
+    self
+      .compressors
+      .iter()
+      .find(|compressor| compressor.algorithm().is_same_kind(algorithm))
+      .cloned()
+  }
+
+  /// Serializes an outgoing message with `serialize`, compressing it if compression is already on.
+  /// Handshake messages are updated to negotiate compression first, and if this is the server's
+  /// ServerInfo, compression is turned on for everything after it.
+  pub(super) fn encode<T: CompressionHandshake>(
+    &mut self,
+    msg: &mut T,
+    serialize: impl FnOnce(&T) -> ButtplugSerializedMessage,
+  ) -> Result<ButtplugSerializedMessage, ButtplugConnectorError> {
+    if self.compressors.is_empty() {
+      return Ok(serialize(msg));
+    }
+    msg.set_compression_supported(self.supported.clone());
+    let mut newly_active = None;
+    if !self.client_supported.is_empty() {
+      let algorithm = CompressionAlgorithm::negotiate(&self.client_supported, &self.supported);
+      if algorithm != CompressionAlgorithm::None && msg.set_compression_enabled(algorithm) {
+        info!("Negotiated {:?} compression with client", algorithm);
+        newly_active = self.compressor_for(&algorithm);
+        self.client_supported.clear();
+      }
+    }
+    let serialized_msg = self.compress(serialize(msg))?;
+    if newly_active.is_some() {
+      self.active = newly_active;
+    }
+    Ok(serialized_msg)
+  }
+
+  /// Undoes [ConnectionCompression::encode] on an incoming message, before it's deserialized.
+  pub(super) fn decompress(
+    &self,
+    msg: ButtplugSerializedMessage,
+  ) -> Result<ButtplugSerializedMessage, ButtplugConnectorError> {
+    let Some(compressor) = &self.active else {
+      return Ok(msg);
+    };
+    let data = match msg {
+      ButtplugSerializedMessage::Text(text) => compressor.decompress(text.as_bytes())?,
+      ButtplugSerializedMessage::Binary(bin) => compressor.decompress(&bin)?,
+    };
+    // Text serializers (JSON) are the common case, anything that isn't UTF-8 is passed on as
+    // binary.
+    Ok(match String::from_utf8(data) {
+      Ok(text) => ButtplugSerializedMessage::Text(text),
+      Err(err) => ButtplugSerializedMessage::Binary(err.into_bytes()),
+    })
+  }
+
+  /// Picks up compression negotiation from a deserialized incoming message. Fails if the server
+  /// enabled an algorithm we didn't offer, as nothing it sends after this can be read.
+  pub(super) fn on_incoming<T: CompressionHandshake>(
+    &mut self,
+    msg: &T,
+  ) -> Result<(), ButtplugConnectorError> {
+    if self.compressors.is_empty() {
+      return Ok(());
+    }
+    if let Some(algorithms) = msg.compression_supported() {
+      self.client_supported = algorithms.to_vec();
+    }
+    match msg.compression_enabled() {
+      Some(algorithm) if algorithm != CompressionAlgorithm::None => {
+        self.active = self.compressor_for(&algorithm);
+        if self.active.is_none() {
+          return Err(ButtplugConnectorError::ConnectorGenericError(format!(
+            "Server enabled {:?} compression, which we didn't offer",
+            algorithm
+          )));
+        }
+        info!("Server enabled {:?} compression", algorithm);
+        Ok(())
+      }
+      _ => Ok(()),
+    }
+  }
+
+  fn compress(
+    &self,
+    msg: ButtplugSerializedMessage,
+  ) -> Result<ButtplugSerializedMessage, ButtplugConnectorError> {
+    let Some(compressor) = &self.active else {
+      return Ok(msg);
+    };
+    let data = match msg {
+      ButtplugSerializedMessage::Text(text) => compressor.compress(text.as_bytes())?,
+      ButtplugSerializedMessage::Binary(bin) => compressor.compress(&bin)?,
+    };
+    Ok(ButtplugSerializedMessage::Binary(data))
+  }
+}
+
+#[cfg(all(test, any(feature = "zstd", feature = "lz4")))]
+mod test {
+  use super::*;
+
+  fn check_round_trip(compressor: &dyn ButtplugCompressor) {
+    let message = r#"[{"Ok":{"Id":1}}]"#.repeat(100);
+    let compressed = compressor.compress(message.as_bytes()).unwrap();
+    assert!(compressed.len() < message.len());
+    assert_eq!(
+      compressor.decompress(&compressed).unwrap(),
+      message.as_bytes()
+    );
+    assert!(compressor.decompress(b"not compressed").is_err());
+    // Messages that would decompress past the limit are rejected.
+    let oversized = compressor
+      .compress(&vec![0; MAX_DECOMPRESSED_MESSAGE_SIZE + 1])
+      .unwrap();
+    assert!(compressor.decompress(&oversized).is_err());
+  }
+
+  #[cfg(feature = "zstd")]
+  #[test]
+  fn test_zstd_compressor() {
+    check_round_trip(&ZstdCompressor::new(3));
+  }
+
+  #[cfg(feature = "lz4")]
+  #[test]
+  fn test_lz4_compressor() {
+    check_round_trip(&Lz4Compressor::default());
+  }
+}
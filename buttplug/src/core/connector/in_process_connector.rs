@@ -10,7 +10,11 @@
 use crate::{
   core::{
     connector::{ButtplugConnector, ButtplugConnectorError, ButtplugConnectorResultFuture},
-    message::{ButtplugCurrentSpecClientMessage, ButtplugCurrentSpecServerMessage},
+    message::{
+      ButtplugCurrentSpecClientMessage,
+      ButtplugCurrentSpecServerMessage,
+      ButtplugServerMessage,
+    },
   },
   server::{ButtplugServer, ButtplugServerBuilder},
   util::async_manager,
@@ -131,6 +135,12 @@ impl ButtplugConnector<ButtplugCurrentSpecClientMessage, ButtplugCurrentSpecServ
           info!("Starting In Process Client Connector Event Sender Loop");
           pin_mut!(server_recv);
           while let Some(event) = server_recv.next().await {
+            let event = if let ButtplugServerMessage::LaggedEvents(lagged) = event {
+              warn!("Server event stream fell behind, client missed {} events.", lagged.count());
+              ButtplugServerMessage::Error(lagged.into())
+            } else {
+              event
+            };
             // If we get an error back, it means the client dropped our event
             // handler, so just stop trying. Otherwise, since this is an
             // in-process conversion, we can unwrap because we know our
@@ -63,14 +63,35 @@
 //! There are slightly more useful situations like device forwarders where this work comes in also,
 //! but that Windows 7/Android example is where the idea originally came from.
 
+mod compression;
 mod in_process_connector;
 pub mod remote_connector;
+mod send_queue;
+mod telemetry;
 pub mod transport;
+#[cfg(all(
+  feature = "unix",
+  feature = "tokio-runtime",
+  feature = "serialize-json",
+  unix
+))]
+mod unix_stream;
 
 use crate::{
-  core::message::{serializer::ButtplugSerializedMessage, ButtplugMessage},
+  core::message::{
+    serializer::{ButtplugSerializedMessage, CodecType},
+    ButtplugMessage,
+    MessageTransformer,
+  },
   util::future::{ButtplugFuture, ButtplugFutureStateShared},
 };
+#[cfg(feature = "lz4")]
+pub use compression::Lz4Compressor;
+#[cfg(feature = "zstd")]
+pub use compression::ZstdCompressor;
+#[cfg(any(feature = "zstd", feature = "lz4"))]
+pub use compression::MAX_DECOMPRESSED_MESSAGE_SIZE;
+pub use compression::{ButtplugCompressor, CompressionHandshake};
 use displaydoc::Display;
 use futures::future::{self, BoxFuture, FutureExt};
 #[cfg(all(feature = "server", feature = "client"))]
@@ -83,10 +104,25 @@ pub use remote_connector::{
   ButtplugRemoteConnector,
   ButtplugRemoteServerConnector,
 };
+pub use send_queue::{ConnectionMetrics, MessageSorterStats, SendQueueOverflowPolicy};
+use std::{net::SocketAddr, sync::Arc, time::Duration};
+pub use telemetry::ConnectorTelemetry;
+#[cfg(feature = "metrics")]
+pub use telemetry::HistogramTelemetry;
 use thiserror::Error;
 use tokio::sync::mpsc::Sender;
+#[cfg(feature = "axum")]
+pub use transport::ButtplugAxumWebsocketTransport;
 #[cfg(feature = "websockets")]
 pub use transport::ButtplugWebsocketClientTransport;
+pub use transport::TlsConfig;
+#[cfg(all(
+  feature = "unix",
+  feature = "tokio-runtime",
+  feature = "serialize-json",
+  unix
+))]
+pub use unix_stream::ButtplugUnixStreamServerConnector;
 
 #[cfg(feature = "websockets")]
 pub use transport::{ButtplugWebsocketServerTransport, ButtplugWebsocketServerTransportBuilder};
@@ -109,12 +145,45 @@ pub enum ButtplugConnectorError {
   ConnectorChannelClosed,
   /// Connector already connected, cannot be connected twice.
   ConnectorAlreadyConnected,
+  /// Connector send queue is full, message not sent.
+  ConnectorSendQueueFull,
+  /// Connector could not send the message within {0:?}.
+  ConnectorSendTimedOut(Duration),
   /// Connector error: {0}
   ConnectorGenericError(String),
   /// Specific error for connector type: {0}.
   TransportSpecificError(transport::ButtplugConnectorTransportSpecificError),
 }
 
+impl ButtplugConnectorError {
+  /// Returns the underlying OS error code, if the error was caused by an OS level failure (socket
+  /// bind failures, connection resets, etc...).
+  pub fn os_error_code(&self) -> Option<i32> {
+    match self {
+      Self::TransportSpecificError(err) => err.os_error_code(),
+      _ => None,
+    }
+  }
+
+  /// Returns a suggestion for how to fix the error, for errors where there's a likely fix.
+  pub fn hint(&self) -> Option<&'static str> {
+    match self {
+      Self::ConnectorNotConnected | Self::ConnectorChannelClosed => {
+        Some("Check that the server is running and the address is correct, then reconnect")
+      }
+      Self::ConnectorAlreadyConnected => Some("Disconnect before connecting again"),
+      Self::ConnectorSendQueueFull => {
+        Some("Send messages less often, or raise the connector's send queue capacity")
+      }
+      Self::ConnectorSendTimedOut(_) => Some("Raise the connector's timeout"),
+      Self::TransportSpecificError(_) => {
+        Some("Check that the server is running and the address is correct")
+      }
+      Self::ConnectorGenericError(_) => None,
+    }
+  }
+}
+
 impl<T> From<ButtplugConnectorError> for BoxFuture<'static, Result<T, ButtplugConnectorError>>
 where
   T: Send + 'static,
@@ -180,6 +249,53 @@ where
   /// If the connector is not currently connected, or an error happens during
   /// the send operation, this will return a [ButtplugConnectorError]
   fn send(&self, msg: OutboundMessageType) -> ButtplugConnectorResultFuture;
+  /// Requests human-readable output from the connector's serializer, if it has one. Must be set
+  /// before [ButtplugConnector::connect] is called. Connectors that don't serialize messages (like
+  /// in-process connectors) ignore this.
+  fn set_pretty_print_messages(&mut self, _pretty_print: bool) {
+  }
+  /// Passes transformers to the connector's serializer, to rewrite incoming messages before they
+  /// are deserialized. Must be set before [ButtplugConnector::connect] is called. Connectors that
+  /// don't serialize messages ignore this.
+  fn set_message_transformers(&mut self, _transformers: Vec<Arc<dyn MessageTransformer>>) {
+  }
+  /// Wire format used by this connector.
+  fn codec_type(&self) -> CodecType {
+    CodecType::Unserialized
+  }
+  /// Address of the other end of the connection, for diagnostics. None if not connected, or if
+  /// the connector doesn't run over the network (in-process, IPC, etc).
+  fn peer_address(&self) -> Option<SocketAddr> {
+    None
+  }
+  /// Passes TLS settings to the connector's transport. Must be set before
+  /// [ButtplugConnector::connect] is called. Connectors that don't accept network connections
+  /// ignore this.
+  fn set_tls_config(&mut self, _tls_config: TlsConfig) {
+  }
+  /// Largest message the connector can carry in one piece, in bytes, for transports with frame or
+  /// MTU limits. None (the default) means there is no limit, as for TCP based transports.
+  fn max_message_size(&self) -> Option<usize> {
+    None
+  }
+  /// Check that a connected connector can still pass messages, without sending anything to the
+  /// other side. Connectors that have no way of checking report themselves healthy.
+  fn check_health(&self) -> ButtplugConnectorResultFuture {
+    future::ready(Ok(())).boxed()
+  }
+  /// Sets how long [ButtplugConnector::send] may wait for the connector to take a message before
+  /// failing with [ButtplugConnectorError::ConnectorSendTimedOut]. Can be changed at any time,
+  /// including while connected, and applies to sends started afterwards. Connectors that never
+  /// wait on sends ignore this.
+  #[allow(clippy::result_large_err)]
+  fn set_timeout(&self, _timeout: Duration) -> Result<(), ButtplugConnectorError> {
+    Ok(())
+  }
+  /// Live metrics for this connection, for connectors that keep them. Clients record their
+  /// [MessageSorterStats] into these.
+  fn connection_metrics(&self) -> Option<ConnectionMetrics> {
+    None
+  }
 }
 
 #[cfg(all(feature = "websockets", feature = "serialize-json"))]
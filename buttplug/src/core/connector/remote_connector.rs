@@ -8,10 +8,15 @@
 //! Generic remote transport handling methods and traits
 
 use super::{
-  transport::{ButtplugConnectorTransport, ButtplugTransportIncomingMessage},
+  compression::{ButtplugCompressor, CompressionHandshake, ConnectionCompression},
+  send_queue::SendQueue,
+  transport::{ButtplugConnectorTransport, ButtplugTransportIncomingMessage, TlsConfig},
   ButtplugConnector,
   ButtplugConnectorError,
   ButtplugConnectorResultFuture,
+  ConnectionMetrics,
+  ConnectorTelemetry,
+  SendQueueOverflowPolicy,
 };
 use crate::{
   core::message::{
@@ -19,24 +24,44 @@ use crate::{
       ButtplugClientJSONSerializer,
       ButtplugMessageSerializer,
       ButtplugSerializedMessage,
+      CodecType,
     },
     ButtplugClientMessage,
     ButtplugCurrentSpecClientMessage,
     ButtplugCurrentSpecServerMessage,
     ButtplugMessage,
     ButtplugServerMessage,
+    MessageTransformer,
   },
   util::async_manager,
 };
-use futures::{future::BoxFuture, FutureExt};
-use std::marker::PhantomData;
-use tokio::sync::mpsc::{channel, Receiver, Sender};
+use futures::{
+  future::{self, BoxFuture},
+  FutureExt,
+};
+use std::{
+  marker::PhantomData,
+  net::SocketAddr,
+  sync::{Arc, Mutex},
+  time::{Duration, Instant},
+};
+use tokio::sync::{
+  mpsc::{channel, Receiver, Sender},
+  oneshot,
+};
+
+/// Default capacity of the outgoing message queue, matching the size of the channels used
+/// elsewhere in the connector.
+pub const DEFAULT_SEND_QUEUE_CAPACITY: usize = 256;
 
 enum ButtplugRemoteConnectorMessage<T>
 where
   T: ButtplugMessage + 'static,
 {
-  Message(T),
+  /// Outgoing message, along with when it was sent to the connector, for telemetry.
+  Message(T, Instant),
+  /// Asks the event loop to reply once it's reached, with an error if the transport has gone.
+  HealthCheck(oneshot::Sender<Result<(), ButtplugConnectorError>>),
   Close,
 }
 
@@ -49,6 +74,7 @@ where
   Outgoing(ButtplugRemoteConnectorMessage<T>),
 }
 
+#[allow(clippy::too_many_arguments)]
 async fn remote_connector_event_loop<
   TransportType,
   SerializerType,
@@ -56,7 +82,7 @@ async fn remote_connector_event_loop<
   InboundMessageType,
 >(
   // Takes messages from the client
-  mut connector_outgoing_recv: Receiver<ButtplugRemoteConnectorMessage<OutboundMessageType>>,
+  send_queue: Arc<SendQueue<ButtplugRemoteConnectorMessage<OutboundMessageType>>>,
   // Sends messages not matched in the sorter to the client.
   connector_incoming_sender: Sender<InboundMessageType>,
   transport: TransportType,
@@ -64,29 +90,32 @@ async fn remote_connector_event_loop<
   transport_outgoing_sender: Sender<ButtplugSerializedMessage>,
   // Takes data coming in from the transport.
   mut transport_incoming_recv: Receiver<ButtplugTransportIncomingMessage>,
+  // Serializer, already configured by the connector.
+  serializer: SerializerType,
+  telemetry: Option<Arc<dyn ConnectorTelemetry>>,
+  mut compression: ConnectionCompression,
 ) where
   TransportType: ButtplugConnectorTransport + 'static,
   SerializerType: ButtplugMessageSerializer<Inbound = InboundMessageType, Outbound = OutboundMessageType>
     + 'static,
-  OutboundMessageType: ButtplugMessage + 'static,
-  InboundMessageType: ButtplugMessage + 'static,
+  OutboundMessageType: ButtplugMessage + CompressionHandshake + 'static,
+  InboundMessageType: ButtplugMessage + CompressionHandshake + 'static,
 {
   // Message sorter that receives messages that come in from the client.
-  let serializer = SerializerType::default();
   loop {
     // We use two Options instead of an enum because we may never get anything.
     //
     // For the type, we will get back one of two things: Either a serialized
     // incoming message from the transport for the connector, or an outgoing
     // message from the connector to go to the transport.
-    let mut stream_return = select! {
+    let stream_return = select! {
       // Catch messages coming in from the transport.
       transport = transport_incoming_recv.recv().fuse() =>
       match transport {
         Some(msg) => StreamValue::Incoming(msg),
         None => StreamValue::NoValue,
       },
-      connector = connector_outgoing_recv.recv().fuse() =>
+      connector = send_queue.pop().fuse() =>
       match connector {
         // Catch messages that need to be sent out through the connector.
         Some(msg) => StreamValue::Outgoing(msg),
@@ -103,9 +132,34 @@ async fn remote_connector_event_loop<
       StreamValue::Incoming(remote_msg) => {
         match remote_msg {
           ButtplugTransportIncomingMessage::Message(serialized_msg) => {
+            let serialized_msg = match compression.decompress(serialized_msg) {
+              Ok(msg) => msg,
+              Err(e) => {
+                // Both sides have to agree on compression for anything after this to be
+                // readable, so there's no point carrying on.
+                error!(
+                  "Could not decompress remote message, closing connection: {}",
+                  e
+                );
+                if let Err(e) = transport.disconnect().await {
+                  error!("Error disconnecting transport: {:?}", e);
+                }
+                break;
+              }
+            };
             match serializer.deserialize(&serialized_msg) {
               Ok(array) => {
                 for smsg in array {
+                  if let Err(e) = compression.on_incoming(&smsg) {
+                    error!("{}, closing connection.", e);
+                    if let Err(e) = transport.disconnect().await {
+                      error!("Error disconnecting transport: {:?}", e);
+                    }
+                    return;
+                  }
+                  if let Some(telemetry) = &telemetry {
+                    telemetry.on_receive(smsg.id());
+                  }
                   // TODO Test validity here.
                   if connector_incoming_sender.send(smsg).await.is_err() {
                     error!("Connector has disconnected, ending remote connector loop.");
@@ -138,12 +192,24 @@ async fn remote_connector_event_loop<
       }
       // If we receive something from the client, register it with our sorter
       // then let the connector figure out what to do with it.
-      StreamValue::Outgoing(ref mut buttplug_msg) => {
+      StreamValue::Outgoing(buttplug_msg) => {
         match buttplug_msg {
-          ButtplugRemoteConnectorMessage::Message(msg) => {
+          ButtplugRemoteConnectorMessage::Message(mut msg, send_started_at) => {
             // Create future sets our message ID, so make sure this
             // happens before we send out the message.
-            let serialized_msg = serializer.serialize(&[msg.clone()]);
+            let serialized_msg = match compression.encode(&mut msg, |msg| {
+              serializer.serialize(std::slice::from_ref(msg))
+            }) {
+              Ok(serialized_msg) => serialized_msg,
+              Err(e) => {
+                error!(
+                  "Could not compress message {}, dropping it: {}",
+                  msg.id(),
+                  e
+                );
+                continue;
+              }
+            };
             if transport_outgoing_sender
               .send(serialized_msg)
               .await
@@ -152,6 +218,16 @@ async fn remote_connector_event_loop<
               error!("Transport has disconnected, exiting remote connector loop.");
               return;
             }
+            if let Some(telemetry) = &telemetry {
+              telemetry.on_send_complete(msg.id(), send_started_at.elapsed());
+            }
+          }
+          ButtplugRemoteConnectorMessage::HealthCheck(reply_sender) => {
+            let _ = reply_sender.send(if transport_outgoing_sender.is_closed() {
+              Err(ButtplugConnectorError::ConnectorChannelClosed)
+            } else {
+              Ok(())
+            });
           }
           ButtplugRemoteConnectorMessage::Close => {
             if let Err(e) = transport.disconnect().await {
@@ -191,8 +267,8 @@ pub struct ButtplugRemoteConnector<
   TransportType: ButtplugConnectorTransport + 'static,
   SerializerType: ButtplugMessageSerializer<Inbound = InboundMessageType, Outbound = OutboundMessageType>
     + 'static,
-  OutboundMessageType: ButtplugMessage + 'static,
-  InboundMessageType: ButtplugMessage + 'static,
+  OutboundMessageType: ButtplugMessage + CompressionHandshake + 'static,
+  InboundMessageType: ButtplugMessage + CompressionHandshake + 'static,
 {
   /// Transport that the connector will use to communicate with the other
   /// connector.
@@ -204,8 +280,24 @@ pub struct ButtplugRemoteConnector<
   /// the lifetime of the event loop, meaning if for any reason we exit, we make
   /// sure the transport is dropped.
   transport: Option<TransportType>,
-  /// Sender for forwarding outgoing messages to the connector event loop.
-  event_loop_sender: Option<Sender<ButtplugRemoteConnectorMessage<OutboundMessageType>>>,
+  /// Queue for forwarding outgoing messages to the connector event loop.
+  send_queue: Arc<SendQueue<ButtplugRemoteConnectorMessage<OutboundMessageType>>>,
+  /// Set once connect has been called and the event loop is running.
+  connected: bool,
+  /// Passed along to the serializer once the event loop is created.
+  pretty_print_messages: bool,
+  /// Passed along to the serializer once the event loop is created.
+  message_transformers: Vec<Arc<dyn MessageTransformer>>,
+  /// Latency hooks, if any have been set.
+  telemetry: Option<Arc<dyn ConnectorTelemetry>>,
+  /// Copied from the transport once connected, since the transport moves to the event loop.
+  peer_address: Arc<Mutex<Option<SocketAddr>>>,
+  /// Copied from the transport on creation, for the same reason.
+  max_message_size: Option<usize>,
+  /// Longest a send may wait on the send queue, see [ButtplugConnector::set_timeout].
+  send_timeout: Mutex<Option<Duration>>,
+  /// Algorithms offered to (or accepted from) the other side during the handshake.
+  compressors: Vec<Arc<dyn ButtplugCompressor>>,
   dummy_serializer: PhantomData<SerializerType>,
 }
 
@@ -215,16 +307,78 @@ where
   TransportType: ButtplugConnectorTransport + 'static,
   SerializerType: ButtplugMessageSerializer<Inbound = InboundMessageType, Outbound = OutboundMessageType>
     + 'static,
-  OutboundMessageType: ButtplugMessage + 'static,
-  InboundMessageType: ButtplugMessage + 'static,
+  OutboundMessageType: ButtplugMessage + CompressionHandshake + 'static,
+  InboundMessageType: ButtplugMessage + CompressionHandshake + 'static,
 {
   pub fn new(transport: TransportType) -> Self {
+    Self::with_send_queue(
+      transport,
+      DEFAULT_SEND_QUEUE_CAPACITY,
+      SendQueueOverflowPolicy::default(),
+    )
+  }
+
+  /// Creates a connector whose outgoing queue holds up to `capacity` messages, using
+  /// `overflow_policy` to decide what happens when sends outpace the transport.
+  pub fn with_send_queue(
+    transport: TransportType,
+    capacity: usize,
+    overflow_policy: SendQueueOverflowPolicy,
+  ) -> Self {
     Self {
+      max_message_size: transport.max_message_size(),
       transport: Some(transport),
-      event_loop_sender: None,
+      send_queue: Arc::new(SendQueue::new(capacity, overflow_policy)),
+      connected: false,
+      pretty_print_messages: false,
+      message_transformers: vec![],
+      telemetry: None,
+      peer_address: Arc::new(Mutex::new(None)),
+      send_timeout: Mutex::new(None),
+      compressors: vec![],
       dummy_serializer: PhantomData::default(),
     }
   }
+
+  /// Call the given hooks as messages are sent and received by this connector.
+  pub fn with_telemetry(mut self, telemetry: Arc<dyn ConnectorTelemetry>) -> Self {
+    self.telemetry = Some(telemetry);
+    self
+  }
+
+  /// Negotiate compression with the other side using these compressors. Clients offer them in
+  /// RequestServerInfo, servers pick the most preferred algorithm both sides have and return it in
+  /// ServerInfo, and everything after that is compressed. Nothing is compressed if there's no
+  /// algorithm in common, or the other side doesn't support compression.
+  pub fn with_compression(mut self, compressors: Vec<Arc<dyn ButtplugCompressor>>) -> Self {
+    self.compressors = compressors;
+    self
+  }
+
+  /// Returns a handle to the live metrics for this connection.
+  pub fn metrics(&self) -> ConnectionMetrics {
+    self.send_queue.metrics()
+  }
+}
+
+impl<TransportType, SerializerType, OutboundMessageType, InboundMessageType> Drop
+  for ButtplugRemoteConnector<
+    TransportType,
+    SerializerType,
+    OutboundMessageType,
+    InboundMessageType,
+  >
+where
+  TransportType: ButtplugConnectorTransport + 'static,
+  SerializerType: ButtplugMessageSerializer<Inbound = InboundMessageType, Outbound = OutboundMessageType>
+    + 'static,
+  OutboundMessageType: ButtplugMessage + CompressionHandshake + 'static,
+  InboundMessageType: ButtplugMessage + CompressionHandshake + 'static,
+{
+  fn drop(&mut self) {
+    // Closing the queue ends the event loop, same as dropping a channel sender would.
+    self.send_queue.close();
+  }
 }
 
 impl<TransportType, SerializerType, OutboundMessageType, InboundMessageType>
@@ -239,20 +393,38 @@ where
   TransportType: ButtplugConnectorTransport + 'static,
   SerializerType: ButtplugMessageSerializer<Inbound = InboundMessageType, Outbound = OutboundMessageType>
     + 'static,
-  OutboundMessageType: ButtplugMessage + 'static,
-  InboundMessageType: ButtplugMessage + 'static,
+  OutboundMessageType: ButtplugMessage + CompressionHandshake + 'static,
+  InboundMessageType: ButtplugMessage + CompressionHandshake + 'static,
 {
   fn connect(
     &mut self,
     connector_incoming_sender: Sender<InboundMessageType>,
   ) -> BoxFuture<'static, Result<(), ButtplugConnectorError>> {
+    if !self.compressors.is_empty()
+      && self
+        .transport
+        .as_ref()
+        .is_some_and(|transport| !transport.supports_binary_messages())
+    {
+      // Compressed messages are binary, and would be split apart by the transport's framing.
+      return ButtplugConnectorError::ConnectorGenericError(
+        "Compression needs a transport that supports binary messages".to_owned(),
+      )
+      .into();
+    }
     if self.transport.is_some() {
       let transport = self
         .transport
         .take()
         .expect("Already checked that this would be a valid take().");
-      let (connector_outgoing_sender, connector_outgoing_receiver) = channel(256);
-      self.event_loop_sender = Some(connector_outgoing_sender);
+      self.connected = true;
+      let send_queue = self.send_queue.clone();
+      let mut serializer = SerializerType::default();
+      serializer.set_pretty_print(self.pretty_print_messages);
+      serializer.set_message_transformers(self.message_transformers.clone());
+      let telemetry = self.telemetry.clone();
+      let compression = ConnectionCompression::new(self.compressors.clone());
+      let peer_address = self.peer_address.clone();
       async move {
         let (transport_outgoing_sender, transport_outgoing_receiver) = channel(256);
         let (transport_incoming_sender, transport_incoming_receiver) = channel(256);
@@ -263,6 +435,7 @@ where
           // If we connect successfully, we get back the channel from the transport
           // to send outgoing messages and receieve incoming events, all serialized.
           Ok(()) => {
+            *peer_address.lock().expect("Lock poisoned") = transport.peer_address();
             async_manager::spawn(async move {
               remote_connector_event_loop::<
                 TransportType,
@@ -270,17 +443,26 @@ where
                 OutboundMessageType,
                 InboundMessageType,
               >(
-                connector_outgoing_receiver,
+                send_queue.clone(),
                 connector_incoming_sender,
                 transport,
                 transport_outgoing_sender,
                 transport_incoming_receiver,
+                serializer,
+                telemetry,
+                compression,
               )
-              .await
+              .await;
+              // Nothing will drain the queue after this, so make sure further sends fail.
+              send_queue.close();
+              *peer_address.lock().expect("Lock poisoned") = None;
             });
             Ok(())
           }
-          Err(e) => Err(e),
+          Err(e) => {
+            send_queue.close();
+            Err(e)
+          }
         }
       }
       .boxed()
@@ -290,32 +472,92 @@ where
   }
 
   fn disconnect(&self) -> ButtplugConnectorResultFuture {
-    if let Some(ref sender) = self.event_loop_sender {
-      let sender_clone = sender.clone();
-      async move {
-        sender_clone
-          .send(ButtplugRemoteConnectorMessage::Close)
-          .await
-          .map_err(|_| ButtplugConnectorError::ConnectorNotConnected)
-      }
-      .boxed()
+    if self.connected
+      && self
+        .send_queue
+        .force_push(ButtplugRemoteConnectorMessage::Close)
+    {
+      future::ready(Ok(())).boxed()
     } else {
       ButtplugConnectorError::ConnectorNotConnected.into()
     }
   }
 
   fn send(&self, msg: OutboundMessageType) -> ButtplugConnectorResultFuture {
-    if let Some(ref sender) = self.event_loop_sender {
-      let sender_clone = sender.clone();
+    if self.connected {
+      let send_queue = self.send_queue.clone();
+      if let Some(telemetry) = &self.telemetry {
+        telemetry.on_send_start(msg.id());
+      }
+      let send_started_at = Instant::now();
+      let send_timeout = *self.send_timeout.lock().expect("Lock poisoned");
       async move {
-        sender_clone
-          .send(ButtplugRemoteConnectorMessage::Message(msg))
-          .await
-          .map_err(|_| ButtplugConnectorError::ConnectorNotConnected)
+        let push = send_queue.push(ButtplugRemoteConnectorMessage::Message(
+          msg,
+          send_started_at,
+        ));
+        match send_timeout {
+          Some(send_timeout) => tokio::time::timeout(send_timeout, push)
+            .await
+            .map_err(|_| ButtplugConnectorError::ConnectorSendTimedOut(send_timeout))?,
+          None => push.await,
+        }
       }
       .boxed()
     } else {
       ButtplugConnectorError::ConnectorNotConnected.into()
     }
   }
+
+  fn set_pretty_print_messages(&mut self, pretty_print: bool) {
+    self.pretty_print_messages = pretty_print;
+  }
+
+  fn set_message_transformers(&mut self, transformers: Vec<Arc<dyn MessageTransformer>>) {
+    self.message_transformers = transformers;
+  }
+
+  fn codec_type(&self) -> CodecType {
+    SerializerType::codec_type()
+  }
+
+  fn peer_address(&self) -> Option<SocketAddr> {
+    *self.peer_address.lock().expect("Lock poisoned")
+  }
+
+  fn max_message_size(&self) -> Option<usize> {
+    self.max_message_size
+  }
+
+  fn check_health(&self) -> ButtplugConnectorResultFuture {
+    if !self.connected {
+      return ButtplugConnectorError::ConnectorNotConnected.into();
+    }
+    let send_queue = self.send_queue.clone();
+    async move {
+      let (reply_sender, reply_receiver) = oneshot::channel();
+      send_queue
+        .push(ButtplugRemoteConnectorMessage::HealthCheck(reply_sender))
+        .await?;
+      reply_receiver
+        .await
+        .map_err(|_| ButtplugConnectorError::ConnectorChannelClosed)?
+    }
+    .boxed()
+  }
+
+  fn set_tls_config(&mut self, tls_config: TlsConfig) {
+    if let Some(transport) = self.transport.as_mut() {
+      transport.set_tls_config(tls_config);
+    }
+  }
+
+  fn set_timeout(&self, timeout: Duration) -> Result<(), ButtplugConnectorError> {
+    *self.send_timeout.lock().expect("Lock poisoned") = Some(timeout);
+    Ok(())
+  }
+
+  fn connection_metrics(&self) -> Option<ConnectionMetrics> {
+    Some(self.metrics())
+  }
 }
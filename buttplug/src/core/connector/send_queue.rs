@@ -0,0 +1,317 @@
+// Buttplug Rust Source Code File - See https://buttplug.io for more info.
+//
+// Copyright 2016-2023 Nonpolynomial Labs LLC. All rights reserved.
+//
+// Licensed under the BSD 3-Clause license. See LICENSE file in the project root
+// for full license information.
+
+//! Outgoing message queue for remote connectors, decoupling message production from the rate at
+//! which the transport can send.
+
+use super::ButtplugConnectorError;
+use getset::CopyGetters;
+use std::{
+  collections::VecDeque,
+  sync::{
+    atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering},
+    Arc,
+    Mutex,
+  },
+};
+use tokio::sync::Notify;
+
+/// What to do when a message is sent while the connector send queue is at capacity.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SendQueueOverflowPolicy {
+  /// Remove the oldest queued message to make room for the new one.
+  DropOldest,
+  /// Discard the new message, leaving the queue as is.
+  DropNewest,
+  /// Wait until there is room in the queue.
+  #[default]
+  Block,
+  /// Fail the send with [ButtplugConnectorError::ConnectorSendQueueFull].
+  ReturnError,
+}
+
+/// Counts kept by the client message sorter, which pairs server replies with the messages they
+/// answer. See [ConnectionMetrics::message_sorter_stats].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, CopyGetters)]
+#[getset(get_copy = "pub")]
+pub struct MessageSorterStats {
+  /// Messages registered to wait on a reply.
+  registered: u64,
+  /// Messages whose reply arrived.
+  resolved: u64,
+  /// Messages failed for not getting a reply in time.
+  expired: u64,
+  /// Messages failed because their id was reused before their reply arrived.
+  collisions: u64,
+  /// Messages currently waiting on a reply.
+  current_pending: usize,
+}
+
+/// Counters behind [MessageSorterStats].
+#[derive(Debug, Default)]
+pub(crate) struct MessageSorterCounters {
+  pub(crate) registered: AtomicU64,
+  pub(crate) resolved: AtomicU64,
+  pub(crate) expired: AtomicU64,
+  pub(crate) collisions: AtomicU64,
+  pub(crate) current_pending: AtomicUsize,
+}
+
+/// Live statistics about a connection. Clones share the same underlying counters, so a handle can
+/// be held while the connector itself is owned by a client or server.
+#[derive(Debug, Clone, Default)]
+pub struct ConnectionMetrics {
+  send_queue_depth: Arc<AtomicUsize>,
+  pub(crate) message_sorter: Arc<MessageSorterCounters>,
+}
+
+impl ConnectionMetrics {
+  /// Number of messages waiting to be handed to the transport.
+  pub fn send_queue_depth(&self) -> usize {
+    self.send_queue_depth.load(Ordering::SeqCst)
+  }
+
+  /// Reply matching statistics of the client using this connection. All zero for server
+  /// connections.
+  pub fn message_sorter_stats(&self) -> MessageSorterStats {
+    let counters = &self.message_sorter;
+    MessageSorterStats {
+      registered: counters.registered.load(Ordering::SeqCst),
+      resolved: counters.resolved.load(Ordering::SeqCst),
+      expired: counters.expired.load(Ordering::SeqCst),
+      collisions: counters.collisions.load(Ordering::SeqCst),
+      current_pending: counters.current_pending.load(Ordering::SeqCst),
+    }
+  }
+}
+
+/// A queued message, noting whether it was force pushed, so overflow handling leaves it alone.
+struct QueuedItem<T> {
+  item: T,
+  control: bool,
+}
+
+/// Number of queued messages that weren't force pushed.
+fn regular_count<T>(queue: &VecDeque<QueuedItem<T>>) -> usize {
+  queue.iter().filter(|queued| !queued.control).count()
+}
+
+pub(super) struct SendQueue<T> {
+  queue: Mutex<VecDeque<QueuedItem<T>>>,
+  capacity: usize,
+  overflow_policy: SendQueueOverflowPolicy,
+  item_available: Notify,
+  space_available: Notify,
+  closed: AtomicBool,
+  metrics: ConnectionMetrics,
+}
+
+impl<T> SendQueue<T> {
+  pub fn new(capacity: usize, overflow_policy: SendQueueOverflowPolicy) -> Self {
+    Self {
+      queue: Mutex::new(VecDeque::with_capacity(capacity)),
+      // A zero capacity queue could never hand anything off, so treat it as 1.
+      capacity: capacity.max(1),
+      overflow_policy,
+      item_available: Notify::new(),
+      space_available: Notify::new(),
+      closed: AtomicBool::new(false),
+      metrics: ConnectionMetrics::default(),
+    }
+  }
+
+  pub fn metrics(&self) -> ConnectionMetrics {
+    self.metrics.clone()
+  }
+
+  fn update_depth(&self, queue: &VecDeque<QueuedItem<T>>) {
+    self
+      .metrics
+      .send_queue_depth
+      .store(queue.len(), Ordering::SeqCst);
+  }
+
+  /// Queue a message, applying the overflow policy if the queue is full.
+  pub async fn push(&self, item: T) -> Result<(), ButtplugConnectorError> {
+    loop {
+      // Register interest in free space before checking, so we can't miss a pop (or close) that
+      // happens between the check and the wait.
+      let space_available = self.space_available.notified();
+      if self.closed.load(Ordering::SeqCst) {
+        return Err(ButtplugConnectorError::ConnectorNotConnected);
+      }
+      {
+        let mut queue = self.queue.lock().expect("Send queue lock poisoned");
+        // Control messages don't count towards capacity, and are never dropped.
+        if regular_count(&queue) >= self.capacity {
+          match self.overflow_policy {
+            SendQueueOverflowPolicy::DropOldest => {
+              warn!("Connector send queue full, dropping oldest queued message.");
+              if let Some(oldest) = queue.iter().position(|queued| !queued.control) {
+                queue.remove(oldest);
+              }
+            }
+            SendQueueOverflowPolicy::DropNewest => {
+              warn!("Connector send queue full, dropping newest message.");
+              return Ok(());
+            }
+            SendQueueOverflowPolicy::ReturnError => {
+              return Err(ButtplugConnectorError::ConnectorSendQueueFull);
+            }
+            SendQueueOverflowPolicy::Block => {}
+          }
+        }
+        if regular_count(&queue) < self.capacity {
+          queue.push_back(QueuedItem {
+            item,
+            control: false,
+          });
+          self.update_depth(&queue);
+          self.item_available.notify_one();
+          return Ok(());
+        }
+      }
+      space_available.await;
+    }
+  }
+
+  /// Queue a message regardless of capacity. Used for control messages (like closing the
+  /// connection) that should never be dropped or blocked, so they're also skipped when
+  /// [SendQueueOverflowPolicy::DropOldest] makes room. Returns false if the queue is closed.
+  pub fn force_push(&self, item: T) -> bool {
+    if self.closed.load(Ordering::SeqCst) {
+      return false;
+    }
+    let mut queue = self.queue.lock().expect("Send queue lock poisoned");
+    queue.push_back(QueuedItem {
+      item,
+      control: true,
+    });
+    self.update_depth(&queue);
+    self.item_available.notify_one();
+    true
+  }
+
+  /// Wait for the next queued message. Returns None once the queue has been closed.
+  pub async fn pop(&self) -> Option<T> {
+    loop {
+      let item_available = self.item_available.notified();
+      if self.closed.load(Ordering::SeqCst) {
+        return None;
+      }
+      {
+        let mut queue = self.queue.lock().expect("Send queue lock poisoned");
+        if let Some(queued) = queue.pop_front() {
+          self.update_depth(&queue);
+          self.space_available.notify_one();
+          return Some(queued.item);
+        }
+      }
+      item_available.await;
+    }
+  }
+
+  /// Close the queue, waking up anyone waiting on it. Queued messages are discarded.
+  pub fn close(&self) {
+    self.closed.store(true, Ordering::SeqCst);
+    let mut queue = self.queue.lock().expect("Send queue lock poisoned");
+    queue.clear();
+    self.update_depth(&queue);
+    self.item_available.notify_waiters();
+    self.space_available.notify_waiters();
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+  use crate::util::async_manager;
+  use futures::FutureExt;
+
+  fn full_queue(policy: SendQueueOverflowPolicy) -> SendQueue<u32> {
+    let queue = SendQueue::new(2, policy);
+    async_manager::block_on(async {
+      queue.push(1).await.unwrap();
+      queue.push(2).await.unwrap();
+    });
+    assert_eq!(queue.metrics().send_queue_depth(), 2);
+    queue
+  }
+
+  #[test]
+  fn test_send_queue_drop_oldest() {
+    let queue = full_queue(SendQueueOverflowPolicy::DropOldest);
+    async_manager::block_on(async {
+      queue.push(3).await.unwrap();
+      assert_eq!(queue.metrics().send_queue_depth(), 2);
+      assert_eq!(queue.pop().await, Some(2));
+      assert_eq!(queue.pop().await, Some(3));
+    });
+  }
+
+  #[test]
+  fn test_send_queue_drop_oldest_keeps_control_messages() {
+    let queue = SendQueue::new(2, SendQueueOverflowPolicy::DropOldest);
+    async_manager::block_on(async {
+      assert!(queue.force_push(0));
+      queue.push(1).await.unwrap();
+      queue.push(2).await.unwrap();
+      queue.push(3).await.unwrap();
+      assert_eq!(queue.metrics().send_queue_depth(), 3);
+      assert_eq!(queue.pop().await, Some(0));
+      assert_eq!(queue.pop().await, Some(2));
+      assert_eq!(queue.pop().await, Some(3));
+    });
+  }
+
+  #[test]
+  fn test_send_queue_drop_newest() {
+    let queue = full_queue(SendQueueOverflowPolicy::DropNewest);
+    async_manager::block_on(async {
+      queue.push(3).await.unwrap();
+      assert_eq!(queue.pop().await, Some(1));
+      assert_eq!(queue.pop().await, Some(2));
+      assert_eq!(queue.metrics().send_queue_depth(), 0);
+    });
+  }
+
+  #[test]
+  fn test_send_queue_return_error() {
+    let queue = full_queue(SendQueueOverflowPolicy::ReturnError);
+    async_manager::block_on(async {
+      assert!(matches!(
+        queue.push(3).await,
+        Err(ButtplugConnectorError::ConnectorSendQueueFull)
+      ));
+    });
+  }
+
+  #[test]
+  fn test_send_queue_block() {
+    let queue = full_queue(SendQueueOverflowPolicy::Block);
+    async_manager::block_on(async {
+      let mut blocked_push = Box::pin(queue.push(3));
+      assert!((&mut blocked_push).now_or_never().is_none());
+      assert_eq!(queue.pop().await, Some(1));
+      blocked_push.await.unwrap();
+      assert_eq!(queue.pop().await, Some(2));
+      assert_eq!(queue.pop().await, Some(3));
+    });
+  }
+
+  #[test]
+  fn test_send_queue_close() {
+    let queue = full_queue(SendQueueOverflowPolicy::Block);
+    queue.close();
+    assert_eq!(queue.metrics().send_queue_depth(), 0);
+    async_manager::block_on(async {
+      assert_eq!(queue.pop().await, None);
+      assert!(queue.push(3).await.is_err());
+    });
+    assert!(!queue.force_push(4));
+  }
+}
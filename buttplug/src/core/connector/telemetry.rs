@@ -0,0 +1,133 @@
+// Buttplug Rust Source Code File - See https://buttplug.io for more info.
+//
+// Copyright 2016-2023 Nonpolynomial Labs LLC. All rights reserved.
+//
+// Licensed under the BSD 3-Clause license. See LICENSE file in the project root
+// for full license information.
+
+//! Hooks for measuring connector send/receive latency.
+
+#[cfg(feature = "metrics")]
+use hdrhistogram::Histogram;
+#[cfg(feature = "metrics")]
+use std::sync::{
+  atomic::{AtomicU64, Ordering},
+  Arc,
+  Mutex,
+};
+use std::time::Duration;
+
+/// Callbacks for connector message traffic. Set on a connector with
+/// [ButtplugRemoteConnector::with_telemetry](super::ButtplugRemoteConnector::with_telemetry).
+///
+/// These are called inline from the connector, so implementations should return quickly.
+pub trait ConnectorTelemetry: Send + Sync {
+  /// Called when a message is handed to the connector to be sent.
+  fn on_send_start(&self, _msg_id: u32) {
+  }
+  /// Called once a message has been serialized and handed to the transport, with the time elapsed
+  /// since [ConnectorTelemetry::on_send_start].
+  fn on_send_complete(&self, _msg_id: u32, _latency: Duration) {
+  }
+  /// Called for each message received and deserialized from the transport.
+  fn on_receive(&self, _msg_id: u32) {
+  }
+}
+
+/// Built in [ConnectorTelemetry] that keeps a histogram of send latencies, using
+/// [hdrhistogram].
+///
+/// Latencies are recorded in microseconds to 3 significant figures. Clones share the same
+/// histogram, so a handle can be kept while another is given to a connector.
+#[cfg(feature = "metrics")]
+#[derive(Debug, Clone)]
+pub struct HistogramTelemetry {
+  send_latencies: Arc<Mutex<Histogram<u64>>>,
+  received: Arc<AtomicU64>,
+}
+
+#[cfg(feature = "metrics")]
+impl Default for HistogramTelemetry {
+  fn default() -> Self {
+    Self {
+      send_latencies: Arc::new(Mutex::new(
+        Histogram::new(3).expect("3 significant figures is in the valid range"),
+      )),
+      received: Arc::new(AtomicU64::new(0)),
+    }
+  }
+}
+
+#[cfg(feature = "metrics")]
+impl HistogramTelemetry {
+  /// Number of send latencies recorded.
+  pub fn send_count(&self) -> u64 {
+    self.send_latencies.lock().expect("Lock poisoned").len()
+  }
+
+  /// Number of messages received.
+  pub fn receive_count(&self) -> u64 {
+    self.received.load(Ordering::SeqCst)
+  }
+
+  /// Send latency at the given percentile (0.0-100.0), or None if nothing has been recorded yet.
+  pub fn send_latency_percentile(&self, percentile: f64) -> Option<Duration> {
+    let send_latencies = self.send_latencies.lock().expect("Lock poisoned");
+    if send_latencies.is_empty() {
+      return None;
+    }
+    let micros = send_latencies.value_at_percentile(percentile.clamp(0.0, 100.0));
+    Some(Duration::from_micros(micros))
+  }
+
+  /// Clear all recorded values.
+  pub fn reset(&self) {
+    self.send_latencies.lock().expect("Lock poisoned").reset();
+    self.received.store(0, Ordering::SeqCst);
+  }
+}
+
+#[cfg(feature = "metrics")]
+impl ConnectorTelemetry for HistogramTelemetry {
+  fn on_send_complete(&self, _msg_id: u32, latency: Duration) {
+    let micros = u64::try_from(latency.as_micros()).unwrap_or(u64::MAX);
+    let mut send_latencies = self.send_latencies.lock().expect("Lock poisoned");
+    // The histogram grows to fit new values, so this only fails for absurdly long latencies.
+    if send_latencies.record(micros).is_err() {
+      send_latencies.saturating_record(micros);
+    }
+  }
+
+  fn on_receive(&self, _msg_id: u32) {
+    self.received.fetch_add(1, Ordering::SeqCst);
+  }
+}
+
+#[cfg(all(test, feature = "metrics"))]
+mod test {
+  use super::*;
+
+  #[test]
+  fn test_histogram_telemetry_percentiles() {
+    let telemetry = HistogramTelemetry::default();
+    assert!(telemetry.send_latency_percentile(50.0).is_none());
+    for _ in 0..9 {
+      telemetry.on_send_complete(0, Duration::from_micros(100));
+    }
+    telemetry.on_send_complete(0, Duration::from_millis(10));
+    telemetry.on_receive(1);
+    assert_eq!(telemetry.send_count(), 10);
+    assert_eq!(telemetry.receive_count(), 1);
+    assert_eq!(
+      telemetry.send_latency_percentile(50.0),
+      Some(Duration::from_micros(100))
+    );
+    // 10ms is recorded to 3 significant figures.
+    let max = telemetry
+      .send_latency_percentile(100.0)
+      .expect("Latencies were recorded");
+    assert!(max >= Duration::from_millis(10) && max < Duration::from_micros(10_010));
+    telemetry.reset();
+    assert_eq!(telemetry.send_count(), 0);
+  }
+}
@@ -0,0 +1,126 @@
+// Buttplug Rust Source Code File - See https://buttplug.io for more info.
+//
+// Copyright 2016-2023 Nonpolynomial Labs LLC. All rights reserved.
+//
+// Licensed under the BSD 3-Clause license. See LICENSE file in the project root
+// for full license information.
+
+//! Transport over a websocket that an [axum] route has already accepted.
+
+use super::{ButtplugConnectorTransport, ButtplugTransportIncomingMessage};
+use crate::{
+  core::{
+    connector::{ButtplugConnectorError, ButtplugConnectorResultFuture},
+    message::serializer::ButtplugSerializedMessage,
+  },
+  util::async_manager,
+};
+use axum::extract::ws::{Message, WebSocket};
+use futures::{future::BoxFuture, FutureExt, SinkExt, StreamExt};
+use std::sync::{Arc, Mutex};
+use tokio::sync::{
+  mpsc::{Receiver, Sender},
+  Notify,
+};
+use tracing::Instrument;
+
+/// Transport for a websocket upgraded by axum, see
+/// [buttplug_axum_handler](crate::server::buttplug_axum_handler) for a route that uses it.
+pub struct ButtplugAxumWebsocketTransport {
+  /// Taken when connecting, as the socket can only be used once.
+  socket: Mutex<Option<WebSocket>>,
+  disconnect_notifier: Arc<Notify>,
+}
+
+impl From<WebSocket> for ButtplugAxumWebsocketTransport {
+  fn from(socket: WebSocket) -> Self {
+    Self {
+      socket: Mutex::new(Some(socket)),
+      disconnect_notifier: Arc::new(Notify::new()),
+    }
+  }
+}
+
+impl ButtplugConnectorTransport for ButtplugAxumWebsocketTransport {
+  fn connect(
+    &self,
+    mut outgoing_receiver: Receiver<ButtplugSerializedMessage>,
+    incoming_sender: Sender<ButtplugTransportIncomingMessage>,
+  ) -> BoxFuture<'static, Result<(), ButtplugConnectorError>> {
+    let socket = self.socket.lock().expect("Lock poisoned").take();
+    let disconnect_notifier = self.disconnect_notifier.clone();
+    async move {
+      let socket = socket.ok_or(ButtplugConnectorError::ConnectorAlreadyConnected)?;
+      let (mut sender, mut receiver) = socket.split();
+      async_manager::spawn(
+        async move {
+          let close_reason = loop {
+            tokio::select! {
+              msg = outgoing_receiver.recv() => {
+                let Some(msg) = msg else {
+                  info!("Connector holding websocket dropped, returning");
+                  break "Connector closed connection";
+                };
+                let msg = match msg {
+                  ButtplugSerializedMessage::Text(text) => Message::Text(text),
+                  ButtplugSerializedMessage::Binary(bin) => Message::Binary(bin),
+                };
+                if let Err(err) = sender.send(msg).await {
+                  error!("Error writing to websocket (assuming disconnect): {}", err);
+                  break "Websocket closed";
+                }
+              }
+              msg = receiver.next() => {
+                // axum answers pings itself, so only messages and closes need handling here.
+                let msg = match msg {
+                  Some(Ok(Message::Text(text))) => ButtplugSerializedMessage::Text(text),
+                  Some(Ok(Message::Binary(bin))) => ButtplugSerializedMessage::Binary(bin),
+                  Some(Ok(Message::Ping(_))) | Some(Ok(Message::Pong(_))) => continue,
+                  Some(Ok(Message::Close(_))) | None => break "Remote closed connection",
+                  Some(Err(err)) => {
+                    error!("Error reading from websocket (assuming disconnect): {}", err);
+                    break "Websocket closed";
+                  }
+                };
+                if incoming_sender
+                  .send(ButtplugTransportIncomingMessage::Message(msg))
+                  .await
+                  .is_err()
+                {
+                  warn!("Websocket holder has closed, exiting websocket loop.");
+                  return;
+                }
+              }
+              _ = disconnect_notifier.notified() => {
+                info!("Websocket requested to disconnect.");
+                break "Disconnect notifier triggered, closed connection";
+              }
+            }
+          };
+          sender.close().await.unwrap_or_else(|err| error!("{}", err));
+          if incoming_sender
+            .send(ButtplugTransportIncomingMessage::Close(
+              close_reason.to_owned(),
+            ))
+            .await
+            .is_err()
+          {
+            warn!("Websocket holder has closed, exiting websocket loop.");
+          }
+        }
+        .instrument(tracing::info_span!("Axum Websocket Transport I/O Task")),
+      );
+      Ok(())
+    }
+    .boxed()
+  }
+
+  fn disconnect(self) -> ButtplugConnectorResultFuture {
+    let disconnect_notifier = self.disconnect_notifier;
+    async move {
+      disconnect_notifier.notify_waiters();
+      Ok(())
+    }
+    .boxed()
+  }
+}
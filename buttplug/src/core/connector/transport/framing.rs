@@ -0,0 +1,144 @@
+// Buttplug Rust Source Code File - See https://buttplug.io for more info.
+//
+// Copyright 2016-2023 Nonpolynomial Labs LLC. All rights reserved.
+//
+// Licensed under the BSD 3-Clause license. See LICENSE file in the project root
+// for full license information.
+
+//! Splitting byte streams into messages, for transports that don't have message boundaries of
+//! their own (TCP, pipes, serial ports). Websockets frame messages themselves, so they don't use
+//! this.
+
+use bytes::{Buf, BufMut, Bytes, BytesMut};
+use thiserror::Error;
+
+/// Frame read from a stream is bigger than the transport allows, see
+/// [ButtplugStreamTransport::with_max_frame_size](super::ButtplugStreamTransport::with_max_frame_size).
+#[derive(Debug, Error, Clone, Copy, PartialEq, Eq)]
+#[error("Frame of at least {size} bytes is over the {max_size} byte limit")]
+pub struct FrameTooLarge {
+  pub size: usize,
+  pub max_size: usize,
+}
+
+/// Turns serialized messages into frames to write to a stream, and splits frames back out of
+/// what's been read from it, see [ButtplugStreamTransport](super::ButtplugStreamTransport).
+pub trait ButtplugFramer: Send + Sync + 'static {
+  /// Frame a message for writing.
+  fn encode(&self, msg: &[u8]) -> Bytes;
+  /// Remove the first complete frame from `buf` and return its message, or None if `buf` doesn't
+  /// hold a complete frame yet. Incomplete data is left in `buf` for the next read to finish.
+  ///
+  /// Fails as soon as the frame is known to hold more than `max_frame_size` bytes, without
+  /// waiting for (or making room for) the rest of it.
+  fn decode(
+    &self,
+    buf: &mut BytesMut,
+    max_frame_size: usize,
+  ) -> Result<Option<Bytes>, FrameTooLarge>;
+  /// Whether frames can hold any bytes. False for framers that would split a binary message on
+  /// one of its bytes, like [NewlineFramer].
+  fn supports_binary(&self) -> bool {
+    true
+  }
+}
+
+/// Messages separated by `\n`. Works for JSON, which never has raw newlines in it, but not for
+/// binary messages.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct NewlineFramer;
+
+impl ButtplugFramer for NewlineFramer {
+  fn encode(&self, msg: &[u8]) -> Bytes {
+    let mut frame = BytesMut::with_capacity(msg.len() + 1);
+    frame.put_slice(msg);
+    frame.put_u8(b'\n');
+    frame.freeze()
+  }
+
+  fn decode(
+    &self,
+    buf: &mut BytesMut,
+    max_frame_size: usize,
+  ) -> Result<Option<Bytes>, FrameTooLarge> {
+    let Some(end) = buf.iter().position(|byte| *byte == b'\n') else {
+      if buf.len() > max_frame_size {
+        return Err(FrameTooLarge {
+          size: buf.len(),
+          max_size: max_frame_size,
+        });
+      }
+      return Ok(None);
+    };
+    if end > max_frame_size {
+      return Err(FrameTooLarge {
+        size: end,
+        max_size: max_frame_size,
+      });
+    }
+    let mut frame = buf.split_to(end + 1);
+    frame.truncate(end);
+    // Tolerate peers that send \r\n line endings.
+    if frame.last() == Some(&b'\r') {
+      frame.truncate(end - 1);
+    }
+    Ok(Some(frame.freeze()))
+  }
+
+  fn supports_binary(&self) -> bool {
+    false
+  }
+}
+
+/// Byte order of the length in [LengthPrefixFramer] frames.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ByteOrder {
+  #[default]
+  BigEndian,
+  LittleEndian,
+}
+
+/// Messages preceded by their length in bytes, as a u32 in the given byte order. Works for any
+/// message, including binary ones.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct LengthPrefixFramer(pub ByteOrder);
+
+impl ButtplugFramer for LengthPrefixFramer {
+  fn encode(&self, msg: &[u8]) -> Bytes {
+    let mut frame = BytesMut::with_capacity(msg.len() + 4);
+    match self.0 {
+      ByteOrder::BigEndian => frame.put_u32(msg.len() as u32),
+      ByteOrder::LittleEndian => frame.put_u32_le(msg.len() as u32),
+    }
+    frame.put_slice(msg);
+    frame.freeze()
+  }
+
+  fn decode(
+    &self,
+    buf: &mut BytesMut,
+    max_frame_size: usize,
+  ) -> Result<Option<Bytes>, FrameTooLarge> {
+    let Some(prefix) = buf.get(..4) else {
+      return Ok(None);
+    };
+    let prefix: [u8; 4] = prefix.try_into().expect("Slice is 4 bytes");
+    let len = match self.0 {
+      ByteOrder::BigEndian => u32::from_be_bytes(prefix),
+      ByteOrder::LittleEndian => u32::from_le_bytes(prefix),
+    } as usize;
+    // The length comes from the peer, so check it before making room for it.
+    if len > max_frame_size {
+      return Err(FrameTooLarge {
+        size: len,
+        max_size: max_frame_size,
+      });
+    }
+    if buf.len() < 4 + len {
+      buf.reserve(4 + len - buf.len());
+      return Ok(None);
+    }
+    buf.advance(4);
+    Ok(Some(buf.split_to(len).freeze()))
+  }
+}
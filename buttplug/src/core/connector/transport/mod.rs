@@ -7,6 +7,11 @@
 
 //! Transports for remote (IPC/network/etc) communication between clients and servers
 
+#[cfg(feature = "axum")]
+mod axum_websocket;
+mod framing;
+mod stream;
+mod tls;
 #[cfg(feature = "websockets")]
 mod websocket;
 use crate::core::connector::{
@@ -14,8 +19,14 @@ use crate::core::connector::{
   ButtplugConnectorResultFuture,
   ButtplugSerializedMessage,
 };
+#[cfg(feature = "axum")]
+pub use axum_websocket::ButtplugAxumWebsocketTransport;
+pub use framing::{ButtplugFramer, ByteOrder, FrameTooLarge, LengthPrefixFramer, NewlineFramer};
 use futures::future::BoxFuture;
+use std::net::SocketAddr;
+pub use stream::{ButtplugStreamTransport, DEFAULT_MAX_FRAME_SIZE};
 use thiserror::Error;
+pub use tls::TlsConfig;
 use tokio::sync::mpsc::{Receiver, Sender};
 #[cfg(feature = "websockets")]
 pub use websocket::{
@@ -25,6 +36,19 @@ pub use websocket::{
   TungsteniteError,
 };
 
+/// ALPN protocol identifier for connections using the binary Buttplug protocol.
+pub const BUTTPLUG_ALPN_PROTOCOL: &str = "buttplug";
+/// ALPN protocol identifier for connections using the JSON Buttplug protocol.
+pub const BUTTPLUG_JSON_ALPN_PROTOCOL: &str = "buttplug-json";
+
+/// Returns true if the ALPN token matches one of the Buttplug protocol identifiers. TLS acceptors
+/// should reject connections that don't negotiate a known protocol, so that generic HTTPS clients
+/// can't accidentally connect.
+pub fn is_buttplug_alpn_protocol(protocol: &[u8]) -> bool {
+  protocol == BUTTPLUG_ALPN_PROTOCOL.as_bytes()
+    || protocol == BUTTPLUG_JSON_ALPN_PROTOCOL.as_bytes()
+}
+
 /// Messages we can receive from a connector.
 #[derive(Clone, Debug, Display)]
 pub enum ButtplugTransportIncomingMessage {
@@ -46,6 +70,23 @@ pub trait ButtplugConnectorTransport: Send + Sync {
     incoming_sender: Sender<ButtplugTransportIncomingMessage>,
   ) -> BoxFuture<'static, Result<(), ButtplugConnectorError>>;
   fn disconnect(self) -> ButtplugConnectorResultFuture;
+  /// Address of the other end of the connection, once connected. None for transports that don't
+  /// run over the network.
+  fn peer_address(&self) -> Option<SocketAddr> {
+    None
+  }
+  /// Serve connections over TLS with the given certificate, replacing any TLS settings the
+  /// transport was built with. Transports that don't accept connections ignore this.
+  fn set_tls_config(&mut self, _tls_config: TlsConfig) {
+  }
+  /// Largest message the transport can carry in one piece, in bytes. None if there is no limit.
+  fn max_message_size(&self) -> Option<usize> {
+    None
+  }
+  /// Whether the transport can carry [ButtplugSerializedMessage::Binary] messages intact.
+  fn supports_binary_messages(&self) -> bool {
+    true
+  }
 }
 
 #[derive(Error, Debug)]
@@ -55,4 +96,18 @@ pub enum ButtplugConnectorTransportSpecificError {
   TungsteniteError(#[from] TungsteniteError),
   #[error("Network error: {0}")]
   GenericNetworkError(String),
+  #[error("IO error: {0}")]
+  IoError(#[from] std::io::Error),
+}
+
+impl ButtplugConnectorTransportSpecificError {
+  /// Returns the underlying OS error code, if this error came from the operating system.
+  pub fn os_error_code(&self) -> Option<i32> {
+    match self {
+      #[cfg(feature = "websockets")]
+      Self::TungsteniteError(TungsteniteError::Io(err)) => err.raw_os_error(),
+      Self::IoError(err) => err.raw_os_error(),
+      _ => None,
+    }
+  }
 }
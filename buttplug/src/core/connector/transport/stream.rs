@@ -0,0 +1,187 @@
+// Buttplug Rust Source Code File - See https://buttplug.io for more info.
+//
+// Copyright 2016-2023 Nonpolynomial Labs LLC. All rights reserved.
+//
+// Licensed under the BSD 3-Clause license. See LICENSE file in the project root
+// for full license information.
+
+//! Transport over any already connected byte stream, using a [ButtplugFramer] to find message
+//! boundaries.
+
+use super::{
+  framing::ButtplugFramer,
+  ButtplugConnectorTransport,
+  ButtplugTransportIncomingMessage,
+};
+use crate::{
+  core::{
+    connector::{ButtplugConnectorError, ButtplugConnectorResultFuture},
+    message::serializer::ButtplugSerializedMessage,
+  },
+  util::async_manager,
+};
+use bytes::BytesMut;
+use futures::{future::BoxFuture, FutureExt};
+use std::sync::{Arc, Mutex};
+use tokio::{
+  io::{self, AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt},
+  sync::{
+    mpsc::{Receiver, Sender},
+    Notify,
+  },
+};
+use tracing::Instrument;
+
+/// Largest frame [ButtplugStreamTransport] accepts by default, in bytes.
+pub const DEFAULT_MAX_FRAME_SIZE: usize = 16 * 1024 * 1024;
+
+/// Transport for a byte stream the application has already connected, like a TCP socket, unix
+/// socket or pipe. Messages are framed with `framer`, which both ends need to agree on.
+pub struct ButtplugStreamTransport<S, F> {
+  /// Taken when connecting, as the stream can only be used once.
+  stream: Mutex<Option<S>>,
+  framer: Arc<F>,
+  max_frame_size: usize,
+  disconnect_notifier: Arc<Notify>,
+}
+
+impl<S, F> ButtplugStreamTransport<S, F>
+where
+  S: AsyncRead + AsyncWrite + Send + Unpin + 'static,
+  F: ButtplugFramer,
+{
+  pub fn new(stream: S, framer: F) -> Self {
+    Self {
+      stream: Mutex::new(Some(stream)),
+      framer: Arc::new(framer),
+      max_frame_size: DEFAULT_MAX_FRAME_SIZE,
+      disconnect_notifier: Arc::new(Notify::new()),
+    }
+  }
+
+  /// Largest frame to accept from the other end, in bytes, [DEFAULT_MAX_FRAME_SIZE] by default.
+  /// The connection is closed as soon as a bigger frame starts arriving. This is also the
+  /// connector's [max_message_size](crate::core::connector::ButtplugConnector::max_message_size).
+  pub fn with_max_frame_size(mut self, max_frame_size: usize) -> Self {
+    self.max_frame_size = max_frame_size;
+    self
+  }
+}
+
+impl<S, F> ButtplugConnectorTransport for ButtplugStreamTransport<S, F>
+where
+  S: AsyncRead + AsyncWrite + Send + Unpin + 'static,
+  F: ButtplugFramer,
+{
+  fn connect(
+    &self,
+    mut outgoing_receiver: Receiver<ButtplugSerializedMessage>,
+    incoming_sender: Sender<ButtplugTransportIncomingMessage>,
+  ) -> BoxFuture<'static, Result<(), ButtplugConnectorError>> {
+    let stream = self.stream.lock().expect("Lock poisoned").take();
+    let framer = self.framer.clone();
+    let max_frame_size = self.max_frame_size;
+    let disconnect_notifier = self.disconnect_notifier.clone();
+    async move {
+      let stream = stream.ok_or(ButtplugConnectorError::ConnectorAlreadyConnected)?;
+      let (mut reader, mut writer) = io::split(stream);
+      async_manager::spawn(
+        async move {
+          let mut buffer = BytesMut::new();
+          let close_reason = loop {
+            tokio::select! {
+              msg = outgoing_receiver.recv() => {
+                let Some(msg) = msg else {
+                  info!("Connector holding stream dropped, returning");
+                  break "Connector closed connection";
+                };
+                let frame = match msg {
+                  ButtplugSerializedMessage::Text(text) => framer.encode(text.as_bytes()),
+                  ButtplugSerializedMessage::Binary(bin) => framer.encode(&bin),
+                };
+                if let Err(err) = writer.write_all(&frame).await {
+                  error!("Error writing to stream (assuming disconnect): {}", err);
+                  break "Stream closed";
+                }
+              }
+              read = reader.read_buf(&mut buffer) => {
+                match read {
+                  Ok(0) => break "Remote closed connection",
+                  Ok(_) => {}
+                  Err(err) => {
+                    error!("Error reading from stream (assuming disconnect): {}", err);
+                    break "Stream closed";
+                  }
+                }
+                let frame_too_large = loop {
+                  let frame = match framer.decode(&mut buffer, max_frame_size) {
+                    Ok(Some(frame)) => frame,
+                    Ok(None) => break false,
+                    Err(err) => {
+                      error!("Error reading from stream, closing connection: {}", err);
+                      break true;
+                    }
+                  };
+                  // Text serializers (JSON) are the common case, anything that isn't UTF-8 is
+                  // passed on as binary.
+                  let msg = match String::from_utf8(frame.to_vec()) {
+                    Ok(text) => ButtplugSerializedMessage::Text(text),
+                    Err(err) => ButtplugSerializedMessage::Binary(err.into_bytes()),
+                  };
+                  if incoming_sender
+                    .send(ButtplugTransportIncomingMessage::Message(msg))
+                    .await
+                    .is_err()
+                  {
+                    warn!("Stream holder has closed, exiting stream loop.");
+                    return;
+                  }
+                };
+                if frame_too_large {
+                  break "Frame too large";
+                }
+              }
+              _ = disconnect_notifier.notified() => {
+                info!("Stream requested to disconnect.");
+                break "Disconnect notifier triggered, closed connection";
+              }
+            }
+          };
+          writer
+            .shutdown()
+            .await
+            .unwrap_or_else(|err| error!("{}", err));
+          if incoming_sender
+            .send(ButtplugTransportIncomingMessage::Close(
+              close_reason.to_owned(),
+            ))
+            .await
+            .is_err()
+          {
+            warn!("Stream holder has closed, exiting stream loop.");
+          }
+        }
+        .instrument(tracing::info_span!("Stream Transport I/O Task")),
+      );
+      Ok(())
+    }
+    .boxed()
+  }
+
+  fn max_message_size(&self) -> Option<usize> {
+    Some(self.max_frame_size)
+  }
+
+  fn supports_binary_messages(&self) -> bool {
+    self.framer.supports_binary()
+  }
+
+  fn disconnect(self) -> ButtplugConnectorResultFuture {
+    let disconnect_notifier = self.disconnect_notifier;
+    async move {
+      disconnect_notifier.notify_waiters();
+      Ok(())
+    }
+    .boxed()
+  }
+}
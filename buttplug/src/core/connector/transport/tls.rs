@@ -0,0 +1,264 @@
+// Buttplug Rust Source Code File - See https://buttplug.io for more info.
+//
+// Copyright 2016-2023 Nonpolynomial Labs LLC. All rights reserved.
+//
+// Licensed under the BSD 3-Clause license. See LICENSE file in the project root
+// for full license information.
+
+//! TLS settings for transports that accept connections.
+
+#[cfg(feature = "websockets")]
+use super::{BUTTPLUG_ALPN_PROTOCOL, BUTTPLUG_JSON_ALPN_PROTOCOL};
+use getset::{CopyGetters, Getters};
+use serde::{Deserialize, Serialize};
+#[cfg(feature = "websockets")]
+use std::path::Path;
+use std::path::PathBuf;
+#[cfg(feature = "rustls")]
+use std::{io::BufRead, sync::Arc};
+#[cfg(feature = "rustls")]
+use tokio_rustls::rustls::{
+  server::{AllowAnyAnonymousOrAuthenticatedClient, AllowAnyAuthenticatedClient, NoClientAuth},
+  Certificate,
+  PrivateKey,
+  RootCertStore,
+  ServerConfig,
+};
+
+/// Acceptor used to serve TLS connections: rustls with the `rustls` feature, native-tls otherwise.
+#[cfg(all(feature = "websockets", not(feature = "rustls")))]
+pub(crate) type ServerTlsAcceptor = tokio_native_tls::TlsAcceptor;
+#[cfg(feature = "rustls")]
+pub(crate) type ServerTlsAcceptor = tokio_rustls::TlsAcceptor;
+
+#[cfg(feature = "websockets")]
+fn read_file(path: &Path) -> Result<Vec<u8>, String> {
+  std::fs::read(path).map_err(|err| format!("Cannot read {}: {}", path.display(), err))
+}
+
+/// Read every PEM item `parse` finds in the file at `path`.
+#[cfg(feature = "rustls")]
+fn read_pem(
+  path: &Path,
+  parse: fn(&mut dyn BufRead) -> std::io::Result<Vec<Vec<u8>>>,
+) -> Result<Vec<Vec<u8>>, String> {
+  let file = read_file(path)?;
+  parse(&mut file.as_slice()).map_err(|err| format!("Cannot parse {}: {}", path.display(), err))
+}
+
+/// Certificate and key files for serving TLS connections, set on a server with
+/// [ButtplugServerBuilder::with_tls_config](crate::server::ButtplugServerBuilder::with_tls_config)
+/// or on a transport builder directly. Files are PEM encoded, with the key in PKCS #8 format.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, Getters, CopyGetters)]
+pub struct TlsConfig {
+  /// Server certificate chain.
+  #[getset(get = "pub")]
+  cert_path: PathBuf,
+  /// Private key for the server certificate.
+  #[getset(get = "pub")]
+  key_path: PathBuf,
+  /// Certificate authorities that client certificates are checked against.
+  #[getset(get = "pub")]
+  client_ca_path: Option<PathBuf>,
+  /// If true, clients without a certificate signed by `client_ca_path` are rejected.
+  #[getset(get_copy = "pub")]
+  require_client_cert: bool,
+}
+
+impl TlsConfig {
+  pub fn new(cert_path: PathBuf, key_path: PathBuf) -> Self {
+    Self {
+      cert_path,
+      key_path,
+      client_ca_path: None,
+      require_client_cert: false,
+    }
+  }
+
+  /// Check client certificates against the certificate authorities in `client_ca_path`. If
+  /// `require_client_cert` is true, clients that don't present a valid certificate are rejected.
+  ///
+  /// Only supported with the `rustls` feature, transports fail to start with this set otherwise.
+  pub fn with_client_ca(mut self, client_ca_path: PathBuf, require_client_cert: bool) -> Self {
+    self.client_ca_path = Some(client_ca_path);
+    self.require_client_cert = require_client_cert;
+    self
+  }
+
+  /// Build a native-tls acceptor from the configured files, which offers the Buttplug ALPN
+  /// protocols to clients.
+  ///
+  /// native-tls can't verify client certificates, so configs with a client CA are rejected rather
+  /// than silently accepting any client. Turn on the `rustls` feature to verify them.
+  #[cfg(all(feature = "websockets", not(feature = "rustls")))]
+  pub(crate) fn tls_acceptor(&self) -> Result<ServerTlsAcceptor, String> {
+    if self.client_ca_path.is_some() || self.require_client_cert {
+      return Err("Client certificate verification needs the rustls feature.".to_owned());
+    }
+    let cert = read_file(&self.cert_path)?;
+    let key = read_file(&self.key_path)?;
+    let identity = native_tls::Identity::from_pkcs8(&cert, &key)
+      .map_err(|err| format!("Invalid TLS certificate or key: {}", err))?;
+    native_tls::TlsAcceptor::builder(identity)
+      .accept_alpn(&[BUTTPLUG_ALPN_PROTOCOL, BUTTPLUG_JSON_ALPN_PROTOCOL])
+      .build()
+      .map(ServerTlsAcceptor::from)
+      .map_err(|err| format!("Cannot create TLS acceptor: {}", err))
+  }
+
+  /// Build a rustls acceptor from the configured files, which offers the Buttplug ALPN protocols
+  /// to clients and checks client certificates against `client_ca_path`, if set.
+  #[cfg(feature = "rustls")]
+  pub(crate) fn tls_acceptor(&self) -> Result<ServerTlsAcceptor, String> {
+    let certs = read_pem(&self.cert_path, rustls_pemfile::certs)?
+      .into_iter()
+      .map(Certificate)
+      .collect();
+    let key = read_pem(&self.key_path, rustls_pemfile::pkcs8_private_keys)?
+      .into_iter()
+      .next()
+      .map(PrivateKey)
+      .ok_or_else(|| format!("No PKCS #8 private key in {}", self.key_path.display()))?;
+    let client_cert_verifier = match &self.client_ca_path {
+      Some(client_ca_path) => {
+        let mut roots = RootCertStore::empty();
+        for cert in read_pem(client_ca_path, rustls_pemfile::certs)? {
+          roots
+            .add(&Certificate(cert))
+            .map_err(|err| format!("Invalid client CA in {}: {}", client_ca_path.display(), err))?;
+        }
+        if self.require_client_cert {
+          AllowAnyAuthenticatedClient::new(roots).boxed()
+        } else {
+          AllowAnyAnonymousOrAuthenticatedClient::new(roots).boxed()
+        }
+      }
+      None if self.require_client_cert => {
+        return Err("Requiring client certificates needs a client CA.".to_owned());
+      }
+      None => NoClientAuth::boxed(),
+    };
+    let mut config = ServerConfig::builder()
+      .with_safe_defaults()
+      .with_client_cert_verifier(client_cert_verifier)
+      .with_single_cert(certs, key)
+      .map_err(|err| format!("Invalid TLS certificate or key: {}", err))?;
+    config.alpn_protocols = vec![
+      BUTTPLUG_ALPN_PROTOCOL.as_bytes().to_vec(),
+      BUTTPLUG_JSON_ALPN_PROTOCOL.as_bytes().to_vec(),
+    ];
+    Ok(ServerTlsAcceptor::from(Arc::new(config)))
+  }
+}
+
+#[cfg(all(test, feature = "rustls"))]
+mod test {
+  use super::*;
+  use crate::util::async_manager;
+  use rcgen::{BasicConstraints, Certificate as GeneratedCertificate, CertificateParams, IsCa};
+  use tokio_rustls::{
+    rustls::{ClientConfig, ServerName},
+    TlsConnector,
+  };
+
+  /// Certificate authority that signs the server and client certificates of a test.
+  fn generate_ca() -> GeneratedCertificate {
+    let mut params = CertificateParams::new(vec![]);
+    params.is_ca = IsCa::Ca(BasicConstraints::Unconstrained);
+    GeneratedCertificate::from_params(params).unwrap()
+  }
+
+  /// Certificate chain and PKCS #8 key signed by `ca`, as DER.
+  fn generate_signed(ca: &GeneratedCertificate) -> (Vec<Certificate>, PrivateKey) {
+    let cert =
+      GeneratedCertificate::from_params(CertificateParams::new(vec!["localhost".to_owned()]))
+        .unwrap();
+    (
+      vec![Certificate(cert.serialize_der_with_signer(ca).unwrap())],
+      PrivateKey(cert.serialize_private_key_der()),
+    )
+  }
+
+  /// Write the server's certificate, key and client CA for the test called `name`.
+  fn write_tls_config(name: &str, ca: &GeneratedCertificate, client_ca: bool) -> TlsConfig {
+    let dir = std::env::temp_dir().join(format!("buttplug-tls-test-{}", name));
+    std::fs::create_dir_all(&dir).unwrap();
+    let server =
+      GeneratedCertificate::from_params(CertificateParams::new(vec!["localhost".to_owned()]))
+        .unwrap();
+    std::fs::write(
+      dir.join("cert.pem"),
+      server.serialize_pem_with_signer(ca).unwrap(),
+    )
+    .unwrap();
+    std::fs::write(dir.join("key.pem"), server.serialize_private_key_pem()).unwrap();
+    std::fs::write(dir.join("ca.pem"), ca.serialize_pem().unwrap()).unwrap();
+    let tls_config = TlsConfig::new(dir.join("cert.pem"), dir.join("key.pem"));
+    if client_ca {
+      tls_config.with_client_ca(dir.join("ca.pem"), true)
+    } else {
+      tls_config
+    }
+  }
+
+  /// Connect a client to `acceptor` over an in-memory stream, returning the ALPN protocol the
+  /// server negotiated, or None if the server rejected the client.
+  async fn handshake(
+    acceptor: ServerTlsAcceptor,
+    ca: &GeneratedCertificate,
+    client_identity: Option<(Vec<Certificate>, PrivateKey)>,
+  ) -> Option<Vec<u8>> {
+    let mut roots = RootCertStore::empty();
+    roots
+      .add(&Certificate(ca.serialize_der().unwrap()))
+      .unwrap();
+    let builder = ClientConfig::builder()
+      .with_safe_defaults()
+      .with_root_certificates(roots);
+    let mut client_config = match client_identity {
+      Some((certs, key)) => builder.with_client_auth_cert(certs, key).unwrap(),
+      None => builder.with_no_client_auth(),
+    };
+    client_config.alpn_protocols = vec![BUTTPLUG_JSON_ALPN_PROTOCOL.as_bytes().to_vec()];
+    let (client_stream, server_stream) = tokio::io::duplex(16 * 1024);
+    let connector = TlsConnector::from(Arc::new(client_config));
+    let server_name = ServerName::try_from("localhost").unwrap();
+    let (_, accepted) = tokio::join!(
+      connector.connect(server_name, client_stream),
+      acceptor.accept(server_stream)
+    );
+    accepted
+      .ok()
+      .map(|stream| stream.get_ref().1.alpn_protocol().unwrap().to_vec())
+  }
+
+  #[test]
+  fn test_rustls_acceptor_negotiates_alpn() {
+    async_manager::block_on(async {
+      let ca = generate_ca();
+      let acceptor = write_tls_config("alpn", &ca, false).tls_acceptor().unwrap();
+      assert_eq!(
+        handshake(acceptor, &ca, None).await,
+        Some(BUTTPLUG_JSON_ALPN_PROTOCOL.as_bytes().to_vec())
+      );
+    });
+  }
+
+  #[test]
+  fn test_rustls_acceptor_requires_client_cert() {
+    async_manager::block_on(async {
+      let ca = generate_ca();
+      let tls_config = write_tls_config("client-cert", &ca, true);
+      // Clients without a certificate, or with one from another CA, are rejected.
+      let acceptor = tls_config.tls_acceptor().unwrap();
+      assert_eq!(handshake(acceptor, &ca, None).await, None);
+      let acceptor = tls_config.tls_acceptor().unwrap();
+      let other_identity = generate_signed(&generate_ca());
+      assert_eq!(handshake(acceptor, &ca, Some(other_identity)).await, None);
+      let acceptor = tls_config.tls_acceptor().unwrap();
+      assert!(handshake(acceptor, &ca, Some(generate_signed(&ca)))
+        .await
+        .is_some());
+    });
+  }
+}
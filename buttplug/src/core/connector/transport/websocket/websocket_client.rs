@@ -14,6 +14,7 @@ use crate::{
         ButtplugConnectorTransport,
         ButtplugConnectorTransportSpecificError,
         ButtplugTransportIncomingMessage,
+        BUTTPLUG_JSON_ALPN_PROTOCOL,
       },
       ButtplugConnectorError,
       ButtplugConnectorResultFuture,
@@ -22,9 +23,16 @@ use crate::{
   },
   util::async_manager,
 };
-use async_tungstenite::{tokio::connect_async_with_tls_connector, tungstenite::protocol::Message};
+use async_tungstenite::{
+  stream::Stream,
+  tokio::connect_async_with_tls_connector,
+  tungstenite::protocol::Message,
+};
 use futures::{future::BoxFuture, FutureExt, SinkExt, StreamExt};
-use std::sync::Arc;
+use std::{
+  net::SocketAddr,
+  sync::{Arc, Mutex},
+};
 use tokio::sync::{
   mpsc::{Receiver, Sender},
   Notify,
@@ -42,6 +50,8 @@ pub struct ButtplugWebsocketClientTransport {
   bypass_cert_verify: bool,
   /// Internally held sender, used for when disconnect is called.
   disconnect_notifier: Arc<Notify>,
+  /// Address of the server, once connected.
+  peer_address: Arc<Mutex<Option<SocketAddr>>>,
 }
 
 impl ButtplugWebsocketClientTransport {
@@ -51,6 +61,7 @@ impl ButtplugWebsocketClientTransport {
       address: address.to_owned(),
       bypass_cert_verify,
       disconnect_notifier: Arc::new(Notify::new()),
+      peer_address: Arc::new(Mutex::new(None)),
     }
   }
 
@@ -85,34 +96,39 @@ impl ButtplugConnectorTransport for ButtplugWebsocketClientTransport {
 
     // If we're supposed to be a secure connection, generate a TLS connector
     // based on our certificate verfication needs. Otherwise, just pass None in
-    // which case we won't wrap.
+    // which case we won't wrap. We only speak JSON over websockets, so that's
+    // the protocol we advertise via ALPN.
     let tls_connector = if self.should_use_tls {
       use native_tls::TlsConnector;
-      if self.bypass_cert_verify {
-        Some(
-          TlsConnector::builder()
-            .danger_accept_invalid_certs(true)
-            .build()
-            .expect("Should always succeed, we're not setting any fallible options.")
-            .into(),
-        )
-      } else {
-        Some(
-          TlsConnector::new()
-            .expect("Should always succeed, not setting options.")
-            .into(),
-        )
-      }
+      Some(
+        TlsConnector::builder()
+          .danger_accept_invalid_certs(self.bypass_cert_verify)
+          .request_alpns(&[BUTTPLUG_JSON_ALPN_PROTOCOL])
+          .build()
+          .expect("Should always succeed, we're not setting any fallible options.")
+          .into(),
+      )
     } else {
       // If we're not using a secure connection, just return None, at which
       // point async_tungstenite won't use a wrapper.
       None
     };
     let address = self.address.clone();
+    let peer_address = self.peer_address.clone();
 
     async move {
       match connect_async_with_tls_connector(&address, tls_connector).await {
         Ok((stream, _)) => {
+          *peer_address.lock().expect("Lock poisoned") = match stream.get_ref() {
+            Stream::Plain(tcp_stream) => tcp_stream.get_ref().peer_addr().ok(),
+            Stream::Tls(tls_stream) => tls_stream
+              .get_ref()
+              .get_ref()
+              .get_ref()
+              .get_ref()
+              .peer_addr()
+              .ok(),
+          };
           let (mut writer, mut reader) = stream.split();
 
           async_manager::spawn(
@@ -241,4 +257,8 @@ impl ButtplugConnectorTransport for ButtplugWebsocketClientTransport {
     }
     .boxed()
   }
+
+  fn peer_address(&self) -> Option<SocketAddr> {
+    *self.peer_address.lock().expect("Lock poisoned")
+  }
 }
@@ -9,9 +9,11 @@ use crate::{
   core::{
     connector::{
       transport::{
+        is_buttplug_alpn_protocol,
         ButtplugConnectorTransport,
         ButtplugConnectorTransportSpecificError,
         ButtplugTransportIncomingMessage,
+        TlsConfig,
       },
       ButtplugConnectorError,
       ButtplugConnectorResultFuture,
@@ -20,10 +22,25 @@ use crate::{
   },
   util::async_manager,
 };
-use futures::{future::BoxFuture, AsyncRead, AsyncWrite, FutureExt, SinkExt, StreamExt};
-use std::{sync::Arc, time::Duration};
+use async_tungstenite::tungstenite::{
+  handshake::server::{Request, Response},
+  http::{header, HeaderValue},
+};
+use futures::{
+  future::{self, BoxFuture},
+  AsyncRead,
+  AsyncWrite,
+  FutureExt,
+  SinkExt,
+  StreamExt,
+};
+use std::{
+  net::SocketAddr,
+  sync::{Arc, Mutex},
+  time::Duration,
+};
 use tokio::{
-  net::TcpListener,
+  net::{TcpListener, TcpStream},
   sync::{
     mpsc::{Receiver, Sender},
     Notify,
@@ -31,12 +48,39 @@ use tokio::{
   time::sleep,
 };
 
+/// True if the TLS connection negotiated one of the Buttplug ALPN protocols. Used to reject
+/// generic HTTPS clients that connect by accident.
+///
+/// Only the OpenSSL backend of native-tls can pick a protocol for incoming connections, so on
+/// Windows and Apple platforms this is always false. See
+/// [ButtplugWebsocketServerTransportBuilder::allow_missing_alpn].
+#[cfg(not(feature = "rustls"))]
+fn has_buttplug_alpn_protocol(tls_stream: &tokio_native_tls::TlsStream<TcpStream>) -> bool {
+  let protocol = tls_stream.get_ref().negotiated_alpn().ok().flatten();
+  protocol.as_deref().is_some_and(is_buttplug_alpn_protocol)
+}
+
+/// True if the TLS connection negotiated one of the Buttplug ALPN protocols. Used to reject
+/// generic HTTPS clients that connect by accident.
+#[cfg(feature = "rustls")]
+fn has_buttplug_alpn_protocol(tls_stream: &tokio_rustls::server::TlsStream<TcpStream>) -> bool {
+  let protocol = tls_stream.get_ref().1.alpn_protocol();
+  protocol.is_some_and(is_buttplug_alpn_protocol)
+}
+
 #[derive(Clone, Debug)]
 pub struct ButtplugWebsocketServerTransportBuilder {
   /// If true, listens all on available interfaces. Otherwise, only listens on 127.0.0.1.
   listen_on_all_interfaces: bool,
   /// Insecure port for listening for websocket connections.
   port: u16,
+  /// Origins sent back in the Access-Control-Allow-Origin header of the upgrade response. If
+  /// empty, no CORS headers are sent.
+  cors_origins: Vec<String>,
+  /// If set, connections are served over TLS.
+  tls_config: Option<TlsConfig>,
+  /// If true, TLS clients that don't negotiate a Buttplug ALPN protocol are accepted.
+  allow_missing_alpn: bool,
 }
 
 impl Default for ButtplugWebsocketServerTransportBuilder {
@@ -44,6 +88,9 @@ impl Default for ButtplugWebsocketServerTransportBuilder {
     Self {
       listen_on_all_interfaces: false,
       port: 12345,
+      cors_origins: vec![],
+      tls_config: None,
+      allow_missing_alpn: false,
     }
   }
 }
@@ -59,15 +106,106 @@ impl ButtplugWebsocketServerTransportBuilder {
     self
   }
 
+  /// Add an Access-Control-Allow-Origin header to the websocket upgrade response for requests
+  /// coming from any of the given origins, for browser clients that require it. Use `"*"` to allow
+  /// any origin, though this lets any website a user visits connect to the server.
+  pub fn enable_cors(&mut self, origins: Vec<String>) -> &mut Self {
+    if origins.iter().any(|origin| origin == "*") {
+      warn!("Websocket server CORS allows any origin, any website can connect to this server.");
+    }
+    self.cors_origins = origins;
+    self
+  }
+
+  /// Serve connections over TLS (wss://) using the given certificate.
+  pub fn tls_config(&mut self, tls_config: TlsConfig) -> &mut Self {
+    self.tls_config = Some(tls_config);
+    self
+  }
+
+  /// Accept TLS clients that don't negotiate a Buttplug ALPN protocol, instead of turning them
+  /// away. The native-tls backend can't negotiate ALPN for incoming connections on Windows and
+  /// Apple platforms, so without this (or the `rustls` feature) no TLS client can connect there.
+  /// This also lets in generic HTTPS clients that connect by accident.
+  pub fn allow_missing_alpn(&mut self, allow_missing_alpn: bool) -> &mut Self {
+    self.allow_missing_alpn = allow_missing_alpn;
+    self
+  }
+
   pub fn finish(&self) -> ButtplugWebsocketServerTransport {
     ButtplugWebsocketServerTransport {
       port: self.port,
       listen_on_all_interfaces: self.listen_on_all_interfaces,
+      cors_origins: self.cors_origins.clone(),
+      tls_config: self.tls_config.clone(),
+      allow_missing_alpn: self.allow_missing_alpn,
       disconnect_notifier: Arc::new(Notify::new()),
+      peer_address: Arc::new(Mutex::new(None)),
     }
   }
 }
 
+/// Returns the Access-Control-Allow-Origin value to send for a request from `origin`, if it is
+/// allowed by `cors_origins`.
+fn cors_allowed_origin(cors_origins: &[String], origin: Option<&str>) -> Option<String> {
+  if cors_origins.iter().any(|allowed| allowed == "*") {
+    return Some("*".to_owned());
+  }
+  let origin = origin?;
+  cors_origins
+    .iter()
+    .find(|allowed| *allowed == origin)
+    .cloned()
+}
+
+/// Run the websocket handshake over an accepted connection, then hand it off to the connection
+/// loop.
+async fn accept_websocket<S>(
+  stream: S,
+  cors_origins: Vec<String>,
+  outgoing_receiver: Receiver<ButtplugSerializedMessage>,
+  response_sender: Sender<ButtplugTransportIncomingMessage>,
+  disconnect_notifier: Arc<Notify>,
+) -> Result<(), ButtplugConnectorError>
+where
+  S: tokio::io::AsyncRead + tokio::io::AsyncWrite + Unpin + Send + 'static,
+{
+  // The error type is set by tungstenite's handshake callback signature.
+  #[allow(clippy::result_large_err)]
+  let add_cors_header = move |request: &Request, mut response: Response| {
+    let origin = request
+      .headers()
+      .get(header::ORIGIN)
+      .and_then(|origin| origin.to_str().ok());
+    if let Some(allowed_origin) = cors_allowed_origin(&cors_origins, origin) {
+      if let Ok(value) = HeaderValue::from_str(&allowed_origin) {
+        response
+          .headers_mut()
+          .insert(header::ACCESS_CONTROL_ALLOW_ORIGIN, value);
+      }
+    }
+    Ok(response)
+  };
+  let ws_stream = async_tungstenite::tokio::accept_hdr_async(stream, add_cors_header)
+    .await
+    .map_err(|err| {
+      error!("Websocket server accept error: {:?}", err);
+      ButtplugConnectorError::TransportSpecificError(
+        ButtplugConnectorTransportSpecificError::TungsteniteError(err),
+      )
+    })?;
+  async_manager::spawn(async move {
+    run_connection_loop(
+      ws_stream,
+      outgoing_receiver,
+      response_sender,
+      disconnect_notifier,
+    )
+    .await;
+  });
+  Ok(())
+}
+
 async fn run_connection_loop<S>(
   ws_stream: async_tungstenite::WebSocketStream<S>,
   mut request_receiver: Receiver<ButtplugSerializedMessage>,
@@ -176,8 +314,12 @@ async fn run_connection_loop<S>(
                   pong_count += 1;
                   continue;
                 }
-                async_tungstenite::tungstenite::Message::Binary(_) => {
-                  error!("Don't know how to handle binary message types!");
+                async_tungstenite::tungstenite::Message::Binary(binary_msg) => {
+                  // Only sent by clients that negotiated compression.
+                  if response_sender.send(ButtplugTransportIncomingMessage::Message(ButtplugSerializedMessage::Binary(binary_msg))).await.is_err() {
+                    warn!("Connector that owns transport no longer available, exiting.");
+                    break;
+                  }
                 }
               }
             },
@@ -201,16 +343,37 @@ async fn run_connection_loop<S>(
 pub struct ButtplugWebsocketServerTransport {
   port: u16,
   listen_on_all_interfaces: bool,
+  cors_origins: Vec<String>,
+  tls_config: Option<TlsConfig>,
+  allow_missing_alpn: bool,
   disconnect_notifier: Arc<Notify>,
+  /// Address of the client that connected, if any.
+  peer_address: Arc<Mutex<Option<SocketAddr>>>,
 }
 
 impl ButtplugConnectorTransport for ButtplugWebsocketServerTransport {
+  fn set_tls_config(&mut self, tls_config: TlsConfig) {
+    self.tls_config = Some(tls_config);
+  }
+
   fn connect(
     &self,
     outgoing_receiver: Receiver<ButtplugSerializedMessage>,
     incoming_sender: Sender<ButtplugTransportIncomingMessage>,
   ) -> BoxFuture<'static, Result<(), ButtplugConnectorError>> {
     let disconnect_notifier = self.disconnect_notifier.clone();
+    // Load certificates up front, so bad TLS settings fail before we start listening.
+    let tls_acceptor = match self.tls_config.as_ref().map(TlsConfig::tls_acceptor) {
+      Some(Ok(acceptor)) => Some(acceptor),
+      Some(Err(err)) => {
+        return future::ready(Err(ButtplugConnectorError::ConnectorGenericError(err))).boxed()
+      }
+      None => None,
+    };
+    let allow_missing_alpn = self.allow_missing_alpn;
+    if tls_acceptor.is_some() && allow_missing_alpn {
+      warn!("Websocket server accepts TLS clients without a Buttplug ALPN protocol.");
+    }
 
     let base_addr = if self.listen_on_all_interfaces {
       "0.0.0.0"
@@ -222,35 +385,54 @@ impl ButtplugConnectorTransport for ButtplugWebsocketServerTransport {
     debug!("Websocket: Trying to listen on {}", addr);
     let response_sender_clone = incoming_sender;
     let disconnect_notifier_clone = disconnect_notifier;
+    let cors_origins = self.cors_origins.clone();
+    let peer_address = self.peer_address.clone();
     let fut = async move {
       // Create the event loop and TCP listener we'll accept connections on.
       let try_socket = TcpListener::bind(&addr).await;
       debug!("Websocket: Socket bound.");
       let listener = try_socket.map_err(|e| {
         ButtplugConnectorError::TransportSpecificError(
-          ButtplugConnectorTransportSpecificError::GenericNetworkError(format!("{:?}", e)),
+          ButtplugConnectorTransportSpecificError::IoError(e),
         )
       })?;
       debug!("Websocket: Listening on: {}", addr);
-      if let Ok((stream, _)) = listener.accept().await {
-        info!("Websocket: Got connection");
-        let ws_fut = async_tungstenite::tokio::accept_async(stream);
-        let ws_stream = ws_fut.await.map_err(|err| {
-          error!("Websocket server accept error: {:?}", err);
-          ButtplugConnectorError::TransportSpecificError(
-            ButtplugConnectorTransportSpecificError::TungsteniteError(err),
+      if let Ok((stream, client_address)) = listener.accept().await {
+        info!("Websocket: Got connection from {}", client_address);
+        *peer_address.lock().expect("Lock poisoned") = Some(client_address);
+        if let Some(tls_acceptor) = tls_acceptor {
+          let tls_stream = tls_acceptor.accept(stream).await.map_err(|err| {
+            error!("Websocket server TLS accept error: {:?}", err);
+            ButtplugConnectorError::TransportSpecificError(
+              ButtplugConnectorTransportSpecificError::GenericNetworkError(err.to_string()),
+            )
+          })?;
+          if !allow_missing_alpn && !has_buttplug_alpn_protocol(&tls_stream) {
+            error!("Websocket server TLS client did not negotiate a Buttplug ALPN protocol.");
+            return Err(ButtplugConnectorError::TransportSpecificError(
+              ButtplugConnectorTransportSpecificError::GenericNetworkError(
+                "Client did not negotiate a Buttplug ALPN protocol".to_owned(),
+              ),
+            ));
+          }
+          accept_websocket(
+            tls_stream,
+            cors_origins,
+            outgoing_receiver,
+            response_sender_clone,
+            disconnect_notifier_clone,
           )
-        })?;
-        async_manager::spawn(async move {
-          run_connection_loop(
-            ws_stream,
+          .await
+        } else {
+          accept_websocket(
+            stream,
+            cors_origins,
             outgoing_receiver,
             response_sender_clone,
             disconnect_notifier_clone,
           )
-          .await;
-        });
-        Ok(())
+          .await
+        }
       } else {
         Err(ButtplugConnectorError::ConnectorGenericError(
           "Could not run accept for port".to_owned(),
@@ -269,4 +451,29 @@ impl ButtplugConnectorTransport for ButtplugWebsocketServerTransport {
     }
     .boxed()
   }
+
+  fn peer_address(&self) -> Option<SocketAddr> {
+    *self.peer_address.lock().expect("Lock poisoned")
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+
+  #[test]
+  fn test_cors_allowed_origin() {
+    let origins = vec!["https://example.com".to_owned()];
+    assert_eq!(
+      cors_allowed_origin(&origins, Some("https://example.com")),
+      Some("https://example.com".to_owned())
+    );
+    assert!(cors_allowed_origin(&origins, Some("https://other.com")).is_none());
+    assert!(cors_allowed_origin(&origins, None).is_none());
+    assert!(cors_allowed_origin(&[], Some("https://example.com")).is_none());
+    assert_eq!(
+      cors_allowed_origin(&["*".to_owned()], Some("https://other.com")),
+      Some("*".to_owned())
+    );
+  }
 }
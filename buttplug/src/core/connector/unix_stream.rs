@@ -0,0 +1,96 @@
+// Buttplug Rust Source Code File - See https://buttplug.io for more info.
+//
+// Copyright 2016-2023 Nonpolynomial Labs LLC. All rights reserved.
+//
+// Licensed under the BSD 3-Clause license. See LICENSE file in the project root
+// for full license information.
+
+//! [ButtplugConnector] implementation for unix sockets handed to the application already
+//! connected, like ones passed in by systemd socket activation.
+
+use super::{
+  transport::{ButtplugStreamTransport, NewlineFramer},
+  ButtplugConnector,
+  ButtplugConnectorError,
+  ButtplugConnectorResultFuture,
+  ButtplugRemoteServerConnector,
+  ConnectionMetrics,
+};
+use crate::core::message::{
+  serializer::{ButtplugServerJSONSerializer, CodecType},
+  ButtplugClientMessage,
+  ButtplugServerMessage,
+  MessageTransformer,
+};
+use futures::future::BoxFuture;
+use std::{sync::Arc, time::Duration};
+use tokio::{net::UnixStream, sync::mpsc::Sender};
+
+/// Server side connector over a connected unix socket, using newline delimited JSON.
+///
+/// Build one from a stream obtained elsewhere, e.g. from systemd socket activation, with
+/// `ButtplugUnixStreamServerConnector::from(stream)`.
+pub struct ButtplugUnixStreamServerConnector {
+  connector: ButtplugRemoteServerConnector<
+    ButtplugStreamTransport<UnixStream, NewlineFramer>,
+    ButtplugServerJSONSerializer,
+  >,
+}
+
+impl From<UnixStream> for ButtplugUnixStreamServerConnector {
+  fn from(stream: UnixStream) -> Self {
+    Self {
+      connector: ButtplugRemoteServerConnector::new(ButtplugStreamTransport::new(
+        stream,
+        NewlineFramer,
+      )),
+    }
+  }
+}
+
+impl ButtplugConnector<ButtplugServerMessage, ButtplugClientMessage>
+  for ButtplugUnixStreamServerConnector
+{
+  fn connect(
+    &mut self,
+    message_receiver: Sender<ButtplugClientMessage>,
+  ) -> BoxFuture<'static, Result<(), ButtplugConnectorError>> {
+    self.connector.connect(message_receiver)
+  }
+
+  fn disconnect(&self) -> ButtplugConnectorResultFuture {
+    self.connector.disconnect()
+  }
+
+  fn send(&self, msg: ButtplugServerMessage) -> ButtplugConnectorResultFuture {
+    self.connector.send(msg)
+  }
+
+  fn set_pretty_print_messages(&mut self, pretty_print: bool) {
+    self.connector.set_pretty_print_messages(pretty_print);
+  }
+
+  fn set_message_transformers(&mut self, transformers: Vec<Arc<dyn MessageTransformer>>) {
+    self.connector.set_message_transformers(transformers);
+  }
+
+  fn codec_type(&self) -> CodecType {
+    CodecType::Json
+  }
+
+  fn max_message_size(&self) -> Option<usize> {
+    self.connector.max_message_size()
+  }
+
+  fn check_health(&self) -> ButtplugConnectorResultFuture {
+    self.connector.check_health()
+  }
+
+  fn set_timeout(&self, timeout: Duration) -> Result<(), ButtplugConnectorError> {
+    self.connector.set_timeout(timeout)
+  }
+
+  fn connection_metrics(&self) -> Option<ConnectionMetrics> {
+    self.connector.connection_metrics()
+  }
+}
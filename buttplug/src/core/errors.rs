@@ -7,9 +7,10 @@
 
 //! Buttplug Error Structs/Enums, representing protocol errors.
 
+#[cfg(feature = "std")]
+use super::message::serializer::ButtplugSerializerError;
 use super::message::{
   self,
-  serializer::ButtplugSerializerError,
   ActuatorType,
   ButtplugDeviceMessageType,
   ButtplugMessageSpecVersion,
@@ -17,11 +18,18 @@ use super::message::{
   ErrorCode,
   SensorType,
 };
+use super::ButtplugString;
 #[cfg(feature = "server")]
 use crate::server::device::hardware::communication::HardwareSpecificError;
+#[cfg(not(feature = "std"))]
+use alloc::{boxed::Box, vec::Vec};
+use core::time::Duration;
 use displaydoc::Display;
+#[cfg(feature = "std")]
 use futures::future::BoxFuture;
+#[cfg(feature = "serialize")]
 use serde::{Deserialize, Serialize};
+#[cfg(feature = "std")]
 use thiserror::Error;
 
 pub type ButtplugResult<T = ()> = Result<T, ButtplugError>;
@@ -30,6 +38,7 @@ pub type ButtplugResult<T = ()> = Result<T, ButtplugError>;
 /// usually involves protocol handshake errors. For connector errors (i.e. when
 /// a remote network connection cannot be established), see
 /// [crate::connector::ButtplugConnectorError].
+#[cfg(feature = "std")]
 impl<T> From<ButtplugHandshakeError> for BoxFuture<'static, Result<T, ButtplugError>>
 where
   T: Send + 'static,
@@ -39,11 +48,12 @@ where
   }
 }
 
-#[derive(Debug, Error, Display, Clone, PartialEq, Eq)]
-#[cfg_attr(feature = "serialize-json", derive(Serialize, Deserialize))]
+#[derive(Debug, Display, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "std", derive(Error))]
+#[cfg_attr(feature = "serialize", derive(Serialize, Deserialize))]
 pub enum ButtplugHandshakeError {
   /// Expected either a ServerInfo or Error message, received {0}
-  UnexpectedHandshakeMessageReceived(String),
+  UnexpectedHandshakeMessageReceived(ButtplugString),
   /// Expected a RequestServerInfo message to start connection. Message either not received or wrong message received.
   RequestServerInfoExpected,
   /// Handshake already happened, cannot run handshake again.
@@ -51,11 +61,12 @@ pub enum ButtplugHandshakeError {
   /// Server spec version ({0}) must be equal or greater than client version ({1})
   MessageSpecVersionMismatch(ButtplugMessageSpecVersion, ButtplugMessageSpecVersion),
   /// Untyped Deserialized Error: {0}
-  UntypedDeserializedError(String),
+  UntypedDeserializedError(ButtplugString),
 }
 
 /// Message errors occur when a message is somehow malformed on creation, or
 /// received unexpectedly by a client or server.
+#[cfg(feature = "std")]
 impl<T> From<ButtplugMessageError> for BoxFuture<'static, Result<T, ButtplugError>>
 where
   T: Send + 'static,
@@ -65,31 +76,36 @@ where
   }
 }
 
-#[derive(Debug, Error, Display, Clone, PartialEq, Eq)]
-#[cfg_attr(feature = "serialize-json", derive(Serialize, Deserialize))]
+#[derive(Debug, Display, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "std", derive(Error))]
+#[cfg_attr(feature = "serialize", derive(Serialize, Deserialize))]
 pub enum ButtplugMessageError {
   /// Got unexpected message type: {0}
-  UnexpectedMessageType(String),
+  UnexpectedMessageType(ButtplugString),
   /// {0} {1} cannot be converted to {2}
-  VersionError(String, String, String),
+  VersionError(ButtplugString, ButtplugString, ButtplugString),
   /// Message conversion error: {0}
-  MessageConversionError(String),
+  MessageConversionError(ButtplugString),
   /// Invalid message contents: {0}
-  InvalidMessageContents(String),
+  InvalidMessageContents(ButtplugString),
   /// Unhandled message type: {0}
-  UnhandledMessage(String),
+  UnhandledMessage(ButtplugString),
   /// Message validation error(s): {0}
-  ValidationError(String),
+  ValidationError(ButtplugString),
   /// Message serialization error
+  #[cfg(feature = "std")]
   #[error(transparent)]
   MessageSerializationError(#[from] ButtplugSerializerError),
+  /// Client message rate limit exceeded, message dropped.
+  RateLimitExceeded,
   /// Untyped Deserialized Error: {0}
-  UntypedDeserializedError(String),
+  UntypedDeserializedError(ButtplugString),
 }
 
 /// Ping errors occur when a server requires a ping response (set up during
 /// connection handshake), and the client does not return a response in the
 /// alloted timeframe. This also signifies a server disconnect.
+#[cfg(feature = "std")]
 impl<T> From<ButtplugPingError> for BoxFuture<'static, Result<T, ButtplugError>>
 where
   T: Send + 'static,
@@ -99,8 +115,9 @@ where
   }
 }
 
-#[derive(Debug, Error, Display, Clone, PartialEq, Eq)]
-#[cfg_attr(feature = "serialize-json", derive(Serialize, Deserialize))]
+#[derive(Debug, Display, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "std", derive(Error))]
+#[cfg_attr(feature = "serialize", derive(Serialize, Deserialize))]
 pub enum ButtplugPingError {
   /// Pinged timer exhausted, system has shut down.
   PingedOut,
@@ -109,12 +126,13 @@ pub enum ButtplugPingError {
   /// Ping time must be greater than 0.
   InvalidPingTimeout,
   /// Untyped Deserialized Error: {0}
-  UntypedDeserializedError(String),
+  UntypedDeserializedError(ButtplugString),
 }
 
 /// Device errors occur during device interactions, including sending
 /// unsupported message commands, addressing the wrong number of device
 /// attributes, etc...
+#[cfg(feature = "std")]
 impl<T> From<ButtplugDeviceError> for BoxFuture<'static, Result<T, ButtplugError>>
 where
   T: Send + 'static,
@@ -123,11 +141,12 @@ where
     ButtplugError::from(err).into()
   }
 }
-#[derive(Debug, Error, Display, Clone, PartialEq, Eq)]
-#[cfg_attr(feature = "serialize-json", derive(Serialize, Deserialize))]
+#[derive(Debug, Display, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "std", derive(Error))]
+#[cfg_attr(feature = "serialize", derive(Serialize, Deserialize))]
 pub enum ButtplugDeviceError {
   /// Device {0} not connected
-  DeviceNotConnected(String),
+  DeviceNotConnected(ButtplugString),
   /// Device does not support message type {0}.
   MessageNotSupported(ButtplugDeviceMessageType),
   /// Device only has {0} features, but {1} commands were sent.
@@ -137,52 +156,65 @@ pub enum ButtplugDeviceError {
   /// Device only has {0} sensors, but was given an index of {1}
   DeviceSensorIndexError(u32, u32),
   /// Device connection error: {0}
-  DeviceConnectionError(String),
+  DeviceConnectionError(ButtplugString),
   /// Device communication error: {0}
-  DeviceCommunicationError(String),
+  DeviceCommunicationError(ButtplugString),
   /// Device does not have endpoint {0}
   InvalidEndpoint(Endpoint),
   /// Device does not handle command type: {0}
-  UnhandledCommand(String),
+  UnhandledCommand(ButtplugString),
   #[cfg(feature = "server")]
   #[error(transparent)]
   /// Device type specific error: {0}.
   DeviceSpecificError(#[from] HardwareSpecificError),
   #[cfg(not(feature = "server"))]
   /// Device type specific error: {0}.
-  DeviceSpecificError(String),
+  DeviceSpecificError(ButtplugString),
   /// No device available at index {0}
   DeviceNotAvailable(u32),
+  /// Device {0} is disabled
+  DeviceDisabled(u32),
   /// Device scanning already started.
   DeviceScanningAlreadyStarted,
   /// Device scanning already stopped.
   DeviceScanningAlreadyStopped,
   /// Device permission error: {0}
-  DevicePermissionError(String),
+  DevicePermissionError(ButtplugString),
   /// {0}
-  ProtocolAttributesNotFound(String),
+  ProtocolAttributesNotFound(ButtplugString),
   /// Protocol {0} not implemented in library
-  ProtocolNotImplemented(String),
+  ProtocolNotImplemented(ButtplugString),
   /// {0} protocol specific error: {1}
-  ProtocolSpecificError(String, String),
+  ProtocolSpecificError(ButtplugString, ButtplugString),
   /// {0}
-  ProtocolRequirementError(String),
+  ProtocolRequirementError(ButtplugString),
   /// Protocol already added to system {0},
-  ProtocolAlreadyAdded(String),
+  ProtocolAlreadyAdded(ButtplugString),
   /// Untyped Deserialized Error: {0}
-  UntypedDeserializedError(String),
+  UntypedDeserializedError(ButtplugString),
   /// Device Configuration Error: {0}
-  DeviceConfigurationError(String),
+  DeviceConfigurationError(ButtplugString),
   /// Actuator Type Mismatch: Index {0} got command for {1}, but expects {2}
-  DeviceActuatorTypeMismatch(String, ActuatorType, ActuatorType),
+  DeviceActuatorTypeMismatch(ButtplugString, ActuatorType, ActuatorType),
   /// Sensor Type Mismatch: Index {0} got command for {1}, but expects {2}
   DeviceSensorTypeMismatch(u32, SensorType, SensorType),
   /// Protocol does not have an implementation available for Sensor Type {0}
   ProtocolSensorNotSupported(SensorType),
+  /// Raw stream expected chunk {0}, but got chunk {1}
+  DeviceRawStreamChunkOutOfOrder(u32, u32),
+  /// Raw stream is larger than the {0} byte limit
+  DeviceRawStreamTooLarge(usize),
+  /// Device index {0} is already taken by device {1}
+  DeviceIndexConflict(u32, ButtplugString),
+  /// Command was flushed from the device's queue before it was sent
+  DeviceCommandFlushed,
+  /// Devices {0:?} did not stop in time
+  DevicesStopTimedOut(Vec<u32>),
 }
 
 /// Unknown errors occur in exceptional circumstances where no other error type
 /// will suffice. These are rare and usually fatal (disconnecting) errors.
+#[cfg(feature = "std")]
 impl<T> From<ButtplugUnknownError> for BoxFuture<'static, Result<T, ButtplugError>>
 where
   T: Send + 'static,
@@ -192,35 +224,176 @@ where
   }
 }
 
-#[derive(Debug, Error, Display, Clone, PartialEq, Eq)]
-#[cfg_attr(feature = "serialize-json", derive(Serialize, Deserialize))]
+#[derive(Debug, Display, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "std", derive(Error))]
+#[cfg_attr(feature = "serialize", derive(Serialize, Deserialize))]
 pub enum ButtplugUnknownError {
   /// Cannot start scanning, no device communication managers available to use for scanning.
   NoDeviceCommManagers,
   /// Got unexpected enum type: {0}
-  UnexpectedType(String),
+  UnexpectedType(ButtplugString),
   /// Untyped Deserialized Error: {0}
-  UntypedDeserializedError(String),
+  UntypedDeserializedError(ButtplugString),
   /// Device Manager has been shut down by its owning server and is no longer available.
   DeviceManagerNotRunning,
+  /// Server did not finish shutting down within {0:?}.
+  ShutdownTimedOut(Duration),
+  /// Devices did not all stop within {0:?}.
+  StopDevicesTimedOut(Duration),
+  /// Server still had commands in progress after {0:?}.
+  IdleTimedOut(Duration),
+  /// Client did not reconnect within {0:?}.
+  ReconnectTimedOut(Duration),
+  /// Server did not reply within {0:?}.
+  ReplyTimedOut(Duration),
+  /// Message id {0} was reused by a new message before the server replied to the old one.
+  MessageIdReused(u32),
+  /// Connector disconnected before the server replied.
+  ConnectorDisconnected,
+  /// Message handling did not finish within {0:?}, and was abandoned.
+  TaskWatchdogTriggered(Duration),
+  /// No one is listening for remote server events.
+  NoEventListeners,
+  /// Cannot read session transcript: {0}
+  TranscriptReadError(ButtplugString),
+  /// Connector failed its health check: {0}
+  ConnectorUnhealthy(ButtplugString),
+  /// Cannot change connector timeout: {0}
+  ConnectorTimeoutNotSet(ButtplugString),
+  /// Server event stream fell behind, {0} events were missed.
+  EventsLagged(u64),
 }
 
 /// Aggregation enum for protocol error types.
-#[derive(Debug, Error, Clone, PartialEq, Eq)]
-#[cfg_attr(feature = "serialize-json", derive(Serialize, Deserialize))]
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "std", derive(Error))]
+#[cfg_attr(feature = "serialize", derive(Serialize, Deserialize))]
 pub enum ButtplugError {
-  #[error(transparent)]
-  ButtplugHandshakeError(#[from] ButtplugHandshakeError),
-  #[error(transparent)]
-  ButtplugMessageError(#[from] ButtplugMessageError),
-  #[error(transparent)]
-  ButtplugPingError(#[from] ButtplugPingError),
-  #[error(transparent)]
-  ButtplugDeviceError(#[from] ButtplugDeviceError),
-  #[error(transparent)]
-  ButtplugUnknownError(#[from] ButtplugUnknownError),
+  #[cfg_attr(feature = "std", error(transparent))]
+  ButtplugHandshakeError(#[cfg_attr(feature = "std", from)] ButtplugHandshakeError),
+  #[cfg_attr(feature = "std", error(transparent))]
+  ButtplugMessageError(#[cfg_attr(feature = "std", from)] ButtplugMessageError),
+  #[cfg_attr(feature = "std", error(transparent))]
+  ButtplugPingError(#[cfg_attr(feature = "std", from)] ButtplugPingError),
+  #[cfg_attr(feature = "std", error(transparent))]
+  ButtplugDeviceError(#[cfg_attr(feature = "std", from)] ButtplugDeviceError),
+  #[cfg_attr(feature = "std", error(transparent))]
+  ButtplugUnknownError(#[cfg_attr(feature = "std", from)] ButtplugUnknownError),
+  /// Error with extra information added by a layer it passed through, see
+  /// [ButtplugError::chain_context].
+  #[cfg_attr(feature = "std", error("{context}: {cause}"))]
+  ContextualError {
+    context: ButtplugString,
+    cause: Box<ButtplugError>,
+  },
 }
 
+impl ButtplugError {
+  /// Wrap the error with `context` describing what was being done when it happened. The original
+  /// error is kept as the cause, and both are shown when the error is displayed.
+  pub fn chain_context(self, context: impl core::fmt::Display) -> ButtplugError {
+    ButtplugError::ContextualError {
+      context: format_buttplug_string!("{}", context),
+      cause: Box::new(self),
+    }
+  }
+
+  /// Returns a suggestion for how to fix the error, for errors where there's a likely fix.
+  pub fn hint(&self) -> Option<&'static str> {
+    match self {
+      ButtplugError::ButtplugHandshakeError(
+        ButtplugHandshakeError::MessageSpecVersionMismatch(..),
+      ) => Some("Update the server to a version that supports the client's message spec"),
+      ButtplugError::ButtplugHandshakeError(ButtplugHandshakeError::RequestServerInfoExpected) => {
+        Some("Send RequestServerInfo as the first message after connecting")
+      }
+      ButtplugError::ButtplugMessageError(ButtplugMessageError::RateLimitExceeded) => {
+        Some("Send messages less often, or raise the server's client rate limit")
+      }
+      ButtplugError::ButtplugPingError(ButtplugPingError::PingedOut) => {
+        Some("Send Ping messages more often than the server's max ping time")
+      }
+      ButtplugError::ButtplugDeviceError(err) => match err {
+        ButtplugDeviceError::DeviceNotAvailable(_) => {
+          Some("Ensure your device is powered on and in range, then scan for devices again")
+        }
+        ButtplugDeviceError::DeviceDisabled(_) => {
+          Some("Ask the server operator to enable the device again")
+        }
+        ButtplugDeviceError::DeviceNotConnected(_) => {
+          Some("Ensure your device is powered on and in range")
+        }
+        ButtplugDeviceError::MessageNotSupported(_) => {
+          Some("Check the device's message attributes for the commands it supports")
+        }
+        ButtplugDeviceError::DeviceFeatureIndexError(..)
+        | ButtplugDeviceError::DeviceFeatureCountMismatch(..)
+        | ButtplugDeviceError::DeviceSensorIndexError(..) => {
+          Some("Check the device's message attributes for its feature and sensor counts")
+        }
+        ButtplugDeviceError::DevicePermissionError(_) => {
+          Some("Check that this program has permission to access Bluetooth/USB/serial devices")
+        }
+        ButtplugDeviceError::DeviceScanningAlreadyStarted => {
+          Some("Stop scanning before starting it again")
+        }
+        ButtplugDeviceError::DeviceScanningAlreadyStopped => {
+          Some("Start scanning before stopping it")
+        }
+        ButtplugDeviceError::DeviceIndexConflict(..) => {
+          Some("Pick another index, or clear the override for the device holding this one")
+        }
+        _ => None,
+      },
+      ButtplugError::ButtplugUnknownError(ButtplugUnknownError::NoDeviceCommManagers) => {
+        Some("Add at least one device communication manager to the server")
+      }
+      ButtplugError::ButtplugUnknownError(ButtplugUnknownError::DeviceManagerNotRunning) => {
+        Some("Create a new server, this one has been shut down")
+      }
+      ButtplugError::ContextualError { cause, .. } => cause.hint(),
+      _ => None,
+    }
+  }
+}
+
+// Without std there's no thiserror, so the Display and From impls it generates are written out.
+#[cfg(not(feature = "std"))]
+impl core::fmt::Display for ButtplugError {
+  fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+    match self {
+      ButtplugError::ButtplugHandshakeError(err) => err.fmt(f),
+      ButtplugError::ButtplugMessageError(err) => err.fmt(f),
+      ButtplugError::ButtplugPingError(err) => err.fmt(f),
+      ButtplugError::ButtplugDeviceError(err) => err.fmt(f),
+      ButtplugError::ButtplugUnknownError(err) => err.fmt(f),
+      ButtplugError::ContextualError { context, cause } => write!(f, "{}: {}", context, cause),
+    }
+  }
+}
+
+#[cfg(not(feature = "std"))]
+macro_rules! impl_from_error {
+  ($($error:ident),*) => {
+    $(
+      impl From<$error> for ButtplugError {
+        fn from(err: $error) -> Self {
+          ButtplugError::$error(err)
+        }
+      }
+    )*
+  };
+}
+
+#[cfg(not(feature = "std"))]
+impl_from_error!(
+  ButtplugHandshakeError,
+  ButtplugMessageError,
+  ButtplugPingError,
+  ButtplugDeviceError,
+  ButtplugUnknownError
+);
+
 impl From<message::Error> for ButtplugError {
   /// Turns a Buttplug Protocol Error Message [super::messages::Error] into a [ButtplugError] type.
   fn from(error: message::Error) -> Self {
@@ -243,3 +416,44 @@ impl From<message::Error> for ButtplugError {
     }
   }
 }
+
+#[cfg(test)]
+mod test {
+  use super::*;
+
+  #[test]
+  fn test_error_hint() {
+    assert!(
+      ButtplugError::from(ButtplugDeviceError::DeviceNotAvailable(0))
+        .hint()
+        .is_some()
+    );
+    assert!(ButtplugError::from(ButtplugPingError::PingedOut)
+      .hint()
+      .is_some());
+    assert!(
+      ButtplugError::from(ButtplugMessageError::UnhandledMessage("Test".to_owned()))
+        .hint()
+        .is_none()
+    );
+  }
+
+  #[test]
+  fn test_error_chain_context() {
+    let err = ButtplugError::from(ButtplugPingError::PingedOut)
+      .chain_context("Sending vibrate command")
+      .chain_context("Running pattern");
+    assert_eq!(
+      err.to_string(),
+      format!(
+        "Running pattern: Sending vibrate command: {}",
+        ButtplugPingError::PingedOut
+      )
+    );
+    assert_eq!(
+      err.hint(),
+      ButtplugError::from(ButtplugPingError::PingedOut).hint()
+    );
+    assert_eq!(message::Error::from(err).error_code(), ErrorCode::ErrorPing);
+  }
+}
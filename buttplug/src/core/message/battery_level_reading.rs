@@ -7,18 +7,18 @@
 
 use super::*;
 use getset::CopyGetters;
-#[cfg(feature = "serialize-json")]
+#[cfg(feature = "serialize")]
 use serde::{Deserialize, Serialize};
 
 /// Battery level response
 #[derive(Debug, ButtplugDeviceMessage, ButtplugMessageFinalizer, PartialEq, Clone, CopyGetters)]
-#[cfg_attr(feature = "serialize-json", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serialize", derive(Serialize, Deserialize))]
 pub struct BatteryLevelReading {
-  #[cfg_attr(feature = "serialize-json", serde(rename = "Id"))]
+  #[cfg_attr(feature = "serialize", serde(rename = "Id"))]
   id: u32,
-  #[cfg_attr(feature = "serialize-json", serde(rename = "DeviceIndex"))]
+  #[cfg_attr(feature = "serialize", serde(rename = "DeviceIndex"))]
   device_index: u32,
-  #[cfg_attr(feature = "serialize-json", serde(rename = "BatteryLevel"))]
+  #[cfg_attr(feature = "serialize", serde(rename = "BatteryLevel"))]
   #[getset(get_copy = "pub")]
   battery_level: f64,
 }
@@ -38,7 +38,7 @@ impl ButtplugMessageValidator for BatteryLevelReading {
     self.is_not_system_id(self.id)?;
     self.is_in_command_range(
       self.battery_level,
-      "BatteryLevelReading must be between 0.0 and 1.0".to_string(),
+      buttplug_string("BatteryLevelReading must be between 0.0 and 1.0"),
     )
   }
 }
@@ -6,14 +6,20 @@
 // for full license information.
 
 use crate::core::{
+  buttplug_string,
   errors::ButtplugDeviceError,
   message::{ButtplugDeviceMessageType, Endpoint},
+  ButtplugString,
 };
+#[cfg(not(feature = "std"))]
+use alloc::{vec, vec::Vec};
+use core::ops::RangeInclusive;
 use getset::{Getters, MutGetters, Setters};
+#[cfg(feature = "serialize")]
 use serde::{ser::SerializeSeq, Deserialize, Serialize, Serializer};
-use std::ops::RangeInclusive;
 
-#[derive(Debug, Display, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[derive(Debug, Display, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serialize", derive(Serialize, Deserialize))]
 pub enum ActuatorType {
   Unknown,
   Vibrate,
@@ -27,7 +33,8 @@ pub enum ActuatorType {
   Position,
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Display)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Display)]
+#[cfg_attr(feature = "serialize", derive(Serialize, Deserialize))]
 pub enum SensorType {
   Unknown,
   Battery,
@@ -48,65 +55,65 @@ pub enum SensorType {
 // then we denote this by prefixing the type with Client/Server. Server attributes will usually be
 // hosted in the server/device/configuration module.
 #[derive(Clone, Debug, Default, PartialEq, Eq, Getters, MutGetters, Setters)]
-#[cfg_attr(feature = "serialize-json", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serialize", derive(Serialize, Deserialize))]
 pub struct ClientDeviceMessageAttributes {
   // Generic commands
   #[getset(get = "pub", get_mut = "pub(super)")]
-  #[serde(rename = "ScalarCmd")]
-  #[serde(skip_serializing_if = "Option::is_none")]
+  #[cfg_attr(feature = "serialize", serde(rename = "ScalarCmd"))]
+  #[cfg_attr(feature = "serialize", serde(skip_serializing_if = "Option::is_none"))]
   scalar_cmd: Option<Vec<ClientGenericDeviceMessageAttributes>>,
   #[getset(get = "pub", get_mut = "pub(super)")]
-  #[serde(rename = "RotateCmd")]
-  #[serde(skip_serializing_if = "Option::is_none")]
+  #[cfg_attr(feature = "serialize", serde(rename = "RotateCmd"))]
+  #[cfg_attr(feature = "serialize", serde(skip_serializing_if = "Option::is_none"))]
   rotate_cmd: Option<Vec<ClientGenericDeviceMessageAttributes>>,
   #[getset(get = "pub", get_mut = "pub(super)")]
-  #[serde(rename = "LinearCmd")]
-  #[serde(skip_serializing_if = "Option::is_none")]
+  #[cfg_attr(feature = "serialize", serde(rename = "LinearCmd"))]
+  #[cfg_attr(feature = "serialize", serde(skip_serializing_if = "Option::is_none"))]
   linear_cmd: Option<Vec<ClientGenericDeviceMessageAttributes>>,
 
   // Sensor Messages
   #[getset(get = "pub")]
-  #[serde(rename = "SensorReadCmd")]
-  #[serde(skip_serializing_if = "Option::is_none")]
+  #[cfg_attr(feature = "serialize", serde(rename = "SensorReadCmd"))]
+  #[cfg_attr(feature = "serialize", serde(skip_serializing_if = "Option::is_none"))]
   sensor_read_cmd: Option<Vec<SensorDeviceMessageAttributes>>,
   #[getset(get = "pub")]
-  #[serde(rename = "SensorSubscribeCmd")]
-  #[serde(skip_serializing_if = "Option::is_none")]
+  #[cfg_attr(feature = "serialize", serde(rename = "SensorSubscribeCmd"))]
+  #[cfg_attr(feature = "serialize", serde(skip_serializing_if = "Option::is_none"))]
   sensor_subscribe_cmd: Option<Vec<SensorDeviceMessageAttributes>>,
 
   // StopDeviceCmd always exists
   #[getset(get = "pub")]
-  #[serde(rename = "StopDeviceCmd")]
-  #[serde(skip_deserializing)]
+  #[cfg_attr(feature = "serialize", serde(rename = "StopDeviceCmd"))]
+  #[cfg_attr(feature = "serialize", serde(skip_deserializing))]
   stop_device_cmd: NullDeviceMessageAttributes,
 
   // Raw commands are only added post-serialization
   #[getset(get = "pub")]
-  #[serde(rename = "RawReadCmd")]
-  #[serde(skip_deserializing)]
-  #[serde(skip_serializing_if = "Option::is_none")]
+  #[cfg_attr(feature = "serialize", serde(rename = "RawReadCmd"))]
+  #[cfg_attr(feature = "serialize", serde(skip_deserializing))]
+  #[cfg_attr(feature = "serialize", serde(skip_serializing_if = "Option::is_none"))]
   raw_read_cmd: Option<RawDeviceMessageAttributes>,
   // Raw commands are only added post-serialization
   #[getset(get = "pub")]
-  #[serde(rename = "RawWriteCmd")]
-  #[serde(skip_deserializing)]
-  #[serde(skip_serializing_if = "Option::is_none")]
+  #[cfg_attr(feature = "serialize", serde(rename = "RawWriteCmd"))]
+  #[cfg_attr(feature = "serialize", serde(skip_deserializing))]
+  #[cfg_attr(feature = "serialize", serde(skip_serializing_if = "Option::is_none"))]
   raw_write_cmd: Option<RawDeviceMessageAttributes>,
   // Raw commands are only added post-serialization
   #[getset(get = "pub")]
-  #[serde(rename = "RawSubscribeCmd")]
-  #[serde(skip_deserializing)]
-  #[serde(skip_serializing_if = "Option::is_none")]
+  #[cfg_attr(feature = "serialize", serde(rename = "RawSubscribeCmd"))]
+  #[cfg_attr(feature = "serialize", serde(skip_deserializing))]
+  #[cfg_attr(feature = "serialize", serde(skip_serializing_if = "Option::is_none"))]
   raw_subscribe_cmd: Option<RawDeviceMessageAttributes>,
 
   // Needed to load from config for fallback, but unused here.
   #[getset(get = "pub")]
-  #[serde(rename = "FleshlightLaunchFW12Cmd")]
-  #[serde(skip_serializing)]
+  #[cfg_attr(feature = "serialize", serde(rename = "FleshlightLaunchFW12Cmd"))]
+  #[cfg_attr(feature = "serialize", serde(skip_serializing))]
   fleshlight_launch_fw12_cmd: Option<NullDeviceMessageAttributes>,
   #[getset(get = "pub")]
-  #[serde(rename = "VorzeA10CycloneCmd")]
-  #[serde(skip_serializing)]
+  #[cfg_attr(feature = "serialize", serde(rename = "VorzeA10CycloneCmd"))]
+  #[cfg_attr(feature = "serialize", serde(skip_serializing))]
   vorze_a10_cyclone_cmd: Option<NullDeviceMessageAttributes>,
 }
 
@@ -118,6 +125,7 @@ impl ClientDeviceMessageAttributes {
   pub fn message_allowed(&self, message_type: &ButtplugDeviceMessageType) -> bool {
     match message_type {
       ButtplugDeviceMessageType::ScalarCmd => self.scalar_cmd.is_some(),
+      ButtplugDeviceMessageType::PrioritizedScalarCmd => self.scalar_cmd.is_some(),
       // VibrateCmd and SingleMotorVibrateCmd will derive from Scalars, so errors will be thrown in
       // the scalar parser if the actuator isn't correct.
       ButtplugDeviceMessageType::VibrateCmd => self.scalar_cmd.is_some(),
@@ -152,6 +160,8 @@ impl ClientDeviceMessageAttributes {
       ButtplugDeviceMessageType::RawSubscribeCmd => self.raw_subscribe_cmd.is_some(),
       ButtplugDeviceMessageType::RawUnsubscribeCmd => self.raw_subscribe_cmd.is_some(),
       ButtplugDeviceMessageType::RawWriteCmd => self.raw_write_cmd.is_some(),
+      // Streams end up as a single raw write, so they're allowed anywhere raw writes are.
+      ButtplugDeviceMessageType::RawStreamCmd => self.raw_write_cmd.is_some(),
       ButtplugDeviceMessageType::VorzeA10CycloneCmd => self.vorze_a10_cyclone_cmd.is_some(),
       ButtplugDeviceMessageType::StopDeviceCmd => true,
       ButtplugDeviceMessageType::KiirooCmd => false,
@@ -230,36 +240,40 @@ impl ClientDeviceMessageAttributesBuilder {
   }
 }
 
-#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serialize", derive(Serialize, Deserialize))]
 pub struct NullDeviceMessageAttributes {}
 
-fn unspecified_feature() -> String {
-  "N/A".to_string()
+#[cfg(feature = "serialize")]
+fn unspecified_feature() -> ButtplugString {
+  buttplug_string("N/A")
 }
 
-#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize, Getters, Setters)]
+#[derive(Clone, Debug, PartialEq, Eq, Getters, Setters)]
+#[cfg_attr(feature = "serialize", derive(Serialize, Deserialize))]
 pub struct ClientGenericDeviceMessageAttributes {
   #[getset(get = "pub")]
-  #[serde(rename = "FeatureDescriptor")]
-  #[serde(default = "unspecified_feature")]
-  feature_descriptor: String,
+  #[cfg_attr(feature = "serialize", serde(rename = "FeatureDescriptor"))]
+  #[cfg_attr(feature = "serialize", serde(default = "unspecified_feature"))]
+  feature_descriptor: ButtplugString,
   #[getset(get = "pub")]
-  #[serde(rename = "ActuatorType")]
+  #[cfg_attr(feature = "serialize", serde(rename = "ActuatorType"))]
   actuator_type: ActuatorType,
-  #[serde(rename = "StepCount")]
+  #[cfg_attr(feature = "serialize", serde(rename = "StepCount"))]
   #[getset(get = "pub")]
   step_count: u32,
   // TODO This needs to actually be part of the device info relayed to the client in spec v4.
   #[getset(get = "pub")]
-  #[serde(skip, default)]
+  #[cfg_attr(feature = "serialize", serde(skip, default))]
   index: u32,
 }
 
 impl ClientGenericDeviceMessageAttributes {
   pub fn new(feature_descriptor: &str, step_count: u32, actuator_type: ActuatorType) -> Self {
+    #[cfg(feature = "std")]
     info!("GENERIC DEVICE MESSAGE CONSTRUCTOR CALLED");
     Self {
-      feature_descriptor: feature_descriptor.to_owned(),
+      feature_descriptor: buttplug_string(feature_descriptor),
       actuator_type,
       step_count,
       index: 0,
@@ -273,10 +287,11 @@ impl ClientGenericDeviceMessageAttributes {
   }
 }
 
-#[derive(Clone, Debug, PartialEq, Eq, Default, Serialize, Deserialize, Getters, Setters)]
+#[derive(Clone, Debug, PartialEq, Eq, Default, Getters, Setters)]
+#[cfg_attr(feature = "serialize", derive(Serialize, Deserialize))]
 pub struct RawDeviceMessageAttributes {
   #[getset(get = "pub")]
-  #[serde(rename = "Endpoints")]
+  #[cfg_attr(feature = "serialize", serde(rename = "Endpoints"))]
   endpoints: Vec<Endpoint>,
 }
 
@@ -288,6 +303,7 @@ impl RawDeviceMessageAttributes {
   }
 }
 
+#[cfg(feature = "serialize")]
 fn range_sequence_serialize<S>(
   range_vec: &Vec<RangeInclusive<u32>>,
   serializer: S,
@@ -302,88 +318,93 @@ where
   seq.end()
 }
 
-#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize, Getters, Setters)]
+#[derive(Clone, Debug, PartialEq, Eq, Getters, Setters)]
+#[cfg_attr(feature = "serialize", derive(Serialize, Deserialize))]
 pub struct SensorDeviceMessageAttributes {
   #[getset(get = "pub")]
-  #[serde(rename = "FeatureDescriptor")]
-  feature_descriptor: String,
+  #[cfg_attr(feature = "serialize", serde(rename = "FeatureDescriptor"))]
+  feature_descriptor: ButtplugString,
   #[getset(get = "pub")]
-  #[serde(rename = "SensorType")]
+  #[cfg_attr(feature = "serialize", serde(rename = "SensorType"))]
   sensor_type: SensorType,
   #[getset(get = "pub")]
-  #[serde(rename = "SensorRange", serialize_with = "range_sequence_serialize")]
+  #[cfg_attr(
+    feature = "serialize",
+    serde(rename = "SensorRange", serialize_with = "range_sequence_serialize")
+  )]
   sensor_range: Vec<RangeInclusive<u32>>,
   // TODO This needs to actually be part of the device info relayed to the client in spec v4.
   #[getset(get = "pub")]
-  #[serde(skip, default)]
+  #[cfg_attr(feature = "serialize", serde(skip, default))]
   index: u32,
 }
 
 /*
 impl SensorDeviceMessageAttributes {
   pub fn new(feature_descriptor: &str, sensor_type: SensorType) -> Self {
-    Self { feature_descriptor: feature_descriptor.to_owned(), sensor_type }
+    Self { feature_descriptor: buttplug_string(feature_descriptor), sensor_type }
   }
 }
  */
 
-#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize, Getters, Setters)]
+#[derive(Clone, Debug, PartialEq, Eq, Getters, Setters)]
+#[cfg_attr(feature = "serialize", derive(Serialize, Deserialize))]
 pub struct ClientDeviceMessageAttributesV2 {
   // Generic commands
   #[getset(get = "pub")]
-  #[serde(rename = "VibrateCmd")]
-  #[serde(skip_serializing_if = "Option::is_none")]
+  #[cfg_attr(feature = "serialize", serde(rename = "VibrateCmd"))]
+  #[cfg_attr(feature = "serialize", serde(skip_serializing_if = "Option::is_none"))]
   vibrate_cmd: Option<GenericDeviceMessageAttributesV2>,
   #[getset(get = "pub")]
-  #[serde(rename = "RotateCmd")]
-  #[serde(skip_serializing_if = "Option::is_none")]
+  #[cfg_attr(feature = "serialize", serde(rename = "RotateCmd"))]
+  #[cfg_attr(feature = "serialize", serde(skip_serializing_if = "Option::is_none"))]
   rotate_cmd: Option<GenericDeviceMessageAttributesV2>,
   #[getset(get = "pub")]
-  #[serde(rename = "LinearCmd")]
-  #[serde(skip_serializing_if = "Option::is_none")]
+  #[cfg_attr(feature = "serialize", serde(rename = "LinearCmd"))]
+  #[cfg_attr(feature = "serialize", serde(skip_serializing_if = "Option::is_none"))]
   linear_cmd: Option<GenericDeviceMessageAttributesV2>,
   #[getset(get = "pub")]
-  #[serde(rename = "BatteryLevelCmd")]
-  #[serde(skip_serializing_if = "Option::is_none")]
+  #[cfg_attr(feature = "serialize", serde(rename = "BatteryLevelCmd"))]
+  #[cfg_attr(feature = "serialize", serde(skip_serializing_if = "Option::is_none"))]
   battery_level_cmd: Option<NullDeviceMessageAttributes>,
 
   // RSSILevel is added post-serialization (only for bluetooth devices)
   #[getset(get = "pub")]
-  #[serde(rename = "RSSILevelCmd")]
-  #[serde(skip_serializing_if = "Option::is_none")]
+  #[cfg_attr(feature = "serialize", serde(rename = "RSSILevelCmd"))]
+  #[cfg_attr(feature = "serialize", serde(skip_serializing_if = "Option::is_none"))]
   rssi_level_cmd: Option<NullDeviceMessageAttributes>,
 
   // StopDeviceCmd always exists
   #[getset(get = "pub")]
-  #[serde(rename = "StopDeviceCmd")]
+  #[cfg_attr(feature = "serialize", serde(rename = "StopDeviceCmd"))]
   stop_device_cmd: NullDeviceMessageAttributes,
 
   // Raw commands are only added post-serialization
   #[getset(get = "pub")]
-  #[serde(rename = "RawReadCmd")]
-  #[serde(skip_serializing_if = "Option::is_none")]
+  #[cfg_attr(feature = "serialize", serde(rename = "RawReadCmd"))]
+  #[cfg_attr(feature = "serialize", serde(skip_serializing_if = "Option::is_none"))]
   raw_read_cmd: Option<RawDeviceMessageAttributes>,
   #[getset(get = "pub")]
-  #[serde(rename = "RawWriteCmd")]
-  #[serde(skip_serializing_if = "Option::is_none")]
+  #[cfg_attr(feature = "serialize", serde(rename = "RawWriteCmd"))]
+  #[cfg_attr(feature = "serialize", serde(skip_serializing_if = "Option::is_none"))]
   raw_write_cmd: Option<RawDeviceMessageAttributes>,
   #[getset(get = "pub")]
-  #[serde(rename = "RawSubscribeCmd")]
-  #[serde(skip_serializing_if = "Option::is_none")]
+  #[cfg_attr(feature = "serialize", serde(rename = "RawSubscribeCmd"))]
+  #[cfg_attr(feature = "serialize", serde(skip_serializing_if = "Option::is_none"))]
   raw_subscribe_cmd: Option<RawDeviceMessageAttributes>,
   #[getset(get = "pub")]
-  #[serde(rename = "RawUnsubscribeCmd")]
-  #[serde(skip_serializing_if = "Option::is_none")]
+  #[cfg_attr(feature = "serialize", serde(rename = "RawUnsubscribeCmd"))]
+  #[cfg_attr(feature = "serialize", serde(skip_serializing_if = "Option::is_none"))]
   raw_unsubscribe_cmd: Option<RawDeviceMessageAttributes>,
 
   // Needed to load from config for fallback, but unused here.
   #[getset(get = "pub")]
-  #[serde(rename = "FleshlightLaunchFW12Cmd")]
-  #[serde(skip)]
+  #[cfg_attr(feature = "serialize", serde(rename = "FleshlightLaunchFW12Cmd"))]
+  #[cfg_attr(feature = "serialize", serde(skip))]
   fleshlight_launch_fw12_cmd: Option<NullDeviceMessageAttributes>,
   #[getset(get = "pub")]
-  #[serde(rename = "VorzeA10CycloneCmd")]
-  #[serde(skip)]
+  #[cfg_attr(feature = "serialize", serde(rename = "VorzeA10CycloneCmd"))]
+  #[cfg_attr(feature = "serialize", serde(skip))]
   vorze_a10_cyclone_cmd: Option<NullDeviceMessageAttributes>,
 }
 
@@ -441,13 +462,14 @@ impl From<ClientDeviceMessageAttributes> for ClientDeviceMessageAttributesV2 {
   }
 }
 
-#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize, Getters, Setters)]
+#[derive(Clone, Debug, PartialEq, Eq, Getters, Setters)]
+#[cfg_attr(feature = "serialize", derive(Serialize, Deserialize))]
 pub struct GenericDeviceMessageAttributesV2 {
   #[getset(get = "pub")]
-  #[serde(rename = "FeatureCount")]
+  #[cfg_attr(feature = "serialize", serde(rename = "FeatureCount"))]
   feature_count: u32,
   #[getset(get = "pub")]
-  #[serde(rename = "StepCount")]
+  #[cfg_attr(feature = "serialize", serde(rename = "StepCount"))]
   step_count: Vec<u32>,
 }
 
@@ -479,20 +501,21 @@ impl From<Vec<ClientGenericDeviceMessageAttributes>> for GenericDeviceMessageAtt
   }
 }
 
-#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize, Getters, Setters)]
+#[derive(Clone, Debug, PartialEq, Eq, Getters, Setters)]
+#[cfg_attr(feature = "serialize", derive(Serialize, Deserialize))]
 pub struct ClientDeviceMessageAttributesV1 {
   // Generic commands
   #[getset(get = "pub")]
-  #[serde(rename = "VibrateCmd")]
-  #[serde(skip_serializing_if = "Option::is_none")]
+  #[cfg_attr(feature = "serialize", serde(rename = "VibrateCmd"))]
+  #[cfg_attr(feature = "serialize", serde(skip_serializing_if = "Option::is_none"))]
   vibrate_cmd: Option<GenericDeviceMessageAttributesV1>,
   #[getset(get = "pub")]
-  #[serde(rename = "RotateCmd")]
-  #[serde(skip_serializing_if = "Option::is_none")]
+  #[cfg_attr(feature = "serialize", serde(rename = "RotateCmd"))]
+  #[cfg_attr(feature = "serialize", serde(skip_serializing_if = "Option::is_none"))]
   rotate_cmd: Option<GenericDeviceMessageAttributesV1>,
   #[getset(get = "pub")]
-  #[serde(rename = "LinearCmd")]
-  #[serde(skip_serializing_if = "Option::is_none")]
+  #[cfg_attr(feature = "serialize", serde(rename = "LinearCmd"))]
+  #[cfg_attr(feature = "serialize", serde(skip_serializing_if = "Option::is_none"))]
   linear_cmd: Option<GenericDeviceMessageAttributesV1>,
 
   // StopDeviceCmd always exists
@@ -501,13 +524,13 @@ pub struct ClientDeviceMessageAttributesV1 {
 
   // Obsolete commands are only added post-serialization
   #[getset(get = "pub")]
-  #[serde(skip_serializing_if = "Option::is_none")]
+  #[cfg_attr(feature = "serialize", serde(skip_serializing_if = "Option::is_none"))]
   single_motor_vibrate_cmd: Option<NullDeviceMessageAttributes>,
   #[getset(get = "pub")]
-  #[serde(skip_serializing_if = "Option::is_none")]
+  #[cfg_attr(feature = "serialize", serde(skip_serializing_if = "Option::is_none"))]
   fleshlight_launch_fw12_cmd: Option<NullDeviceMessageAttributes>,
   #[getset(get = "pub")]
-  #[serde(skip_serializing_if = "Option::is_none")]
+  #[cfg_attr(feature = "serialize", serde(skip_serializing_if = "Option::is_none"))]
   vorze_a10_cyclone_cmd: Option<NullDeviceMessageAttributes>,
 }
 
@@ -538,9 +561,10 @@ impl From<ClientDeviceMessageAttributesV2> for ClientDeviceMessageAttributesV1 {
   }
 }
 
-#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize, Getters, Setters)]
+#[derive(Clone, Debug, PartialEq, Eq, Getters, Setters)]
+#[cfg_attr(feature = "serialize", derive(Serialize, Deserialize))]
 pub struct GenericDeviceMessageAttributesV1 {
-  #[serde(rename = "FeatureCount")]
+  #[cfg_attr(feature = "serialize", serde(rename = "FeatureCount"))]
   feature_count: u32,
 }
 
@@ -0,0 +1,26 @@
+// Buttplug Rust Source Code File - See https://buttplug.io for more info.
+//
+// Copyright 2016-2023 Nonpolynomial Labs LLC. All rights reserved.
+//
+// Licensed under the BSD 3-Clause license. See LICENSE file in the project root
+// for full license information.
+
+#[cfg(feature = "serialize")]
+use serde::{Deserialize, Serialize};
+
+/// How urgently a device command should be sent, see
+/// [PrioritizedScalarCmd](super::PrioritizedScalarCmd). Commands waiting to be sent to a device go
+/// out highest priority first, and in the order they arrived within a priority. Commands without a
+/// priority are [CommandPriority::Normal], except for stop commands, which are
+/// [CommandPriority::Critical]. Critical commands skip the queue and are sent without waiting for
+/// pending writes. Critical is kept for stop commands, client commands asking for it are sent at
+/// [CommandPriority::High].
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Copy, Hash, Default)]
+#[cfg_attr(feature = "serialize", derive(Serialize, Deserialize))]
+pub enum CommandPriority {
+  Low,
+  #[default]
+  Normal,
+  High,
+  Critical,
+}
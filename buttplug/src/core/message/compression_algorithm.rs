@@ -0,0 +1,94 @@
+// Buttplug Rust Source Code File - See https://buttplug.io for more info.
+//
+// Copyright 2016-2023 Nonpolynomial Labs LLC. All rights reserved.
+//
+// Licensed under the BSD 3-Clause license. See LICENSE file in the project root
+// for full license information.
+
+use core::mem::discriminant;
+#[cfg(feature = "serialize")]
+use serde::{Deserialize, Serialize};
+
+/// Compression applied to serialized messages once the handshake finishes, as negotiated through
+/// [RequestServerInfo](super::RequestServerInfo) and [ServerInfo](super::ServerInfo).
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default)]
+#[cfg_attr(feature = "serialize", derive(Serialize, Deserialize))]
+pub enum CompressionAlgorithm {
+  #[default]
+  None,
+  Zstd {
+    #[cfg_attr(feature = "serialize", serde(rename = "Level"))]
+    level: i32,
+  },
+  Lz4,
+}
+
+impl CompressionAlgorithm {
+  /// True if both are the same algorithm, whatever their settings.
+  pub fn is_same_kind(&self, other: &CompressionAlgorithm) -> bool {
+    discriminant(self) == discriminant(other)
+  }
+
+  // Higher is preferred when negotiating. Zstd compresses best, lz4 is cheaper but still better
+  // than nothing.
+  fn preference(&self) -> u8 {
+    match self {
+      CompressionAlgorithm::None => 0,
+      CompressionAlgorithm::Lz4 => 1,
+      CompressionAlgorithm::Zstd { .. } => 2,
+    }
+  }
+
+  /// Picks the most preferred algorithm in `server_supported` that the client also listed, or
+  /// [CompressionAlgorithm::None] if there isn't one. Settings (like the zstd level) are taken from
+  /// the server's entry, as the server is the one making the choice.
+  pub fn negotiate(
+    client_supported: &[CompressionAlgorithm],
+    server_supported: &[CompressionAlgorithm],
+  ) -> CompressionAlgorithm {
+    server_supported
+      .iter()
+      .filter(|algorithm| {
+        client_supported
+          .iter()
+          .any(|client_algorithm| client_algorithm.is_same_kind(algorithm))
+      })
+      .max_by_key(|algorithm| algorithm.preference())
+      .copied()
+      .unwrap_or_default()
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use super::CompressionAlgorithm;
+
+  #[test]
+  fn test_compression_negotiation() {
+    let client = [
+      CompressionAlgorithm::Lz4,
+      CompressionAlgorithm::Zstd { level: 1 },
+    ];
+    assert_eq!(
+      CompressionAlgorithm::negotiate(
+        &client,
+        &[
+          CompressionAlgorithm::Zstd { level: 9 },
+          CompressionAlgorithm::Lz4
+        ]
+      ),
+      CompressionAlgorithm::Zstd { level: 9 }
+    );
+    assert_eq!(
+      CompressionAlgorithm::negotiate(
+        &[CompressionAlgorithm::Lz4],
+        &[CompressionAlgorithm::Zstd { level: 3 }]
+      ),
+      CompressionAlgorithm::None
+    );
+    assert_eq!(
+      CompressionAlgorithm::negotiate(&[], &client),
+      CompressionAlgorithm::None
+    );
+  }
+}
@@ -10,31 +10,31 @@ use super::*;
 
 use getset::{CopyGetters, Getters};
 
-#[cfg(feature = "serialize-json")]
+#[cfg(feature = "serialize")]
 use serde::{Deserialize, Serialize};
 
 /// Notification that a device has been found and connected to the server.
 #[derive(ButtplugMessage, Clone, Debug, PartialEq, Eq, Getters, CopyGetters)]
-#[cfg_attr(feature = "serialize-json", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serialize", derive(Serialize, Deserialize))]
 pub struct DeviceAdded {
-  #[cfg_attr(feature = "serialize-json", serde(rename = "Id"))]
+  #[cfg_attr(feature = "serialize", serde(rename = "Id"))]
   id: u32,
   // DeviceAdded is not considered a device message because it only notifies of existence and is not
   // a command (and goes from server to client), therefore we have to define the getter ourselves.
-  #[cfg_attr(feature = "serialize-json", serde(rename = "DeviceIndex"))]
+  #[cfg_attr(feature = "serialize", serde(rename = "DeviceIndex"))]
   #[getset(get_copy = "pub")]
   device_index: u32,
-  #[cfg_attr(feature = "serialize-json", serde(rename = "DeviceName"))]
+  #[cfg_attr(feature = "serialize", serde(rename = "DeviceName"))]
   #[getset(get = "pub")]
-  device_name: String,
+  device_name: ButtplugString,
   #[cfg_attr(
-    feature = "serialize-json",
+    feature = "serialize",
     serde(rename = "DeviceDisplayName", skip_serializing_if = "Option::is_none")
   )]
   #[getset(get = "pub")]
-  device_display_name: Option<String>,
+  device_display_name: Option<ButtplugString>,
   #[cfg_attr(
-    feature = "serialize-json",
+    feature = "serialize",
     serde(
       rename = "DeviceMessageTimingGap",
       skip_serializing_if = "Option::is_none"
@@ -42,7 +42,7 @@ pub struct DeviceAdded {
   )]
   #[getset(get = "pub")]
   device_message_timing_gap: Option<u32>,
-  #[cfg_attr(feature = "serialize-json", serde(rename = "DeviceMessages"))]
+  #[cfg_attr(feature = "serialize", serde(rename = "DeviceMessages"))]
   #[getset(get = "pub")]
   device_messages: ClientDeviceMessageAttributes,
 }
@@ -51,14 +51,14 @@ impl DeviceAdded {
   pub fn new(
     device_index: u32,
     device_name: &str,
-    device_display_name: &Option<String>,
+    device_display_name: &Option<ButtplugString>,
     device_message_timing_gap: &Option<u32>,
     device_messages: &ClientDeviceMessageAttributes,
   ) -> Self {
     let mut obj = Self {
       id: 0,
       device_index,
-      device_name: device_name.to_string(),
+      device_name: buttplug_string(device_name),
       device_display_name: device_display_name.clone(),
       device_message_timing_gap: *device_message_timing_gap,
       device_messages: device_messages.clone(),
@@ -81,17 +81,17 @@ impl ButtplugMessageFinalizer for DeviceAdded {
 }
 
 #[derive(ButtplugMessage, Clone, Debug, PartialEq, Eq, Getters, CopyGetters)]
-#[cfg_attr(feature = "serialize-json", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serialize", derive(Serialize, Deserialize))]
 pub struct DeviceAddedV2 {
-  #[cfg_attr(feature = "serialize-json", serde(rename = "Id"))]
+  #[cfg_attr(feature = "serialize", serde(rename = "Id"))]
   id: u32,
-  #[cfg_attr(feature = "serialize-json", serde(rename = "DeviceIndex"))]
+  #[cfg_attr(feature = "serialize", serde(rename = "DeviceIndex"))]
   #[getset(get_copy = "pub")]
   device_index: u32,
-  #[cfg_attr(feature = "serialize-json", serde(rename = "DeviceName"))]
+  #[cfg_attr(feature = "serialize", serde(rename = "DeviceName"))]
   #[getset(get = "pub")]
-  device_name: String,
-  #[cfg_attr(feature = "serialize-json", serde(rename = "DeviceMessages"))]
+  device_name: ButtplugString,
+  #[cfg_attr(feature = "serialize", serde(rename = "DeviceMessages"))]
   #[getset(get = "pub")]
   device_messages: ClientDeviceMessageAttributesV2,
 }
@@ -121,17 +121,17 @@ impl ButtplugMessageFinalizer for DeviceAddedV2 {
 }
 
 #[derive(ButtplugMessage, Clone, Debug, PartialEq, Eq, Getters, CopyGetters)]
-#[cfg_attr(feature = "serialize-json", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serialize", derive(Serialize, Deserialize))]
 pub struct DeviceAddedV1 {
-  #[cfg_attr(feature = "serialize-json", serde(rename = "Id"))]
+  #[cfg_attr(feature = "serialize", serde(rename = "Id"))]
   id: u32,
-  #[cfg_attr(feature = "serialize-json", serde(rename = "DeviceIndex"))]
+  #[cfg_attr(feature = "serialize", serde(rename = "DeviceIndex"))]
   #[getset(get_copy = "pub")]
   device_index: u32,
-  #[cfg_attr(feature = "serialize-json", serde(rename = "DeviceName"))]
+  #[cfg_attr(feature = "serialize", serde(rename = "DeviceName"))]
   #[getset(get = "pub")]
-  device_name: String,
-  #[cfg_attr(feature = "serialize-json", serde(rename = "DeviceMessages"))]
+  device_name: ButtplugString,
+  #[cfg_attr(feature = "serialize", serde(rename = "DeviceMessages"))]
   #[getset(get = "pub")]
   device_messages: ClientDeviceMessageAttributesV1,
 }
@@ -162,17 +162,17 @@ impl ButtplugMessageFinalizer for DeviceAddedV1 {
 }
 
 #[derive(Default, ButtplugMessage, Clone, Debug, PartialEq, Eq, Getters, CopyGetters)]
-#[cfg_attr(feature = "serialize-json", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serialize", derive(Serialize, Deserialize))]
 pub struct DeviceAddedV0 {
-  #[cfg_attr(feature = "serialize-json", serde(rename = "Id"))]
+  #[cfg_attr(feature = "serialize", serde(rename = "Id"))]
   id: u32,
-  #[cfg_attr(feature = "serialize-json", serde(rename = "DeviceIndex"))]
+  #[cfg_attr(feature = "serialize", serde(rename = "DeviceIndex"))]
   #[getset(get_copy = "pub")]
   device_index: u32,
-  #[cfg_attr(feature = "serialize-json", serde(rename = "DeviceName"))]
+  #[cfg_attr(feature = "serialize", serde(rename = "DeviceName"))]
   #[getset(get = "pub")]
-  device_name: String,
-  #[cfg_attr(feature = "serialize-json", serde(rename = "DeviceMessages"))]
+  device_name: ButtplugString,
+  #[cfg_attr(feature = "serialize", serde(rename = "DeviceMessages"))]
   #[getset(get = "pub")]
   device_messages: Vec<ButtplugDeviceMessageType>,
 }
@@ -8,16 +8,16 @@
 use super::device_message_info::{DeviceMessageInfoV0, DeviceMessageInfoV1, DeviceMessageInfoV2};
 use super::*;
 use getset::Getters;
-#[cfg(feature = "serialize-json")]
+#[cfg(feature = "serialize")]
 use serde::{Deserialize, Serialize};
 
 /// List of all devices currently connected to the server.
 #[derive(Default, Clone, Debug, PartialEq, Eq, ButtplugMessage, Getters)]
-#[cfg_attr(feature = "serialize-json", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serialize", derive(Serialize, Deserialize))]
 pub struct DeviceList {
-  #[cfg_attr(feature = "serialize-json", serde(rename = "Id"))]
+  #[cfg_attr(feature = "serialize", serde(rename = "Id"))]
   id: u32,
-  #[cfg_attr(feature = "serialize-json", serde(rename = "Devices"))]
+  #[cfg_attr(feature = "serialize", serde(rename = "Devices"))]
   #[getset(get = "pub")]
   devices: Vec<DeviceMessageInfo>,
 }
@@ -43,11 +43,11 @@ impl ButtplugMessageFinalizer for DeviceList {
 }
 
 #[derive(Default, Clone, Debug, PartialEq, Eq, ButtplugMessage, Getters)]
-#[cfg_attr(feature = "serialize-json", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serialize", derive(Serialize, Deserialize))]
 pub struct DeviceListV2 {
-  #[cfg_attr(feature = "serialize-json", serde(rename = "Id"))]
+  #[cfg_attr(feature = "serialize", serde(rename = "Id"))]
   id: u32,
-  #[cfg_attr(feature = "serialize-json", serde(rename = "Devices"))]
+  #[cfg_attr(feature = "serialize", serde(rename = "Devices"))]
   #[getset(get = "pub")]
   devices: Vec<DeviceMessageInfoV2>,
 }
@@ -75,11 +75,11 @@ impl ButtplugMessageFinalizer for DeviceListV2 {
 }
 
 #[derive(Default, Clone, Debug, PartialEq, Eq, ButtplugMessage, Getters)]
-#[cfg_attr(feature = "serialize-json", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serialize", derive(Serialize, Deserialize))]
 pub struct DeviceListV1 {
-  #[cfg_attr(feature = "serialize-json", serde(rename = "Id"))]
+  #[cfg_attr(feature = "serialize", serde(rename = "Id"))]
   id: u32,
-  #[cfg_attr(feature = "serialize-json", serde(rename = "Devices"))]
+  #[cfg_attr(feature = "serialize", serde(rename = "Devices"))]
   #[getset(get = "pub")]
   devices: Vec<DeviceMessageInfoV1>,
 }
@@ -108,11 +108,11 @@ impl ButtplugMessageFinalizer for DeviceListV1 {
 }
 
 #[derive(Default, Clone, Debug, PartialEq, Eq, ButtplugMessage, Getters)]
-#[cfg_attr(feature = "serialize-json", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serialize", derive(Serialize, Deserialize))]
 pub struct DeviceListV0 {
-  #[cfg_attr(feature = "serialize-json", serde(rename = "Id"))]
+  #[cfg_attr(feature = "serialize", serde(rename = "Id"))]
   id: u32,
-  #[cfg_attr(feature = "serialize-json", serde(rename = "Devices"))]
+  #[cfg_attr(feature = "serialize", serde(rename = "Devices"))]
   #[getset(get = "pub")]
   devices: Vec<DeviceMessageInfoV0>,
 }
@@ -7,27 +7,27 @@
 
 use super::*;
 use getset::{CopyGetters, Getters, MutGetters};
-#[cfg(feature = "serialize-json")]
+#[cfg(feature = "serialize")]
 use serde::{Deserialize, Serialize};
 
 /// Substructure of device messages, used for attribute information (name, messages supported, etc...)
 #[derive(Clone, Debug, PartialEq, Eq, MutGetters, Getters, CopyGetters)]
-#[cfg_attr(feature = "serialize-json", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serialize", derive(Serialize, Deserialize))]
 pub struct DeviceMessageInfo {
-  #[cfg_attr(feature = "serialize-json", serde(rename = "DeviceIndex"))]
+  #[cfg_attr(feature = "serialize", serde(rename = "DeviceIndex"))]
   #[getset(get_copy = "pub")]
   device_index: u32,
-  #[cfg_attr(feature = "serialize-json", serde(rename = "DeviceName"))]
+  #[cfg_attr(feature = "serialize", serde(rename = "DeviceName"))]
   #[getset(get = "pub")]
-  device_name: String,
+  device_name: ButtplugString,
   #[cfg_attr(
-    feature = "serialize-json",
+    feature = "serialize",
     serde(rename = "DeviceDisplayName", skip_serializing_if = "Option::is_none")
   )]
   #[getset(get = "pub")]
-  device_display_name: Option<String>,
+  device_display_name: Option<ButtplugString>,
   #[cfg_attr(
-    feature = "serialize-json",
+    feature = "serialize",
     serde(
       rename = "DeviceMessageTimingGap",
       skip_serializing_if = "Option::is_none"
@@ -35,7 +35,7 @@ pub struct DeviceMessageInfo {
   )]
   #[getset(get = "pub")]
   device_message_timing_gap: Option<u32>,
-  #[cfg_attr(feature = "serialize-json", serde(rename = "DeviceMessages"))]
+  #[cfg_attr(feature = "serialize", serde(rename = "DeviceMessages"))]
   #[getset(get = "pub", get_mut = "pub(super)")]
   device_messages: ClientDeviceMessageAttributes,
 }
@@ -44,13 +44,13 @@ impl DeviceMessageInfo {
   pub fn new(
     device_index: u32,
     device_name: &str,
-    device_display_name: &Option<String>,
+    device_display_name: &Option<ButtplugString>,
     device_message_timing_gap: &Option<u32>,
     device_messages: ClientDeviceMessageAttributes,
   ) -> Self {
     Self {
       device_index,
-      device_name: device_name.to_owned(),
+      device_name: buttplug_string(device_name),
       device_display_name: device_display_name.clone(),
       device_message_timing_gap: *device_message_timing_gap,
       device_messages,
@@ -71,15 +71,15 @@ impl From<DeviceAdded> for DeviceMessageInfo {
 }
 
 #[derive(Clone, Debug, PartialEq, Eq, Getters, CopyGetters)]
-#[cfg_attr(feature = "serialize-json", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serialize", derive(Serialize, Deserialize))]
 pub struct DeviceMessageInfoV2 {
-  #[cfg_attr(feature = "serialize-json", serde(rename = "DeviceIndex"))]
+  #[cfg_attr(feature = "serialize", serde(rename = "DeviceIndex"))]
   #[getset(get_copy = "pub")]
   device_index: u32,
-  #[cfg_attr(feature = "serialize-json", serde(rename = "DeviceName"))]
+  #[cfg_attr(feature = "serialize", serde(rename = "DeviceName"))]
   #[getset(get = "pub")]
-  device_name: String,
-  #[cfg_attr(feature = "serialize-json", serde(rename = "DeviceMessages"))]
+  device_name: ButtplugString,
+  #[cfg_attr(feature = "serialize", serde(rename = "DeviceMessages"))]
   #[getset(get = "pub")]
   device_messages: ClientDeviceMessageAttributesV2,
 }
@@ -114,15 +114,15 @@ impl From<DeviceMessageInfo> for DeviceMessageInfoV2 {
 }
 
 #[derive(Clone, Debug, PartialEq, Eq, Getters, CopyGetters)]
-#[cfg_attr(feature = "serialize-json", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serialize", derive(Serialize, Deserialize))]
 pub struct DeviceMessageInfoV1 {
-  #[cfg_attr(feature = "serialize-json", serde(rename = "DeviceIndex"))]
+  #[cfg_attr(feature = "serialize", serde(rename = "DeviceIndex"))]
   #[getset(get_copy = "pub")]
   device_index: u32,
-  #[cfg_attr(feature = "serialize-json", serde(rename = "DeviceName"))]
+  #[cfg_attr(feature = "serialize", serde(rename = "DeviceName"))]
   #[getset(get = "pub")]
-  device_name: String,
-  #[cfg_attr(feature = "serialize-json", serde(rename = "DeviceMessages"))]
+  device_name: ButtplugString,
+  #[cfg_attr(feature = "serialize", serde(rename = "DeviceMessages"))]
   #[getset(get = "pub")]
   device_messages: ClientDeviceMessageAttributesV1,
 }
@@ -146,15 +146,15 @@ impl From<DeviceMessageInfoV2> for DeviceMessageInfoV1 {
 }
 
 #[derive(Clone, Debug, PartialEq, Eq, Getters, CopyGetters)]
-#[cfg_attr(feature = "serialize-json", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serialize", derive(Serialize, Deserialize))]
 pub struct DeviceMessageInfoV0 {
-  #[cfg_attr(feature = "serialize-json", serde(rename = "DeviceIndex"))]
+  #[cfg_attr(feature = "serialize", serde(rename = "DeviceIndex"))]
   #[getset(get_copy = "pub")]
   device_index: u32,
-  #[cfg_attr(feature = "serialize-json", serde(rename = "DeviceName"))]
+  #[cfg_attr(feature = "serialize", serde(rename = "DeviceName"))]
   #[getset(get = "pub")]
-  device_name: String,
-  #[cfg_attr(feature = "serialize-json", serde(rename = "DeviceMessages"))]
+  device_name: ButtplugString,
+  #[cfg_attr(feature = "serialize", serde(rename = "DeviceMessages"))]
   #[getset(get = "pub")]
   device_messages: Vec<ButtplugDeviceMessageType>,
 }
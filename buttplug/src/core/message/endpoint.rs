@@ -1,3 +1,8 @@
+use core::{
+  fmt::{self, Debug},
+  hash::Hash,
+  str::FromStr,
+};
 use serde::{
   de::{self, Visitor},
   Deserialize,
@@ -5,13 +10,6 @@ use serde::{
   Serialize,
   Serializer,
 };
-use std::{
-  fmt::{self, Debug},
-  str::FromStr,
-  string::ToString,
-};
-
-use core::hash::Hash;
 
 // We need this array to be exposed in our WASM FFI, but the only way to do that
 // is to expose it at the declaration level. Therefore, we use the WASM feature
@@ -131,7 +129,7 @@ impl Serialize for Endpoint {
   where
     S: Serializer,
   {
-    serializer.serialize_str(&self.to_string())
+    serializer.collect_str(self)
   }
 }
 
@@ -148,7 +146,7 @@ impl<'de> Visitor<'de> for EndpointVisitor {
   where
     E: de::Error,
   {
-    Endpoint::from_str(value).map_err(|e| E::custom(format!("{}", e)))
+    Endpoint::from_str(value).map_err(|_| E::custom("Matching variant not found"))
   }
 }
 
@@ -10,15 +10,15 @@
 use super::*;
 use crate::core::errors::*;
 use getset::{CopyGetters, Getters};
-#[cfg(feature = "serialize-json")]
+#[cfg(feature = "serialize")]
 use serde::{Deserialize, Serialize};
-#[cfg(feature = "serialize-json")]
+#[cfg(feature = "serialize")]
 use serde_repr::{Deserialize_repr, Serialize_repr};
 
 /// Error codes pertaining to error classes that can be represented in the
 /// Buttplug [Error] message.
 #[derive(Debug, Clone, PartialEq, Eq, Copy)]
-#[cfg_attr(feature = "serialize-json", derive(Serialize_repr, Deserialize_repr))]
+#[cfg_attr(feature = "serialize", derive(Serialize_repr, Deserialize_repr))]
 #[repr(u8)]
 pub enum ErrorCode {
   ErrorUnknown = 0,
@@ -42,20 +42,20 @@ pub enum ErrorCode {
   Getters,
   CopyGetters,
 )]
-#[cfg_attr(feature = "serialize-json", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serialize", derive(Serialize, Deserialize))]
 pub struct Error {
   /// Message Id, used for matching message pairs in remote connection instances.
-  #[cfg_attr(feature = "serialize-json", serde(rename = "Id"))]
+  #[cfg_attr(feature = "serialize", serde(rename = "Id"))]
   id: u32,
   /// Specifies the class of the error.
-  #[cfg_attr(feature = "serialize-json", serde(rename = "ErrorCode"))]
+  #[cfg_attr(feature = "serialize", serde(rename = "ErrorCode"))]
   #[getset(get_copy = "pub")]
   error_code: ErrorCode,
   /// Description of the error.
-  #[cfg_attr(feature = "serialize-json", serde(rename = "ErrorMessage"))]
+  #[cfg_attr(feature = "serialize", serde(rename = "ErrorMessage"))]
   #[getset(get = "pub")]
-  error_message: String,
-  #[cfg_attr(feature = "serialize-json", serde(skip))]
+  error_message: ButtplugString,
+  #[cfg_attr(feature = "serialize", serde(skip))]
   original_error: Option<ButtplugError>,
 }
 
@@ -77,7 +77,7 @@ impl Error {
     Self {
       id: 0,
       error_code,
-      error_message: error_message.to_string(),
+      error_message: buttplug_string(error_message),
       original_error,
     }
   }
@@ -101,17 +101,23 @@ impl Error {
   }
 }
 
+/// Error code for the error, looking through any added context to the original cause.
+fn error_code(error: &ButtplugError) -> ErrorCode {
+  match error {
+    ButtplugError::ButtplugDeviceError { .. } => ErrorCode::ErrorDevice,
+    ButtplugError::ButtplugMessageError { .. } => ErrorCode::ErrorMessage,
+    ButtplugError::ButtplugPingError { .. } => ErrorCode::ErrorPing,
+    ButtplugError::ButtplugHandshakeError { .. } => ErrorCode::ErrorHandshake,
+    ButtplugError::ButtplugUnknownError { .. } => ErrorCode::ErrorUnknown,
+    ButtplugError::ContextualError { cause, .. } => error_code(cause),
+  }
+}
+
 impl From<ButtplugError> for Error {
   /// Converts a [ButtplugError] object into a Buttplug Protocol
   /// [Error] message.
   fn from(error: ButtplugError) -> Self {
-    let code = match error {
-      ButtplugError::ButtplugDeviceError { .. } => ErrorCode::ErrorDevice,
-      ButtplugError::ButtplugMessageError { .. } => ErrorCode::ErrorMessage,
-      ButtplugError::ButtplugPingError { .. } => ErrorCode::ErrorPing,
-      ButtplugError::ButtplugHandshakeError { .. } => ErrorCode::ErrorHandshake,
-      ButtplugError::ButtplugUnknownError { .. } => ErrorCode::ErrorUnknown,
-    };
+    let code = error_code(&error);
     #[cfg(feature = "serialize-json")]
     let msg = serde_json::to_string(&error).expect("All buttplug errors are serializable");
     #[cfg(not(feature = "serialize-json"))]
@@ -131,19 +137,19 @@ impl From<ButtplugError> for Error {
   Getters,
   CopyGetters,
 )]
-#[cfg_attr(feature = "serialize-json", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serialize", derive(Serialize, Deserialize))]
 pub struct ErrorV0 {
   /// Message Id, used for matching message pairs in remote connection instances.
-  #[cfg_attr(feature = "serialize-json", serde(rename = "Id"))]
+  #[cfg_attr(feature = "serialize", serde(rename = "Id"))]
   id: u32,
   /// Specifies the class of the error.
-  #[cfg_attr(feature = "serialize-json", serde(rename = "ErrorCode"))]
+  #[cfg_attr(feature = "serialize", serde(rename = "ErrorCode"))]
   #[getset(get_copy = "pub")]
   error_code: ErrorCode,
   /// Description of the error.
-  #[cfg_attr(feature = "serialize-json", serde(rename = "ErrorMessage"))]
+  #[cfg_attr(feature = "serialize", serde(rename = "ErrorMessage"))]
   #[getset(get = "pub")]
-  error_message: String,
+  error_message: ButtplugString,
 }
 
 impl ErrorV0 {
@@ -152,7 +158,7 @@ impl ErrorV0 {
     Self {
       id: 0,
       error_code,
-      error_message: error_message.to_string(),
+      error_message: buttplug_string(error_message),
     }
   }
 }
@@ -9,22 +9,22 @@
 
 use super::*;
 use getset::CopyGetters;
-#[cfg(feature = "serialize-json")]
+#[cfg(feature = "serialize")]
 use serde::{Deserialize, Serialize};
 
 #[derive(
   Debug, ButtplugDeviceMessage, ButtplugMessageFinalizer, PartialEq, Eq, Clone, CopyGetters,
 )]
-#[cfg_attr(feature = "serialize-json", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serialize", derive(Serialize, Deserialize))]
 pub struct FleshlightLaunchFW12Cmd {
-  #[cfg_attr(feature = "serialize-json", serde(rename = "Id"))]
+  #[cfg_attr(feature = "serialize", serde(rename = "Id"))]
   id: u32,
-  #[cfg_attr(feature = "serialize-json", serde(rename = "DeviceIndex"))]
+  #[cfg_attr(feature = "serialize", serde(rename = "DeviceIndex"))]
   device_index: u32,
-  #[cfg_attr(feature = "serialize-json", serde(rename = "Position"))]
+  #[cfg_attr(feature = "serialize", serde(rename = "Position"))]
   #[getset(get_copy = "pub")]
   position: u8,
-  #[cfg_attr(feature = "serialize-json", serde(rename = "Speed"))]
+  #[cfg_attr(feature = "serialize", serde(rename = "Speed"))]
   #[getset(get_copy = "pub")]
   speed: u8,
 }
@@ -44,15 +44,19 @@ impl ButtplugMessageValidator for FleshlightLaunchFW12Cmd {
   fn is_valid(&self) -> Result<(), ButtplugMessageError> {
     self.is_not_system_id(self.id)?;
     if !(0..100).contains(&self.speed) {
-      Err(ButtplugMessageError::InvalidMessageContents(format!(
-        "FleshlightFW12Cmd speed {} invalid, should be between 0 and 99",
-        self.speed
-      )))
+      Err(ButtplugMessageError::InvalidMessageContents(
+        format_buttplug_string!(
+          "FleshlightFW12Cmd speed {} invalid, should be between 0 and 99",
+          self.speed
+        ),
+      ))
     } else if !(0..100).contains(&self.position) {
-      Err(ButtplugMessageError::InvalidMessageContents(format!(
-        "FleshlightFW12Cmd position {} invalid, should be between 0 and 99",
-        self.position
-      )))
+      Err(ButtplugMessageError::InvalidMessageContents(
+        format_buttplug_string!(
+          "FleshlightFW12Cmd position {} invalid, should be between 0 and 99",
+          self.position
+        ),
+      ))
     } else {
       Ok(())
     }
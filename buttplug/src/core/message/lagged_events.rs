@@ -0,0 +1,66 @@
+// Buttplug Rust Source Code File - See https://buttplug.io for more info.
+//
+// Copyright 2016-2023 Nonpolynomial Labs LLC. All rights reserved.
+//
+// Licensed under the BSD 3-Clause license. See LICENSE file in the project root
+// for full license information.
+
+//! Notification that an event stream fell behind and skipped events. Only emitted on server
+//! event streams. Clients have no message for it, so they're sent an [Error] instead.
+
+use super::*;
+use crate::core::errors::{ButtplugError, ButtplugUnknownError};
+use getset::CopyGetters;
+#[cfg(feature = "serialize")]
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Default, ButtplugMessage, Clone, PartialEq, Eq, CopyGetters)]
+#[cfg_attr(feature = "serialize", derive(Serialize, Deserialize))]
+pub struct LaggedEvents {
+  #[cfg_attr(feature = "serialize", serde(rename = "Id"))]
+  id: u32,
+  /// Number of events skipped.
+  #[cfg_attr(feature = "serialize", serde(rename = "Count"))]
+  #[getset(get_copy = "pub")]
+  count: u64,
+}
+
+impl LaggedEvents {
+  pub fn new(count: u64) -> Self {
+    Self { id: 0, count }
+  }
+}
+
+impl ButtplugMessageValidator for LaggedEvents {
+  fn is_valid(&self) -> Result<(), ButtplugMessageError> {
+    self.is_system_id(self.id)
+  }
+}
+
+impl ButtplugMessageFinalizer for LaggedEvents {
+}
+
+impl From<LaggedEvents> for Error {
+  /// Converts to an [ButtplugUnknownError::EventsLagged] error, which clients surface as an error
+  /// event. Clients seeing it should request a fresh device list.
+  fn from(lagged: LaggedEvents) -> Self {
+    ButtplugError::from(ButtplugUnknownError::EventsLagged(lagged.count())).into()
+  }
+}
+
+#[cfg(feature = "serialize-json")]
+#[cfg(test)]
+mod test {
+  use super::*;
+
+  #[test]
+  fn test_lagged_events_error_round_trip() {
+    let error = Error::from(LaggedEvents::new(5));
+    let json = serde_json::to_string(&error).expect("Infallible serialization.");
+    let deserialized: Error = serde_json::from_str(&json).expect("Infallible deserialization.");
+    assert_eq!(
+      deserialized.original_error(),
+      ButtplugError::from(ButtplugUnknownError::EventsLagged(5))
+    );
+  }
+}
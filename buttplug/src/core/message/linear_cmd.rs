@@ -7,19 +7,19 @@
 
 use super::*;
 use getset::{CopyGetters, Getters};
-#[cfg(feature = "serialize-json")]
+#[cfg(feature = "serialize")]
 use serde::{Deserialize, Serialize};
 
 /// Move device to a certain position in a certain amount of time
 #[derive(Debug, PartialEq, Clone, CopyGetters)]
-#[cfg_attr(feature = "serialize-json", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serialize", derive(Serialize, Deserialize))]
 #[getset(get_copy = "pub")]
 pub struct VectorSubcommand {
-  #[cfg_attr(feature = "serialize-json", serde(rename = "Index"))]
+  #[cfg_attr(feature = "serialize", serde(rename = "Index"))]
   index: u32,
-  #[cfg_attr(feature = "serialize-json", serde(rename = "Duration"))]
+  #[cfg_attr(feature = "serialize", serde(rename = "Duration"))]
   duration: u32,
-  #[cfg_attr(feature = "serialize-json", serde(rename = "Position"))]
+  #[cfg_attr(feature = "serialize", serde(rename = "Position"))]
   position: f64,
 }
 
@@ -34,13 +34,13 @@ impl VectorSubcommand {
 }
 
 #[derive(Debug, ButtplugDeviceMessage, ButtplugMessageFinalizer, PartialEq, Clone, Getters)]
-#[cfg_attr(feature = "serialize-json", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serialize", derive(Serialize, Deserialize))]
 pub struct LinearCmd {
-  #[cfg_attr(feature = "serialize-json", serde(rename = "Id"))]
+  #[cfg_attr(feature = "serialize", serde(rename = "Id"))]
   id: u32,
-  #[cfg_attr(feature = "serialize-json", serde(rename = "DeviceIndex"))]
+  #[cfg_attr(feature = "serialize", serde(rename = "DeviceIndex"))]
   device_index: u32,
-  #[cfg_attr(feature = "serialize-json", serde(rename = "Vectors"))]
+  #[cfg_attr(feature = "serialize", serde(rename = "Vectors"))]
   #[getset(get = "pub")]
   vectors: Vec<VectorSubcommand>,
 }
@@ -61,9 +61,10 @@ impl ButtplugMessageValidator for LinearCmd {
     for vec in &self.vectors {
       self.is_in_command_range(
         vec.position,
-        format!(
+        format_buttplug_string!(
           "VectorSubcommand position {} for index {} is invalid, should be between 0.0 and 1.0",
-          vec.position, vec.index
+          vec.position,
+          vec.index
         ),
       )?;
     }
@@ -7,23 +7,23 @@
 
 use super::*;
 use getset::{CopyGetters, Getters};
-#[cfg(feature = "serialize-json")]
+#[cfg(feature = "serialize")]
 use serde::{Deserialize, Serialize};
 
 /// Log message received from server (Version 1 Message, Deprecated)
 #[derive(
   Debug, ButtplugMessage, ButtplugMessageFinalizer, PartialEq, Eq, Clone, Getters, CopyGetters,
 )]
-#[cfg_attr(feature = "serialize-json", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serialize", derive(Serialize, Deserialize))]
 pub struct Log {
-  #[cfg_attr(feature = "serialize-json", serde(rename = "Id"))]
+  #[cfg_attr(feature = "serialize", serde(rename = "Id"))]
   id: u32,
-  #[cfg_attr(feature = "serialize-json", serde(rename = "LogLevel"))]
+  #[cfg_attr(feature = "serialize", serde(rename = "LogLevel"))]
   #[getset(get_copy = "pub")]
   log_level: LogLevel,
-  #[cfg_attr(feature = "serialize-json", serde(rename = "LogMessage"))]
+  #[cfg_attr(feature = "serialize", serde(rename = "LogMessage"))]
   #[getset(get = "pub")]
-  log_message: String,
+  log_message: ButtplugString,
 }
 
 impl Log {
@@ -31,7 +31,7 @@ impl Log {
     Self {
       id: 0,
       log_level,
-      log_message: log_message.to_owned(),
+      log_message: buttplug_string(log_message),
     }
   }
 }
@@ -5,14 +5,15 @@
 // Licensed under the BSD 3-Clause license. See LICENSE file in the project root
 // for full license information.
 
-#[cfg(feature = "serialize-json")]
+use core::cmp::Ord;
+#[cfg(feature = "serialize")]
 use serde::{Deserialize, Serialize};
-use std::cmp::Ord;
+#[cfg(feature = "std")]
 use tracing::Level;
 
 /// Log Levels (Version 1 Message, Deprecated)
 #[derive(Debug, PartialEq, Clone, Ord, PartialOrd, Eq, Copy)]
-#[cfg_attr(feature = "serialize-json", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serialize", derive(Serialize, Deserialize))]
 pub enum LogLevel {
   Off = 0,
   Fatal,
@@ -23,6 +24,7 @@ pub enum LogLevel {
   Trace,
 }
 
+#[cfg(feature = "std")]
 impl From<Level> for LogLevel {
   fn from(level: Level) -> Self {
     match level {
@@ -35,6 +37,7 @@ impl From<Level> for LogLevel {
   }
 }
 
+#[cfg(feature = "std")]
 impl From<LogLevel> for Level {
   fn from(level: LogLevel) -> Level {
     match level {
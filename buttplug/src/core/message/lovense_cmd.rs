@@ -7,7 +7,7 @@
 
 use super::*;
 use getset::Getters;
-#[cfg(feature = "serialize-json")]
+#[cfg(feature = "serialize")]
 use serde::{Deserialize, Serialize};
 
 /// Lovense specific commands (Version 0 Message, **Deprecated**)
@@ -15,15 +15,15 @@ use serde::{Deserialize, Serialize};
 // Lovense devices even on spec v1 connections, we can put a null validator on
 // it.
 #[derive(Debug, ButtplugDeviceMessage, ButtplugMessageFinalizer, PartialEq, Eq, Clone, Getters)]
-#[cfg_attr(feature = "serialize-json", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serialize", derive(Serialize, Deserialize))]
 pub struct LovenseCmd {
-  #[cfg_attr(feature = "serialize-json", serde(rename = "Id"))]
+  #[cfg_attr(feature = "serialize", serde(rename = "Id"))]
   id: u32,
-  #[cfg_attr(feature = "serialize-json", serde(rename = "DeviceIndex"))]
+  #[cfg_attr(feature = "serialize", serde(rename = "DeviceIndex"))]
   device_index: u32,
-  #[cfg_attr(feature = "serialize-json", serde(rename = "Command"))]
+  #[cfg_attr(feature = "serialize", serde(rename = "Command"))]
   #[getset(get = "pub")]
-  command: String,
+  command: ButtplugString,
 }
 
 impl LovenseCmd {
@@ -31,7 +31,7 @@ impl LovenseCmd {
     Self {
       id: 1,
       device_index,
-      command: command.to_owned(),
+      command: buttplug_string(command),
     }
   }
 }
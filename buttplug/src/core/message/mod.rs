@@ -16,6 +16,8 @@
 mod battery_level_cmd;
 mod battery_level_reading;
 mod client_device_message_attributes;
+mod command_priority;
+mod compression_algorithm;
 mod device_added;
 mod device_list;
 mod device_message_info;
@@ -24,14 +26,17 @@ mod endpoint;
 mod error;
 mod fleshlight_launch_fw12_cmd;
 mod kiiroo_cmd;
+mod lagged_events;
 mod linear_cmd;
 mod log;
 mod log_level;
 mod lovense_cmd;
 mod ok;
 mod ping;
+mod prioritized_scalar_cmd;
 mod raw_read_cmd;
 mod raw_reading;
+mod raw_stream_cmd;
 mod raw_subscribe_cmd;
 mod raw_unsubscribe_cmd;
 mod raw_write_cmd;
@@ -47,6 +52,7 @@ mod sensor_read_cmd;
 mod sensor_reading;
 mod sensor_subscribe_cmd;
 mod sensor_unsubscribe_cmd;
+#[cfg(feature = "std")]
 pub mod serializer;
 mod server_info;
 mod single_motor_vibrate_cmd;
@@ -55,6 +61,8 @@ mod stop_all_devices;
 mod stop_device_cmd;
 mod stop_scanning;
 mod test;
+#[cfg(feature = "std")]
+mod transformer;
 mod vibrate_cmd;
 mod vorze_a10_cyclone_cmd;
 
@@ -73,6 +81,8 @@ pub use client_device_message_attributes::{
   SensorDeviceMessageAttributes,
   SensorType,
 };
+pub use command_priority::CommandPriority;
+pub use compression_algorithm::CompressionAlgorithm;
 pub use device_added::{DeviceAdded, DeviceAddedV0, DeviceAddedV1, DeviceAddedV2};
 pub use device_list::{DeviceList, DeviceListV0, DeviceListV1, DeviceListV2};
 pub use device_message_info::{
@@ -86,13 +96,16 @@ pub use endpoint::Endpoint;
 pub use error::{Error, ErrorCode, ErrorV0};
 pub use fleshlight_launch_fw12_cmd::FleshlightLaunchFW12Cmd;
 pub use kiiroo_cmd::KiirooCmd;
+pub use lagged_events::LaggedEvents;
 pub use linear_cmd::{LinearCmd, VectorSubcommand};
 pub use log_level::LogLevel;
 pub use lovense_cmd::LovenseCmd;
 pub use ok::Ok;
 pub use ping::Ping;
+pub use prioritized_scalar_cmd::PrioritizedScalarCmd;
 pub use raw_read_cmd::RawReadCmd;
 pub use raw_reading::RawReading;
+pub use raw_stream_cmd::RawStreamCmd;
 pub use raw_subscribe_cmd::RawSubscribeCmd;
 pub use raw_unsubscribe_cmd::RawUnsubscribeCmd;
 pub use raw_write_cmd::RawWriteCmd;
@@ -115,21 +128,27 @@ pub use stop_all_devices::StopAllDevices;
 pub use stop_device_cmd::StopDeviceCmd;
 pub use stop_scanning::StopScanning;
 pub use test::Test;
+#[cfg(feature = "std")]
+pub use transformer::{ClampIntensityTransformer, MessageTransformer, NormalizeCaseTransformer};
 pub use vibrate_cmd::{VibrateCmd, VibrateSubcommand};
 pub use vorze_a10_cyclone_cmd::VorzeA10CycloneCmd;
 
 use crate::core::errors::ButtplugMessageError;
+use crate::core::{buttplug_string, ButtplugString};
+#[cfg(not(feature = "std"))]
+use alloc::{string::ToString, vec, vec::Vec};
+use core::cmp::Ordering;
+use core::convert::TryFrom;
+#[cfg(feature = "serialize")]
 use serde::{Deserialize, Serialize};
-#[cfg(feature = "serialize-json")]
+#[cfg(feature = "serialize")]
 use serde_repr::{Deserialize_repr, Serialize_repr};
-use std::cmp::Ordering;
-use std::convert::TryFrom;
 
 /// Enum of possible [Buttplug Message
 /// Spec](https://buttplug-spec.docs.buttplug.io) versions.
 #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Display)]
 #[repr(u32)]
-#[cfg_attr(feature = "serialize-json", derive(Serialize_repr, Deserialize_repr))]
+#[cfg_attr(feature = "serialize", derive(Serialize_repr, Deserialize_repr))]
 pub enum ButtplugMessageSpecVersion {
   Version0 = 0,
   Version1 = 1,
@@ -182,7 +201,7 @@ pub trait ButtplugMessageValidator {
       Ok(())
     } else {
       Err(ButtplugMessageError::InvalidMessageContents(
-        "Message should have id of 0, as it is a system message.".to_string(),
+        buttplug_string("Message should have id of 0, as it is a system message."),
       ))
     }
   }
@@ -190,14 +209,20 @@ pub trait ButtplugMessageValidator {
   fn is_not_system_id(&self, id: u32) -> Result<(), ButtplugMessageError> {
     if id == 0 {
       Err(ButtplugMessageError::InvalidMessageContents(
-        "Message should not have 0 for an Id. Id of 0 is reserved for system messages.".to_string(),
+        buttplug_string(
+          "Message should not have 0 for an Id. Id of 0 is reserved for system messages.",
+        ),
       ))
     } else {
       Ok(())
     }
   }
 
-  fn is_in_command_range(&self, value: f64, error_msg: String) -> Result<(), ButtplugMessageError> {
+  fn is_in_command_range(
+    &self,
+    value: f64,
+    error_msg: ButtplugString,
+  ) -> Result<(), ButtplugMessageError> {
     if !(0.0..=1.0).contains(&value) {
       Err(ButtplugMessageError::InvalidMessageContents(error_msg))
     } else {
@@ -217,7 +242,8 @@ pub trait ButtplugDeviceMessage: ButtplugMessage {
 
 /// Used in [MessageAttributes][crate::core::messages::DeviceMessageAttributes] for denoting message
 /// capabilties.
-#[derive(Copy, Debug, Clone, PartialEq, Eq, Hash, Display, Serialize, Deserialize)]
+#[derive(Copy, Debug, Clone, PartialEq, Eq, Hash, Display)]
+#[cfg_attr(feature = "serialize", derive(Serialize, Deserialize))]
 pub enum ButtplugDeviceMessageType {
   VibrateCmd,
   LinearCmd,
@@ -225,11 +251,13 @@ pub enum ButtplugDeviceMessageType {
   StopDeviceCmd,
   RawWriteCmd,
   RawReadCmd,
+  RawStreamCmd,
   RawSubscribeCmd,
   RawUnsubscribeCmd,
   BatteryLevelCmd,
   RSSILevelCmd,
   ScalarCmd,
+  PrioritizedScalarCmd,
   SensorReadCmd,
   SensorSubscribeCmd,
   SensorUnsubscribeCmd,
@@ -269,6 +297,7 @@ impl Ord for ButtplugDeviceMessageType {
   ButtplugClientMessageType,
   FromSpecificButtplugMessage,
 )]
+#[cfg_attr(feature = "serialize", derive(Serialize, Deserialize))]
 pub enum ButtplugClientMessage {
   Ping(Ping),
   RequestLog(RequestLog),
@@ -285,10 +314,12 @@ pub enum ButtplugClientMessage {
   RotateCmd(RotateCmd),
   RawWriteCmd(RawWriteCmd),
   RawReadCmd(RawReadCmd),
+  RawStreamCmd(RawStreamCmd),
   StopDeviceCmd(StopDeviceCmd),
   RawSubscribeCmd(RawSubscribeCmd),
   RawUnsubscribeCmd(RawUnsubscribeCmd),
   ScalarCmd(ScalarCmd),
+  PrioritizedScalarCmd(PrioritizedScalarCmd),
   // Sensor commands
   BatteryLevelCmd(BatteryLevelCmd),
   RSSILevelCmd(RSSILevelCmd),
@@ -305,6 +336,73 @@ pub enum ButtplugClientMessage {
   // To Add:
 }
 
+/// Rough size of a serialized message with no variable length payload: the message name, Id,
+/// DeviceIndex, and JSON punctuation.
+const ESTIMATED_MESSAGE_BASE_SIZE: usize = 128;
+/// Rough size of a serialized actuator subcommand (index, value, actuator type).
+const ESTIMATED_SUBCOMMAND_SIZE: usize = 64;
+/// Serialized raw data bytes take at most 3 digits and a comma each.
+const ESTIMATED_RAW_BYTE_SIZE: usize = 4;
+
+impl ButtplugClientMessage {
+  /// Spec version the message type was introduced in. Clients that negotiated an older spec
+  /// version can't send it.
+  pub fn schema_version(&self) -> u32 {
+    let version = match self {
+      ButtplugClientMessage::Ping(_)
+      | ButtplugClientMessage::RequestLog(_)
+      | ButtplugClientMessage::RequestServerInfo(_)
+      | ButtplugClientMessage::StartScanning(_)
+      | ButtplugClientMessage::StopScanning(_)
+      | ButtplugClientMessage::RequestDeviceList(_)
+      | ButtplugClientMessage::StopAllDevices(_)
+      | ButtplugClientMessage::StopDeviceCmd(_)
+      | ButtplugClientMessage::SingleMotorVibrateCmd(_)
+      | ButtplugClientMessage::FleshlightLaunchFW12Cmd(_)
+      | ButtplugClientMessage::LovenseCmd(_)
+      | ButtplugClientMessage::KiirooCmd(_)
+      | ButtplugClientMessage::VorzeA10CycloneCmd(_) => ButtplugMessageSpecVersion::Version0,
+      ButtplugClientMessage::VibrateCmd(_)
+      | ButtplugClientMessage::LinearCmd(_)
+      | ButtplugClientMessage::RotateCmd(_) => ButtplugMessageSpecVersion::Version1,
+      ButtplugClientMessage::RawWriteCmd(_)
+      | ButtplugClientMessage::RawReadCmd(_)
+      | ButtplugClientMessage::RawSubscribeCmd(_)
+      | ButtplugClientMessage::RawUnsubscribeCmd(_)
+      | ButtplugClientMessage::BatteryLevelCmd(_)
+      | ButtplugClientMessage::RSSILevelCmd(_) => ButtplugMessageSpecVersion::Version2,
+      ButtplugClientMessage::RawStreamCmd(_)
+      | ButtplugClientMessage::ScalarCmd(_)
+      | ButtplugClientMessage::PrioritizedScalarCmd(_)
+      | ButtplugClientMessage::SensorReadCmd(_)
+      | ButtplugClientMessage::SensorSubscribeCmd(_)
+      | ButtplugClientMessage::SensorUnsubscribeCmd(_) => ButtplugMessageSpecVersion::Version3,
+    };
+    version as u32
+  }
+
+  /// Returns a rough upper bound of the size of this message serialized to JSON, for pre-sizing
+  /// serialization buffers.
+  pub fn estimated_size(&self) -> usize {
+    ESTIMATED_MESSAGE_BASE_SIZE
+      + match self {
+        ButtplugClientMessage::RequestServerInfo(msg) => msg.client_name().len(),
+        ButtplugClientMessage::VibrateCmd(msg) => msg.speeds().len() * ESTIMATED_SUBCOMMAND_SIZE,
+        ButtplugClientMessage::LinearCmd(msg) => msg.vectors().len() * ESTIMATED_SUBCOMMAND_SIZE,
+        ButtplugClientMessage::RotateCmd(msg) => msg.rotations().len() * ESTIMATED_SUBCOMMAND_SIZE,
+        ButtplugClientMessage::ScalarCmd(msg) => msg.scalars().len() * ESTIMATED_SUBCOMMAND_SIZE,
+        ButtplugClientMessage::PrioritizedScalarCmd(msg) => {
+          msg.scalars().len() * ESTIMATED_SUBCOMMAND_SIZE
+        }
+        ButtplugClientMessage::RawWriteCmd(msg) => msg.data().len() * ESTIMATED_RAW_BYTE_SIZE,
+        ButtplugClientMessage::RawStreamCmd(msg) => msg.data().len() * ESTIMATED_RAW_BYTE_SIZE,
+        ButtplugClientMessage::LovenseCmd(msg) => msg.command().len(),
+        ButtplugClientMessage::KiirooCmd(msg) => msg.command().len(),
+        _ => 0,
+      }
+  }
+}
+
 /// Represents all possible messages a
 /// [ButtplugServer][crate::server::ButtplugServer] can send to a
 /// [ButtplugClient][crate::client::ButtplugClient].
@@ -338,6 +436,8 @@ pub enum ButtplugServerMessage {
   // Deprecated Server Messages
   BatteryLevelReading(BatteryLevelReading),
   RSSILevelReading(RSSILevelReading),
+  // Server event stream notifications
+  LaggedEvents(LaggedEvents),
 }
 
 /// Represents all possible messages a [ButtplugServer][crate::server::ButtplugServer] can send to a
@@ -388,7 +488,7 @@ pub type ButtplugCurrentSpecServerMessage = ButtplugSpecV3ServerMessage;
   FromSpecificButtplugMessage,
   TryFromButtplugClientMessage,
 )]
-#[cfg_attr(feature = "serialize-json", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serialize", derive(Serialize, Deserialize))]
 pub enum ButtplugSpecV3ClientMessage {
   // Handshake messages
   RequestServerInfo(RequestServerInfo),
@@ -404,6 +504,7 @@ pub enum ButtplugSpecV3ClientMessage {
   RotateCmd(RotateCmd),
   RawWriteCmd(RawWriteCmd),
   RawReadCmd(RawReadCmd),
+  RawStreamCmd(RawStreamCmd),
   StopDeviceCmd(StopDeviceCmd),
   RawSubscribeCmd(RawSubscribeCmd),
   RawUnsubscribeCmd(RawUnsubscribeCmd),
@@ -414,6 +515,33 @@ pub enum ButtplugSpecV3ClientMessage {
   SensorUnsubscribeCmd(SensorUnsubscribeCmd),
 }
 
+impl ButtplugSpecV3ClientMessage {
+  /// Same as [ButtplugClientMessage::estimated_size].
+  pub fn estimated_size(&self) -> usize {
+    ESTIMATED_MESSAGE_BASE_SIZE
+      + match self {
+        ButtplugSpecV3ClientMessage::RequestServerInfo(msg) => msg.client_name().len(),
+        ButtplugSpecV3ClientMessage::VibrateCmd(msg) => {
+          msg.speeds().len() * ESTIMATED_SUBCOMMAND_SIZE
+        }
+        ButtplugSpecV3ClientMessage::LinearCmd(msg) => {
+          msg.vectors().len() * ESTIMATED_SUBCOMMAND_SIZE
+        }
+        ButtplugSpecV3ClientMessage::RotateCmd(msg) => {
+          msg.rotations().len() * ESTIMATED_SUBCOMMAND_SIZE
+        }
+        ButtplugSpecV3ClientMessage::ScalarCmd(msg) => {
+          msg.scalars().len() * ESTIMATED_SUBCOMMAND_SIZE
+        }
+        ButtplugSpecV3ClientMessage::RawWriteCmd(msg) => msg.data().len() * ESTIMATED_RAW_BYTE_SIZE,
+        ButtplugSpecV3ClientMessage::RawStreamCmd(msg) => {
+          msg.data().len() * ESTIMATED_RAW_BYTE_SIZE
+        }
+        _ => 0,
+      }
+  }
+}
+
 /// Represents all server-to-client messages in v3 of the Buttplug Spec
 #[derive(
   Debug,
@@ -423,9 +551,8 @@ pub enum ButtplugSpecV3ClientMessage {
   ButtplugMessageValidator,
   ButtplugServerMessageType,
   FromSpecificButtplugMessage,
-  TryFromButtplugServerMessage,
 )]
-#[cfg_attr(feature = "serialize-json", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serialize", derive(Serialize, Deserialize))]
 pub enum ButtplugSpecV3ServerMessage {
   // Status messages
   Ok(Ok),
@@ -443,6 +570,34 @@ pub enum ButtplugSpecV3ServerMessage {
   SensorReading(SensorReading),
 }
 
+// Written out instead of derived, as the derive builds its error with a String, which isn't a
+// ButtplugString in builds without std.
+impl TryFrom<ButtplugServerMessage> for ButtplugSpecV3ServerMessage {
+  type Error = ButtplugMessageError;
+  fn try_from(msg: ButtplugServerMessage) -> Result<Self, ButtplugMessageError> {
+    match msg {
+      ButtplugServerMessage::Ok(msg) => Ok(ButtplugSpecV3ServerMessage::Ok(msg)),
+      ButtplugServerMessage::Error(msg) => Ok(ButtplugSpecV3ServerMessage::Error(msg)),
+      ButtplugServerMessage::ServerInfo(msg) => Ok(ButtplugSpecV3ServerMessage::ServerInfo(msg)),
+      ButtplugServerMessage::DeviceList(msg) => Ok(ButtplugSpecV3ServerMessage::DeviceList(msg)),
+      ButtplugServerMessage::DeviceAdded(msg) => Ok(ButtplugSpecV3ServerMessage::DeviceAdded(msg)),
+      ButtplugServerMessage::DeviceRemoved(msg) => {
+        Ok(ButtplugSpecV3ServerMessage::DeviceRemoved(msg))
+      }
+      ButtplugServerMessage::ScanningFinished(msg) => {
+        Ok(ButtplugSpecV3ServerMessage::ScanningFinished(msg))
+      }
+      ButtplugServerMessage::RawReading(msg) => Ok(ButtplugSpecV3ServerMessage::RawReading(msg)),
+      ButtplugServerMessage::SensorReading(msg) => {
+        Ok(ButtplugSpecV3ServerMessage::SensorReading(msg))
+      }
+      _ => Err(ButtplugMessageError::MessageConversionError(
+        buttplug_string("ButtplugServerMessage cannot be converted to ButtplugSpecV3ServerMessage"),
+      )),
+    }
+  }
+}
+
 impl ButtplugMessageFinalizer for ButtplugSpecV3ServerMessage {
   fn finalize(&mut self) {
     match self {
@@ -465,7 +620,7 @@ impl ButtplugMessageFinalizer for ButtplugSpecV3ServerMessage {
   FromSpecificButtplugMessage,
   TryFromButtplugClientMessage,
 )]
-#[cfg_attr(feature = "serialize-json", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serialize", derive(Serialize, Deserialize))]
 pub enum ButtplugSpecV2ClientMessage {
   // Handshake messages
   RequestServerInfo(RequestServerInfo),
@@ -499,7 +654,7 @@ pub enum ButtplugSpecV2ClientMessage {
   ButtplugMessageFinalizer,
   ButtplugServerMessageType,
 )]
-#[cfg_attr(feature = "serialize-json", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serialize", derive(Serialize, Deserialize))]
 pub enum ButtplugSpecV2ServerMessage {
   // Status messages
   Ok(Ok),
@@ -541,9 +696,9 @@ impl TryFrom<ButtplugServerMessage> for ButtplugSpecV2ServerMessage {
         Ok(ButtplugSpecV2ServerMessage::ScanningFinished(msg))
       }
       _ => Err(ButtplugMessageError::VersionError(
-        "ButtplugServerMessage".to_owned(),
-        format!("{:?}", msg),
-        "ButtplugSpecV2ServerMessage".to_owned(),
+        buttplug_string("ButtplugServerMessage"),
+        format_buttplug_string!("{:?}", msg),
+        buttplug_string("ButtplugSpecV2ServerMessage"),
       )),
     }
   }
@@ -560,7 +715,7 @@ impl TryFrom<ButtplugServerMessage> for ButtplugSpecV2ServerMessage {
   ButtplugMessageFinalizer,
   TryFromButtplugClientMessage,
 )]
-#[cfg_attr(feature = "serialize-json", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serialize", derive(Serialize, Deserialize))]
 pub enum ButtplugSpecV1ClientMessage {
   // Handshake messages
   RequestServerInfo(RequestServerInfo),
@@ -595,7 +750,7 @@ pub enum ButtplugSpecV1ClientMessage {
   ButtplugMessageFinalizer,
   ButtplugServerMessageType,
 )]
-#[cfg_attr(feature = "serialize-json", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serialize", derive(Serialize, Deserialize))]
 pub enum ButtplugSpecV1ServerMessage {
   // Status messages
   Ok(Ok),
@@ -636,9 +791,9 @@ impl TryFrom<ButtplugServerMessage> for ButtplugSpecV1ServerMessage {
         Ok(ButtplugSpecV1ServerMessage::ScanningFinished(msg))
       }
       _ => Err(ButtplugMessageError::VersionError(
-        "ButtplugServerMessage".to_owned(),
-        format!("{:?}", msg),
-        "ButtplugSpecV1ServerMessage".to_owned(),
+        buttplug_string("ButtplugServerMessage"),
+        format_buttplug_string!("{:?}", msg),
+        buttplug_string("ButtplugSpecV1ServerMessage"),
       )),
     }
   }
@@ -655,7 +810,7 @@ impl TryFrom<ButtplugServerMessage> for ButtplugSpecV1ServerMessage {
   ButtplugMessageFinalizer,
   TryFromButtplugClientMessage,
 )]
-#[cfg_attr(feature = "serialize-json", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serialize", derive(Serialize, Deserialize))]
 pub enum ButtplugSpecV0ClientMessage {
   RequestLog(RequestLog),
   Ping(Ping),
@@ -688,7 +843,7 @@ pub enum ButtplugSpecV0ClientMessage {
   ButtplugMessageFinalizer,
   ButtplugServerMessageType,
 )]
-#[cfg_attr(feature = "serialize-json", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serialize", derive(Serialize, Deserialize))]
 pub enum ButtplugSpecV0ServerMessage {
   // Status messages
   Ok(Ok),
@@ -729,9 +884,9 @@ impl TryFrom<ButtplugServerMessage> for ButtplugSpecV0ServerMessage {
         Ok(ButtplugSpecV0ServerMessage::ScanningFinished(msg))
       }
       _ => Err(ButtplugMessageError::VersionError(
-        "ButtplugServerMessage".to_owned(),
-        format!("{:?}", msg),
-        "ButtplugSpecV0ServerMessage".to_owned(),
+        buttplug_string("ButtplugServerMessage"),
+        format_buttplug_string!("{:?}", msg),
+        buttplug_string("ButtplugSpecV0ServerMessage"),
       )),
     }
   }
@@ -770,7 +925,7 @@ pub enum ButtplugDeviceManagerMessageUnion {
   FromSpecificButtplugMessage,
   TryFromButtplugClientMessage,
 )]
-#[cfg_attr(feature = "serialize-json", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serialize", derive(Serialize, Deserialize))]
 pub enum ButtplugDeviceCommandMessageUnion {
   FleshlightLaunchFW12Cmd(FleshlightLaunchFW12Cmd),
   SingleMotorVibrateCmd(SingleMotorVibrateCmd),
@@ -782,13 +937,34 @@ pub enum ButtplugDeviceCommandMessageUnion {
   RotateCmd(RotateCmd),
   RawWriteCmd(RawWriteCmd),
   RawReadCmd(RawReadCmd),
+  RawStreamCmd(RawStreamCmd),
   StopDeviceCmd(StopDeviceCmd),
   RawSubscribeCmd(RawSubscribeCmd),
   RawUnsubscribeCmd(RawUnsubscribeCmd),
   BatteryLevelCmd(BatteryLevelCmd),
   RSSILevelCmd(RSSILevelCmd),
   ScalarCmd(ScalarCmd),
+  PrioritizedScalarCmd(PrioritizedScalarCmd),
   SensorReadCmd(SensorReadCmd),
   SensorSubscribeCmd(SensorSubscribeCmd),
   SensorUnsubscribeCmd(SensorUnsubscribeCmd),
 }
+
+impl ButtplugDeviceCommandMessageUnion {
+  /// Whether the message sets actuator values, as opposed to stopping the device, reading from it
+  /// or accessing its endpoints directly.
+  pub fn is_actuator_command(&self) -> bool {
+    matches!(
+      self,
+      Self::FleshlightLaunchFW12Cmd(_)
+        | Self::SingleMotorVibrateCmd(_)
+        | Self::VorzeA10CycloneCmd(_)
+        | Self::KiirooCmd(_)
+        | Self::VibrateCmd(_)
+        | Self::LinearCmd(_)
+        | Self::RotateCmd(_)
+        | Self::ScalarCmd(_)
+        | Self::PrioritizedScalarCmd(_)
+    )
+  }
+}
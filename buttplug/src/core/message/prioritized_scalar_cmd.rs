@@ -0,0 +1,66 @@
+// Buttplug Rust Source Code File - See https://buttplug.io for more info.
+//
+// Copyright 2016-2023 Nonpolynomial Labs LLC. All rights reserved.
+//
+// Licensed under the BSD 3-Clause license. See LICENSE file in the project root
+// for full license information.
+
+use super::*;
+use getset::{CopyGetters, Getters};
+#[cfg(feature = "serialize")]
+use serde::{Deserialize, Serialize};
+
+/// [ScalarCmd] with a [CommandPriority], so background commands (like a slow heartbeat pulse) can
+/// be sent at a low priority without holding up more important ones to the same device.
+///
+/// This is a server-local extension, not part of any message spec version, so it can't be sent by
+/// remote clients. Applications embedding a [ButtplugServer](crate::server::ButtplugServer) send
+/// it through [ButtplugServer::parse_message](crate::server::ButtplugServer::parse_message).
+#[derive(
+  Debug,
+  Default,
+  ButtplugDeviceMessage,
+  ButtplugMessageFinalizer,
+  PartialEq,
+  Clone,
+  Getters,
+  CopyGetters,
+)]
+#[cfg_attr(feature = "serialize", derive(Serialize, Deserialize))]
+pub struct PrioritizedScalarCmd {
+  #[cfg_attr(feature = "serialize", serde(rename = "Id"))]
+  id: u32,
+  #[cfg_attr(feature = "serialize", serde(rename = "DeviceIndex"))]
+  device_index: u32,
+  #[cfg_attr(feature = "serialize", serde(rename = "Priority"))]
+  #[getset(get_copy = "pub")]
+  priority: CommandPriority,
+  #[cfg_attr(feature = "serialize", serde(rename = "Scalars"))]
+  #[getset(get = "pub")]
+  scalars: Vec<ScalarSubcommand>,
+}
+
+impl PrioritizedScalarCmd {
+  pub fn new(device_index: u32, priority: CommandPriority, scalars: Vec<ScalarSubcommand>) -> Self {
+    Self {
+      id: 1,
+      device_index,
+      priority,
+      scalars,
+    }
+  }
+}
+
+impl ButtplugMessageValidator for PrioritizedScalarCmd {
+  fn is_valid(&self) -> Result<(), ButtplugMessageError> {
+    ScalarCmd::from(self.clone()).is_valid()
+  }
+}
+
+impl From<PrioritizedScalarCmd> for ScalarCmd {
+  fn from(msg: PrioritizedScalarCmd) -> Self {
+    let mut scalar_cmd = ScalarCmd::new(msg.device_index, msg.scalars);
+    scalar_cmd.set_id(msg.id);
+    scalar_cmd
+  }
+}
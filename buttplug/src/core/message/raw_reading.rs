@@ -7,7 +7,7 @@
 
 use super::*;
 use getset::{CopyGetters, Getters};
-#[cfg(feature = "serialize-json")]
+#[cfg(feature = "serialize")]
 use serde::{Deserialize, Serialize};
 
 // This message can have an Id of 0, as it can be emitted as part of a
@@ -23,18 +23,27 @@ use serde::{Deserialize, Serialize};
   Getters,
   CopyGetters,
 )]
-#[cfg_attr(feature = "serialize-json", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serialize", derive(Serialize, Deserialize))]
 pub struct RawReading {
-  #[cfg_attr(feature = "serialize-json", serde(rename = "Id"))]
+  #[cfg_attr(feature = "serialize", serde(rename = "Id"))]
   id: u32,
-  #[cfg_attr(feature = "serialize-json", serde(rename = "DeviceIndex"))]
+  #[cfg_attr(feature = "serialize", serde(rename = "DeviceIndex"))]
   device_index: u32,
-  #[cfg_attr(feature = "serialize-json", serde(rename = "Endpoint"))]
+  #[cfg_attr(feature = "serialize", serde(rename = "Endpoint"))]
   #[getset(get_copy = "pub")]
   endpoint: Endpoint,
-  #[cfg_attr(feature = "serialize-json", serde(rename = "Data"))]
+  #[cfg_attr(feature = "serialize", serde(rename = "Data"))]
   #[getset(get = "pub")]
   data: Vec<u8>,
+  /// True if this reading was split to fit the connector's message size limit, and more of its
+  /// data follows in the next reading with the same id, device and endpoint.
+  #[cfg_attr(
+    feature = "serialize",
+    serde(rename = "Continued"),
+    serde(default, skip_serializing_if = "core::ops::Not::not")
+  )]
+  #[getset(get_copy = "pub")]
+  continued: bool,
 }
 
 impl RawReading {
@@ -44,8 +53,14 @@ impl RawReading {
       device_index,
       endpoint,
       data,
+      continued: false,
     }
   }
+
+  /// Marks the reading as one piece of a larger reading, see [RawReading::continued].
+  pub fn set_continued(&mut self, continued: bool) {
+    self.continued = continued;
+  }
 }
 
 #[cfg(feature = "serialize-json")]
@@ -74,4 +89,18 @@ mod test {
       "{\"RawReading\":{\"Id\":0,\"DeviceIndex\":0,\"Endpoint\":\"tx\",\"Data\":[0]}}";
     assert_eq!(js, endpoint_str);
   }
+
+  #[test]
+  fn test_continued_serialize() {
+    let mut reading = RawReading::new(0, Endpoint::Tx, vec![0]);
+    reading.set_continued(true);
+    let union = ButtplugCurrentSpecServerMessage::RawReading(reading);
+    let js = serde_json::to_string(&union).expect("Infallible serialization.");
+    let continued_str = "{\"RawReading\":{\"Id\":0,\"DeviceIndex\":0,\"Endpoint\":\"tx\",\
+                         \"Data\":[0],\"Continued\":true}}";
+    assert_eq!(js, continued_str);
+    let deserialized: ButtplugCurrentSpecServerMessage =
+      serde_json::from_str(continued_str).expect("Infallible deserialization.");
+    assert_eq!(deserialized, union);
+  }
 }
@@ -0,0 +1,62 @@
+// Buttplug Rust Source Code File - See https://buttplug.io for more info.
+//
+// Copyright 2016-2023 Nonpolynomial Labs LLC. All rights reserved.
+//
+// Licensed under the BSD 3-Clause license. See LICENSE file in the project root
+// for full license information.
+
+use super::*;
+use getset::{CopyGetters, Getters};
+#[cfg(feature = "serialize")]
+use serde::{Deserialize, Serialize};
+
+/// One chunk of a raw data transfer too large to send in a single [RawWriteCmd]. Chunks for an
+/// endpoint are buffered by the server in order, starting at chunk 0, and written to the device as
+/// a single write once the chunk with `is_last` set arrives.
+#[derive(
+  Debug, ButtplugDeviceMessage, ButtplugMessageFinalizer, PartialEq, Eq, Clone, Getters, CopyGetters,
+)]
+#[cfg_attr(feature = "serialize", derive(Serialize, Deserialize))]
+pub struct RawStreamCmd {
+  #[cfg_attr(feature = "serialize", serde(rename = "Id"))]
+  id: u32,
+  #[cfg_attr(feature = "serialize", serde(rename = "DeviceIndex"))]
+  device_index: u32,
+  #[cfg_attr(feature = "serialize", serde(rename = "Endpoint"))]
+  #[getset(get_copy = "pub")]
+  endpoint: Endpoint,
+  #[cfg_attr(feature = "serialize", serde(rename = "ChunkIndex"))]
+  #[getset(get_copy = "pub")]
+  chunk_index: u32,
+  #[cfg_attr(feature = "serialize", serde(rename = "IsLast"))]
+  #[getset(get_copy = "pub")]
+  is_last: bool,
+  #[cfg_attr(feature = "serialize", serde(rename = "Data"))]
+  #[getset(get = "pub")]
+  data: Vec<u8>,
+}
+
+impl RawStreamCmd {
+  pub fn new(
+    device_index: u32,
+    endpoint: Endpoint,
+    chunk_index: u32,
+    is_last: bool,
+    data: &[u8],
+  ) -> Self {
+    Self {
+      id: 1,
+      device_index,
+      endpoint,
+      chunk_index,
+      is_last,
+      data: data.to_vec(),
+    }
+  }
+}
+
+impl ButtplugMessageValidator for RawStreamCmd {
+  fn is_valid(&self) -> Result<(), ButtplugMessageError> {
+    self.is_not_system_id(self.id)
+  }
+}
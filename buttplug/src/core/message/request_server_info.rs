@@ -7,41 +7,56 @@
 
 use super::*;
 use getset::{CopyGetters, Getters};
-#[cfg(feature = "serialize-json")]
+#[cfg(feature = "serialize")]
 use serde::{Deserialize, Serialize};
 
+#[cfg(feature = "serialize")]
 fn return_version0() -> ButtplugMessageSpecVersion {
   ButtplugMessageSpecVersion::Version0
 }
 #[derive(
   Debug, ButtplugMessage, ButtplugMessageFinalizer, Clone, PartialEq, Eq, Getters, CopyGetters,
 )]
-#[cfg_attr(feature = "serialize-json", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serialize", derive(Serialize, Deserialize))]
 pub struct RequestServerInfo {
-  #[cfg_attr(feature = "serialize-json", serde(rename = "Id"))]
+  #[cfg_attr(feature = "serialize", serde(rename = "Id"))]
   id: u32,
-  #[cfg_attr(feature = "serialize-json", serde(rename = "ClientName"))]
+  #[cfg_attr(feature = "serialize", serde(rename = "ClientName"))]
   #[getset(get = "pub")]
-  client_name: String,
+  client_name: ButtplugString,
   // Default for this message is set to 0, as this field didn't exist in the
   // first version of the protocol.
   #[cfg_attr(
-    feature = "serialize-json",
+    feature = "serialize",
     serde(rename = "MessageVersion"),
     serde(default = "return_version0")
   )]
   #[getset(get_copy = "pub")]
   message_version: ButtplugMessageSpecVersion,
+  // Only sent by clients that can compress messages, so older servers never see it.
+  #[cfg_attr(
+    feature = "serialize",
+    serde(rename = "CompressionSupported"),
+    serde(default, skip_serializing_if = "Vec::is_empty")
+  )]
+  #[getset(get = "pub")]
+  compression_supported: Vec<CompressionAlgorithm>,
 }
 
 impl RequestServerInfo {
   pub fn new(client_name: &str, message_version: ButtplugMessageSpecVersion) -> Self {
     Self {
       id: 1,
-      client_name: client_name.to_string(),
+      client_name: buttplug_string(client_name),
       message_version,
+      compression_supported: vec![],
     }
   }
+
+  /// Offers the server these algorithms for compressing messages after the handshake.
+  pub fn set_compression_supported(&mut self, algorithms: Vec<CompressionAlgorithm>) {
+    self.compression_supported = algorithms;
+  }
 }
 
 impl ButtplugMessageValidator for RequestServerInfo {
@@ -68,6 +83,7 @@ mod test {
       id: 1,
       client_name: "Test Client".to_owned(),
       message_version: ButtplugMessageSpecVersion::Version2,
+      compression_supported: vec![],
     };
     assert_eq!(
       serde_json::from_str::<RequestServerInfo>(new_json).expect("Test unwrap"),
@@ -88,6 +104,7 @@ mod test {
       id: 1,
       client_name: "Test Client".to_owned(),
       message_version: ButtplugMessageSpecVersion::Version0,
+      compression_supported: vec![],
     };
     assert_eq!(
       serde_json::from_str::<RequestServerInfo>(old_json).expect("Test unwrap"),
@@ -7,18 +7,18 @@
 
 use super::*;
 pub use getset::{CopyGetters, Getters};
-#[cfg(feature = "serialize-json")]
+#[cfg(feature = "serialize")]
 use serde::{Deserialize, Serialize};
 
 #[derive(Debug, PartialEq, Clone, CopyGetters)]
-#[cfg_attr(feature = "serialize-json", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serialize", derive(Serialize, Deserialize))]
 #[getset(get_copy = "pub")]
 pub struct RotationSubcommand {
-  #[cfg_attr(feature = "serialize-json", serde(rename = "Index"))]
+  #[cfg_attr(feature = "serialize", serde(rename = "Index"))]
   index: u32,
-  #[cfg_attr(feature = "serialize-json", serde(rename = "Speed"))]
+  #[cfg_attr(feature = "serialize", serde(rename = "Speed"))]
   speed: f64,
-  #[cfg_attr(feature = "serialize-json", serde(rename = "Clockwise"))]
+  #[cfg_attr(feature = "serialize", serde(rename = "Clockwise"))]
   clockwise: bool,
 }
 
@@ -33,14 +33,14 @@ impl RotationSubcommand {
 }
 
 #[derive(Debug, ButtplugDeviceMessage, ButtplugMessageFinalizer, PartialEq, Clone, Getters)]
-#[cfg_attr(feature = "serialize-json", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serialize", derive(Serialize, Deserialize))]
 pub struct RotateCmd {
-  #[cfg_attr(feature = "serialize-json", serde(rename = "Id"))]
+  #[cfg_attr(feature = "serialize", serde(rename = "Id"))]
   id: u32,
-  #[cfg_attr(feature = "serialize-json", serde(rename = "DeviceIndex"))]
+  #[cfg_attr(feature = "serialize", serde(rename = "DeviceIndex"))]
   device_index: u32,
   #[getset(get = "pub")]
-  #[cfg_attr(feature = "serialize-json", serde(rename = "Rotations"))]
+  #[cfg_attr(feature = "serialize", serde(rename = "Rotations"))]
   #[getset(get = "pub")]
   rotations: Vec<RotationSubcommand>,
 }
@@ -61,9 +61,10 @@ impl ButtplugMessageValidator for RotateCmd {
     for rotation in &self.rotations {
       self.is_in_command_range(
         rotation.speed,
-        format!(
+        format_buttplug_string!(
           "Speed {} for RotateCmd index {} is invalid. Speed should be a value between 0.0 and 1.0",
-          rotation.speed, rotation.index
+          rotation.speed,
+          rotation.index
         ),
       )?;
     }
@@ -7,19 +7,19 @@
 
 use super::*;
 use getset::CopyGetters;
-#[cfg(feature = "serialize-json")]
+#[cfg(feature = "serialize")]
 use serde::{Deserialize, Serialize};
 
 #[derive(
   Debug, ButtplugDeviceMessage, ButtplugMessageFinalizer, PartialEq, Eq, Clone, CopyGetters,
 )]
-#[cfg_attr(feature = "serialize-json", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serialize", derive(Serialize, Deserialize))]
 pub struct RSSILevelReading {
-  #[cfg_attr(feature = "serialize-json", serde(rename = "Id"))]
+  #[cfg_attr(feature = "serialize", serde(rename = "Id"))]
   id: u32,
-  #[cfg_attr(feature = "serialize-json", serde(rename = "DeviceIndex"))]
+  #[cfg_attr(feature = "serialize", serde(rename = "DeviceIndex"))]
   device_index: u32,
-  #[cfg_attr(feature = "serialize-json", serde(rename = "RSSILevel"))]
+  #[cfg_attr(feature = "serialize", serde(rename = "RSSILevel"))]
   #[getset(get_copy = "pub")]
   rssi_level: i32,
 }
@@ -38,10 +38,12 @@ impl ButtplugMessageValidator for RSSILevelReading {
   fn is_valid(&self) -> Result<(), ButtplugMessageError> {
     self.is_not_system_id(self.id)?;
     if self.rssi_level > 0 {
-      Err(ButtplugMessageError::InvalidMessageContents(format!(
-        "RSSI level {} is invalid. RSSI Levels are always negative.",
-        self.rssi_level
-      )))
+      Err(ButtplugMessageError::InvalidMessageContents(
+        format_buttplug_string!(
+          "RSSI level {} is invalid. RSSI Levels are always negative.",
+          self.rssi_level
+        ),
+      ))
     } else {
       Ok(())
     }
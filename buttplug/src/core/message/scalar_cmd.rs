@@ -7,19 +7,19 @@
 
 use super::*;
 use getset::{CopyGetters, Getters};
-#[cfg(feature = "serialize-json")]
+#[cfg(feature = "serialize")]
 use serde::{Deserialize, Serialize};
 
 /// Generic command for setting a level (single magnitude value) of a device feature.
 #[derive(Debug, PartialEq, Clone, CopyGetters)]
-#[cfg_attr(feature = "serialize-json", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serialize", derive(Serialize, Deserialize))]
 #[getset(get_copy = "pub")]
 pub struct ScalarSubcommand {
-  #[cfg_attr(feature = "serialize-json", serde(rename = "Index"))]
+  #[cfg_attr(feature = "serialize", serde(rename = "Index"))]
   index: u32,
-  #[cfg_attr(feature = "serialize-json", serde(rename = "Scalar"))]
+  #[cfg_attr(feature = "serialize", serde(rename = "Scalar"))]
   scalar: f64,
-  #[cfg_attr(feature = "serialize-json", serde(rename = "ActuatorType"))]
+  #[cfg_attr(feature = "serialize", serde(rename = "ActuatorType"))]
   actuator_type: ActuatorType,
 }
 
@@ -36,13 +36,13 @@ impl ScalarSubcommand {
 #[derive(
   Debug, Default, ButtplugDeviceMessage, ButtplugMessageFinalizer, PartialEq, Clone, Getters,
 )]
-#[cfg_attr(feature = "serialize-json", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serialize", derive(Serialize, Deserialize))]
 pub struct ScalarCmd {
-  #[cfg_attr(feature = "serialize-json", serde(rename = "Id"))]
+  #[cfg_attr(feature = "serialize", serde(rename = "Id"))]
   id: u32,
-  #[cfg_attr(feature = "serialize-json", serde(rename = "DeviceIndex"))]
+  #[cfg_attr(feature = "serialize", serde(rename = "DeviceIndex"))]
   device_index: u32,
-  #[cfg_attr(feature = "serialize-json", serde(rename = "Scalars"))]
+  #[cfg_attr(feature = "serialize", serde(rename = "Scalars"))]
   #[getset(get = "pub")]
   scalars: Vec<ScalarSubcommand>,
 }
@@ -63,9 +63,10 @@ impl ButtplugMessageValidator for ScalarCmd {
     for level in &self.scalars {
       self.is_in_command_range(
         level.scalar,
-        format!(
+        format_buttplug_string!(
           "Level {} for ScalarCmd index {} is invalid. Level should be a value between 0.0 and 1.0",
-          level.scalar, level.index
+          level.scalar,
+          level.index
         ),
       )?;
     }
@@ -5,7 +5,12 @@
 // Licensed under the BSD 3-Clause license. See LICENSE file in the project root
 // for full license information.
 
-use super::{ButtplugMessageSerializer, ButtplugSerializedMessage, ButtplugSerializerError};
+use super::{
+  ButtplugMessageSerializer,
+  ButtplugSerializedMessage,
+  ButtplugSerializerError,
+  CodecType,
+};
 use crate::core::{
   errors::{ButtplugError, ButtplugHandshakeError},
   message::{
@@ -25,12 +30,13 @@ use crate::core::{
     ButtplugSpecV2ServerMessage,
     ButtplugSpecV3ClientMessage,
     ButtplugSpecV3ServerMessage,
+    MessageTransformer,
   },
 };
 use jsonschema::JSONSchema;
 use once_cell::sync::OnceCell;
 use serde::{Deserialize, Serialize};
-use std::{convert::TryFrom, fmt::Debug};
+use std::{convert::TryFrom, fmt::Debug, sync::Arc};
 
 static MESSAGE_JSON_SCHEMA: &str =
   include_str!("../../../../buttplug-schema/schema/buttplug-schema.json");
@@ -44,6 +50,8 @@ pub fn create_message_validator() -> JSONSchema {
 pub struct ButtplugServerJSONSerializer {
   pub(super) message_version: OnceCell<message::ButtplugMessageSpecVersion>,
   validator: JSONSchema,
+  pretty_print: bool,
+  message_transformers: Vec<Arc<dyn MessageTransformer>>,
 }
 
 impl Default for ButtplugServerJSONSerializer {
@@ -51,6 +59,8 @@ impl Default for ButtplugServerJSONSerializer {
     Self {
       message_version: OnceCell::new(),
       validator: create_message_validator(),
+      pretty_print: false,
+      message_transformers: vec![],
     }
   }
 }
@@ -62,6 +72,16 @@ impl ButtplugServerJSONSerializer {
       .set(*version)
       .expect("This should only ever be called once.");
   }
+
+  /// Run the JSON form of a packet through all transformers, in order.
+  fn transform_json(&self, msg: &str) -> Result<String, ButtplugSerializerError> {
+    let mut value: serde_json::Value = serde_json::from_str(msg)
+      .map_err(|e| ButtplugSerializerError::JsonSerializerError(format!("{:?}", e)))?;
+    for transformer in &self.message_transformers {
+      value = transformer.transform_inbound_json(value);
+    }
+    Ok(value.to_string())
+  }
 }
 
 /// Returns the message as a string in Buttplug JSON Protocol format.
@@ -79,6 +99,24 @@ where
   serde_json::to_string(msg).expect("Infallible serialization")
 }
 
+/// Same as [vec_to_protocol_json], but writes into a buffer pre-allocated to `capacity` bytes.
+pub fn vec_to_protocol_json_with_capacity<T>(msg: &[T], capacity: usize) -> String
+where
+  T: ButtplugMessage + Serialize + Deserialize<'static>,
+{
+  let mut buf = Vec::with_capacity(capacity);
+  serde_json::to_writer(&mut buf, msg).expect("Infallible serialization");
+  String::from_utf8(buf).expect("serde_json always writes valid UTF-8")
+}
+
+/// Same as [vec_to_protocol_json], but indented for readability.
+pub fn vec_to_pretty_protocol_json<T>(msg: &[T]) -> String
+where
+  T: ButtplugMessage + Serialize + Deserialize<'static>,
+{
+  serde_json::to_string_pretty(msg).expect("Infallible serialization")
+}
+
 pub fn deserialize_to_message<T>(
   validator: &JSONSchema,
   msg: &str,
@@ -123,7 +161,19 @@ where
 fn serialize_to_version(
   version: ButtplugMessageSpecVersion,
   msgs: &[ButtplugServerMessage],
+  pretty_print: bool,
 ) -> ButtplugSerializedMessage {
+  fn to_json<T>(msg_vec: &[T], pretty_print: bool) -> String
+  where
+    T: ButtplugMessage + Serialize + Deserialize<'static>,
+  {
+    if pretty_print {
+      vec_to_pretty_protocol_json(msg_vec)
+    } else {
+      vec_to_protocol_json(msg_vec)
+    }
+  }
+
   ButtplugSerializedMessage::Text(match version {
     ButtplugMessageSpecVersion::Version0 => {
       let msg_vec: Vec<ButtplugSpecV0ServerMessage> = msgs
@@ -136,7 +186,7 @@ fn serialize_to_version(
           ),
         })
         .collect();
-      vec_to_protocol_json(&msg_vec)
+      to_json(&msg_vec, pretty_print)
     }
     ButtplugMessageSpecVersion::Version1 => {
       let msg_vec: Vec<ButtplugSpecV1ServerMessage> = msgs
@@ -149,7 +199,7 @@ fn serialize_to_version(
           ),
         })
         .collect();
-      vec_to_protocol_json(&msg_vec)
+      to_json(&msg_vec, pretty_print)
     }
     ButtplugMessageSpecVersion::Version2 => {
       let msg_vec: Vec<ButtplugSpecV2ServerMessage> = msgs
@@ -160,7 +210,7 @@ fn serialize_to_version(
           Err(err) => ButtplugSpecV2ServerMessage::Error(ButtplugError::from(err).into()),
         })
         .collect();
-      vec_to_protocol_json(&msg_vec)
+      to_json(&msg_vec, pretty_print)
     }
     ButtplugMessageSpecVersion::Version3 => {
       let msg_vec: Vec<ButtplugSpecV3ServerMessage> = msgs
@@ -171,7 +221,7 @@ fn serialize_to_version(
           Err(err) => ButtplugSpecV3ServerMessage::Error(ButtplugError::from(err).into()),
         })
         .collect();
-      vec_to_protocol_json(&msg_vec)
+      to_json(&msg_vec, pretty_print)
     }
   })
 }
@@ -189,6 +239,13 @@ impl ButtplugMessageSerializer for ButtplugServerJSONSerializer {
     } else {
       return Err(ButtplugSerializerError::BinaryDeserializationError);
     };
+    let transformed_msg;
+    let msg = if self.message_transformers.is_empty() {
+      msg
+    } else {
+      transformed_msg = self.transform_json(msg)?;
+      &transformed_msg
+    };
     // If we don't have a message version yet, we need to parse this as a
     // RequestServerInfo message to get the version. RequestServerInfo can
     // always be parsed as the latest message version, as we keep it
@@ -249,13 +306,17 @@ impl ButtplugMessageSerializer for ButtplugServerJSONSerializer {
 
   fn serialize(&self, msgs: &[ButtplugServerMessage]) -> ButtplugSerializedMessage {
     if let Some(version) = self.message_version.get() {
-      serialize_to_version(*version, msgs)
+      serialize_to_version(*version, msgs, self.pretty_print)
     } else {
       // In the rare event that there is a problem with the
       // RequestServerInfo message (so we can't set up our known spec
       // version), just encode to the latest and return.
       if let ButtplugServerMessage::Error(_) = &msgs[0] {
-        serialize_to_version(ButtplugMessageSpecVersion::Version3, msgs)
+        serialize_to_version(
+          ButtplugMessageSpecVersion::Version3,
+          msgs,
+          self.pretty_print,
+        )
       } else {
         // If we don't even have enough info to know which message
         // version to convert to, consider this a handshake error.
@@ -267,6 +328,18 @@ impl ButtplugMessageSerializer for ButtplugServerJSONSerializer {
       }
     }
   }
+
+  fn set_pretty_print(&mut self, pretty_print: bool) {
+    self.pretty_print = pretty_print;
+  }
+
+  fn set_message_transformers(&mut self, transformers: Vec<Arc<dyn MessageTransformer>>) {
+    self.message_transformers = transformers;
+  }
+
+  fn codec_type() -> CodecType {
+    CodecType::Json
+  }
 }
 
 pub struct ButtplugClientJSONSerializerImpl {
@@ -321,7 +394,13 @@ impl ButtplugMessageSerializer for ButtplugClientJSONSerializer {
   }
 
   fn serialize(&self, msg: &[Self::Outbound]) -> ButtplugSerializedMessage {
-    self.serializer_impl.serialize(msg)
+    // Array brackets, plus a comma between each message.
+    let capacity = msg.iter().map(|m| m.estimated_size() + 1).sum::<usize>() + 2;
+    ButtplugSerializedMessage::Text(vec_to_protocol_json_with_capacity(msg, capacity))
+  }
+
+  fn codec_type() -> CodecType {
+    CodecType::Json
   }
 }
 
@@ -363,6 +442,99 @@ mod test {
     assert!(msg.is_err());
   }
 
+  #[test]
+  fn test_message_transformers() {
+    let json = r#"[{
+            "requestserverinfo": {
+                "Id": 1,
+                "ClientName": "Test Client",
+                "MessageVersion": 3
+            }
+        }]"#;
+    let mut serializer = ButtplugServerJSONSerializer::default();
+    assert!(serializer
+      .deserialize(&ButtplugSerializedMessage::Text(json.to_owned()))
+      .is_err());
+    serializer
+      .set_message_transformers(vec![Arc::new(message::NormalizeCaseTransformer::default())]);
+    let msgs = serializer
+      .deserialize(&ButtplugSerializedMessage::Text(json.to_owned()))
+      .expect("Transformer should fix message type case");
+    assert!(matches!(
+      msgs[0],
+      ButtplugClientMessage::RequestServerInfo(_)
+    ));
+  }
+
+  #[test]
+  fn test_prioritized_scalar_cmd_not_accepted_over_the_wire() {
+    let serializer = ButtplugServerJSONSerializer::default();
+    let handshake = r#"[{
+            "RequestServerInfo": {
+                "Id": 1,
+                "ClientName": "Test Client",
+                "MessageVersion": 3
+            }
+        }]"#;
+    serializer
+      .deserialize(&ButtplugSerializedMessage::Text(handshake.to_owned()))
+      .expect("Infallible deserialization");
+    let json = r#"[{
+            "PrioritizedScalarCmd": {
+                "Id": 2,
+                "DeviceIndex": 0,
+                "Priority": "High",
+                "Scalars": [{ "Index": 0, "Scalar": 0.5, "ActuatorType": "Vibrate" }]
+            }
+        }]"#;
+    assert!(serializer
+      .deserialize(&ButtplugSerializedMessage::Text(json.to_owned()))
+      .is_err());
+  }
+
+  #[test]
+  fn test_pretty_print_messages() {
+    let mut serializer = ButtplugServerJSONSerializer::default();
+    serializer.force_message_version(&BUTTPLUG_CURRENT_MESSAGE_SPEC_VERSION);
+    let msgs: Vec<ButtplugServerMessage> = vec![message::Ok::new(1).into()];
+    let minified = serializer.serialize(&msgs);
+    serializer.set_pretty_print(true);
+    let pretty = serializer.serialize(&msgs);
+    match (minified, pretty) {
+      (ButtplugSerializedMessage::Text(minified), ButtplugSerializedMessage::Text(pretty)) => {
+        assert!(!minified.contains('\n'));
+        assert!(pretty.contains('\n'));
+        let minified_value: serde_json::Value = serde_json::from_str(&minified).unwrap();
+        let pretty_value: serde_json::Value = serde_json::from_str(&pretty).unwrap();
+        assert_eq!(minified_value, pretty_value);
+      }
+      _ => panic!("JSON serializer should always return text"),
+    }
+  }
+
+  #[test]
+  fn test_client_message_estimated_size() {
+    let msgs: Vec<ButtplugCurrentSpecClientMessage> = vec![
+      message::ScalarCmd::new(
+        0,
+        (0..16)
+          .map(|i| message::ScalarSubcommand::new(i, 0.123456789, message::ActuatorType::Vibrate))
+          .collect(),
+      )
+      .into(),
+      message::RawWriteCmd::new(0, message::Endpoint::Tx, &[255; 64], true).into(),
+      RequestServerInfo::new("test client", BUTTPLUG_CURRENT_MESSAGE_SPEC_VERSION).into(),
+    ];
+    for msg in &msgs {
+      let serialized = vec_to_protocol_json(std::slice::from_ref(msg));
+      assert!(
+        msg.estimated_size() >= serialized.len(),
+        "{} is larger than its estimate",
+        serialized
+      );
+    }
+  }
+
   #[test]
   fn test_client_incorrect_messages() {
     let incorrect_incoming_messages = vec![
@@ -16,7 +16,9 @@ pub use json_serializer::{
   ButtplugServerJSONSerializer,
 };
 
+use super::MessageTransformer;
 use serde::{Deserialize, Serialize};
+use std::sync::Arc;
 use thiserror::Error;
 pub type ButtplugSerializerResult<T> = Result<T, ButtplugSerializerError>;
 
@@ -37,6 +39,17 @@ pub enum ButtplugSerializerError {
   MessageSpecVersionNotReceived,
 }
 
+/// Wire format used to exchange messages with the other side of a connection.
+#[derive(Debug, Display, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum CodecType {
+  /// Buttplug JSON protocol.
+  Json,
+  /// Messages are passed as rust types, with no serialization (e.g. in-process connectors).
+  Unserialized,
+  /// A serializer outside of this library.
+  Custom,
+}
+
 #[derive(Debug, Display, Clone, PartialEq, Eq)]
 pub enum ButtplugSerializedMessage {
   Text(String),
@@ -63,4 +76,16 @@ pub trait ButtplugMessageSerializer: Default + Sync + Send {
     msg: &ButtplugSerializedMessage,
   ) -> ButtplugSerializerResult<Vec<Self::Inbound>>;
   fn serialize(&self, msg: &[Self::Outbound]) -> ButtplugSerializedMessage;
+  /// Enables human-readable output, for serializers that support it. This is a debugging aid, as
+  /// it has a cost at high message rates. Serializers that have no such mode ignore it.
+  fn set_pretty_print(&mut self, _pretty_print: bool) {
+  }
+  /// Sets transformers to run on incoming messages before they are deserialized. Serializers that
+  /// have no intermediate format to transform ignore this.
+  fn set_message_transformers(&mut self, _transformers: Vec<Arc<dyn MessageTransformer>>) {
+  }
+  /// Wire format this serializer produces.
+  fn codec_type() -> CodecType {
+    CodecType::Custom
+  }
 }
@@ -7,25 +7,33 @@
 
 use super::*;
 use getset::{CopyGetters, Getters};
-#[cfg(feature = "serialize-json")]
+#[cfg(feature = "serialize")]
 use serde::{Deserialize, Serialize};
 
 #[derive(
   Debug, ButtplugMessage, ButtplugMessageFinalizer, PartialEq, Eq, Clone, Getters, CopyGetters,
 )]
-#[cfg_attr(feature = "serialize-json", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serialize", derive(Serialize, Deserialize))]
 pub struct ServerInfo {
-  #[cfg_attr(feature = "serialize-json", serde(rename = "Id"))]
+  #[cfg_attr(feature = "serialize", serde(rename = "Id"))]
   id: u32,
-  #[cfg_attr(feature = "serialize-json", serde(rename = "MessageVersion"))]
+  #[cfg_attr(feature = "serialize", serde(rename = "MessageVersion"))]
   #[getset(get_copy = "pub")]
   message_version: ButtplugMessageSpecVersion,
-  #[cfg_attr(feature = "serialize-json", serde(rename = "MaxPingTime"))]
+  #[cfg_attr(feature = "serialize", serde(rename = "MaxPingTime"))]
   #[getset(get_copy = "pub")]
   max_ping_time: u32,
-  #[cfg_attr(feature = "serialize-json", serde(rename = "ServerName"))]
+  #[cfg_attr(feature = "serialize", serde(rename = "ServerName"))]
   #[getset(get = "pub")]
-  server_name: String,
+  server_name: ButtplugString,
+  // Only set when the client offered compression, so older clients never see it.
+  #[cfg_attr(
+    feature = "serialize",
+    serde(rename = "CompressionEnabled"),
+    serde(default, skip_serializing_if = "Option::is_none")
+  )]
+  #[getset(get_copy = "pub")]
+  compression_enabled: Option<CompressionAlgorithm>,
 }
 
 impl ServerInfo {
@@ -38,9 +46,15 @@ impl ServerInfo {
       id: 1,
       message_version,
       max_ping_time,
-      server_name: server_name.to_string(),
+      server_name: buttplug_string(server_name),
+      compression_enabled: None,
     }
   }
+
+  /// Tells the client that messages after this one will be compressed with `algorithm`.
+  pub fn set_compression_enabled(&mut self, algorithm: CompressionAlgorithm) {
+    self.compression_enabled = Some(algorithm);
+  }
 }
 
 impl ButtplugMessageValidator for ServerInfo {
@@ -52,28 +66,28 @@ impl ButtplugMessageValidator for ServerInfo {
 #[derive(
   Debug, ButtplugMessage, ButtplugMessageFinalizer, PartialEq, Eq, Clone, Getters, CopyGetters,
 )]
-#[cfg_attr(feature = "serialize-json", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serialize", derive(Serialize, Deserialize))]
 pub struct ServerInfoV0 {
-  #[cfg_attr(feature = "serialize-json", serde(rename = "Id"))]
+  #[cfg_attr(feature = "serialize", serde(rename = "Id"))]
   id: u32,
-  #[cfg_attr(feature = "serialize-json", serde(rename = "MajorVersion"))]
+  #[cfg_attr(feature = "serialize", serde(rename = "MajorVersion"))]
   #[getset(get_copy = "pub")]
   major_version: u32,
-  #[cfg_attr(feature = "serialize-json", serde(rename = "MinorVersion"))]
+  #[cfg_attr(feature = "serialize", serde(rename = "MinorVersion"))]
   #[getset(get_copy = "pub")]
   minor_version: u32,
-  #[cfg_attr(feature = "serialize-json", serde(rename = "BuildVersion"))]
+  #[cfg_attr(feature = "serialize", serde(rename = "BuildVersion"))]
   #[getset(get_copy = "pub")]
   build_version: u32,
-  #[cfg_attr(feature = "serialize-json", serde(rename = "MessageVersion"))]
+  #[cfg_attr(feature = "serialize", serde(rename = "MessageVersion"))]
   #[getset(get_copy = "pub")]
   message_version: ButtplugMessageSpecVersion,
-  #[cfg_attr(feature = "serialize-json", serde(rename = "MaxPingTime"))]
+  #[cfg_attr(feature = "serialize", serde(rename = "MaxPingTime"))]
   #[getset(get_copy = "pub")]
   max_ping_time: u32,
-  #[cfg_attr(feature = "serialize-json", serde(rename = "ServerName"))]
+  #[cfg_attr(feature = "serialize", serde(rename = "ServerName"))]
   #[getset(get = "pub")]
-  server_name: String,
+  server_name: ButtplugString,
 }
 
 impl ServerInfoV0 {
@@ -89,7 +103,7 @@ impl ServerInfoV0 {
       build_version: 0,
       message_version,
       max_ping_time,
-      server_name: server_name.to_string(),
+      server_name: buttplug_string(server_name),
     }
   }
 }
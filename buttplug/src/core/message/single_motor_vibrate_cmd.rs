@@ -7,17 +7,17 @@
 
 use super::*;
 use getset::CopyGetters;
-#[cfg(feature = "serialize-json")]
+#[cfg(feature = "serialize")]
 use serde::{Deserialize, Serialize};
 
 #[derive(Debug, ButtplugDeviceMessage, ButtplugMessageFinalizer, PartialEq, Clone, CopyGetters)]
-#[cfg_attr(feature = "serialize-json", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serialize", derive(Serialize, Deserialize))]
 pub struct SingleMotorVibrateCmd {
-  #[cfg_attr(feature = "serialize-json", serde(rename = "Id"))]
+  #[cfg_attr(feature = "serialize", serde(rename = "Id"))]
   id: u32,
-  #[cfg_attr(feature = "serialize-json", serde(rename = "DeviceIndex"))]
+  #[cfg_attr(feature = "serialize", serde(rename = "DeviceIndex"))]
   device_index: u32,
-  #[cfg_attr(feature = "serialize-json", serde(rename = "Speed"))]
+  #[cfg_attr(feature = "serialize", serde(rename = "Speed"))]
   #[getset(get_copy = "pub")]
   speed: f64,
 }
@@ -37,7 +37,7 @@ impl ButtplugMessageValidator for SingleMotorVibrateCmd {
     self.is_not_system_id(self.id)?;
     self.is_in_command_range(
       self.speed,
-      format!(
+      format_buttplug_string!(
         "SingleMotorVibrateCmd Speed {} is invalid. Valid speeds are 0.0-1.0.",
         self.speed
       ),
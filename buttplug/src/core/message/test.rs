@@ -7,21 +7,21 @@
 
 use super::*;
 use getset::Getters;
-#[cfg(feature = "serialize-json")]
+#[cfg(feature = "serialize")]
 use serde::{Deserialize, Serialize};
 
 #[derive(
   Debug, Default, ButtplugMessage, ButtplugMessageFinalizer, Clone, PartialEq, Eq, Getters,
 )]
-#[cfg_attr(feature = "serialize-json", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serialize", derive(Serialize, Deserialize))]
 pub struct Test {
   /// Message Id, used for matching message pairs in remote connection instances.
-  #[cfg_attr(feature = "serialize-json", serde(rename = "Id"))]
+  #[cfg_attr(feature = "serialize", serde(rename = "Id"))]
   id: u32,
   /// Test string, which will be echoed back to client when sent to server.
-  #[cfg_attr(feature = "serialize-json", serde(rename = "TestString"))]
+  #[cfg_attr(feature = "serialize", serde(rename = "TestString"))]
   #[getset(get = "pub")]
-  test_string: String,
+  test_string: ButtplugString,
 }
 
 impl Test {
@@ -29,7 +29,7 @@ impl Test {
   pub fn new(test: &str) -> Self {
     Self {
       id: 1,
-      test_string: test.to_owned(),
+      test_string: buttplug_string(test),
     }
   }
 }
@@ -0,0 +1,198 @@
+// Buttplug Rust Source Code File - See https://buttplug.io for more info.
+//
+// Copyright 2016-2023 Nonpolynomial Labs LLC. All rights reserved.
+//
+// Licensed under the BSD 3-Clause license. See LICENSE file in the project root
+// for full license information.
+
+//! Hooks for rewriting client messages before a server handles them, mostly to accommodate
+//! clients that don't quite follow the spec.
+
+use super::{
+  ButtplugClientMessage,
+  ButtplugDeviceMessage,
+  ButtplugMessage,
+  LinearCmd,
+  RotateCmd,
+  RotationSubcommand,
+  ScalarCmd,
+  ScalarSubcommand,
+  VectorSubcommand,
+  VibrateCmd,
+  VibrateSubcommand,
+};
+
+/// Rewrites incoming client messages. Registered on a server with
+/// [ButtplugServerBuilder::message_transformer](crate::server::ButtplugServerBuilder::message_transformer),
+/// and applied in registration order before messages are validated.
+pub trait MessageTransformer: Send + Sync {
+  /// Rewrite a deserialized client message.
+  fn transform_inbound(&self, msg: ButtplugClientMessage) -> ButtplugClientMessage {
+    msg
+  }
+
+  /// Rewrite a client message packet in its JSON form, before it is deserialized. Only called by
+  /// JSON serializers, for fixes that can't happen once a message has been parsed.
+  #[cfg(feature = "serialize-json")]
+  fn transform_inbound_json(&self, msg: serde_json::Value) -> serde_json::Value {
+    msg
+  }
+}
+
+/// Message type names accepted from clients, across all spec versions.
+#[cfg(feature = "serialize-json")]
+const CLIENT_MESSAGE_NAMES: [&str; 28] = [
+  "Ping",
+  "RequestLog",
+  "RequestServerInfo",
+  "StartScanning",
+  "StopScanning",
+  "RequestDeviceList",
+  "StopAllDevices",
+  "VibrateCmd",
+  "LinearCmd",
+  "RotateCmd",
+  "RawWriteCmd",
+  "RawReadCmd",
+  "RawStreamCmd",
+  "StopDeviceCmd",
+  "RawSubscribeCmd",
+  "RawUnsubscribeCmd",
+  "ScalarCmd",
+  "BatteryLevelCmd",
+  "RSSILevelCmd",
+  "SensorReadCmd",
+  "SensorSubscribeCmd",
+  "SensorUnsubscribeCmd",
+  "SingleMotorVibrateCmd",
+  "FleshlightLaunchFW12Cmd",
+  "LovenseCmd",
+  "KiirooCmd",
+  "VorzeA10CycloneCmd",
+  "Test",
+];
+
+/// Fixes message type fields sent with the wrong case (e.g. `requestserverinfo` or
+/// `VIBRATECMD`), which would otherwise fail to parse.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct NormalizeCaseTransformer {}
+
+impl MessageTransformer for NormalizeCaseTransformer {
+  #[cfg(feature = "serialize-json")]
+  fn transform_inbound_json(&self, mut msg: serde_json::Value) -> serde_json::Value {
+    if let Some(packet) = msg.as_array_mut() {
+      for message in packet.iter_mut() {
+        if let Some(fields) = message.as_object_mut() {
+          let renames: Vec<(String, &str)> = fields
+            .keys()
+            .filter_map(|key| {
+              CLIENT_MESSAGE_NAMES
+                .iter()
+                .find(|name| name.eq_ignore_ascii_case(key) && **name != key)
+                .map(|name| (key.clone(), *name))
+            })
+            .collect();
+          for (key, name) in renames {
+            if let Some(value) = fields.remove(&key) {
+              fields.insert(name.to_owned(), value);
+            }
+          }
+        }
+      }
+    }
+    msg
+  }
+}
+
+/// Clamps actuator values outside of 0.0-1.0 into range, instead of letting the message fail
+/// validation.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct ClampIntensityTransformer {}
+
+impl MessageTransformer for ClampIntensityTransformer {
+  fn transform_inbound(&self, msg: ButtplugClientMessage) -> ButtplugClientMessage {
+    let id = msg.id();
+    let mut clamped: ButtplugClientMessage = match msg {
+      ButtplugClientMessage::ScalarCmd(cmd) => ScalarCmd::new(
+        cmd.device_index(),
+        cmd
+          .scalars()
+          .iter()
+          .map(|s| ScalarSubcommand::new(s.index(), s.scalar().clamp(0.0, 1.0), s.actuator_type()))
+          .collect(),
+      )
+      .into(),
+      ButtplugClientMessage::VibrateCmd(cmd) => VibrateCmd::new(
+        cmd.device_index(),
+        cmd
+          .speeds()
+          .iter()
+          .map(|s| VibrateSubcommand::new(s.index(), s.speed().clamp(0.0, 1.0)))
+          .collect(),
+      )
+      .into(),
+      ButtplugClientMessage::RotateCmd(cmd) => RotateCmd::new(
+        cmd.device_index(),
+        cmd
+          .rotations()
+          .iter()
+          .map(|r| RotationSubcommand::new(r.index(), r.speed().clamp(0.0, 1.0), r.clockwise()))
+          .collect(),
+      )
+      .into(),
+      ButtplugClientMessage::LinearCmd(cmd) => LinearCmd::new(
+        cmd.device_index(),
+        cmd
+          .vectors()
+          .iter()
+          .map(|v| VectorSubcommand::new(v.index(), v.duration(), v.position().clamp(0.0, 1.0)))
+          .collect(),
+      )
+      .into(),
+      msg => return msg,
+    };
+    clamped.set_id(id);
+    clamped
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+  use crate::core::message::{ActuatorType, ButtplugMessageValidator};
+
+  #[test]
+  fn test_clamp_intensity_transformer() {
+    let mut msg = ScalarCmd::new(
+      0,
+      vec![
+        ScalarSubcommand::new(0, 1.5, ActuatorType::Vibrate),
+        ScalarSubcommand::new(1, -0.5, ActuatorType::Vibrate),
+      ],
+    );
+    msg.set_id(5);
+    assert!(msg.is_valid().is_err());
+    let transformed = ClampIntensityTransformer::default().transform_inbound(msg.into());
+    assert_eq!(transformed.id(), 5);
+    if let ButtplugClientMessage::ScalarCmd(cmd) = &transformed {
+      assert_eq!(cmd.scalars()[0].scalar(), 1.0);
+      assert_eq!(cmd.scalars()[1].scalar(), 0.0);
+    } else {
+      panic!("Transformer should not change message type");
+    }
+    assert!(transformed.is_valid().is_ok());
+  }
+
+  #[cfg(feature = "serialize-json")]
+  #[test]
+  fn test_normalize_case_transformer() {
+    let json: serde_json::Value = serde_json::from_str(
+      r#"[{"requestserverinfo": {"Id": 1}}, {"VIBRATECMD": {"Id": 2}}, {"Ping": {"Id": 3}}]"#,
+    )
+    .unwrap();
+    let transformed = NormalizeCaseTransformer::default().transform_inbound_json(json);
+    assert!(transformed[0].get("RequestServerInfo").is_some());
+    assert!(transformed[1].get("VibrateCmd").is_some());
+    assert!(transformed[2].get("Ping").is_some());
+  }
+}
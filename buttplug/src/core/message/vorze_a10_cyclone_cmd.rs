@@ -7,22 +7,22 @@
 
 use super::*;
 use getset::CopyGetters;
-#[cfg(feature = "serialize-json")]
+#[cfg(feature = "serialize")]
 use serde::{Deserialize, Serialize};
 
 #[derive(
   Debug, ButtplugDeviceMessage, ButtplugMessageFinalizer, Default, PartialEq, Eq, Clone, CopyGetters,
 )]
-#[cfg_attr(feature = "serialize-json", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serialize", derive(Serialize, Deserialize))]
 pub struct VorzeA10CycloneCmd {
-  #[cfg_attr(feature = "serialize-json", serde(rename = "Id"))]
+  #[cfg_attr(feature = "serialize", serde(rename = "Id"))]
   id: u32,
-  #[cfg_attr(feature = "serialize-json", serde(rename = "DeviceIndex"))]
+  #[cfg_attr(feature = "serialize", serde(rename = "DeviceIndex"))]
   device_index: u32,
-  #[cfg_attr(feature = "serialize-json", serde(rename = "Speed"))]
+  #[cfg_attr(feature = "serialize", serde(rename = "Speed"))]
   #[getset(get_copy = "pub")]
   speed: u32,
-  #[cfg_attr(feature = "serialize-json", serde(rename = "Clockwise"))]
+  #[cfg_attr(feature = "serialize", serde(rename = "Clockwise"))]
   #[getset(get_copy = "pub")]
   clockwise: bool,
 }
@@ -42,10 +42,12 @@ impl ButtplugMessageValidator for VorzeA10CycloneCmd {
   fn is_valid(&self) -> Result<(), ButtplugMessageError> {
     self.is_not_system_id(self.id)?;
     if self.speed > 99 {
-      Err(ButtplugMessageError::InvalidMessageContents(format!(
-        "Speed {} for VorzeA10CycloneCmd is invalid. Speed should be a value between 0.0 and 1.0",
-        self.speed
-      )))
+      Err(ButtplugMessageError::InvalidMessageContents(
+        format_buttplug_string!(
+          "Speed {} for VorzeA10CycloneCmd is invalid. Speed should be a value between 0.0 and 1.0",
+          self.speed
+        ),
+      ))
     } else {
       Ok(())
     }
@@ -7,17 +7,103 @@
 
 //! Protocol message and error definitions.
 
+/// Formats arguments into a [ButtplugString], truncating the result if it doesn't fit.
+macro_rules! format_buttplug_string {
+  ($($arg:tt)*) => {
+    $crate::core::format_buttplug_string(format_args!($($arg)*))
+  };
+}
+
 #[cfg(any(feature = "client", feature = "server"))]
 pub mod connector;
 pub mod errors;
 pub mod message;
 
 use errors::ButtplugError;
+#[cfg(feature = "std")]
 use futures::future::{self, BoxFuture, FutureExt};
 
 pub type ButtplugResult<T = ()> = Result<T, ButtplugError>;
+#[cfg(feature = "std")]
 pub type ButtplugResultFuture<T = ()> = BoxFuture<'static, ButtplugResult<T>>;
 
+/// Maximum length, in bytes, of strings in messages and errors when built without std. Defaults to
+/// 64, and can be changed by setting the `BUTTPLUG_STRING_CAPACITY` environment variable when
+/// building.
+pub const BUTTPLUG_STRING_CAPACITY: usize = match option_env!("BUTTPLUG_STRING_CAPACITY") {
+  Some(capacity) => parse_string_capacity(capacity),
+  None => 64,
+};
+
+const fn parse_string_capacity(value: &str) -> usize {
+  let digits = value.as_bytes();
+  assert!(
+    !digits.is_empty(),
+    "BUTTPLUG_STRING_CAPACITY must be a number"
+  );
+  let mut capacity = 0;
+  let mut i = 0;
+  while i < digits.len() {
+    assert!(
+      digits[i].is_ascii_digit(),
+      "BUTTPLUG_STRING_CAPACITY must be a number"
+    );
+    capacity = capacity * 10 + (digits[i] - b'0') as usize;
+    i += 1;
+  }
+  capacity
+}
+
+/// String type used in messages and errors. This is a [String] when built with std, and a fixed
+/// capacity [heapless::String] when built without it.
+#[cfg(feature = "std")]
+pub type ButtplugString = String;
+#[cfg(not(feature = "std"))]
+pub type ButtplugString = heapless::String<BUTTPLUG_STRING_CAPACITY>;
+
+/// Creates a [ButtplugString] from `value`. Without std, anything past
+/// [BUTTPLUG_STRING_CAPACITY] bytes is cut off at the last whole character that fits. Use
+/// `ButtplugString::try_from` to get an error instead.
+pub fn buttplug_string(value: &str) -> ButtplugString {
+  format_buttplug_string!("{}", value)
+}
+
+#[doc(hidden)]
+pub fn format_buttplug_string(args: ::core::fmt::Arguments) -> ButtplugString {
+  #[cfg(feature = "std")]
+  {
+    std::fmt::format(args)
+  }
+  #[cfg(not(feature = "std"))]
+  {
+    struct Truncating {
+      string: ButtplugString,
+      full: bool,
+    }
+
+    impl ::core::fmt::Write for Truncating {
+      fn write_str(&mut self, s: &str) -> ::core::fmt::Result {
+        for c in s.chars() {
+          if self.full || self.string.push(c).is_err() {
+            self.full = true;
+            break;
+          }
+        }
+        Ok(())
+      }
+    }
+
+    let mut truncating = Truncating {
+      string: ButtplugString::new(),
+      full: false,
+    };
+    // Truncating never fails, so the only errors are from Display impls in args.
+    let _ = ::core::fmt::Write::write_fmt(&mut truncating, args);
+    truncating.string
+  }
+}
+
+#[cfg(feature = "std")]
 impl<T> From<ButtplugError> for BoxFuture<'static, Result<T, ButtplugError>>
 where
   T: Send + 'static,
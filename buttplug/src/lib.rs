@@ -10,6 +10,7 @@
 // Required for select! expansion in RemoteServer
 #![recursion_limit = "512"]
 #![doc = include_str!("../README.md")]
+#![cfg_attr(not(feature = "std"), no_std)]
 
 //! # An Overview of Buttplug's Module System
 //!
@@ -30,7 +31,13 @@
 //!   - Utilities for all portions of the library that may not be specifically related to sex toy
 //!     functionality. This includes managers for different async runtimes, configuration file
 //!     loading, utilities for streams and futures, etc...
+//!
+//! Building with `default-features = false` (and without any feature that turns on `std`) leaves
+//! only the message and error types in [Core](crate::core), with fixed capacity strings in place of
+//! [String], for use in microcontroller firmware. Turn on `serialize` to get their serde impls.
 
+#[cfg(not(feature = "std"))]
+extern crate alloc;
 #[macro_use]
 extern crate buttplug_derive;
 #[macro_use]
@@ -38,6 +45,7 @@ extern crate strum_macros;
 #[cfg(any(feature = "client", feature = "server"))]
 #[macro_use]
 extern crate futures;
+#[cfg(feature = "std")]
 #[macro_use]
 extern crate tracing;
 
@@ -46,4 +54,5 @@ pub mod client;
 pub mod core;
 #[cfg(feature = "server")]
 pub mod server;
+#[cfg(feature = "std")]
 pub mod util;
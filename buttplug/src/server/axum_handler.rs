@@ -0,0 +1,106 @@
+// Buttplug Rust Source Code File - See https://buttplug.io for more info.
+//
+// Copyright 2016-2023 Nonpolynomial Labs LLC. All rights reserved.
+//
+// Licensed under the BSD 3-Clause license. See LICENSE file in the project root
+// for full license information.
+
+//! [axum] route serving the Buttplug protocol over websockets, for embedding a server in an
+//! existing axum app.
+
+use super::ButtplugRemoteServer;
+use crate::{
+  core::{
+    connector::{ButtplugAxumWebsocketTransport, ButtplugRemoteServerConnector},
+    message::serializer::ButtplugServerJSONSerializer,
+  },
+  util::async_manager,
+};
+use axum::{
+  extract::ws::WebSocketUpgrade,
+  routing::{get, MethodRouter},
+};
+use std::sync::Arc;
+use tokio::sync::mpsc;
+use tokio_stream::wrappers::ReceiverStream;
+
+/// Route that upgrades requests to websockets and serves the Buttplug protocol (using JSON) on
+/// them, i.e. `router.route("/buttplug", buttplug_axum_handler(server))`.
+///
+/// Each websocket gets its own session on `server`, as with
+/// [ButtplugRemoteServer::start_accepting], until the route is dropped. Must be called from within
+/// a tokio runtime.
+pub fn buttplug_axum_handler<S>(server: Arc<ButtplugRemoteServer>) -> MethodRouter<S>
+where
+  S: Clone + Send + Sync + 'static,
+{
+  let (connector_sender, connector_receiver) = mpsc::channel(16);
+  async_manager::spawn(async move {
+    server
+      .start_accepting(ReceiverStream::new(connector_receiver))
+      .await;
+  });
+  get(move |ws: WebSocketUpgrade| {
+    let connector_sender = connector_sender.clone();
+    async move {
+      ws.on_upgrade(move |socket| async move {
+        let connector = ButtplugRemoteServerConnector::<_, ButtplugServerJSONSerializer>::new(
+          ButtplugAxumWebsocketTransport::from(socket),
+        );
+        if connector_sender.send(connector).await.is_err() {
+          warn!("Server no longer accepting connections, dropping websocket.");
+        }
+      })
+    }
+  })
+}
+
+#[cfg(all(test, feature = "client", feature = "websockets"))]
+mod test {
+  use super::*;
+  use crate::{client::ButtplugClient, core::connector::new_json_ws_client_connector};
+  use axum::Router;
+  use std::net::TcpListener;
+
+  #[test]
+  fn test_axum_handler() {
+    async_manager::block_on(async {
+      let server = Arc::new(ButtplugRemoteServer::default());
+      let router = Router::new().route("/buttplug", buttplug_axum_handler(server.clone()));
+      let listener = TcpListener::bind("127.0.0.1:0").expect("Test, assuming infallible.");
+      let address = format!(
+        "ws://{}/buttplug",
+        listener.local_addr().expect("Test, assuming infallible.")
+      );
+      let http_server = axum::Server::from_tcp(listener)
+        .expect("Test, assuming infallible.")
+        .serve(router.into_make_service());
+      async_manager::spawn(async move {
+        http_server.await.expect("Test, assuming infallible.");
+      });
+
+      // Every websocket gets its own session, so two clients can be connected at once.
+      let client = ButtplugClient::new("Test Client");
+      client
+        .connect(new_json_ws_client_connector(&address))
+        .await
+        .expect("Test, assuming infallible.");
+      let other_client = ButtplugClient::new("Other Test Client");
+      other_client
+        .connect(new_json_ws_client_connector(&address))
+        .await
+        .expect("Test, assuming infallible.");
+      assert!(client.connected());
+      assert!(other_client.connected());
+      assert!(server.is_running());
+      client
+        .stop_all_devices()
+        .await
+        .expect("Test, assuming infallible.");
+      other_client
+        .stop_all_devices()
+        .await
+        .expect("Test, assuming infallible.");
+    });
+  }
+}
@@ -0,0 +1,167 @@
+// Buttplug Rust Source Code File - See https://buttplug.io for more info.
+//
+// Copyright 2016-2023 Nonpolynomial Labs LLC. All rights reserved.
+//
+// Licensed under the BSD 3-Clause license. See LICENSE file in the project root
+// for full license information.
+
+//! Connection quality score for the current client, see
+//! [ButtplugRemoteServer::connection_quality].
+
+#[cfg(doc)]
+use super::{ButtplugRemoteServer, ButtplugRemoteServerEvent};
+use getset::CopyGetters;
+use std::{sync::Mutex, time::Duration};
+
+/// How much each part of the connection quality score counts, and when the connection counts as
+/// degraded. Weights are relative to each other, so only their ratios matter.
+#[derive(Debug, Clone, Copy, PartialEq, CopyGetters)]
+#[getset(get_copy = "pub")]
+pub struct ConnectionQualityConfig {
+  /// Weight of the time the server takes to handle client messages and send their replies. This is
+  /// measured on the server, so it doesn't include network round trip time.
+  reply_time_weight: f32,
+  /// Weight of the fraction of client messages that got an error reply.
+  error_rate_weight: f32,
+  /// Weight of the fraction of client messages dropped by the rate limiter.
+  drop_rate_weight: f32,
+  /// Reply time at which the reply time part of the score reaches 0.
+  max_reply_time: Duration,
+  /// Score below which [ButtplugRemoteServerEvent::ConnectionQualityDegraded] is sent.
+  degraded_threshold: f32,
+}
+
+impl Default for ConnectionQualityConfig {
+  fn default() -> Self {
+    Self {
+      reply_time_weight: 0.4,
+      error_rate_weight: 0.3,
+      drop_rate_weight: 0.3,
+      max_reply_time: Duration::from_secs(1),
+      degraded_threshold: 0.5,
+    }
+  }
+}
+
+impl ConnectionQualityConfig {
+  pub fn new(
+    reply_time_weight: f32,
+    error_rate_weight: f32,
+    drop_rate_weight: f32,
+    max_reply_time: Duration,
+    degraded_threshold: f32,
+  ) -> Self {
+    Self {
+      reply_time_weight,
+      error_rate_weight,
+      drop_rate_weight,
+      max_reply_time,
+      degraded_threshold,
+    }
+  }
+}
+
+/// How much of the last reply time carries into the running average.
+const REPLY_TIME_SMOOTHING: f64 = 0.2;
+
+#[derive(Default)]
+struct QualityStats {
+  replies: u64,
+  errors: u64,
+  drops: u64,
+  /// Smoothed time from receiving a client message to sending its reply, in seconds.
+  average_reply_time: Option<f64>,
+  /// True once a degraded event was sent, until the score recovers.
+  degraded: bool,
+}
+
+/// Connection quality of the current session.
+pub(super) struct ConnectionQuality {
+  config: ConnectionQualityConfig,
+  stats: Mutex<QualityStats>,
+}
+
+impl Default for ConnectionQuality {
+  fn default() -> Self {
+    Self::new(ConnectionQualityConfig::default())
+  }
+}
+
+impl ConnectionQuality {
+  pub(super) fn new(config: ConnectionQualityConfig) -> Self {
+    Self {
+      config,
+      stats: Mutex::new(QualityStats::default()),
+    }
+  }
+
+  pub(super) fn score(&self) -> f32 {
+    self.score_for(&self.stats.lock().expect("Lock poisoned"))
+  }
+
+  /// Record a reply sent `reply_time` after its message arrived. Returns the score if this took
+  /// the connection below the degraded threshold.
+  pub(super) fn record_reply(&self, reply_time: Duration, error: bool) -> Option<f32> {
+    let mut stats = self.stats.lock().expect("Lock poisoned");
+    stats.replies += 1;
+    if error {
+      stats.errors += 1;
+    }
+    let reply_time = reply_time.as_secs_f64();
+    stats.average_reply_time = Some(match stats.average_reply_time {
+      Some(average) => average + (reply_time - average) * REPLY_TIME_SMOOTHING,
+      None => reply_time,
+    });
+    self.check_degraded(&mut stats)
+  }
+
+  /// Record a message dropped by the rate limiter. Returns the score if this took the connection
+  /// below the degraded threshold.
+  pub(super) fn record_drop(&self) -> Option<f32> {
+    let mut stats = self.stats.lock().expect("Lock poisoned");
+    stats.drops += 1;
+    self.check_degraded(&mut stats)
+  }
+
+  fn check_degraded(&self, stats: &mut QualityStats) -> Option<f32> {
+    let score = self.score_for(stats);
+    if score >= self.config.degraded_threshold {
+      stats.degraded = false;
+      None
+    } else if stats.degraded {
+      None
+    } else {
+      stats.degraded = true;
+      Some(score)
+    }
+  }
+
+  fn score_for(&self, stats: &QualityStats) -> f32 {
+    let config = &self.config;
+    let total_weight =
+      config.reply_time_weight + config.error_rate_weight + config.drop_rate_weight;
+    if total_weight <= 0.0 {
+      return 1.0;
+    }
+    let reply_time_penalty = match stats.average_reply_time {
+      Some(_) if config.max_reply_time.is_zero() => 1.0,
+      Some(average) => (average / config.max_reply_time.as_secs_f64()).min(1.0),
+      None => 0.0,
+    };
+    let error_rate = if stats.replies == 0 {
+      0.0
+    } else {
+      stats.errors as f64 / stats.replies as f64
+    };
+    let received = stats.replies + stats.drops;
+    let drop_rate = if received == 0 {
+      0.0
+    } else {
+      stats.drops as f64 / received as f64
+    };
+    let penalty = config.reply_time_weight as f64 * reply_time_penalty
+      + config.error_rate_weight as f64 * error_rate
+      + config.drop_rate_weight as f64 * drop_rate;
+    (1.0 - penalty / total_weight as f64).clamp(0.0, 1.0) as f32
+  }
+}
@@ -0,0 +1,146 @@
+// Buttplug Rust Source Code File - See https://buttplug.io for more info.
+//
+// Copyright 2016-2023 Nonpolynomial Labs LLC. All rights reserved.
+//
+// Licensed under the BSD 3-Clause license. See LICENSE file in the project root
+// for full license information.
+
+//! Resolution of contradictory actuator commands sent to the same device by different clients.
+
+use crate::core::message::{
+  ButtplugDeviceCommandMessageUnion,
+  ButtplugDeviceMessage,
+  ButtplugMessage,
+  ScalarCmd,
+  ScalarSubcommand,
+};
+use dashmap::DashMap;
+use serde::{Deserialize, Serialize};
+
+/// How to pick the value sent to an actuator when more than one client is commanding it.
+///
+/// Each client's most recent nonzero value for an actuator is its "active" command. A value of 0
+/// (or stopping the device) withdraws the client's active command. Only scalar commands
+/// ([ScalarCmd] and [VibrateCmd](crate::core::message::VibrateCmd)) are resolved, other commands
+/// are always passed through as sent.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum CommandConflictPolicy {
+  /// The most recent command is sent, whichever client it came from.
+  #[default]
+  LastWins,
+  /// The client that started commanding an actuator first keeps control of it, until it withdraws
+  /// its command.
+  FirstWins,
+  /// The average of all clients' active commands is sent.
+  AverageIntensity,
+  /// The highest of all clients' active commands is sent.
+  MaxIntensity,
+}
+
+/// Tracks the active command of each client for each actuator, and recomputes the value to send
+/// according to a [CommandConflictPolicy].
+#[derive(Debug, Default)]
+pub(super) struct CommandConflictResolver {
+  policy: CommandConflictPolicy,
+  /// Active commands as (client, value), in the order clients started commanding the actuator,
+  /// keyed by (device index, actuator index).
+  active_commands: DashMap<(u32, u32), Vec<(u64, f64)>>,
+}
+
+impl CommandConflictResolver {
+  pub(super) fn new(policy: CommandConflictPolicy) -> Self {
+    Self {
+      policy,
+      active_commands: DashMap::new(),
+    }
+  }
+
+  /// Record the values in a command from the given client, and return the command to actually
+  /// send. Scalar commands have each value replaced according to the policy, and VibrateCmd is
+  /// turned into ScalarCmd to do so. Stopping a device withdraws every client's commands for it.
+  pub(super) fn resolve(
+    &self,
+    client: u64,
+    msg: ButtplugDeviceCommandMessageUnion,
+  ) -> ButtplugDeviceCommandMessageUnion {
+    if self.policy == CommandConflictPolicy::LastWins {
+      return msg;
+    }
+    match msg {
+      ButtplugDeviceCommandMessageUnion::ScalarCmd(msg) => {
+        self.resolve_scalar_cmd(client, msg).into()
+      }
+      ButtplugDeviceCommandMessageUnion::VibrateCmd(msg) => {
+        self.resolve_scalar_cmd(client, msg.into()).into()
+      }
+      ButtplugDeviceCommandMessageUnion::StopDeviceCmd(_) => {
+        self.clear_device(msg.device_index());
+        msg
+      }
+      _ => msg,
+    }
+  }
+
+  fn resolve_scalar_cmd(&self, client: u64, msg: ScalarCmd) -> ScalarCmd {
+    let scalars = msg
+      .scalars()
+      .iter()
+      .map(|scalar| {
+        ScalarSubcommand::new(
+          scalar.index(),
+          self.resolve_value(client, msg.device_index(), scalar.index(), scalar.scalar()),
+          scalar.actuator_type(),
+        )
+      })
+      .collect();
+    let mut resolved = ScalarCmd::new(msg.device_index(), scalars);
+    resolved.set_id(msg.id());
+    resolved
+  }
+
+  fn resolve_value(&self, client: u64, device_index: u32, actuator_index: u32, value: f64) -> f64 {
+    let mut active = self
+      .active_commands
+      .entry((device_index, actuator_index))
+      .or_default();
+    match active.iter_mut().find(|(id, _)| *id == client) {
+      Some((_, active_value)) if value > 0.0 => *active_value = value,
+      Some(_) => active.retain(|(id, _)| *id != client),
+      None if value > 0.0 => active.push((client, value)),
+      None => {}
+    }
+    if active.is_empty() {
+      return value;
+    }
+    match self.policy {
+      CommandConflictPolicy::LastWins => value,
+      CommandConflictPolicy::FirstWins => active[0].1,
+      CommandConflictPolicy::AverageIntensity => {
+        active.iter().map(|(_, value)| value).sum::<f64>() / active.len() as f64
+      }
+      CommandConflictPolicy::MaxIntensity => {
+        active.iter().map(|(_, value)| *value).fold(0.0, f64::max)
+      }
+    }
+  }
+
+  /// Forget all active commands for a device, when it is stopped.
+  fn clear_device(&self, device_index: u32) {
+    self
+      .active_commands
+      .retain(|(index, _), _| *index != device_index);
+  }
+
+  /// Withdraw all of a client's active commands, when it disconnects.
+  pub(super) fn clear_session(&self, client: u64) {
+    self.active_commands.retain(|_, active| {
+      active.retain(|(id, _)| *id != client);
+      !active.is_empty()
+    });
+  }
+
+  /// Forget all active commands, when every device is stopped.
+  pub(super) fn clear(&self) {
+    self.active_commands.clear();
+  }
+}
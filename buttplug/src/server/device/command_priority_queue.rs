@@ -0,0 +1,119 @@
+// Buttplug Rust Source Code File - See https://buttplug.io for more info.
+//
+// Copyright 2016-2023 Nonpolynomial Labs LLC. All rights reserved.
+//
+// Licensed under the BSD 3-Clause license. See LICENSE file in the project root
+// for full license information.
+
+//! Ordering hardware commands to a device by [CommandPriority].
+
+use crate::core::message::CommandPriority;
+use std::{
+  cmp::Ordering,
+  collections::BinaryHeap,
+  sync::{Arc, Mutex},
+};
+use tokio::sync::oneshot;
+
+/// Hardware command waiting for its turn to be sent.
+struct QueuedCommand {
+  priority: CommandPriority,
+  /// Arrival order, so commands of the same priority go out first come, first served.
+  sequence: u64,
+  sender: oneshot::Sender<CommandTurn>,
+}
+
+impl PartialEq for QueuedCommand {
+  fn eq(&self, other: &Self) -> bool {
+    self.cmp(other) == Ordering::Equal
+  }
+}
+
+impl Eq for QueuedCommand {
+}
+
+impl PartialOrd for QueuedCommand {
+  fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+    Some(self.cmp(other))
+  }
+}
+
+impl Ord for QueuedCommand {
+  // BinaryHeap pops the greatest item, so that's the highest priority, then the lowest sequence.
+  fn cmp(&self, other: &Self) -> Ordering {
+    self
+      .priority
+      .cmp(&other.priority)
+      .then_with(|| other.sequence.cmp(&self.sequence))
+  }
+}
+
+#[derive(Default)]
+struct QueueState {
+  /// True while a command holds the turn.
+  busy: bool,
+  next_sequence: u64,
+  waiting: BinaryHeap<QueuedCommand>,
+}
+
+/// Lets one hardware command at a time through to a device. While a command is being sent, others
+/// wait, and are let through highest priority first, regardless of the order they arrived in.
+///
+/// [CommandPriority::Critical] commands (stops) don't go through the queue at all, they're sent
+/// straight away even if another command's write is still pending.
+#[derive(Clone, Default)]
+pub(super) struct CommandPriorityQueue {
+  state: Arc<Mutex<QueueState>>,
+}
+
+impl CommandPriorityQueue {
+  /// Wait until it's a command of this priority's turn to be sent. The turn lasts until the
+  /// returned [CommandTurn] is dropped.
+  pub(super) async fn acquire(&self, priority: CommandPriority) -> CommandTurn {
+    let receiver = {
+      let mut state = self.state.lock().expect("Lock poisoned");
+      if !state.busy {
+        state.busy = true;
+        return CommandTurn {
+          state: self.state.clone(),
+        };
+      }
+      let (sender, receiver) = oneshot::channel();
+      let sequence = state.next_sequence;
+      state.next_sequence += 1;
+      state.waiting.push(QueuedCommand {
+        priority,
+        sequence,
+        sender,
+      });
+      receiver
+    };
+    // The sender is only dropped with the queue state, which we're holding a reference to.
+    receiver.await.expect("Queue outlives its waiters")
+  }
+}
+
+/// Turn to send a command to a device, from [CommandPriorityQueue::acquire]. Dropping it passes
+/// the turn on to the next waiting command.
+pub(super) struct CommandTurn {
+  state: Arc<Mutex<QueueState>>,
+}
+
+impl Drop for CommandTurn {
+  fn drop(&mut self) {
+    let next = {
+      let mut state = self.state.lock().expect("Lock poisoned");
+      let next = state.waiting.pop();
+      if next.is_none() {
+        state.busy = false;
+      }
+      next
+    };
+    if let Some(next) = next {
+      // If the waiter gave up, the turn comes back here and is dropped, which passes it on again.
+      let _ = next.sender.send(CommandTurn {
+        state: self.state.clone(),
+      });
+    }
+  }
+}
@@ -147,7 +147,12 @@ pub use server_device_message_attributes::{
   ServerGenericDeviceMessageAttributes,
 };
 
-use super::protocol::{get_default_protocol_map, ProtocolIdentifierFactory, ProtocolSpecializer};
+use super::protocol::{
+  get_default_protocol_map,
+  ProtocolIdentifier,
+  ProtocolIdentifierFactory,
+  ProtocolSpecializer,
+};
 use crate::{
   core::{
     errors::ButtplugDeviceError,
@@ -160,11 +165,12 @@ use derivative::Derivative;
 use getset::{Getters, MutGetters, Setters};
 use serde::{Deserialize, Serialize};
 use std::{
-  collections::HashMap,
+  collections::{BTreeSet, HashMap},
   sync::{
     atomic::{AtomicU32, Ordering},
     Arc,
   },
+  time::Duration,
 };
 
 /// Denotes what set of protocols attributes should be used: Default (generic) or device class
@@ -178,6 +184,32 @@ pub enum ProtocolAttributesType {
   Identifier(String),
 }
 
+/// Summary of a protocol the library can use, for listing supported hardware.
+#[derive(Debug, Clone, PartialEq, Eq, Getters)]
+#[getset(get = "pub")]
+pub struct ProtocolInfo {
+  /// Protocol name, as used in device configuration files.
+  name: String,
+  /// Manufacturer name, taken from the name of the protocol's default device configuration.
+  manufacturer: String,
+  /// Names of devices the configuration lists specifically for this protocol.
+  device_names: Vec<String>,
+  /// Device messages at least one device using this protocol supports.
+  supported_messages: Vec<String>,
+  /// Instructions for putting a device into pairing mode, from the protocol.
+  pairing_hints: Vec<String>,
+}
+
+/// Message types checked when building [ProtocolInfo::supported_messages]. Only covers messages
+/// from the current spec, older messages are derived from these.
+const PROTOCOL_INFO_MESSAGE_TYPES: [ButtplugDeviceMessageType; 5] = [
+  ButtplugDeviceMessageType::ScalarCmd,
+  ButtplugDeviceMessageType::RotateCmd,
+  ButtplugDeviceMessageType::LinearCmd,
+  ButtplugDeviceMessageType::SensorReadCmd,
+  ButtplugDeviceMessageType::SensorSubscribeCmd,
+];
+
 /// A version of [ServerDeviceIdentifier] used for protocol lookup and matching.
 ///
 /// This mirrors [ServerDeviceIdentifier], except that address is optional, as we will have protocol
@@ -260,6 +292,8 @@ pub struct ProtocolDeviceAttributes {
   display_name: Option<String>,
   /// Message attributes for this device instance.
   pub(super) message_attributes: ServerDeviceMessageAttributes,
+  /// Minimum time between commands to the same actuator, assuming one is configured.
+  command_debounce: Option<Duration>,
 }
 
 impl ProtocolDeviceAttributes {
@@ -277,6 +311,7 @@ impl ProtocolDeviceAttributes {
       display_name,
       message_attributes,
       parent,
+      command_debounce: None,
     }
   }
 
@@ -292,6 +327,7 @@ impl ProtocolDeviceAttributes {
       name: Some(self.name().to_owned()),
       display_name: self.display_name(),
       message_attributes: self.message_attributes(),
+      command_debounce: self.command_debounce(),
     }
   }
 
@@ -330,6 +366,24 @@ impl ProtocolDeviceAttributes {
     }
   }
 
+  /// Return the minimum time between commands to the same actuator for this instance, assuming one
+  /// is configured.
+  pub fn command_debounce(&self) -> Option<Duration> {
+    if let Some(debounce) = self.command_debounce {
+      Some(debounce)
+    } else if let Some(parent) = &self.parent {
+      parent.command_debounce()
+    } else {
+      None
+    }
+  }
+
+  /// Set the minimum time between commands to the same actuator for this instance. Commands sent
+  /// before this has elapsed are dropped.
+  pub fn set_command_debounce(&mut self, debounce: Option<Duration>) {
+    self.command_debounce = debounce;
+  }
+
   /// Check to make sure the message attributes of an instance are valid.
   fn is_valid(&self) -> Result<(), ButtplugDeviceError> {
     if let Some(attrs) = self.message_attributes.scalar_cmd() {
@@ -388,6 +442,8 @@ pub struct DeviceConfigurationManagerBuilder {
   /// [ServerDeviceIdentifier].
   denied_addresses: Vec<String>,
   reserved_indexes: Vec<(ServerDeviceIdentifier, u32)>,
+  /// Debounce interval for devices that don't configure their own.
+  command_debounce: Option<Duration>,
 }
 
 impl DeviceConfigurationManagerBuilder {
@@ -449,6 +505,11 @@ impl DeviceConfigurationManagerBuilder {
     self
   }
 
+  pub fn command_debounce(&mut self, debounce: Duration) -> &mut Self {
+    self.command_debounce = Some(debounce);
+    self
+  }
+
   pub fn finish(&mut self) -> Result<DeviceConfigurationManager, ButtplugDeviceError> {
     // Map of protocol names to their respective protocol instance factories
     let mut protocol_map = if !self.skip_default_protocols {
@@ -555,7 +616,10 @@ impl DeviceConfigurationManagerBuilder {
       allowed_addresses: self.allowed_addresses.clone(),
       denied_addresses: self.denied_addresses.clone(),
       reserved_indexes,
+      index_overrides: DashMap::new(),
       current_index: AtomicU32::new(0),
+      command_debounce: self.command_debounce,
+      device_tags: DashMap::new(),
     })
   }
 }
@@ -581,7 +645,14 @@ pub struct DeviceConfigurationManager {
   allowed_addresses: Vec<String>,
   denied_addresses: Vec<String>,
   reserved_indexes: DashMap<ServerDeviceIdentifier, u32>,
+  /// Indexes set by the application, by device address. Take precedence over reserved indexes.
+  index_overrides: DashMap<String, u32>,
   current_index: AtomicU32,
+  /// Debounce interval for devices that don't configure their own.
+  command_debounce: Option<Duration>,
+  /// Tags set by the application, by device address. Kept when devices disconnect, so a device
+  /// gets its tags back when it reconnects.
+  device_tags: DashMap<String, HashMap<String, String>>,
 }
 
 impl Default for DeviceConfigurationManager {
@@ -619,12 +690,16 @@ impl DeviceConfigurationManager {
   }
 
   pub fn device_index(&self, identifier: &ServerDeviceIdentifier) -> u32 {
-    // See if we have a reserved or reusable device index here.
-    if let Some(id) = self.reserved_indexes.get(identifier) {
+    // See if we have an overridden, reserved or reusable device index here.
+    if let Some(id) = self.index_overrides.get(identifier.address()) {
+      *id
+    } else if let Some(id) = self.reserved_indexes.get(identifier) {
       *id
     } else {
       let mut current_index = self.current_index.load(Ordering::SeqCst);
-      while self.reserved_indexes.iter().any(|x| *x == current_index) {
+      while self.reserved_indexes.iter().any(|x| *x == current_index)
+        || self.index_overrides.iter().any(|x| *x == current_index)
+      {
         current_index += 1;
       }
       let generated_device_index = current_index;
@@ -637,6 +712,59 @@ impl DeviceConfigurationManager {
     }
   }
 
+  /// Always give the device with the given address `index` when it connects, until cleared with
+  /// [DeviceConfigurationManager::clear_index_override]. Fails if the index is overridden or
+  /// reserved for a device with a different address.
+  pub fn set_index_override(&self, address: &str, index: u32) -> Result<(), ButtplugDeviceError> {
+    if let Some(other) = self
+      .index_overrides
+      .iter()
+      .find(|entry| *entry.value() == index && entry.key() != address)
+    {
+      return Err(ButtplugDeviceError::DeviceIndexConflict(
+        index,
+        other.key().clone(),
+      ));
+    }
+    if let Some(other) = self
+      .reserved_indexes
+      .iter()
+      .find(|entry| *entry.value() == index && entry.key().address() != address)
+    {
+      return Err(ButtplugDeviceError::DeviceIndexConflict(
+        index,
+        other.key().address().clone(),
+      ));
+    }
+    self.index_overrides.insert(address.to_owned(), index);
+    Ok(())
+  }
+
+  /// Remove the index override for the device with the given address. Returns false if there
+  /// wasn't one.
+  pub fn clear_index_override(&self, address: &str) -> bool {
+    self.index_overrides.remove(address).is_some()
+  }
+
+  /// Tags set for the device with the given address via
+  /// [DeviceConfigurationManager::set_device_tag].
+  pub fn device_tags(&self, address: &str) -> HashMap<String, String> {
+    self
+      .device_tags
+      .get(address)
+      .map(|tags| tags.value().clone())
+      .unwrap_or_default()
+  }
+
+  /// Set a tag on the device with the given address, replacing any earlier value for `key`.
+  pub fn set_device_tag(&self, address: &str, key: String, value: String) {
+    self
+      .device_tags
+      .entry(address.to_owned())
+      .or_default()
+      .insert(key, value);
+  }
+
   /// Provides read-only access to the internal protocol/identifier map. Mainly
   /// used for WebBluetooth filter construction, but could also be handy for
   /// listing capabilities in UI, etc.
@@ -646,6 +774,80 @@ impl DeviceConfigurationManager {
     self.communication_specifiers.clone()
   }
 
+  /// Summaries of every protocol that has an implementation, sorted by protocol name. User
+  /// configurations for specific devices aren't included.
+  pub fn protocol_info(&self) -> Vec<ProtocolInfo> {
+    let mut protocol_names: Vec<&String> = self.protocol_map.keys().collect();
+    protocol_names.sort();
+    protocol_names
+      .into_iter()
+      .map(|protocol_name| {
+        let mut manufacturer = String::new();
+        let mut device_names = BTreeSet::new();
+        let mut supported_messages: BTreeSet<ButtplugDeviceMessageType> = BTreeSet::new();
+        for (ident, attrs) in self
+          .protocol_attributes
+          .iter()
+          .filter(|(ident, _)| ident.protocol == *protocol_name && ident.address.is_none())
+        {
+          match &ident.attributes_identifier {
+            ProtocolAttributesType::Default => {
+              manufacturer = attrs
+                .name()
+                .trim_end_matches(" Device")
+                .trim_end()
+                .to_owned()
+            }
+            ProtocolAttributesType::Identifier(_) => {
+              device_names.insert(attrs.name().to_owned());
+            }
+          }
+          let message_attributes = attrs.message_attributes();
+          supported_messages.extend(
+            PROTOCOL_INFO_MESSAGE_TYPES
+              .iter()
+              .filter(|message_type| message_attributes.message_allowed(message_type)),
+          );
+        }
+        ProtocolInfo {
+          name: protocol_name.clone(),
+          manufacturer,
+          device_names: device_names.into_iter().collect(),
+          supported_messages: supported_messages
+            .into_iter()
+            .map(|message_type| message_type.to_string())
+            .collect(),
+          pairing_hints: self.protocol_map[protocol_name].pairing_hints(),
+        }
+      })
+      .collect()
+  }
+
+  /// Returns the name of a protocol whose communication specifiers match the given
+  /// specifier, if any. Unlike [Self::protocol_specializers], this doesn't create any protocol
+  /// identifiers, so it's cheap enough to use for informational purposes.
+  pub fn protocol_name_for_specifier(
+    &self,
+    specifier: &ProtocolCommunicationSpecifier,
+  ) -> Option<String> {
+    self
+      .communication_specifiers
+      .iter()
+      .find(|(name, specifiers)| {
+        specifiers.contains(specifier) && self.protocol_map.contains_key(*name)
+      })
+      .map(|(name, _)| name.clone())
+  }
+
+  /// Create a new identifier for the named protocol, skipping the communication specifier matching
+  /// that [Self::protocol_specializers] does. Used for devices that are already connected.
+  pub fn protocol_identifier(&self, protocol_name: &str) -> Option<Box<dyn ProtocolIdentifier>> {
+    self
+      .protocol_map
+      .get(protocol_name)
+      .map(|factory| factory.create())
+  }
+
   pub fn protocol_specializers(
     &self,
     specifier: &ProtocolCommunicationSpecifier,
@@ -712,6 +914,10 @@ impl DeviceConfigurationManager {
       flat_attrs.add_raw_messages(raw_endpoints);
     }
 
+    if flat_attrs.command_debounce().is_none() {
+      flat_attrs.set_command_debounce(self.command_debounce);
+    }
+
     Some(flat_attrs)
   }
 }
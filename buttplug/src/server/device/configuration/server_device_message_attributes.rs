@@ -1,7 +1,7 @@
 use std::ops::RangeInclusive;
 
 use getset::{Getters, MutGetters, Setters};
-use serde::{Deserialize, Serialize};
+use serde::{Deserialize, Serialize, Serializer};
 
 use crate::core::{
   errors::ButtplugDeviceError,
@@ -93,6 +93,7 @@ impl ServerDeviceMessageAttributes {
   pub fn message_allowed(&self, message_type: &ButtplugDeviceMessageType) -> bool {
     match message_type {
       ButtplugDeviceMessageType::ScalarCmd => self.scalar_cmd.is_some(),
+      ButtplugDeviceMessageType::PrioritizedScalarCmd => self.scalar_cmd.is_some(),
       // VibrateCmd and SingleMotorVibrateCmd will derive from Scalars, so errors will be thrown in
       // the scalar parser if the actuator isn't correct.
       ButtplugDeviceMessageType::VibrateCmd => self.scalar_cmd.is_some(),
@@ -127,6 +128,8 @@ impl ServerDeviceMessageAttributes {
       ButtplugDeviceMessageType::RawSubscribeCmd => self.raw_subscribe_cmd.is_some(),
       ButtplugDeviceMessageType::RawUnsubscribeCmd => self.raw_subscribe_cmd.is_some(),
       ButtplugDeviceMessageType::RawWriteCmd => self.raw_write_cmd.is_some(),
+      // Streams end up as a single raw write, so they're allowed anywhere raw writes are.
+      ButtplugDeviceMessageType::RawStreamCmd => self.raw_write_cmd.is_some(),
       ButtplugDeviceMessageType::VorzeA10CycloneCmd => self.vorze_a10_cyclone_cmd.is_some(),
       ButtplugDeviceMessageType::StopDeviceCmd => true,
       ButtplugDeviceMessageType::KiirooCmd => false,
@@ -280,6 +283,14 @@ fn unspecified_feature() -> String {
   "N/A".to_string()
 }
 
+/// Writes step ranges in the same `[start, end]` form device configuration files use.
+fn serialize_step_range<S>(range: &RangeInclusive<u32>, serializer: S) -> Result<S::Ok, S::Error>
+where
+  S: Serializer,
+{
+  [*range.start(), *range.end()].serialize(serializer)
+}
+
 #[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize, Getters, Setters)]
 pub struct ServerGenericDeviceMessageAttributes {
   #[getset(get = "pub")]
@@ -290,7 +301,7 @@ pub struct ServerGenericDeviceMessageAttributes {
   #[serde(rename = "ActuatorType")]
   actuator_type: ActuatorType,
   #[serde(rename = "StepRange")]
-  #[serde(skip_serializing)]
+  #[serde(serialize_with = "serialize_step_range")]
   #[getset(get = "pub", set = "pub")]
   step_range: RangeInclusive<u32>,
 }
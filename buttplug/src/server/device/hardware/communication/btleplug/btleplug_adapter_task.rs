@@ -6,7 +6,10 @@
 // for full license information.
 
 use super::btleplug_hardware::BtleplugHardwareConnector;
-use crate::server::device::hardware::communication::HardwareCommunicationManagerEvent;
+use crate::server::device::hardware::communication::{
+  AdvertisementRecord,
+  HardwareCommunicationManagerEvent,
+};
 use btleplug::{
   api::{Central, CentralEvent, Manager as _, Peripheral, ScanFilter},
   platform::{Adapter, Manager, PeripheralId},
@@ -43,6 +46,8 @@ pub struct BtleplugAdapterTask {
   event_sender: Sender<HardwareCommunicationManagerEvent>,
   command_receiver: Receiver<BtleplugAdapterCommand>,
   adapter_connected: Arc<AtomicBool>,
+  /// Set while advertisement scans are running, the only time advertisements are wanted.
+  reporting_advertisements: Arc<AtomicBool>,
 }
 
 impl BtleplugAdapterTask {
@@ -50,11 +55,13 @@ impl BtleplugAdapterTask {
     event_sender: Sender<HardwareCommunicationManagerEvent>,
     command_receiver: Receiver<BtleplugAdapterCommand>,
     adapter_connected: Arc<AtomicBool>,
+    reporting_advertisements: Arc<AtomicBool>,
   ) -> Self {
     Self {
       event_sender,
       command_receiver,
       adapter_connected,
+      reporting_advertisements,
     }
   }
 
@@ -87,6 +94,34 @@ impl BtleplugAdapterTask {
       String::new()
     };
 
+    // While advertisement scans are running, every advertisement is reported, including ones
+    // from devices we'll ignore below.
+    if self.reporting_advertisements.load(Ordering::SeqCst) {
+      let mut manufacturer_data: Vec<(&u16, &Vec<u8>)> =
+        properties.manufacturer_data.iter().collect();
+      manufacturer_data.sort_by_key(|(company_id, _)| **company_id);
+      let manufacturer_data: Vec<u8> = manufacturer_data
+        .into_iter()
+        .flat_map(|(company_id, data)| company_id.to_le_bytes().into_iter().chain(data.clone()))
+        .collect();
+      if self
+        .event_sender
+        .send(HardwareCommunicationManagerEvent::Advertisement(
+          AdvertisementRecord::new(
+            &format!("{:?}", peripheral_id),
+            &device_name,
+            properties.rssi.map(i32::from),
+            &manufacturer_data,
+            &properties.services,
+          ),
+        ))
+        .await
+        .is_err()
+      {
+        error!("Device manager receiver dropped, cannot send advertisement.");
+      }
+    }
+
     let peripheral_info = PeripheralInfo {
       name: properties.local_name.clone(),
       peripheral_id: peripheral_id.clone(),
@@ -121,6 +156,7 @@ impl BtleplugAdapterTask {
         .send(HardwareCommunicationManagerEvent::DeviceFound {
           name: device_name,
           address: format!("{:?}", peripheral_id),
+          rssi: properties.rssi.map(i32::from),
           creator: device_creator,
         })
         .await
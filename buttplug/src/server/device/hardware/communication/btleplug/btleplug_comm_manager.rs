@@ -38,6 +38,7 @@ pub struct BtlePlugCommunicationManager {
   adapter_event_sender: Sender<BtleplugAdapterCommand>,
   scanning_status: Arc<AtomicBool>,
   adapter_connected: Arc<AtomicBool>,
+  reporting_advertisements: Arc<AtomicBool>,
 }
 
 impl BtlePlugCommunicationManager {
@@ -45,14 +46,22 @@ impl BtlePlugCommunicationManager {
     let (sender, receiver) = channel(256);
     let adapter_connected = Arc::new(AtomicBool::new(false));
     let adapter_connected_clone = adapter_connected.clone();
+    let reporting_advertisements = Arc::new(AtomicBool::new(false));
+    let reporting_advertisements_clone = reporting_advertisements.clone();
     async_manager::spawn(async move {
-      let mut task = BtleplugAdapterTask::new(event_sender, receiver, adapter_connected_clone);
+      let mut task = BtleplugAdapterTask::new(
+        event_sender,
+        receiver,
+        adapter_connected_clone,
+        reporting_advertisements_clone,
+      );
       task.run().await;
     });
     Self {
       adapter_event_sender: sender,
       scanning_status: Arc::new(AtomicBool::new(false)),
       adapter_connected,
+      reporting_advertisements,
     }
   }
 }
@@ -119,6 +128,12 @@ impl HardwareCommunicationManager for BtlePlugCommunicationManager {
   fn can_scan(&self) -> bool {
     self.adapter_connected.load(Ordering::SeqCst)
   }
+
+  fn set_reporting_advertisements(&mut self, reporting: bool) {
+    self
+      .reporting_advertisements
+      .store(reporting, Ordering::SeqCst);
+  }
 }
 /*
 impl Drop for BtlePlugCommunicationManager {
@@ -186,6 +186,7 @@ impl LovenseConnectServiceCommunicationManager {
               .send(HardwareCommunicationManagerEvent::DeviceFound {
                 name: toy.name.clone(),
                 address: toy.id.clone(),
+                rssi: None,
                 creator: device_creator,
               })
               .await
@@ -633,6 +633,7 @@ impl LovenseDongleState for LovenseDongleDeviceLoop {
       .send_event(HardwareCommunicationManagerEvent::DeviceFound {
         name: "Lovense Dongle Device".to_owned(),
         address: self.device_id.clone(),
+        rssi: None,
         creator: Box::new(LovenseDongleHardwareConnector::new(
           &self.device_id,
           device_write_sender,
@@ -47,11 +47,51 @@ use crate::{
 };
 use async_trait::async_trait;
 use futures::future::{self, FutureExt};
+use getset::{CopyGetters, Getters};
 use serde::{Deserialize, Serialize};
 use std::{sync::Arc, time::Duration};
 use thiserror::Error;
 use tokio::sync::mpsc::Sender;
 use tokio_util::sync::CancellationToken;
+use uuid::Uuid;
+
+/// Advertisement heard during scanning, whether or not the device it came from is one we know
+/// how to talk to. Reported by comm managers for hardware that advertises (i.e. bluetooth).
+#[derive(Debug, Clone, PartialEq, Eq, Getters, CopyGetters)]
+pub struct AdvertisementRecord {
+  #[getset(get = "pub")]
+  address: String,
+  /// Advertised name, empty if the device didn't advertise one.
+  #[getset(get = "pub")]
+  name: String,
+  #[getset(get_copy = "pub")]
+  rssi: Option<i32>,
+  /// Manufacturer specific data, as it appears in the advertisement: the 2 byte company
+  /// identifier, little endian, followed by the data. Devices that advertise data for more than
+  /// one company have their entries concatenated, ordered by company identifier.
+  #[getset(get = "pub")]
+  manufacturer_data: Vec<u8>,
+  #[getset(get = "pub")]
+  service_uuids: Vec<Uuid>,
+}
+
+impl AdvertisementRecord {
+  pub fn new(
+    address: &str,
+    name: &str,
+    rssi: Option<i32>,
+    manufacturer_data: &[u8],
+    service_uuids: &[Uuid],
+  ) -> Self {
+    Self {
+      address: address.to_owned(),
+      name: name.to_owned(),
+      rssi,
+      manufacturer_data: manufacturer_data.to_vec(),
+      service_uuids: service_uuids.to_vec(),
+    }
+  }
+}
 
 #[derive(Debug)]
 pub enum HardwareCommunicationManagerEvent {
@@ -60,8 +100,15 @@ pub enum HardwareCommunicationManagerEvent {
   DeviceFound {
     name: String,
     address: String,
+    /// Signal strength of the advertisement, for hardware that reports one (i.e. bluetooth).
+    rssi: Option<i32>,
     creator: Box<dyn HardwareConnector>,
   },
+  /// An advertisement was heard, see
+  /// [ServerDeviceManager::scan_advertisements](crate::server::device::ServerDeviceManager::scan_advertisements).
+  /// Sent along with DeviceFound, not instead of it, and only while
+  /// [HardwareCommunicationManager::set_reporting_advertisements] is on.
+  Advertisement(AdvertisementRecord),
   ScanningFinished,
 }
 
@@ -80,9 +127,21 @@ pub trait HardwareCommunicationManager: Send + Sync {
     false
   }
   fn can_scan(&self) -> bool;
+  /// Whether to send [HardwareCommunicationManagerEvent::Advertisement] events, which are only
+  /// wanted while advertisement scans are running. Managers that never send them can ignore this.
+  fn set_reporting_advertisements(&mut self, _reporting: bool) {
+  }
   // Events happen via channel senders passed to the comm manager.
 }
 
+impl std::fmt::Debug for dyn HardwareCommunicationManager {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    f.debug_struct("HardwareCommunicationManager")
+      .field("name", &self.name())
+      .finish()
+  }
+}
+
 #[derive(Error, Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
 pub enum HardwareSpecificError {
   // XInput library doesn't derive error on its error enum. :(
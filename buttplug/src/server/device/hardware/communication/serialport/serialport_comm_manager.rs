@@ -72,6 +72,7 @@ impl TimedRetryCommunicationManagerImpl for SerialPortCommunicationManager {
             .send(HardwareCommunicationManagerEvent::DeviceFound {
               name: format!("Serial Port Device {}", p.port_name),
               address: p.port_name.clone(),
+              rssi: None,
               creator: Box::new(SerialPortHardwareConnector::new(&p)),
             })
             .await
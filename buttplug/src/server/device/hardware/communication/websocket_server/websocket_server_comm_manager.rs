@@ -147,6 +147,7 @@ impl WebsocketServerDeviceCommunicationManager {
                   .send(HardwareCommunicationManagerEvent::DeviceFound {
                     name: format!("Websocket Device {}", info_packet.identifier),
                     address: info_packet.address.clone(),
+                    rssi: None,
                     creator: Box::new(WebsocketServerHardwareConnector::new(
                       info_packet,
                       ws_stream,
@@ -85,6 +85,7 @@ impl TimedRetryCommunicationManagerImpl for XInputDeviceCommunicationManager {
             .send(HardwareCommunicationManagerEvent::DeviceFound {
               name: i.to_string(),
               address: i.to_string(),
+              rssi: None,
               creator: device_creator,
             })
             .await
@@ -1,6 +1,10 @@
 pub mod communication;
 
-use std::fmt::Debug;
+use std::{
+  fmt::Debug,
+  sync::{Arc, Mutex},
+  time::Instant,
+};
 
 use crate::{
   core::{
@@ -10,7 +14,7 @@ use crate::{
   server::device::configuration::ProtocolCommunicationSpecifier,
 };
 use async_trait::async_trait;
-use futures::future::BoxFuture;
+use futures::{future::BoxFuture, FutureExt};
 use getset::{CopyGetters, Getters};
 use serde::{Deserialize, Serialize};
 use tokio::sync::broadcast;
@@ -240,6 +244,8 @@ pub struct Hardware {
   endpoints: Vec<Endpoint>,
   /// Internal implementation details
   internal_impl: Box<dyn HardwareInternal>,
+  /// Time of the last successful command or notification, see [Hardware::last_seen].
+  last_seen: Arc<Mutex<Instant>>,
 }
 
 impl Hardware {
@@ -254,6 +260,7 @@ impl Hardware {
       address: address.to_owned(),
       endpoints: endpoints.into(),
       internal_impl,
+      last_seen: Arc::new(Mutex::new(Instant::now())),
     }
   }
 
@@ -272,6 +279,37 @@ impl Hardware {
     self.endpoints.clone()
   }
 
+  /// Time the device was last heard from: when it connected, last completed a read, write,
+  /// subscribe or unsubscribe, or last had a notification passed on by its
+  /// [ServerDevice](crate::server::device::ServerDevice).
+  pub fn last_seen(&self) -> Instant {
+    *self.last_seen.lock().expect("Lock poisoned")
+  }
+
+  /// Record that the device was just heard from.
+  pub(crate) fn mark_seen(&self) {
+    *self.last_seen.lock().expect("Lock poisoned") = Instant::now();
+  }
+
+  /// Update [Hardware::last_seen] once `fut` succeeds.
+  fn track_seen<T>(
+    &self,
+    fut: BoxFuture<'static, Result<T, ButtplugDeviceError>>,
+  ) -> BoxFuture<'static, Result<T, ButtplugDeviceError>>
+  where
+    T: Send + 'static,
+  {
+    let last_seen = self.last_seen.clone();
+    async move {
+      let result = fut.await;
+      if result.is_ok() {
+        *last_seen.lock().expect("Lock poisoned") = Instant::now();
+      }
+      result
+    }
+    .boxed()
+  }
+
   /// Returns a receiver for any events the device may emit.
   ///
   /// This uses a broadcast channel and can be called multiple times to create multiple streams if
@@ -301,7 +339,7 @@ impl Hardware {
     &self,
     msg: &HardwareReadCmd,
   ) -> BoxFuture<'static, Result<HardwareReading, ButtplugDeviceError>> {
-    self.internal_impl.read_value(msg)
+    self.track_seen(self.internal_impl.read_value(msg))
   }
 
   /// Write a value to the device
@@ -309,7 +347,7 @@ impl Hardware {
     &self,
     msg: &HardwareWriteCmd,
   ) -> BoxFuture<'static, Result<(), ButtplugDeviceError>> {
-    self.internal_impl.write_value(msg)
+    self.track_seen(self.internal_impl.write_value(msg))
   }
 
   /// Subscribe to a device endpoint, if it exists
@@ -317,7 +355,7 @@ impl Hardware {
     &self,
     msg: &HardwareSubscribeCmd,
   ) -> BoxFuture<'static, Result<(), ButtplugDeviceError>> {
-    self.internal_impl.subscribe(msg)
+    self.track_seen(self.internal_impl.subscribe(msg))
   }
 
   /// Unsubscribe from a device endpoint, if it exists
@@ -325,7 +363,7 @@ impl Hardware {
     &self,
     msg: &HardwareUnsubscribeCmd,
   ) -> BoxFuture<'static, Result<(), ButtplugDeviceError>> {
-    self.internal_impl.unsubscribe(msg)
+    self.track_seen(self.internal_impl.unsubscribe(msg))
   }
 }
 
@@ -0,0 +1,88 @@
+// Buttplug Rust Source Code File - See https://buttplug.io for more info.
+//
+// Copyright 2016-2023 Nonpolynomial Labs LLC. All rights reserved.
+//
+// Licensed under the BSD 3-Clause license. See LICENSE file in the project root
+// for full license information.
+
+//! Latency histograms for device commands.
+
+use std::time::Duration;
+
+/// Number of power-of-two microsecond buckets, covering latencies up to ~35 minutes.
+const HISTOGRAM_BUCKETS: usize = 32;
+
+/// Histogram of how long device commands took, from the server receiving the command until the
+/// device acknowledged it.
+///
+/// Latencies are recorded in power-of-two microsecond buckets, so percentiles are upper bounds
+/// accurate to within a factor of 2.
+#[derive(Debug, Clone, Default)]
+pub struct LatencyHistogram {
+  buckets: [u64; HISTOGRAM_BUCKETS],
+  max: Duration,
+}
+
+impl LatencyHistogram {
+  fn bucket_index(latency: Duration) -> usize {
+    let micros = latency.as_micros().max(1);
+    // Bucket n holds latencies in (2^(n-1), 2^n] microseconds.
+    let index = (u128::BITS - (micros - 1).leading_zeros()) as usize;
+    index.min(HISTOGRAM_BUCKETS - 1)
+  }
+
+  pub(crate) fn record(&mut self, latency: Duration) {
+    self.buckets[Self::bucket_index(latency)] += 1;
+    self.max = self.max.max(latency);
+  }
+
+  /// Number of latencies recorded.
+  pub fn count(&self) -> u64 {
+    self.buckets.iter().sum()
+  }
+
+  /// Longest latency recorded.
+  pub fn max(&self) -> Duration {
+    self.max
+  }
+
+  /// Upper bound of the latency at the given percentile (0.0-100.0), or None if nothing has been
+  /// recorded yet.
+  pub fn percentile(&self, percentile: f64) -> Option<Duration> {
+    let total = self.count();
+    if total == 0 {
+      return None;
+    }
+    let target = ((percentile.clamp(0.0, 100.0) / 100.0) * total as f64).ceil() as u64;
+    let mut seen = 0;
+    for (index, bucket) in self.buckets.iter().enumerate() {
+      seen += bucket;
+      if seen >= target.max(1) {
+        return Some(Duration::from_micros(1 << index));
+      }
+    }
+    Some(Duration::from_micros(1 << (HISTOGRAM_BUCKETS - 1)))
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+
+  #[test]
+  fn test_latency_histogram_percentiles() {
+    let mut histogram = LatencyHistogram::default();
+    assert!(histogram.percentile(50.0).is_none());
+    for _ in 0..3 {
+      histogram.record(Duration::from_micros(100));
+    }
+    histogram.record(Duration::from_millis(10));
+    assert_eq!(histogram.count(), 4);
+    assert_eq!(histogram.max(), Duration::from_millis(10));
+    assert_eq!(histogram.percentile(50.0), Some(Duration::from_micros(128)));
+    assert_eq!(
+      histogram.percentile(100.0),
+      Some(Duration::from_micros(16384))
+    );
+  }
+}
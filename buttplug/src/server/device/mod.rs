@@ -95,12 +95,36 @@
 //!
 //!
 
+mod command_conflict;
+mod command_priority_queue;
 pub mod configuration;
 pub mod hardware;
+#[cfg(feature = "metrics")]
+mod latency_histogram;
 pub mod protocol;
 pub mod server_device;
 mod server_device_manager;
 mod server_device_manager_event_loop;
+mod virtual_device;
 
-pub use server_device::{ServerDevice, ServerDeviceEvent, ServerDeviceIdentifier};
-pub use server_device_manager::{ServerDeviceManager, ServerDeviceManagerBuilder};
+pub use command_conflict::CommandConflictPolicy;
+#[cfg(feature = "metrics")]
+pub use latency_histogram::LatencyHistogram;
+pub use server_device::{
+  ServerDevice,
+  ServerDeviceEvent,
+  ServerDeviceIdentifier,
+  LATENCY_AVERAGE_WINDOW,
+  MAX_RAW_STREAM_SIZE,
+};
+pub use server_device_manager::{
+  CommManagerStatus,
+  CommandStatistics,
+  DiscoveredDevice,
+  ServerDeviceInfo,
+  ServerDeviceManager,
+  ServerDeviceManagerBuilder,
+  DEFAULT_DEVICE_MAX_RECONNECT_ATTEMPTS,
+  DEFAULT_DEVICE_RECONNECT_DELAY,
+};
+pub use virtual_device::{VirtualDeviceConfig, VIRTUAL_DEVICE_PROTOCOL};
@@ -5,30 +5,10 @@
 // Licensed under the BSD 3-Clause license. See LICENSE file in the project root
 // for full license information.
 
-use crate::{
-  core::{errors::ButtplugDeviceError, message::Endpoint},
-  server::device::{
-    hardware::{HardwareCommand, HardwareWriteCmd},
-    protocol::{generic_protocol_setup, ProtocolHandler},
-  },
-};
+use crate::server::device::protocol::buttplug_protocol;
 
-generic_protocol_setup!(Aneros, "aneros");
-
-#[derive(Default)]
-pub struct Aneros {}
-
-impl ProtocolHandler for Aneros {
-  fn handle_scalar_vibrate_cmd(
-    &self,
-    index: u32,
-    scalar: u32,
-  ) -> Result<Vec<HardwareCommand>, ButtplugDeviceError> {
-    Ok(vec![HardwareWriteCmd::new(
-      Endpoint::Tx,
-      vec![0xF1 + (index as u8), scalar as u8],
-      false,
-    )
-    .into()])
-  }
-}
+buttplug_protocol!(
+  Aneros,
+  "aneros";
+  scalar_vibrate: |index, scalar| [0xF1 + (index as u8), scalar as u8],
+);
@@ -5,30 +5,10 @@
 // Licensed under the BSD 3-Clause license. See LICENSE file in the project root
 // for full license information.
 
-use crate::{
-  core::{errors::ButtplugDeviceError, message::Endpoint},
-  server::device::{
-    hardware::{HardwareCommand, HardwareWriteCmd},
-    protocol::{generic_protocol_setup, ProtocolHandler},
-  },
-};
+use crate::server::device::protocol::buttplug_protocol;
 
-generic_protocol_setup!(Fox, "fox");
-
-#[derive(Default)]
-pub struct Fox {}
-
-impl ProtocolHandler for Fox {
-  fn handle_scalar_vibrate_cmd(
-    &self,
-    _index: u32,
-    scalar: u32,
-  ) -> Result<Vec<HardwareCommand>, ButtplugDeviceError> {
-    Ok(vec![HardwareWriteCmd::new(
-      Endpoint::Tx,
-      vec![0x03, 0x01, 0x01, 0xfe, scalar as u8],
-      false,
-    )
-    .into()])
-  }
-}
+buttplug_protocol!(
+  Fox,
+  "fox";
+  scalar_vibrate: |_index, scalar| [0x03, 0x01, 0x01, 0xfe, scalar as u8],
+);
@@ -23,7 +23,7 @@ use crate::{
 use getset::Getters;
 use std::{
   ops::RangeInclusive,
-  sync::atomic::{AtomicBool, AtomicU32, Ordering::SeqCst},
+  sync::atomic::{AtomicBool, AtomicU32, AtomicU64, Ordering::SeqCst},
 };
 
 #[derive(Getters)]
@@ -32,6 +32,8 @@ struct ScalarGenericCommand {
   actuator: ActuatorType,
   step_range: RangeInclusive<u32>,
   value: AtomicU32,
+  /// Last 0.0-1.0 value sent, stored as f64 bits, so it can be sent again exactly.
+  scalar: AtomicU64,
 }
 
 impl ScalarGenericCommand {
@@ -40,6 +42,7 @@ impl ScalarGenericCommand {
       actuator: *attributes.actuator_type(),
       step_range: attributes.step_range().clone(),
       value: AtomicU32::new(0),
+      scalar: AtomicU64::new(0.0f64.to_bits()),
     }
   }
 }
@@ -185,6 +188,9 @@ impl GenericCommandManager {
       // these values get None in our return vector.
       let current_scalar = self.scalars[index].value().load(SeqCst);
       let sent_scalar = self.sent_scalar.load(SeqCst);
+      self.scalars[index]
+        .scalar()
+        .store(scalar_command.scalar().to_bits(), SeqCst);
       if !sent_scalar || scalar != current_scalar {
         self.scalars[index].value().store(scalar, SeqCst);
         result[index] = Some((*self.scalars[index].actuator(), scalar));
@@ -223,6 +229,22 @@ impl GenericCommandManager {
       .collect()
   }
 
+  /// Last value (0.0-1.0) sent to each scalar actuator, 0.0 for actuators nothing was sent to.
+  pub fn scalar_values(&self) -> Vec<ScalarSubcommand> {
+    self
+      .scalars
+      .iter()
+      .enumerate()
+      .map(|(index, cmd)| {
+        ScalarSubcommand::new(
+          index as u32,
+          f64::from_bits(cmd.scalar().load(SeqCst)),
+          *cmd.actuator(),
+        )
+      })
+      .collect()
+  }
+
   pub fn update_rotation(
     &self,
     msg: &RotateCmd,
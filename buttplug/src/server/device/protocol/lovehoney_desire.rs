@@ -12,7 +12,7 @@ use crate::{
   },
   server::device::{
     hardware::{HardwareCommand, HardwareWriteCmd},
-    protocol::{generic_protocol_setup, ProtocolHandler},
+    protocol::{generic_protocol_setup, ProtocolCapabilityFlags, ProtocolHandler},
   },
 };
 
@@ -26,6 +26,20 @@ impl ProtocolHandler for LovehoneyDesire {
     true
   }
 
+  fn capability_flags(&self) -> ProtocolCapabilityFlags {
+    ProtocolCapabilityFlags::SAFE_STOP
+  }
+
+  fn handle_safe_stop(&self) -> Result<Vec<HardwareCommand>, ButtplugDeviceError> {
+    // Motor index 0 addresses both motors.
+    Ok(vec![HardwareWriteCmd::new(
+      Endpoint::Tx,
+      vec![0xF3, 0, 0],
+      true,
+    )
+    .into()])
+  }
+
   fn handle_scalar_cmd(
     &self,
     cmds: &[Option<(ActuatorType, u32)>],
@@ -111,12 +111,18 @@ use futures::{
   future::{self, BoxFuture, FutureExt},
   StreamExt,
 };
+use getset::{CopyGetters, Getters};
 use std::pin::Pin;
 use std::{collections::HashMap, sync::Arc};
 
 pub trait ProtocolIdentifierFactory: Send + Sync {
   fn identifier(&self) -> &str;
   fn create(&self) -> Box<dyn ProtocolIdentifier>;
+  /// Instructions for getting a device using this protocol into pairing mode, shown to users by
+  /// [ButtplugServer::start_pairing](crate::server::ButtplugServer::start_pairing).
+  fn pairing_hints(&self) -> Vec<String> {
+    vec![]
+  }
 }
 
 pub fn get_default_protocol_map() -> HashMap<String, Arc<dyn ProtocolIdentifierFactory>> {
@@ -464,11 +470,86 @@ impl ProtocolInitializer for GenericProtocolInitializer {
   }
 }
 
+bitflags::bitflags! {
+  /// Optional features of a device, reported by its protocol handler and used to advertise what a
+  /// device can do.
+  #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+  #[cfg_attr(feature = "serialize-json", derive(serde::Serialize, serde::Deserialize))]
+  pub struct ProtocolCapabilityFlags: u32 {
+    /// The protocol implements [ProtocolHandler::handle_safe_stop], a single command that stops
+    /// all device actuators, which is used instead of the regular stop commands.
+    const SAFE_STOP = 1;
+    /// The device can report its battery level.
+    const SUPPORTS_BATTERY = 1 << 1;
+    /// The device can report its RSSI level.
+    const SUPPORTS_RSSI = 1 << 2;
+    /// The device has readable or subscribable sensors.
+    const SUPPORTS_SENSORS = 1 << 3;
+    /// The protocol implements [ProtocolHandler::handle_calibrate].
+    const CALIBRATION = 1 << 4;
+  }
+}
+
+/// Outcome of a device calibration sequence, see [ProtocolHandler::handle_calibrate].
+#[derive(Debug, Clone, PartialEq, Default, Getters, CopyGetters)]
+pub struct CalibrationResult {
+  #[getset(get_copy = "pub")]
+  success: bool,
+  /// Protocol specific values measured or set during calibration, like a linear actuator's zero
+  /// point.
+  #[getset(get = "pub")]
+  data: HashMap<String, f64>,
+}
+
+impl CalibrationResult {
+  pub fn new(success: bool, data: HashMap<String, f64>) -> Self {
+    Self { success, data }
+  }
+}
+
 pub trait ProtocolHandler: Sync + Send {
   fn needs_full_command_set(&self) -> bool {
     false
   }
 
+  /// Version of the protocol implementation, bumped when the way it talks to hardware changes.
+  /// Reported along with the protocol name, see
+  /// [ServerDevice::protocol_version](crate::server::device::ServerDevice::protocol_version).
+  fn version(&self) -> &'static str {
+    "1.0"
+  }
+
+  /// Features provided by the protocol implementation itself. Sensor support flags are also
+  /// derived from the device configuration, so protocols only need to set them for sensors that
+  /// aren't listed there.
+  fn capability_flags(&self) -> ProtocolCapabilityFlags {
+    ProtocolCapabilityFlags::empty()
+  }
+
+  /// Highest value that's safe to send to the scalar actuator at the given index, as a fraction of
+  /// its step range. Protocols for hardware that shouldn't be run at full power can lower this.
+  fn max_actuator_value(&self, _actuator_index: u32) -> f64 {
+    1.0
+  }
+
+  /// Commands to stop all actuators at once. Only called if [ProtocolHandler::capability_flags]
+  /// includes [ProtocolCapabilityFlags::SAFE_STOP].
+  fn handle_safe_stop(&self) -> Result<Vec<HardwareCommand>, ButtplugDeviceError> {
+    self.command_unimplemented("SafeStop")
+  }
+
+  /// Run the device's calibration sequence. Only called if [ProtocolHandler::capability_flags]
+  /// includes [ProtocolCapabilityFlags::CALIBRATION].
+  fn handle_calibrate(
+    &self,
+    _device: Arc<Hardware>,
+  ) -> BoxFuture<Result<CalibrationResult, ButtplugDeviceError>> {
+    future::ready(Err(ButtplugDeviceError::UnhandledCommand(
+      "Command not implemented for this protocol: Calibrate".to_string(),
+    )))
+    .boxed()
+  }
+
   fn has_handle_message(&self) -> bool {
     false
   }
@@ -754,6 +835,158 @@ macro_rules! generic_protocol_initializer_setup {
   };
 }
 
+/// Defines a generic (no initialization step) protocol that writes a fixed byte layout for each
+/// supported scalar command.
+///
+/// Takes the protocol struct name and its identifier in the device configuration file, followed by
+/// optional `endpoint` (`Tx` by default) and `write_with_response` (`false` by default) settings.
+/// After a `;` comes a byte array for each supported command, named after its [ProtocolHandler]
+/// method without the `handle_` and `_cmd` parts. The array is preceded by names for the actuator
+/// index and the stepped command value, both `u32`, in closure style.
+///
+/// ```ignore
+/// buttplug_protocol!(
+///   Aneros,
+///   "aneros";
+///   scalar_vibrate: |index, scalar| [0xF1 + (index as u8), scalar as u8],
+/// );
+/// ```
+#[macro_export]
+macro_rules! buttplug_protocol {
+  (@endpoint []) => {
+    $crate::core::message::Endpoint::Tx
+  };
+  (@endpoint [$endpoint:ident]) => {
+    $crate::core::message::Endpoint::$endpoint
+  };
+  (@write_with_response []) => {
+    false
+  };
+  (@write_with_response [$write_with_response:literal]) => {
+    $write_with_response
+  };
+  (
+    @define $protocol_name:ident,
+    $protocol_identifier:literal,
+    $endpoint:tt,
+    $write_with_response:tt;
+    $($command:ident: |$index:ident, $scalar:ident| [$($byte:expr),* $(,)?]),* $(,)?
+  ) => {
+    $crate::generic_protocol_setup!($protocol_name, $protocol_identifier);
+
+    #[derive(Default)]
+    pub struct $protocol_name {}
+
+    paste::paste! {
+      impl $crate::server::device::protocol::ProtocolHandler for $protocol_name {
+        $(
+          fn [< handle_ $command _cmd >](
+            &self,
+            $index: u32,
+            $scalar: u32,
+          ) -> Result<
+            Vec<$crate::server::device::hardware::HardwareCommand>,
+            $crate::core::errors::ButtplugDeviceError,
+          > {
+            Ok(vec![$crate::server::device::hardware::HardwareWriteCmd::new(
+              $crate::buttplug_protocol!(@endpoint $endpoint),
+              vec![$($byte),*],
+              $crate::buttplug_protocol!(@write_with_response $write_with_response),
+            )
+            .into()])
+          }
+        )*
+      }
+    }
+  };
+  (
+    $protocol_name:ident,
+    $protocol_identifier:literal
+    $(, endpoint: $endpoint:ident)?
+    $(, write_with_response: $write_with_response:literal)?;
+    $($commands:tt)*
+  ) => {
+    $crate::buttplug_protocol!(
+      @define $protocol_name,
+      $protocol_identifier,
+      [$($endpoint)?],
+      [$($write_with_response)?];
+      $($commands)*
+    );
+  };
+}
+
 use crate::server::device::configuration::ProtocolDeviceAttributes;
+pub use buttplug_protocol;
 pub use generic_protocol_initializer_setup;
 pub use generic_protocol_setup;
+
+#[cfg(test)]
+mod test {
+  use super::{ProtocolHandler, ProtocolIdentifierFactory};
+  use crate::{
+    core::message::Endpoint,
+    server::device::hardware::{HardwareCommand, HardwareWriteCmd},
+  };
+
+  mod default_settings {
+    crate::server::device::protocol::buttplug_protocol!(
+      DefaultSettings,
+      "default-settings";
+      scalar_vibrate: |index, scalar| [0x01, index as u8, scalar as u8],
+    );
+  }
+
+  mod custom_settings {
+    crate::server::device::protocol::buttplug_protocol!(
+      CustomSettings,
+      "custom-settings",
+      endpoint: Rx,
+      write_with_response: true;
+      scalar_vibrate: |_index, scalar| [scalar as u8],
+      scalar_rotate: |index, scalar| [0x02, index as u8, scalar as u8]
+    );
+  }
+
+  fn write(endpoint: Endpoint, data: Vec<u8>, write_with_response: bool) -> Vec<HardwareCommand> {
+    vec![HardwareWriteCmd::new(endpoint, data, write_with_response).into()]
+  }
+
+  #[test]
+  fn test_buttplug_protocol_default_settings() {
+    assert_eq!(
+      default_settings::setup::DefaultSettingsIdentifierFactory::default().identifier(),
+      "default-settings"
+    );
+    let protocol = default_settings::DefaultSettings::default();
+    assert_eq!(
+      protocol
+        .handle_scalar_vibrate_cmd(1, 20)
+        .expect("Test, assuming infallible."),
+      write(Endpoint::Tx, vec![0x01, 1, 20], false)
+    );
+    // Commands that weren't listed keep the ProtocolHandler default.
+    assert!(protocol.handle_scalar_rotate_cmd(0, 20).is_err());
+  }
+
+  #[test]
+  fn test_buttplug_protocol_custom_settings() {
+    assert_eq!(
+      custom_settings::setup::CustomSettingsIdentifierFactory::default().identifier(),
+      "custom-settings"
+    );
+    let protocol = custom_settings::CustomSettings::default();
+    assert_eq!(
+      protocol
+        .handle_scalar_vibrate_cmd(0, 5)
+        .expect("Test, assuming infallible."),
+      write(Endpoint::Rx, vec![5], true)
+    );
+    assert_eq!(
+      protocol
+        .handle_scalar_rotate_cmd(2, 5)
+        .expect("Test, assuming infallible."),
+      write(Endpoint::Rx, vec![0x02, 2, 5], true)
+    );
+  }
+}
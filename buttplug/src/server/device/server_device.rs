@@ -6,8 +6,15 @@
 // for full license information.
 
 use std::{
+  collections::VecDeque,
   fmt::{self, Debug},
-  sync::Arc,
+  sync::{
+    atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering},
+    Arc,
+    Mutex,
+    Weak,
+  },
+  time::{Duration, Instant},
 };
 
 use crate::{
@@ -23,10 +30,14 @@ use crate::{
       ButtplugMessage,
       ButtplugServerDeviceMessage,
       ButtplugServerMessage,
+      CommandPriority,
       Endpoint,
+      LinearCmd,
+      PrioritizedScalarCmd,
       RSSILevelReading,
       RawReading,
       RawSubscribeCmd,
+      RotateCmd,
       ScalarCmd,
       ScalarSubcommand,
       SensorDeviceMessageAttributes,
@@ -38,25 +49,171 @@ use crate::{
   server::{
     device::{
       configuration::{DeviceConfigurationManager, ProtocolAttributesType},
-      hardware::{Hardware, HardwareCommand, HardwareConnector, HardwareEvent},
-      protocol::ProtocolHandler,
+      hardware::{Hardware, HardwareCommand, HardwareConnector, HardwareEvent, HardwareWriteCmd},
+      protocol::{CalibrationResult, ProtocolCapabilityFlags, ProtocolHandler, ProtocolIdentifier},
     },
     ButtplugServerResultFuture,
   },
-  util::stream::convert_broadcast_receiver_to_stream,
+  util::{async_manager, stream::convert_broadcast_receiver_to_stream},
 };
 use core::hash::{Hash, Hasher};
-use dashmap::DashSet;
+use dashmap::{DashMap, DashSet};
 use futures::future::{self, FutureExt};
 use getset::{Getters, MutGetters, Setters};
 use serde::{Deserialize, Serialize};
 use tokio_stream::StreamExt;
 
 use super::{
+  command_priority_queue::CommandPriorityQueue,
   configuration::{ProtocolDeviceAttributes, ServerDeviceMessageAttributes},
   protocol::{generic_command_manager::GenericCommandManager, ProtocolSpecializer},
 };
 
+/// Most data a [RawStreamCmd](message::RawStreamCmd) transfer can hold before it's written, in
+/// bytes. Transfers growing past this are dropped.
+pub const MAX_RAW_STREAM_SIZE: usize = 1024 * 1024;
+
+/// Number of recent hardware writes averaged by [ServerDevice::average_hardware_latency].
+pub const LATENCY_AVERAGE_WINDOW: usize = 100;
+
+/// How long the last [LATENCY_AVERAGE_WINDOW] successful hardware writes took, oldest first.
+#[derive(Default)]
+struct LatencyWindow {
+  samples: VecDeque<Duration>,
+  total: Duration,
+}
+
+impl LatencyWindow {
+  fn record(&mut self, latency: Duration) {
+    if self.samples.len() == LATENCY_AVERAGE_WINDOW {
+      if let Some(oldest) = self.samples.pop_front() {
+        self.total -= oldest;
+      }
+    }
+    self.samples.push_back(latency);
+    self.total += latency;
+  }
+
+  fn average(&self) -> Option<Duration> {
+    if self.samples.is_empty() {
+      None
+    } else {
+      Some(self.total / self.samples.len() as u32)
+    }
+  }
+}
+
+/// Priority to send a client's [PrioritizedScalarCmd] at. Critical is kept for stop commands, so
+/// client commands never tie with them.
+fn client_priority(msg: &PrioritizedScalarCmd) -> CommandPriority {
+  msg.priority().min(CommandPriority::High)
+}
+
+/// Actuators are debounced separately, by message type and actuator index.
+type ActuatorKey = (ButtplugDeviceMessageType, u32);
+
+/// Debounce state of a single actuator.
+#[derive(Default)]
+struct ActuatorDebounce {
+  /// When a value last went out to the actuator. Put back to what it was if sending fails, so a
+  /// failed send doesn't hold back the next value.
+  last_sent: Option<Instant>,
+  /// Bumped for every value, so a held back value is only sent if nothing newer came in after it.
+  generation: u64,
+}
+
+/// Subcommand held back by debouncing, to be sent at `flush_at` unless its actuator's generation
+/// has moved on by then.
+struct HeldSubcommand<T> {
+  key: ActuatorKey,
+  generation: u64,
+  flush_at: Instant,
+  subcommand: T,
+}
+
+/// Hardware commands that have been generated but not sent yet. The generation changes each time
+/// they're flushed, so command series started before a flush know to stop. Stop series can't be
+/// flushed, so they're counted separately.
+#[derive(Default)]
+struct PendingCommands {
+  generation: u64,
+  count: usize,
+  stop_count: usize,
+}
+
+/// Tracks the unsent part of a command series in [PendingCommands], removing whatever is left
+/// when the series ends, fails or is dropped.
+struct PendingCommandsGuard {
+  pending_commands: Arc<Mutex<PendingCommands>>,
+  generation: u64,
+  remaining: usize,
+  /// True for stop series, which keep going through flushes.
+  stop: bool,
+}
+
+impl PendingCommandsGuard {
+  fn new(pending_commands: Arc<Mutex<PendingCommands>>, count: usize, stop: bool) -> Self {
+    let generation = {
+      let mut pending = pending_commands.lock().expect("Lock poisoned");
+      if stop {
+        pending.stop_count += count;
+      } else {
+        pending.count += count;
+      }
+      pending.generation
+    };
+    Self {
+      pending_commands,
+      generation,
+      remaining: count,
+      stop,
+    }
+  }
+
+  /// Take the next command off the queue, returning false if the queue was flushed.
+  fn take_next(&mut self) -> bool {
+    let mut pending = self.pending_commands.lock().expect("Lock poisoned");
+    if self.stop {
+      pending.stop_count -= 1;
+    } else if pending.generation != self.generation {
+      self.remaining = 0;
+      return false;
+    } else {
+      pending.count -= 1;
+    }
+    self.remaining -= 1;
+    true
+  }
+}
+
+/// Counts a client command as in flight for [ServerDevice::is_busy] until it's dropped, whether it
+/// finished, failed or was abandoned.
+struct InFlightGuard(Arc<AtomicUsize>);
+
+impl InFlightGuard {
+  fn new(in_flight: Arc<AtomicUsize>) -> Self {
+    in_flight.fetch_add(1, Ordering::SeqCst);
+    Self(in_flight)
+  }
+}
+
+impl Drop for InFlightGuard {
+  fn drop(&mut self) {
+    self.0.fetch_sub(1, Ordering::SeqCst);
+  }
+}
+
+impl Drop for PendingCommandsGuard {
+  fn drop(&mut self) {
+    let mut pending = self.pending_commands.lock().expect("Lock poisoned");
+    if self.stop {
+      pending.stop_count -= self.remaining;
+    } else if pending.generation == self.generation {
+      pending.count -= self.remaining;
+    }
+  }
+}
+
 #[derive(Debug)]
 pub enum ServerDeviceEvent {
   Connected(Arc<ServerDevice>),
@@ -95,7 +252,7 @@ pub(super) async fn build_server_device(
   device_config_manager: Arc<DeviceConfigurationManager>,
   mut hardware_connector: Box<dyn HardwareConnector>,
   protocol_specializers: Vec<ProtocolSpecializer>,
-) -> Result<ServerDevice, ButtplugDeviceError> {
+) -> Result<Arc<ServerDevice>, ButtplugDeviceError> {
   // We've already checked to make sure we have specializers in the server device manager event
   // loop. That check used to be here for sake of continuity in building devices in this method, but
   // having that done before we get here fixes issues with some device advertisement timing (See
@@ -125,9 +282,45 @@ pub(super) async fn build_server_device(
     ));
   }
 
-  let mut protocol_identifier_stage = protocol_identifier.unwrap();
-  let hardware = Arc::new(hardware_out.unwrap());
+  initialize_server_device(
+    device_config_manager,
+    protocol_identifier.unwrap(),
+    Arc::new(hardware_out.unwrap()),
+    Arc::new(DashSet::new()),
+  )
+  .await
+}
+
+/// Rebuild a connected device by running its protocol's identification and initialization
+/// handshake again on the same hardware, for when the device's capabilities may have changed
+/// (firmware updates, mode switches, etc). Raw endpoint subscriptions carry over.
+pub(super) async fn requery_server_device(
+  device_config_manager: Arc<DeviceConfigurationManager>,
+  device: &ServerDevice,
+) -> Result<Arc<ServerDevice>, ButtplugDeviceError> {
+  let protocol_identifier = device_config_manager
+    .protocol_identifier(device.identifier().protocol())
+    .ok_or_else(|| {
+      ButtplugDeviceError::DeviceConfigurationError(format!(
+        "Protocol {} is no longer available, cannot requery device.",
+        device.identifier().protocol()
+      ))
+    })?;
+  initialize_server_device(
+    device_config_manager,
+    protocol_identifier,
+    device.hardware.clone(),
+    device.raw_subscribed_endpoints.clone(),
+  )
+  .await
+}
 
+async fn initialize_server_device(
+  device_config_manager: Arc<DeviceConfigurationManager>,
+  mut protocol_identifier_stage: Box<dyn ProtocolIdentifier>,
+  hardware: Arc<Hardware>,
+  raw_subscribed_endpoints: Arc<DashSet<Endpoint>>,
+) -> Result<Arc<ServerDevice>, ButtplugDeviceError> {
   let (identifier, mut protocol_initializer) =
     protocol_identifier_stage.identify(hardware.clone()).await?;
 
@@ -158,7 +351,13 @@ pub(super) async fn build_server_device(
     .await?;
 
   // We now have fully initialized hardware, return a server device.
-  Ok(ServerDevice::new(identifier, handler, hardware, &attrs))
+  Ok(ServerDevice::new(
+    identifier,
+    handler,
+    hardware,
+    &attrs,
+    raw_subscribed_endpoints,
+  ))
 }
 
 pub struct ServerDevice {
@@ -169,6 +368,37 @@ pub struct ServerDevice {
   /// Unique identifier for the device
   identifier: ServerDeviceIdentifier,
   raw_subscribed_endpoints: Arc<DashSet<Endpoint>>,
+  /// Minimum time between commands to the same actuator, if debouncing is on.
+  command_debounce: Option<Duration>,
+  /// Debounce state of each actuator that's been sent a command.
+  actuator_debounce: DashMap<ActuatorKey, ActuatorDebounce>,
+  /// In progress [RawStreamCmd](message::RawStreamCmd) transfers, keyed by endpoint, holding the
+  /// next expected chunk index and the data received so far, up to [MAX_RAW_STREAM_SIZE].
+  raw_stream_buffers: DashMap<Endpoint, (u32, Vec<u8>)>,
+  /// False while an operator has blocked client commands to the device.
+  enabled: AtomicBool,
+  pending_commands: Arc<Mutex<PendingCommands>>,
+  /// Number of client commands dispatched to the device that haven't finished yet.
+  commands_in_flight: Arc<AtomicUsize>,
+  /// Number of hardware commands that failed since the device connected.
+  communication_error_count: Arc<AtomicU64>,
+  /// How long recent hardware writes took to be acknowledged, not counting time spent queued.
+  hardware_latencies: Arc<Mutex<LatencyWindow>>,
+  /// True once the device has been reported for going over the communication error threshold.
+  unstable_reported: AtomicBool,
+  /// Orders hardware commands waiting to be sent to the device by priority.
+  command_queue: CommandPriorityQueue,
+  /// Last battery level read from the device, and when it was read.
+  battery_level_cache: Arc<Mutex<Option<(f64, Instant)>>>,
+  /// When the device was added to the device manager, once it has been.
+  connected_at: Mutex<Option<Instant>>,
+  /// When a client last sent the device a command.
+  last_command_at: Mutex<Option<Instant>>,
+  /// When a client last sent the device an actuator command, cleared once an idle shutdown
+  /// handles it.
+  last_actuator_command_at: Mutex<Option<Instant>>,
+  /// Used to send values held back by debouncing once their window closes.
+  weak_self: Weak<ServerDevice>,
 }
 impl Debug for ServerDevice {
   fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
@@ -196,22 +426,134 @@ impl PartialEq for ServerDevice {
 
 impl ServerDevice {
   /// Given a protocol and a device impl, create a new ButtplugDevice instance
-  fn new(
+  pub(super) fn new(
     identifier: ServerDeviceIdentifier,
     handler: Arc<dyn ProtocolHandler>,
     hardware: Arc<Hardware>,
     attributes: &ProtocolDeviceAttributes,
-  ) -> Self {
+    raw_subscribed_endpoints: Arc<DashSet<Endpoint>>,
+  ) -> Arc<Self> {
     // Hook up our stream mapper now.
 
-    Self {
+    Arc::new_cyclic(|weak_self| Self {
       identifier,
       generic_command_manager: GenericCommandManager::new(attributes),
       handler,
       hardware,
       attributes: attributes.clone(),
-      raw_subscribed_endpoints: Arc::new(DashSet::new()),
-    }
+      raw_subscribed_endpoints,
+      command_debounce: attributes.command_debounce(),
+      actuator_debounce: DashMap::new(),
+      raw_stream_buffers: DashMap::new(),
+      enabled: AtomicBool::new(true),
+      pending_commands: Arc::new(Mutex::new(PendingCommands::default())),
+      commands_in_flight: Arc::new(AtomicUsize::new(0)),
+      communication_error_count: Arc::new(AtomicU64::new(0)),
+      hardware_latencies: Arc::new(Mutex::new(LatencyWindow::default())),
+      unstable_reported: AtomicBool::new(false),
+      command_queue: CommandPriorityQueue::default(),
+      battery_level_cache: Arc::new(Mutex::new(None)),
+      connected_at: Mutex::new(None),
+      last_command_at: Mutex::new(None),
+      last_actuator_command_at: Mutex::new(None),
+      weak_self: weak_self.clone(),
+    })
+  }
+
+  /// When the device was added to the device manager. None until it has been.
+  pub fn connected_at(&self) -> Option<Instant> {
+    *self.connected_at.lock().expect("Lock poisoned")
+  }
+
+  pub(super) fn set_connected_at(&self, connected_at: Option<Instant>) {
+    *self.connected_at.lock().expect("Lock poisoned") = connected_at;
+  }
+
+  /// When a client last sent the device a command, including stops. None if no client has since
+  /// it connected.
+  pub fn last_command_at(&self) -> Option<Instant> {
+    *self.last_command_at.lock().expect("Lock poisoned")
+  }
+
+  pub(super) fn set_last_command_at(&self, last_command_at: Option<Instant>) {
+    *self.last_command_at.lock().expect("Lock poisoned") = last_command_at;
+  }
+
+  /// When a client last sent the device an actuator command, see
+  /// [ButtplugDeviceCommandMessageUnion::is_actuator_command]. None if no client has since it
+  /// connected, or since an idle shutdown last stopped it.
+  pub fn last_actuator_command_at(&self) -> Option<Instant> {
+    *self.last_actuator_command_at.lock().expect("Lock poisoned")
+  }
+
+  pub(super) fn set_last_actuator_command_at(&self, last_actuator_command_at: Option<Instant>) {
+    *self.last_actuator_command_at.lock().expect("Lock poisoned") = last_actuator_command_at;
+  }
+
+  /// How long the device has been connected, or zero if it hasn't been added to the device manager
+  /// yet.
+  pub fn uptime(&self) -> Duration {
+    self
+      .connected_at()
+      .map_or(Duration::ZERO, |connected_at| connected_at.elapsed())
+  }
+
+  /// False if client commands to the device are currently blocked, see
+  /// [ServerDeviceManager::disable_device](super::ServerDeviceManager::disable_device).
+  pub fn enabled(&self) -> bool {
+    self.enabled.load(Ordering::SeqCst)
+  }
+
+  pub(super) fn set_enabled(&self, enabled: bool) {
+    self.enabled.store(enabled, Ordering::SeqCst);
+  }
+
+  /// Drop hardware commands that have been generated for the device but not sent yet, returning
+  /// how many were dropped. Commands already being sent still finish, and the futures of flushed
+  /// commands fail with [ButtplugDeviceError::DeviceCommandFlushed]. Stop commands are never
+  /// flushed.
+  pub fn flush_command_queue(&self) -> usize {
+    let mut pending = self.pending_commands.lock().expect("Lock poisoned");
+    pending.generation += 1;
+    std::mem::take(&mut pending.count)
+  }
+
+  /// Number of hardware commands generated for the device that haven't been sent yet.
+  pub fn pending_command_count(&self) -> usize {
+    let pending = self.pending_commands.lock().expect("Lock poisoned");
+    pending.count + pending.stop_count
+  }
+
+  /// True while a client command is being run on the device, on any of its actuators or sensors.
+  pub fn is_busy(&self) -> bool {
+    self.commands_in_flight.load(Ordering::SeqCst) > 0
+  }
+
+  /// Number of hardware commands that failed since the device connected, e.g. writes that didn't
+  /// go through.
+  pub fn communication_error_count(&self) -> u64 {
+    self.communication_error_count.load(Ordering::SeqCst)
+  }
+
+  /// Average time the last [LATENCY_AVERAGE_WINDOW] successful hardware writes took, from handing
+  /// the write to the hardware until it was acknowledged. Time spent debounced or waiting in the
+  /// command queue isn't included. None if nothing has been written since the device connected.
+  pub fn average_hardware_latency(&self) -> Option<Duration> {
+    self
+      .hardware_latencies
+      .lock()
+      .expect("Lock poisoned")
+      .average()
+  }
+
+  pub(super) fn clear_hardware_latencies(&self) {
+    *self.hardware_latencies.lock().expect("Lock poisoned") = LatencyWindow::default();
+  }
+
+  /// Note that the device has gone over the communication error threshold, returning true the
+  /// first time only.
+  pub(super) fn mark_unstable(&self) -> bool {
+    !self.unstable_reported.swap(true, Ordering::SeqCst)
   }
 
   /// Returns the device identifier
@@ -247,6 +589,11 @@ impl ServerDevice {
     }
   }
 
+  /// Time the device was last successfully communicated with, see [Hardware::last_seen].
+  pub fn last_seen(&self) -> Instant {
+    self.hardware.last_seen()
+  }
+
   /// Disconnect from the device, if it's connected.
   pub fn disconnect(&self) -> ButtplugResultFuture {
     let fut = self.hardware.disconnect();
@@ -258,19 +605,150 @@ impl ServerDevice {
     self.attributes.message_attributes()
   }
 
+  /// Last value (0.0-1.0) sent to each scalar actuator, 0.0 for actuators nothing was sent to.
+  /// Empty for devices without scalar actuators.
+  pub fn scalar_values(&self) -> Vec<ScalarSubcommand> {
+    self.generic_command_manager.scalar_values()
+  }
+
+  /// Highest safe value for the scalar actuator at the given index, or None if the device has no
+  /// such actuator. This is the protocol's limit rounded down to the nearest step in the
+  /// actuator's configured step range.
+  pub fn max_actuator_value(&self, actuator_index: u32) -> Option<f64> {
+    let attributes = self.message_attributes();
+    let step_count = attributes
+      .scalar_cmd()
+      .as_ref()?
+      .get(actuator_index as usize)?
+      .step_count();
+    if step_count == 0 {
+      // Every value maps to the start of the range, so nothing above 0 does anything.
+      return Some(0.0);
+    }
+    let limit = self
+      .handler
+      .max_actuator_value(actuator_index)
+      .clamp(0.0, 1.0);
+    Some((limit * step_count as f64).floor() / step_count as f64)
+  }
+
+  /// Last battery level (0.0-1.0) read from the device, by anyone, and when it was read. None if
+  /// it hasn't been read since the device connected.
+  pub fn battery_level_cache(&self) -> Option<(f64, Instant)> {
+    *self.battery_level_cache.lock().expect("Lock poisoned")
+  }
+
+  /// Name and version of the protocol implementation running the device, e.g. `lovense/1.0`.
+  pub fn protocol_version(&self) -> String {
+    format!("{}/{}", self.identifier.protocol(), self.handler.version())
+  }
+
+  /// Features of the device, from both the protocol handler and the device configuration.
+  pub fn capability_flags(&self) -> ProtocolCapabilityFlags {
+    let mut flags = self.handler.capability_flags();
+    let attributes = self.message_attributes();
+    let sensors: Vec<&SensorDeviceMessageAttributes> = attributes
+      .sensor_read_cmd()
+      .iter()
+      .chain(attributes.sensor_subscribe_cmd().iter())
+      .flatten()
+      .collect();
+    if !sensors.is_empty() {
+      flags |= ProtocolCapabilityFlags::SUPPORTS_SENSORS;
+    }
+    if sensors
+      .iter()
+      .any(|sensor| *sensor.sensor_type() == SensorType::Battery)
+    {
+      flags |= ProtocolCapabilityFlags::SUPPORTS_BATTERY;
+    }
+    if sensors
+      .iter()
+      .any(|sensor| *sensor.sensor_type() == SensorType::RSSI)
+    {
+      flags |= ProtocolCapabilityFlags::SUPPORTS_RSSI;
+    }
+    flags
+  }
+
+  /// Run the protocol's calibration sequence, failing if the protocol doesn't have one.
+  pub async fn calibrate(&self) -> Result<CalibrationResult, ButtplugError> {
+    if !self
+      .handler
+      .capability_flags()
+      .contains(ProtocolCapabilityFlags::CALIBRATION)
+    {
+      return Err(
+        ButtplugDeviceError::UnhandledCommand(format!(
+          "Protocol {} does not support calibration",
+          self.identifier.protocol()
+        ))
+        .into(),
+      );
+    }
+    self
+      .handler
+      .handle_calibrate(self.hardware.clone())
+      .await
+      .map_err(|err| err.into())
+  }
+
+  /// Stop all actuators on the device, using the protocol's safe stop command if it has one.
+  pub fn stop(&self) -> ButtplugServerResultFuture {
+    if !self
+      .handler
+      .capability_flags()
+      .contains(ProtocolCapabilityFlags::SAFE_STOP)
+    {
+      return self.parse_message(message::StopDeviceCmd::new(1).into());
+    }
+    self.drop_held_values();
+    // Run the regular stop commands through the command manager without sending them, so it knows
+    // the device is stopped and doesn't skip the next command as redundant.
+    for command in self.generic_command_manager.stop_commands() {
+      let _ = match command {
+        ButtplugDeviceCommandMessageUnion::ScalarCmd(msg) => self
+          .generic_command_manager
+          .update_scalar(&msg, self.handler.needs_full_command_set())
+          .map(|_| ()),
+        ButtplugDeviceCommandMessageUnion::RotateCmd(msg) => self
+          .generic_command_manager
+          .update_rotation(&msg)
+          .map(|_| ()),
+        _ => Ok(()),
+      };
+    }
+    self.handle_generic_command_result(self.handler.handle_safe_stop(), CommandPriority::Critical)
+  }
+
   /// Retreive the event stream for the device.
   ///
   /// This will include connections, disconnections, and notification events from subscribed
   /// endpoints.
   pub fn event_stream(&self) -> impl futures::Stream<Item = ServerDeviceEvent> + Send {
+    self
+      .hardware_event_stream()
+      .merge(self.handler_event_stream())
+  }
+
+  /// Connection, disconnection and raw notification events from the hardware only, which don't
+  /// change when the device is requeried.
+  pub(super) fn hardware_event_stream(
+    &self,
+  ) -> impl futures::Stream<Item = ServerDeviceEvent> + Send {
     let identifier = self.identifier.clone();
     let raw_endpoints = self.raw_subscribed_endpoints.clone();
-    let hardware_stream = convert_broadcast_receiver_to_stream(self.hardware.event_stream())
-      .filter_map(move |hardware_event| {
+    // Weak, since the stream lives as long as the hardware's event sender does.
+    let hardware = Arc::downgrade(&self.hardware);
+    convert_broadcast_receiver_to_stream(self.hardware.event_stream()).filter_map(
+      move |hardware_event| {
         let id = identifier.clone();
         match hardware_event {
           HardwareEvent::Disconnected(_) => Some(ServerDeviceEvent::Disconnected(id)),
           HardwareEvent::Notification(_address, endpoint, data) => {
+            if let Some(hardware) = hardware.upgrade() {
+              hardware.mark_seen();
+            }
             // TODO Figure out how we're going to parse raw data into something sendable to the client.
             if raw_endpoints.contains(&endpoint) {
               Some(ServerDeviceEvent::Notification(
@@ -282,14 +760,20 @@ impl ServerDevice {
             }
           }
         }
-      });
+      },
+    )
+  }
 
+  /// Events generated by the protocol handler only. When a device is requeried, the hardware event
+  /// stream is already being listened to, but the new protocol handler's events are not.
+  pub(super) fn handler_event_stream(
+    &self,
+  ) -> impl futures::Stream<Item = ServerDeviceEvent> + Send {
     let identifier = self.identifier.clone();
-    let handler_mapped_stream = self.handler.event_stream().map(move |incoming_message| {
+    self.handler.event_stream().map(move |incoming_message| {
       let id = identifier.clone();
       ServerDeviceEvent::Notification(id, incoming_message)
-    });
-    hardware_stream.merge(handler_mapped_stream)
+    })
   }
 
   pub fn supports_message(
@@ -330,6 +814,9 @@ impl ServerDevice {
       ButtplugDeviceCommandMessageUnion::RawWriteCmd(_) => {
         check_msg(ButtplugDeviceMessageType::RawWriteCmd)
       }
+      ButtplugDeviceCommandMessageUnion::RawStreamCmd(_) => {
+        check_msg(ButtplugDeviceMessageType::RawStreamCmd)
+      }
       ButtplugDeviceCommandMessageUnion::RotateCmd(_) => {
         check_msg(ButtplugDeviceMessageType::RotateCmd)
       }
@@ -339,6 +826,9 @@ impl ServerDevice {
       ButtplugDeviceCommandMessageUnion::ScalarCmd(_) => {
         check_msg(ButtplugDeviceMessageType::ScalarCmd)
       }
+      ButtplugDeviceCommandMessageUnion::PrioritizedScalarCmd(_) => {
+        check_msg(ButtplugDeviceMessageType::PrioritizedScalarCmd)
+      }
       // We translate SingleMotorVibrateCmd into Vibrate, so this one is special.
       ButtplugDeviceCommandMessageUnion::SingleMotorVibrateCmd(_) => {
         check_msg(ButtplugDeviceMessageType::VibrateCmd)
@@ -376,10 +866,253 @@ impl ServerDevice {
       return future::ready(Err(err)).boxed();
     }
 
+    let (command_message, keys) = self.debounce_command(command_message);
+    match command_message {
+      Some(command_message) => {
+        let in_flight_guard = InFlightGuard::new(self.commands_in_flight.clone());
+        let fut = self.send_debounced(command_message, keys);
+        async move {
+          let _in_flight_guard = in_flight_guard;
+          fut.await
+        }
+        .boxed()
+      }
+      None => {
+        trace!("All actuators in command are within debounce window, holding command back.");
+        future::ready(Ok(message::Ok::default().into())).boxed()
+      }
+    }
+  }
+
+  /// Send a command that made it through debouncing, starting the debounce window of each of the
+  /// actuators in `keys`. If sending fails, the windows are put back the way they were.
+  fn send_debounced(
+    &self,
+    command_message: ButtplugDeviceCommandMessageUnion,
+    keys: Vec<ActuatorKey>,
+  ) -> ButtplugServerResultFuture {
+    let sent_at = Instant::now();
+    let previous: Vec<_> = keys
+      .into_iter()
+      .map(|key| {
+        let mut state = self.actuator_debounce.entry(key).or_default();
+        let last_sent = state.last_sent.replace(sent_at);
+        (key, last_sent)
+      })
+      .collect();
+    let fut = self.handle_command_message(command_message, CommandPriority::Normal);
+    let device = self.weak_self.clone();
+    async move {
+      let result = fut.await;
+      if result.is_err() {
+        if let Some(device) = device.upgrade() {
+          for (key, last_sent) in previous {
+            if let Some(mut state) = device.actuator_debounce.get_mut(&key) {
+              if state.last_sent == Some(sent_at) {
+                state.last_sent = last_sent;
+              }
+            }
+          }
+        }
+      }
+      result
+    }
+    .boxed()
+  }
+
+  /// Drop any values held back by debouncing, so they aren't sent after the device is stopped.
+  fn drop_held_values(&self) {
+    for mut state in self.actuator_debounce.iter_mut() {
+      state.generation += 1;
+    }
+  }
+
+  /// Split actuator subcommands into those that can be sent now, and those still inside their
+  /// actuator's debounce window. Stop values are always sent now. A held back subcommand replaces
+  /// any value already held for its actuator, and is sent once the window closes.
+  fn debounce_subcommands<T>(
+    &self,
+    message_type: ButtplugDeviceMessageType,
+    subcommands: &[T],
+    index: impl Fn(&T) -> u32,
+    is_stop: impl Fn(&T) -> bool,
+  ) -> (Vec<T>, Vec<ActuatorKey>, Vec<HeldSubcommand<T>>)
+  where
+    T: Clone,
+  {
+    let debounce = self
+      .command_debounce
+      .expect("Only called with debouncing on");
+    let mut send_now = vec![];
+    let mut keys = vec![];
+    let mut held = vec![];
+    for subcommand in subcommands {
+      let key = (message_type, index(subcommand));
+      let mut state = self.actuator_debounce.entry(key).or_default();
+      // Whatever happens to this value, it's newer than anything held back before it.
+      state.generation += 1;
+      match state.last_sent {
+        Some(last_sent) if !is_stop(subcommand) && last_sent.elapsed() < debounce => {
+          held.push(HeldSubcommand {
+            key,
+            generation: state.generation,
+            flush_at: last_sent + debounce,
+            subcommand: subcommand.clone(),
+          });
+        }
+        _ => {
+          send_now.push(subcommand.clone());
+          keys.push(key);
+        }
+      }
+    }
+    (send_now, keys, held)
+  }
+
+  /// Send `message` once `held`'s debounce window closes, unless a newer value for the same
+  /// actuator was sent or held back by then.
+  fn schedule_held_value<T>(
+    &self,
+    held: HeldSubcommand<T>,
+    message: ButtplugDeviceCommandMessageUnion,
+  ) where
+    T: Send + 'static,
+  {
+    let device = self.weak_self.clone();
+    async_manager::spawn(async move {
+      tokio::time::sleep(held.flush_at.saturating_duration_since(Instant::now())).await;
+      let Some(device) = device.upgrade() else {
+        return;
+      };
+      let current = device
+        .actuator_debounce
+        .get(&held.key)
+        .is_some_and(|state| state.generation == held.generation);
+      if !current {
+        return;
+      }
+      if let Err(err) = device.send_debounced(message, vec![held.key]).await {
+        error!(
+          "Error sending debounced command to {}: {:?}",
+          device.name(),
+          err
+        );
+      }
+    });
+  }
+
+  /// Hold back any actuator subcommands that are still inside of their debounce window, to be sent
+  /// when it closes. Returns what's left to send now, if anything, along with the actuators it
+  /// covers.
+  fn debounce_command(
+    &self,
+    command_message: ButtplugDeviceCommandMessageUnion,
+  ) -> (Option<ButtplugDeviceCommandMessageUnion>, Vec<ActuatorKey>) {
+    if self.command_debounce.is_none() {
+      return (Some(command_message), vec![]);
+    }
+    let id = command_message.id();
+    let device_index = command_message.device_index();
+    let (mut debounced_message, keys): (ButtplugDeviceCommandMessageUnion, _) =
+      match &command_message {
+        ButtplugDeviceCommandMessageUnion::ScalarCmd(msg) => {
+          let (scalars, keys, held) = self.debounce_subcommands(
+            ButtplugDeviceMessageType::ScalarCmd,
+            msg.scalars(),
+            |cmd| cmd.index(),
+            |cmd| cmd.scalar() == 0.0,
+          );
+          for held in held {
+            let message = ScalarCmd::new(device_index, vec![held.subcommand.clone()]).into();
+            self.schedule_held_value(held, message);
+          }
+          if scalars.is_empty() {
+            return (None, vec![]);
+          }
+          (ScalarCmd::new(device_index, scalars).into(), keys)
+        }
+        ButtplugDeviceCommandMessageUnion::PrioritizedScalarCmd(msg) => {
+          let (scalars, keys, held) = self.debounce_subcommands(
+            ButtplugDeviceMessageType::ScalarCmd,
+            msg.scalars(),
+            |cmd| cmd.index(),
+            |cmd| cmd.scalar() == 0.0,
+          );
+          for held in held {
+            let message = PrioritizedScalarCmd::new(
+              device_index,
+              msg.priority(),
+              vec![held.subcommand.clone()],
+            )
+            .into();
+            self.schedule_held_value(held, message);
+          }
+          if scalars.is_empty() {
+            return (None, vec![]);
+          }
+          (
+            PrioritizedScalarCmd::new(device_index, msg.priority(), scalars).into(),
+            keys,
+          )
+        }
+        ButtplugDeviceCommandMessageUnion::RotateCmd(msg) => {
+          let (rotations, keys, held) = self.debounce_subcommands(
+            ButtplugDeviceMessageType::RotateCmd,
+            msg.rotations(),
+            |cmd| cmd.index(),
+            |cmd| cmd.speed() == 0.0,
+          );
+          for held in held {
+            let message = RotateCmd::new(device_index, vec![held.subcommand.clone()]).into();
+            self.schedule_held_value(held, message);
+          }
+          if rotations.is_empty() {
+            return (None, vec![]);
+          }
+          (RotateCmd::new(device_index, rotations).into(), keys)
+        }
+        ButtplugDeviceCommandMessageUnion::LinearCmd(msg) => {
+          // Positions have no stop value, so every position is debounced.
+          let (vectors, keys, held) = self.debounce_subcommands(
+            ButtplugDeviceMessageType::LinearCmd,
+            msg.vectors(),
+            |cmd| cmd.index(),
+            |_| false,
+          );
+          for held in held {
+            let message = LinearCmd::new(device_index, vec![held.subcommand.clone()]).into();
+            self.schedule_held_value(held, message);
+          }
+          if vectors.is_empty() {
+            return (None, vec![]);
+          }
+          (LinearCmd::new(device_index, vectors).into(), keys)
+        }
+        // Everything else (including stop commands, and older messages that are converted to the
+        // generic commands before being sent) goes through as is.
+        _ => return (Some(command_message), vec![]),
+      };
+    debounced_message.set_id(id);
+    (Some(debounced_message), keys)
+  }
+
+  // Handles a message that has already been checked for support and debounced. Stop commands come
+  // straight here, as they should never be dropped. Hardware commands generated for the message are
+  // sent with the given priority, unless the message carries its own.
+  fn handle_command_message(
+    &self,
+    command_message: ButtplugDeviceCommandMessageUnion,
+    priority: CommandPriority,
+  ) -> ButtplugServerResultFuture {
     // If a handler implements handle message, bypass all of our parsing and let it do its own
     // thing. This should be a very rare thing.
     if self.handler.has_handle_message() {
-      let fut = self.handle_generic_command_result(self.handler.handle_message(&command_message));
+      let priority = match &command_message {
+        ButtplugDeviceCommandMessageUnion::PrioritizedScalarCmd(msg) => client_priority(msg),
+        _ => priority,
+      };
+      let fut =
+        self.handle_generic_command_result(self.handler.handle_message(&command_message), priority);
       return async move { fut.await }.boxed();
     }
 
@@ -387,6 +1120,7 @@ impl ServerDevice {
       // messages we can handle in this struct
       ButtplugDeviceCommandMessageUnion::RawReadCmd(msg) => self.handle_raw_read_cmd(msg),
       ButtplugDeviceCommandMessageUnion::RawWriteCmd(msg) => self.handle_raw_write_cmd(msg),
+      ButtplugDeviceCommandMessageUnion::RawStreamCmd(msg) => self.handle_raw_stream_cmd(msg),
       ButtplugDeviceCommandMessageUnion::RawSubscribeCmd(msg) => self.handle_raw_subscribe_cmd(msg),
       ButtplugDeviceCommandMessageUnion::RawUnsubscribeCmd(msg) => {
         self.handle_raw_unsubscribe_cmd(msg)
@@ -400,69 +1134,31 @@ impl ServerDevice {
       // Message that return lists of hardware commands which we'll handle sending to the devices
       // here, in order to reduce boilerplate in the implementations. Generic messages that we can
       // use the generic command manager for, but still need protocol level translation.
-      ButtplugDeviceCommandMessageUnion::ScalarCmd(msg) => {
-        // TODO Add ability to turn off actuator matching
-        let attributes = self.attributes.message_attributes();
-        let attrs = attributes
-          .scalar_cmd()
-          .as_ref()
-          .expect("Already checked existence");
-        for command in msg.scalars() {
-          if command.index() > attrs.len() as u32 {
-            return future::ready(Err(
-              ButtplugDeviceError::DeviceFeatureIndexError(attrs.len() as u32, command.index())
-                .into(),
-            ))
-            .boxed();
-          }
-          if *attrs[command.index() as usize].actuator_type() != command.actuator_type() {
-            return future::ready(Err(
-              ButtplugDeviceError::DeviceActuatorTypeMismatch(
-                self.name(),
-                command.actuator_type(),
-                *attrs[command.index() as usize].actuator_type(),
-              )
-              .into(),
-            ))
-            .boxed();
-          }
-        }
-
-        let commands = match self
-          .generic_command_manager
-          .update_scalar(&msg, self.handler.needs_full_command_set())
-        {
-          Ok(values) => values,
-          Err(err) => return future::ready(Err(err)).boxed(),
-        };
-
-        if commands.is_empty() {
-          trace!(
-            "No commands generated for incoming device packet, skipping and returning success."
-          );
-          return future::ready(Ok(message::Ok::default().into())).boxed();
-        }
-
-        self.handle_generic_command_result(self.handler.handle_scalar_cmd(&commands))
+      ButtplugDeviceCommandMessageUnion::ScalarCmd(msg) => self.handle_scalar_cmd(msg, priority),
+      ButtplugDeviceCommandMessageUnion::PrioritizedScalarCmd(msg) => {
+        let priority = client_priority(&msg);
+        self.handle_scalar_cmd(msg.into(), priority)
       }
       ButtplugDeviceCommandMessageUnion::RotateCmd(msg) => {
         let commands = match self.generic_command_manager.update_rotation(&msg) {
           Ok(values) => values,
           Err(err) => return future::ready(Err(err)).boxed(),
         };
-        self.handle_generic_command_result(self.handler.handle_rotate_cmd(&commands))
+        self.handle_generic_command_result(self.handler.handle_rotate_cmd(&commands), priority)
       }
       ButtplugDeviceCommandMessageUnion::VibrateCmd(msg) => {
         self.parse_message(ScalarCmd::from(msg).into())
       }
       ButtplugDeviceCommandMessageUnion::LinearCmd(msg) => {
-        self.handle_generic_command_result(self.handler.handle_linear_cmd(msg))
-      }
-      ButtplugDeviceCommandMessageUnion::FleshlightLaunchFW12Cmd(msg) => {
-        self.handle_generic_command_result(self.handler.handle_fleshlight_launch_fw12_cmd(msg))
+        self.handle_generic_command_result(self.handler.handle_linear_cmd(msg), priority)
       }
+      ButtplugDeviceCommandMessageUnion::FleshlightLaunchFW12Cmd(msg) => self
+        .handle_generic_command_result(
+          self.handler.handle_fleshlight_launch_fw12_cmd(msg),
+          priority,
+        ),
       ButtplugDeviceCommandMessageUnion::VorzeA10CycloneCmd(msg) => {
-        self.handle_generic_command_result(self.handler.handle_vorze_a10_cyclone_cmd(msg))
+        self.handle_generic_command_result(self.handler.handle_vorze_a10_cyclone_cmd(msg), priority)
       }
       ButtplugDeviceCommandMessageUnion::SensorReadCmd(msg) => self.handle_sensor_read_cmd(msg),
       ButtplugDeviceCommandMessageUnion::SensorSubscribeCmd(msg) => {
@@ -479,8 +1175,68 @@ impl ServerDevice {
     }
   }
 
-  fn handle_hardware_commands(&self, commands: Vec<HardwareCommand>) -> ButtplugServerResultFuture {
+  fn handle_scalar_cmd(
+    &self,
+    msg: ScalarCmd,
+    priority: CommandPriority,
+  ) -> ButtplugServerResultFuture {
+    // TODO Add ability to turn off actuator matching
+    let attributes = self.attributes.message_attributes();
+    let attrs = attributes
+      .scalar_cmd()
+      .as_ref()
+      .expect("Already checked existence");
+    for command in msg.scalars() {
+      if command.index() > attrs.len() as u32 {
+        return future::ready(Err(
+          ButtplugDeviceError::DeviceFeatureIndexError(attrs.len() as u32, command.index()).into(),
+        ))
+        .boxed();
+      }
+      if *attrs[command.index() as usize].actuator_type() != command.actuator_type() {
+        return future::ready(Err(
+          ButtplugDeviceError::DeviceActuatorTypeMismatch(
+            self.name(),
+            command.actuator_type(),
+            *attrs[command.index() as usize].actuator_type(),
+          )
+          .into(),
+        ))
+        .boxed();
+      }
+    }
+
+    let commands = match self
+      .generic_command_manager
+      .update_scalar(&msg, self.handler.needs_full_command_set())
+    {
+      Ok(values) => values,
+      Err(err) => return future::ready(Err(err)).boxed(),
+    };
+
+    if commands.is_empty() {
+      trace!("No commands generated for incoming device packet, skipping and returning success.");
+      return future::ready(Ok(message::Ok::default().into())).boxed();
+    }
+
+    self.handle_generic_command_result(self.handler.handle_scalar_cmd(&commands), priority)
+  }
+
+  fn handle_hardware_commands(
+    &self,
+    commands: Vec<HardwareCommand>,
+    priority: CommandPriority,
+  ) -> ButtplugServerResultFuture {
     let hardware = self.hardware.clone();
+    let command_queue = self.command_queue.clone();
+    let communication_error_count = self.communication_error_count.clone();
+    let hardware_latencies = self.hardware_latencies.clone();
+    // Stops are the only commands sent at critical priority, see CommandPriority.
+    let mut pending_guard = PendingCommandsGuard::new(
+      self.pending_commands.clone(),
+      commands.len(),
+      priority == CommandPriority::Critical,
+    );
     async move {
       // Run commands in order, otherwise we may end up sending out of order. This may take a while,
       // but it's what 99% of protocols expect. If they want something else, they can implement it
@@ -488,8 +1244,29 @@ impl ServerDevice {
       //
       // If anything errors out, just bail on the command series. This most likely means the device
       // disconnected.
+      //
+      // Each command waits its turn in the priority queue separately, so higher priority commands
+      // can get in between the commands of a long, lower priority series. Stops skip the queue
+      // entirely, so a write that hangs can't hold them back.
       for command in commands {
-        hardware.parse_message(&command).await?;
+        let _turn = if priority == CommandPriority::Critical {
+          None
+        } else {
+          Some(command_queue.acquire(priority).await)
+        };
+        if !pending_guard.take_next() {
+          debug!("Device command queue flushed, dropping the rest of the command series.");
+          return Err(ButtplugDeviceError::DeviceCommandFlushed.into());
+        }
+        let written_at = Instant::now();
+        if let Err(err) = hardware.parse_message(&command).await {
+          communication_error_count.fetch_add(1, Ordering::SeqCst);
+          return Err(err.into());
+        }
+        hardware_latencies
+          .lock()
+          .expect("Lock poisoned")
+          .record(written_at.elapsed());
       }
       Ok(message::Ok::default().into())
     }
@@ -499,21 +1276,23 @@ impl ServerDevice {
   fn handle_generic_command_result(
     &self,
     command_result: Result<Vec<HardwareCommand>, ButtplugDeviceError>,
+    priority: CommandPriority,
   ) -> ButtplugServerResultFuture {
     let hardware_commands = match command_result {
       Ok(commands) => commands,
       Err(err) => return future::ready(Err(err.into())).boxed(),
     };
 
-    self.handle_hardware_commands(hardware_commands)
+    self.handle_hardware_commands(hardware_commands, priority)
   }
 
   fn handle_stop_device_cmd(&self) -> ButtplugServerResultFuture {
+    self.drop_held_values();
     let commands = self.generic_command_manager.stop_commands();
     let mut fut_vec = vec![];
-    commands
-      .iter()
-      .for_each(|msg| fut_vec.push(self.parse_message(msg.clone())));
+    commands.iter().for_each(|msg| {
+      fut_vec.push(self.handle_command_message(msg.clone(), CommandPriority::Critical))
+    });
     async move {
       for fut in fut_vec {
         fut.await?;
@@ -663,6 +1442,48 @@ impl ServerDevice {
     .boxed()
   }
 
+  fn handle_raw_stream_cmd(&self, message: message::RawStreamCmd) -> ButtplugServerResultFuture {
+    let id = message.id();
+    let endpoint = message.endpoint();
+    let chunk_index = message.chunk_index();
+    // Chunk 0 always starts a new transfer, dropping any unfinished one on the same endpoint. Any
+    // other chunk takes the buffer out, and only puts it back if the chunk was the expected one, so
+    // an out of order chunk cancels the transfer.
+    let buffer = self
+      .raw_stream_buffers
+      .remove(&endpoint)
+      .map(|(_, buffer)| buffer);
+    let (expected_chunk, mut data) = if chunk_index == 0 {
+      (0, vec![])
+    } else {
+      buffer.unwrap_or((0, vec![]))
+    };
+    if chunk_index != expected_chunk {
+      return ButtplugDeviceError::DeviceRawStreamChunkOutOfOrder(expected_chunk, chunk_index)
+        .into();
+    }
+    if data.len() + message.data().len() > MAX_RAW_STREAM_SIZE {
+      return ButtplugDeviceError::DeviceRawStreamTooLarge(MAX_RAW_STREAM_SIZE).into();
+    }
+    data.extend_from_slice(message.data());
+    if !message.is_last() {
+      self
+        .raw_stream_buffers
+        .insert(endpoint, (chunk_index + 1, data));
+      return future::ready(Ok(message::Ok::new(id).into())).boxed();
+    }
+    let fut = self
+      .hardware
+      .write_value(&HardwareWriteCmd::new(endpoint, data, true));
+    async move {
+      fut
+        .await
+        .map(|_| message::Ok::new(id).into())
+        .map_err(|err| err.into())
+    }
+    .boxed()
+  }
+
   fn handle_raw_read_cmd(&self, message: message::RawReadCmd) -> ButtplugServerResultFuture {
     let id = message.id();
     let fut = self.hardware.read_value(&message.into());
@@ -731,14 +1552,15 @@ impl ServerDevice {
           let sensor_read_msg = SensorReadCmd::new(0, index as u32, SensorType::Battery);
           let sensor_read = self.handle_sensor_read_cmd(sensor_read_msg);
           let sensor_range_end = *sensor.sensor_range()[0].end();
+          let battery_level_cache = self.battery_level_cache.clone();
           return async move {
             let return_msg = sensor_read.await?;
             if let ButtplugServerMessage::SensorReading(reading) = return_msg {
               if reading.sensor_type() == SensorType::Battery {
-                Ok(
-                  BatteryLevelReading::new(0, reading.data()[0] as f64 / sensor_range_end as f64)
-                    .into(),
-                )
+                let battery_level = reading.data()[0] as f64 / sensor_range_end as f64;
+                *battery_level_cache.lock().expect("Lock poisoned") =
+                  Some((battery_level, Instant::now()));
+                Ok(BatteryLevelReading::new(0, battery_level).into())
               } else {
                 Err(ButtplugError::ButtplugDeviceError(
                   ButtplugDeviceError::ProtocolSensorNotSupported(SensorType::Battery),
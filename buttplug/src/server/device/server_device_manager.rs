@@ -8,10 +8,15 @@
 //! Buttplug Device Manager, manages Device Subtype (Platform/Communication bus
 //! specific) Managers
 
-use super::server_device_manager_event_loop::ServerDeviceManagerEventLoop;
+#[cfg(feature = "metrics")]
+use super::LatencyHistogram;
+use super::{
+  command_conflict::{CommandConflictPolicy, CommandConflictResolver},
+  server_device_manager_event_loop::ServerDeviceManagerEventLoop,
+};
 use crate::{
   core::{
-    errors::{ButtplugDeviceError, ButtplugMessageError, ButtplugUnknownError},
+    errors::{ButtplugDeviceError, ButtplugError, ButtplugMessageError, ButtplugUnknownError},
     message::{
       self,
       ButtplugClientMessage,
@@ -20,64 +25,479 @@ use crate::{
       ButtplugDeviceMessage,
       ButtplugMessage,
       ButtplugServerMessage,
+      ClientDeviceMessageAttributes,
+      DeviceAdded,
       DeviceList,
       DeviceMessageInfo,
+      ScalarSubcommand,
     },
   },
   server::{
     device::{
       configuration::{
+        DeviceConfigurationManager,
         DeviceConfigurationManagerBuilder,
         ProtocolAttributesIdentifier,
         ProtocolCommunicationSpecifier,
         ProtocolDeviceAttributes,
+        ProtocolInfo,
+        ServerDeviceMessageAttributes,
       },
       hardware::communication::{
+        AdvertisementRecord,
         HardwareCommunicationManager,
         HardwareCommunicationManagerBuilder,
+        HardwareCommunicationManagerEvent,
       },
-      protocol::ProtocolIdentifierFactory,
+      protocol::{CalibrationResult, ProtocolCapabilityFlags, ProtocolIdentifierFactory},
+      virtual_device::build_virtual_server_device,
       ServerDevice,
       ServerDeviceIdentifier,
+      VirtualDeviceConfig,
     },
     ButtplugServerError,
     ButtplugServerResultFuture,
+    IdleAction,
+    IdleShutdownPolicy,
+    LOCAL_SESSION_ID,
+  },
+  util::{
+    async_manager,
+    stream::{
+      convert_broadcast_receiver_to_stream,
+      convert_broadcast_receiver_to_stream_with_lag_marker,
+    },
   },
-  util::{async_manager, stream::convert_broadcast_receiver_to_stream},
 };
 use dashmap::DashMap;
 use futures::{
   future::{self, FutureExt},
+  stream,
   Stream,
 };
-use getset::Getters;
+use getset::{CopyGetters, Getters};
 use std::{
+  any::{type_name, TypeId},
+  collections::{HashMap, HashSet},
   convert::TryFrom,
   sync::{
     atomic::{AtomicBool, Ordering},
     Arc,
+    Mutex,
+    RwLock,
   },
+  time::{Duration, Instant},
+};
+use tokio::{
+  sync::{broadcast, mpsc, oneshot},
+  time::{sleep, timeout},
 };
-use tokio::sync::{broadcast, mpsc};
 use tokio_util::sync::CancellationToken;
 
+type DeviceCommandCallback = Arc<dyn Fn(u32, &ButtplugClientMessage) + Send + Sync>;
+
+/// Receives whether a device being reconnected by [ServerDeviceManager::reconnect_all_devices]
+/// came back.
+pub(super) type ReconnectOutcomeReceiver = oneshot::Receiver<Result<(), ButtplugError>>;
+
 #[derive(Debug)]
 pub(super) enum DeviceManagerCommand {
   StartScanning,
   StopScanning,
+  QueryDevice(
+    u32,
+    oneshot::Sender<Result<ServerDeviceInfo, ButtplugError>>,
+  ),
+  Reset(oneshot::Sender<()>),
+  AddCommManager(
+    Box<dyn HardwareCommunicationManager>,
+    oneshot::Sender<Result<(), ButtplugServerError>>,
+  ),
+  AddVirtualDevice(Arc<ServerDevice>),
+  /// Replies with the index of each device being reconnected, and a receiver for the outcome.
+  ReconnectAllDevices(oneshot::Sender<Vec<(u32, ReconnectOutcomeReceiver)>>),
+  /// Replies with the name of each comm manager, whether it can scan, and its last scanning
+  /// error.
+  CommManagerStatus(oneshot::Sender<Vec<(&'static str, bool, Option<String>)>>),
+  /// Scan for the given duration, sending advertisements to the sender without connecting.
+  ScanAdvertisements(Duration, mpsc::Sender<AdvertisementRecord>),
 }
 
-#[derive(Debug, Getters)]
+#[derive(Debug, Clone, Getters)]
+#[cfg_attr(
+  feature = "serialize-json",
+  derive(serde::Serialize, serde::Deserialize)
+)]
 #[getset(get = "pub")]
 pub struct ServerDeviceInfo {
   identifier: ServerDeviceIdentifier,
+  name: String,
   display_name: Option<String>,
+  message_attributes: ServerDeviceMessageAttributes,
+  capability_flags: ProtocolCapabilityFlags,
+  /// See [ServerDevice::protocol_version].
+  protocol_version: String,
+  #[getset(skip)]
+  enabled: bool,
+  /// Tags set with [ServerDeviceManager::set_device_tag].
+  tags: HashMap<String, String>,
+  #[getset(skip)]
+  uptime_seconds: u64,
+}
+
+impl ServerDeviceInfo {
+  pub(super) fn new(
+    device: &ServerDevice,
+    device_config_manager: &DeviceConfigurationManager,
+  ) -> Self {
+    Self {
+      identifier: device.identifier().clone(),
+      name: device.name(),
+      display_name: device.display_name(),
+      message_attributes: device.message_attributes(),
+      capability_flags: device.capability_flags(),
+      protocol_version: device.protocol_version(),
+      enabled: device.enabled(),
+      tags: device_config_manager.device_tags(device.identifier().address()),
+      uptime_seconds: device.uptime().as_secs(),
+    }
+  }
+
+  /// False if the device has been disabled with [ServerDeviceManager::disable_device].
+  pub fn enabled(&self) -> bool {
+    self.enabled
+  }
+
+  /// Whole seconds the device had been connected for when this info was taken, see
+  /// [ServerDeviceManager::device_uptime].
+  pub fn uptime_seconds(&self) -> u64 {
+    self.uptime_seconds
+  }
+}
+
+/// Hardware seen during scanning, regardless of whether it was allowed, matched a protocol, or
+/// ended up connected.
+#[derive(Debug, Clone, Getters)]
+#[getset(get = "pub")]
+pub struct DiscoveredDevice {
+  address: String,
+  advertisement_name: String,
+  /// Only reported by some hardware (i.e. bluetooth), None otherwise.
+  rssi: Option<i32>,
+  protocol_guess: Option<String>,
+  discovered_at: Instant,
+}
+
+impl DiscoveredDevice {
+  pub(super) fn new(
+    address: &str,
+    advertisement_name: &str,
+    rssi: Option<i32>,
+    protocol_guess: Option<String>,
+  ) -> Self {
+    Self {
+      address: address.to_owned(),
+      advertisement_name: advertisement_name.to_owned(),
+      rssi,
+      protocol_guess,
+      discovered_at: Instant::now(),
+    }
+  }
+}
+
+/// State of a comm manager, as listed by [ServerDeviceManager::comm_manager_status].
+#[derive(Debug, Clone, Getters, CopyGetters)]
+pub struct CommManagerStatus {
+  #[getset(get = "pub")]
+  name: String,
+  /// True if the comm manager can scan, i.e. the hardware it needs (like a Bluetooth adapter) is
+  /// present.
+  #[getset(get_copy = "pub")]
+  active: bool,
+  /// Error from the comm manager's last attempt to start or stop scanning, if it failed.
+  #[getset(get = "pub")]
+  error: Option<String>,
+  /// Number of connected devices found by the comm manager.
+  #[getset(get_copy = "pub")]
+  device_count: usize,
+}
+
+impl CommManagerStatus {
+  pub(super) fn new(name: &str, active: bool, error: Option<String>, device_count: usize) -> Self {
+    Self {
+      name: name.to_owned(),
+      active,
+      error,
+      device_count,
+    }
+  }
+}
+
+/// Usage statistics for a device, collected when command statistics tracking is on. Reset when
+/// the device disconnects.
+#[derive(Debug, Clone, Default, Getters, CopyGetters)]
+pub struct CommandStatistics {
+  /// Number of successful commands sent to the device.
+  #[getset(get_copy = "pub")]
+  total_commands: u64,
+  /// Number of successful commands, keyed by message type name.
+  #[getset(get = "pub")]
+  commands_by_type: HashMap<String, u64>,
+  /// Average of all actuator values (0.0-1.0) sent in ScalarCmd, VibrateCmd, RotateCmd,
+  /// LinearCmd, and SingleMotorVibrateCmd messages.
+  #[getset(get_copy = "pub")]
+  average_intensity: f64,
+  #[getset(get_copy = "pub")]
+  last_command_at: Option<Instant>,
+  /// Number of actuator values averaged into average_intensity.
+  intensity_samples: u64,
+}
+
+impl CommandStatistics {
+  fn record(&mut self, message: &ButtplugDeviceCommandMessageUnion) {
+    let (command_name, intensities): (&str, Vec<f64>) = match message {
+      ButtplugDeviceCommandMessageUnion::FleshlightLaunchFW12Cmd(_) => {
+        ("FleshlightLaunchFW12Cmd", vec![])
+      }
+      ButtplugDeviceCommandMessageUnion::SingleMotorVibrateCmd(msg) => {
+        ("SingleMotorVibrateCmd", vec![msg.speed()])
+      }
+      ButtplugDeviceCommandMessageUnion::VorzeA10CycloneCmd(_) => ("VorzeA10CycloneCmd", vec![]),
+      ButtplugDeviceCommandMessageUnion::KiirooCmd(_) => ("KiirooCmd", vec![]),
+      ButtplugDeviceCommandMessageUnion::VibrateCmd(msg) => (
+        "VibrateCmd",
+        msg.speeds().iter().map(|cmd| cmd.speed()).collect(),
+      ),
+      ButtplugDeviceCommandMessageUnion::LinearCmd(msg) => (
+        "LinearCmd",
+        msg.vectors().iter().map(|cmd| cmd.position()).collect(),
+      ),
+      ButtplugDeviceCommandMessageUnion::RotateCmd(msg) => (
+        "RotateCmd",
+        msg.rotations().iter().map(|cmd| cmd.speed()).collect(),
+      ),
+      ButtplugDeviceCommandMessageUnion::RawWriteCmd(_) => ("RawWriteCmd", vec![]),
+      ButtplugDeviceCommandMessageUnion::RawReadCmd(_) => ("RawReadCmd", vec![]),
+      ButtplugDeviceCommandMessageUnion::RawStreamCmd(_) => ("RawStreamCmd", vec![]),
+      ButtplugDeviceCommandMessageUnion::StopDeviceCmd(_) => ("StopDeviceCmd", vec![]),
+      ButtplugDeviceCommandMessageUnion::RawSubscribeCmd(_) => ("RawSubscribeCmd", vec![]),
+      ButtplugDeviceCommandMessageUnion::RawUnsubscribeCmd(_) => ("RawUnsubscribeCmd", vec![]),
+      ButtplugDeviceCommandMessageUnion::BatteryLevelCmd(_) => ("BatteryLevelCmd", vec![]),
+      ButtplugDeviceCommandMessageUnion::RSSILevelCmd(_) => ("RSSILevelCmd", vec![]),
+      ButtplugDeviceCommandMessageUnion::ScalarCmd(msg) => (
+        "ScalarCmd",
+        msg.scalars().iter().map(|cmd| cmd.scalar()).collect(),
+      ),
+      ButtplugDeviceCommandMessageUnion::PrioritizedScalarCmd(msg) => (
+        "PrioritizedScalarCmd",
+        msg.scalars().iter().map(|cmd| cmd.scalar()).collect(),
+      ),
+      ButtplugDeviceCommandMessageUnion::SensorReadCmd(_) => ("SensorReadCmd", vec![]),
+      ButtplugDeviceCommandMessageUnion::SensorSubscribeCmd(_) => ("SensorSubscribeCmd", vec![]),
+      ButtplugDeviceCommandMessageUnion::SensorUnsubscribeCmd(_) => {
+        ("SensorUnsubscribeCmd", vec![])
+      }
+    };
+    self.total_commands += 1;
+    *self
+      .commands_by_type
+      .entry(command_name.to_owned())
+      .or_default() += 1;
+    for intensity in intensities {
+      self.intensity_samples += 1;
+      self.average_intensity +=
+        (intensity - self.average_intensity) / self.intensity_samples as f64;
+    }
+    self.last_command_at = Some(Instant::now());
+  }
+
+  /// Fold statistics collected by another session into these.
+  fn merge(&mut self, other: &CommandStatistics) {
+    self.total_commands += other.total_commands;
+    for (command_name, count) in &other.commands_by_type {
+      *self
+        .commands_by_type
+        .entry(command_name.clone())
+        .or_default() += count;
+    }
+    let intensity_samples = self.intensity_samples + other.intensity_samples;
+    if intensity_samples > 0 {
+      self.average_intensity = (self.average_intensity * self.intensity_samples as f64
+        + other.average_intensity * other.intensity_samples as f64)
+        / intensity_samples as f64;
+    }
+    self.intensity_samples = intensity_samples;
+    self.last_command_at = self.last_command_at.max(other.last_command_at);
+  }
 }
 
+/// Usage statistics for each device, then for each session that sent the device commands, so one
+/// session's statistics can be cleared without touching the others'.
+pub(super) type SessionCommandStatistics = DashMap<u32, HashMap<u64, CommandStatistics>>;
+
+/// Number of times a disconnected device is looked for again, if
+/// [ServerDeviceManagerBuilder::device_reconnect_delay] is set but the attempt count isn't.
+pub const DEFAULT_DEVICE_MAX_RECONNECT_ATTEMPTS: u32 = 3;
+
+/// Time between attempts made by [ServerDeviceManager::reconnect_all_devices], if
+/// [ServerDeviceManagerBuilder::device_reconnect_delay] isn't set.
+pub const DEFAULT_DEVICE_RECONNECT_DELAY: Duration = Duration::from_secs(2);
+
 #[derive(Default)]
 pub struct ServerDeviceManagerBuilder {
   configuration_manager_builder: DeviceConfigurationManagerBuilder,
-  comm_managers: Vec<Box<dyn HardwareCommunicationManagerBuilder>>,
+  comm_managers: Vec<(TypeId, Box<dyn HardwareCommunicationManagerBuilder>)>,
+  track_command_statistics: bool,
+  device_reconnect_delay: Option<Duration>,
+  device_max_reconnect_attempts: Option<u32>,
+  device_stale_timeout: Option<Duration>,
+  comm_manager_init_timeout: Option<Duration>,
+  max_devices: Option<u32>,
+  command_conflict_policy: CommandConflictPolicy,
+  device_error_threshold: Option<u64>,
+}
+
+/// Pass events from a single comm manager on to the device manager, noting which comm manager
+/// found each device in `device_sources`.
+async fn forward_comm_manager_events(
+  name: &'static str,
+  mut receiver: mpsc::Receiver<HardwareCommunicationManagerEvent>,
+  sender: mpsc::Sender<HardwareCommunicationManagerEvent>,
+  device_sources: Arc<DashMap<String, &'static str>>,
+) {
+  while let Some(event) = receiver.recv().await {
+    if let HardwareCommunicationManagerEvent::DeviceFound { address, .. } = &event {
+      device_sources.insert(address.clone(), name);
+    }
+    if sender.send(event).await.is_err() {
+      break;
+    }
+  }
+}
+
+/// Build a comm manager with [build_comm_manager], and pass its events on to `sender`.
+fn finish_comm_manager(
+  builder: Box<dyn HardwareCommunicationManagerBuilder>,
+  sender: mpsc::Sender<HardwareCommunicationManagerEvent>,
+  init_timeout: Option<Duration>,
+  device_sources: Arc<DashMap<String, &'static str>>,
+) -> Option<BuiltCommManager> {
+  // Each comm manager gets its own channel, so devices can be traced back to where they were found.
+  let (manager_sender, manager_receiver) = mpsc::channel(256);
+  let finished = build_comm_manager(builder, manager_sender, init_timeout);
+  if let Some((_, comm_manager)) = &finished {
+    async_manager::spawn(forward_comm_manager_events(
+      comm_manager.name(),
+      manager_receiver,
+      sender,
+      device_sources,
+    ));
+  }
+  finished
+}
+
+/// Like [finish_comm_manager], but waits on the comm manager without blocking the executor.
+async fn finish_comm_manager_async(
+  builder: Box<dyn HardwareCommunicationManagerBuilder>,
+  sender: mpsc::Sender<HardwareCommunicationManagerEvent>,
+  init_timeout: Option<Duration>,
+  device_sources: Arc<DashMap<String, &'static str>>,
+) -> Option<BuiltCommManager> {
+  let (manager_sender, manager_receiver) = mpsc::channel(256);
+  let finished = build_comm_manager_async(builder, manager_sender, init_timeout).await;
+  if let Some((_, comm_manager)) = &finished {
+    async_manager::spawn(forward_comm_manager_events(
+      comm_manager.name(),
+      manager_receiver,
+      sender,
+      device_sources,
+    ));
+  }
+  finished
+}
+
+type BuiltCommManager = (
+  Box<dyn HardwareCommunicationManagerBuilder>,
+  Box<dyn HardwareCommunicationManager>,
+);
+
+/// Build a comm manager on its own thread, handing it to `on_finished` once done, so callers can
+/// stop waiting on comm managers that hang while initializing.
+#[cfg(feature = "tokio-runtime")]
+fn spawn_comm_manager_build<F>(
+  mut builder: Box<dyn HardwareCommunicationManagerBuilder>,
+  sender: mpsc::Sender<HardwareCommunicationManagerEvent>,
+  on_finished: F,
+) where
+  F: FnOnce(BuiltCommManager) + Send + 'static,
+{
+  // Comm managers spawn their tasks while being built, so the thread needs the runtime.
+  let runtime = tokio::runtime::Handle::try_current().ok();
+  std::thread::spawn(move || {
+    let _runtime_guard = runtime.as_ref().map(|runtime| runtime.enter());
+    let comm_manager = builder.finish(sender);
+    on_finished((builder, comm_manager));
+  });
+}
+
+/// Build a comm manager, on its own thread if there's an init timeout, giving up if it takes longer
+/// than that. Returns the builder along with the comm manager so it can be reused, or None if
+/// building timed out.
+fn build_comm_manager(
+  mut builder: Box<dyn HardwareCommunicationManagerBuilder>,
+  sender: mpsc::Sender<HardwareCommunicationManagerEvent>,
+  init_timeout: Option<Duration>,
+) -> Option<BuiltCommManager> {
+  #[cfg(feature = "tokio-runtime")]
+  if let Some(init_timeout) = init_timeout {
+    let (result_sender, result_receiver) = std::sync::mpsc::channel();
+    spawn_comm_manager_build(builder, sender, move |finished| {
+      let _ = result_sender.send(finished);
+    });
+    let finished = result_receiver.recv_timeout(init_timeout).ok();
+    if finished.is_none() {
+      warn!(
+        "Device communication manager did not initialize within {:?}, skipping it.",
+        init_timeout
+      );
+    }
+    return finished;
+  }
+  #[cfg(not(feature = "tokio-runtime"))]
+  let _ = init_timeout;
+  let comm_manager = builder.finish(sender);
+  Some((builder, comm_manager))
+}
+
+/// Like [build_comm_manager], for use from async code, waiting on the build thread without
+/// blocking the executor.
+async fn build_comm_manager_async(
+  mut builder: Box<dyn HardwareCommunicationManagerBuilder>,
+  sender: mpsc::Sender<HardwareCommunicationManagerEvent>,
+  init_timeout: Option<Duration>,
+) -> Option<BuiltCommManager> {
+  #[cfg(feature = "tokio-runtime")]
+  if let Some(init_timeout) = init_timeout {
+    let (result_sender, result_receiver) = oneshot::channel();
+    spawn_comm_manager_build(builder, sender, move |finished| {
+      let _ = result_sender.send(finished);
+    });
+    let finished = timeout(init_timeout, result_receiver)
+      .await
+      .ok()
+      .and_then(Result::ok);
+    if finished.is_none() {
+      warn!(
+        "Device communication manager did not initialize within {:?}, skipping it.",
+        init_timeout
+      );
+    }
+    return finished;
+  }
+  #[cfg(not(feature = "tokio-runtime"))]
+  let _ = init_timeout;
+  let comm_manager = builder.finish(sender);
+  Some((builder, comm_manager))
 }
 
 impl ServerDeviceManagerBuilder {
@@ -85,7 +505,9 @@ impl ServerDeviceManagerBuilder {
   where
     T: HardwareCommunicationManagerBuilder + 'static,
   {
-    self.comm_managers.push(Box::new(builder));
+    self
+      .comm_managers
+      .push((TypeId::of::<T>(), Box::new(builder)));
     self
   }
 
@@ -146,17 +568,100 @@ impl ServerDeviceManagerBuilder {
     self
   }
 
+  pub fn device_command_debounce(&mut self, debounce: Duration) -> &mut Self {
+    self
+      .configuration_manager_builder
+      .command_debounce(debounce);
+    self
+  }
+
+  pub fn track_command_statistics(&mut self, track: bool) -> &mut Self {
+    self.track_command_statistics = track;
+    self
+  }
+
+  /// When a device disconnects, wait this long and then scan for it again, instead of leaving it
+  /// disconnected. Waiting gives Bluetooth adapters time to settle, as reconnecting immediately
+  /// can make them unstable.
+  pub fn device_reconnect_delay(&mut self, delay: Duration) -> &mut Self {
+    self.device_reconnect_delay = Some(delay);
+    self
+  }
+
+  /// Number of reconnect attempts made for a disconnected device before giving up, if
+  /// [ServerDeviceManagerBuilder::device_reconnect_delay] is set. Defaults to
+  /// [DEFAULT_DEVICE_MAX_RECONNECT_ATTEMPTS].
+  pub fn device_max_reconnect_attempts(&mut self, attempts: u32) -> &mut Self {
+    self.device_max_reconnect_attempts = Some(attempts);
+    self
+  }
+
+  /// Disconnect devices that haven't been successfully communicated with in this long (see
+  /// [ServerDeviceManager::device_last_seen]), for catching devices that went out of range
+  /// without the hardware reporting a disconnect. Devices are only heard from when they're sent
+  /// commands or send notifications, so this should be longer than the gaps between expected
+  /// traffic.
+  pub fn device_stale_timeout(&mut self, timeout: Duration) -> &mut Self {
+    self.device_stale_timeout = Some(timeout);
+    self
+  }
+
+  /// Connect at most this many devices at once. Devices found while at the limit are ignored, and
+  /// reported on [ServerDeviceManager::device_limit_reached_stream]. Once a device disconnects,
+  /// newly found devices are connected again.
+  pub fn max_devices(&mut self, max_devices: u32) -> &mut Self {
+    self.max_devices = Some(max_devices);
+    self
+  }
+
+  /// Report devices on [ServerDeviceManager::device_unstable_stream] once their
+  /// [ServerDevice::communication_error_count] goes over this many errors.
+  pub fn device_error_threshold(&mut self, threshold: u64) -> &mut Self {
+    self.device_error_threshold = Some(threshold);
+    self
+  }
+
+  /// How to resolve contradictory commands sent to the same actuator by different clients.
+  /// Defaults to [CommandConflictPolicy::LastWins].
+  pub fn command_conflict_policy(&mut self, policy: CommandConflictPolicy) -> &mut Self {
+    self.command_conflict_policy = policy;
+    self
+  }
+
+  /// Skip comm managers that take longer than this to initialize, instead of waiting on them, so
+  /// missing or unresponsive hardware (like a Bluetooth adapter) can't hold up startup. Each comm
+  /// manager is initialized on its own thread while this is set.
+  #[cfg(feature = "tokio-runtime")]
+  pub fn comm_manager_init_timeout(&mut self, timeout: Duration) -> &mut Self {
+    self.comm_manager_init_timeout = Some(timeout);
+    self
+  }
+
   pub fn finish(&mut self) -> Result<ServerDeviceManager, ButtplugServerError> {
     let config_mgr = self
       .configuration_manager_builder
       .finish()
       .map_err(ButtplugServerError::DeviceConfigurationManagerError)?;
+    let protocols = config_mgr.protocol_info();
+    let config_mgr = Arc::new(config_mgr);
 
     let (device_command_sender, device_command_receiver) = mpsc::channel(256);
     let (device_event_sender, device_event_receiver) = mpsc::channel(256);
     let mut comm_managers: Vec<Box<dyn HardwareCommunicationManager>> = Vec::new();
-    for builder in &mut self.comm_managers {
-      let comm_mgr = builder.finish(device_event_sender.clone());
+    let device_sources = Arc::new(DashMap::new());
+    let mut comm_manager_types = HashSet::new();
+    for (builder_type, builder) in std::mem::take(&mut self.comm_managers) {
+      let (builder, comm_mgr) = match finish_comm_manager(
+        builder,
+        device_event_sender.clone(),
+        self.comm_manager_init_timeout,
+        device_sources.clone(),
+      ) {
+        Some(finished) => finished,
+        None => continue,
+      };
+      self.comm_managers.push((builder_type, builder));
+      comm_manager_types.insert(builder_type);
 
       if comm_managers
         .iter()
@@ -195,45 +700,188 @@ impl ServerDeviceManagerBuilder {
     }
 
     let devices = Arc::new(DashMap::new());
+    let discovered_devices = Arc::new(DashMap::new());
+    let scan_started_at = Arc::new(Mutex::new(None));
     let loop_cancellation_token = CancellationToken::new();
 
     let output_sender = broadcast::channel(255).0;
+    let device_update_sender = broadcast::channel(255).0;
+    let device_reconnect_failed_sender = broadcast::channel(255).0;
+    let device_limit_reached_sender = broadcast::channel(255).0;
+    let device_unstable_sender = broadcast::channel(255).0;
+    let reconnect_policy = self.device_reconnect_delay.map(|delay| {
+      (
+        delay,
+        self
+          .device_max_reconnect_attempts
+          .unwrap_or(DEFAULT_DEVICE_MAX_RECONNECT_ATTEMPTS),
+      )
+    });
+    let command_statistics = self
+      .track_command_statistics
+      .then(|| Arc::new(DashMap::new()));
 
     let mut event_loop = ServerDeviceManagerEventLoop::new(
       comm_managers,
-      config_mgr,
+      config_mgr.clone(),
       devices.clone(),
+      discovered_devices.clone(),
+      scan_started_at.clone(),
       loop_cancellation_token.child_token(),
       output_sender.clone(),
+      device_update_sender.clone(),
+      device_reconnect_failed_sender.clone(),
+      reconnect_policy,
+      self.max_devices,
+      device_limit_reached_sender.clone(),
+      command_statistics.clone(),
+      device_sources.clone(),
       device_event_receiver,
       device_command_receiver,
     );
     async_manager::spawn(async move {
       event_loop.run().await;
     });
+    if let Some(stale_timeout) = self.device_stale_timeout {
+      async_manager::spawn(disconnect_stale_devices(
+        devices.clone(),
+        stale_timeout,
+        loop_cancellation_token.child_token(),
+      ));
+    }
     Ok(ServerDeviceManager {
       devices,
+      discovered_devices,
+      scan_started_at,
       device_command_sender,
       loop_cancellation_token,
       running: Arc::new(AtomicBool::new(true)),
       output_sender,
+      device_update_sender,
+      device_reconnect_failed_sender,
+      device_limit_reached_sender,
+      device_error_threshold: self.device_error_threshold,
+      device_unstable_sender,
+      command_statistics,
+      #[cfg(feature = "metrics")]
+      command_latencies: Arc::new(DashMap::new()),
+      protocols,
+      comm_manager_event_sender: device_event_sender,
+      device_sources,
+      comm_manager_init_timeout: self.comm_manager_init_timeout,
+      comm_manager_types: Mutex::new(comm_manager_types),
+      device_config_manager: config_mgr,
+      command_conflict_resolver: Arc::new(CommandConflictResolver::new(
+        self.command_conflict_policy,
+      )),
+      device_command_callbacks: RwLock::new(vec![]),
     })
   }
 }
 
+/// Disconnect devices that haven't been heard from within `stale_timeout`. The disconnections are
+/// then handled by the event loop like any other.
+async fn disconnect_stale_devices(
+  devices: Arc<DashMap<u32, Arc<ServerDevice>>>,
+  stale_timeout: Duration,
+  cancellation_token: CancellationToken,
+) {
+  // Checking at half the timeout means devices are dropped at most 1.5x the timeout after they
+  // were last seen.
+  let check_interval = (stale_timeout / 2).max(Duration::from_millis(1));
+  loop {
+    tokio::select! {
+      _ = cancellation_token.cancelled() => break,
+      _ = sleep(check_interval) => {}
+    }
+    let stale_devices: Vec<(u32, Arc<ServerDevice>)> = devices
+      .iter()
+      .filter(|device| device.value().last_seen().elapsed() >= stale_timeout)
+      .map(|device| (*device.key(), device.value().clone()))
+      .collect();
+    for (index, device) in stale_devices {
+      warn!(
+        "Device {} ({}) not seen in {:?}, disconnecting.",
+        index,
+        device.name(),
+        stale_timeout
+      );
+      if let Err(err) = device.disconnect().await {
+        error!("Error disconnecting stale device {}: {:?}", index, err);
+      }
+    }
+  }
+}
+
 pub struct ServerDeviceManager {
   devices: Arc<DashMap<u32, Arc<ServerDevice>>>,
+  discovered_devices: Arc<DashMap<String, DiscoveredDevice>>,
+  /// When the client scan currently running started, if there is one.
+  scan_started_at: Arc<Mutex<Option<Instant>>>,
   device_command_sender: mpsc::Sender<DeviceManagerCommand>,
   loop_cancellation_token: CancellationToken,
   running: Arc<AtomicBool>,
   output_sender: broadcast::Sender<ButtplugServerMessage>,
+  device_update_sender: broadcast::Sender<(u32, ServerDeviceInfo)>,
+  device_reconnect_failed_sender: broadcast::Sender<u32>,
+  device_limit_reached_sender: broadcast::Sender<()>,
+  device_error_threshold: Option<u64>,
+  device_unstable_sender: broadcast::Sender<(u32, u64)>,
+  /// Per device and session usage statistics, if tracking is on.
+  command_statistics: Option<Arc<SessionCommandStatistics>>,
+  /// Per device command latencies.
+  #[cfg(feature = "metrics")]
+  command_latencies: Arc<DashMap<u32, LatencyHistogram>>,
+  /// Protocols available to the device configuration, which can't change after building.
+  protocols: Vec<ProtocolInfo>,
+  /// Sender handed to comm managers added after building, and how long they may take to build.
+  comm_manager_event_sender: mpsc::Sender<HardwareCommunicationManagerEvent>,
+  comm_manager_init_timeout: Option<Duration>,
+  /// Builder types of the comm managers added so far, so duplicates can be turned away before
+  /// they're built.
+  comm_manager_types: Mutex<HashSet<TypeId>>,
+  /// Name of the comm manager that found each device, keyed by address.
+  device_sources: Arc<DashMap<String, &'static str>>,
+  /// Shared with the event loop, so virtual devices can be given their index up front.
+  device_config_manager: Arc<DeviceConfigurationManager>,
+  command_conflict_resolver: Arc<CommandConflictResolver>,
+  /// Callbacks registered via [ServerDeviceManager::on_device_command].
+  device_command_callbacks: RwLock<Vec<DeviceCommandCallback>>,
 }
 
 impl ServerDeviceManager {
   pub fn event_stream(&self) -> impl Stream<Item = ButtplugServerMessage> {
     // Unlike the client API, we can expect anyone using the server to pin this
     // themselves.
-    convert_broadcast_receiver_to_stream(self.output_sender.subscribe())
+    convert_broadcast_receiver_to_stream_with_lag_marker(self.output_sender.subscribe(), |count| {
+      Some(message::LaggedEvents::new(count).into())
+    })
+  }
+
+  /// Stream of device indexes and updated info for devices whose capabilities changed after being
+  /// requeried with [ServerDeviceManager::query_device], or that were disabled or enabled.
+  pub fn device_update_stream(&self) -> impl Stream<Item = (u32, ServerDeviceInfo)> {
+    convert_broadcast_receiver_to_stream(self.device_update_sender.subscribe())
+  }
+
+  /// Stream of indexes of devices that disconnected and couldn't be reconnected within the
+  /// attempts set by [ServerDeviceManagerBuilder::device_max_reconnect_attempts].
+  pub fn device_reconnect_failed_stream(&self) -> impl Stream<Item = u32> {
+    convert_broadcast_receiver_to_stream(self.device_reconnect_failed_sender.subscribe())
+  }
+
+  /// Stream with an item each time a device is found but ignored because
+  /// [ServerDeviceManagerBuilder::max_devices] are already connected. Only the first ignored
+  /// device is reported until a device disconnects.
+  pub fn device_limit_reached_stream(&self) -> impl Stream<Item = ()> {
+    convert_broadcast_receiver_to_stream(self.device_limit_reached_sender.subscribe())
+  }
+
+  /// Stream of device indexes and error counts for devices whose communication errors went over
+  /// [ServerDeviceManagerBuilder::device_error_threshold]. Each device is only reported once per
+  /// connection.
+  pub fn device_unstable_stream(&self) -> impl Stream<Item = (u32, u64)> {
+    convert_broadcast_receiver_to_stream(self.device_unstable_sender.subscribe())
   }
 
   fn start_scanning(&self) -> ButtplugServerResultFuture {
@@ -268,13 +916,15 @@ impl ServerDeviceManager {
 
   pub(crate) fn stop_all_devices(&self) -> ButtplugServerResultFuture {
     let device_map = self.devices.clone();
+    self.command_conflict_resolver.clear();
     // TODO This could use some error reporting.
     async move {
       let fut_vec: Vec<_> = device_map
         .iter()
         .map(|dev| {
           let device = dev.value();
-          device.parse_message(message::StopDeviceCmd::new(1).into())
+          device.set_last_command_at(Some(Instant::now()));
+          device.stop()
         })
         .collect();
       future::join_all(fut_vec).await;
@@ -283,15 +933,129 @@ impl ServerDeviceManager {
     .boxed()
   }
 
+  /// Emergency stop for every device. Unlike [ServerDeviceManager::parse_message] with a
+  /// StopAllDevices message, this first drops commands queued for each device, and the stops skip
+  /// the device command queue, so they don't wait behind anything clients have queued. Each device
+  /// gets up to `stop_timeout` to stop, so one unresponsive device doesn't hold up the result.
+  ///
+  /// Every device is stopped even if some fail. If any devices time out, a
+  /// [ButtplugDeviceError::DevicesStopTimedOut] listing their indexes is returned, otherwise the
+  /// first error is.
+  pub async fn force_stop_all_devices(&self, stop_timeout: Duration) -> Result<(), ButtplugError> {
+    self.command_conflict_resolver.clear();
+    let stop_futures: Vec<_> = self
+      .devices
+      .iter()
+      .map(|device| {
+        let index = *device.key();
+        let device = device.value();
+        device.flush_command_queue();
+        timeout(stop_timeout, device.stop()).map(move |result| (index, result))
+      })
+      .collect();
+    let mut timed_out = vec![];
+    let mut first_error = None;
+    for (index, result) in future::join_all(stop_futures).await {
+      match result {
+        Err(_) => timed_out.push(index),
+        Ok(Err(err)) => {
+          first_error.get_or_insert(err);
+        }
+        Ok(Ok(_)) => {}
+      }
+    }
+    if !timed_out.is_empty() {
+      timed_out.sort_unstable();
+      warn!(
+        "Devices {:?} did not stop within {:?}",
+        timed_out, stop_timeout
+      );
+      return Err(ButtplugDeviceError::DevicesStopTimedOut(timed_out).into());
+    }
+    first_error.map_or(Ok(()), Err)
+  }
+
   fn parse_device_message(
     &self,
+    session_id: u64,
+    msg: &ButtplugClientMessage,
     device_msg: ButtplugDeviceCommandMessageUnion,
   ) -> ButtplugServerResultFuture {
     match self.devices.get(&device_msg.device_index()) {
+      // Stops still go through, so clients can always make a device safe.
+      Some(device)
+        if !device.enabled()
+          && !matches!(
+            device_msg,
+            ButtplugDeviceCommandMessageUnion::StopDeviceCmd(_)
+          ) =>
+      {
+        ButtplugDeviceError::DeviceDisabled(device_msg.device_index()).into()
+      }
       Some(device) => {
-        let fut = device.parse_message(device_msg);
+        device.set_last_command_at(Some(Instant::now()));
+        if device_msg.is_actuator_command() {
+          device.set_last_actuator_command_at(Some(Instant::now()));
+        }
+        // Copied out of the lock, so callbacks can add more callbacks.
+        let device_command_callbacks = self
+          .device_command_callbacks
+          .read()
+          .expect("Lock poisoned")
+          .clone();
+        let client_msg = (!device_command_callbacks.is_empty()).then(|| msg.clone());
+        let device_msg = self
+          .command_conflict_resolver
+          .resolve(session_id, device_msg);
+        let command_statistics = self.command_statistics.clone();
+        let device_error_threshold = self.device_error_threshold;
+        let device_unstable_sender = self.device_unstable_sender.clone();
+        let device = device.clone();
+        #[cfg(feature = "metrics")]
+        let command_latencies = self.command_latencies.clone();
+        #[cfg(feature = "metrics")]
+        let received_at = Instant::now();
+        let fut = device.parse_message(device_msg.clone());
         // Create a future to run the message through the device, then handle adding the id to the result.
-        async move { fut.await }.boxed()
+        async move {
+          let result = fut.await;
+          // Only the device knows whether it accepts everything in the command (subcommand
+          // indexes, etc...), so wait until it has.
+          if let (Some(client_msg), Ok(_)) = (&client_msg, &result) {
+            for callback in &device_command_callbacks {
+              callback(device_msg.device_index(), client_msg);
+            }
+          }
+          #[cfg(feature = "metrics")]
+          if result.is_ok() {
+            command_latencies
+              .entry(device_msg.device_index())
+              .or_default()
+              .record(received_at.elapsed());
+          }
+          if let (Some(command_statistics), Ok(_)) = (command_statistics, &result) {
+            command_statistics
+              .entry(device_msg.device_index())
+              .or_default()
+              .entry(session_id)
+              .or_default()
+              .record(&device_msg);
+          }
+          if let (Some(threshold), Err(_)) = (device_error_threshold, &result) {
+            let error_count = device.communication_error_count();
+            if error_count > threshold && device.mark_unstable() {
+              warn!(
+                "Device {} had {} communication errors, marking unstable.",
+                device_msg.device_index(),
+                error_count
+              );
+              // No one listening is fine, the count is still available from the device.
+              let _ = device_unstable_sender.send((device_msg.device_index(), error_count));
+            }
+          }
+          result
+        }
+        .boxed()
       }
       None => ButtplugDeviceError::DeviceNotAvailable(device_msg.device_index()).into(),
     }
@@ -328,13 +1092,24 @@ impl ServerDeviceManager {
   }
 
   pub fn parse_message(&self, msg: ButtplugClientMessage) -> ButtplugServerResultFuture {
+    self.parse_session_message(LOCAL_SESSION_ID, msg)
+  }
+
+  /// Like [ServerDeviceManager::parse_message], for a message from the remote client session with
+  /// the given id. Each session's commands are tracked separately when resolving conflicting
+  /// commands, see [CommandConflictPolicy].
+  pub(crate) fn parse_session_message(
+    &self,
+    session_id: u64,
+    msg: ButtplugClientMessage,
+  ) -> ButtplugServerResultFuture {
     if !self.running.load(Ordering::SeqCst) {
       return future::ready(Err(ButtplugUnknownError::DeviceManagerNotRunning.into())).boxed();
     }
     // If this is a device command message, just route it directly to the
     // device.
     match ButtplugDeviceCommandMessageUnion::try_from(msg.clone()) {
-      Ok(device_msg) => self.parse_device_message(device_msg),
+      Ok(device_msg) => self.parse_device_message(session_id, &msg, device_msg),
       Err(_) => match ButtplugDeviceManagerMessageUnion::try_from(msg.clone()) {
         Ok(manager_msg) => self.parse_device_manager_message(manager_msg),
         Err(_) => ButtplugMessageError::UnexpectedMessageType(format!("{:?}", msg)).into(),
@@ -342,13 +1117,681 @@ impl ServerDeviceManager {
     }
   }
 
+  /// Forget everything kept about a remote client session's commands, once it has disconnected.
+  pub(crate) fn end_session(&self, session_id: u64) {
+    self.command_conflict_resolver.clear_session(session_id);
+  }
+
+  /// Call `callback` with the device index and message of every device command from now on, once
+  /// the device has accepted and run it. Commands are seen as the client sent them, whichever
+  /// connector or client API they came through.
+  ///
+  /// Unlike other callbacks, this is run inline in the command path, so it should return quickly.
+  pub fn on_device_command<F>(&self, callback: F)
+  where
+    F: Fn(u32, &ButtplugClientMessage) + Send + Sync + 'static,
+  {
+    self
+      .device_command_callbacks
+      .write()
+      .expect("Lock poisoned")
+      .push(Arc::new(callback));
+  }
+
+  /// Drop commands generated for the device at the given index that haven't been sent yet, see
+  /// [ServerDevice::flush_command_queue]. Returns the number dropped, or 0 for unknown devices.
+  pub fn flush_device_queue(&self, index: u32) -> usize {
+    self
+      .devices
+      .get(&index)
+      .map_or(0, |device| device.flush_command_queue())
+  }
+
+  /// Number of hardware commands generated for all devices that haven't been sent yet, see
+  /// [ServerDevice::pending_command_count].
+  pub fn pending_command_count(&self) -> usize {
+    self
+      .devices
+      .iter()
+      .map(|device| device.pending_command_count())
+      .sum()
+  }
+
+  /// True if the device at the given index is still running a command, see
+  /// [ServerDevice::is_busy]. False for unknown devices.
+  pub fn device_is_busy(&self, index: u32) -> bool {
+    self
+      .devices
+      .get(&index)
+      .is_some_and(|device| device.is_busy())
+  }
+
+  /// Number of communication errors the device at the given index has had since it connected, see
+  /// [ServerDevice::communication_error_count]. 0 for unknown devices.
+  pub fn device_communication_error_count(&self, index: u32) -> u64 {
+    self
+      .devices
+      .get(&index)
+      .map_or(0, |device| device.communication_error_count())
+  }
+
+  /// True if the device at the given index accepts the given message type. False for unknown
+  /// devices and for messages that aren't device commands.
+  pub fn device_supports_message(&self, index: u32, msg: &ButtplugClientMessage) -> bool {
+    match ButtplugDeviceCommandMessageUnion::try_from(msg.clone()) {
+      Ok(device_msg) => self
+        .devices
+        .get(&index)
+        .is_some_and(|device| device.supports_message(&device_msg).is_ok()),
+      Err(_) => false,
+    }
+  }
+
+  /// Usage statistics for the device at the given index, from every session. Empty if command
+  /// statistics tracking is off, or nothing has been sent to the device since it connected.
+  pub fn command_statistics(&self, index: u32) -> CommandStatistics {
+    let mut statistics = CommandStatistics::default();
+    if let Some(sessions) = self
+      .command_statistics
+      .as_ref()
+      .and_then(|command_statistics| command_statistics.get(&index))
+    {
+      for session_statistics in sessions.values() {
+        statistics.merge(session_statistics);
+      }
+    }
+    statistics
+  }
+
+  /// Forget the usage statistics and command latencies collected for the device at the given
+  /// index, so the next [ServerDeviceManager::command_statistics] call for it returns empty
+  /// statistics.
+  pub fn clear_command_history(&self, index: u32) {
+    if let Some(command_statistics) = &self.command_statistics {
+      command_statistics.remove(&index);
+    }
+    #[cfg(feature = "metrics")]
+    self.command_latencies.remove(&index);
+    if let Some(device) = self.devices.get(&index) {
+      device.clear_hardware_latencies();
+    }
+  }
+
+  /// Forget the usage statistics and command latencies collected for every device.
+  pub fn clear_all_command_history(&self) {
+    if let Some(command_statistics) = &self.command_statistics {
+      command_statistics.clear();
+    }
+    #[cfg(feature = "metrics")]
+    self.command_latencies.clear();
+    for device in self.devices.iter() {
+      device.clear_hardware_latencies();
+    }
+  }
+
+  /// Forget the usage statistics collected from one session's commands, leaving other sessions'
+  /// alone. Latencies aren't kept per session, so they're only cleared with everything else.
+  pub(crate) fn clear_session_command_history(&self, session_id: u64) {
+    if let Some(command_statistics) = &self.command_statistics {
+      command_statistics.retain(|_, sessions| {
+        sessions.remove(&session_id);
+        !sessions.is_empty()
+      });
+    }
+  }
+
+  /// Latencies of successful commands sent to the device at the given index, or None if none have
+  /// been sent. Kept across reconnects, since reconnected devices get their old index back.
+  #[cfg(feature = "metrics")]
+  pub fn command_latency_histogram(&self, index: u32) -> Option<LatencyHistogram> {
+    self
+      .command_latencies
+      .get(&index)
+      .map(|histogram| histogram.clone())
+  }
+
+  /// Average hardware write time of the device at the given index, see
+  /// [ServerDevice::average_hardware_latency]. None if there's no such device. Starts over when the
+  /// device reconnects.
+  pub fn device_average_latency(&self, index: u32) -> Option<Duration> {
+    self
+      .devices
+      .get(&index)
+      .and_then(|device| device.average_hardware_latency())
+  }
+
+  /// Protocols that devices can be connected with, sorted by name.
+  pub fn list_protocols(&self) -> Vec<ProtocolInfo> {
+    self.protocols.clone()
+  }
+
+  /// Time the device at the given index was last successfully communicated with, or None if there
+  /// is no device at that index.
+  pub fn device_last_seen(&self, index: u32) -> Option<Instant> {
+    self
+      .devices
+      .get(&index)
+      .map(|device| device.value().last_seen())
+  }
+
+  /// Last value sent to each scalar actuator on the device at the given index, see
+  /// [ServerDevice::scalar_values]. None if there is no device at that index.
+  pub fn scalar_values(&self, index: u32) -> Option<Vec<ScalarSubcommand>> {
+    self
+      .devices
+      .get(&index)
+      .map(|device| device.value().scalar_values())
+  }
+
+  /// How long the device at the given index has been connected, see [ServerDevice::uptime]. Short
+  /// uptimes on devices that keep coming back can point to a weak signal. None if there's no such
+  /// device.
+  pub fn device_uptime(&self, index: u32) -> Option<Duration> {
+    self
+      .devices
+      .get(&index)
+      .map(|device| device.value().uptime())
+  }
+
+  /// When a client last sent the device at the given index a command, see
+  /// [ServerDevice::last_command_at]. None if there's no such device, or no command since it
+  /// connected.
+  pub fn device_last_command_at(&self, index: u32) -> Option<Instant> {
+    self
+      .devices
+      .get(&index)
+      .and_then(|device| device.value().last_command_at())
+  }
+
+  /// When a client last sent the device at the given index an actuator command, see
+  /// [ServerDevice::last_actuator_command_at]. None if there's no such device, or no actuator
+  /// command since it connected or was last shut down for being idle.
+  pub fn device_last_actuator_command_at(&self, index: u32) -> Option<Instant> {
+    self
+      .devices
+      .get(&index)
+      .and_then(|device| device.value().last_actuator_command_at())
+  }
+
+  /// Apply `policy` to every device that's gone idle, all at once. Each device is only handled
+  /// once per idle period, the next actuator command sent to it starts a new one.
+  pub(crate) async fn shut_down_idle_devices(&self, policy: IdleShutdownPolicy) {
+    let idle_devices: Vec<(u32, Arc<ServerDevice>)> = self
+      .devices
+      .iter()
+      .filter(|device| {
+        device
+          .value()
+          .last_actuator_command_at()
+          .is_some_and(|last_command_at| last_command_at.elapsed() >= policy.idle_after())
+      })
+      .map(|device| (*device.key(), device.value().clone()))
+      .collect();
+    let shutdown_futures = idle_devices.into_iter().map(|(index, device)| async move {
+      device.set_last_actuator_command_at(None);
+      info!(
+        "Device {} ({}) idle for {:?}, applying {:?}.",
+        index,
+        device.name(),
+        policy.idle_after(),
+        policy.action()
+      );
+      let shutdown = async {
+        match policy.action() {
+          IdleAction::Stop => device.stop().await.map(|_| ()),
+          IdleAction::Disconnect => device.disconnect().await,
+        }
+      };
+      match timeout(policy.shutdown_timeout(), shutdown).await {
+        Ok(Ok(())) => {}
+        Ok(Err(err)) => error!("Error shutting down idle device {}: {:?}", index, err),
+        Err(_) => warn!(
+          "Idle device {} did not shut down within {:?}",
+          index,
+          policy.shutdown_timeout()
+        ),
+      }
+    });
+    future::join_all(shutdown_futures).await;
+  }
+
+  /// Last battery level read from the device at the given index, and when it was read, see
+  /// [ServerDevice::battery_level_cache]. None if there's no such device, or no reading yet.
+  pub fn device_battery_level_cache(&self, index: u32) -> Option<(f64, Instant)> {
+    self
+      .devices
+      .get(&index)
+      .and_then(|device| device.value().battery_level_cache())
+  }
+
+  /// Protocol name and version for the device at the given index, see
+  /// [ServerDevice::protocol_version]. None if there's no such device.
+  pub fn device_protocol_version(&self, index: u32) -> Option<String> {
+    self
+      .devices
+      .get(&index)
+      .map(|device| device.value().protocol_version())
+  }
+
+  /// Highest safe value for an actuator on the device at the given index, see
+  /// [ServerDevice::max_actuator_value]. None if either doesn't exist.
+  pub fn max_actuator_value(&self, index: u32, actuator_index: u32) -> Option<f64> {
+    self
+      .devices
+      .get(&index)
+      .and_then(|device| device.value().max_actuator_value(actuator_index))
+  }
+
+  /// Stop the device at the given index, then make all further client commands to it fail with
+  /// [ButtplugDeviceError::DeviceDisabled] until [ServerDeviceManager::enable_device] is called.
+  /// [StopDeviceCmd](crate::core::message::StopDeviceCmd) is still allowed. The device stays
+  /// connected, and the change is emitted on [ServerDeviceManager::device_update_stream].
+  pub async fn disable_device(&self, index: u32) -> Result<(), ButtplugError> {
+    let device = self.set_device_enabled(index, false)?;
+    device.stop().await.map(|_| ())
+  }
+
+  /// Allow client commands to a device disabled with [ServerDeviceManager::disable_device] again.
+  pub fn enable_device(&self, index: u32) -> Result<(), ButtplugError> {
+    self.set_device_enabled(index, true).map(|_| ())
+  }
+
+  fn set_device_enabled(
+    &self,
+    index: u32,
+    enabled: bool,
+  ) -> Result<Arc<ServerDevice>, ButtplugError> {
+    let device = self
+      .devices
+      .get(&index)
+      .map(|device| device.value().clone())
+      .ok_or(ButtplugDeviceError::DeviceNotAvailable(index))?;
+    if device.enabled() != enabled {
+      device.set_enabled(enabled);
+      if self
+        .device_update_sender
+        .send((
+          index,
+          ServerDeviceInfo::new(device.as_ref(), &self.device_config_manager),
+        ))
+        .is_err()
+      {
+        debug!("No one listening for device updates, dropping Device Updated event.");
+      }
+    }
+    Ok(device)
+  }
+
+  /// Tags set on the device at the given index with [ServerDeviceManager::set_device_tag], or
+  /// None if there is no device at that index.
+  pub fn device_tags(&self, index: u32) -> Option<HashMap<String, String>> {
+    self.devices.get(&index).map(|device| {
+      self
+        .device_config_manager
+        .device_tags(device.value().identifier().address())
+    })
+  }
+
+  /// Attach a key-value tag (owner, location, etc.) to the device at the given index. Tags are
+  /// stored by device address, so they're kept if the device disconnects and comes back. The
+  /// change is emitted on [ServerDeviceManager::device_update_stream].
+  pub fn set_device_tag(
+    &self,
+    index: u32,
+    key: String,
+    value: String,
+  ) -> Result<(), ButtplugError> {
+    let device = self
+      .devices
+      .get(&index)
+      .map(|device| device.value().clone())
+      .ok_or(ButtplugDeviceError::DeviceNotAvailable(index))?;
+    self
+      .device_config_manager
+      .set_device_tag(device.identifier().address(), key, value);
+    if self
+      .device_update_sender
+      .send((
+        index,
+        ServerDeviceInfo::new(device.as_ref(), &self.device_config_manager),
+      ))
+      .is_err()
+    {
+      debug!("No one listening for device updates, dropping Device Updated event.");
+    }
+    Ok(())
+  }
+
+  /// Index of the connected device with the given hardware address, if there is one.
+  pub fn device_index_for_address(&self, address: &str) -> Option<u32> {
+    self
+      .devices
+      .iter()
+      .find(|device| device.value().identifier().address() == address)
+      .map(|device| *device.key())
+  }
+
+  /// Give the device with the given address `index` whenever it connects from now on, until
+  /// cleared with [ServerDeviceManager::clear_device_index_override]. A device that's already
+  /// connected keeps its current index until it reconnects. Fails with
+  /// [ButtplugDeviceError::DeviceIndexConflict] if a different device is connected at that index,
+  /// or the index is overridden or reserved for one.
+  pub fn override_device_index(&self, address: &str, index: u32) -> Result<(), ButtplugError> {
+    if let Some(device) = self.devices.get(&index) {
+      let other_address = device.value().identifier().address();
+      if other_address != address {
+        return Err(ButtplugDeviceError::DeviceIndexConflict(index, other_address.clone()).into());
+      }
+    }
+    self
+      .device_config_manager
+      .set_index_override(address, index)
+      .map_err(|err| err.into())
+  }
+
+  /// Remove the index override for the device with the given address, see
+  /// [ServerDeviceManager::override_device_index]. Returns false if there wasn't one.
+  pub fn clear_device_index_override(&self, address: &str) -> bool {
+    self.device_config_manager.clear_index_override(address)
+  }
+
+  /// Run the calibration sequence for the device at the given index, see [ServerDevice::calibrate].
+  pub async fn calibrate_device(&self, index: u32) -> Result<CalibrationResult, ButtplugError> {
+    let device = self
+      .devices
+      .get(&index)
+      .map(|device| device.value().clone())
+      .ok_or(ButtplugDeviceError::DeviceNotAvailable(index))?;
+    device.calibrate().await
+  }
+
+  /// Indexes of all connected devices, in ascending order.
+  pub fn device_indexes(&self) -> Vec<u32> {
+    let mut indexes: Vec<u32> = self.devices.iter().map(|device| *device.key()).collect();
+    indexes.sort_unstable();
+    indexes
+  }
+
   pub fn device_info(&self, index: u32) -> Option<ServerDeviceInfo> {
-    self.devices.get(&index).map(|device| ServerDeviceInfo {
-      identifier: device.value().identifier().clone(),
-      display_name: device.value().display_name(),
+    self
+      .devices
+      .get(&index)
+      .map(|device| ServerDeviceInfo::new(device.value().as_ref(), &self.device_config_manager))
+  }
+
+  /// Message attributes of the device at the given index, as sent to clients in its
+  /// [DeviceAdded] message. None if there is no device at that index.
+  pub fn device_message_attributes(&self, index: u32) -> Option<ClientDeviceMessageAttributes> {
+    self
+      .devices
+      .get(&index)
+      .map(|device| device.value().message_attributes().into())
+  }
+
+  /// [DeviceAdded] messages for all connected devices, in ascending index order, matching what
+  /// was sent to clients when each device connected.
+  pub fn device_added_messages(&self) -> Vec<DeviceAdded> {
+    self
+      .device_indexes()
+      .into_iter()
+      .filter_map(|index| {
+        self.devices.get(&index).map(|device| {
+          DeviceAdded::new(
+            index,
+            &device.name(),
+            &device.display_name(),
+            &None,
+            &device.message_attributes().into(),
+          )
+        })
+      })
+      .collect()
+  }
+
+  /// Rerun the protocol identification and initialization handshake for a connected device,
+  /// replacing its capability information in place (the device keeps its index). Useful when a
+  /// device's capabilities may have changed due to firmware updates, mode changes, etc.
+  pub async fn query_device(&self, index: u32) -> Result<ServerDeviceInfo, ButtplugError> {
+    if !self.running.load(Ordering::SeqCst) {
+      return Err(ButtplugUnknownError::DeviceManagerNotRunning.into());
+    }
+    let (reply_sender, reply_receiver) = oneshot::channel();
+    self
+      .device_command_sender
+      .send(DeviceManagerCommand::QueryDevice(index, reply_sender))
+      .await
+      .map_err(|_| ButtplugUnknownError::DeviceManagerNotRunning)?;
+    reply_receiver
+      .await
+      .map_err(|_| ButtplugUnknownError::DeviceManagerNotRunning)?
+  }
+
+  /// Try to reconnect every device that disconnected and hasn't come back, all at once, e.g. after
+  /// a Bluetooth adapter crashed and was reset. Devices are looked for by address, and keep their
+  /// old index when found. Returns the outcome for each device, once it has either reconnected or
+  /// run out of attempts (see [ServerDeviceManagerBuilder::device_max_reconnect_attempts]).
+  pub async fn reconnect_all_devices(&self) -> Vec<(u32, Result<(), ButtplugError>)> {
+    if !self.running.load(Ordering::SeqCst) {
+      return vec![];
+    }
+    let (reply_sender, reply_receiver) = oneshot::channel();
+    if self
+      .device_command_sender
+      .send(DeviceManagerCommand::ReconnectAllDevices(reply_sender))
+      .await
+      .is_err()
+    {
+      return vec![];
+    }
+    let receivers = reply_receiver.await.unwrap_or_default();
+    future::join_all(receivers.into_iter().map(|(index, receiver)| async move {
+      // The waiter is dropped if the device manager is reset or shut down first.
+      let result = receiver
+        .await
+        .unwrap_or_else(|_| Err(ButtplugDeviceError::DeviceNotAvailable(index).into()));
+      (index, result)
+    }))
+    .await
+  }
+
+  /// Stop scanning, stop and disconnect all devices, and clear scan results, pending reconnects
+  /// and command statistics. Device indexes that have been handed out stay reserved.
+  pub async fn reset(&self) -> Result<(), ButtplugError> {
+    if !self.running.load(Ordering::SeqCst) {
+      return Err(ButtplugUnknownError::DeviceManagerNotRunning.into());
+    }
+    let (reply_sender, reply_receiver) = oneshot::channel();
+    self
+      .device_command_sender
+      .send(DeviceManagerCommand::Reset(reply_sender))
+      .await
+      .map_err(|_| ButtplugUnknownError::DeviceManagerNotRunning)?;
+    reply_receiver
+      .await
+      .map_err(|_| ButtplugUnknownError::DeviceManagerNotRunning.into())
+  }
+
+  /// Build a comm manager and add it to the running device manager, e.g. when a USB dongle is
+  /// plugged in. If a scan is in progress, the new comm manager starts scanning straight away.
+  pub async fn add_comm_manager<T>(&self, builder: T) -> Result<(), ButtplugServerError>
+  where
+    T: HardwareCommunicationManagerBuilder + 'static,
+  {
+    if !self.running.load(Ordering::SeqCst) {
+      return Err(ButtplugServerError::DeviceManagerNotRunning);
+    }
+    // Claim the builder type up front, so a duplicate is never built, even when added
+    // concurrently.
+    let builder_type = TypeId::of::<T>();
+    if !self
+      .comm_manager_types
+      .lock()
+      .expect("Lock poisoned")
+      .insert(builder_type)
+    {
+      return Err(
+        ButtplugServerError::DeviceCommunicationManagerTypeAlreadyAdded(
+          type_name::<T>().to_owned(),
+        ),
+      );
+    }
+    let result = self.add_built_comm_manager(Box::new(builder)).await;
+    if result.is_err() {
+      self
+        .comm_manager_types
+        .lock()
+        .expect("Lock poisoned")
+        .remove(&builder_type);
+    }
+    result
+  }
+
+  async fn add_built_comm_manager(
+    &self,
+    builder: Box<dyn HardwareCommunicationManagerBuilder>,
+  ) -> Result<(), ButtplugServerError> {
+    let (_, comm_manager) = finish_comm_manager_async(
+      builder,
+      self.comm_manager_event_sender.clone(),
+      self.comm_manager_init_timeout,
+      self.device_sources.clone(),
+    )
+    .await
+    .ok_or(ButtplugServerError::DeviceCommunicationManagerInitTimedOut)?;
+    let (reply_sender, reply_receiver) = oneshot::channel();
+    self
+      .device_command_sender
+      .send(DeviceManagerCommand::AddCommManager(
+        comm_manager,
+        reply_sender,
+      ))
+      .await
+      .map_err(|_| ButtplugServerError::DeviceManagerNotRunning)?;
+    reply_receiver
+      .await
+      .map_err(|_| ButtplugServerError::DeviceManagerNotRunning)?
+  }
+
+  /// Name, availability, last scanning error and connected device count of each comm manager, in
+  /// the order they were added. Useful for finding out why no devices are showing up, e.g. because
+  /// there's no Bluetooth adapter.
+  pub async fn comm_manager_status(&self) -> Result<Vec<CommManagerStatus>, ButtplugError> {
+    let (reply_sender, reply_receiver) = oneshot::channel();
+    self
+      .device_command_sender
+      .send(DeviceManagerCommand::CommManagerStatus(reply_sender))
+      .await
+      .map_err(|_| ButtplugUnknownError::DeviceManagerNotRunning)?;
+    let statuses = reply_receiver
+      .await
+      .map_err(|_| ButtplugUnknownError::DeviceManagerNotRunning)?;
+    Ok(
+      statuses
+        .into_iter()
+        .map(|(name, active, error)| {
+          let device_count = self
+            .devices
+            .iter()
+            .filter(|device| {
+              self
+                .device_sources
+                .get(device.value().identifier().address())
+                .is_some_and(|source| *source == name)
+            })
+            .count();
+          CommManagerStatus::new(name, active, error, device_count)
+        })
+        .collect(),
+    )
+  }
+
+  /// Add a device with no hardware behind it, for development, testing or relaying. Commands to it
+  /// go through the same checks and protocol handling as any other device, but do nothing once
+  /// they reach the hardware layer beyond waiting out
+  /// [VirtualDeviceConfig::response_delay]. Returns the index assigned to the device,
+  /// [DeviceAdded] is sent as usual once it has been registered.
+  pub fn add_virtual_device(&self, config: VirtualDeviceConfig) -> Result<u32, ButtplugError> {
+    if !self.running.load(Ordering::SeqCst) {
+      return Err(ButtplugUnknownError::DeviceManagerNotRunning.into());
+    }
+    let device = build_virtual_server_device(&config);
+    // Indexes stick to identifiers, so the event loop will assign this same index on registering.
+    let index = self.device_config_manager.device_index(device.identifier());
+    self
+      .device_command_sender
+      .try_send(DeviceManagerCommand::AddVirtualDevice(device))
+      .map_err(|_| ButtplugUnknownError::DeviceManagerNotRunning)?;
+    Ok(index)
+  }
+
+  /// Devices found during the most recent scan that aren't currently connected, including ones
+  /// that were filtered out by allow/deny lists or didn't match any protocol.
+  pub fn scan_results(&self) -> Vec<DiscoveredDevice> {
+    self
+      .discovered_devices
+      .iter()
+      .filter(|entry| {
+        !self
+          .devices
+          .iter()
+          .any(|device| device.value().identifier().address() == entry.key())
+      })
+      .map(|entry| entry.value().clone())
+      .collect()
+  }
+
+  /// Number of devices [ServerDeviceManager::scan_results] would return, without copying them.
+  /// Cheap enough to poll during a scan, for showing progress.
+  pub fn pending_scan_results(&self) -> usize {
+    self
+      .discovered_devices
+      .iter()
+      .filter(|entry| {
+        !self
+          .devices
+          .iter()
+          .any(|device| device.value().identifier().address() == entry.key())
+      })
+      .count()
+  }
+
+  /// How long the current scan has been running, from StartScanning until ScanningFinished is
+  /// emitted. None if no scan is running. Scans started for reconnecting devices or by
+  /// [ServerDeviceManager::scan_advertisements] don't count.
+  pub fn active_scan_duration(&self) -> Option<Duration> {
+    self
+      .scan_started_at
+      .lock()
+      .expect("Lock poisoned")
+      .map(|started_at| started_at.elapsed())
+  }
+
+  /// Scan for `duration`, reporting every advertisement heard, without connecting to the devices
+  /// they came from. The stream ends once `duration` runs out, or when scanning is stopped with
+  /// StopScanning. Devices are still connected if a client starts its own scan while this is
+  /// running, or if they're being reconnected. Only comm managers for hardware that advertises
+  /// (i.e. bluetooth) report anything.
+  pub fn scan_advertisements(&self, duration: Duration) -> impl Stream<Item = AdvertisementRecord> {
+    let (sender, receiver) = mpsc::channel(256);
+    // If this fails the sender is dropped with the command, so the stream just ends.
+    if self
+      .device_command_sender
+      .try_send(DeviceManagerCommand::ScanAdvertisements(duration, sender))
+      .is_err()
+    {
+      warn!("Device manager not running, cannot scan for advertisements.");
+    }
+    stream::unfold(receiver, |mut receiver| async move {
+      let record = receiver.recv().await?;
+      Some((record, receiver))
     })
   }
 
+  /// False once the owning server has shut the manager down.
+  pub(crate) fn is_running(&self) -> bool {
+    self.running.load(Ordering::SeqCst)
+  }
+
   // Only a ButtplugServer should be able to call this. We don't want to expose this capability to
   // the outside world. Note that this could cause issues for lifetimes if someone holds this longer
   // than the lifetime of the server that originally created it. Ideally we should lock the Server
@@ -6,25 +6,47 @@
 // for full license information.
 
 use crate::{
-  core::message::{ButtplugServerMessage, DeviceAdded, DeviceRemoved, ScanningFinished},
+  core::{
+    errors::{ButtplugDeviceError, ButtplugError},
+    message::{ButtplugServerMessage, DeviceAdded, DeviceRemoved, ScanningFinished},
+  },
   server::device::{
     configuration::DeviceConfigurationManager,
-    hardware::communication::{HardwareCommunicationManager, HardwareCommunicationManagerEvent},
-    server_device::build_server_device,
+    hardware::communication::{
+      AdvertisementRecord,
+      HardwareCommunicationManager,
+      HardwareCommunicationManagerEvent,
+    },
+    server_device::{build_server_device, requery_server_device},
     ServerDevice,
     ServerDeviceEvent,
   },
+  server::ButtplugServerError,
   util::async_manager,
 };
 use dashmap::{DashMap, DashSet};
 use futures::{future, FutureExt, StreamExt};
-use std::sync::Arc;
-use tokio::sync::{broadcast, mpsc};
+use std::{
+  collections::HashMap,
+  sync::{Arc, Mutex},
+  time::{Duration, Instant},
+};
+use tokio::sync::{broadcast, mpsc, oneshot};
 use tokio_util::sync::CancellationToken;
 use tracing;
 use tracing_futures::Instrument;
 
-use super::server_device_manager::DeviceManagerCommand;
+use super::server_device_manager::{
+  DeviceManagerCommand,
+  DiscoveredDevice,
+  ReconnectOutcomeReceiver,
+  ServerDeviceInfo,
+  SessionCommandStatistics,
+  DEFAULT_DEVICE_MAX_RECONNECT_ATTEMPTS,
+  DEFAULT_DEVICE_RECONNECT_DELAY,
+};
+
+type ReconnectWaiter = oneshot::Sender<Result<(), ButtplugError>>;
 
 pub(super) struct ServerDeviceManagerEventLoop {
   comm_managers: Vec<Box<dyn HardwareCommunicationManager>>,
@@ -32,9 +54,56 @@ pub(super) struct ServerDeviceManagerEventLoop {
   device_command_receiver: mpsc::Receiver<DeviceManagerCommand>,
   /// Maps device index (exposed to the outside world) to actual device objects held by the server.
   device_map: Arc<DashMap<u32, Arc<ServerDevice>>>,
+  /// Everything seen since the last scan started, keyed by address.
+  discovered_devices: Arc<DashMap<String, DiscoveredDevice>>,
   /// Broadcaster that relays device events in the form of Buttplug Messages to
   /// whoever owns the Buttplug Server.
   server_sender: broadcast::Sender<ButtplugServerMessage>,
+  /// Broadcaster for device capability updates after a requery.
+  device_update_sender: broadcast::Sender<(u32, ServerDeviceInfo)>,
+  /// Broadcaster for indexes of devices we've given up on reconnecting.
+  device_reconnect_failed_sender: broadcast::Sender<u32>,
+  /// Delay between reconnect attempts and max number of attempts, if reconnecting is on.
+  reconnect_policy: Option<(Duration, u32)>,
+  /// Most devices that can be connected at once, if limited.
+  max_devices: Option<u32>,
+  /// Broadcaster for devices ignored due to the device limit.
+  device_limit_reached_sender: broadcast::Sender<()>,
+  /// True once an ignored device has been reported, until a device disconnects, so repeated
+  /// advertisements don't flood listeners.
+  device_limit_reported: bool,
+  /// Disconnected devices we're trying to reconnect, keyed by address, with their index and the
+  /// number of attempts made so far.
+  reconnecting_devices: HashMap<String, (u32, u32)>,
+  /// True if scanning was started to look for reconnecting devices, rather than for a client.
+  reconnect_scanning: bool,
+  /// Devices that disconnected and haven't come back, keyed by address, with their index.
+  disconnected_devices: HashMap<String, u32>,
+  /// Callers of
+  /// [ServerDeviceManager::reconnect_all_devices](super::ServerDeviceManager::reconnect_all_devices)
+  /// waiting on each reconnecting device, keyed by address.
+  reconnect_waiters: HashMap<String, Vec<ReconnectWaiter>>,
+  /// Receives device addresses once their reconnect delay has passed.
+  reconnect_timer_receiver: mpsc::Receiver<String>,
+  reconnect_timer_sender: mpsc::Sender<String>,
+  /// Streams from
+  /// [ServerDeviceManager::scan_advertisements](super::ServerDeviceManager::scan_advertisements),
+  /// keyed by scan id. Devices found while any are running only connect if a client scan was also
+  /// started, or they're being reconnected.
+  advertisement_scans: HashMap<u64, mpsc::Sender<AdvertisementRecord>>,
+  next_advertisement_scan_id: u64,
+  /// Last value passed to
+  /// [HardwareCommunicationManager::set_reporting_advertisements], true while any advertisement
+  /// scans are running.
+  reporting_advertisements: bool,
+  /// Receives advertisement scan ids once their duration has passed.
+  advertisement_timer_receiver: mpsc::Receiver<u64>,
+  advertisement_timer_sender: mpsc::Sender<u64>,
+  /// Per device usage statistics, if tracking is on. Cleared when devices disconnect.
+  command_statistics: Option<Arc<SessionCommandStatistics>>,
+  /// Comm manager that found each device, by address. Entries go when devices disconnect, and
+  /// for devices never connected, when a new scan starts.
+  device_sources: Arc<DashMap<String, &'static str>>,
   /// As the device manager owns the Device Communication Managers, it will have
   /// a receiver that the comm managers all send thru.
   device_comm_receiver: mpsc::Receiver<HardwareCommunicationManagerEvent>,
@@ -47,36 +116,119 @@ pub(super) struct ServerDeviceManagerEventLoop {
   scanning_bringup_in_progress: bool,
   /// Denote whether scanning has been started since we last sent a ScanningFinished message.
   scanning_started: bool,
+  /// When scanning_started was last set, shared with the device manager.
+  scan_started_at: Arc<Mutex<Option<Instant>>>,
   /// Devices currently trying to connect.
   connecting_devices: Arc<DashSet<String>>,
   /// Cancellation token for the event loop
   loop_cancellation_token: CancellationToken,
+  /// Error from each comm manager's last failed attempt to start or stop scanning, keyed by name.
+  comm_manager_errors: HashMap<&'static str, String>,
+  /// Stops forwarding events from each device's protocol handler, keyed by device index, so a
+  /// requeried device's old handler is no longer listened to.
+  handler_event_forwarders: Arc<DashMap<u32, CancellationToken>>,
+}
+
+/// Forward events from the protocol handler of `device` to `event_sender`, replacing the
+/// forwarder already running for `device_index`, if any.
+fn spawn_handler_event_forwarder(
+  handler_event_forwarders: &DashMap<u32, CancellationToken>,
+  event_sender: mpsc::Sender<ServerDeviceEvent>,
+  device_index: u32,
+  device: &ServerDevice,
+) {
+  let cancellation_token = CancellationToken::new();
+  if let Some(old_token) = handler_event_forwarders.insert(device_index, cancellation_token.clone())
+  {
+    old_token.cancel();
+  }
+  let event_listener = device.handler_event_stream();
+  async_manager::spawn(async move {
+    pin_mut!(event_listener);
+    loop {
+      let event = tokio::select! {
+        event = event_listener.next() => event,
+        _ = cancellation_token.cancelled() => break,
+      };
+      let Some(event) = event else {
+        break;
+      };
+      if event_sender.send(event).await.is_err() {
+        break;
+      }
+    }
+  });
+}
+
+/// Stop forwarding protocol handler events for a device that's gone.
+fn cancel_handler_event_forwarder(
+  handler_event_forwarders: &DashMap<u32, CancellationToken>,
+  device_index: u32,
+) {
+  if let Some((_, token)) = handler_event_forwarders.remove(&device_index) {
+    token.cancel();
+  }
 }
 
 impl ServerDeviceManagerEventLoop {
+  #[allow(clippy::too_many_arguments)]
   pub fn new(
     comm_managers: Vec<Box<dyn HardwareCommunicationManager>>,
-    device_config_manager: DeviceConfigurationManager,
+    device_config_manager: Arc<DeviceConfigurationManager>,
     device_map: Arc<DashMap<u32, Arc<ServerDevice>>>,
+    discovered_devices: Arc<DashMap<String, DiscoveredDevice>>,
+    scan_started_at: Arc<Mutex<Option<Instant>>>,
     loop_cancellation_token: CancellationToken,
     server_sender: broadcast::Sender<ButtplugServerMessage>,
+    device_update_sender: broadcast::Sender<(u32, ServerDeviceInfo)>,
+    device_reconnect_failed_sender: broadcast::Sender<u32>,
+    reconnect_policy: Option<(Duration, u32)>,
+    max_devices: Option<u32>,
+    device_limit_reached_sender: broadcast::Sender<()>,
+    command_statistics: Option<Arc<SessionCommandStatistics>>,
+    device_sources: Arc<DashMap<String, &'static str>>,
     device_comm_receiver: mpsc::Receiver<HardwareCommunicationManagerEvent>,
     device_command_receiver: mpsc::Receiver<DeviceManagerCommand>,
   ) -> Self {
     let (device_event_sender, device_event_receiver) = mpsc::channel(256);
+    let (reconnect_timer_sender, reconnect_timer_receiver) = mpsc::channel(256);
+    let (advertisement_timer_sender, advertisement_timer_receiver) = mpsc::channel(256);
     Self {
       comm_managers,
-      device_config_manager: Arc::new(device_config_manager),
+      device_config_manager,
       server_sender,
+      device_update_sender,
+      device_reconnect_failed_sender,
+      reconnect_policy,
+      max_devices,
+      device_limit_reached_sender,
+      device_limit_reported: false,
+      reconnecting_devices: HashMap::new(),
+      reconnect_scanning: false,
+      disconnected_devices: HashMap::new(),
+      reconnect_waiters: HashMap::new(),
+      reconnect_timer_receiver,
+      reconnect_timer_sender,
+      advertisement_scans: HashMap::new(),
+      next_advertisement_scan_id: 0,
+      reporting_advertisements: false,
+      advertisement_timer_receiver,
+      advertisement_timer_sender,
+      command_statistics,
+      device_sources,
       device_map,
+      discovered_devices,
       device_comm_receiver,
       device_event_sender,
       device_event_receiver,
       device_command_receiver,
       scanning_bringup_in_progress: false,
       scanning_started: false,
+      scan_started_at,
       connecting_devices: Arc::new(DashSet::new()),
       loop_cancellation_token,
+      handler_event_forwarders: Arc::new(DashMap::new()),
+      comm_manager_errors: HashMap::new(),
     }
   }
 
@@ -88,34 +240,363 @@ impl ServerDeviceManagerEventLoop {
     false
   }
 
+  fn set_scanning_started(&mut self, scanning_started: bool) {
+    self.scanning_started = scanning_started;
+    *self.scan_started_at.lock().expect("Lock poisoned") = scanning_started.then(Instant::now);
+  }
+
   async fn handle_start_scanning(&mut self) {
-    if self.scanning_status() || self.scanning_bringup_in_progress {
+    // Managers already scanning for advertisements are asked to scan again, so they report
+    // devices they've already seen to the now connecting scan.
+    if (self.scanning_status() && self.advertisement_scans.is_empty())
+      || self.scanning_bringup_in_progress
+    {
       debug!("System already scanning, ignoring new scanning request");
       return;
     }
 
     info!("No scan currently in progress, starting new scan.");
+    self.discovered_devices.clear();
+    // Sources of devices that were found but never connected are of no use after this.
+    let device_map = &self.device_map;
+    self.device_sources.retain(|address, _| {
+      device_map
+        .iter()
+        .any(|device| device.value().identifier().address() == address)
+    });
     self.scanning_bringup_in_progress = true;
-    self.scanning_started = true;
+    self.set_scanning_started(true);
     let fut_vec: Vec<_> = self
       .comm_managers
       .iter_mut()
-      .map(|guard| guard.start_scanning())
+      .map(|guard| {
+        let name = guard.name();
+        guard.start_scanning().map(move |result| (name, result))
+      })
       .collect();
-    // TODO If start_scanning fails anywhere, this will ignore it. We should maybe at least log?
-    future::join_all(fut_vec).await;
+    let results = future::join_all(fut_vec).await;
+    self.record_comm_manager_results(results);
     debug!("Scanning started for all hardware comm managers.");
     self.scanning_bringup_in_progress = false;
   }
 
   async fn handle_stop_scanning(&mut self) {
+    // Dropping the senders ends the advertisement streams.
+    self.advertisement_scans.clear();
+    self.update_reporting_advertisements();
     let fut_vec: Vec<_> = self
       .comm_managers
       .iter_mut()
-      .map(|guard| guard.stop_scanning())
+      .map(|guard| {
+        let name = guard.name();
+        guard.stop_scanning().map(move |result| (name, result))
+      })
       .collect();
-    // TODO If stop_scanning fails anywhere, this will ignore it. We should maybe at least log?
-    future::join_all(fut_vec).await;
+    let results = future::join_all(fut_vec).await;
+    self.record_comm_manager_results(results);
+  }
+
+  /// Log failed scanning starts and stops, and keep track of which comm managers are currently in
+  /// an error state, for
+  /// [ServerDeviceManager::comm_manager_status](super::ServerDeviceManager::comm_manager_status).
+  fn record_comm_manager_results(
+    &mut self,
+    results: Vec<(&'static str, Result<(), ButtplugError>)>,
+  ) {
+    for (name, result) in results {
+      match result {
+        Ok(()) => {
+          self.comm_manager_errors.remove(name);
+        }
+        Err(err) => {
+          warn!(
+            "Comm manager {} failed to start or stop scanning: {:?}",
+            name, err
+          );
+          self.comm_manager_errors.insert(name, err.to_string());
+        }
+      }
+    }
+  }
+
+  fn handle_comm_manager_status(&self) -> Vec<(&'static str, bool, Option<String>)> {
+    self
+      .comm_managers
+      .iter()
+      .map(|comm_manager| {
+        (
+          comm_manager.name(),
+          comm_manager.can_scan(),
+          self.comm_manager_errors.get(comm_manager.name()).cloned(),
+        )
+      })
+      .collect()
+  }
+
+  async fn handle_add_comm_manager(
+    &mut self,
+    mut comm_manager: Box<dyn HardwareCommunicationManager>,
+  ) -> Result<(), ButtplugServerError> {
+    if self
+      .comm_managers
+      .iter()
+      .any(|mgr| mgr.name() == comm_manager.name())
+    {
+      return Err(
+        ButtplugServerError::DeviceCommunicationManagerTypeAlreadyAdded(
+          comm_manager.name().to_owned(),
+        ),
+      );
+    }
+    info!("Adding comm manager {}", comm_manager.name());
+    comm_manager.set_reporting_advertisements(self.reporting_advertisements);
+    // Commands are handled one at a time, so a scan can't be starting or stopping while we're in
+    // here. If one is running the new manager joins it, and is then included in ScanningFinished
+    // tracking like the rest.
+    if self.scanning_started {
+      if let Err(err) = comm_manager.start_scanning().await {
+        warn!(
+          "Comm manager {} failed to start scanning: {:?}",
+          comm_manager.name(),
+          err
+        );
+        self
+          .comm_manager_errors
+          .insert(comm_manager.name(), err.to_string());
+      }
+    }
+    self.comm_managers.push(comm_manager);
+    Ok(())
+  }
+
+  /// Return to the state the loop started in, minus anything the comm managers hold internally.
+  async fn handle_reset(&mut self) {
+    self.handle_stop_scanning().await;
+    self.scanning_bringup_in_progress = false;
+    self.set_scanning_started(false);
+    self.reconnect_scanning = false;
+    self.reconnecting_devices.clear();
+    self.disconnected_devices.clear();
+    // Dropping the waiters tells them the devices are gone for good.
+    self.reconnect_waiters.clear();
+    self.advertisement_scans.clear();
+    self.update_reporting_advertisements();
+    self.discovered_devices.clear();
+    self.device_sources.clear();
+    if let Some(command_statistics) = &self.command_statistics {
+      command_statistics.clear();
+    }
+    // Take devices out of the map first, so their disconnection events are ignored instead of
+    // starting reconnects.
+    let devices: Vec<(u32, Arc<ServerDevice>)> = self
+      .device_map
+      .iter()
+      .map(|pair| (*pair.key(), pair.value().clone()))
+      .collect();
+    self.device_map.clear();
+    for (device_index, device) in devices {
+      cancel_handler_event_forwarder(&self.handler_event_forwarders, device_index);
+      if let Err(err) = device.stop().await {
+        warn!(
+          "Error stopping device {} during reset: {:?}",
+          device_index, err
+        );
+      }
+      if let Err(err) = device.disconnect().await {
+        warn!(
+          "Error disconnecting device {} during reset: {:?}",
+          device_index, err
+        );
+      }
+      if self
+        .server_sender
+        .send(DeviceRemoved::new(device_index).into())
+        .is_err()
+      {
+        debug!("Server not currently available, dropping Device Removed event.");
+      }
+    }
+  }
+
+  /// Delay between reconnect attempts and max number of attempts. Without a reconnect policy,
+  /// devices are only reconnected when asked to with
+  /// [DeviceManagerCommand::ReconnectAllDevices], which uses the defaults.
+  fn reconnect_settings(&self) -> (Duration, u32) {
+    self.reconnect_policy.unwrap_or((
+      DEFAULT_DEVICE_RECONNECT_DELAY,
+      DEFAULT_DEVICE_MAX_RECONNECT_ATTEMPTS,
+    ))
+  }
+
+  /// Wait out the reconnect delay for a device, then let the event loop know.
+  fn schedule_reconnect_attempt(&self, address: String) {
+    let (delay, _) = self.reconnect_settings();
+    let reconnect_timer_sender = self.reconnect_timer_sender.clone();
+    async_manager::spawn(async move {
+      tokio::time::sleep(delay).await;
+      let _ = reconnect_timer_sender.send(address).await;
+    });
+  }
+
+  async fn handle_reconnect_timer(&mut self, address: String) {
+    let (_, max_attempts) = self.reconnect_settings();
+    let (device_index, attempts) =
+      if let Some(reconnect_state) = self.reconnecting_devices.get_mut(&address) {
+        reconnect_state
+      } else {
+        // Device came back since the timer was started.
+        return;
+      };
+    if *attempts >= max_attempts {
+      info!(
+        "Device {} did not reconnect after {} attempts, giving up.",
+        address, attempts
+      );
+      let device_index = *device_index;
+      let attempts = *attempts;
+      self.reconnecting_devices.remove(&address);
+      for waiter in self.reconnect_waiters.remove(&address).unwrap_or_default() {
+        let _ = waiter.send(Err(
+          ButtplugDeviceError::DeviceConnectionError(format!(
+            "Device did not reconnect after {} attempts",
+            attempts
+          ))
+          .into(),
+        ));
+      }
+      if self
+        .device_reconnect_failed_sender
+        .send(device_index)
+        .is_err()
+      {
+        debug!("No one listening for reconnect failures, dropping Device Reconnect Failed event.");
+      }
+      self.stop_reconnect_scanning().await;
+      return;
+    }
+    *attempts += 1;
+    info!(
+      "Trying to reconnect device {}, attempt {}.",
+      address, attempts
+    );
+    self.schedule_reconnect_attempt(address);
+    // Scanning is how devices get found again, so just make sure it's running. Managers that are
+    // already scanning for a client will pick the device up on their own.
+    if !self.scanning_status() && !self.scanning_bringup_in_progress {
+      self.reconnect_scanning = true;
+      let fut_vec: Vec<_> = self
+        .comm_managers
+        .iter_mut()
+        .map(|guard| guard.start_scanning())
+        .collect();
+      future::join_all(fut_vec).await;
+    }
+  }
+
+  /// Start reconnecting every device that disconnected and hasn't come back, e.g. after a
+  /// Bluetooth adapter reset. Replies with a receiver for the outcome of each device, keyed by
+  /// index. Devices that are already being reconnected carry on with their current attempts.
+  async fn handle_reconnect_all_devices(
+    &mut self,
+    reply_sender: oneshot::Sender<Vec<(u32, ReconnectOutcomeReceiver)>>,
+  ) {
+    let disconnected_devices: Vec<(String, u32)> = self
+      .disconnected_devices
+      .iter()
+      .map(|(address, index)| (address.clone(), *index))
+      .collect();
+    let mut receivers = vec![];
+    for (address, device_index) in disconnected_devices {
+      let (waiter, receiver) = oneshot::channel();
+      self
+        .reconnect_waiters
+        .entry(address.clone())
+        .or_default()
+        .push(waiter);
+      receivers.push((device_index, receiver));
+      if !self.reconnecting_devices.contains_key(&address) {
+        self
+          .reconnecting_devices
+          .insert(address.clone(), (device_index, 0));
+        // Make the first attempt now rather than after the reconnect delay.
+        self.handle_reconnect_timer(address).await;
+      }
+    }
+    let _ = reply_sender.send(receivers);
+  }
+
+  /// Stop any scan we started for reconnecting, once there's nothing left to reconnect and no
+  /// client has started a scan of its own.
+  async fn stop_reconnect_scanning(&mut self) {
+    if self.reconnect_scanning
+      && self.reconnecting_devices.is_empty()
+      && !self.scanning_started
+      && self.advertisement_scans.is_empty()
+    {
+      self.reconnect_scanning = false;
+      self.handle_stop_scanning().await;
+    }
+  }
+
+  /// Start scanning without connecting, if nothing else is scanning already, and end the scan
+  /// after `duration`.
+  async fn handle_scan_advertisements(
+    &mut self,
+    duration: Duration,
+    sender: mpsc::Sender<AdvertisementRecord>,
+  ) {
+    let scan_id = self.next_advertisement_scan_id;
+    self.next_advertisement_scan_id += 1;
+    let already_scanning = self.scanning_status()
+      || self.scanning_bringup_in_progress
+      || !self.advertisement_scans.is_empty();
+    self.advertisement_scans.insert(scan_id, sender);
+    self.update_reporting_advertisements();
+    let advertisement_timer_sender = self.advertisement_timer_sender.clone();
+    async_manager::spawn(async move {
+      tokio::time::sleep(duration).await;
+      let _ = advertisement_timer_sender.send(scan_id).await;
+    });
+    if !already_scanning {
+      info!("Scanning for advertisements for {:?}.", duration);
+      let fut_vec: Vec<_> = self
+        .comm_managers
+        .iter_mut()
+        .map(|guard| {
+          let name = guard.name();
+          guard.start_scanning().map(move |result| (name, result))
+        })
+        .collect();
+      let results = future::join_all(fut_vec).await;
+      self.record_comm_manager_results(results);
+    }
+  }
+
+  /// Tell comm managers whether to report advertisements, if that's changed since they were last
+  /// told.
+  fn update_reporting_advertisements(&mut self) {
+    let reporting = !self.advertisement_scans.is_empty();
+    if reporting == self.reporting_advertisements {
+      return;
+    }
+    self.reporting_advertisements = reporting;
+    for comm_manager in self.comm_managers.iter_mut() {
+      comm_manager.set_reporting_advertisements(reporting);
+    }
+  }
+
+  /// End an advertisement scan whose duration ran out, stopping scanning if nothing else needs
+  /// it.
+  async fn handle_advertisement_timer(&mut self, scan_id: u64) {
+    // Already gone if scanning was stopped in the meantime.
+    if self.advertisement_scans.remove(&scan_id).is_none() {
+      return;
+    }
+    self.update_reporting_advertisements();
+    if self.advertisement_scans.is_empty() && !self.scanning_started && !self.reconnect_scanning {
+      debug!("Advertisement scans finished, stopping scanning.");
+      self.handle_stop_scanning().await;
+    }
   }
 
   async fn handle_device_communication(&mut self, event: HardwareCommunicationManagerEvent) {
@@ -130,7 +611,7 @@ impl ServerDeviceManagerEventLoop {
         }
         if !self.scanning_status() && self.scanning_started {
           debug!("All managers finished, emitting ScanningFinished");
-          self.scanning_started = false;
+          self.set_scanning_started(false);
           if self
             .server_sender
             .send(ScanningFinished::default().into())
@@ -140,12 +621,43 @@ impl ServerDeviceManagerEventLoop {
           }
         }
       }
+      HardwareCommunicationManagerEvent::Advertisement(record) => {
+        // Scans whose stream was dropped are cleaned up here, the rest skip records they're too
+        // far behind to take.
+        self
+          .advertisement_scans
+          .retain(|_, sender| match sender.try_send(record.clone()) {
+            Ok(()) | Err(mpsc::error::TrySendError::Full(_)) => true,
+            Err(mpsc::error::TrySendError::Closed(_)) => false,
+          });
+        self.update_reporting_advertisements();
+      }
       HardwareCommunicationManagerEvent::DeviceFound {
         name,
         address,
+        rssi,
         creator,
       } => {
         info!("Device {} ({}) found.", name, address);
+        // Record everything we see before any filtering happens, so scan results reflect the
+        // whole environment.
+        let protocol_guess = self
+          .device_config_manager
+          .protocol_name_for_specifier(&creator.specifier());
+        self.discovered_devices.insert(
+          address.clone(),
+          DiscoveredDevice::new(&address, &name, rssi, protocol_guess),
+        );
+        if !self.advertisement_scans.is_empty()
+          && !self.scanning_started
+          && !self.reconnecting_devices.contains_key(&address)
+        {
+          debug!(
+            "Device {} found while scanning for advertisements only, not connecting.",
+            address
+          );
+          return;
+        }
         // Make sure the device isn't on the deny list, or is on the allow list if anything is on it.
         if !self.device_config_manager.address_allowed(&address) {
           return;
@@ -169,6 +681,26 @@ impl ServerDeviceManagerEventLoop {
           return;
         }
 
+        // Devices still connecting count against the limit, so a burst of discoveries can't go
+        // over it.
+        if let Some(max_devices) = self.max_devices {
+          if self.device_map.len() + self.connecting_devices.len() >= max_devices as usize {
+            info!(
+              "Device limit of {} reached, ignoring device {}.",
+              max_devices, address
+            );
+            if !self.device_limit_reported {
+              self.device_limit_reported = true;
+              if self.device_limit_reached_sender.send(()).is_err() {
+                debug!(
+                  "No one listening for the device limit, dropping Device Limit Reached event."
+                );
+              }
+            }
+            return;
+          }
+        }
+
         // First off, we need to see if we even have a configuration available for the device we're
         // trying to create. If we don't, exit, because this isn't actually an error. However, if we
         // actually *do* have a configuration but something goes wrong after this, then it's an
@@ -220,7 +752,7 @@ impl ServerDeviceManagerEventLoop {
           match build_server_device(device_config_manager, creator, protocol_specializers).await {
             Ok(device) => {
               if device_event_sender_clone
-                .send(ServerDeviceEvent::Connected(Arc::new(device)))
+                .send(ServerDeviceEvent::Connected(device))
                 .await
                 .is_err() {
                 error!("Device manager disappeared before connection established, device will be dropped.");
@@ -269,8 +801,15 @@ impl ServerDeviceManagerEventLoop {
           info!("Device map does not contain key {}.", device_index);
         }
 
-        // Create event loop for forwarding device events into our selector.
-        let event_listener = device.event_stream();
+        // Create event loops for forwarding device events into our selector. Protocol handler
+        // events get their own, as the handler is replaced if the device is requeried.
+        spawn_handler_event_forwarder(
+          &self.handler_event_forwarders,
+          self.device_event_sender.clone(),
+          device_index,
+          &device,
+        );
+        let event_listener = device.hardware_event_stream();
         let event_sender = self.device_event_sender.clone();
         async_manager::spawn(async move {
           pin_mut!(event_listener);
@@ -283,6 +822,25 @@ impl ServerDeviceManagerEventLoop {
           }
         });
 
+        self
+          .disconnected_devices
+          .remove(device.identifier().address());
+        if self
+          .reconnecting_devices
+          .remove(device.identifier().address())
+          .is_some()
+        {
+          info!("Device {} reconnected.", device.identifier().address());
+          self.stop_reconnect_scanning().await;
+        }
+        for waiter in self
+          .reconnect_waiters
+          .remove(device.identifier().address())
+          .unwrap_or_default()
+        {
+          let _ = waiter.send(Ok(()));
+        }
+
         info!("Assigning index {} to {}", device_index, device.name());
         let device_added_message = DeviceAdded::new(
           device_index,
@@ -291,6 +849,7 @@ impl ServerDeviceManagerEventLoop {
           &None,
           &device.message_attributes().into(),
         );
+        device.set_connected_at(Some(Instant::now()));
         self.device_map.insert(device_index, device);
         // After that, we can send out to the server's event listeners to let
         // them know a device has been added.
@@ -305,7 +864,11 @@ impl ServerDeviceManagerEventLoop {
       ServerDeviceEvent::Disconnected(identifier) => {
         let mut device_index = None;
         for device_pair in self.device_map.iter() {
-          if *device_pair.value().identifier() == identifier {
+          // Match on address and protocol only, as a requeried device may be identified with
+          // different attributes than the ones its hardware events were tagged with on connection.
+          if device_pair.value().identifier().address() == identifier.address()
+            && device_pair.value().identifier().protocol() == identifier.protocol()
+          {
             device_index = Some(*device_pair.key());
             break;
           }
@@ -315,6 +878,15 @@ impl ServerDeviceManagerEventLoop {
             .device_map
             .remove(&device_index)
             .expect("Remove will always work.");
+          cancel_handler_event_forwarder(&self.handler_event_forwarders, device_index);
+          if let Some(command_statistics) = &self.command_statistics {
+            command_statistics.remove(&device_index);
+          }
+          self.device_sources.remove(identifier.address());
+          self.device_limit_reported = false;
+          self
+            .disconnected_devices
+            .insert(identifier.address().clone(), device_index);
           if self
             .server_sender
             .send(DeviceRemoved::new(device_index).into())
@@ -322,6 +894,12 @@ impl ServerDeviceManagerEventLoop {
           {
             debug!("Server not currently available, dropping Device Removed event.");
           }
+          if self.reconnect_policy.is_some() {
+            self
+              .reconnecting_devices
+              .insert(identifier.address().clone(), (device_index, 0));
+            self.schedule_reconnect_attempt(identifier.address().clone());
+          }
         }
       }
       ServerDeviceEvent::Notification(_, message) => {
@@ -332,6 +910,76 @@ impl ServerDeviceManagerEventLoop {
     }
   }
 
+  fn handle_query_device(
+    &self,
+    device_index: u32,
+    reply_sender: oneshot::Sender<Result<ServerDeviceInfo, ButtplugError>>,
+  ) {
+    let device = if let Some(device) = self.device_map.get(&device_index) {
+      device.value().clone()
+    } else {
+      let _ = reply_sender.send(Err(
+        ButtplugDeviceError::DeviceNotAvailable(device_index).into(),
+      ));
+      return;
+    };
+    let device_config_manager = self.device_config_manager.clone();
+    let device_map = self.device_map.clone();
+    let device_event_sender = self.device_event_sender.clone();
+    let device_update_sender = self.device_update_sender.clone();
+    let server_sender = self.server_sender.clone();
+    let handler_event_forwarders = self.handler_event_forwarders.clone();
+    // Handshakes can take a while, so run them outside of the event loop.
+    async_manager::spawn(async move {
+      let result = match requery_server_device(device_config_manager.clone(), &device).await {
+        Ok(new_device) => {
+          // Requerying doesn't reconnect the device, so it's been connected as long as before.
+          new_device.set_connected_at(device.connected_at());
+          // If the device disconnected while we were talking to it, don't bring it back.
+          if device_map.contains_key(&device_index) {
+            device_map.insert(device_index, new_device.clone());
+            // The hardware event stream is still being forwarded from when the device first
+            // connected, but the old protocol handler's events are swapped for the new one's.
+            spawn_handler_event_forwarder(
+              &handler_event_forwarders,
+              device_event_sender,
+              device_index,
+              &new_device,
+            );
+            // The spec has no message for changed attributes, so clients are told the device was
+            // removed and added again under the same index.
+            let device_added_message = DeviceAdded::new(
+              device_index,
+              &new_device.name(),
+              &new_device.display_name(),
+              &None,
+              &new_device.message_attributes().into(),
+            );
+            if server_sender
+              .send(DeviceRemoved::new(device_index).into())
+              .is_err()
+              || server_sender.send(device_added_message.into()).is_err()
+            {
+              debug!("Server not currently available, dropping Device Updated messages.");
+            }
+            let device_info = ServerDeviceInfo::new(new_device.as_ref(), &device_config_manager);
+            if device_update_sender
+              .send((device_index, device_info.clone()))
+              .is_err()
+            {
+              debug!("No one listening for device updates, dropping Device Updated event.");
+            }
+            Ok(device_info)
+          } else {
+            Err(ButtplugDeviceError::DeviceNotAvailable(device_index).into())
+          }
+        }
+        Err(err) => Err(err.into()),
+      };
+      let _ = reply_sender.send(result);
+    });
+  }
+
   pub async fn run(&mut self) {
     debug!("Starting Device Manager Loop");
     loop {
@@ -359,12 +1007,40 @@ impl ServerDeviceManagerEventLoop {
             match msg {
               DeviceManagerCommand::StartScanning => self.handle_start_scanning().await,
               DeviceManagerCommand::StopScanning => self.handle_stop_scanning().await,
+              DeviceManagerCommand::QueryDevice(device_index, reply_sender) => {
+                self.handle_query_device(device_index, reply_sender)
+              }
+              DeviceManagerCommand::Reset(reply_sender) => {
+                self.handle_reset().await;
+                let _ = reply_sender.send(());
+              }
+              DeviceManagerCommand::AddCommManager(comm_manager, reply_sender) => {
+                let _ = reply_sender.send(self.handle_add_comm_manager(comm_manager).await);
+              }
+              DeviceManagerCommand::CommManagerStatus(reply_sender) => {
+                let _ = reply_sender.send(self.handle_comm_manager_status());
+              }
+              DeviceManagerCommand::ReconnectAllDevices(reply_sender) => {
+                self.handle_reconnect_all_devices(reply_sender).await
+              }
+              DeviceManagerCommand::AddVirtualDevice(device) => {
+                self.handle_device_event(ServerDeviceEvent::Connected(device)).await
+              }
+              DeviceManagerCommand::ScanAdvertisements(duration, sender) => {
+                self.handle_scan_advertisements(duration, sender).await
+              }
             }
           } else {
             debug!("Channel to Device Manager frontend dropped, exiting event loop.");
             break;
           }
         }
+        Some(address) = self.reconnect_timer_receiver.recv() => {
+          self.handle_reconnect_timer(address).await;
+        }
+        Some(scan_id) = self.advertisement_timer_receiver.recv() => {
+          self.handle_advertisement_timer(scan_id).await;
+        }
         _ = self.loop_cancellation_token.cancelled().fuse() => {
           debug!("Device event loop cancelled, exiting.");
           break;
@@ -0,0 +1,280 @@
+// Buttplug Rust Source Code File - See https://buttplug.io for more info.
+//
+// Copyright 2016-2022 Nonpolynomial Labs LLC. All rights reserved.
+//
+// Licensed under the BSD 3-Clause license. See LICENSE file in the project root
+// for full license information.
+
+//! Devices with no hardware behind them, see
+//! [ServerDeviceManager::add_virtual_device](super::ServerDeviceManager::add_virtual_device).
+
+use super::{
+  configuration::{
+    ProtocolAttributesType,
+    ProtocolDeviceAttributes,
+    ServerDeviceMessageAttributes,
+  },
+  hardware::{
+    Hardware,
+    HardwareCommand,
+    HardwareEvent,
+    HardwareInternal,
+    HardwareReadCmd,
+    HardwareReading,
+    HardwareSubscribeCmd,
+    HardwareUnsubscribeCmd,
+    HardwareWriteCmd,
+  },
+  protocol::ProtocolHandler,
+  ServerDevice,
+  ServerDeviceIdentifier,
+};
+use crate::core::{
+  errors::ButtplugDeviceError,
+  message::{self, Endpoint},
+};
+use dashmap::DashSet;
+use futures::{future::BoxFuture, FutureExt};
+use getset::{CopyGetters, Getters};
+use std::{
+  sync::{
+    atomic::{AtomicU32, Ordering},
+    Arc,
+  },
+  time::Duration,
+};
+use tokio::{sync::broadcast, time::sleep};
+
+/// Protocol name used in the identifiers of virtual devices.
+pub const VIRTUAL_DEVICE_PROTOCOL: &str = "virtual";
+
+/// Used to give each virtual device its own address.
+static NEXT_VIRTUAL_DEVICE_ID: AtomicU32 = AtomicU32::new(0);
+
+/// Settings for a device added with
+/// [ServerDeviceManager::add_virtual_device](super::ServerDeviceManager::add_virtual_device).
+#[derive(Debug, Clone, Getters, CopyGetters)]
+pub struct VirtualDeviceConfig {
+  /// Name reported to clients.
+  #[getset(get = "pub")]
+  name: String,
+  /// Messages the device accepts, and its actuators and sensors.
+  #[getset(get = "pub")]
+  capabilities: ServerDeviceMessageAttributes,
+  /// How long each hardware command takes to complete, for simulating slow devices.
+  #[getset(get_copy = "pub")]
+  response_delay: Duration,
+}
+
+impl VirtualDeviceConfig {
+  pub fn new(
+    name: &str,
+    capabilities: ServerDeviceMessageAttributes,
+    response_delay: Duration,
+  ) -> Self {
+    Self {
+      name: name.to_owned(),
+      capabilities,
+      response_delay,
+    }
+  }
+}
+
+/// Build a [ServerDevice] for a virtual device, with a unique address.
+pub(super) fn build_virtual_server_device(config: &VirtualDeviceConfig) -> Arc<ServerDevice> {
+  let address = format!(
+    "virtual-{}",
+    NEXT_VIRTUAL_DEVICE_ID.fetch_add(1, Ordering::SeqCst)
+  );
+  let identifier = ServerDeviceIdentifier::new(
+    &address,
+    VIRTUAL_DEVICE_PROTOCOL,
+    &ProtocolAttributesType::Default,
+  );
+  let hardware = Hardware::new(
+    config.name(),
+    &address,
+    &[Endpoint::Tx],
+    Box::new(VirtualHardware::new(&address, config.response_delay())),
+  );
+  let attributes = ProtocolDeviceAttributes::new(
+    ProtocolAttributesType::Default,
+    Some(config.name().clone()),
+    None,
+    config.capabilities().clone(),
+    None,
+  );
+  ServerDevice::new(
+    identifier,
+    Arc::new(VirtualProtocol::default()),
+    Arc::new(hardware),
+    &attributes,
+    Arc::new(DashSet::new()),
+  )
+}
+
+/// Hardware that accepts every write after the configured delay, and does nothing with it.
+struct VirtualHardware {
+  address: String,
+  response_delay: Duration,
+  event_sender: broadcast::Sender<HardwareEvent>,
+}
+
+impl VirtualHardware {
+  fn new(address: &str, response_delay: Duration) -> Self {
+    Self {
+      address: address.to_owned(),
+      response_delay,
+      event_sender: broadcast::channel(256).0,
+    }
+  }
+
+  fn respond<T: Send + 'static>(
+    &self,
+    result: Result<T, ButtplugDeviceError>,
+  ) -> BoxFuture<'static, Result<T, ButtplugDeviceError>> {
+    let response_delay = self.response_delay;
+    async move {
+      if !response_delay.is_zero() {
+        sleep(response_delay).await;
+      }
+      result
+    }
+    .boxed()
+  }
+}
+
+impl HardwareInternal for VirtualHardware {
+  fn disconnect(&self) -> BoxFuture<'static, Result<(), ButtplugDeviceError>> {
+    let sender = self.event_sender.clone();
+    let address = self.address.clone();
+    async move {
+      // No one listening just means the device is already gone.
+      let _ = sender.send(HardwareEvent::Disconnected(address));
+      Ok(())
+    }
+    .boxed()
+  }
+
+  fn event_stream(&self) -> broadcast::Receiver<HardwareEvent> {
+    self.event_sender.subscribe()
+  }
+
+  fn read_value(
+    &self,
+    msg: &HardwareReadCmd,
+  ) -> BoxFuture<'static, Result<HardwareReading, ButtplugDeviceError>> {
+    self.respond(Ok(HardwareReading::new(msg.endpoint(), &[])))
+  }
+
+  fn write_value(
+    &self,
+    _msg: &HardwareWriteCmd,
+  ) -> BoxFuture<'static, Result<(), ButtplugDeviceError>> {
+    self.respond(Ok(()))
+  }
+
+  fn subscribe(
+    &self,
+    _msg: &HardwareSubscribeCmd,
+  ) -> BoxFuture<'static, Result<(), ButtplugDeviceError>> {
+    self.respond(Ok(()))
+  }
+
+  fn unsubscribe(
+    &self,
+    _msg: &HardwareUnsubscribeCmd,
+  ) -> BoxFuture<'static, Result<(), ButtplugDeviceError>> {
+    self.respond(Ok(()))
+  }
+}
+
+/// Turns every actuator command into a single write, so commands still reach the hardware layer
+/// (and its response delay) the same way they would for real devices.
+#[derive(Default)]
+struct VirtualProtocol {}
+
+impl VirtualProtocol {
+  fn write(index: u32, value: u32) -> Vec<HardwareCommand> {
+    let mut data = index.to_le_bytes().to_vec();
+    data.extend_from_slice(&value.to_le_bytes());
+    vec![HardwareWriteCmd::new(Endpoint::Tx, data, false).into()]
+  }
+}
+
+impl ProtocolHandler for VirtualProtocol {
+  fn handle_scalar_vibrate_cmd(
+    &self,
+    index: u32,
+    scalar: u32,
+  ) -> Result<Vec<HardwareCommand>, ButtplugDeviceError> {
+    Ok(Self::write(index, scalar))
+  }
+
+  fn handle_scalar_rotate_cmd(
+    &self,
+    index: u32,
+    scalar: u32,
+  ) -> Result<Vec<HardwareCommand>, ButtplugDeviceError> {
+    Ok(Self::write(index, scalar))
+  }
+
+  fn handle_scalar_oscillate_cmd(
+    &self,
+    index: u32,
+    scalar: u32,
+  ) -> Result<Vec<HardwareCommand>, ButtplugDeviceError> {
+    Ok(Self::write(index, scalar))
+  }
+
+  fn handle_scalar_inflate_cmd(
+    &self,
+    index: u32,
+    scalar: u32,
+  ) -> Result<Vec<HardwareCommand>, ButtplugDeviceError> {
+    Ok(Self::write(index, scalar))
+  }
+
+  fn handle_scalar_constrict_cmd(
+    &self,
+    index: u32,
+    scalar: u32,
+  ) -> Result<Vec<HardwareCommand>, ButtplugDeviceError> {
+    Ok(Self::write(index, scalar))
+  }
+
+  fn handle_scalar_position_cmd(
+    &self,
+    index: u32,
+    scalar: u32,
+  ) -> Result<Vec<HardwareCommand>, ButtplugDeviceError> {
+    Ok(Self::write(index, scalar))
+  }
+
+  fn handle_rotate_cmd(
+    &self,
+    commands: &[Option<(u32, bool)>],
+  ) -> Result<Vec<HardwareCommand>, ButtplugDeviceError> {
+    Ok(
+      commands
+        .iter()
+        .enumerate()
+        .filter_map(|(index, command)| command.map(|(speed, _)| (index as u32, speed)))
+        .flat_map(|(index, speed)| Self::write(index, speed))
+        .collect(),
+    )
+  }
+
+  fn handle_linear_cmd(
+    &self,
+    message: message::LinearCmd,
+  ) -> Result<Vec<HardwareCommand>, ButtplugDeviceError> {
+    Ok(
+      message
+        .vectors()
+        .iter()
+        .flat_map(|vector| Self::write(vector.index(), (vector.position() * 100.0) as u32))
+        .collect(),
+    )
+  }
+}
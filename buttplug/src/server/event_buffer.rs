@@ -0,0 +1,85 @@
+// Buttplug Rust Source Code File - See https://buttplug.io for more info.
+//
+// Copyright 2016-2023 Nonpolynomial Labs LLC. All rights reserved.
+//
+// Licensed under the BSD 3-Clause license. See LICENSE file in the project root
+// for full license information.
+
+//! Buffer of recent server events, so late subscribers can catch up on what they missed.
+
+use super::ButtplugServer;
+use crate::{
+  core::message::{ButtplugServerMessage, LaggedEvents},
+  util::stream::convert_broadcast_receiver_to_stream_with_lag_marker,
+};
+use futures::{future::Either, pin_mut, stream, Stream, StreamExt};
+use std::{
+  collections::VecDeque,
+  sync::{Arc, Mutex},
+  time::Instant,
+};
+use tokio::sync::broadcast;
+
+/// Holds the most recent server events along with when they happened, see
+/// [ButtplugServerBuilder::event_buffer_size](super::ButtplugServerBuilder::event_buffer_size).
+pub(super) struct EventBuffer {
+  events: Mutex<VecDeque<(Instant, ButtplugServerMessage)>>,
+  size: usize,
+  /// Events are re-sent from here once buffered, so subscribers can't see an event both in the
+  /// buffer and live.
+  sender: broadcast::Sender<ButtplugServerMessage>,
+}
+
+impl EventBuffer {
+  pub fn new(size: usize) -> Self {
+    Self {
+      events: Mutex::new(VecDeque::with_capacity(size)),
+      size,
+      sender: broadcast::channel(256).0,
+    }
+  }
+
+  /// Buffer events from `events` until it ends.
+  pub async fn record(self: Arc<Self>, events: impl Stream<Item = ButtplugServerMessage>) {
+    pin_mut!(events);
+    while let Some(event) = events.next().await {
+      let mut buffered = self.events.lock().expect("Lock poisoned");
+      if buffered.len() >= self.size {
+        buffered.pop_front();
+      }
+      buffered.push_back((Instant::now(), event.clone()));
+      // Sent while holding the lock, so it lines up with what subscribers see in the buffer.
+      let _ = self.sender.send(event);
+    }
+  }
+
+  fn stream_since(&self, instant: Instant) -> impl Stream<Item = ButtplugServerMessage> {
+    let (missed, receiver) = {
+      let buffered = self.events.lock().expect("Lock poisoned");
+      let missed: Vec<ButtplugServerMessage> = buffered
+        .iter()
+        .filter(|(timestamp, _)| *timestamp >= instant)
+        .map(|(_, event)| event.clone())
+        .collect();
+      (missed, self.sender.subscribe())
+    };
+    stream::iter(missed).chain(convert_broadcast_receiver_to_stream_with_lag_marker(
+      receiver,
+      |count| Some(LaggedEvents::new(count).into()),
+    ))
+  }
+}
+
+impl ButtplugServer {
+  /// Like [ButtplugServer::event_stream], but starts by replaying buffered events that happened at
+  /// or after `instant`. Only the last
+  /// [ButtplugServerBuilder::event_buffer_size](super::ButtplugServerBuilder::event_buffer_size)
+  /// events are kept, and if buffering is off this is the same as
+  /// [ButtplugServer::event_stream].
+  pub fn event_stream_since(&self, instant: Instant) -> impl Stream<Item = ButtplugServerMessage> {
+    match &self.event_buffer {
+      Some(event_buffer) => Either::Left(event_buffer.stream_since(instant)),
+      None => Either::Right(self.event_stream()),
+    }
+  }
+}
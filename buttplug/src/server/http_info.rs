@@ -0,0 +1,161 @@
+// Buttplug Rust Source Code File - See https://buttplug.io for more info.
+//
+// Copyright 2016-2023 Nonpolynomial Labs LLC. All rights reserved.
+//
+// Licensed under the BSD 3-Clause license. See LICENSE file in the project root
+// for full license information.
+
+//! HTTP endpoint serving server status for liveness probes and dashboards, see
+//! [ButtplugRemoteServer::serve_http_info_endpoint].
+
+#[cfg(doc)]
+use super::ButtplugRemoteServer;
+use super::StatusReport;
+use crate::util::async_manager;
+use hyper::{
+  header::CONTENT_TYPE,
+  service::{make_service_fn, service_fn},
+  Body,
+  Request,
+  Response,
+  Server,
+  StatusCode,
+};
+use serde_json::json;
+use std::{convert::Infallible, net::SocketAddr, sync::Arc, time::Duration};
+use tokio_util::sync::CancellationToken;
+
+/// How long clients get to send their request headers, so idle connections can't be held open.
+const REQUEST_HEADER_READ_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// HTTP info endpoint started with [ButtplugRemoteServer::serve_http_info_endpoint]. Dropping the
+/// handle does not stop the endpoint.
+#[derive(Debug, Clone)]
+pub struct HttpInfoHandle {
+  address: SocketAddr,
+  cancellation_token: CancellationToken,
+}
+
+impl HttpInfoHandle {
+  /// Address the endpoint is listening on. Useful when it was started on port 0.
+  pub fn address(&self) -> SocketAddr {
+    self.address
+  }
+
+  /// Stop serving. Requests already being handled are finished first.
+  pub fn stop(&self) {
+    self.cancellation_token.cancel();
+  }
+
+  /// True if the endpoint was stopped.
+  pub fn is_stopped(&self) -> bool {
+    self.cancellation_token.is_cancelled()
+  }
+}
+
+/// Where the endpoint gets the data it serves from.
+pub(super) struct HttpInfoSource {
+  pub(super) status: Box<dyn Fn() -> StatusReport + Send + Sync>,
+  #[cfg(feature = "metrics")]
+  pub(super) metrics: Box<dyn Fn() -> String + Send + Sync>,
+}
+
+/// [StatusReport] as served at `/info`.
+fn status_json(report: &StatusReport) -> serde_json::Value {
+  json!({
+    "server_name": report.server_name(),
+    "uptime_seconds": report.uptime().as_secs_f64(),
+    "client_name": report.client_name(),
+    "connection_duration_seconds": report.connection_duration().as_secs_f64(),
+    "devices": report.devices().iter().map(|device| json!({
+      "index": device.index(),
+      "name": device.name(),
+      "address": device.address(),
+      "last_command_age_seconds": device.last_command_age().map(|age| age.as_secs_f64()),
+    })).collect::<Vec<_>>(),
+    "message_count": report.message_count(),
+    "error_count": report.error_count(),
+    "features": report.features(),
+  })
+}
+
+fn respond(source: &HttpInfoSource, request: Request<Body>) -> Response<Body> {
+  let (content_type, body) = match request.uri().path() {
+    "/info" => (
+      "application/json",
+      status_json(&(source.status)()).to_string(),
+    ),
+    #[cfg(feature = "metrics")]
+    "/metrics" => ("text/plain; version=0.0.4", (source.metrics)()),
+    _ => {
+      return Response::builder()
+        .status(StatusCode::NOT_FOUND)
+        .body(Body::empty())
+        .expect("Response is valid")
+    }
+  };
+  Response::builder()
+    .header(CONTENT_TYPE, content_type)
+    .body(Body::from(body))
+    .expect("Response is valid")
+}
+
+pub(super) fn start_http_info_endpoint(
+  addr: SocketAddr,
+  source: HttpInfoSource,
+) -> Result<HttpInfoHandle, std::io::Error> {
+  let (address, cancellation_token) = serve_http(addr, "HTTP info endpoint", move |request| {
+    respond(&source, request)
+  })?;
+  Ok(HttpInfoHandle {
+    address,
+    cancellation_token,
+  })
+}
+
+/// Answer HTTP requests on `addr` with `respond`, until the returned token is cancelled. `name`
+/// is used in logs. Returns the address listened on, which differs from `addr` for port 0.
+pub(super) fn serve_http<F>(
+  addr: SocketAddr,
+  name: &'static str,
+  respond: F,
+) -> Result<(SocketAddr, CancellationToken), std::io::Error>
+where
+  F: Fn(Request<Body>) -> Response<Body> + Send + Sync + 'static,
+{
+  // Bound here rather than in the task, so bind errors can be returned.
+  let listener = std::net::TcpListener::bind(addr)?;
+  listener.set_nonblocking(true)?;
+  let address = listener.local_addr()?;
+  let cancellation_token = CancellationToken::new();
+  let token = cancellation_token.clone();
+  let respond = Arc::new(respond);
+  async_manager::spawn(async move {
+    let builder = match Server::from_tcp(listener) {
+      Ok(builder) => builder.http1_header_read_timeout(REQUEST_HEADER_READ_TIMEOUT),
+      Err(err) => {
+        error!("Cannot serve {}: {}", name, err);
+        return;
+      }
+    };
+    let make_service = make_service_fn(move |_| {
+      let respond = respond.clone();
+      async move {
+        Ok::<_, Infallible>(service_fn(move |request| {
+          let response = respond(request);
+          async move { Ok::<_, Infallible>(response) }
+        }))
+      }
+    });
+    info!("Serving {} on {}", name, address);
+    if let Err(err) = builder
+      .serve(make_service)
+      .with_graceful_shutdown(token.cancelled())
+      .await
+    {
+      error!("{} failed: {}", name, err);
+    }
+    info!("Stopped serving {} on {}", name, address);
+  });
+  Ok((address, cancellation_token))
+}
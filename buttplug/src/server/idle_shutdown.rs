@@ -0,0 +1,56 @@
+// Buttplug Rust Source Code File - See https://buttplug.io for more info.
+//
+// Copyright 2016-2023 Nonpolynomial Labs LLC. All rights reserved.
+//
+// Licensed under the BSD 3-Clause license. See LICENSE file in the project root
+// for full license information.
+
+//! Shutting down devices clients have stopped sending commands to, see
+//! [ButtplugRemoteServerBuilder::idle_device_shutdown].
+
+#[cfg(doc)]
+use super::ButtplugRemoteServerBuilder;
+use getset::CopyGetters;
+use std::time::Duration;
+
+/// What to do with a device that has gone idle.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IdleAction {
+  /// Stop all of the device's actuators, leaving it connected.
+  Stop,
+  /// Disconnect the device.
+  Disconnect,
+}
+
+/// Longest an idle device gets to stop or disconnect by default, see
+/// [IdleShutdownPolicy::with_shutdown_timeout].
+pub const DEFAULT_IDLE_SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// When a device counts as idle, and what to do with it then. A device goes idle once `idle_after`
+/// has passed since the last actuator command a client sent it. Devices that have never been sent
+/// one are left alone.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, CopyGetters)]
+#[getset(get_copy = "pub")]
+pub struct IdleShutdownPolicy {
+  idle_after: Duration,
+  action: IdleAction,
+  shutdown_timeout: Duration,
+}
+
+impl IdleShutdownPolicy {
+  pub fn new(idle_after: Duration, action: IdleAction) -> Self {
+    Self {
+      idle_after,
+      action,
+      shutdown_timeout: DEFAULT_IDLE_SHUTDOWN_TIMEOUT,
+    }
+  }
+
+  /// Longest each idle device gets to stop or disconnect, [DEFAULT_IDLE_SHUTDOWN_TIMEOUT] by
+  /// default. Idle devices are handled at the same time, so one unresponsive device doesn't hold
+  /// up the rest.
+  pub fn with_shutdown_timeout(mut self, shutdown_timeout: Duration) -> Self {
+    self.shutdown_timeout = shutdown_timeout;
+    self
+  }
+}
@@ -45,34 +45,96 @@
 //!   - If the server object is dropped, all devices are stopped and disconnected as part
 //!     of the [DeviceManager] teardown.
 
+#[cfg(feature = "axum")]
+mod axum_handler;
+mod connection_quality;
 pub mod device;
+mod event_buffer;
+#[cfg(feature = "http-info")]
+mod http_info;
+mod idle_shutdown;
+mod pairing;
+mod pause;
 mod ping_timer;
 mod remote_server;
+#[cfg(feature = "chrono")]
+mod scheduler;
+#[cfg(feature = "tower")]
+mod service;
+mod session_log;
+mod session_recorder;
+mod status_report;
+mod telemetry;
+mod typed_event;
 
+#[cfg(feature = "axum")]
+pub use axum_handler::buttplug_axum_handler;
+pub use connection_quality::ConnectionQualityConfig;
+#[cfg(feature = "http-info")]
+pub use http_info::HttpInfoHandle;
+pub use idle_shutdown::{IdleAction, IdleShutdownPolicy, DEFAULT_IDLE_SHUTDOWN_TIMEOUT};
+pub use pairing::PairingEvent;
+pub use pause::PauseHandle;
 pub use remote_server::*;
+#[cfg(feature = "chrono")]
+pub use scheduler::ScheduleHandle;
+#[cfg(feature = "tower")]
+pub use service::ButtplugServerService;
+pub use session_log::{
+  SessionEvent,
+  SessionEventKind,
+  SessionLog,
+  MAX_SESSION_LOGS,
+  MAX_SESSION_LOG_EVENTS,
+};
+pub use session_recorder::{
+  replay_transcript,
+  MessageDirection,
+  SessionRecorder,
+  TimestampedMessage,
+};
+pub use status_report::{StatusReport, StatusReportDevice};
+pub use telemetry::{TelemetryConfig, TelemetryError};
+pub use typed_event::{ButtplugEvent, ClientConnectedEvent, DeviceAddedEvent, DeviceRemovedEvent};
 
 use self::device::{
   configuration::{
     ProtocolAttributesIdentifier,
     ProtocolCommunicationSpecifier,
     ProtocolDeviceAttributes,
+    ProtocolInfo,
+    ServerDeviceMessageAttributes,
   },
-  hardware::communication::HardwareCommunicationManagerBuilder,
-  protocol::ProtocolIdentifierFactory,
+  hardware::communication::{AdvertisementRecord, HardwareCommunicationManagerBuilder},
+  protocol::{CalibrationResult, ProtocolIdentifierFactory},
+  CommManagerStatus,
+  CommandConflictPolicy,
+  CommandStatistics,
+  DiscoveredDevice,
   ServerDeviceIdentifier,
+  ServerDeviceInfo,
   ServerDeviceManager,
   ServerDeviceManagerBuilder,
+  VirtualDeviceConfig,
 };
+use self::{event_buffer::EventBuffer, session_log::SessionLogRecorder};
+#[cfg(feature = "http-config")]
+use crate::util::device_configuration::validate_protocol_config;
 use crate::{
   core::{
+    connector::TlsConfig,
     errors::*,
     message::{
       self,
+      ActuatorType,
       ButtplugClientMessage,
       ButtplugDeviceCommandMessageUnion,
       ButtplugDeviceManagerMessageUnion,
+      ButtplugDeviceMessage,
       ButtplugMessage,
       ButtplugServerMessage,
+      MessageTransformer,
+      StartScanning,
       StopAllDevices,
       StopScanning,
       BUTTPLUG_CURRENT_MESSAGE_SPEC_VERSION,
@@ -81,24 +143,37 @@ use crate::{
   util::{
     async_manager,
     device_configuration::{load_protocol_configs, DEVICE_CONFIGURATION_JSON},
-    stream::convert_broadcast_receiver_to_stream,
+    stream::convert_broadcast_receiver_to_stream_with_lag_marker,
   },
 };
+use dashmap::DashMap;
 use futures::{
   future::{self, BoxFuture, FutureExt},
   Stream,
 };
+use getset::Getters;
 use ping_timer::PingTimer;
+use serde::{Deserialize, Serialize};
+#[cfg(feature = "http-config")]
+use std::path::PathBuf;
 use std::{
+  collections::HashMap,
   fmt,
   sync::{
-    atomic::{AtomicBool, Ordering},
+    atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering},
     Arc,
+    Mutex,
+    RwLock,
   },
+  time::{Duration, Instant, SystemTime},
 };
 use thiserror::Error;
-use tokio::sync::broadcast;
+use tokio::{
+  sync::broadcast,
+  time::{sleep, timeout},
+};
 use tokio_stream::StreamExt;
+use tokio_util::sync::CancellationToken;
 use tracing_futures::Instrument;
 
 /// Result type for Buttplug Server methods, as the server will always communicate in
@@ -110,6 +185,10 @@ pub type ButtplugServerResult = Result<ButtplugServerMessage, ButtplugError>;
 /// Spec](http://buttplug-spec.docs.buttplug.io).
 pub type ButtplugServerResultFuture = BoxFuture<'static, ButtplugServerResult>;
 
+/// Session id for messages sent straight to a [ButtplugServer] or [ServerDeviceManager], rather
+/// than by a remote client session. Remote session ids count up from 0, so they never reach it.
+pub(crate) const LOCAL_SESSION_ID: u64 = u64::MAX;
+
 /// Error enum for Buttplug Server configuration errors.
 #[derive(Error, Debug)]
 pub enum ButtplugServerError {
@@ -125,6 +204,104 @@ pub enum ButtplugServerError {
   /// Requested protocol has not been registered with the system.
   #[error("Buttplug Protocol of type {0} does not exist in the system and cannot be removed.")]
   ProtocolDoesNotExist(String),
+  /// Device configuration could not be downloaded, and no cached copy was available.
+  #[error("Device configuration could not be downloaded: {0}")]
+  DeviceConfigurationDownloadError(String),
+  /// DeviceCommunicationManager did not finish building within the init timeout.
+  #[error("DeviceCommunicationManager did not initialize in time.")]
+  DeviceCommunicationManagerInitTimedOut,
+  /// Device manager has been shut down, so nothing can be added to it.
+  #[error("Device manager is not running.")]
+  DeviceManagerNotRunning,
+}
+
+/// Tracks a single in-flight [ButtplugServer::parse_message] call. Decrementing on drop means the
+/// count stays correct even if the caller drops the future without polling it to completion.
+struct ActiveCommandGuard(Arc<AtomicUsize>);
+
+impl ActiveCommandGuard {
+  fn new(count: &Arc<AtomicUsize>) -> Self {
+    count.fetch_add(1, Ordering::SeqCst);
+    Self(count.clone())
+  }
+}
+
+impl Drop for ActiveCommandGuard {
+  fn drop(&mut self) {
+    self.0.fetch_sub(1, Ordering::SeqCst);
+  }
+}
+
+type DeviceAddedCallback = Arc<dyn Fn(ServerDeviceInfo) + Send + Sync>;
+type DeviceRemovedCallback = Arc<dyn Fn(u32) + Send + Sync>;
+type ErrorCallback = Arc<dyn Fn(ButtplugError) + Send + Sync>;
+type DeviceErrorCallback = Arc<dyn Fn(u32, ButtplugError) + Send + Sync>;
+
+/// Handler for device events, registered with [ButtplugServer::register_device_event_handler]. An
+/// alternative to the separate [ButtplugServer::on_device_added],
+/// [ButtplugServer::on_device_removed] and [ButtplugServer::on_error] callbacks, for callers that
+/// keep their device handling in one place. Handlers only need to implement the events they care
+/// about.
+pub trait DeviceEventHandler {
+  /// A device connected.
+  fn on_added(&self, _info: ServerDeviceInfo) {
+  }
+  /// The device at this index disconnected.
+  fn on_removed(&self, _index: u32) {
+  }
+  /// A command to the device at this index failed.
+  fn on_error(&self, _index: u32, _err: ButtplugError) {
+  }
+}
+
+/// Callbacks registered via [ButtplugServer::on_device_added] and
+/// [ButtplugServer::on_device_removed].
+#[derive(Default)]
+struct DeviceCallbacks {
+  added: RwLock<Vec<DeviceAddedCallback>>,
+  removed: RwLock<Vec<DeviceRemovedCallback>>,
+  /// Set once the task that watches for device events and calls the callbacks is running.
+  dispatching: AtomicBool,
+}
+
+/// Settings a [ButtplugServer] was built with, as returned by [ButtplugServer::export_config].
+/// Meant for logging or reproducing a server setup while debugging.
+///
+/// Only settings that can be serialized are captured. Comm managers, protocol factories, and
+/// protocol specifiers/attributes added via API calls are not included.
+#[derive(Debug, Clone, Serialize, Deserialize, Getters)]
+#[getset(get = "pub")]
+pub struct ButtplugServerConfig {
+  name: String,
+  max_ping_time: Option<u32>,
+  /// Base device configuration JSON, if it differs from the one built into the library.
+  custom_device_configuration_json: Option<String>,
+  user_device_configuration_json: Option<String>,
+  allow_raw_messages: bool,
+  skip_default_protocols: bool,
+  allowed_addresses: Vec<String>,
+  denied_addresses: Vec<String>,
+  reserved_indexes: Vec<(ServerDeviceIdentifier, u32)>,
+  pretty_print_messages: bool,
+  client_idle_timeout: Option<Duration>,
+  client_rate_limit: Option<(u32, Duration)>,
+  task_watchdog_timeout: Option<Duration>,
+  device_command_debounce: Option<Duration>,
+  track_command_statistics: bool,
+  clear_history_on_disconnect: bool,
+  device_reconnect_delay: Option<Duration>,
+  device_max_reconnect_attempts: Option<u32>,
+  device_stale_timeout: Option<Duration>,
+  comm_manager_init_timeout: Option<Duration>,
+  max_devices: Option<u32>,
+  command_conflict_policy: CommandConflictPolicy,
+  device_error_threshold: Option<u64>,
+  tls_config: Option<TlsConfig>,
+  event_buffer_size: usize,
+  auto_start_scanning: bool,
+  auto_scan_duration: Option<Duration>,
+  record_session: bool,
+  battery_cache_ttl: Option<Duration>,
 }
 
 /// Configures and creates [ButtplugServer] instances.
@@ -141,6 +318,62 @@ pub struct ButtplugServerBuilder {
   user_device_configuration_json: Option<String>,
   /// Device manager builder for the server
   device_manager_builder: ServerDeviceManagerBuilder,
+  /// If true, remote connectors will indent outgoing JSON.
+  pretty_print_messages: bool,
+  /// If set, remote servers disconnect clients that haven't sent a message in this long.
+  client_idle_timeout: Option<Duration>,
+  /// If set, remote servers drop client messages over this count per time window.
+  client_rate_limit: Option<(u32, Duration)>,
+  task_watchdog_timeout: Option<Duration>,
+  /// Applied to messages from remote clients, in order, before they're validated.
+  message_transformers: Vec<Arc<dyn MessageTransformer>>,
+  /// If set, passed to the connectors of remote servers.
+  tls_config: Option<TlsConfig>,
+  /// Settings passed through to the device manager builder, recorded for
+  /// [ButtplugServer::export_config].
+  allow_raw_messages: bool,
+  skip_default_protocols: bool,
+  allowed_addresses: Vec<String>,
+  denied_addresses: Vec<String>,
+  reserved_indexes: Vec<(ServerDeviceIdentifier, u32)>,
+  device_command_debounce: Option<Duration>,
+  track_command_statistics: bool,
+  /// If true, command statistics are cleared when the client disconnects.
+  clear_history_on_disconnect: bool,
+  device_reconnect_delay: Option<Duration>,
+  device_max_reconnect_attempts: Option<u32>,
+  device_stale_timeout: Option<Duration>,
+  comm_manager_init_timeout: Option<Duration>,
+  max_devices: Option<u32>,
+  command_conflict_policy: CommandConflictPolicy,
+  device_error_threshold: Option<u64>,
+  /// Number of recent events kept for [ButtplugServer::event_stream_since]. 0 turns buffering off.
+  event_buffer_size: usize,
+  /// If true, start scanning as soon as a client completes the handshake.
+  auto_start_scanning: bool,
+  /// If set, automatically started scans are stopped after this long.
+  auto_scan_duration: Option<Duration>,
+  /// If true, keep a log of the current session for [ButtplugServer::export_session_log].
+  record_session: bool,
+  /// How long [ButtplugServer::battery_level] can use a cached reading for.
+  battery_cache_ttl: Option<Duration>,
+  /// Where configs downloaded by [ButtplugServerBuilder::with_device_config_url] are cached.
+  #[cfg(feature = "http-config")]
+  device_config_cache_path: Option<PathBuf>,
+}
+
+#[cfg(feature = "http-config")]
+async fn download_device_config(url: &str) -> Result<String, ButtplugServerError> {
+  let download_error = |err: reqwest::Error| {
+    ButtplugServerError::DeviceConfigurationDownloadError(format!("{}: {}", url, err))
+  };
+  reqwest::get(url)
+    .await
+    .and_then(|response| response.error_for_status())
+    .map_err(download_error)?
+    .text()
+    .await
+    .map_err(download_error)
 }
 
 impl Default for ButtplugServerBuilder {
@@ -151,6 +384,34 @@ impl Default for ButtplugServerBuilder {
       device_configuration_json: Some(DEVICE_CONFIGURATION_JSON.to_owned()),
       user_device_configuration_json: None,
       device_manager_builder: ServerDeviceManagerBuilder::default(),
+      pretty_print_messages: false,
+      client_idle_timeout: None,
+      client_rate_limit: None,
+      task_watchdog_timeout: None,
+      message_transformers: vec![],
+      tls_config: None,
+      allow_raw_messages: false,
+      skip_default_protocols: false,
+      allowed_addresses: vec![],
+      denied_addresses: vec![],
+      reserved_indexes: vec![],
+      device_command_debounce: None,
+      track_command_statistics: false,
+      clear_history_on_disconnect: false,
+      device_reconnect_delay: None,
+      device_max_reconnect_attempts: None,
+      device_stale_timeout: None,
+      comm_manager_init_timeout: None,
+      max_devices: None,
+      command_conflict_policy: CommandConflictPolicy::default(),
+      device_error_threshold: None,
+      event_buffer_size: 0,
+      auto_start_scanning: false,
+      auto_scan_duration: None,
+      record_session: false,
+      battery_cache_ttl: None,
+      #[cfg(feature = "http-config")]
+      device_config_cache_path: None,
     }
   }
 }
@@ -181,12 +442,119 @@ impl ButtplugServerBuilder {
     self
   }
 
+  /// Set a file to cache device configurations downloaded by
+  /// [ButtplugServerBuilder::with_device_config_url] in. If the download fails later on, the cached
+  /// copy is used instead, so the server can still start without network access.
+  #[cfg(feature = "http-config")]
+  pub fn device_config_cache_path(&mut self, path: PathBuf) -> &mut Self {
+    self.device_config_cache_path = Some(path);
+    self
+  }
+
+  /// Download the device configuration json file from a URL, to be loaded during build. The file
+  /// is checked before being used, and saved to the
+  /// [cache path](ButtplugServerBuilder::device_config_cache_path) if one is set. If the download
+  /// fails, the cached file is loaded instead.
+  #[cfg(feature = "http-config")]
+  pub async fn with_device_config_url(
+    &mut self,
+    url: &str,
+  ) -> Result<&mut Self, ButtplugServerError> {
+    let config_json = match download_device_config(url).await {
+      Ok(config_json) => {
+        validate_protocol_config(&config_json)
+          .map_err(ButtplugServerError::DeviceConfigurationManagerError)?;
+        if let Some(path) = &self.device_config_cache_path {
+          if let Err(err) = std::fs::write(path, &config_json) {
+            warn!(
+              "Cannot write device configuration cache to {:?}: {}",
+              path, err
+            );
+          }
+        }
+        config_json
+      }
+      Err(err) => match self
+        .device_config_cache_path
+        .as_ref()
+        .and_then(|path| std::fs::read_to_string(path).ok())
+      {
+        Some(config_json) => {
+          warn!(
+            "Cannot download device configuration ({}), using cached configuration.",
+            err
+          );
+          config_json
+        }
+        None => return Err(err),
+      },
+    };
+    self.device_configuration_json = Some(config_json);
+    Ok(self)
+  }
+
   /// Set the user device configuration json file contents, to be loaded during build.
   pub fn user_device_configuration_json(&mut self, config_json: Option<String>) -> &mut Self {
     self.user_device_configuration_json = config_json;
     self
   }
 
+  /// If true, connectors started through a [ButtplugRemoteServer] will pretty print serialized
+  /// messages, to make raw wire traffic readable while debugging. Defaults to false, as pretty
+  /// printing has noticeable overhead at high message rates.
+  pub fn pretty_print_messages(&mut self, pretty_print: bool) -> &mut Self {
+    self.pretty_print_messages = pretty_print;
+    self
+  }
+
+  /// Set the amount of time a client connected through a [ButtplugRemoteServer] can go without
+  /// sending a message before it is disconnected. If this is not called, idle clients stay
+  /// connected.
+  ///
+  /// Unlike [ButtplugServerBuilder::max_ping_time], this doesn't require the client to send Ping
+  /// messages, any message resets the timer.
+  pub fn client_idle_timeout(&mut self, timeout: Duration) -> &mut Self {
+    self.client_idle_timeout = Some(timeout);
+    self
+  }
+
+  /// Limit clients connected through a [ButtplugRemoteServer] to `max_messages` messages per
+  /// `window`. Messages over the limit are dropped and answered with an error, and the remote
+  /// server emits a [ButtplugRemoteServerEvent::RateLimitExceeded] event. Stop messages are never
+  /// rate limited.
+  pub fn client_rate_limit(&mut self, max_messages: u32, window: Duration) -> &mut Self {
+    self.client_rate_limit = Some((max_messages, window));
+    self
+  }
+
+  /// Give up on client messages to a [ButtplugRemoteServer] that take longer than this to handle,
+  /// so a protocol bug that hangs a device command can't leave the client waiting forever. The
+  /// client gets an error reply, and the remote server emits a
+  /// [ButtplugRemoteServerEvent::InternalError] event.
+  pub fn task_watchdog_timeout(&mut self, timeout: Duration) -> &mut Self {
+    self.task_watchdog_timeout = Some(timeout);
+    self
+  }
+
+  /// Use TLS for connectors started by a [ButtplugRemoteServer], so every connector shares the
+  /// same certificate settings. This replaces any TLS settings given to the connector's transport
+  /// directly.
+  pub fn with_tls_config(&mut self, tls_config: TlsConfig) -> &mut Self {
+    self.tls_config = Some(tls_config);
+    self
+  }
+
+  /// Add a transformer for messages from clients connected through a [ButtplugRemoteServer].
+  /// Transformers run in the order they're added, before messages are validated, so they can be
+  /// used to fix up messages from clients that don't quite follow the spec.
+  pub fn message_transformer<T>(&mut self, transformer: T) -> &mut Self
+  where
+    T: MessageTransformer + 'static,
+  {
+    self.message_transformers.push(Arc::new(transformer));
+    self
+  }
+
   pub fn comm_manager<T>(&mut self, builder: T) -> &mut Self
   where
     T: HardwareCommunicationManagerBuilder + 'static,
@@ -197,11 +565,13 @@ impl ButtplugServerBuilder {
 
   pub fn allowed_address(&mut self, address: &str) -> &mut Self {
     self.device_manager_builder.allowed_address(address);
+    self.allowed_addresses.push(address.to_owned());
     self
   }
 
   pub fn denied_address(&mut self, address: &str) -> &mut Self {
     self.device_manager_builder.denied_address(address);
+    self.denied_addresses.push(address.to_owned());
     self
   }
 
@@ -209,6 +579,7 @@ impl ButtplugServerBuilder {
     self
       .device_manager_builder
       .reserved_index(identifier, index);
+    self.reserved_indexes.push((identifier.clone(), index));
     self
   }
 
@@ -244,11 +615,152 @@ impl ButtplugServerBuilder {
 
   pub fn skip_default_protocols(&mut self) -> &mut Self {
     self.device_manager_builder.skip_default_protocols();
+    self.skip_default_protocols = true;
     self
   }
 
   pub fn allow_raw_messages(&mut self) -> &mut Self {
     self.device_manager_builder.allow_raw_messages();
+    self.allow_raw_messages = true;
+    self
+  }
+
+  /// Set the minimum interval between commands to the same device actuator, to avoid mechanical
+  /// stress from rapid toggling. Values that arrive before the interval has elapsed are held back,
+  /// and the last of them is sent once it has. Stop values (0) always go through right away.
+  /// Devices can override this via the `command-debounce-ms` field of their user device
+  /// configuration.
+  pub fn device_command_debounce(&mut self, debounce: Duration) -> &mut Self {
+    self
+      .device_manager_builder
+      .device_command_debounce(debounce);
+    self.device_command_debounce = Some(debounce);
+    self
+  }
+
+  /// If true, keep per device counts of commands and the average intensity sent, available via
+  /// [ButtplugServer::command_statistics].
+  pub fn track_command_statistics(&mut self, track: bool) -> &mut Self {
+    self.device_manager_builder.track_command_statistics(track);
+    self.track_command_statistics = track;
+    self
+  }
+
+  /// If true, clear a session's command statistics when its client disconnects, so usage data
+  /// from one session isn't kept around for the next. Other sessions' statistics are left alone,
+  /// and everything is cleared once the last client disconnects, see
+  /// [ButtplugServer::clear_all_command_history].
+  pub fn clear_history_on_disconnect(&mut self, clear: bool) -> &mut Self {
+    self.clear_history_on_disconnect = clear;
+    self
+  }
+
+  /// When a device disconnects unexpectedly, wait this long and then scan for it again. Devices
+  /// that can't be found again are reported on [ButtplugServer::device_reconnect_failed_stream].
+  /// If this is not called, disconnected devices stay disconnected until the client scans again.
+  pub fn device_reconnect_delay(&mut self, delay: Duration) -> &mut Self {
+    self.device_manager_builder.device_reconnect_delay(delay);
+    self.device_reconnect_delay = Some(delay);
+    self
+  }
+
+  /// Number of times to try reconnecting a device before giving up, if
+  /// [ButtplugServerBuilder::device_reconnect_delay] is set.
+  pub fn device_max_reconnect_attempts(&mut self, attempts: u32) -> &mut Self {
+    self
+      .device_manager_builder
+      .device_max_reconnect_attempts(attempts);
+    self.device_max_reconnect_attempts = Some(attempts);
+    self
+  }
+
+  /// Disconnect devices that haven't been successfully communicated with in this long, see
+  /// [ButtplugServer::device_last_seen]. Meant for devices that go out of range without reporting a
+  /// disconnect, so it should be longer than the gaps between commands or notifications.
+  pub fn device_stale_timeout(&mut self, timeout: Duration) -> &mut Self {
+    self.device_manager_builder.device_stale_timeout(timeout);
+    self.device_stale_timeout = Some(timeout);
+    self
+  }
+
+  /// Give up on comm managers that take longer than this to initialize, logging a warning and
+  /// starting the server without them.
+  #[cfg(feature = "tokio-runtime")]
+  pub fn comm_manager_init_timeout(&mut self, timeout: Duration) -> &mut Self {
+    self
+      .device_manager_builder
+      .comm_manager_init_timeout(timeout);
+    self.comm_manager_init_timeout = Some(timeout);
+    self
+  }
+
+  /// Connect at most this many devices at once, for shared deployments where one client shouldn't
+  /// be able to tie up every device in range. Devices found while at the limit are ignored and
+  /// reported on [ButtplugServer::device_limit_reached_stream], until a device disconnects.
+  pub fn max_devices(&mut self, max_devices: u32) -> &mut Self {
+    self.device_manager_builder.max_devices(max_devices);
+    self.max_devices = Some(max_devices);
+    self
+  }
+
+  /// How to resolve contradictory commands sent to the same device actuator by different clients,
+  /// see [CommandConflictPolicy]. Defaults to [CommandConflictPolicy::LastWins], which sends every
+  /// command as it arrives.
+  pub fn command_conflict_policy(&mut self, policy: CommandConflictPolicy) -> &mut Self {
+    self.device_manager_builder.command_conflict_policy(policy);
+    self.command_conflict_policy = policy;
+    self
+  }
+
+  /// Report devices on [ButtplugServer::device_unstable_stream] once they've had more than
+  /// `threshold` communication errors since connecting, see
+  /// [ButtplugServer::device_communication_error_count]. Flaky connections often show up as
+  /// failed writes well before the device drops.
+  pub fn device_error_threshold(&mut self, threshold: u64) -> &mut Self {
+    self
+      .device_manager_builder
+      .device_error_threshold(threshold);
+    self.device_error_threshold = Some(threshold);
+    self
+  }
+
+  /// Keep the last `size` server events, so subscribers that start late can catch up using
+  /// [ButtplugServer::event_stream_since]. Off (0) by default.
+  pub fn event_buffer_size(&mut self, size: usize) -> &mut Self {
+    self.event_buffer_size = size;
+    self
+  }
+
+  /// If true, keep a log of device connections, client messages, and errors for each client
+  /// session, available via [ButtplugServer::export_session_log]. Logs of the last
+  /// [MAX_SESSION_LOGS] sessions are kept, each holding at most [MAX_SESSION_LOG_EVENTS] events.
+  pub fn record_session(&mut self, record: bool) -> &mut Self {
+    self.record_session = record;
+    self
+  }
+
+  /// If true, start scanning for devices as soon as the first client completes the
+  /// [RequestServerInfo](message::RequestServerInfo) handshake, so kiosk style setups don't need
+  /// the client to send [StartScanning]. Later handshakes, such as reconnects, don't start it
+  /// again. Scanning still stops when the client disconnects.
+  pub fn auto_start_scanning(&mut self, auto_start: bool) -> &mut Self {
+    self.auto_start_scanning = auto_start;
+    self
+  }
+
+  /// Stop scans started by [ButtplugServerBuilder::auto_start_scanning] after this long. If this is
+  /// not called, they run until the client stops them or disconnects. If the client sends its own
+  /// [StartScanning] before then, the scan is left to the client.
+  pub fn auto_scan_duration(&mut self, duration: Duration) -> &mut Self {
+    self.auto_scan_duration = Some(duration);
+    self
+  }
+
+  /// Let [ButtplugServer::battery_level] answer from the last reading of a device, if it's younger
+  /// than `ttl`, instead of querying the device, since battery reads are a full round trip. If
+  /// this is not called, every call queries the device.
+  pub fn battery_cache_ttl(&mut self, ttl: Duration) -> &mut Self {
+    self.battery_cache_ttl = Some(ttl);
     self
   }
 
@@ -308,15 +820,87 @@ impl ButtplugServerBuilder {
       );
     }
 
+    let config = ButtplugServerConfig {
+      name: self.name.clone(),
+      max_ping_time: self.max_ping_time,
+      custom_device_configuration_json: self
+        .device_configuration_json
+        .clone()
+        .filter(|config_json| config_json != DEVICE_CONFIGURATION_JSON),
+      user_device_configuration_json: self.user_device_configuration_json.clone(),
+      allow_raw_messages: self.allow_raw_messages,
+      skip_default_protocols: self.skip_default_protocols,
+      allowed_addresses: self.allowed_addresses.clone(),
+      denied_addresses: self.denied_addresses.clone(),
+      reserved_indexes: self.reserved_indexes.clone(),
+      pretty_print_messages: self.pretty_print_messages,
+      client_idle_timeout: self.client_idle_timeout,
+      client_rate_limit: self.client_rate_limit,
+      task_watchdog_timeout: self.task_watchdog_timeout,
+      device_command_debounce: self.device_command_debounce,
+      track_command_statistics: self.track_command_statistics,
+      clear_history_on_disconnect: self.clear_history_on_disconnect,
+      device_reconnect_delay: self.device_reconnect_delay,
+      device_max_reconnect_attempts: self.device_max_reconnect_attempts,
+      device_stale_timeout: self.device_stale_timeout,
+      comm_manager_init_timeout: self.comm_manager_init_timeout,
+      max_devices: self.max_devices,
+      command_conflict_policy: self.command_conflict_policy,
+      device_error_threshold: self.device_error_threshold,
+      tls_config: self.tls_config.clone(),
+      event_buffer_size: self.event_buffer_size,
+      auto_start_scanning: self.auto_start_scanning,
+      auto_scan_duration: self.auto_scan_duration,
+      record_session: self.record_session,
+      battery_cache_ttl: self.battery_cache_ttl,
+    };
+
     // Assuming everything passed, return the server.
-    Ok(ButtplugServer {
-      server_name: self.name.clone(),
+    let server = ButtplugServer {
+      server_name: RwLock::new(self.name.clone()),
       max_ping_time: ping_time,
       device_manager,
       ping_timer,
       connected,
+      client_spec_versions: Arc::new(DashMap::new()),
       output_sender,
-    })
+      active_command_count: Arc::new(AtomicUsize::new(0)),
+      message_count: Arc::new(AtomicU64::new(0)),
+      error_count: Arc::new(AtomicU64::new(0)),
+      started_at: RwLock::new(Instant::now()),
+      pretty_print_messages: self.pretty_print_messages,
+      client_idle_timeout: self.client_idle_timeout,
+      client_rate_limit: self.client_rate_limit,
+      task_watchdog_timeout: self.task_watchdog_timeout,
+      message_transformers: self.message_transformers.clone(),
+      tls_config: self.tls_config.clone(),
+      device_callbacks: Arc::new(DeviceCallbacks::default()),
+      error_callbacks: Arc::new(RwLock::new(vec![])),
+      device_error_callbacks: Arc::new(RwLock::new(vec![])),
+      event_buffer: (self.event_buffer_size > 0)
+        .then(|| Arc::new(EventBuffer::new(self.event_buffer_size))),
+      auto_start_scanning: self.auto_start_scanning,
+      auto_scan_duration: self.auto_scan_duration,
+      auto_scan_started: Arc::new(AtomicBool::new(false)),
+      auto_scan_timer: Arc::new(Mutex::new(None)),
+      battery_cache_ttl: self.battery_cache_ttl,
+      clear_history_on_disconnect: self.clear_history_on_disconnect,
+      session_log: self
+        .record_session
+        .then(|| Arc::new(SessionLogRecorder::default())),
+      config,
+    };
+    if let Some(event_buffer) = &server.event_buffer {
+      async_manager::spawn(event_buffer.clone().record(server.event_stream()));
+    }
+    if let Some(session_log) = &server.session_log {
+      async_manager::spawn(
+        session_log
+          .clone()
+          .record_server_events(server.event_stream()),
+      );
+    }
+    Ok(server)
   }
 }
 
@@ -324,8 +908,9 @@ impl ButtplugServerBuilder {
 /// communication.
 pub struct ButtplugServer {
   /// The name of the server, which is relayed to the client on connection (mostly for
-  /// confirmation in UI dialogs)
-  server_name: String,
+  /// confirmation in UI dialogs). Can be changed at runtime, see
+  /// [ButtplugServer::set_server_name].
+  server_name: RwLock<String>,
   /// The maximum ping time, in milliseconds, for the server. If the server does not receive a
   /// [Ping](crate::core::messages::Ping) message in this amount of time after the handshake has
   /// succeeded, the server will automatically disconnect. If this is not called, the ping timer
@@ -340,15 +925,61 @@ pub struct ButtplugServer {
   device_manager: Arc<ServerDeviceManager>,
   /// If true, client is currently connected to server
   connected: Arc<AtomicBool>,
+  /// Spec version each connected client session negotiated in its handshake, keyed by session
+  /// id.
+  client_spec_versions: Arc<DashMap<u64, u32>>,
   /// Broadcaster for server events. Receivers for this are handed out through the
   /// [ButtplugServer::event_stream()] method.
   output_sender: broadcast::Sender<ButtplugServerMessage>,
+  /// If true, connectors attached via [ButtplugRemoteServer] pretty print serialized messages.
+  pretty_print_messages: bool,
+  /// Number of [ButtplugServer::parse_message] futures that have not finished yet.
+  active_command_count: Arc<AtomicUsize>,
+  /// Number of client messages handled, and how many of those got an error reply.
+  message_count: Arc<AtomicU64>,
+  error_count: Arc<AtomicU64>,
+  /// When the server was built, or its metrics last reset, for [ButtplugServer::uptime].
+  started_at: RwLock<Instant>,
+  /// If set, remote servers disconnect clients that haven't sent a message in this long.
+  client_idle_timeout: Option<Duration>,
+  /// If set, remote servers drop client messages over this count per time window.
+  client_rate_limit: Option<(u32, Duration)>,
+  task_watchdog_timeout: Option<Duration>,
+  /// Applied to messages from remote clients, in order, before they're validated.
+  message_transformers: Vec<Arc<dyn MessageTransformer>>,
+  /// If set, passed to the connectors of remote servers.
+  tls_config: Option<TlsConfig>,
+  /// Callbacks for device connection and disconnection.
+  device_callbacks: Arc<DeviceCallbacks>,
+  /// Callbacks registered via [ButtplugServer::on_error].
+  error_callbacks: Arc<RwLock<Vec<ErrorCallback>>>,
+  /// Error callbacks of handlers registered via [ButtplugServer::register_device_event_handler].
+  device_error_callbacks: Arc<RwLock<Vec<DeviceErrorCallback>>>,
+  /// Recent events, if buffering is on.
+  event_buffer: Option<Arc<EventBuffer>>,
+  /// If true, start scanning as soon as a client completes the handshake.
+  auto_start_scanning: bool,
+  /// If set, automatically started scans are stopped after this long.
+  auto_scan_duration: Option<Duration>,
+  /// Set once the automatic scan has been started, so it only happens for the first handshake.
+  auto_scan_started: Arc<AtomicBool>,
+  /// Cancels the [ButtplugServerBuilder::auto_scan_duration] timer, once the client starts a scan
+  /// itself.
+  auto_scan_timer: Arc<Mutex<Option<CancellationToken>>>,
+  /// How long [ButtplugServer::battery_level] can use a cached reading for.
+  battery_cache_ttl: Option<Duration>,
+  /// Log of the current session, if recording is on.
+  session_log: Option<Arc<SessionLogRecorder>>,
+  /// If true, command statistics are cleared when the client disconnects.
+  clear_history_on_disconnect: bool,
+  /// Settings the server was built with, see [ButtplugServer::export_config].
+  config: ButtplugServerConfig,
 }
 
 impl std::fmt::Debug for ButtplugServer {
   fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
     f.debug_struct("ButtplugServer")
-      .field("server_name", &self.server_name)
+      .field("server_name", &self.server_name())
       .field("max_ping_time", &self.max_ping_time)
       .field("connected", &self.connected)
       .finish()
@@ -369,19 +1000,773 @@ impl ButtplugServer {
   /// Retreive an async stream of ButtplugServerMessages. This is how the server sends out
   /// non-query-related updates to the system, including information on devices being added/removed,
   /// client disconnection, etc...
+  ///
+  /// If the stream isn't read fast enough to keep up, events are skipped and a
+  /// [LaggedEvents](crate::core::message::LaggedEvents) message with the number skipped is emitted
+  /// in their place, so consumers can resync (e.g. by requesting the device list again).
   pub fn event_stream(&self) -> impl Stream<Item = ButtplugServerMessage> {
     // Unlike the client API, we can expect anyone using the server to pin this
     // themselves.
-    let server_receiver = convert_broadcast_receiver_to_stream(self.output_sender.subscribe());
+    let server_receiver = convert_broadcast_receiver_to_stream_with_lag_marker(
+      self.output_sender.subscribe(),
+      |count| Some(message::LaggedEvents::new(count).into()),
+    );
     let device_receiver = self.device_manager.event_stream();
     device_receiver.merge(server_receiver)
   }
 
+  /// Send an event to [ButtplugServer::event_stream] listeners as if it came from the server, for
+  /// testing event consumers without devices. The event isn't checked, and devices it refers to
+  /// don't need to exist.
+  #[cfg(any(test, feature = "testing"))]
+  pub fn simulate_device_event(&self, event: ButtplugServerMessage) {
+    if self.output_sender.send(event).is_err() {
+      debug!("No listeners for simulated server event.");
+    }
+  }
+
+  /// Name of the server, as sent to clients in [ServerInfo](crate::core::message::ServerInfo).
+  pub fn server_name(&self) -> String {
+    self.server_name.read().expect("Lock poisoned").clone()
+  }
+
+  /// Change the name of the server. Takes effect for any
+  /// [RequestServerInfo](crate::core::message::RequestServerInfo) received after this is called,
+  /// clients that have already connected will not be notified.
+  pub fn set_server_name(&self, name: &str) {
+    *self.server_name.write().expect("Lock poisoned") = name.to_owned();
+  }
+
+  /// Returns the settings this server was built with, in a form that can be serialized for logging
+  /// or to reproduce the server later. Reflects the current server name if it was changed via
+  /// [ButtplugServer::set_server_name].
+  pub fn export_config(&self) -> ButtplugServerConfig {
+    ButtplugServerConfig {
+      name: self.server_name(),
+      ..self.config.clone()
+    }
+  }
+
   /// Returns a references to the internal device manager, for handling configuration.
   pub fn device_manager(&self) -> Arc<ServerDeviceManager> {
     self.device_manager.clone()
   }
 
+  /// Discard commands queued for the device at the given index, returning how many were
+  /// discarded. The device stays connected and keeps whatever state it was last put in, so follow
+  /// this with [ButtplugServer::stop_device] for a clean reset.
+  pub fn flush_device_queue(&self, device_index: u32) -> usize {
+    self.device_manager.flush_device_queue(device_index)
+  }
+
+  /// Emergency stop. Stops every device without going through [ButtplugServer::parse_message], and
+  /// drops commands queued for each device first, so the stop is sent straight away. Waits up to
+  /// `stop_timeout` for each device, and reports the ones that didn't stop in time. Works whether
+  /// or not a client is connected. See [ServerDeviceManager::force_stop_all_devices].
+  pub async fn force_stop_all_devices(&self, stop_timeout: Duration) -> Result<(), ButtplugError> {
+    self
+      .device_manager
+      .force_stop_all_devices(stop_timeout)
+      .await
+  }
+
+  /// Stop every device and any scan in progress, in one call. Both are sent at once, and both are
+  /// waited on even if one fails, the first error is returned. Like
+  /// [ButtplugServer::force_stop_all_devices], this works whether or not a client is connected.
+  pub async fn global_stop(&self) -> Result<(), ButtplugError> {
+    let (stop_devices, stop_scanning) = future::join(
+      self
+        .device_manager
+        .parse_message(StopAllDevices::default().into()),
+      self
+        .device_manager
+        .parse_message(StopScanning::default().into()),
+    )
+    .await;
+    stop_devices.and(stop_scanning).map(|_| ())
+  }
+
+  /// State of each comm manager, for diagnosing missing devices, see
+  /// [ServerDeviceManager::comm_manager_status].
+  pub async fn comm_manager_status(&self) -> Result<Vec<CommManagerStatus>, ButtplugError> {
+    self.device_manager.comm_manager_status().await
+  }
+
+  /// Add a device with no hardware behind it, returning its index, see
+  /// [ServerDeviceManager::add_virtual_device].
+  pub fn add_virtual_device(&self, config: VirtualDeviceConfig) -> Result<u32, ButtplugError> {
+    self.device_manager.add_virtual_device(config)
+  }
+
+  /// Check whether the device at the given index supports a message before sending it, see
+  /// [ServerDeviceManager::device_supports_message].
+  pub fn device_supports_message(&self, device_index: u32, msg: &ButtplugClientMessage) -> bool {
+    self
+      .device_manager
+      .device_supports_message(device_index, msg)
+  }
+
+  /// Add a comm manager while the server is running. See
+  /// [ServerDeviceManager::add_comm_manager].
+  pub async fn add_comm_manager<T>(&self, builder: T) -> Result<(), ButtplugServerError>
+  where
+    T: HardwareCommunicationManagerBuilder + 'static,
+  {
+    self.device_manager.add_comm_manager(builder).await
+  }
+
+  /// Rerun the initialization handshake for an already connected device, updating its capability
+  /// information in place. Emits the updated info on [ButtplugServer::device_update_stream].
+  ///
+  /// Note that clients are not notified of the new capabilities, they'll need to request the
+  /// device list again.
+  pub async fn query_device(&self, index: u32) -> Result<ServerDeviceInfo, ButtplugError> {
+    self.device_manager.query_device(index).await
+  }
+
+  /// Stop several devices at once. The stop commands are all sent in parallel, so there's no gap
+  /// between devices stopping. Waits for every stop to finish, then returns the first error, if
+  /// any.
+  pub async fn batch_stop(&self, indices: &[u32]) -> Result<(), ButtplugError> {
+    future::join_all(indices.iter().map(|index| self.stop_device(*index)))
+      .await
+      .into_iter()
+      .collect()
+  }
+
+  /// Stop the device at the given index and block further client commands to it (other than
+  /// stopping it), e.g. while it's being maintained. Commands fail with
+  /// [ButtplugDeviceError::DeviceDisabled](crate::core::errors::ButtplugDeviceError::DeviceDisabled).
+  /// The device stays connected.
+  pub async fn disable_device(&self, index: u32) -> Result<(), ButtplugError> {
+    self.device_manager.disable_device(index).await
+  }
+
+  /// Allow client commands to a device blocked with [ButtplugServer::disable_device] again.
+  pub fn enable_device(&self, index: u32) -> Result<(), ButtplugError> {
+    self.device_manager.enable_device(index)
+  }
+
+  /// Run the calibration sequence for the device at the given index, for devices that need it
+  /// (like setting the zero point of a linear actuator). Fails for devices whose protocol has no
+  /// calibration sequence.
+  pub async fn calibrate_device(&self, index: u32) -> Result<CalibrationResult, ButtplugError> {
+    self.device_manager.calibrate_device(index).await
+  }
+
+  /// Stop the device at the given index, the same as a client sending a
+  /// [StopDeviceCmd](message::StopDeviceCmd). Doesn't require a client to be connected.
+  pub async fn stop_device(&self, index: u32) -> Result<(), ButtplugError> {
+    self
+      .device_manager
+      .parse_message(message::StopDeviceCmd::new(index).into())
+      .await
+      .map(|_| ())
+  }
+
+  /// Number of features of the given type on a device, for building commands that address all of
+  /// them.
+  fn feature_count(
+    &self,
+    index: u32,
+    message_type: message::ButtplugDeviceMessageType,
+    features: impl FnOnce(&ServerDeviceMessageAttributes) -> Option<usize>,
+  ) -> Result<u32, ButtplugError> {
+    let info = self
+      .device_manager
+      .device_info(index)
+      .ok_or(ButtplugDeviceError::DeviceNotAvailable(index))?;
+    features(info.message_attributes())
+      .map(|count| count as u32)
+      .ok_or_else(|| ButtplugDeviceError::MessageNotSupported(message_type).into())
+  }
+
+  async fn send_device_command<T>(&self, msg: T) -> Result<(), ButtplugError>
+  where
+    T: message::ButtplugMessageValidator + Into<ButtplugClientMessage>,
+  {
+    msg.is_valid()?;
+    self
+      .device_manager
+      .parse_message(msg.into())
+      .await
+      .map(|_| ())
+  }
+
+  /// Set every vibrator on the device at the given index to `speed` (0.0-1.0). Shortcut for
+  /// sending a [VibrateCmd](message::VibrateCmd), without requiring a client to be connected.
+  pub async fn vibrate(&self, device_index: u32, speed: f64) -> Result<(), ButtplugError> {
+    self
+      .send_device_command(message::SingleMotorVibrateCmd::new(device_index, speed))
+      .await
+  }
+
+  /// Set the vibration speed of several devices at once, given as `(device_index, speed)` pairs.
+  /// Like [ButtplugServer::batch_stop], the commands are sent in parallel to avoid timing gaps
+  /// between devices, but a result is returned for each command so partial failures are visible.
+  pub async fn bulk_vibrate(&self, commands: &[(u32, f64)]) -> Vec<Result<(), ButtplugError>> {
+    future::join_all(
+      commands
+        .iter()
+        .map(|(device_index, speed)| self.vibrate(*device_index, *speed)),
+    )
+    .await
+  }
+
+  /// Set every rotator on the device at the given index to `speed` (0.0-1.0) in the given
+  /// direction. Shortcut for sending a [RotateCmd](message::RotateCmd).
+  pub async fn rotate(
+    &self,
+    device_index: u32,
+    speed: f64,
+    clockwise: bool,
+  ) -> Result<(), ButtplugError> {
+    let count = self.feature_count(
+      device_index,
+      message::ButtplugDeviceMessageType::RotateCmd,
+      |attrs| attrs.rotate_cmd().as_ref().map(Vec::len),
+    )?;
+    let rotations = (0..count)
+      .map(|index| message::RotationSubcommand::new(index, speed, clockwise))
+      .collect();
+    self
+      .send_device_command(message::RotateCmd::new(device_index, rotations))
+      .await
+  }
+
+  /// Move every linear actuator on the device at the given index to `position` (0.0-1.0) over
+  /// `duration` milliseconds. Shortcut for sending a [LinearCmd](message::LinearCmd).
+  pub async fn linear(
+    &self,
+    device_index: u32,
+    duration: u32,
+    position: f64,
+  ) -> Result<(), ButtplugError> {
+    let count = self.feature_count(
+      device_index,
+      message::ButtplugDeviceMessageType::LinearCmd,
+      |attrs| attrs.linear_cmd().as_ref().map(Vec::len),
+    )?;
+    let vectors = (0..count)
+      .map(|index| message::VectorSubcommand::new(index, duration, position))
+      .collect();
+    self
+      .send_device_command(message::LinearCmd::new(device_index, vectors))
+      .await
+  }
+
+  /// Put the server back in its just-built state without rebuilding it: disconnects the client,
+  /// stops scanning, stops and disconnects all devices, and clears scan results, pending
+  /// reconnects and command statistics. Useful for kiosk style setups that hand the same server
+  /// to a new user.
+  pub async fn reset(&self) -> Result<(), ButtplugError> {
+    self
+      .disconnect()
+      .await
+      .map_err(|err| err.original_error())?;
+    self.device_manager.reset().await
+  }
+
+  /// Usage statistics for the device at the given index, reset when the device disconnects. Only
+  /// collected if [ButtplugServerBuilder::track_command_statistics] is on.
+  pub fn command_statistics(&self, device_index: u32) -> CommandStatistics {
+    self.device_manager.command_statistics(device_index)
+  }
+
+  /// Forget the usage statistics and latencies collected for a device, see
+  /// [ServerDeviceManager::clear_command_history].
+  pub fn clear_command_history(&self, device_index: u32) {
+    self.device_manager.clear_command_history(device_index)
+  }
+
+  /// Forget the usage statistics and latencies collected for every device, see
+  /// [ServerDeviceManager::clear_all_command_history].
+  pub fn clear_all_command_history(&self) {
+    self.device_manager.clear_all_command_history()
+  }
+
+  /// Time the device at the given index was last successfully communicated with (connected,
+  /// completed a command, or sent a notification), or None if there is no device at that index.
+  pub fn device_last_seen(&self, device_index: u32) -> Option<Instant> {
+    self.device_manager.device_last_seen(device_index)
+  }
+
+  /// Average time recent hardware writes to a device took to be acknowledged, for picking polling
+  /// rates or predicting command latency. See
+  /// [ServerDeviceManager::device_average_latency].
+  pub fn device_average_latency(&self, device_index: u32) -> Option<Duration> {
+    self.device_manager.device_average_latency(device_index)
+  }
+
+  /// Name to show for a device: its user set display name if it has one, otherwise its name from
+  /// the device configuration. None if there is no device at that index.
+  pub fn device_name(&self, device_index: u32) -> Option<String> {
+    self.device_manager.device_info(device_index).map(|info| {
+      info
+        .display_name()
+        .clone()
+        .unwrap_or_else(|| info.name().clone())
+    })
+  }
+
+  /// Like [ButtplugServer::device_name], but always returns something to show, "Unknown Device"
+  /// if there is no device at that index or it has no name.
+  pub fn device_display_name_or_fallback(&self, device_index: u32) -> String {
+    self
+      .device_name(device_index)
+      .filter(|name| !name.is_empty())
+      .unwrap_or_else(|| "Unknown Device".to_owned())
+  }
+
+  /// How long a device has been connected, or None if there is no device at that index. See
+  /// [ServerDeviceManager::device_uptime].
+  pub fn device_uptime(&self, device_index: u32) -> Option<Duration> {
+    self.device_manager.device_uptime(device_index)
+  }
+
+  /// Last battery level (0.0-1.0) read from a device, and when it was read. Never queries the
+  /// device. None if there is no device at that index, or it hasn't been read yet. See
+  /// [ServerDeviceManager::device_battery_level_cache].
+  pub fn device_battery_level_cache(&self, device_index: u32) -> Option<(f64, Instant)> {
+    self.device_manager.device_battery_level_cache(device_index)
+  }
+
+  /// Battery level (0.0-1.0) of a device. Uses the last reading if it's younger than
+  /// [ButtplugServerBuilder::battery_cache_ttl], otherwise queries the device.
+  pub async fn battery_level(&self, device_index: u32) -> Result<f64, ButtplugError> {
+    if let (Some(ttl), Some((battery_level, read_at))) = (
+      self.battery_cache_ttl,
+      self.device_battery_level_cache(device_index),
+    ) {
+      if read_at.elapsed() < ttl {
+        return Ok(battery_level);
+      }
+    }
+    match self
+      .device_manager
+      .parse_message(message::BatteryLevelCmd::new(device_index).into())
+      .await?
+    {
+      ButtplugServerMessage::BatteryLevelReading(reading) => Ok(reading.battery_level()),
+      _ => {
+        Err(ButtplugDeviceError::ProtocolSensorNotSupported(message::SensorType::Battery).into())
+      }
+    }
+  }
+
+  /// Name and version of the protocol implementation running a device, e.g. `lovense/1.0`. None if
+  /// there is no device at that index. See [ServerDeviceManager::device_protocol_version].
+  pub fn device_protocol_version(&self, device_index: u32) -> Option<String> {
+    self.device_manager.device_protocol_version(device_index)
+  }
+
+  /// Highest value clients should send to a scalar actuator on a device, which may be below 1.0 if
+  /// the device's protocol limits it. None if there's no such device or actuator.
+  pub fn max_actuator_value(&self, device_index: u32, actuator_index: u32) -> Option<f64> {
+    self
+      .device_manager
+      .max_actuator_value(device_index, actuator_index)
+  }
+
+  /// Number of vibration actuators (ScalarCmd actuators of type Vibrate) on a device, as listed in
+  /// its DeviceAdded message. None if there is no device at that index.
+  pub fn device_actuator_count(&self, device_index: u32) -> Option<usize> {
+    self
+      .device_manager
+      .device_message_attributes(device_index)
+      .map(|attrs| {
+        attrs.scalar_cmd().as_ref().map_or(0, |scalars| {
+          scalars
+            .iter()
+            .filter(|attr| *attr.actuator_type() == ActuatorType::Vibrate)
+            .count()
+        })
+      })
+  }
+
+  /// Number of RotateCmd actuators on a device, or None if there is no device at that index.
+  pub fn rotation_actuator_count(&self, device_index: u32) -> Option<usize> {
+    self
+      .device_manager
+      .device_message_attributes(device_index)
+      .map(|attrs| attrs.rotate_cmd().as_ref().map_or(0, Vec::len))
+  }
+
+  /// Number of LinearCmd actuators on a device, or None if there is no device at that index.
+  pub fn linear_actuator_count(&self, device_index: u32) -> Option<usize> {
+    self
+      .device_manager
+      .device_message_attributes(device_index)
+      .map(|attrs| attrs.linear_cmd().as_ref().map_or(0, Vec::len))
+  }
+
+  /// Number of sensors that can be read with SensorReadCmd on a device, or None if there is no
+  /// device at that index.
+  pub fn sensor_count(&self, device_index: u32) -> Option<usize> {
+    self
+      .device_manager
+      .device_message_attributes(device_index)
+      .map(|attrs| attrs.sensor_read_cmd().as_ref().map_or(0, Vec::len))
+  }
+
+  /// Tags attached to a device with [ButtplugServer::set_device_tag], or None if there is no
+  /// device at that index.
+  pub fn device_tags(&self, device_index: u32) -> Option<HashMap<String, String>> {
+    self.device_manager.device_tags(device_index)
+  }
+
+  /// Attach a key-value tag to a device, see [ServerDeviceManager::set_device_tag].
+  pub fn set_device_tag(
+    &self,
+    device_index: u32,
+    key: String,
+    value: String,
+  ) -> Result<(), ButtplugError> {
+    self.device_manager.set_device_tag(device_index, key, value)
+  }
+
+  /// Index of the connected device with the given hardware address, e.g. for mapping addresses
+  /// saved by a client back to device indexes after reconnecting. Scans the whole device list.
+  pub fn device_index_for_address(&self, address: &str) -> Option<u32> {
+    self.device_manager.device_index_for_address(address)
+  }
+
+  /// Device protocols compiled in (or added via [ButtplugServerBuilder::protocol_factory]), with
+  /// the devices and messages their configurations support.
+  pub fn list_protocols(&self) -> Vec<ProtocolInfo> {
+    self.device_manager.list_protocols()
+  }
+
+  /// Stream of device indexes and updated info, for devices requeried via
+  /// [ButtplugServer::query_device] or disabled/enabled via [ButtplugServer::disable_device] and
+  /// [ButtplugServer::enable_device].
+  pub fn device_update_stream(&self) -> impl Stream<Item = (u32, ServerDeviceInfo)> {
+    self.device_manager.device_update_stream()
+  }
+
+  /// Try to reconnect every device that disconnected and hasn't come back, e.g. after a Bluetooth
+  /// adapter reset, see [ServerDeviceManager::reconnect_all_devices].
+  pub async fn reconnect_all_devices(&self) -> Vec<(u32, Result<(), ButtplugError>)> {
+    self.device_manager.reconnect_all_devices().await
+  }
+
+  /// Stream of indexes of devices that disconnected and couldn't be reconnected, see
+  /// [ButtplugServerBuilder::device_reconnect_delay].
+  pub fn device_reconnect_failed_stream(&self) -> impl Stream<Item = u32> {
+    self.device_manager.device_reconnect_failed_stream()
+  }
+
+  /// Stream with an item each time a device is ignored because the limit set by
+  /// [ButtplugServerBuilder::max_devices] was reached.
+  pub fn device_limit_reached_stream(&self) -> impl Stream<Item = ()> {
+    self.device_manager.device_limit_reached_stream()
+  }
+
+  /// Stream of device indexes and error counts for devices that went over the threshold set by
+  /// [ButtplugServerBuilder::device_error_threshold].
+  pub fn device_unstable_stream(&self) -> impl Stream<Item = (u32, u64)> {
+    self.device_manager.device_unstable_stream()
+  }
+
+  /// True if the device at the given index is still running a command on at least one of its
+  /// actuators, see [ServerDeviceManager::device_is_busy].
+  pub fn device_is_busy(&self, device_index: u32) -> bool {
+    self.device_manager.device_is_busy(device_index)
+  }
+
+  /// Number of communication errors the device at the given index has had since it connected,
+  /// see [ServerDeviceManager::device_communication_error_count].
+  pub fn device_communication_error_count(&self, device_index: u32) -> u64 {
+    self
+      .device_manager
+      .device_communication_error_count(device_index)
+  }
+
+  /// Call `callback` with the info of each device that connects from now on. This is a simpler
+  /// alternative to filtering [ButtplugServer::event_stream] for DeviceAdded messages.
+  ///
+  /// Callbacks are run as separate tasks, so they may run out of order relative to each other.
+  pub fn on_device_added<F>(&self, callback: F)
+  where
+    F: Fn(ServerDeviceInfo) + Send + Sync + 'static,
+  {
+    self
+      .device_callbacks
+      .added
+      .write()
+      .expect("Lock poisoned")
+      .push(Arc::new(callback));
+    self.start_device_callback_dispatch();
+  }
+
+  /// Call `callback` with the index of each device that disconnects from now on.
+  ///
+  /// Callbacks are run as separate tasks, so they may run out of order relative to each other.
+  pub fn on_device_removed<F>(&self, callback: F)
+  where
+    F: Fn(u32) + Send + Sync + 'static,
+  {
+    self
+      .device_callbacks
+      .removed
+      .write()
+      .expect("Lock poisoned")
+      .push(Arc::new(callback));
+    self.start_device_callback_dispatch();
+  }
+
+  /// Call `callback` with each error returned by a device command from now on, in addition to the
+  /// error being returned to whoever sent the command. Useful when commands are sent without
+  /// waiting on the result.
+  ///
+  /// Callbacks are run as separate tasks, so they may run out of order relative to each other.
+  pub fn on_error<F>(&self, callback: F)
+  where
+    F: Fn(ButtplugError) + Send + Sync + 'static,
+  {
+    self
+      .error_callbacks
+      .write()
+      .expect("Lock poisoned")
+      .push(Arc::new(callback));
+  }
+
+  /// Call `handler` for each device that connects or disconnects, and each device command that
+  /// fails, from now on. Any number of handlers can be registered, and all of them are called for
+  /// every event.
+  ///
+  /// Like the other callbacks, handler calls are run as separate tasks, so they may run out of
+  /// order relative to each other.
+  pub fn register_device_event_handler(&self, handler: Arc<dyn DeviceEventHandler + Send + Sync>) {
+    let added_handler = handler.clone();
+    self.on_device_added(move |info| added_handler.on_added(info));
+    let removed_handler = handler.clone();
+    self.on_device_removed(move |index| removed_handler.on_removed(index));
+    self
+      .device_error_callbacks
+      .write()
+      .expect("Lock poisoned")
+      .push(Arc::new(move |index, err| handler.on_error(index, err)));
+  }
+
+  /// Call `callback` with each device command the device accepts, see
+  /// [ServerDeviceManager::on_device_command].
+  pub fn on_device_command<F>(&self, callback: F)
+  where
+    F: Fn(u32, &ButtplugClientMessage) + Send + Sync + 'static,
+  {
+    self.device_manager.on_device_command(callback);
+  }
+
+  /// Start the task serving device callbacks, if it isn't running yet.
+  fn start_device_callback_dispatch(&self) {
+    if self
+      .device_callbacks
+      .dispatching
+      .swap(true, Ordering::SeqCst)
+    {
+      return;
+    }
+    let callbacks = self.device_callbacks.clone();
+    // Only hold a weak reference, so this task doesn't keep the device manager alive.
+    let device_manager = Arc::downgrade(&self.device_manager);
+    let mut events = Box::pin(self.device_manager.event_stream());
+    async_manager::spawn(async move {
+      while let Some(event) = events.next().await {
+        match event {
+          ButtplugServerMessage::DeviceAdded(added) => {
+            let info = if let Some(device_manager) = device_manager.upgrade() {
+              device_manager.device_info(added.device_index())
+            } else {
+              break;
+            };
+            // The device may have already disconnected, in which case there's nothing to report.
+            if let Some(info) = info {
+              for callback in callbacks.added.read().expect("Lock poisoned").iter() {
+                let callback = callback.clone();
+                let info = info.clone();
+                async_manager::spawn(async move { callback(info) });
+              }
+            }
+          }
+          ButtplugServerMessage::DeviceRemoved(removed) => {
+            let device_index = removed.device_index();
+            for callback in callbacks.removed.read().expect("Lock poisoned").iter() {
+              let callback = callback.clone();
+              async_manager::spawn(async move { callback(device_index) });
+            }
+          }
+          _ => {}
+        }
+      }
+    });
+  }
+
+  /// Start scanning, then call `callback` once with the info of every device that connected during
+  /// the scan, when it finishes. For one-shot scans, where streaming events isn't needed. Devices
+  /// found but still connecting when scanning finishes aren't included.
+  ///
+  /// If scanning can't be started, or the server shuts down before scanning finishes, `callback`
+  /// is dropped without being called.
+  pub fn on_scan_complete<F>(&self, callback: F)
+  where
+    F: FnOnce(Vec<ServerDeviceInfo>) + Send + 'static,
+  {
+    // Only hold a weak reference, so this task doesn't keep the device manager alive.
+    let device_manager = Arc::downgrade(&self.device_manager);
+    // Subscribed before scanning starts, so devices found right away aren't missed.
+    let mut events = Box::pin(self.device_manager.event_stream());
+    let start_scanning = self
+      .device_manager
+      .parse_message(message::StartScanning::default().into());
+    async_manager::spawn(async move {
+      if let Err(err) = start_scanning.await {
+        warn!("Cannot start scan for scan complete callback: {:?}", err);
+        return;
+      }
+      let mut devices = vec![];
+      while let Some(event) = events.next().await {
+        let Some(device_manager) = device_manager.upgrade() else {
+          break;
+        };
+        match event {
+          ButtplugServerMessage::DeviceAdded(added) => {
+            // The device may have already disconnected, in which case there's nothing to report.
+            if let Some(info) = device_manager.device_info(added.device_index()) {
+              devices.push(info);
+            }
+          }
+          ButtplugServerMessage::ScanningFinished(_) => {
+            // Shutting down stops scanning too, which doesn't count as the scan completing.
+            if device_manager.is_running() {
+              callback(devices);
+            }
+            return;
+          }
+          _ => {}
+        }
+      }
+      debug!("Server went away before scanning finished, dropping scan complete callback.");
+    });
+  }
+
+  /// Returns devices seen during the last scan that haven't been connected, including those
+  /// filtered out by allow/deny lists or lacking a matching protocol.
+  pub fn scan_results(&self) -> Vec<DiscoveredDevice> {
+    self.device_manager.scan_results()
+  }
+
+  /// Number of devices found so far by the current or last scan that haven't been connected, see
+  /// [ServerDeviceManager::pending_scan_results].
+  pub fn pending_scan_results(&self) -> usize {
+    self.device_manager.pending_scan_results()
+  }
+
+  /// How long the current device scan has been running, or None if there isn't one. Along with
+  /// [ButtplugServerBuilder::auto_scan_duration], this can be used to show a countdown. See
+  /// [ServerDeviceManager::active_scan_duration].
+  pub fn active_scan_duration(&self) -> Option<Duration> {
+    self.device_manager.active_scan_duration()
+  }
+
+  /// Scan for `duration` without connecting to anything, see
+  /// [ServerDeviceManager::scan_advertisements].
+  pub fn scan_advertisements(&self, duration: Duration) -> impl Stream<Item = AdvertisementRecord> {
+    self.device_manager.scan_advertisements(duration)
+  }
+
+  /// If true, serialized messages sent by connectors for this server are pretty printed.
+  pub fn pretty_print_messages(&self) -> bool {
+    self.pretty_print_messages
+  }
+
+  /// Amount of time a remote client can go without sending a message before being disconnected,
+  /// if set.
+  pub fn client_idle_timeout(&self) -> Option<Duration> {
+    self.client_idle_timeout
+  }
+
+  /// Maximum number of messages, and the time window they're counted over, that a remote client
+  /// can send before messages are dropped, if set.
+  pub fn client_rate_limit(&self) -> Option<(u32, Duration)> {
+    self.client_rate_limit
+  }
+
+  /// Longest a remote client message can take to handle before it's abandoned, if set.
+  pub fn task_watchdog_timeout(&self) -> Option<Duration> {
+    self.task_watchdog_timeout
+  }
+
+  /// TLS settings for remote server connectors, if set with
+  /// [ButtplugServerBuilder::with_tls_config].
+  pub fn tls_config(&self) -> Option<TlsConfig> {
+    self.tls_config.clone()
+  }
+
+  /// Transformers registered via [ButtplugServerBuilder::message_transformer].
+  pub fn message_transformers(&self) -> Vec<Arc<dyn MessageTransformer>> {
+    self.message_transformers.clone()
+  }
+
+  /// Run a client message through all registered transformers, in order.
+  pub fn transform_message(&self, msg: ButtplugClientMessage) -> ButtplugClientMessage {
+    self
+      .message_transformers
+      .iter()
+      .fold(msg, |msg, transformer| transformer.transform_inbound(msg))
+  }
+
+  /// Number of [ButtplugServer::parse_message] calls currently in flight, i.e. whose returned
+  /// futures have been created but have not yet resolved. Useful for comparing load across
+  /// multiple server instances.
+  pub fn active_command_count(&self) -> usize {
+    self.active_command_count.load(Ordering::SeqCst)
+  }
+
+  /// Number of hardware commands queued for devices but not sent yet, see
+  /// [ServerDeviceManager::pending_command_count].
+  pub fn pending_command_count(&self) -> usize {
+    self.device_manager.pending_command_count()
+  }
+
+  /// Wait until no client messages are being handled and no device commands are queued, e.g.
+  /// before shutting down or taking a snapshot. Fails with [ButtplugUnknownError::IdleTimedOut]
+  /// if there is still work in progress after `timeout_duration`.
+  pub async fn wait_for_idle(&self, timeout_duration: Duration) -> Result<(), ButtplugError> {
+    timeout(timeout_duration, async {
+      while self.active_command_count() > 0 || self.pending_command_count() > 0 {
+        sleep(Duration::from_millis(10)).await;
+      }
+    })
+    .await
+    .map_err(|_| ButtplugUnknownError::IdleTimedOut(timeout_duration).into())
+  }
+
+  /// Number of client messages handled by [ButtplugServer::parse_message] since the server was
+  /// built, or [ButtplugServer::reset_metrics] was last called.
+  pub fn message_count(&self) -> u64 {
+    self.message_count.load(Ordering::SeqCst)
+  }
+
+  /// Number of handled client messages that got an error reply.
+  pub fn error_count(&self) -> u64 {
+    self.error_count.load(Ordering::SeqCst)
+  }
+
+  /// Time since the server was built, or [ButtplugServer::reset_metrics] was last called.
+  pub fn uptime(&self) -> Duration {
+    self.started_at.read().expect("Lock poisoned").elapsed()
+  }
+
+  /// Zero [ButtplugServer::message_count] and [ButtplugServer::error_count], and restart
+  /// [ButtplugServer::uptime], e.g. to compare sessions against each other. Clients and devices
+  /// aren't affected.
+  pub fn reset_metrics(&self) {
+    *self.started_at.write().expect("Lock poisoned") = Instant::now();
+    self.message_count.store(0, Ordering::SeqCst);
+    self.error_count.store(0, Ordering::SeqCst);
+  }
+
   /// If true, client is currently connected to the server.
   pub fn connected(&self) -> bool {
     self.connected.load(Ordering::SeqCst)
@@ -389,7 +1774,10 @@ impl ButtplugServer {
 
   /// Disconnects the server from a client, if it is connected.
   pub fn disconnect(&self) -> BoxFuture<Result<(), message::Error>> {
-    debug!("Buttplug Server {} disconnect requested", self.server_name);
+    debug!(
+      "Buttplug Server {} disconnect requested",
+      self.server_name()
+    );
     let ping_timer = self.ping_timer.clone();
     let stop_scanning_fut =
       self.parse_message(ButtplugClientMessage::StopScanning(StopScanning::default()));
@@ -397,8 +1785,21 @@ impl ButtplugServer {
       StopAllDevices::default(),
     ));
     let connected = self.connected.clone();
+    let client_spec_versions = self.client_spec_versions.clone();
+    let session_log = self.session_log.clone();
+    let device_manager = self
+      .clear_history_on_disconnect
+      .then(|| self.device_manager.clone());
     async move {
-      connected.store(false, Ordering::SeqCst);
+      if connected.swap(false, Ordering::SeqCst) {
+        client_spec_versions.clear();
+        if let Some(session_log) = session_log {
+          session_log.end_all_sessions();
+        }
+        if let Some(device_manager) = device_manager {
+          device_manager.clear_all_command_history();
+        }
+      }
       ping_timer.stop_ping_timer().await;
       // Ignore returns here, we just want to stop.
       info!("Server disconnected, stopping device scanning if it was started...");
@@ -415,13 +1816,24 @@ impl ButtplugServer {
   pub fn parse_message(
     &self,
     msg: ButtplugClientMessage,
+  ) -> BoxFuture<'static, Result<ButtplugServerMessage, message::Error>> {
+    self.parse_session_message(LOCAL_SESSION_ID, msg)
+  }
+
+  /// Like [ButtplugServer::parse_message], for a message from the remote client session with the
+  /// given id, so commands from different sessions can be told apart.
+  pub(crate) fn parse_session_message(
+    &self,
+    session_id: u64,
+    msg: ButtplugClientMessage,
   ) -> BoxFuture<'static, Result<ButtplugServerMessage, message::Error>> {
     trace!(
       "Buttplug Server {} received message to client parse: {:?}",
-      self.server_name,
+      self.server_name(),
       msg
     );
     let id = msg.id();
+    self.message_count.fetch_add(1, Ordering::SeqCst);
     if !self.connected() {
       // Check for ping timeout first! There's no way we should've pinged out if
       // we haven't received RequestServerInfo first, but we do want to know if
@@ -439,40 +1851,115 @@ impl ButtplugServer {
       };
       if let Some(mut return_error) = error {
         return_error.set_id(msg.id());
+        self.error_count.fetch_add(1, Ordering::SeqCst);
         return future::ready(Err(return_error)).boxed();
       }
       // If we haven't pinged out and we got an RSI message, fall thru.
+    } else {
+      // Don't accept messages from spec versions newer than the one the client said it speaks.
+      let client_spec_version = self
+        .client_spec_versions
+        .get(&session_id)
+        .map_or(BUTTPLUG_CURRENT_MESSAGE_SPEC_VERSION as u32, |version| {
+          *version
+        });
+      if msg.schema_version() > client_spec_version {
+        let mut return_error =
+          message::Error::from(ButtplugError::from(ButtplugMessageError::VersionError(
+            "Message".to_owned(),
+            format!("{:?}", msg),
+            format!("message spec version {}", client_spec_version),
+          )));
+        return_error.set_id(id);
+        self.error_count.fetch_add(1, Ordering::SeqCst);
+        return future::ready(Err(return_error)).boxed();
+      }
     }
+    let session_log = self
+      .session_log
+      .clone()
+      .map(|session_log| (session_log, msg.clone(), SystemTime::now(), Instant::now()));
     // Produce whatever future is needed to reply to the message, this may be a
     // device command future, or something the server handles. All futures will
     // return Result<ButtplugServerMessage, ButtplugError>, and we'll handle
     // tagging the result with the message id in the future we put out as the
     // return value from this method.
+    if matches!(msg, ButtplugClientMessage::StartScanning(_)) {
+      if let Some(token) = self.auto_scan_timer.lock().expect("Lock poisoned").take() {
+        token.cancel();
+      }
+    }
     let out_fut = if ButtplugDeviceManagerMessageUnion::try_from(msg.clone()).is_ok()
       || ButtplugDeviceCommandMessageUnion::try_from(msg.clone()).is_ok()
     {
-      self.device_manager.parse_message(msg.clone())
+      let device_fut = self
+        .device_manager
+        .parse_session_message(session_id, msg.clone());
+      let error_callbacks = self.error_callbacks.clone();
+      let device_error_callbacks = self.device_error_callbacks.clone();
+      let device_index = ButtplugDeviceCommandMessageUnion::try_from(msg.clone())
+        .ok()
+        .map(|device_msg| device_msg.device_index());
+      async move {
+        let result = device_fut.await;
+        if let Err(err) = &result {
+          for callback in error_callbacks.read().expect("Lock poisoned").iter() {
+            let callback = callback.clone();
+            let err = err.clone();
+            async_manager::spawn(async move { callback(err) });
+          }
+          if let Some(device_index) = device_index {
+            for callback in device_error_callbacks.read().expect("Lock poisoned").iter() {
+              let callback = callback.clone();
+              let err = err.clone();
+              async_manager::spawn(async move { callback(device_index, err) });
+            }
+          }
+        }
+        result
+      }
+      .boxed()
     } else {
       match msg {
-        ButtplugClientMessage::RequestServerInfo(rsi_msg) => self.perform_handshake(rsi_msg),
+        ButtplugClientMessage::RequestServerInfo(rsi_msg) => {
+          self.perform_handshake(session_id, rsi_msg)
+        }
         ButtplugClientMessage::Ping(p) => self.handle_ping(p),
         _ => ButtplugMessageError::UnexpectedMessageType(format!("{:?}", msg)).into(),
       }
     };
+    let command_guard = ActiveCommandGuard::new(&self.active_command_count);
+    let error_count = self.error_count.clone();
     // Simple way to set the ID on the way out. Just rewrap
     // the returned future to make sure it happens.
     async move {
-      out_fut
+      let _command_guard = command_guard;
+      let result = out_fut
         .await
         .map(|mut ok_msg| {
           ok_msg.set_id(id);
           ok_msg
         })
         .map_err(|err| {
+          error_count.fetch_add(1, Ordering::SeqCst);
           let mut error = message::Error::from(err);
           error.set_id(id);
           error
-        })
+        });
+      if let Some((session_log, message, received_at, started)) = session_log {
+        let kind = match &result {
+          Ok(_) => SessionEventKind::Command {
+            message,
+            duration: started.elapsed(),
+          },
+          Err(error) => SessionEventKind::Error {
+            message: Some(message),
+            error: error.clone(),
+          },
+        };
+        session_log.record(session_id, received_at, kind);
+      }
+      result
     }
     .instrument(info_span!("Buttplug Server Message", id = id))
     .boxed()
@@ -483,7 +1970,11 @@ impl ButtplugServer {
   /// Protocol Spec](https://buttplug-spec.docs.buttplug.io). This is the first thing that must
   /// happens upon connection to the server, in order to make sure the server can speak the same
   /// protocol version as the client.
-  fn perform_handshake(&self, msg: message::RequestServerInfo) -> ButtplugServerResultFuture {
+  fn perform_handshake(
+    &self,
+    session_id: u64,
+    msg: message::RequestServerInfo,
+  ) -> ButtplugServerResultFuture {
     if self.connected() {
       return ButtplugHandshakeError::HandshakeAlreadyHappened.into();
     }
@@ -501,11 +1992,25 @@ impl ButtplugServer {
     }
     // Only start the ping timer after we've received the handshake.
     let ping_timer = self.ping_timer.clone();
-    let out_msg =
-      message::ServerInfo::new(&self.server_name, msg.message_version(), self.max_ping_time);
+    let out_msg = message::ServerInfo::new(
+      &self.server_name(),
+      msg.message_version(),
+      self.max_ping_time,
+    );
     let connected = self.connected.clone();
+    let client_spec_versions = self.client_spec_versions.clone();
+    let message_version = msg.message_version() as u32;
+    if self.auto_start_scanning && !self.auto_scan_started.swap(true, Ordering::SeqCst) {
+      self.start_auto_scan();
+    }
+    let session_log = self.session_log.clone();
+    let client_name = msg.client_name().clone();
     async move {
+      if let Some(session_log) = session_log {
+        session_log.start_session(session_id, &client_name);
+      }
       ping_timer.start_ping_timer().await;
+      client_spec_versions.insert(session_id, message_version);
       connected.store(true, Ordering::SeqCst);
       debug!("Server handshake check successful.");
       Result::Ok(out_msg.into())
@@ -513,6 +2018,119 @@ impl ButtplugServer {
     .boxed()
   }
 
+  /// Start the scan for [ButtplugServerBuilder::auto_start_scanning] in the background, so the
+  /// handshake reply doesn't wait on comm managers bringing up scanning.
+  fn start_auto_scan(&self) {
+    let device_manager = self.device_manager.clone();
+    // The timer token is stored before spawning, so a StartScanning from the client right after
+    // the handshake still cancels it.
+    let timer = self.auto_scan_duration.map(|duration| {
+      let token = CancellationToken::new();
+      *self.auto_scan_timer.lock().expect("Lock poisoned") = Some(token.clone());
+      (duration, token)
+    });
+    async_manager::spawn(async move {
+      info!("Automatically starting device scanning.");
+      if let Err(err) = device_manager
+        .parse_message(StartScanning::default().into())
+        .await
+      {
+        error!("Cannot automatically start scanning: {:?}", err);
+        return;
+      }
+      if let Some((duration, token)) = timer {
+        tokio::select! {
+          _ = sleep(duration) => {
+            info!("Automatic scan duration elapsed, stopping device scanning.");
+            // Scanning may have already been stopped by the client, so ignore errors.
+            let _ = device_manager
+              .parse_message(StopScanning::default().into())
+              .await;
+          }
+          _ = token.cancelled() => {
+            debug!("Client started scanning itself, leaving the automatic scan running.");
+          }
+        }
+      }
+    });
+  }
+
+  /// Forget everything kept about a remote client session, once it has disconnected.
+  pub(crate) fn end_session(&self, session_id: u64) {
+    if let Some(session_log) = &self.session_log {
+      session_log.end_session(session_id);
+    }
+    self.client_spec_versions.remove(&session_id);
+    self.device_manager.end_session(session_id);
+    if self.clear_history_on_disconnect {
+      self
+        .device_manager
+        .clear_session_command_history(session_id);
+    }
+  }
+
+  /// Like [ButtplugServer::parse_session_message], but a
+  /// [RequestServerInfo](message::RequestServerInfo) received while another client is already
+  /// connected joins that connection, instead of failing with
+  /// [ButtplugHandshakeError::HandshakeAlreadyHappened]. Used by remote servers that share one
+  /// server between several client sessions, until the session has done its handshake.
+  pub(crate) fn parse_joining_session_message(
+    &self,
+    session_id: u64,
+    msg: ButtplugClientMessage,
+  ) -> BoxFuture<'static, Result<ButtplugServerMessage, message::Error>> {
+    let rsi_msg = match msg {
+      ButtplugClientMessage::RequestServerInfo(rsi_msg) if self.connected() => rsi_msg,
+      msg => return self.parse_session_message(session_id, msg),
+    };
+    let result: Result<ButtplugServerMessage, ButtplugError> =
+      if BUTTPLUG_CURRENT_MESSAGE_SPEC_VERSION < rsi_msg.message_version() {
+        Err(
+          ButtplugHandshakeError::MessageSpecVersionMismatch(
+            BUTTPLUG_CURRENT_MESSAGE_SPEC_VERSION,
+            rsi_msg.message_version(),
+          )
+          .into(),
+        )
+      } else {
+        info!(
+          "Client {} joining existing connection at message version {}.",
+          rsi_msg.client_name(),
+          rsi_msg.message_version()
+        );
+        self
+          .client_spec_versions
+          .insert(session_id, rsi_msg.message_version() as u32);
+        if let Some(session_log) = &self.session_log {
+          session_log.start_session(session_id, rsi_msg.client_name());
+        }
+        Ok(
+          message::ServerInfo::new(
+            &self.server_name(),
+            rsi_msg.message_version(),
+            self.max_ping_time,
+          )
+          .into(),
+        )
+      };
+    let id = rsi_msg.id();
+    self.message_count.fetch_add(1, Ordering::SeqCst);
+    future::ready(
+      result
+        .map(|mut ok_msg| {
+          ok_msg.set_id(id);
+          ok_msg
+        })
+        .map_err(|err| {
+          self.error_count.fetch_add(1, Ordering::SeqCst);
+          let mut error = message::Error::from(err);
+          error.set_id(id);
+          error
+        }),
+    )
+    .boxed()
+  }
+
   /// Update the [PingTimer] with the latest received ping message.
   fn handle_ping(&self, msg: message::Ping) -> ButtplugServerResultFuture {
     if self.max_ping_time == 0 {
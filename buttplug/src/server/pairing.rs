@@ -0,0 +1,77 @@
+// Buttplug Rust Source Code File - See https://buttplug.io for more info.
+//
+// Copyright 2016-2023 Nonpolynomial Labs LLC. All rights reserved.
+//
+// Licensed under the BSD 3-Clause license. See LICENSE file in the project root
+// for full license information.
+
+//! Guided pairing, for devices that need a button press or code entry before they can be found.
+
+use super::{device::ServerDeviceInfo, ButtplugServer};
+use crate::core::message::{self, ButtplugServerMessage};
+use async_stream::stream;
+use futures::{pin_mut, Stream, StreamExt};
+
+/// Progress of a pairing attempt started with [ButtplugServer::start_pairing]. More events may be
+/// added as protocols with other pairing steps (e.g. code entry) are supported.
+#[derive(Debug, Clone)]
+#[allow(clippy::large_enum_variant)]
+#[non_exhaustive]
+pub enum PairingEvent {
+  /// Scanning has started, and the server is waiting for the device to show up.
+  Waiting,
+  /// Instruction for the user from the protocol, e.g. which button to hold.
+  Hint(String),
+  /// A device using the protocol connected. Last event of the stream.
+  Success(ServerDeviceInfo),
+  /// Pairing can't go ahead. Last event of the stream.
+  Failure(String),
+}
+
+impl ButtplugServer {
+  /// Start scanning and walk through pairing a device using `protocol`, reporting progress and
+  /// any hints the protocol has for the user. The stream ends once a device using the protocol
+  /// connects. It doesn't time out, so drop it to give up. Scanning is left running either way.
+  pub fn start_pairing(&self, protocol: &str) -> impl Stream<Item = PairingEvent> {
+    let protocol_info = self
+      .list_protocols()
+      .into_iter()
+      .find(|info| info.name() == protocol);
+    let protocol = protocol.to_owned();
+    let device_manager = self.device_manager();
+    // Subscribe before scanning starts, so a device that connects quickly isn't missed.
+    let events = device_manager.event_stream();
+    stream! {
+      let protocol_info = match protocol_info {
+        Some(protocol_info) => protocol_info,
+        None => {
+          yield PairingEvent::Failure(format!("Protocol {} does not exist.", protocol));
+          return;
+        }
+      };
+      pin_mut!(events);
+      if let Err(err) = device_manager
+        .parse_message(message::StartScanning::default().into())
+        .await
+      {
+        yield PairingEvent::Failure(format!("Cannot start scanning: {}", err));
+        return;
+      }
+      yield PairingEvent::Waiting;
+      for hint in protocol_info.pairing_hints() {
+        yield PairingEvent::Hint(hint.clone());
+      }
+      while let Some(event) = events.next().await {
+        if let ButtplugServerMessage::DeviceAdded(added) = event {
+          if let Some(info) = device_manager.device_info(added.device_index()) {
+            if *info.identifier().protocol() == protocol {
+              yield PairingEvent::Success(info);
+              return;
+            }
+          }
+        }
+      }
+      yield PairingEvent::Failure("Device manager shut down.".to_owned());
+    }
+  }
+}
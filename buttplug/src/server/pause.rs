@@ -0,0 +1,134 @@
+// Buttplug Rust Source Code File - See https://buttplug.io for more info.
+//
+// Copyright 2016-2023 Nonpolynomial Labs LLC. All rights reserved.
+//
+// Licensed under the BSD 3-Clause license. See LICENSE file in the project root
+// for full license information.
+
+//! Temporarily zeroing all scalar actuators, then putting them back the way they were.
+
+use super::{device::ServerDeviceManager, ButtplugServer};
+use crate::core::{
+  errors::ButtplugError,
+  message::{ScalarCmd, ScalarSubcommand},
+};
+use futures::future;
+use std::{sync::Arc, time::Instant};
+
+/// Devices paused with [ButtplugServer::pause_all_devices], and the values to put back when
+/// they're resumed. Dropping the handle without calling [PauseHandle::resume] leaves the devices
+/// stopped.
+pub struct PauseHandle {
+  device_manager: Arc<ServerDeviceManager>,
+  /// Scalar values each paused device had, by device index.
+  paused: Vec<(u32, Vec<ScalarSubcommand>)>,
+  /// When the devices were paused, to tell which ones clients have sent commands to since.
+  paused_at: Instant,
+  resumed: bool,
+}
+
+impl PauseHandle {
+  /// Indexes of the devices that were paused, i.e. that had at least one scalar actuator running.
+  pub fn device_indexes(&self) -> Vec<u32> {
+    self.paused.iter().map(|(index, _)| *index).collect()
+  }
+
+  /// Send every paused device the scalar values it had before it was paused. Devices a client has
+  /// sent commands to while paused (including stops), or that no longer have all of their scalar
+  /// actuators at 0, are left as they are, so resuming never undoes a newer command. Commands to
+  /// all devices are sent in parallel. Waits for all of them to finish, then returns the first
+  /// error, if any (e.g. for devices that disconnected while paused).
+  pub async fn resume(mut self) -> Result<(), ButtplugError> {
+    self.resumed = true;
+    let paused: Vec<_> = std::mem::take(&mut self.paused)
+      .into_iter()
+      .filter(|(index, _)| {
+        let commanded = self
+          .device_manager
+          .device_last_command_at(*index)
+          .is_some_and(|last_command_at| last_command_at > self.paused_at);
+        // Devices that are gone are still sent their values, so resuming reports the error.
+        let still_paused = self
+          .device_manager
+          .scalar_values(*index)
+          .is_none_or(|scalars| scalars.iter().all(|scalar| scalar.scalar() == 0.0));
+        if commanded || !still_paused {
+          info!("Device {} changed while paused, not resuming it.", index);
+        }
+        !commanded && still_paused
+      })
+      .collect();
+    future::join_all(paused.into_iter().map(|(index, scalars)| {
+      self
+        .device_manager
+        .parse_message(ScalarCmd::new(index, scalars).into())
+    }))
+    .await
+    .into_iter()
+    .try_for_each(|result| result.map(|_| ()))
+  }
+}
+
+impl Drop for PauseHandle {
+  fn drop(&mut self) {
+    if !self.resumed && !self.paused.is_empty() {
+      warn!(
+        "Pause handle for devices {:?} dropped without resuming, devices stay stopped.",
+        self.device_indexes()
+      );
+    }
+  }
+}
+
+impl ButtplugServer {
+  /// Set every scalar actuator (vibrators, oscillators, etc.) on every device to 0.0, remembering
+  /// the values they had so they can be put back with [PauseHandle::resume], e.g. for a "hold on a
+  /// moment" button. Devices with no running scalar actuators are left alone. Other actuator
+  /// types, like rotators and linear actuators, aren't paused.
+  ///
+  /// Devices that fail to pause are logged and left out of the handle.
+  pub async fn pause_all_devices(&self) -> PauseHandle {
+    let paused: Vec<(u32, Vec<ScalarSubcommand>)> = self
+      .device_manager
+      .device_indexes()
+      .into_iter()
+      .filter_map(|index| {
+        let scalars = self.device_manager.scalar_values(index)?;
+        scalars
+          .iter()
+          .any(|scalar| scalar.scalar() > 0.0)
+          .then_some((index, scalars))
+      })
+      .collect();
+    let results = future::join_all(paused.iter().map(|(index, scalars)| {
+      let stopped = scalars
+        .iter()
+        .map(|scalar| ScalarSubcommand::new(scalar.index(), 0.0, scalar.actuator_type()))
+        .collect();
+      self
+        .device_manager
+        .parse_message(ScalarCmd::new(*index, stopped).into())
+    }))
+    .await;
+    // The pause commands count as commands to the devices too, so only commands after this point
+    // keep a device from being resumed.
+    let paused_at = Instant::now();
+    let paused = paused
+      .into_iter()
+      .zip(results)
+      .filter_map(|((index, scalars), result)| match result {
+        Ok(_) => Some((index, scalars)),
+        Err(err) => {
+          warn!("Cannot pause device {}: {}", index, err);
+          None
+        }
+      })
+      .collect();
+    PauseHandle {
+      device_manager: self.device_manager.clone(),
+      paused,
+      paused_at,
+      resumed: false,
+    }
+  }
+}
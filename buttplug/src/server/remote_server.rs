@@ -5,144 +5,2081 @@
 // Licensed under the BSD 3-Clause license. See LICENSE file in the project root
 // for full license information.
 
-use super::{ButtplugServer, ButtplugServerBuilder};
+#[cfg(feature = "metrics")]
+use super::device::LatencyHistogram;
+#[cfg(feature = "http-info")]
+use super::http_info::{self, HttpInfoHandle, HttpInfoSource};
+#[cfg(all(feature = "http-info", feature = "metrics"))]
+use super::telemetry::prometheus_metrics;
+use super::{
+  connection_quality::ConnectionQuality,
+  device::ServerDeviceInfo,
+  session_recorder::{SessionRecorder, SessionRecorders},
+  status_report::ENABLED_FEATURES,
+  telemetry::TelemetryState,
+  ButtplugServer,
+  ButtplugServerBuilder,
+  ConnectionQualityConfig,
+  IdleShutdownPolicy,
+  StatusReport,
+  StatusReportDevice,
+  TelemetryConfig,
+  TelemetryError,
+};
 use crate::{
   core::{
-    connector::ButtplugConnector,
-    errors::ButtplugError,
+    connector::{ButtplugConnector, ButtplugConnectorError},
+    errors::{ButtplugError, ButtplugMessageError, ButtplugUnknownError},
     message::{
       self,
+      serializer::CodecType,
       //ButtplugDeviceCommandMessageUnion,
       ButtplugClientMessage,
+      ButtplugDeviceCommandMessageUnion,
+      ButtplugDeviceMessage,
       ButtplugMessage,
       ButtplugMessageValidator,
       ButtplugServerMessage,
+      BUTTPLUG_CURRENT_MESSAGE_SPEC_VERSION,
     },
   },
-  util::{async_manager, stream::convert_broadcast_receiver_to_stream},
+  util::async_manager,
+};
+use async_trait::async_trait;
+use futures::{
+  future::{self, Future},
+  select_biased,
+  stream::{self, FuturesUnordered},
+  FutureExt,
+  Stream,
+  StreamExt,
+};
+use getset::{CopyGetters, Getters};
+#[cfg(feature = "serialize-json")]
+use serde::{Deserialize, Serialize};
+use std::{
+  any::type_name,
+  collections::{HashMap, HashSet, VecDeque},
+  net::SocketAddr,
+  sync::{
+    atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering},
+    Arc,
+    Mutex,
+    Weak,
+  },
+  time::{Duration, Instant},
 };
-use futures::{future::Future, select, FutureExt, Stream, StreamExt};
-use std::sync::Arc;
 use thiserror::Error;
-use tokio::sync::{broadcast, mpsc, Notify};
+use tokio::{
+  sync::{broadcast, mpsc, watch, Notify},
+  time::{sleep, sleep_until, timeout},
+};
+use tracing::{field, Level, Span};
+use tracing_futures::Instrument;
+
+/// How long [ButtplugRemoteServer::shutdown_on_signal] waits for the server to shut down before
+/// giving up.
+pub const DEFAULT_SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// How long [ButtplugRemoteServer::force_reconnect] waits for a client to connect again before
+/// giving up.
+pub const DEFAULT_FORCE_RECONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// How [ButtplugRemoteServer::start_with_reconnect] retries connections.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, CopyGetters)]
+#[getset(get_copy = "pub")]
+pub struct RetryPolicy {
+  /// Number of connection attempts in a row that may fail before giving up, or None to retry
+  /// forever. A client session that ends normally resets the count.
+  max_attempts: Option<u32>,
+  /// Time to wait before bringing the connector up again.
+  delay: Duration,
+}
+
+impl RetryPolicy {
+  pub fn new(max_attempts: Option<u32>, delay: Duration) -> Self {
+    Self {
+      max_attempts,
+      delay,
+    }
+  }
+}
+
+/// How [ButtplugRemoteServer] retries sending a message to its client that failed to send, set with
+/// [ButtplugRemoteServerBuilder::connector_retry]. The session ends once the retries run out.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, CopyGetters)]
+#[getset(get_copy = "pub")]
+pub struct ButtplugConnectorRetryConfig {
+  /// Number of times to retry a failed send. 0 (the default) ends the session on the first
+  /// failure.
+  max_write_retries: u32,
+  /// Time to wait between attempts.
+  retry_delay: Duration,
+}
+
+impl ButtplugConnectorRetryConfig {
+  pub fn new(max_write_retries: u32, retry_delay: Duration) -> Self {
+    Self {
+      max_write_retries,
+      retry_delay,
+    }
+  }
+}
+
+/// What happens to a [ButtplugRemoteServer::event_stream] subscriber that falls more events behind
+/// than the [EventBufferPolicy] capacity.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum OverflowPolicy {
+  /// Skip the oldest events the subscriber missed, logging how many, and carry on.
+  #[default]
+  DropOldest,
+  /// End the subscriber's stream.
+  CloseSubscriber,
+}
+
+/// How [ButtplugRemoteServer] buffers events for [ButtplugRemoteServer::event_stream] subscribers,
+/// set with [ButtplugRemoteServer::set_event_buffer_policy].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, CopyGetters)]
+#[getset(get_copy = "pub")]
+pub struct EventBufferPolicy {
+  /// Number of events each subscriber can fall behind by. Treated as 1 if 0.
+  capacity: usize,
+  /// What to do with subscribers that fall further behind than the capacity.
+  overflow: OverflowPolicy,
+  /// If true, new subscribers start with the last `capacity` events, instead of only seeing events
+  /// sent after they subscribed.
+  replay_on_subscribe: bool,
+}
+
+impl EventBufferPolicy {
+  pub fn new(capacity: usize, overflow: OverflowPolicy, replay_on_subscribe: bool) -> Self {
+    Self {
+      capacity,
+      overflow,
+      replay_on_subscribe,
+    }
+  }
+}
+
+impl Default for EventBufferPolicy {
+  fn default() -> Self {
+    Self::new(
+      DEFAULT_EVENT_CHANNEL_CAPACITY,
+      OverflowPolicy::default(),
+      false,
+    )
+  }
+}
 
+/// Why the server ended a client session, as passed to [ButtplugRemoteServer::disconnect_client].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serialize-json", derive(Serialize, Deserialize))]
+pub enum DisconnectReason {
+  /// The server owner asked for the client to be disconnected.
+  UserRequested,
+  /// The client didn't send anything within the server's idle timeout.
+  IdleTimeout,
+  /// The client couldn't be authenticated.
+  AuthFailed,
+  /// The client sent messages faster than the server allows.
+  RateLimitExceeded,
+  /// The server owner dropped the client so it would reconnect, see
+  /// [ButtplugRemoteServer::force_reconnect].
+  ForcedReconnect,
+  /// The session ran out of time, see [ButtplugRemoteServer::close_after].
+  SessionExpired,
+}
+
+/// Events from a [ButtplugRemoteServer], see [ButtplugRemoteServer::event_stream].
+///
+/// With the `serialize-json` feature, events serialize to JSON objects with a `type` field naming
+/// the variant, and the variant's values as named fields, e.g.
+/// `{"type":"DeviceAdded","device_index":0,"name":"Lovense Hush","address":"AA:BB","display_name":null}`.
+/// Field names for tuple variants are:
+///
+/// - `ClientConnected`: `session_id`, `client_name`
+/// - `ClientDisconnected`: `session_id`, `reason`
+/// - `DeviceAdded`: `device_index`, `name`, `address`, `display_name`
+/// - `DeviceRemoved`, `DeviceReconnectFailed`: `device_index`
+/// - `DeviceUpdated`: `device_index`, `device_info`
+/// - `DeviceCommand`: `command`
+/// - `DeviceCommandSent`: `device_index`, `command`
+/// - `DeviceCommandFailed`: `device_index`, `error`
+/// - `Custom`: `payload`
+///
+/// Struct variants use their own field names, and `ClientIdleTimeout`, `DeviceLimitReached` and
+/// `SessionExpired` only have the `type` field.
 // Clone derived here to satisfy tokio broadcast requirements.
 #[derive(Clone, Debug)]
+#[cfg_attr(
+  feature = "serialize-json",
+  derive(Serialize, Deserialize),
+  serde(
+    from = "SerializedRemoteServerEvent",
+    into = "SerializedRemoteServerEvent"
+  )
+)]
 pub enum ButtplugRemoteServerEvent {
-  ClientConnected(String),
-  ClientDisconnected,
+  /// Client finished its handshake. Has the id of the session, which stays the same for the
+  /// matching [ButtplugRemoteServerEvent::ClientDisconnected], and the client name.
+  ClientConnected(u64, String),
+  /// Client session ended. Has the session id, and the reason if the server ended the session, or
+  /// None if the client closed the connection itself.
+  ClientDisconnected(u64, Option<DisconnectReason>),
   DeviceAdded(u32, String, String, Option<String>),
   DeviceRemoved(u32),
-  //DeviceCommand(ButtplugDeviceCommandMessageUnion)
+  /// Client was disconnected for not sending any messages within the server's idle timeout.
+  ClientIdleTimeout,
+  /// Device capabilities changed after being requeried via [ButtplugServer::query_device].
+  DeviceUpdated(u32, Box<ServerDeviceInfo>),
+  /// Client messages are being dropped by the rate limiter set with
+  /// [ButtplugServerBuilder::client_rate_limit]. Emitted for the first dropped message in each
+  /// rate limit window, with the number of messages dropped since the client started going over
+  /// the limit. The count resets once a window passes without drops.
+  RateLimitExceeded {
+    message_id: u32,
+    message_type: String,
+    drop_count: u64,
+  },
+  /// Device disconnected and couldn't be found again within the attempts set by
+  /// [ButtplugServerBuilder::device_max_reconnect_attempts].
+  DeviceReconnectFailed(u32),
+  /// Device had more communication errors since connecting than allowed by
+  /// [ButtplugServerBuilder::device_error_threshold]. Sent once per device connection.
+  DeviceUnstable {
+    device_index: u32,
+    error_count: u64,
+  },
+  /// Device was found but ignored, because [ButtplugServerBuilder::max_devices] devices are
+  /// already connected. Sent once until a device disconnects.
+  DeviceLimitReached,
+  /// Time set with [ButtplugRemoteServer::close_after] ran out. Sent after devices are stopped and
+  /// before the client is disconnected.
+  SessionExpired,
+  /// Client sent a device command, emitted before it's handled. Carries the full command, for
+  /// auditing or usage tracking.
+  DeviceCommand(ButtplugDeviceCommandMessageUnion),
+  /// Client device command was handled successfully.
+  DeviceCommandSent(u32, ButtplugClientMessage),
+  /// Client device command failed.
+  DeviceCommandFailed(u32, ButtplugError),
+  /// Something went wrong inside the server, rather than with a client message. `context` names
+  /// what failed, e.g. "task_watchdog_triggered" for messages abandoned after
+  /// [ButtplugServerBuilder::task_watchdog_timeout].
+  InternalError {
+    context: String,
+    error: ButtplugError,
+  },
+  /// Connection quality score fell below the threshold set with
+  /// [ButtplugRemoteServerBuilder::connection_quality], see
+  /// [ButtplugRemoteServer::connection_quality]. Sent again only after the score recovers and
+  /// drops again.
+  ConnectionQualityDegraded {
+    score: f32,
+  },
+  /// Error in the server loop that would otherwise only be logged, like failing to send a reply to
+  /// the client. `source` names what failed, and `fatal` is true if the error ended the client
+  /// session.
+  Error {
+    source: String,
+    fatal: bool,
+  },
+  /// Application defined event, sent with [ButtplugRemoteServer::emit_custom_event].
+  #[cfg(feature = "custom-events")]
+  Custom(serde_json::Value),
+}
+
+/// Either kind of event a [ButtplugRemoteServer] can report, from
+/// [ButtplugRemoteServer::global_event_stream].
+#[derive(Clone, Debug)]
+pub enum AnyButtplugEvent {
+  /// Event from [ButtplugRemoteServer::event_stream].
+  Remote(ButtplugRemoteServerEvent),
+  /// Message from [ButtplugServer::event_stream].
+  Server(ButtplugServerMessage),
+}
+
+/// JSON shape of [ButtplugRemoteServerEvent], giving names to the fields of tuple variants.
+#[cfg(feature = "serialize-json")]
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "type")]
+enum SerializedRemoteServerEvent {
+  ClientConnected {
+    session_id: u64,
+    client_name: String,
+  },
+  ClientDisconnected {
+    session_id: u64,
+    reason: Option<DisconnectReason>,
+  },
+  DeviceAdded {
+    device_index: u32,
+    name: String,
+    address: String,
+    display_name: Option<String>,
+  },
+  DeviceRemoved {
+    device_index: u32,
+  },
+  ClientIdleTimeout,
+  DeviceUpdated {
+    device_index: u32,
+    device_info: Box<ServerDeviceInfo>,
+  },
+  RateLimitExceeded {
+    message_id: u32,
+    message_type: String,
+    drop_count: u64,
+  },
+  DeviceReconnectFailed {
+    device_index: u32,
+  },
+  DeviceUnstable {
+    device_index: u32,
+    error_count: u64,
+  },
+  DeviceLimitReached,
+  SessionExpired,
+  DeviceCommand {
+    command: ButtplugDeviceCommandMessageUnion,
+  },
+  DeviceCommandSent {
+    device_index: u32,
+    command: ButtplugClientMessage,
+  },
+  DeviceCommandFailed {
+    device_index: u32,
+    error: ButtplugError,
+  },
+  InternalError {
+    context: String,
+    error: ButtplugError,
+  },
+  ConnectionQualityDegraded {
+    score: f32,
+  },
+  Error {
+    source: String,
+    fatal: bool,
+  },
+  #[cfg(feature = "custom-events")]
+  Custom {
+    payload: serde_json::Value,
+  },
+}
+
+#[cfg(feature = "serialize-json")]
+impl From<ButtplugRemoteServerEvent> for SerializedRemoteServerEvent {
+  fn from(event: ButtplugRemoteServerEvent) -> Self {
+    match event {
+      ButtplugRemoteServerEvent::ClientConnected(session_id, client_name) => {
+        Self::ClientConnected {
+          session_id,
+          client_name,
+        }
+      }
+      ButtplugRemoteServerEvent::ClientDisconnected(session_id, reason) => {
+        Self::ClientDisconnected { session_id, reason }
+      }
+      ButtplugRemoteServerEvent::DeviceAdded(device_index, name, address, display_name) => {
+        Self::DeviceAdded {
+          device_index,
+          name,
+          address,
+          display_name,
+        }
+      }
+      ButtplugRemoteServerEvent::DeviceRemoved(device_index) => {
+        Self::DeviceRemoved { device_index }
+      }
+      ButtplugRemoteServerEvent::ClientIdleTimeout => Self::ClientIdleTimeout,
+      ButtplugRemoteServerEvent::DeviceUpdated(device_index, device_info) => Self::DeviceUpdated {
+        device_index,
+        device_info,
+      },
+      ButtplugRemoteServerEvent::RateLimitExceeded {
+        message_id,
+        message_type,
+        drop_count,
+      } => Self::RateLimitExceeded {
+        message_id,
+        message_type,
+        drop_count,
+      },
+      ButtplugRemoteServerEvent::DeviceReconnectFailed(device_index) => {
+        Self::DeviceReconnectFailed { device_index }
+      }
+      ButtplugRemoteServerEvent::DeviceUnstable {
+        device_index,
+        error_count,
+      } => Self::DeviceUnstable {
+        device_index,
+        error_count,
+      },
+      ButtplugRemoteServerEvent::DeviceLimitReached => Self::DeviceLimitReached,
+      ButtplugRemoteServerEvent::SessionExpired => Self::SessionExpired,
+      ButtplugRemoteServerEvent::DeviceCommand(command) => Self::DeviceCommand { command },
+      ButtplugRemoteServerEvent::DeviceCommandSent(device_index, command) => {
+        Self::DeviceCommandSent {
+          device_index,
+          command,
+        }
+      }
+      ButtplugRemoteServerEvent::DeviceCommandFailed(device_index, error) => {
+        Self::DeviceCommandFailed {
+          device_index,
+          error,
+        }
+      }
+      ButtplugRemoteServerEvent::InternalError { context, error } => {
+        Self::InternalError { context, error }
+      }
+      ButtplugRemoteServerEvent::ConnectionQualityDegraded { score } => {
+        Self::ConnectionQualityDegraded { score }
+      }
+      ButtplugRemoteServerEvent::Error { source, fatal } => Self::Error { source, fatal },
+      #[cfg(feature = "custom-events")]
+      ButtplugRemoteServerEvent::Custom(payload) => Self::Custom { payload },
+    }
+  }
+}
+
+#[cfg(feature = "serialize-json")]
+impl From<SerializedRemoteServerEvent> for ButtplugRemoteServerEvent {
+  fn from(event: SerializedRemoteServerEvent) -> Self {
+    match event {
+      SerializedRemoteServerEvent::ClientConnected {
+        session_id,
+        client_name,
+      } => Self::ClientConnected(session_id, client_name),
+      SerializedRemoteServerEvent::ClientDisconnected { session_id, reason } => {
+        Self::ClientDisconnected(session_id, reason)
+      }
+      SerializedRemoteServerEvent::DeviceAdded {
+        device_index,
+        name,
+        address,
+        display_name,
+      } => Self::DeviceAdded(device_index, name, address, display_name),
+      SerializedRemoteServerEvent::DeviceRemoved { device_index } => {
+        Self::DeviceRemoved(device_index)
+      }
+      SerializedRemoteServerEvent::ClientIdleTimeout => Self::ClientIdleTimeout,
+      SerializedRemoteServerEvent::DeviceUpdated {
+        device_index,
+        device_info,
+      } => Self::DeviceUpdated(device_index, device_info),
+      SerializedRemoteServerEvent::RateLimitExceeded {
+        message_id,
+        message_type,
+        drop_count,
+      } => Self::RateLimitExceeded {
+        message_id,
+        message_type,
+        drop_count,
+      },
+      SerializedRemoteServerEvent::DeviceReconnectFailed { device_index } => {
+        Self::DeviceReconnectFailed(device_index)
+      }
+      SerializedRemoteServerEvent::DeviceUnstable {
+        device_index,
+        error_count,
+      } => Self::DeviceUnstable {
+        device_index,
+        error_count,
+      },
+      SerializedRemoteServerEvent::DeviceLimitReached => Self::DeviceLimitReached,
+      SerializedRemoteServerEvent::SessionExpired => Self::SessionExpired,
+      SerializedRemoteServerEvent::DeviceCommand { command } => Self::DeviceCommand(command),
+      SerializedRemoteServerEvent::DeviceCommandSent {
+        device_index,
+        command,
+      } => Self::DeviceCommandSent(device_index, command),
+      SerializedRemoteServerEvent::DeviceCommandFailed {
+        device_index,
+        error,
+      } => Self::DeviceCommandFailed(device_index, error),
+      SerializedRemoteServerEvent::InternalError { context, error } => {
+        Self::InternalError { context, error }
+      }
+      SerializedRemoteServerEvent::ConnectionQualityDegraded { score } => {
+        Self::ConnectionQualityDegraded { score }
+      }
+      SerializedRemoteServerEvent::Error { source, fatal } => Self::Error { source, fatal },
+      #[cfg(feature = "custom-events")]
+      SerializedRemoteServerEvent::Custom { payload } => Self::Custom(payload),
+    }
+  }
+}
+
+/// Events for a single device, as returned by [ButtplugRemoteServer::subscribe_to_device].
+#[derive(Clone, Debug)]
+pub enum DeviceEvent {
+  Connected,
+  /// The device was removed. Always the last event in the stream.
+  Disconnected,
+  /// A command from the client was handled by the device.
+  CommandSent(ButtplugClientMessage),
+  /// A command from the client to the device failed.
+  Error(ButtplugError),
+}
+
+/// Connection settings agreed on during the handshake with the current client, as returned by
+/// [ButtplugRemoteServer::negotiated_config].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, CopyGetters)]
+#[getset(get_copy = "pub")]
+pub struct NegotiatedConfig {
+  /// Message spec version requested by the client, which the server uses for the connection.
+  client_spec_version: u32,
+  /// Newest message spec version the server supports.
+  server_spec_version: u32,
+  /// Wire format of the connector.
+  codec: CodecType,
+  /// Time the client can go without sending a Ping before being disconnected. Zero if the ping
+  /// timer isn't running.
+  ping_timeout: Duration,
+  /// Largest message the connector can carry, see [ButtplugConnector::max_message_size].
+  max_message_size: Option<usize>,
+}
+
+/// Version and features of the buttplug library the server was built with, as returned by
+/// [ButtplugRemoteServer::server_build_info].
+#[derive(Debug, Clone, PartialEq, Eq, Getters, CopyGetters)]
+pub struct BuildInfo {
+  /// Version of the buttplug crate.
+  #[getset(get = "pub")]
+  crate_version: String,
+  /// Newest message spec version the server supports.
+  #[getset(get_copy = "pub")]
+  spec_version: u32,
+  /// Cargo features the crate was built with.
+  #[getset(get = "pub")]
+  features: Vec<String>,
+  /// Value of the `BUTTPLUG_BUILD_TIMESTAMP` environment variable at compile time, for builds that
+  /// set it.
+  #[getset(get = "pub")]
+  build_timestamp: Option<String>,
+}
+
+impl BuildInfo {
+  fn current() -> Self {
+    Self {
+      crate_version: env!("CARGO_PKG_VERSION").to_owned(),
+      spec_version: BUTTPLUG_CURRENT_MESSAGE_SPEC_VERSION as u32,
+      features: ENABLED_FEATURES
+        .iter()
+        .map(|feature| (*feature).to_owned())
+        .collect(),
+      build_timestamp: option_env!("BUTTPLUG_BUILD_TIMESTAMP").map(str::to_owned),
+    }
+  }
+}
+
+/// Errors from [ButtplugRemoteServer::bounded_event_stream].
+#[derive(Error, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ButtplugRemoteServerEventStreamError {
+  /// The subscriber fell behind by more events than the channel capacity, so the stream was closed
+  /// instead of dropping events. This is always the last item in the stream.
+  #[error("Event stream subscriber fell more than {0} events behind and was closed")]
+  Overflowed(usize),
+}
+
+/// External system (MQTT broker, database, webhook, etc.) that gets a copy of every remote server
+/// event, see [ButtplugRemoteServer::mirror_events_to].
+#[async_trait]
+pub trait ButtplugEventSink {
+  /// Called with each event, in the order they were sent. The event is only borrowed, so clone it
+  /// to keep it. Errors are logged, and otherwise ignored.
+  async fn on_event(&self, event: &ButtplugRemoteServerEvent) -> Result<(), String>;
+}
+
+/// How often an auto-resizing event channel checks whether subscribers are missing events, see
+/// [ButtplugRemoteServer::set_event_channel_auto_resize].
+pub const EVENT_CHANNEL_RESIZE_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Subscriber for [ButtplugRemoteServer::bounded_event_stream].
+struct BoundedEventSubscriber {
+  sender: mpsc::Sender<ButtplugRemoteServerEvent>,
+  overflowed: Arc<AtomicBool>,
+}
+
+/// Broadcast channel for [ButtplugRemoteServer::event_stream] subscribers, which is replaced when
+/// the [EventBufferPolicy] changes.
+struct EventChannel {
+  sender: broadcast::Sender<ButtplugRemoteServerEvent>,
+  policy: EventBufferPolicy,
+  /// Last events sent, if the policy replays them to new subscribers.
+  recent_events: VecDeque<ButtplugRemoteServerEvent>,
+  /// Ids of live subscribers.
+  subscribers: HashSet<u64>,
+  next_subscriber_id: u64,
+  /// Number of live subscribers per name, for [ButtplugRemoteServer::event_stream_named].
+  subscriber_names: HashMap<String, usize>,
+  /// Receivers on newer channels for subscribers still reading from a replaced one, oldest first.
+  /// Created when the channel is replaced, so nothing sent in between is missed.
+  migrated_receivers: HashMap<u64, VecDeque<broadcast::Receiver<ButtplugRemoteServerEvent>>>,
+  /// Events skipped by subscribers that fell behind, over the life of the channel.
+  events_discarded: u64,
+  auto_resize: Option<EventChannelAutoResize>,
+}
+
+/// State for growing an [EventChannel] when subscribers fall behind.
+struct EventChannelAutoResize {
+  max_capacity: usize,
+  last_sample: Instant,
+  /// [EventChannel::events_discarded] at the last sample.
+  events_discarded_at_sample: u64,
+}
+
+impl EventChannel {
+  fn new(policy: EventBufferPolicy) -> Self {
+    Self {
+      sender: broadcast::channel(policy.capacity().max(1)).0,
+      policy,
+      recent_events: VecDeque::new(),
+      subscribers: HashSet::new(),
+      next_subscriber_id: 0,
+      subscriber_names: HashMap::new(),
+      migrated_receivers: HashMap::new(),
+      events_discarded: 0,
+      auto_resize: None,
+    }
+  }
+
+  /// If auto-resizing is on and it's time for another sample, doubles the capacity (up to the
+  /// maximum) if subscribers skipped events since the last one.
+  fn sample_for_resize(&mut self) {
+    let events_discarded = self.events_discarded;
+    let subscriber_count = self.subscribers.len();
+    let Some(auto_resize) = &mut self.auto_resize else {
+      return;
+    };
+    if auto_resize.last_sample.elapsed() < EVENT_CHANNEL_RESIZE_INTERVAL {
+      return;
+    }
+    auto_resize.last_sample = Instant::now();
+    let newly_discarded = events_discarded - auto_resize.events_discarded_at_sample;
+    auto_resize.events_discarded_at_sample = events_discarded;
+    let capacity = self.policy.capacity().max(1);
+    if newly_discarded == 0 || subscriber_count == 0 || capacity >= auto_resize.max_capacity {
+      return;
+    }
+    let new_capacity = capacity.saturating_mul(2).min(auto_resize.max_capacity);
+    info!(
+      newly_discarded,
+      subscriber_count,
+      "Event subscribers are falling behind, growing event channel from {} to {}.",
+      capacity,
+      new_capacity
+    );
+    self.set_policy(EventBufferPolicy::new(
+      new_capacity,
+      self.policy.overflow(),
+      self.policy.replay_on_subscribe(),
+    ));
+  }
+
+  fn set_policy(&mut self, policy: EventBufferPolicy) {
+    let sender = broadcast::channel(policy.capacity().max(1)).0;
+    for id in &self.subscribers {
+      self
+        .migrated_receivers
+        .entry(*id)
+        .or_default()
+        .push_back(sender.subscribe());
+    }
+    // Dropping the old sender closes the old channel, once subscribers have read what's left in it.
+    self.sender = sender;
+    self.policy = policy;
+    if policy.replay_on_subscribe() {
+      while self.recent_events.len() > policy.capacity() {
+        self.recent_events.pop_front();
+      }
+    } else {
+      self.recent_events.clear();
+    }
+  }
+}
+
+/// Keeps an [ButtplugRemoteServer::event_stream] subscriber registered with the channel until the
+/// stream is dropped.
+struct EventSubscription {
+  id: u64,
+  /// Set for subscribers from [ButtplugRemoteServer::event_stream_named].
+  name: Option<String>,
+  receiver: broadcast::Receiver<ButtplugRemoteServerEvent>,
+  /// Weak, so streams still end once the remote server and its session loops are gone.
+  channel: Weak<Mutex<EventChannel>>,
+}
+
+impl EventSubscription {
+  async fn next_event(&mut self) -> Option<ButtplugRemoteServerEvent> {
+    loop {
+      match self.receiver.recv().await {
+        Ok(event) => return Some(event),
+        Err(broadcast::error::RecvError::Lagged(count)) => {
+          let channel = self.channel.upgrade()?;
+          let overflow = {
+            let mut channel = channel.lock().expect("Lock poisoned");
+            channel.events_discarded += count;
+            channel.policy.overflow()
+          };
+          if overflow == OverflowPolicy::CloseSubscriber {
+            warn!(
+              "Remote server event subscriber fell {} events behind, closing it.",
+              count
+            );
+            return None;
+          }
+          warn!(
+            "Remote server event subscriber fell behind, skipped {} events.",
+            count
+          );
+        }
+        Err(broadcast::error::RecvError::Closed) => {
+          // The channel was either replaced by a policy change, or the server is gone.
+          let channel = self.channel.upgrade()?;
+          let migrated_receiver = channel
+            .lock()
+            .expect("Lock poisoned")
+            .migrated_receivers
+            .get_mut(&self.id)
+            .and_then(|receivers| receivers.pop_front())?;
+          self.receiver = migrated_receiver;
+        }
+      }
+    }
+  }
+}
+
+impl Drop for EventSubscription {
+  fn drop(&mut self) {
+    if let Some(channel) = self.channel.upgrade() {
+      let mut channel = channel.lock().expect("Lock poisoned");
+      channel.subscribers.remove(&self.id);
+      channel.migrated_receivers.remove(&self.id);
+      if let Some(name) = &self.name {
+        if let Some(count) = channel.subscriber_names.get_mut(name) {
+          *count -= 1;
+          if *count == 0 {
+            channel.subscriber_names.remove(name);
+          }
+        }
+      }
+    }
+  }
+}
+
+/// Transform applied to every remote server event before it's sent, see
+/// [ButtplugRemoteServerBuilder::with_event_transform].
+type EventTransform =
+  Arc<dyn Fn(ButtplugRemoteServerEvent) -> Option<ButtplugRemoteServerEvent> + Send + Sync>;
+
+/// Hook called with each message sent to a client, see
+/// [ButtplugRemoteServerBuilder::on_outbound_message].
+type OutboundMessageHook = Arc<dyn Fn(&ButtplugServerMessage) + Send + Sync>;
+
+/// Sends remote server events to both [ButtplugRemoteServer::event_stream] and
+/// [ButtplugRemoteServer::bounded_event_stream] subscribers. Never waits on subscribers, so a slow
+/// one can't hold up the server loop.
+#[derive(Clone)]
+struct RemoteEventSender {
+  channel: Arc<Mutex<EventChannel>>,
+  bounded_subscribers: Arc<Mutex<Vec<BoundedEventSubscriber>>>,
+  transform: Option<EventTransform>,
+}
+
+impl RemoteEventSender {
+  fn new(policy: EventBufferPolicy, transform: Option<EventTransform>) -> Self {
+    Self {
+      channel: Arc::new(Mutex::new(EventChannel::new(policy))),
+      bounded_subscribers: Arc::new(Mutex::new(vec![])),
+      transform,
+    }
+  }
+
+  fn policy(&self) -> EventBufferPolicy {
+    self.channel.lock().expect("Lock poisoned").policy
+  }
+
+  fn set_policy(&self, policy: EventBufferPolicy) {
+    self
+      .channel
+      .lock()
+      .expect("Lock poisoned")
+      .set_policy(policy);
+  }
+
+  fn set_auto_resize(&self, max_capacity: Option<usize>) {
+    self.channel.lock().expect("Lock poisoned").auto_resize =
+      max_capacity.map(|max_capacity| EventChannelAutoResize {
+        max_capacity,
+        last_sample: Instant::now(),
+        events_discarded_at_sample: 0,
+      });
+  }
+
+  fn events_discarded(&self) -> u64 {
+    self.channel.lock().expect("Lock poisoned").events_discarded
+  }
+
+  fn reset_events_discarded(&self) {
+    let mut channel = self.channel.lock().expect("Lock poisoned");
+    channel.events_discarded = 0;
+    // Keep auto-resizing counting from the same baseline.
+    if let Some(auto_resize) = &mut channel.auto_resize {
+      auto_resize.events_discarded_at_sample = 0;
+    }
+  }
+
+  fn subscribe(&self, name: Option<String>) -> impl Stream<Item = ButtplugRemoteServerEvent> {
+    let (replayed_events, subscription) = {
+      let mut channel = self.channel.lock().expect("Lock poisoned");
+      let id = channel.next_subscriber_id;
+      channel.next_subscriber_id += 1;
+      channel.subscribers.insert(id);
+      if let Some(name) = &name {
+        *channel.subscriber_names.entry(name.clone()).or_default() += 1;
+      }
+      // Subscribing while holding the lock means replayed events and live ones can't overlap.
+      let subscription = EventSubscription {
+        id,
+        name,
+        receiver: channel.sender.subscribe(),
+        channel: Arc::downgrade(&self.channel),
+      };
+      (channel.recent_events.clone(), subscription)
+    };
+    stream::iter(replayed_events).chain(stream::unfold(
+      subscription,
+      |mut subscription| async move {
+        let event = subscription.next_event().await?;
+        Some((event, subscription))
+      },
+    ))
+  }
+
+  fn subscribe_bounded(
+    &self,
+    capacity: usize,
+  ) -> (mpsc::Receiver<ButtplugRemoteServerEvent>, Arc<AtomicBool>) {
+    let (sender, receiver) = mpsc::channel(capacity);
+    let overflowed = Arc::new(AtomicBool::new(false));
+    self
+      .bounded_subscribers
+      .lock()
+      .expect("Lock poisoned")
+      .push(BoundedEventSubscriber {
+        sender,
+        overflowed: overflowed.clone(),
+      });
+    (receiver, overflowed)
+  }
+
+  fn subscriber_names(&self) -> Vec<String> {
+    let mut names: Vec<String> = self
+      .channel
+      .lock()
+      .expect("Lock poisoned")
+      .subscriber_names
+      .keys()
+      .cloned()
+      .collect();
+    names.sort();
+    names
+  }
+
+  fn receiver_count(&self) -> usize {
+    // Dropped bounded subscribers are only pruned on send, so skip them here.
+    self
+      .channel
+      .lock()
+      .expect("Lock poisoned")
+      .subscribers
+      .len()
+      + self
+        .bounded_subscribers
+        .lock()
+        .expect("Lock poisoned")
+        .iter()
+        .filter(|subscriber| !subscriber.sender.is_closed())
+        .count()
+  }
+
+  /// True if sent events go anywhere, either to a subscriber or into the replay buffer. Events are
+  /// only worth building while this is true.
+  fn has_listeners(&self) -> bool {
+    self.policy().replay_on_subscribe() || self.receiver_count() > 0
+  }
+
+  /// Best effort report of a server loop error. Never blocks, and does nothing if there's no one
+  /// listening.
+  fn send_error(&self, source: &str, fatal: bool) {
+    let _ = self.send(ButtplugRemoteServerEvent::Error {
+      source: source.to_owned(),
+      fatal,
+    });
+  }
+
+  /// Send an event to all subscribers, failing if there are none. Events dropped by the event
+  /// transform count as sent.
+  fn send(&self, event: ButtplugRemoteServerEvent) -> Result<(), ButtplugRemoteServerEvent> {
+    let event = match &self.transform {
+      Some(transform) => match transform(event) {
+        Some(event) => event,
+        None => return Ok(()),
+      },
+      None => event,
+    };
+    let mut subscribers = self.bounded_subscribers.lock().expect("Lock poisoned");
+    // Subscribers that are full get dropped, closing their stream with an overflow error.
+    subscribers.retain(
+      |subscriber| match subscriber.sender.try_send(event.clone()) {
+        Ok(()) => true,
+        Err(mpsc::error::TrySendError::Full(_)) => {
+          warn!("Bounded event stream subscriber is full, closing it.");
+          subscriber.overflowed.store(true, Ordering::SeqCst);
+          false
+        }
+        Err(mpsc::error::TrySendError::Closed(_)) => false,
+      },
+    );
+    let sent_to_bounded = !subscribers.is_empty();
+    let mut channel = self.channel.lock().expect("Lock poisoned");
+    channel.sample_for_resize();
+    let replayable = channel.policy.replay_on_subscribe();
+    if replayable {
+      if channel.recent_events.len() >= channel.policy.capacity() {
+        channel.recent_events.pop_front();
+      }
+      channel.recent_events.push_back(event.clone());
+    }
+    match channel.sender.send(event) {
+      Ok(_) => Ok(()),
+      Err(_) if sent_to_bounded || replayable => Ok(()),
+      Err(err) => Err(err.0),
+    }
+  }
 }
 
 #[derive(Error, Debug)]
 pub enum ButtplugServerConnectorError {
-  #[error("Cannot bring up server for connection: {0}")]
-  ConnectorError(String),
+  #[error("Cannot bring up server for connection: {message}")]
+  ConnectorError {
+    message: String,
+    /// Raw OS error code, kept separately since it can't be recovered from the message.
+    os_error_code: Option<i32>,
+  },
+}
+
+impl ButtplugServerConnectorError {
+  /// Returns the OS error code that caused the failure, if there was one.
+  pub fn os_error_code(&self) -> Option<i32> {
+    match self {
+      Self::ConnectorError { os_error_code, .. } => *os_error_code,
+    }
+  }
+}
+
+impl From<ButtplugConnectorError> for ButtplugServerConnectorError {
+  fn from(err: ButtplugConnectorError) -> Self {
+    Self::ConnectorError {
+      os_error_code: err.os_error_code(),
+      message: format!("{:?}", err),
+    }
+  }
 }
 
 pub struct ButtplugRemoteServer {
   server: Arc<ButtplugServer>,
-  event_sender: broadcast::Sender<ButtplugRemoteServerEvent>,
-  disconnect_notifier: Arc<Notify>,
+  event_sender: RemoteEventSender,
+  disconnect_signal: Arc<DisconnectSignal>,
+  /// Used to tell session loops to send the device list to their clients, see
+  /// [ButtplugRemoteServer::announce_device_list_to_client].
+  device_list_announcer: Arc<watch::Sender<()>>,
+  reconnect_stop: Arc<ReconnectStop>,
+  /// Activity of the session last started with [ButtplugRemoteServer::start], which per client
+  /// accessors report on. Every session has its own.
+  client_activity: Arc<Mutex<Arc<ClientActivity>>>,
+  connection_quality: ConnectionQualityConfig,
+  /// Cap on actuator values for client commands, stored as f64 bits, see
+  /// [ButtplugRemoteServer::set_max_intensity_for_session].
+  max_intensity: Arc<AtomicU64>,
+  /// Client messages being handled, across all sessions.
+  message_tasks: Arc<MessageTasks>,
+  connection_history: Arc<ConnectionHistory>,
+  session_recorders: Arc<SessionRecorders>,
+  /// Called with each message sent to a client, see
+  /// [ButtplugRemoteServerBuilder::on_outbound_message].
+  outbound_message_hooks: Arc<Vec<OutboundMessageHook>>,
+  shutdown_callbacks: Arc<ShutdownCallbacks>,
+  /// Whether connectors are health checked before their session starts.
+  preflight_check: bool,
+  connector_retry: ButtplugConnectorRetryConfig,
+  /// Id given to the next client session.
+  next_session_id: Arc<AtomicU64>,
+  /// Number of sessions currently running on the shared server.
+  active_sessions: Arc<AtomicUsize>,
+  telemetry: Arc<TelemetryState>,
+  connector_timeouts: Arc<ConnectorTimeouts>,
 }
 
+/// Tracks incoming traffic for a client session.
+#[derive(Default)]
+struct ClientActivity {
+  /// Time the most recent message was received.
+  last_message_at: Mutex<Option<Instant>>,
+  /// Time the client finished its handshake, if it has.
+  connected_since: Mutex<Option<Instant>>,
+  /// Name the client sent in its handshake, if it has done one.
+  client_name: Mutex<Option<String>>,
+  /// Settings agreed on in the handshake, if the client has done one.
+  negotiated_config: Mutex<Option<NegotiatedConfig>>,
+  /// Type name of the connector the session is running on.
+  connector_type_name: Mutex<Option<&'static str>>,
+  /// Messages received from the connector that the server loop hasn't picked up yet.
+  pending_message_count: AtomicUsize,
+  /// Messages received from and sent to the client this session, for [ConnectionRecord].
+  messages_in: AtomicU64,
+  messages_out: AtomicU64,
+  /// See [ButtplugRemoteServer::connection_quality].
+  quality: ConnectionQuality,
+}
+
+impl ClientActivity {
+  fn new(connection_quality: ConnectionQualityConfig) -> Self {
+    Self {
+      quality: ConnectionQuality::new(connection_quality),
+      ..Default::default()
+    }
+  }
+
+  /// Record the reply to a message received at `received_at`, telling the server owner if this
+  /// degraded the connection.
+  fn reply_sent(&self, received_at: Instant, error: bool, remote_event_sender: &RemoteEventSender) {
+    if let Some(score) = self.quality.record_reply(received_at.elapsed(), error) {
+      send_quality_degraded(score, remote_event_sender);
+    }
+  }
+
+  /// Record a message being dropped by the rate limiter, telling the server owner if this degraded
+  /// the connection.
+  fn message_dropped(&self, remote_event_sender: &RemoteEventSender) {
+    if let Some(score) = self.quality.record_drop() {
+      send_quality_degraded(score, remote_event_sender);
+    }
+  }
+
+  /// True once the client has done its handshake.
+  fn handshake_done(&self) -> bool {
+    self
+      .negotiated_config
+      .lock()
+      .expect("Lock poisoned")
+      .is_some()
+  }
+
+  /// Record a message being picked up by the server loop, returning the time it was received.
+  fn message_received(&self) -> Instant {
+    self.pending_message_count.fetch_sub(1, Ordering::SeqCst);
+    self.messages_in.fetch_add(1, Ordering::SeqCst);
+    let now = Instant::now();
+    *self.last_message_at.lock().expect("Lock poisoned") = Some(now);
+    now
+  }
+}
+
+/// Tell the server owner the client's connection quality dropped below the degraded threshold.
+fn send_quality_degraded(score: f32, remote_event_sender: &RemoteEventSender) {
+  warn!(score, "Client connection quality degraded.");
+  if remote_event_sender.has_listeners()
+    && remote_event_sender
+      .send(ButtplugRemoteServerEvent::ConnectionQualityDegraded { score })
+      .is_err()
+  {
+    error!("Cannot send event to owner, dropping and assuming local server thread has exited.");
+  }
+}
+
+/// See [ButtplugRemoteServer::status_report].
+fn build_status_report(server: &ButtplugServer, client_activity: &ClientActivity) -> StatusReport {
+  let device_manager = server.device_manager();
+  let devices = device_manager
+    .device_indexes()
+    .into_iter()
+    .filter_map(|index| {
+      device_manager.device_info(index).map(|device_info| {
+        StatusReportDevice::new(
+          index,
+          device_info
+            .display_name()
+            .as_ref()
+            .unwrap_or(device_info.name()),
+          device_info.identifier().address(),
+          device_manager
+            .command_statistics(index)
+            .last_command_at()
+            .map(|last_command_at| last_command_at.elapsed()),
+        )
+      })
+    })
+    .collect();
+  StatusReport::new(
+    &server.server_name(),
+    server.uptime(),
+    client_activity
+      .client_name
+      .lock()
+      .expect("Lock poisoned")
+      .clone(),
+    client_activity
+      .connected_since
+      .lock()
+      .expect("Lock poisoned")
+      .map_or(Duration::ZERO, |connected_since| connected_since.elapsed()),
+    devices,
+    server.message_count(),
+    server.error_count(),
+  )
+}
+
+/// Finished client session, as listed by [ButtplugRemoteServer::connection_history].
+#[derive(Debug, Clone, Getters, CopyGetters)]
+pub struct ConnectionRecord {
+  /// Name the client sent in its handshake, or None if it never finished one.
+  #[getset(get = "pub")]
+  client_name: Option<String>,
+  /// Time the connector connected.
+  #[getset(get_copy = "pub")]
+  connected_at: Instant,
+  #[getset(get_copy = "pub")]
+  disconnected_at: Instant,
+  /// Reason the server ended the session, or None if the client closed the connection itself.
+  #[getset(get_copy = "pub")]
+  disconnect_reason: Option<DisconnectReason>,
+  /// Messages received from the client.
+  #[getset(get_copy = "pub")]
+  messages_in: u64,
+  /// Messages sent to the client, including events.
+  #[getset(get_copy = "pub")]
+  messages_out: u64,
+}
+
+/// Ring buffer of the most recent [ConnectionRecord]s.
+struct ConnectionHistory {
+  records: Mutex<VecDeque<ConnectionRecord>>,
+  size: usize,
+  /// Sessions finished since the remote server was built, including those no longer in records.
+  completed: AtomicU64,
+}
+
+impl ConnectionHistory {
+  fn new(size: usize) -> Self {
+    Self {
+      records: Mutex::new(VecDeque::with_capacity(size)),
+      size,
+      completed: AtomicU64::new(0),
+    }
+  }
+
+  fn record(&self, record: ConnectionRecord) {
+    self.completed.fetch_add(1, Ordering::SeqCst);
+    if self.size == 0 {
+      return;
+    }
+    let mut records = self.records.lock().expect("Lock poisoned");
+    if records.len() >= self.size {
+      records.pop_front();
+    }
+    records.push_back(record);
+  }
+}
+
+/// Callbacks registered with [ButtplugRemoteServer::on_shutdown].
+#[derive(Default)]
+struct ShutdownCallbacks {
+  callbacks: Mutex<Vec<Box<dyn FnOnce() + Send>>>,
+  /// Set once the remote server is dropped, so the last session out runs the callbacks.
+  dropped: AtomicBool,
+}
+
+impl ShutdownCallbacks {
+  /// Run and remove all registered callbacks, in registration order.
+  fn run(&self) {
+    let callbacks = std::mem::take(&mut *self.callbacks.lock().expect("Lock poisoned"));
+    for callback in callbacks {
+      callback();
+    }
+  }
+}
+
+/// Sets a connector's timeout, returning the connector error as a string on failure.
+type ConnectorTimeoutSetter = Box<dyn Fn(Duration) -> Result<(), String> + Send + Sync>;
+
+/// Timeout set with [ButtplugRemoteServer::set_connector_timeout], and the connectors of running
+/// sessions to pass it on to.
+#[derive(Default)]
+struct ConnectorTimeouts {
+  timeout: Mutex<Option<Duration>>,
+  /// Keyed by session id.
+  setters: Mutex<HashMap<u64, ConnectorTimeoutSetter>>,
+}
+
+impl ConnectorTimeouts {
+  fn timeout(&self) -> Option<Duration> {
+    *self.timeout.lock().expect("Lock poisoned")
+  }
+
+  /// Pass `timeout` on to every running connector, and keep it for connectors started later.
+  /// Connectors that fail don't stop the rest from being updated, the first error is returned.
+  fn set_timeout(&self, timeout: Duration) -> Result<(), String> {
+    *self.timeout.lock().expect("Lock poisoned") = Some(timeout);
+    let mut result = Ok(());
+    for setter in self.setters.lock().expect("Lock poisoned").values() {
+      if let Err(err) = setter(timeout) {
+        if result.is_ok() {
+          result = Err(err);
+        }
+      }
+    }
+    result
+  }
+
+  fn register(&self, session_id: u64, setter: ConnectorTimeoutSetter) {
+    self
+      .setters
+      .lock()
+      .expect("Lock poisoned")
+      .insert(session_id, setter);
+  }
+
+  fn unregister(&self, session_id: u64) {
+    self
+      .setters
+      .lock()
+      .expect("Lock poisoned")
+      .remove(&session_id);
+  }
+}
+
+/// Used to tell the server loop to drop the current client.
+#[derive(Default)]
+struct DisconnectSignal {
+  notifier: Notify,
+  /// Set if the disconnect should be reported to the owner with a reason.
+  reason: Mutex<Option<DisconnectReason>>,
+}
+
+impl DisconnectSignal {
+  fn disconnect(&self, reason: Option<DisconnectReason>) {
+    *self.reason.lock().expect("Lock poisoned") = reason;
+    self.notifier.notify_waiters();
+  }
+}
+
+/// Counts spawned client message tasks, so shutdown can wait for them to finish.
+#[derive(Default)]
+struct MessageTasks {
+  count: AtomicUsize,
+  idle: Notify,
+}
+
+impl MessageTasks {
+  fn start(self: &Arc<Self>) -> MessageTaskGuard {
+    self.count.fetch_add(1, Ordering::SeqCst);
+    MessageTaskGuard(self.clone())
+  }
+
+  fn count(&self) -> usize {
+    self.count.load(Ordering::SeqCst)
+  }
+
+  /// Wait until no message tasks are running.
+  async fn wait_idle(&self) {
+    loop {
+      // Register before checking, so a task finishing in between can't be missed.
+      let idle = self.idle.notified();
+      pin_mut!(idle);
+      idle.as_mut().enable();
+      if self.count() == 0 {
+        return;
+      }
+      idle.await;
+    }
+  }
+}
+
+struct MessageTaskGuard(Arc<MessageTasks>);
+
+impl Drop for MessageTaskGuard {
+  fn drop(&mut self) {
+    if self.0.count.fetch_sub(1, Ordering::SeqCst) == 1 {
+      self.0.idle.notify_waiters();
+    }
+  }
+}
+
+/// Used to stop [ButtplugRemoteServer::start_with_reconnect] from bringing up new connections.
+#[derive(Default)]
+struct ReconnectStop {
+  notifier: Notify,
+  stopped: AtomicBool,
+}
+
+impl ReconnectStop {
+  fn stop(&self) {
+    self.stopped.store(true, Ordering::SeqCst);
+    self.notifier.notify_waiters();
+  }
+
+  /// Wait out `delay`, returning false if stopped before or during the wait.
+  async fn wait(&self, delay: Duration) -> bool {
+    // Register interest before checking, so a stop between the check and the wait isn't missed.
+    let stopped = self.notifier.notified();
+    if self.stopped.load(Ordering::SeqCst) {
+      return false;
+    }
+    select_biased! {
+      _ = stopped.fuse() => false,
+      _ = sleep(delay).fuse() => true,
+    }
+  }
+}
+
+/// Returns true for safety-critical messages that should be dispatched ahead of regular device
+/// commands, regardless of arrival order.
+fn is_high_priority_message(msg: &ButtplugClientMessage) -> bool {
+  matches!(
+    msg,
+    ButtplugClientMessage::StopAllDevices(_) | ButtplugClientMessage::StopDeviceCmd(_)
+  )
+}
+
+/// Splits incoming connector messages into high and low priority queues, so that a flood of device
+/// commands can't delay stop messages. Dropping the senders on exit signals connector
+/// disconnection to the server loop.
+async fn sort_connector_messages(
+  mut connector_receiver: mpsc::Receiver<ButtplugClientMessage>,
+  high_priority_sender: mpsc::Sender<ButtplugClientMessage>,
+  low_priority_sender: mpsc::Sender<ButtplugClientMessage>,
+  client_activity: Arc<ClientActivity>,
+) {
+  while let Some(client_message) = connector_receiver.recv().await {
+    client_activity
+      .pending_message_count
+      .fetch_add(1, Ordering::SeqCst);
+    let sender = if is_high_priority_message(&client_message) {
+      &high_priority_sender
+    } else {
+      &low_priority_sender
+    };
+    if sender.send(client_message).await.is_err() {
+      client_activity
+        .pending_message_count
+        .fetch_sub(1, Ordering::SeqCst);
+      break;
+    }
+  }
+}
+
+enum RateLimitDecision {
+  Allow,
+  Drop,
+  /// Drop, and report the burst to the server owner, with the number of drops so far.
+  DropAndReport(u64),
+}
+
+/// Fixed window rate limiter for incoming client messages.
+struct ClientRateLimiter {
+  max_messages: u32,
+  window: Duration,
+  window_start: Instant,
+  window_message_count: u32,
+  /// True if a message has been dropped in the current window.
+  dropping: bool,
+  /// Messages dropped since the current burst started.
+  drop_count: u64,
+}
+
+impl ClientRateLimiter {
+  fn new(max_messages: u32, window: Duration) -> Self {
+    Self {
+      max_messages,
+      window,
+      window_start: Instant::now(),
+      window_message_count: 0,
+      dropping: false,
+      drop_count: 0,
+    }
+  }
+
+  fn check(&mut self, now: Instant) -> RateLimitDecision {
+    if now.duration_since(self.window_start) >= self.window {
+      // A burst only carries over into the next window if the last one was dropping messages.
+      if !self.dropping {
+        self.drop_count = 0;
+      }
+      self.window_start = now;
+      self.window_message_count = 0;
+      self.dropping = false;
+    }
+    if self.window_message_count < self.max_messages {
+      self.window_message_count += 1;
+      return RateLimitDecision::Allow;
+    }
+    self.drop_count += 1;
+    if self.dropping {
+      RateLimitDecision::Drop
+    } else {
+      self.dropping = true;
+      RateLimitDecision::DropAndReport(self.drop_count)
+    }
+  }
+}
+
+/// Cap the actuator values of a client command at `max`. Other messages are returned as is.
+fn limit_intensity(msg: ButtplugClientMessage, max: f64) -> ButtplugClientMessage {
+  if max >= 1.0 {
+    return msg;
+  }
+  let id = msg.id();
+  let mut limited: ButtplugClientMessage = match msg {
+    ButtplugClientMessage::ScalarCmd(cmd) => message::ScalarCmd::new(
+      cmd.device_index(),
+      cmd
+        .scalars()
+        .iter()
+        .map(|s| message::ScalarSubcommand::new(s.index(), s.scalar().min(max), s.actuator_type()))
+        .collect(),
+    )
+    .into(),
+    ButtplugClientMessage::VibrateCmd(cmd) => message::VibrateCmd::new(
+      cmd.device_index(),
+      cmd
+        .speeds()
+        .iter()
+        .map(|s| message::VibrateSubcommand::new(s.index(), s.speed().min(max)))
+        .collect(),
+    )
+    .into(),
+    ButtplugClientMessage::RotateCmd(cmd) => message::RotateCmd::new(
+      cmd.device_index(),
+      cmd
+        .rotations()
+        .iter()
+        .map(|r| message::RotationSubcommand::new(r.index(), r.speed().min(max), r.clockwise()))
+        .collect(),
+    )
+    .into(),
+    msg => return msg,
+  };
+  limited.set_id(id);
+  limited
+}
+
+/// Name of the message type, i.e. the enum variant name.
+fn message_type_name<T: std::fmt::Debug>(msg: &T) -> String {
+  let debug = format!("{:?}", msg);
+  debug.split('(').next().unwrap_or(debug.as_str()).to_owned()
+}
+
+/// Peer address of the connector for log lines, or "unknown address" if it doesn't have one.
+fn peer_address_description<ConnectorType>(connector: &ConnectorType) -> String
+where
+  ConnectorType: ButtplugConnector<ButtplugServerMessage, ButtplugClientMessage>,
+{
+  connector.peer_address().map_or_else(
+    || "unknown address".to_owned(),
+    |address| address.to_string(),
+  )
+}
+
+/// Serialized size of a raw reading, in bytes. Sizes are measured as JSON, the only wire format
+/// this library has.
+#[cfg(feature = "serialize-json")]
+fn raw_reading_size(reading: &message::RawReading) -> usize {
+  serde_json::to_string(&[message::ButtplugCurrentSpecServerMessage::RawReading(
+    reading.clone(),
+  )])
+  .expect("Infallible serialization")
+  .len()
+}
+
+/// Split raw readings, both RawReadCmd replies and subscription readings, that serialize to more
+/// than `max_message_size` bytes into several readings that fit through the connector. All but
+/// the last piece are marked as [continued](message::RawReading::continued), and the client puts
+/// the data back together before passing it on. A piece always carries at least one byte, even if
+/// that doesn't fit.
+#[cfg(feature = "serialize-json")]
+fn split_raw_reading(
+  msg: ButtplugServerMessage,
+  max_message_size: usize,
+) -> Vec<ButtplugServerMessage> {
+  let reading = match msg {
+    ButtplugServerMessage::RawReading(reading) if raw_reading_size(&reading) > max_message_size => {
+      reading
+    }
+    msg => return vec![msg],
+  };
+  let new_piece = |data: Vec<u8>, continued: bool| {
+    let mut piece = message::RawReading::new(reading.device_index(), reading.endpoint(), data);
+    piece.set_id(reading.id());
+    piece.set_continued(continued);
+    piece
+  };
+  let piece_overhead = raw_reading_size(&new_piece(vec![], true));
+  let mut pieces = vec![];
+  let mut data = vec![];
+  let mut size = piece_overhead;
+  for &byte in reading.data() {
+    // Bytes are written as decimal numbers, separated by commas.
+    let byte_size = byte.to_string().len();
+    if !data.is_empty() && size + byte_size + 1 > max_message_size {
+      pieces.push(std::mem::take(&mut data));
+      size = piece_overhead;
+    }
+    size += byte_size + usize::from(!data.is_empty());
+    data.push(byte);
+  }
+  pieces.push(data);
+  let last = pieces.len() - 1;
+  pieces
+    .into_iter()
+    .enumerate()
+    .map(|(index, data)| new_piece(data, index != last).into())
+    .collect()
+}
+
+/// Without JSON there's no serialized form to measure, and so nothing to split.
+#[cfg(not(feature = "serialize-json"))]
+fn split_raw_reading(
+  msg: ButtplugServerMessage,
+  _max_message_size: usize,
+) -> Vec<ButtplugServerMessage> {
+  vec![msg]
+}
+
+/// Send a message to the client, after passing it to the outbound message hooks.
+async fn send_to_client<ConnectorType>(
+  outbound_message_hooks: &[OutboundMessageHook],
+  connector: &ConnectorType,
+  client_activity: &ClientActivity,
+  session_recorders: &SessionRecorders,
+  connector_retry: ButtplugConnectorRetryConfig,
+  msg: ButtplugServerMessage,
+) -> Result<(), ButtplugConnectorError>
+where
+  ConnectorType: ButtplugConnector<ButtplugServerMessage, ButtplugClientMessage>,
+{
+  let msgs = match connector.max_message_size() {
+    Some(max_message_size) => split_raw_reading(msg, max_message_size),
+    None => vec![msg],
+  };
+  for msg in msgs {
+    for hook in outbound_message_hooks {
+      hook(&msg);
+    }
+    session_recorders.record_server_message(&msg);
+    let mut retries = 0;
+    while let Err(err) = connector.send(msg.clone()).await {
+      if retries >= connector_retry.max_write_retries {
+        return Err(err);
+      }
+      retries += 1;
+      warn!(
+        error = ?err,
+        "Cannot send message to client, retrying ({}/{}).",
+        retries,
+        connector_retry.max_write_retries
+      );
+      sleep(connector_retry.retry_delay).await;
+    }
+    client_activity.messages_out.fetch_add(1, Ordering::SeqCst);
+  }
+  Ok(())
+}
+
+#[allow(clippy::too_many_arguments)]
+fn handle_client_message<ConnectorType>(
+  session_id: u64,
+  server: Arc<ButtplugServer>,
+  connector: Arc<ConnectorType>,
+  remote_event_sender: RemoteEventSender,
+  client_activity: Arc<ClientActivity>,
+  max_intensity: Arc<AtomicU64>,
+  message_tasks: Arc<MessageTasks>,
+  session_recorders: Arc<SessionRecorders>,
+  outbound_message_hooks: Arc<Vec<OutboundMessageHook>>,
+  connector_retry: ButtplugConnectorRetryConfig,
+  telemetry: &TelemetryState,
+  client_message: ButtplugClientMessage,
+) where
+  ConnectorType: ButtplugConnector<ButtplugServerMessage, ButtplugClientMessage> + 'static,
+{
+  trace!("Got message from connector: {:?}", client_message);
+  // Spans cover the spawned handling below, so tracing backends get the latency of each message.
+  let span = if telemetry.should_sample() {
+    span!(
+      Level::DEBUG,
+      "handle_client_message",
+      session_id,
+      message_id = client_message.id(),
+      message_type = %message_type_name(&client_message),
+      outcome = field::Empty
+    )
+  } else {
+    Span::none()
+  };
+  let task_guard = message_tasks.start();
+  let received_at = Instant::now();
+  async_manager::spawn(async move {
+    let _task_guard = task_guard;
+    let client_message = server.transform_message(client_message);
+    if let Err(e) = client_message.is_valid() {
+      error!(
+        "Message not valid from client at {}: {:?} - Error: {}",
+        peer_address_description(connector.as_ref()),
+        client_message,
+        e
+      );
+      Span::current().record("outcome", "error");
+      let mut err_msg = message::Error::from(ButtplugError::from(e));
+      err_msg.set_id(client_message.id());
+      if send_to_client(&outbound_message_hooks, connector.as_ref(), &client_activity, &session_recorders, connector_retry, err_msg.into())
+        .await
+        .is_err()
+      {
+        error!(
+          "Cannot send reply to client at {}, dropping and assuming remote server thread has exited.",
+          peer_address_description(connector.as_ref())
+        );
+        remote_event_sender.send_error("send_reply_to_client", false);
+      }
+      client_activity.reply_sent(received_at, true, &remote_event_sender);
+      return;
+    }
+    // Checked per message, so changes apply from the next command on.
+    let client_message = limit_intensity(
+      client_message,
+      f64::from_bits(max_intensity.load(Ordering::SeqCst)),
+    );
+    let device_command = ButtplugDeviceCommandMessageUnion::try_from(client_message.clone()).ok();
+    let device_index = device_command.as_ref().map(|msg| msg.device_index());
+    if let Some(device_command) = device_command {
+      if remote_event_sender.has_listeners()
+        && remote_event_sender
+          .send(ButtplugRemoteServerEvent::DeviceCommand(device_command))
+          .is_err()
+      {
+        error!("Cannot send event to owner, dropping and assuming local server thread has exited.");
+      }
+    }
+    // Until this session has done its handshake, it may be joining a server another session is
+    // already connected to.
+    let parse_fut = if !client_activity.handshake_done() {
+      server.parse_joining_session_message(session_id, client_message.clone())
+    } else {
+      server.parse_session_message(session_id, client_message.clone())
+    };
+    let result = match server.task_watchdog_timeout() {
+      Some(watchdog_timeout) => match timeout(watchdog_timeout, parse_fut).await {
+        Ok(result) => result,
+        Err(_) => {
+          // Timing out drops the future, which stops whatever it was stuck on.
+          error!(
+            message_id = client_message.id(),
+            message_type = %message_type_name(&client_message),
+            "Message handling did not finish within {:?}, abandoning it.",
+            watchdog_timeout
+          );
+          let error: ButtplugError =
+            ButtplugUnknownError::TaskWatchdogTriggered(watchdog_timeout).into();
+          if remote_event_sender.has_listeners()
+            && remote_event_sender
+              .send(ButtplugRemoteServerEvent::InternalError {
+                context: "task_watchdog_triggered".to_owned(),
+                error: error.clone(),
+              })
+              .is_err()
+          {
+            error!(
+              "Cannot send event to owner, dropping and assuming local server thread has exited."
+            );
+          }
+          let mut err_msg = message::Error::from(error);
+          err_msg.set_id(client_message.id());
+          Err(err_msg)
+        }
+      },
+      None => parse_fut.await,
+    };
+    if let Some(device_index) = device_index {
+      let event = match &result {
+        Ok(_) => ButtplugRemoteServerEvent::DeviceCommandSent(device_index, client_message.clone()),
+        Err(err) => {
+          ButtplugRemoteServerEvent::DeviceCommandFailed(device_index, err.original_error())
+        }
+      };
+      if remote_event_sender.has_listeners() && remote_event_sender.send(event).is_err() {
+        error!("Cannot send event to owner, dropping and assuming local server thread has exited.");
+      }
+    }
+    Span::current().record("outcome", if result.is_ok() { "ok" } else { "error" });
+    let error = result.is_err();
+    match result {
+      Ok(ret_msg) => {
+        if let ButtplugServerMessage::ServerInfo(server_info) = &ret_msg {
+          *client_activity
+            .negotiated_config
+            .lock()
+            .expect("Lock poisoned") = Some(NegotiatedConfig {
+            client_spec_version: server_info.message_version() as u32,
+            server_spec_version: BUTTPLUG_CURRENT_MESSAGE_SPEC_VERSION as u32,
+            codec: connector.codec_type(),
+            ping_timeout: Duration::from_millis(server_info.max_ping_time().into()),
+            max_message_size: connector.max_message_size(),
+          });
+        }
+        if let ButtplugClientMessage::RequestServerInfo(rsi) = client_message {
+          *client_activity
+            .connected_since
+            .lock()
+            .expect("Lock poisoned") = Some(Instant::now());
+          *client_activity.client_name.lock().expect("Lock poisoned") =
+            Some(rsi.client_name().clone());
+          if remote_event_sender.has_listeners()
+            && remote_event_sender
+              .send(ButtplugRemoteServerEvent::ClientConnected(
+                session_id,
+                rsi.client_name().clone(),
+              ))
+              .is_err()
+          {
+            error!(
+              "Cannot send event to owner, dropping and assuming local server thread has exited."
+            );
+          }
+        }
+        if send_to_client(&outbound_message_hooks, connector.as_ref(), &client_activity, &session_recorders, connector_retry, ret_msg)
+          .await
+          .is_err()
+        {
+          error!(
+            "Cannot send reply to client at {}, dropping and assuming remote server thread has exited.",
+            peer_address_description(connector.as_ref())
+          );
+          remote_event_sender.send_error("send_reply_to_client", false);
+        }
+      }
+      Err(err_msg) => {
+        if send_to_client(&outbound_message_hooks, connector.as_ref(), &client_activity, &session_recorders, connector_retry, err_msg.into())
+          .await
+          .is_err()
+        {
+          error!(
+            "Cannot send reply to client at {}, dropping and assuming remote server thread has exited.",
+            peer_address_description(connector.as_ref())
+          );
+          remote_event_sender.send_error("send_reply_to_client", false);
+        }
+      }
+    }
+    client_activity.reply_sent(received_at, error, &remote_event_sender);
+  }.instrument(span));
+}
+
+#[allow(clippy::too_many_arguments)]
 async fn run_server<ConnectorType>(
+  session_id: u64,
+  active_sessions: Arc<AtomicUsize>,
   server: Arc<ButtplugServer>,
-  remote_event_sender: broadcast::Sender<ButtplugRemoteServerEvent>,
+  remote_event_sender: RemoteEventSender,
   connector: ConnectorType,
-  mut connector_receiver: mpsc::Receiver<ButtplugClientMessage>,
-  disconnect_notifier: Arc<Notify>,
+  connector_receiver: mpsc::Receiver<ButtplugClientMessage>,
+  disconnect_signal: Arc<DisconnectSignal>,
+  device_list_announcer: Arc<watch::Sender<()>>,
+  client_activity: Arc<ClientActivity>,
+  max_intensity: Arc<AtomicU64>,
+  message_tasks: Arc<MessageTasks>,
+  connection_history: Arc<ConnectionHistory>,
+  session_recorders: Arc<SessionRecorders>,
+  outbound_message_hooks: Arc<Vec<OutboundMessageHook>>,
+  shutdown_callbacks: Arc<ShutdownCallbacks>,
+  connector_retry: ButtplugConnectorRetryConfig,
+  telemetry: Arc<TelemetryState>,
+  connector_timeouts: Arc<ConnectorTimeouts>,
 ) where
   ConnectorType: ButtplugConnector<ButtplugServerMessage, ButtplugClientMessage> + 'static,
 {
-  info!("Starting remote server loop");
+  // Each session has its own receiver, so announcements made while it's busy aren't lost.
+  let mut device_list_announcements = device_list_announcer.subscribe();
   let shared_connector = Arc::new(connector);
+  let timeout_connector = Arc::downgrade(&shared_connector);
+  connector_timeouts.register(
+    session_id,
+    Box::new(move |timeout| match timeout_connector.upgrade() {
+      Some(connector) => connector
+        .set_timeout(timeout)
+        .map_err(|err| err.to_string()),
+      None => Ok(()),
+    }),
+  );
+  let connected_at = Instant::now();
+  *client_activity
+    .connector_type_name
+    .lock()
+    .expect("Lock poisoned") = Some(type_name::<ConnectorType>());
+  let mut disconnect_reason = None;
+  active_sessions.fetch_add(1, Ordering::SeqCst);
+  info!(
+    peer_address = %peer_address_description(shared_connector.as_ref()),
+    session_id,
+    "Starting remote server loop"
+  );
   let server_receiver = server.event_stream();
   pin_mut!(server_receiver);
+  let device_update_receiver = server.device_update_stream();
+  pin_mut!(device_update_receiver);
+  let device_reconnect_failed_receiver = server.device_reconnect_failed_stream();
+  pin_mut!(device_reconnect_failed_receiver);
+  let device_limit_reached_receiver = server.device_limit_reached_stream();
+  pin_mut!(device_limit_reached_receiver);
+  let device_unstable_receiver = server.device_unstable_stream();
+  pin_mut!(device_unstable_receiver);
+  let (high_priority_sender, mut high_priority_receiver) = mpsc::channel(256);
+  let (low_priority_sender, mut low_priority_receiver) = mpsc::channel(256);
+  async_manager::spawn(sort_connector_messages(
+    connector_receiver,
+    high_priority_sender,
+    low_priority_sender,
+    client_activity.clone(),
+  ));
+  // The idle timer runs from the start of the connection until the first message arrives.
+  let mut last_activity = Instant::now();
+  let mut rate_limiter = server
+    .client_rate_limit()
+    .map(|(max_messages, window)| ClientRateLimiter::new(max_messages, window));
   loop {
-    select! {
-      connector_msg = connector_receiver.recv().fuse() => match connector_msg {
+    let idle_timeout = match server.client_idle_timeout() {
+      Some(timeout) => sleep_until((last_activity + timeout).into()).boxed(),
+      None => future::pending().boxed(),
+    };
+    // Branch order matters here: disconnects and stop messages are always checked before regular
+    // device commands and server events.
+    select_biased! {
+      _ = disconnect_signal.notifier.notified().fuse() => {
+        // Copied rather than taken, so every session sees the reason.
+        let reason = *disconnect_signal.reason.lock().expect("Lock poisoned");
+        disconnect_reason = reason;
+        info!(reason = ?reason, "Server disconnected via controller disappearance, exiting loop.");
+        if let Some(reason) = reason {
+          if remote_event_sender.has_listeners() && remote_event_sender.send(ButtplugRemoteServerEvent::ClientDisconnected(session_id, Some(reason))).is_err() {
+            warn!(event = "ClientDisconnected", "Cannot update remote about client disconnection");
+          }
+        }
+        break;
+      },
+      _ = device_list_announcements.changed().fuse() => {
+        // Clients that haven't done their handshake don't expect device events yet.
+        if !client_activity.handshake_done() {
+          continue;
+        }
+        for device_added in server.device_manager().device_added_messages() {
+          if send_to_client(&outbound_message_hooks, shared_connector.as_ref(), &client_activity, &session_recorders, connector_retry, device_added.into()).await.is_err() {
+            error!(peer_address = %peer_address_description(shared_connector.as_ref()), "Cannot send device list to client, dropping and assuming remote server thread has exited.");
+            remote_event_sender.send_error("send_device_list_to_client", false);
+            break;
+          }
+        }
+      },
+      connector_msg = high_priority_receiver.recv().fuse() => match connector_msg {
         None => {
-          info!("Connector disconnected, exiting loop.");
-          if remote_event_sender.receiver_count() > 0 && remote_event_sender.send(ButtplugRemoteServerEvent::ClientDisconnected).is_err() {
-            warn!("Cannot update remote about client disconnection");
+          info!(peer_address = %peer_address_description(shared_connector.as_ref()), "Connector disconnected, exiting loop.");
+          if remote_event_sender.has_listeners() && remote_event_sender.send(ButtplugRemoteServerEvent::ClientDisconnected(session_id, None)).is_err() {
+            warn!(event = "ClientDisconnected", "Cannot update remote about client disconnection");
           }
           break;
         }
         Some(client_message) => {
-          trace!("Got message from connector: {:?}", client_message);
-          let server_clone = server.clone();
-          let connector_clone = shared_connector.clone();
-          let remote_event_sender_clone = remote_event_sender.clone();
-          async_manager::spawn(async move {
-            if let Err(e) = client_message.is_valid() {
-              error!("Message not valid: {:?} - Error: {}", client_message, e);
-              let mut err_msg = message::Error::from(ButtplugError::from(e));
-              err_msg.set_id(client_message.id());
-              connector_clone.send(err_msg.into());
-              return;
+          last_activity = client_activity.message_received();
+          session_recorders.record_client_message(&client_message);
+          handle_client_message(session_id, server.clone(), shared_connector.clone(), remote_event_sender.clone(), client_activity.clone(), max_intensity.clone(), message_tasks.clone(), session_recorders.clone(), outbound_message_hooks.clone(), connector_retry, &telemetry, client_message)
+        }
+      },
+      connector_msg = low_priority_receiver.recv().fuse() => match connector_msg {
+        // The sorter drops both senders at the same time, so disconnection is handled on the high
+        // priority branch, which will always be polled first.
+        None => continue,
+        Some(client_message) => {
+          last_activity = client_activity.message_received();
+          session_recorders.record_client_message(&client_message);
+          let decision = rate_limiter.as_mut().map_or(RateLimitDecision::Allow, |limiter| limiter.check(last_activity));
+          if let RateLimitDecision::DropAndReport(drop_count) = decision {
+            warn!(message_id = client_message.id(), message_type = %message_type_name(&client_message), drop_count, "Client over rate limit, dropping messages.");
+            if remote_event_sender.has_listeners() && remote_event_sender.send(ButtplugRemoteServerEvent::RateLimitExceeded { message_id: client_message.id(), message_type: message_type_name(&client_message), drop_count }).is_err() {
+              error!(event = "RateLimitExceeded", "Cannot send event to owner, dropping and assuming local server thread has exited.");
+            }
+          }
+          if let RateLimitDecision::Allow = decision {
+            handle_client_message(session_id, server.clone(), shared_connector.clone(), remote_event_sender.clone(), client_activity.clone(), max_intensity.clone(), message_tasks.clone(), session_recorders.clone(), outbound_message_hooks.clone(), connector_retry, &telemetry, client_message)
+          } else {
+            client_activity.message_dropped(&remote_event_sender);
+            let mut err_msg = message::Error::from(ButtplugError::from(ButtplugMessageError::RateLimitExceeded));
+            err_msg.set_id(client_message.id());
+            if send_to_client(&outbound_message_hooks, shared_connector.as_ref(), &client_activity, &session_recorders, connector_retry, err_msg.into()).await.is_err() {
+              error!(message_id = client_message.id(), peer_address = %peer_address_description(shared_connector.as_ref()), "Cannot send reply to client, dropping and assuming remote server thread has exited.");
+              remote_event_sender.send_error("send_reply_to_client", false);
             }
-            match server_clone.parse_message(client_message.clone()).await {
-              Ok(ret_msg) => {
-                if let ButtplugClientMessage::RequestServerInfo(rsi) = client_message {
-                  if remote_event_sender_clone.receiver_count() > 0 && remote_event_sender_clone.send(ButtplugRemoteServerEvent::ClientConnected(rsi.client_name().clone())).is_err() {
-                    error!("Cannot send event to owner, dropping and assuming local server thread has exited.");
+          }
+        }
+      },
+      server_msg = server_receiver.next().fuse() => match server_msg {
+        None => {
+          info!("Server disconnected via server disappearance, exiting loop.");
+          remote_event_sender.send_error("server_event_stream_closed", true);
+          break;
+        }
+        // Clients have no message for this, so they're sent an error instead.
+        Some(ButtplugServerMessage::LaggedEvents(lagged)) => {
+          warn!(session_id, lagged_count = lagged.count(), "Server event stream fell behind, client missed events.");
+          if send_to_client(&outbound_message_hooks, shared_connector.as_ref(), &client_activity, &session_recorders, connector_retry, message::Error::from(lagged).into()).await.is_err() {
+            error!(peer_address = %peer_address_description(shared_connector.as_ref()), "Cannot send event to client, server disappeared, exiting remote server thread.");
+            remote_event_sender.send_error("send_event_to_client", true);
+            break;
+          }
+        }
+        Some(msg) => {
+          let message_id = msg.id();
+          let span = if telemetry.should_sample() {
+            span!(
+              Level::DEBUG,
+              "handle_server_event",
+              session_id,
+              message_id,
+              message_type = %message_type_name(&msg),
+              outcome = field::Empty
+            )
+          } else {
+            Span::none()
+          };
+          let sent = async {
+            if remote_event_sender.has_listeners() {
+              match &msg {
+                ButtplugServerMessage::DeviceAdded(da) => {
+                  if let Some(device_info) = server.device_manager().device_info(da.device_index()) {
+                    if remote_event_sender.send(ButtplugRemoteServerEvent::DeviceAdded(da.device_index(), da.device_name().clone(), device_info.identifier().address().clone(), device_info.display_name().clone())).is_err() {
+                      error!(event = "DeviceAdded", device_index = da.device_index(), "Cannot send event to owner, dropping and assuming local server thread has exited.");
+                    }
                   }
-                }
-                if connector_clone.send(ret_msg).await.is_err() {
-                  error!("Cannot send reply to server, dropping and assuming remote server thread has exited.");
-                }
-              },
-              Err(err_msg) => {
-                if connector_clone.send(err_msg.into()).await.is_err() {
-                  error!("Cannot send reply to server, dropping and assuming remote server thread has exited.");
-                }
+                },
+                ButtplugServerMessage::DeviceRemoved(dr) => {
+                 if remote_event_sender.send(ButtplugRemoteServerEvent::DeviceRemoved(dr.device_index())).is_err() {
+                   error!(event = "DeviceRemoved", device_index = dr.device_index(), "Cannot send event to owner, dropping and assuming local server thread has exited.");
+                 }
+                },
+                _ => {}
               }
             }
-          });
+            let sent = send_to_client(&outbound_message_hooks, shared_connector.as_ref(), &client_activity, &session_recorders, connector_retry, msg).await.is_ok();
+            Span::current().record("outcome", if sent { "ok" } else { "error" });
+            sent
+          }
+          .instrument(span)
+          .await;
+          if !sent {
+            error!(message_id, peer_address = %peer_address_description(shared_connector.as_ref()), "Cannot send event to client, server disappeared, exiting remote server thread.");
+            remote_event_sender.send_error("send_event_to_client", true);
+            break;
+          }
+        }
+      },
+      device_update = device_update_receiver.next().fuse() => {
+        if let Some((device_index, device_info)) = device_update {
+          if remote_event_sender.has_listeners() && remote_event_sender.send(ButtplugRemoteServerEvent::DeviceUpdated(device_index, Box::new(device_info))).is_err() {
+            error!(event = "DeviceUpdated", device_index, "Cannot send event to owner, dropping and assuming local server thread has exited.");
+          }
+        }
+      },
+      device_index = device_reconnect_failed_receiver.next().fuse() => {
+        if let Some(device_index) = device_index {
+          if remote_event_sender.has_listeners() && remote_event_sender.send(ButtplugRemoteServerEvent::DeviceReconnectFailed(device_index)).is_err() {
+            error!(event = "DeviceReconnectFailed", device_index, "Cannot send event to owner, dropping and assuming local server thread has exited.");
+          }
+        }
+      },
+      device_unstable = device_unstable_receiver.next().fuse() => {
+        if let Some((device_index, error_count)) = device_unstable {
+          if remote_event_sender.has_listeners() && remote_event_sender.send(ButtplugRemoteServerEvent::DeviceUnstable { device_index, error_count }).is_err() {
+            error!(event = "DeviceUnstable", device_index, "Cannot send event to owner, dropping and assuming local server thread has exited.");
+          }
         }
       },
-      _ = disconnect_notifier.notified().fuse() => {
-        info!("Server disconnected via controller disappearance, exiting loop.");
-        break;
+      limit_reached = device_limit_reached_receiver.next().fuse() => {
+        if limit_reached.is_some() && remote_event_sender.has_listeners() && remote_event_sender.send(ButtplugRemoteServerEvent::DeviceLimitReached).is_err() {
+          error!(event = "DeviceLimitReached", "Cannot send event to owner, dropping and assuming local server thread has exited.");
+        }
       },
-      server_msg = server_receiver.next().fuse() => match server_msg {
-        None => {
-          info!("Server disconnected via server disappearance, exiting loop.");
-          break;
+      _ = idle_timeout.fuse() => {
+        info!(peer_address = %peer_address_description(shared_connector.as_ref()), idle_timeout = ?server.client_idle_timeout(), "Client idle timeout reached, exiting loop.");
+        if remote_event_sender.has_listeners() && remote_event_sender.send(ButtplugRemoteServerEvent::ClientIdleTimeout).is_err() {
+          warn!(event = "ClientIdleTimeout", "Cannot update remote about client idle timeout");
         }
-        Some(msg) => {
-          if remote_event_sender.receiver_count() > 0 {
-            match &msg {
-              ButtplugServerMessage::DeviceAdded(da) => {
-                if let Some(device_info) = server.device_manager().device_info(da.device_index()) {
-                  if remote_event_sender.send(ButtplugRemoteServerEvent::DeviceAdded(da.device_index(), da.device_name().clone(), device_info.identifier().address().clone(), device_info.display_name().clone())).is_err() {
-                    error!("Cannot send event to owner, dropping and assuming local server thread has exited.");
-                  }
-                }
-              },
-              ButtplugServerMessage::DeviceRemoved(dr) => {
-               if remote_event_sender.send(ButtplugRemoteServerEvent::DeviceRemoved(dr.device_index())).is_err() {
-                 error!("Cannot send event to owner, dropping and assuming local server thread has exited.");
-               }
-              },
-              _ => {}
-            }
-          }
-          let connector_clone = shared_connector.clone();
-          if connector_clone.send(msg).await.is_err() {
-            error!("Server disappeared, exiting remote server thread.");
-          }
+        disconnect_reason = Some(DisconnectReason::IdleTimeout);
+        if remote_event_sender.has_listeners() && remote_event_sender.send(ButtplugRemoteServerEvent::ClientDisconnected(session_id, Some(DisconnectReason::IdleTimeout))).is_err() {
+          warn!(event = "ClientDisconnected", "Cannot update remote about client disconnection");
         }
+        break;
       },
     };
   }
-  if let Err(err) = server.disconnect().await {
-    error!("Error disconnecting server: {:?}", err);
+  *client_activity
+    .negotiated_config
+    .lock()
+    .expect("Lock poisoned") = None;
+  *client_activity
+    .connected_since
+    .lock()
+    .expect("Lock poisoned") = None;
+  *client_activity
+    .connector_type_name
+    .lock()
+    .expect("Lock poisoned") = None;
+  connection_history.record(ConnectionRecord {
+    client_name: client_activity
+      .client_name
+      .lock()
+      .expect("Lock poisoned")
+      .take(),
+    connected_at,
+    disconnected_at: Instant::now(),
+    disconnect_reason,
+    messages_in: client_activity.messages_in.load(Ordering::SeqCst),
+    messages_out: client_activity.messages_out.load(Ordering::SeqCst),
+  });
+  // Anything still queued is dropped with the session, so stop counting it as pending.
+  high_priority_receiver.close();
+  low_priority_receiver.close();
+  while high_priority_receiver.try_recv().is_ok() || low_priority_receiver.try_recv().is_ok() {
+    client_activity
+      .pending_message_count
+      .fetch_sub(1, Ordering::SeqCst);
   }
-  info!("Exiting remote server loop");
+  server.end_session(session_id);
+  // Other sessions may still be using the server, only the last one out disconnects it.
+  if active_sessions.fetch_sub(1, Ordering::SeqCst) == 1 {
+    if let Err(err) = server.disconnect().await {
+      error!(error = ?err, "Error disconnecting server");
+      remote_event_sender.send_error("server_disconnect", true);
+    }
+    if shutdown_callbacks.dropped.load(Ordering::SeqCst) {
+      shutdown_callbacks.run();
+    }
+  }
+  connector_timeouts.unregister(session_id);
+  info!(
+    peer_address = %peer_address_description(shared_connector.as_ref()),
+    "Exiting remote server loop"
+  );
 }
 
 impl Default for ButtplugRemoteServer {
@@ -155,61 +2092,1114 @@ impl Default for ButtplugRemoteServer {
   }
 }
 
-impl ButtplugRemoteServer {
-  pub fn new(server: ButtplugServer) -> Self {
-    let (event_sender, _) = broadcast::channel(256);
+/// Default capacity of the remote server event channel, see
+/// [ButtplugRemoteServerBuilder::event_channel_capacity].
+pub const DEFAULT_EVENT_CHANNEL_CAPACITY: usize = 256;
+
+/// Default number of finished sessions kept, see
+/// [ButtplugRemoteServerBuilder::connection_history_size].
+pub const DEFAULT_CONNECTION_HISTORY_SIZE: usize = 100;
+
+/// Configures and builds a [ButtplugRemoteServer].
+pub struct ButtplugRemoteServerBuilder {
+  server: Option<ButtplugServer>,
+  event_channel_capacity: usize,
+  event_channel_auto_resize: Option<usize>,
+  connection_history_size: usize,
+  preflight_check: bool,
+  connector_retry: ButtplugConnectorRetryConfig,
+  event_transform: Option<EventTransform>,
+  connection_quality: ConnectionQualityConfig,
+  idle_device_shutdown: Option<IdleShutdownPolicy>,
+  outbound_message_hooks: Vec<OutboundMessageHook>,
+}
+
+impl Default for ButtplugRemoteServerBuilder {
+  fn default() -> Self {
     Self {
+      server: None,
+      event_channel_capacity: DEFAULT_EVENT_CHANNEL_CAPACITY,
+      event_channel_auto_resize: None,
+      connection_history_size: DEFAULT_CONNECTION_HISTORY_SIZE,
+      preflight_check: false,
+      connector_retry: ButtplugConnectorRetryConfig::default(),
+      event_transform: None,
+      connection_quality: ConnectionQualityConfig::default(),
+      idle_device_shutdown: None,
+      outbound_message_hooks: vec![],
+    }
+  }
+}
+
+impl ButtplugRemoteServerBuilder {
+  /// Server to handle client messages with. If not set, a default [ButtplugServer] is used.
+  pub fn server(&mut self, server: ButtplugServer) -> &mut Self {
+    self.server = Some(server);
+    self
+  }
+
+  /// Number of events each subscriber can fall behind by. [ButtplugRemoteServer::event_stream]
+  /// subscribers that fall further behind than this end their stream, and
+  /// [ButtplugRemoteServer::bounded_event_stream] subscribers get an error. Treated as 1 if 0.
+  /// Can be changed later with [ButtplugRemoteServer::set_event_buffer_policy].
+  pub fn event_channel_capacity(&mut self, capacity: usize) -> &mut Self {
+    self.event_channel_capacity = capacity;
+    self
+  }
+
+  /// Grow the event channel capacity when subscribers fall behind, up to `max_capacity`, see
+  /// [ButtplugRemoteServer::set_event_channel_auto_resize].
+  pub fn event_channel_auto_resize(&mut self, max_capacity: usize) -> &mut Self {
+    self.event_channel_auto_resize = Some(max_capacity);
+    self
+  }
+
+  /// Number of finished client sessions kept for [ButtplugRemoteServer::connection_history],
+  /// oldest dropped first. 0 turns history off.
+  pub fn connection_history_size(&mut self, size: usize) -> &mut Self {
+    self.connection_history_size = size;
+    self
+  }
+
+  /// If true, check each connector with [ButtplugRemoteServer::test_connector_health] once it
+  /// connects, dropping connectors that fail instead of starting a session with them.
+  pub fn preflight_check(&mut self, preflight_check: bool) -> &mut Self {
+    self.preflight_check = preflight_check;
+    self
+  }
+
+  /// Retry messages that fail to send to the client, instead of ending the session on the first
+  /// failure.
+  pub fn connector_retry(&mut self, config: ButtplugConnectorRetryConfig) -> &mut Self {
+    self.connector_retry = config;
+    self
+  }
+
+  /// Run every event through `transform` before it's sent to [ButtplugRemoteServer::event_stream]
+  /// and [ButtplugRemoteServer::bounded_event_stream] subscribers, e.g. to add metadata or redact
+  /// fields. Events `transform` returns `None` for are dropped. Runs once per event on the server
+  /// loop, however many subscribers there are, so it should be quick.
+  pub fn with_event_transform<F>(&mut self, transform: F) -> &mut Self
+  where
+    F: Fn(ButtplugRemoteServerEvent) -> Option<ButtplugRemoteServerEvent> + Send + Sync + 'static,
+  {
+    self.event_transform = Some(Arc::new(transform));
+    self
+  }
+
+  /// Weights for [ButtplugRemoteServer::connection_quality], and the score below which
+  /// [ButtplugRemoteServerEvent::ConnectionQualityDegraded] is sent.
+  pub fn connection_quality(&mut self, config: ConnectionQualityConfig) -> &mut Self {
+    self.connection_quality = config;
+    self
+  }
+
+  /// Stop or disconnect devices clients haven't sent a command to within the policy's idle time,
+  /// so devices left running by a client that went quiet don't overheat. Checked by a background
+  /// task that runs until the remote server is dropped. Off by default.
+  pub fn idle_device_shutdown(&mut self, policy: IdleShutdownPolicy) -> &mut Self {
+    self.idle_device_shutdown = Some(policy);
+    self
+  }
+
+  /// Add a hook that is called with each message sent to clients, just before it's handed to the
+  /// connector. Hooks are called inline from the session loop, so they must not block.
+  pub fn on_outbound_message<F>(&mut self, hook: F) -> &mut Self
+  where
+    F: Fn(&ButtplugServerMessage) + Send + Sync + 'static,
+  {
+    self.outbound_message_hooks.push(Arc::new(hook));
+    self
+  }
+
+  pub fn finish(&mut self) -> ButtplugRemoteServer {
+    let server = self.server.take().unwrap_or_else(|| {
+      ButtplugServerBuilder::default()
+        .finish()
+        .expect("Default is infallible")
+    });
+    let event_sender = RemoteEventSender::new(
+      EventBufferPolicy::new(
+        self.event_channel_capacity.max(1),
+        OverflowPolicy::default(),
+        false,
+      ),
+      self.event_transform.clone(),
+    );
+    event_sender.set_auto_resize(self.event_channel_auto_resize);
+    let server = Arc::new(server);
+    if let Some(policy) = self.idle_device_shutdown {
+      async_manager::spawn(shut_down_idle_devices(Arc::downgrade(&server), policy));
+    }
+    ButtplugRemoteServer {
       event_sender,
-      server: Arc::new(server),
-      disconnect_notifier: Arc::new(Notify::new()),
+      server,
+      disconnect_signal: Arc::new(DisconnectSignal::default()),
+      device_list_announcer: Arc::new(watch::channel(()).0),
+      reconnect_stop: Arc::new(ReconnectStop::default()),
+      client_activity: Arc::new(Mutex::new(Arc::new(ClientActivity::new(
+        self.connection_quality,
+      )))),
+      connection_quality: self.connection_quality,
+      max_intensity: Arc::new(AtomicU64::new(1.0f64.to_bits())),
+      message_tasks: Arc::new(MessageTasks::default()),
+      connection_history: Arc::new(ConnectionHistory::new(self.connection_history_size)),
+      session_recorders: Arc::new(SessionRecorders::default()),
+      outbound_message_hooks: Arc::new(self.outbound_message_hooks.clone()),
+      shutdown_callbacks: Arc::new(ShutdownCallbacks::default()),
+      preflight_check: self.preflight_check,
+      connector_retry: self.connector_retry,
+      next_session_id: Arc::new(AtomicU64::new(0)),
+      active_sessions: Arc::new(AtomicUsize::new(0)),
+      telemetry: Arc::new(TelemetryState::default()),
+      connector_timeouts: Arc::new(ConnectorTimeouts::default()),
     }
   }
+}
+
+/// Apply `policy` to idle devices until the server is dropped.
+async fn shut_down_idle_devices(server: Weak<ButtplugServer>, policy: IdleShutdownPolicy) {
+  // Checking at half the idle time means devices are handled at most 1.5x the idle time after
+  // their last command.
+  let check_interval = (policy.idle_after() / 2).max(Duration::from_millis(1));
+  loop {
+    sleep(check_interval).await;
+    let Some(server) = server.upgrade() else {
+      break;
+    };
+    server.device_manager().shut_down_idle_devices(policy).await;
+  }
+}
+
+impl ButtplugRemoteServer {
+  pub fn new(server: ButtplugServer) -> Self {
+    ButtplugRemoteServerBuilder::default()
+      .server(server)
+      .finish()
+  }
+
+  /// Same as [ButtplugRemoteServer::new], with devices shut down once idle, see
+  /// [ButtplugRemoteServerBuilder::idle_device_shutdown].
+  pub fn with_idle_device_shutdown(server: ButtplugServer, policy: IdleShutdownPolicy) -> Self {
+    ButtplugRemoteServerBuilder::default()
+      .server(server)
+      .idle_device_shutdown(policy)
+      .finish()
+  }
 
   pub fn event_stream(&self) -> impl Stream<Item = ButtplugRemoteServerEvent> {
-    convert_broadcast_receiver_to_stream(self.event_sender.subscribe())
+    self.event_sender.subscribe(None)
+  }
+
+  /// Same as [ButtplugRemoteServer::event_stream], but the subscriber is listed under `name` in
+  /// [ButtplugRemoteServer::subscriber_names] until the stream is dropped. Useful for tracking
+  /// down subscribers that are never dropped.
+  pub fn event_stream_named(&self, name: &str) -> impl Stream<Item = ButtplugRemoteServerEvent> {
+    self.event_sender.subscribe(Some(name.to_owned()))
+  }
+
+  /// Names of live [ButtplugRemoteServer::event_stream_named] subscribers, sorted, each listed
+  /// once however many subscribers share it. Unnamed subscribers aren't listed, see
+  /// [ButtplugRemoteServer::num_event_subscribers] for the total.
+  pub fn subscriber_names(&self) -> Vec<String> {
+    self.event_sender.subscriber_names()
+  }
+
+  /// How events are buffered for [ButtplugRemoteServer::event_stream] subscribers.
+  pub fn event_buffer_policy(&self) -> EventBufferPolicy {
+    self.event_sender.policy()
+  }
+
+  /// Change how events are buffered for [ButtplugRemoteServer::event_stream] subscribers, e.g. to
+  /// give a slow UI more room, or to have late subscribers start with recent events. The capacity
+  /// also applies to [ButtplugRemoteServer::bounded_event_stream] subscribers created afterwards.
+  ///
+  /// Existing subscribers are moved over to the new buffer without missing any events, once
+  /// they've read the events already buffered for them.
+  pub fn set_event_buffer_policy(&self, policy: EventBufferPolicy) {
+    info!("Setting remote server event buffer policy to {:?}", policy);
+    self.event_sender.set_policy(policy);
+  }
+
+  /// Grow the event channel when [ButtplugRemoteServer::event_stream] subscribers fall behind, or
+  /// stop growing it if `max_capacity` is None. Every [EVENT_CHANNEL_RESIZE_INTERVAL], on the next
+  /// event sent, the channel checks whether subscribers skipped events since the last check. If
+  /// so, its capacity is doubled, up to `max_capacity`. The capacity is never shrunk
+  /// automatically.
+  ///
+  /// Growing replaces the channel the same way [ButtplugRemoteServer::set_event_buffer_policy]
+  /// does. Subscribers finish reading the old channel before moving to the new one, so events
+  /// aren't duplicated or lost by the move itself, but during the move a subscriber can still be
+  /// as far behind as the old capacity allows, and events it had already fallen behind on are
+  /// still skipped.
+  pub fn set_event_channel_auto_resize(&self, max_capacity: Option<usize>) {
+    self.event_sender.set_auto_resize(max_capacity);
+  }
+
+  /// Number of events [ButtplugRemoteServer::event_stream] subscribers have skipped by falling
+  /// more than the channel capacity behind, since the server was built, or
+  /// [ButtplugRemoteServer::reset_metrics] was last called.
+  pub fn events_discarded_count(&self) -> u64 {
+    self.event_sender.events_discarded()
+  }
+
+  /// Start statistics over, for A/B testing or looking at one session in isolation. Zeroes the
+  /// server's message and error counts and restarts its uptime (see
+  /// [ButtplugServer::reset_metrics]), and zeroes [ButtplugRemoteServer::events_discarded_count].
+  /// Connected clients, devices, and records in [ButtplugRemoteServer::connection_history] are
+  /// left as they are.
+  pub fn reset_metrics(&self) {
+    self.server.reset_metrics();
+    self.event_sender.reset_events_discarded();
+  }
+
+  /// Remote server events and server messages merged into one stream, for listening to both
+  /// without separate subscribers. Ends once both underlying streams have ended.
+  pub fn global_event_stream(&self) -> impl Stream<Item = AnyButtplugEvent> {
+    stream::select(
+      self.event_stream().map(AnyButtplugEvent::Remote),
+      self.server.event_stream().map(AnyButtplugEvent::Server),
+    )
+  }
+
+  /// The server client messages are handled by, for using [ButtplugServer] APIs like
+  /// [ButtplugServer::device_manager] while the remote server is running.
+  pub fn server(&self) -> Arc<ButtplugServer> {
+    self.server.clone()
+  }
+
+  /// Always give the device with the given address `preferred_index`, for setups that need
+  /// predictable indexes. Kept until cleared with
+  /// [ButtplugRemoteServer::clear_device_index_override], see
+  /// [ServerDeviceManager::override_device_index](super::device::ServerDeviceManager::override_device_index).
+  pub fn override_device_index(
+    &self,
+    address: &str,
+    preferred_index: u32,
+  ) -> Result<(), ButtplugError> {
+    self
+      .server
+      .device_manager()
+      .override_device_index(address, preferred_index)
+  }
+
+  /// Remove an index override set with [ButtplugRemoteServer::override_device_index]. Returns
+  /// false if the address didn't have one.
+  pub fn clear_device_index_override(&self, address: &str) -> bool {
+    self
+      .server
+      .device_manager()
+      .clear_device_index_override(address)
+  }
+
+  /// Number of live event subscribers, from [ButtplugRemoteServer::event_stream] and
+  /// [ButtplugRemoteServer::bounded_event_stream]. Useful when debugging events that never arrive.
+  ///
+  /// Subscribers count as soon as their stream is created, even if it's never polled. Events are
+  /// only built and sent while this is above 0, so an unpolled stream also keeps events queuing.
+  pub fn num_event_subscribers(&self) -> usize {
+    self.event_sender.receiver_count()
+  }
+
+  /// True while a client session is running, from a successful connect until the server loop
+  /// exits. With [ButtplugRemoteServer::start_accepting], true while any session is running.
+  pub fn is_running(&self) -> bool {
+    self.active_sessions.load(Ordering::SeqCst) > 0
+  }
+
+  /// Like [ButtplugRemoteServer::event_stream], but instead of skipping events (with only a log
+  /// message) when the subscriber falls too far behind, yields an
+  /// [ButtplugRemoteServerEventStreamError::Overflowed] error as its last item, so the subscriber
+  /// knows events were missed.
+  pub fn bounded_event_stream(
+    &self,
+  ) -> impl Stream<Item = Result<ButtplugRemoteServerEvent, ButtplugRemoteServerEventStreamError>>
+  {
+    let capacity = self.event_sender.policy().capacity().max(1);
+    let (receiver, overflowed) = self.event_sender.subscribe_bounded(capacity);
+    stream::unfold(Some(receiver), move |receiver| {
+      let overflowed = overflowed.clone();
+      async move {
+        let mut receiver = receiver?;
+        match receiver.recv().await {
+          Some(event) => Some((Ok(event), Some(receiver))),
+          None if overflowed.load(Ordering::SeqCst) => Some((
+            Err(ButtplugRemoteServerEventStreamError::Overflowed(capacity)),
+            None,
+          )),
+          None => None,
+        }
+      }
+    })
+  }
+
+  /// Pass every event from now on to `sink`, until the remote server is dropped. Any number of
+  /// sinks can be registered. Each sink is fed from its own task and [event_stream] subscriber
+  /// (listed as "event_sink" in [ButtplugRemoteServer::subscriber_names]), so a slow or failing
+  /// sink never holds up the server or other subscribers. A sink that falls behind misses events
+  /// the same way other subscribers do, see [EventBufferPolicy].
+  ///
+  /// [event_stream]: ButtplugRemoteServer::event_stream
+  pub fn mirror_events_to(&self, sink: Arc<dyn ButtplugEventSink + Send + Sync>) {
+    let mut events = Box::pin(self.event_sender.subscribe(Some("event_sink".to_owned())));
+    async_manager::spawn(async move {
+      while let Some(event) = events.next().await {
+        if let Err(err) = sink.on_event(&event).await {
+          warn!("Event sink failed to handle {:?}: {}", event, err);
+        }
+      }
+    });
+  }
+
+  /// Events for the device at `index`, for UIs that only care about one device. The stream ends
+  /// once the device is removed.
+  pub fn subscribe_to_device(&self, index: u32) -> impl Stream<Item = DeviceEvent> {
+    let device_events = self.event_stream().filter_map(move |event| {
+      future::ready(match event {
+        ButtplugRemoteServerEvent::DeviceAdded(device_index, ..) if device_index == index => {
+          Some(DeviceEvent::Connected)
+        }
+        ButtplugRemoteServerEvent::DeviceRemoved(device_index) if device_index == index => {
+          Some(DeviceEvent::Disconnected)
+        }
+        ButtplugRemoteServerEvent::DeviceCommandSent(device_index, msg)
+          if device_index == index =>
+        {
+          Some(DeviceEvent::CommandSent(msg))
+        }
+        ButtplugRemoteServerEvent::DeviceCommandFailed(device_index, err)
+          if device_index == index =>
+        {
+          Some(DeviceEvent::Error(err))
+        }
+        _ => None,
+      })
+    });
+    // Unfold instead of filtering, so the stream ends as soon as the device is removed rather than
+    // on the next event after that.
+    stream::unfold(
+      (Box::pin(device_events), false),
+      |(mut device_events, removed)| async move {
+        if removed {
+          return None;
+        }
+        let event = device_events.next().await?;
+        let removed = matches!(event, DeviceEvent::Disconnected);
+        Some((event, (device_events, removed)))
+      },
+    )
+  }
+
+  /// Send an event to [ButtplugRemoteServer::event_stream] listeners as if it came from the server,
+  /// for testing event consumers without devices or a client connection.
+  #[cfg(any(test, feature = "testing"))]
+  pub fn inject_server_event(&self, event: ButtplugRemoteServerEvent) {
+    if self.event_sender.send(event).is_err() {
+      debug!("No listeners for injected server event.");
+    }
+  }
+
+  /// Send an application defined payload to [ButtplugRemoteServer::event_stream] listeners, for
+  /// out of band signaling alongside server events (e.g. a session token being renewed). Fails if
+  /// no one is listening.
+  #[cfg(feature = "custom-events")]
+  pub fn emit_custom_event(&self, payload: serde_json::Value) -> Result<(), ButtplugError> {
+    self
+      .event_sender
+      .send(ButtplugRemoteServerEvent::Custom(payload))
+      .map_err(|_| ButtplugUnknownError::NoEventListeners.into())
   }
 
   pub fn start<ConnectorType>(
+    &self,
+    connector: ConnectorType,
+  ) -> impl Future<Output = Result<(), ButtplugServerConnectorError>>
+  where
+    ConnectorType: ButtplugConnector<ButtplugServerMessage, ButtplugClientMessage> + 'static,
+  {
+    self.start_session(connector, None, self.new_client_activity())
+  }
+
+  /// Like [ButtplugRemoteServer::start], but fails if the connector hasn't connected within
+  /// `timeout`, instead of waiting forever on a transport that never gets a connection. Calling
+  /// [ButtplugRemoteServer::disconnect] while waiting also cancels the connection attempt.
+  pub fn start_with_timeout<ConnectorType>(
+    &self,
+    connector: ConnectorType,
+    timeout: Duration,
+  ) -> impl Future<Output = Result<(), ButtplugServerConnectorError>>
+  where
+    ConnectorType: ButtplugConnector<ButtplugServerMessage, ButtplugClientMessage> + 'static,
+  {
+    self.start_session(connector, Some(timeout), self.new_client_activity())
+  }
+
+  /// Serve every connector from `connector_stream` at the same time, each in its own session on
+  /// the shared [ButtplugServer], so several clients can use the same devices. Connectors should
+  /// come from a transport that has already accepted a connection, as each is connected and run
+  /// as soon as it arrives.
+  ///
+  /// Sessions get their own session id in [ButtplugRemoteServerEvent::ClientConnected] and
+  /// [ButtplugRemoteServerEvent::ClientDisconnected], and one session ending doesn't affect the
+  /// others. The server is only disconnected, stopping devices, once no sessions are left.
+  /// [ButtplugRemoteServer::disconnect] and [ButtplugRemoteServer::disconnect_client] end every
+  /// session, and per client accessors like [ButtplugRemoteServer::connected_since] only track
+  /// sessions made with [ButtplugRemoteServer::start].
+  ///
+  /// Resolves once the stream has ended and all of its sessions have finished.
+  pub async fn start_accepting<S, ConnectorType>(&self, connector_stream: S)
+  where
+    S: Stream<Item = ConnectorType>,
+    ConnectorType: ButtplugConnector<ButtplugServerMessage, ButtplugClientMessage> + 'static,
+  {
+    pin_mut!(connector_stream);
+    // Finished sessions are reaped as they end, so long running acceptors don't keep every
+    // session handle around.
+    let mut sessions = FuturesUnordered::new();
+    loop {
+      let connector = select_biased! {
+        _ = sessions.select_next_some() => continue,
+        connector = connector_stream.next().fuse() => connector,
+      };
+      let Some(connector) = connector else {
+        break;
+      };
+      let session = self.start_session(
+        connector,
+        None,
+        Arc::new(ClientActivity::new(self.connection_quality)),
+      );
+      let event_sender = self.event_sender.clone();
+      let handle = async_manager::spawn_with_handle(async move {
+        if let Err(err) = session.await {
+          warn!(error = ?err, "Accepted connector failed to connect.");
+          event_sender.send_error("session_connect", false);
+        }
+      });
+      match handle {
+        Ok(handle) => sessions.push(handle),
+        Err(err) => error!(error = ?err, "Cannot spawn client session."),
+      }
+    }
+    while sessions.next().await.is_some() {}
+  }
+
+  /// Activity tracking for a session started with [ButtplugRemoteServer::start], which becomes the
+  /// one per client accessors report on.
+  fn new_client_activity(&self) -> Arc<ClientActivity> {
+    let client_activity = Arc::new(ClientActivity::new(self.connection_quality));
+    *self.client_activity.lock().expect("Lock poisoned") = client_activity.clone();
+    client_activity
+  }
+
+  /// Activity of the current client session, see [ButtplugRemoteServer::new_client_activity].
+  fn client_activity(&self) -> Arc<ClientActivity> {
+    self.client_activity.lock().expect("Lock poisoned").clone()
+  }
+
+  fn start_session<ConnectorType>(
     &self,
     mut connector: ConnectorType,
+    connect_timeout: Option<Duration>,
+    client_activity: Arc<ClientActivity>,
   ) -> impl Future<Output = Result<(), ButtplugServerConnectorError>>
   where
     ConnectorType: ButtplugConnector<ButtplugServerMessage, ButtplugClientMessage> + 'static,
   {
     let server_clone = self.server.clone();
     let event_sender_clone = self.event_sender.clone();
-    let disconnect_notifier = self.disconnect_notifier.clone();
+    let disconnect_signal = self.disconnect_signal.clone();
+    let device_list_announcer = self.device_list_announcer.clone();
+    let max_intensity = self.max_intensity.clone();
+    let message_tasks = self.message_tasks.clone();
+    let connection_history = self.connection_history.clone();
+    let session_recorders = self.session_recorders.clone();
+    let outbound_message_hooks = self.outbound_message_hooks.clone();
+    let shutdown_callbacks = self.shutdown_callbacks.clone();
+    let preflight_check = self.preflight_check;
+    let connector_retry = self.connector_retry;
+    let session_id = self.next_session_id.fetch_add(1, Ordering::SeqCst);
+    let active_sessions = self.active_sessions.clone();
+    let telemetry = self.telemetry.clone();
+    let connector_timeouts = self.connector_timeouts.clone();
+    connector.set_pretty_print_messages(server_clone.pretty_print_messages());
+    connector.set_message_transformers(server_clone.message_transformers());
+    if let Some(tls_config) = server_clone.tls_config() {
+      connector.set_tls_config(tls_config);
+    }
+    if let Some(timeout) = connector_timeouts.timeout() {
+      if let Err(err) = connector.set_timeout(timeout) {
+        warn!(error = ?err, "Cannot set connector timeout.");
+      }
+    }
     async move {
       let (connector_sender, connector_receiver) = mpsc::channel(256);
-      connector
-        .connect(connector_sender)
-        .await
-        .map_err(|e| ButtplugServerConnectorError::ConnectorError(format!("{:?}", e)))?;
+      match connect_timeout {
+        Some(connect_timeout) => {
+          // Register for disconnects before connecting, so one can't slip in between.
+          let cancelled = disconnect_signal.notifier.notified();
+          pin_mut!(cancelled);
+          cancelled.as_mut().enable();
+          select_biased! {
+            _ = cancelled.fuse() => {
+              return Err(ButtplugServerConnectorError::ConnectorError {
+                message: "connect cancelled by disconnect".to_owned(),
+                os_error_code: None,
+              });
+            }
+            result = timeout(connect_timeout, connector.connect(connector_sender)).fuse() => {
+              result.map_err(|_| ButtplugServerConnectorError::ConnectorError {
+                message: "connect timed out".to_owned(),
+                os_error_code: None,
+              })??;
+            }
+          }
+        }
+        None => connector.connect(connector_sender).await?,
+      }
+      if preflight_check {
+        match ButtplugRemoteServer::test_connector_health(&connector).await {
+          Ok(latency) => debug!(?latency, "Connector passed preflight check"),
+          Err(err) => {
+            let _ = connector.disconnect().await;
+            return Err(ButtplugServerConnectorError::ConnectorError {
+              message: err.to_string(),
+              os_error_code: None,
+            });
+          }
+        }
+      }
       run_server(
+        session_id,
+        active_sessions,
         server_clone,
         event_sender_clone,
         connector,
         connector_receiver,
-        disconnect_notifier,
+        disconnect_signal,
+        device_list_announcer,
+        client_activity,
+        max_intensity,
+        message_tasks,
+        connection_history,
+        session_recorders,
+        outbound_message_hooks,
+        shutdown_callbacks,
+        connector_retry,
+        telemetry,
+        connector_timeouts,
       )
       .await;
       Ok(())
     }
   }
 
+  /// Like [ButtplugRemoteServer::start], but keeps accepting connections until
+  /// [ButtplugRemoteServer::disconnect] is called. A connector is made with `factory` for each
+  /// attempt, and `policy` sets how long to wait between attempts and how many failed attempts in
+  /// a row are allowed before the last error is returned.
+  ///
+  /// Only the wait between attempts is cut short by [ButtplugRemoteServer::disconnect], a
+  /// connector that is still waiting for a client has to give up on its own.
+  pub async fn start_with_reconnect<F, ConnectorType>(
+    &self,
+    factory: F,
+    policy: RetryPolicy,
+  ) -> Result<(), ButtplugServerConnectorError>
+  where
+    F: Fn() -> ConnectorType,
+    ConnectorType: ButtplugConnector<ButtplugServerMessage, ButtplugClientMessage> + 'static,
+  {
+    self.reconnect_stop.stopped.store(false, Ordering::SeqCst);
+    let mut failed_attempts = 0;
+    loop {
+      match self.start(factory()).await {
+        Ok(()) => failed_attempts = 0,
+        Err(err) => {
+          failed_attempts += 1;
+          warn!(
+            attempt = failed_attempts,
+            error = ?err,
+            "Remote server connection attempt failed."
+          );
+          if policy
+            .max_attempts()
+            .is_some_and(|max_attempts| failed_attempts >= max_attempts)
+          {
+            return Err(err);
+          }
+        }
+      }
+      if !self.reconnect_stop.wait(policy.delay()).await {
+        return Ok(());
+      }
+    }
+  }
+
+  /// Cap all actuator values sent by clients at `max` (clamped to 0.0-1.0), without having to
+  /// rebuild the server. Takes effect from the next command on, so running commands aren't
+  /// changed until the client sends a new one. Stays in place across client sessions until
+  /// changed again.
+  pub fn set_max_intensity_for_session(&self, max: f64) {
+    self
+      .max_intensity
+      .store(max.clamp(0.0, 1.0).to_bits(), Ordering::SeqCst);
+  }
+
+  /// Connection quality of the current client session, from 0.0 (unusable) to 1.0 (perfect). Made
+  /// up of the average time taken to reply to client messages, the fraction of replies that were
+  /// errors, and the fraction of messages dropped by the rate limiter, weighted as set with
+  /// [ButtplugRemoteServerBuilder::connection_quality]. 1.0 until the client sends something, and
+  /// starts over with each session.
+  pub fn connection_quality(&self) -> f32 {
+    self.client_activity().quality.score()
+  }
+
+  /// Change the send timeout of the connectors of all running sessions, and of connectors started
+  /// from now on, without reconnecting. See [ButtplugConnector::set_timeout]. Useful for adapting
+  /// timeouts to observed latency. Every connector is updated even if one fails, in which case the
+  /// first error is returned.
+  pub fn set_connector_timeout(&self, timeout: Duration) -> Result<(), ButtplugError> {
+    self
+      .connector_timeouts
+      .set_timeout(timeout)
+      .map_err(|err| ButtplugUnknownError::ConnectorTimeoutNotSet(err).into())
+  }
+
+  /// Current cap on client actuator values, see
+  /// [ButtplugRemoteServer::set_max_intensity_for_session].
+  pub fn max_intensity_for_session(&self) -> f64 {
+    f64::from_bits(self.max_intensity.load(Ordering::SeqCst))
+  }
+
+  /// Name sent to clients during the handshake.
+  pub fn server_name(&self) -> String {
+    self.server.server_name()
+  }
+
+  /// Change the name sent to clients during the handshake, for instance to reflect the current
+  /// client's session. Only affects clients that connect (or handshake) after this is called.
+  pub fn set_server_name(&self, name: String) {
+    self.server.set_server_name(&name);
+  }
+
+  /// Time the most recent message from the current client was received, or None if it hasn't sent
+  /// a message yet.
+  pub fn last_client_message_at(&self) -> Option<Instant> {
+    *self
+      .client_activity()
+      .last_message_at
+      .lock()
+      .expect("Lock poisoned")
+  }
+
+  /// Time the current client completed its handshake (when
+  /// [ButtplugRemoteServerEvent::ClientConnected] was sent), or None if no client is connected.
+  pub fn connected_since(&self) -> Option<Instant> {
+    *self
+      .client_activity()
+      .connected_since
+      .lock()
+      .expect("Lock poisoned")
+  }
+
+  /// Type name of the connector the current session is running on (e.g.
+  /// `buttplug::core::connector::remote_connector::ButtplugRemoteConnector<...>`), or None if no
+  /// session is running. Meant for display in status UIs, the exact format isn't guaranteed.
+  pub fn connector_type_name(&self) -> Option<&'static str> {
+    *self
+      .client_activity()
+      .connector_type_name
+      .lock()
+      .expect("Lock poisoned")
+  }
+
+  /// Turns on telemetry, or changes its settings if it's already on, without restarting the
+  /// server. Metrics are served for Prometheus to scrape if
+  /// [TelemetryConfig::prometheus_port] is set, and only
+  /// [TelemetryConfig::trace_sampling_rate] of client messages and server events get a tracing
+  /// span.
+  pub fn enable_telemetry(&self, config: TelemetryConfig) -> Result<(), TelemetryError> {
+    self
+      .telemetry
+      .enable(config, self.server.clone(), self.active_sessions.clone())
+  }
+
+  /// Stops serving metrics, and goes back to tracing every client message and server event.
+  pub fn disable_telemetry(&self) {
+    self.telemetry.disable();
+  }
+
+  /// Address metrics are being served on for Prometheus, if they are. Useful when
+  /// [TelemetryConfig::prometheus_port] was set to 0.
+  pub fn prometheus_address(&self) -> Option<SocketAddr> {
+    self.telemetry.prometheus_address()
+  }
+
+  /// Time elapsed since the current client connected, or zero if no client is connected.
+  pub fn connection_duration(&self) -> Duration {
+    self
+      .connected_since()
+      .map_or(Duration::ZERO, |connected_since| connected_since.elapsed())
+  }
+
+  /// Number of messages received from the current client that the server hasn't started handling
+  /// yet. Connectors hold up to 256 messages, so a count approaching that means incoming messages
+  /// are about to be dropped or delayed.
+  pub fn pending_client_message_count(&self) -> usize {
+    self
+      .client_activity()
+      .pending_message_count
+      .load(Ordering::SeqCst)
+  }
+
+  /// How long successful commands to the device at the given index took, from the server
+  /// receiving them until the device acknowledged them. None if no commands have succeeded yet.
+  /// Useful for finding devices that are slow to respond.
+  #[cfg(feature = "metrics")]
+  pub fn device_command_latency_histogram(&self, index: u32) -> Option<LatencyHistogram> {
+    self
+      .server
+      .device_manager()
+      .command_latency_histogram(index)
+  }
+
+  /// Summary of the server, current client and connected devices, for diagnostic tools. Devices
+  /// only have a last command time if [ButtplugServerBuilder::track_command_statistics] is on.
+  pub fn status_report(&self) -> StatusReport {
+    build_status_report(&self.server, &self.client_activity())
+  }
+
+  /// Serve server status over HTTP on `addr`, as JSON at `/info`, e.g. for liveness probes. With
+  /// the `metrics` feature, the metrics from [ButtplugRemoteServer::enable_telemetry] are also
+  /// served at `/metrics`, in the Prometheus text format. Other paths get a 404. The endpoint runs
+  /// until stopped with [HttpInfoHandle::stop], independent of client sessions.
+  #[cfg(feature = "http-info")]
+  pub fn serve_http_info_endpoint(
+    &self,
+    addr: SocketAddr,
+  ) -> Result<HttpInfoHandle, std::io::Error> {
+    let server = self.server.clone();
+    let client_activity = self.client_activity.clone();
+    #[cfg(feature = "metrics")]
+    let (metrics_server, active_sessions) = (self.server.clone(), self.active_sessions.clone());
+    http_info::start_http_info_endpoint(
+      addr,
+      HttpInfoSource {
+        status: Box::new(move || {
+          build_status_report(&server, &client_activity.lock().expect("Lock poisoned"))
+        }),
+        #[cfg(feature = "metrics")]
+        metrics: Box::new(move || {
+          prometheus_metrics(&metrics_server, active_sessions.load(Ordering::SeqCst))
+        }),
+      },
+    )
+  }
+
+  /// Number of client sessions that have finished since the remote server was built. Unlike
+  /// [ButtplugRemoteServer::connection_history], this counts every session, however many the
+  /// history keeps.
+  pub fn sessions_completed(&self) -> u64 {
+    self.connection_history.completed.load(Ordering::SeqCst)
+  }
+
+  /// The most recent finished client sessions, oldest first, up to
+  /// [ButtplugRemoteServerBuilder::connection_history_size] of them. See
+  /// [ButtplugRemoteServer::sessions_completed] for the total.
+  pub fn connection_history(&self) -> Vec<ConnectionRecord> {
+    self
+      .connection_history
+      .records
+      .lock()
+      .expect("Lock poisoned")
+      .iter()
+      .cloned()
+      .collect()
+  }
+
+  /// Check that a connected connector can pass messages, see
+  /// [ButtplugConnector::check_health], returning how long the check took. Nothing is sent to the
+  /// client, as the protocol has no server initiated messages a client would answer.
+  pub async fn test_connector_health<ConnectorType>(
+    connector: &ConnectorType,
+  ) -> Result<Duration, ButtplugError>
+  where
+    ConnectorType: ButtplugConnector<ButtplugServerMessage, ButtplugClientMessage>,
+  {
+    let started_at = Instant::now();
+    connector
+      .check_health()
+      .await
+      .map_err(|err| ButtplugUnknownError::ConnectorUnhealthy(err.to_string()))?;
+    Ok(started_at.elapsed())
+  }
+
+  /// Start recording the messages passed between the server and its clients. Recording stops when
+  /// the returned recorder is dropped.
+  pub fn record_session(&self) -> SessionRecorder {
+    self.session_recorders.add()
+  }
+
+  /// Register cleanup to run when the server shuts down, e.g. flushing metrics or closing files.
+  /// Callbacks run once, in registration order, after [ButtplugRemoteServer::shutdown] (or one of
+  /// its variants) has shut down the server. If the remote server is dropped instead, they run
+  /// once the last client session has disconnected the server.
+  pub fn on_shutdown(&self, callback: impl FnOnce() + Send + 'static) {
+    self
+      .shutdown_callbacks
+      .callbacks
+      .lock()
+      .expect("Lock poisoned")
+      .push(Box::new(callback));
+  }
+
+  /// Settings negotiated with the current client during the handshake, or None if no client has
+  /// completed a handshake.
+  pub fn negotiated_config(&self) -> Option<NegotiatedConfig> {
+    *self
+      .client_activity()
+      .negotiated_config
+      .lock()
+      .expect("Lock poisoned")
+  }
+
+  /// Version and features of the library the server was built with, for showing in admin UIs or
+  /// bug reports.
+  pub fn server_build_info(&self) -> BuildInfo {
+    BuildInfo::current()
+  }
+
+  /// Stop the device at the given index and block further client commands to it, see
+  /// [ButtplugServer::disable_device].
+  pub async fn disable_device(&self, index: u32) -> Result<(), ButtplugError> {
+    self.server.disable_device(index).await
+  }
+
+  /// Allow client commands to a device blocked with [ButtplugRemoteServer::disable_device] again.
+  pub fn enable_device(&self, index: u32) -> Result<(), ButtplugError> {
+    self.server.enable_device(index)
+  }
+
+  /// End the current client session without shutting down the server. `reason` is reported in
+  /// the [ButtplugRemoteServerEvent::ClientDisconnected] event.
+  pub async fn disconnect_client(&self, reason: DisconnectReason) {
+    self.disconnect_signal.disconnect(Some(reason));
+  }
+
+  /// Send a [DeviceAdded](message::DeviceAdded) message for every connected device to all clients
+  /// that have completed their handshake, so a client taking over a session can catch up on device
+  /// state without sending [RequestDeviceList](message::RequestDeviceList). Clients may get
+  /// DeviceAdded for devices they already know about.
+  pub fn announce_device_list_to_client(&self) {
+    self.device_list_announcer.send_replace(());
+  }
+
+  /// End the client session once `duration` has passed, for time limited sessions like rentals or
+  /// demos. When time runs out, all devices are stopped,
+  /// [ButtplugRemoteServerEvent::SessionExpired] is sent, and the client is disconnected with
+  /// [DisconnectReason::SessionExpired].
+  ///
+  /// Nothing happens until the returned future is polled, and dropping it cancels the timer. To
+  /// run it in the background, spawn it with
+  /// [async_manager::spawn_with_handle](crate::util::async_manager::spawn_with_handle) and drop
+  /// the handle to cancel.
+  pub fn close_after(&self, duration: Duration) -> impl Future<Output = ()> {
+    let server = self.server.clone();
+    let event_sender = self.event_sender.clone();
+    let disconnect_signal = self.disconnect_signal.clone();
+    async move {
+      sleep(duration).await;
+      info!(
+        "Session time of {:?} expired, ending client session.",
+        duration
+      );
+      if let Err(err) = server.device_manager().stop_all_devices().await {
+        error!(error = ?err, "Cannot stop devices for expired session.");
+      }
+      if event_sender
+        .send(ButtplugRemoteServerEvent::SessionExpired)
+        .is_err()
+      {
+        debug!("No listeners for session expired event.");
+      }
+      disconnect_signal.disconnect(Some(DisconnectReason::SessionExpired));
+    }
+  }
+
+  /// Stop all devices and drop the current client, then wait for a client to connect again, using
+  /// [DEFAULT_FORCE_RECONNECT_TIMEOUT]. Useful for getting a client with inconsistent state to
+  /// start over.
+  pub async fn force_reconnect(&self) -> Result<(), ButtplugError> {
+    self
+      .force_reconnect_with_timeout(DEFAULT_FORCE_RECONNECT_TIMEOUT)
+      .await
+  }
+
+  /// Stop all devices and drop the current client, then wait up to `timeout_duration` for a
+  /// client to connect again. The session ends with a
+  /// [ButtplugRemoteServerEvent::ClientDisconnected] event with
+  /// [DisconnectReason::ForcedReconnect], and as with any other disconnection,
+  /// [ButtplugRemoteServer::start] has to be called again to accept the new connection.
+  pub async fn force_reconnect_with_timeout(
+    &self,
+    timeout_duration: Duration,
+  ) -> Result<(), ButtplugError> {
+    // Subscribe before disconnecting, so a fast reconnect can't be missed.
+    let mut client_connected = self
+      .event_stream()
+      .filter(|event| {
+        future::ready(matches!(
+          event,
+          ButtplugRemoteServerEvent::ClientConnected(..)
+        ))
+      })
+      .boxed();
+    self.server.device_manager().stop_all_devices().await?;
+    self
+      .disconnect_signal
+      .disconnect(Some(DisconnectReason::ForcedReconnect));
+    timeout(timeout_duration, client_connected.next())
+      .await
+      .map_err(|_| ButtplugUnknownError::ReconnectTimedOut(timeout_duration))?;
+    Ok(())
+  }
+
+  /// Drop the current client, and stop [ButtplugRemoteServer::start_with_reconnect] from accepting
+  /// new ones.
   pub async fn disconnect(&self) -> Result<(), ButtplugError> {
-    self.disconnect_notifier.notify_waiters();
+    self.reconnect_stop.stop();
+    self.disconnect_signal.disconnect(None);
     Ok(())
   }
 
+  /// Shut down the server, then run any [ButtplugRemoteServer::on_shutdown] callbacks.
   pub async fn shutdown(&self) -> Result<(), ButtplugError> {
-    self.server.shutdown().await?;
-    Ok(())
+    let result = self.server.shutdown().await;
+    self.shutdown_callbacks.run();
+    result.map(|_| ())
+  }
+
+  /// Disconnect the current client and shut down the server, failing if shutdown (stopping
+  /// devices, tearing down comm managers) takes longer than `timeout`.
+  pub async fn shutdown_with_timeout(
+    &self,
+    timeout_duration: Duration,
+  ) -> Result<(), ButtplugError> {
+    self.disconnect_signal.disconnect(None);
+    timeout(timeout_duration, self.shutdown())
+      .await
+      .map_err(|_| ButtplugUnknownError::ShutdownTimedOut(timeout_duration))?
+  }
+
+  /// Disconnect all clients, then stop all devices and shut down the server. Clients are
+  /// disconnected first so they can't start devices back up once they've been stopped. Client
+  /// messages already being handled get up to `stop_timeout` to finish, then devices get up to
+  /// `stop_timeout` to stop. Shutdown happens even if stopping fails or times out, in which case
+  /// the stop error is returned once it's done.
+  pub async fn shutdown_all_devices_then_disconnect(
+    &self,
+    stop_timeout: Duration,
+  ) -> Result<(), ButtplugError> {
+    self.disconnect_signal.disconnect(None);
+    if timeout(stop_timeout, self.message_tasks.wait_idle())
+      .await
+      .is_err()
+    {
+      warn!(
+        abandoned_tasks = self.message_tasks.count(),
+        "Client messages still running after {:?}, stopping devices anyway.", stop_timeout
+      );
+    }
+    let stop_result = timeout(
+      stop_timeout,
+      self.server.device_manager().stop_all_devices(),
+    )
+    .await
+    .unwrap_or_else(|_| Err(ButtplugUnknownError::StopDevicesTimedOut(stop_timeout).into()));
+    if let Err(err) = &stop_result {
+      warn!(
+        "Cannot stop all devices before shutdown, shutting down anyway: {}",
+        err
+      );
+    }
+    let shutdown_result = self.shutdown().await;
+    stop_result.and(shutdown_result).map(|_| ())
+  }
+
+  /// Disconnect all clients, then wait up to `drain_timeout` for client messages that are already
+  /// being handled to finish and get their replies before shutting down the server. Messages
+  /// still running at the timeout are abandoned, and shutdown goes ahead anyway.
+  pub async fn shutdown_graceful(&self, drain_timeout: Duration) -> Result<(), ButtplugError> {
+    self.disconnect_signal.disconnect(None);
+    if timeout(drain_timeout, self.message_tasks.wait_idle())
+      .await
+      .is_err()
+    {
+      warn!(
+        abandoned_tasks = self.message_tasks.count(),
+        "Client messages still running after {:?}, abandoning them and shutting down.",
+        drain_timeout
+      );
+    }
+    self.shutdown().await
+  }
+
+  /// Wait for Ctrl-C (or SIGTERM on unix platforms), then shut down the server using
+  /// [DEFAULT_SHUTDOWN_TIMEOUT]. Meant to be run alongside [ButtplugRemoteServer::start] in
+  /// command line applications, so devices are stopped when the process is killed.
+  #[cfg(feature = "tokio-runtime")]
+  pub async fn shutdown_on_signal(&self) -> Result<(), ButtplugError> {
+    wait_for_shutdown_signal().await;
+    info!("Shutdown signal received, shutting down server.");
+    self.shutdown_with_timeout(DEFAULT_SHUTDOWN_TIMEOUT).await
+  }
+}
+
+#[cfg(all(feature = "tokio-runtime", unix))]
+async fn wait_for_shutdown_signal() {
+  use tokio::signal::unix::{signal, SignalKind};
+  let terminate = async {
+    match signal(SignalKind::terminate()) {
+      Ok(mut sigterm) => {
+        sigterm.recv().await;
+      }
+      Err(err) => {
+        error!("Cannot listen for SIGTERM: {:?}", err);
+        future::pending::<()>().await
+      }
+    }
+  };
+  futures::select! {
+    _ = wait_for_ctrl_c().fuse() => {},
+    _ = terminate.fuse() => {},
+  }
+}
+
+#[cfg(all(feature = "tokio-runtime", not(unix)))]
+async fn wait_for_shutdown_signal() {
+  wait_for_ctrl_c().await
+}
+
+#[cfg(feature = "tokio-runtime")]
+async fn wait_for_ctrl_c() {
+  // If we can't listen for the signal, never shut down rather than shutting down immediately.
+  if let Err(err) = tokio::signal::ctrl_c().await {
+    error!("Cannot listen for Ctrl-C: {:?}", err);
+    future::pending::<()>().await
   }
 }
 
 impl Drop for ButtplugRemoteServer {
   fn drop(&mut self) {
-    self.disconnect_notifier.notify_waiters();
+    self
+      .shutdown_callbacks
+      .dropped
+      .store(true, Ordering::SeqCst);
+    self.disconnect_signal.disconnect(None);
+    // With no session running, nothing else is left to run the callbacks.
+    if self.active_sessions.load(Ordering::SeqCst) == 0 {
+      self.shutdown_callbacks.run();
+    }
   }
 }
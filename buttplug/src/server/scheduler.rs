@@ -0,0 +1,113 @@
+// Buttplug Rust Source Code File - See https://buttplug.io for more info.
+//
+// Copyright 2016-2023 Nonpolynomial Labs LLC. All rights reserved.
+//
+// Licensed under the BSD 3-Clause license. See LICENSE file in the project root
+// for full license information.
+
+//! Running client messages on a [ButtplugServer] at a given wall clock time.
+
+use super::ButtplugServer;
+use crate::{core::message::ButtplugClientMessage, util::async_manager};
+use chrono::{DateTime, Utc};
+use std::sync::Arc;
+use tokio::time::{sleep_until, Instant};
+use tokio_util::sync::CancellationToken;
+
+/// A message scheduled with [ButtplugServer::schedule_at]. Dropping the handle does not cancel the
+/// schedule.
+#[derive(Debug, Clone)]
+pub struct ScheduleHandle {
+  cancellation_token: CancellationToken,
+}
+
+impl ScheduleHandle {
+  /// Remove the schedule, if the message hasn't been run yet.
+  pub fn cancel(&self) {
+    self.cancellation_token.cancel();
+  }
+
+  /// True if the schedule was cancelled, or the message has already been run.
+  pub fn is_finished(&self) -> bool {
+    self.cancellation_token.is_cancelled()
+  }
+}
+
+impl ButtplugServer {
+  /// Run a client message through [ButtplugServer::parse_message] at the given time. Times in the
+  /// past run immediately. Errors from the message are logged, as there is no client waiting on
+  /// the reply.
+  ///
+  /// The schedule only holds a weak reference to the server, so it is dropped if the server is.
+  pub fn schedule_at(
+    self: &Arc<Self>,
+    datetime: DateTime<Utc>,
+    cmd: ButtplugClientMessage,
+  ) -> ScheduleHandle {
+    let cancellation_token = CancellationToken::new();
+    let token = cancellation_token.clone();
+    let server = Arc::downgrade(self);
+    // The wait is computed once up front, so later changes to the system clock aren't taken into
+    // account.
+    let wait = (datetime - Utc::now()).to_std().unwrap_or_default();
+    let deadline = Instant::now() + wait;
+    async_manager::spawn(async move {
+      tokio::select! {
+        _ = token.cancelled() => {
+          debug!("Scheduled message {:?} cancelled.", cmd);
+          return;
+        }
+        _ = sleep_until(deadline) => {}
+      }
+      token.cancel();
+      if let Some(server) = server.upgrade() {
+        if let Err(err) = server.parse_message(cmd).await {
+          error!("Scheduled message failed: {:?}", err);
+        }
+      }
+    });
+    ScheduleHandle { cancellation_token }
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+  use crate::core::message::{RequestServerInfo, BUTTPLUG_CURRENT_MESSAGE_SPEC_VERSION};
+  use std::time::Duration;
+
+  fn request_server_info() -> ButtplugClientMessage {
+    RequestServerInfo::new("Test Client", BUTTPLUG_CURRENT_MESSAGE_SPEC_VERSION).into()
+  }
+
+  #[test]
+  fn test_server_schedule_at() {
+    async_manager::block_on(async {
+      let server = Arc::new(ButtplugServer::default());
+      let handle = server.schedule_at(
+        Utc::now() + chrono::Duration::milliseconds(50),
+        request_server_info(),
+      );
+      assert!(!handle.is_finished());
+      assert!(!server.connected());
+      tokio::time::sleep(Duration::from_millis(200)).await;
+      assert!(handle.is_finished());
+      assert!(server.connected());
+    });
+  }
+
+  #[test]
+  fn test_server_schedule_at_cancel() {
+    async_manager::block_on(async {
+      let server = Arc::new(ButtplugServer::default());
+      let handle = server.schedule_at(
+        Utc::now() + chrono::Duration::milliseconds(50),
+        request_server_info(),
+      );
+      handle.cancel();
+      tokio::time::sleep(Duration::from_millis(200)).await;
+      assert!(handle.is_finished());
+      assert!(!server.connected());
+    });
+  }
+}
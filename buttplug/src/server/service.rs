@@ -0,0 +1,74 @@
+// Buttplug Rust Source Code File - See https://buttplug.io for more info.
+//
+// Copyright 2016-2023 Nonpolynomial Labs LLC. All rights reserved.
+//
+// Licensed under the BSD 3-Clause license. See LICENSE file in the project root
+// for full license information.
+
+//! [tower_service::Service] adapter for [ButtplugServer], for embedding in tower based stacks.
+
+use super::ButtplugServer;
+use crate::core::message::{self, ButtplugClientMessage, ButtplugServerMessage};
+use futures::future::BoxFuture;
+use std::{
+  sync::Arc,
+  task::{Context, Poll},
+};
+use tower_service::Service;
+
+/// Serves client messages using [ButtplugServer::parse_message]. Created with
+/// [ButtplugServer::as_service]. Clones share the same server.
+#[derive(Clone)]
+pub struct ButtplugServerService {
+  server: Arc<ButtplugServer>,
+}
+
+impl ButtplugServerService {
+  pub fn new(server: Arc<ButtplugServer>) -> Self {
+    Self { server }
+  }
+}
+
+impl Service<ButtplugClientMessage> for ButtplugServerService {
+  type Response = ButtplugServerMessage;
+  type Error = message::Error;
+  type Future = BoxFuture<'static, Result<ButtplugServerMessage, message::Error>>;
+
+  fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+    // The server queues work internally, so it's always ready for another message.
+    Poll::Ready(Ok(()))
+  }
+
+  fn call(&mut self, msg: ButtplugClientMessage) -> Self::Future {
+    self.server.parse_message(msg)
+  }
+}
+
+impl ButtplugServer {
+  /// Wrap the server in a [tower_service::Service] that answers client messages.
+  pub fn as_service(self: &Arc<Self>) -> ButtplugServerService {
+    ButtplugServerService::new(self.clone())
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+  use crate::{
+    core::message::{RequestServerInfo, BUTTPLUG_CURRENT_MESSAGE_SPEC_VERSION},
+    util::async_manager,
+  };
+
+  #[test]
+  fn test_server_service() {
+    async_manager::block_on(async {
+      let server = Arc::new(ButtplugServer::default());
+      let mut service = server.as_service();
+      let reply = service
+        .call(RequestServerInfo::new("Test Client", BUTTPLUG_CURRENT_MESSAGE_SPEC_VERSION).into())
+        .await;
+      assert!(matches!(reply, Ok(ButtplugServerMessage::ServerInfo(_))));
+      assert!(server.connected());
+    });
+  }
+}
@@ -0,0 +1,255 @@
+// Buttplug Rust Source Code File - See https://buttplug.io for more info.
+//
+// Copyright 2016-2023 Nonpolynomial Labs LLC. All rights reserved.
+//
+// Licensed under the BSD 3-Clause license. See LICENSE file in the project root
+// for full license information.
+
+//! Structured logs of what happened during a client session, for analysis after it ends.
+
+use super::ButtplugServer;
+use crate::core::message::{ButtplugClientMessage, ButtplugServerMessage, Error};
+use futures::{pin_mut, Stream, StreamExt};
+use getset::{CopyGetters, Getters};
+use serde::Serialize;
+use std::{
+  collections::VecDeque,
+  sync::{Arc, Mutex},
+  time::{Duration, SystemTime},
+};
+
+/// Most events kept in a single session's log. Once it's full, the oldest events are dropped to
+/// make room for new ones.
+pub const MAX_SESSION_LOG_EVENTS: usize = 10_000;
+
+/// Most session logs kept at once. Starting a session past this drops the log of the session that
+/// started first.
+pub const MAX_SESSION_LOGS: usize = 16;
+
+/// Something that happened during a session, see [SessionEvent].
+#[derive(Debug, Clone, Serialize)]
+pub enum SessionEventKind {
+  /// A client completed the handshake, starting the session.
+  ClientConnected {
+    client_name: String,
+  },
+  ClientDisconnected,
+  DeviceAdded {
+    device_index: u32,
+    device_name: String,
+  },
+  DeviceRemoved {
+    device_index: u32,
+  },
+  /// A client message was handled successfully, taking `duration` to reply to.
+  Command {
+    message: ButtplugClientMessage,
+    duration: Duration,
+  },
+  /// A client message got an error reply, or the server sent an error event.
+  Error {
+    message: Option<ButtplugClientMessage>,
+    error: Error,
+  },
+}
+
+/// Entry in a [SessionLog].
+#[derive(Debug, Clone, Serialize, Getters, CopyGetters)]
+pub struct SessionEvent {
+  /// When the event happened. For messages, this is when the server received them.
+  #[getset(get_copy = "pub")]
+  timestamp: SystemTime,
+  #[getset(get = "pub")]
+  kind: SessionEventKind,
+}
+
+/// Log of a client session, returned by [ButtplugServer::export_session_log].
+#[derive(Debug, Clone, Serialize, Getters, CopyGetters)]
+pub struct SessionLog {
+  /// When the client connected, or when the log was exported if no client has connected.
+  #[getset(get_copy = "pub")]
+  started_at: SystemTime,
+  /// How long the session had been going when the log was exported.
+  #[getset(get_copy = "pub")]
+  duration: Duration,
+  /// Everything that happened in the session, oldest first, up to [MAX_SESSION_LOG_EVENTS].
+  #[getset(get = "pub")]
+  events: Vec<SessionEvent>,
+  /// Number of events dropped from the start of the log to stay under [MAX_SESSION_LOG_EVENTS].
+  #[getset(get_copy = "pub")]
+  dropped_events: usize,
+}
+
+impl SessionLog {
+  fn empty() -> Self {
+    Self {
+      started_at: SystemTime::now(),
+      duration: Duration::ZERO,
+      events: vec![],
+      dropped_events: 0,
+    }
+  }
+}
+
+/// Events recorded for one session.
+struct SessionLogEntry {
+  session_id: u64,
+  started_at: SystemTime,
+  events: VecDeque<SessionEvent>,
+  dropped_events: usize,
+  /// False once the client has disconnected, after which nothing else is recorded.
+  open: bool,
+}
+
+impl SessionLogEntry {
+  fn push(&mut self, timestamp: SystemTime, kind: SessionEventKind) {
+    if self.events.len() >= MAX_SESSION_LOG_EVENTS {
+      self.events.pop_front();
+      self.dropped_events += 1;
+    }
+    self.events.push_back(SessionEvent { timestamp, kind });
+  }
+
+  fn export(&self) -> SessionLog {
+    let mut events: Vec<SessionEvent> = self.events.iter().cloned().collect();
+    // Messages are recorded when they finish, but timestamped when they were received.
+    events.sort_by_key(|event| event.timestamp);
+    SessionLog {
+      started_at: self.started_at,
+      duration: self.started_at.elapsed().unwrap_or_default(),
+      events,
+      dropped_events: self.dropped_events,
+    }
+  }
+}
+
+/// Collects the events of each client session, see
+/// [ButtplugServerBuilder::record_session](super::ButtplugServerBuilder::record_session).
+#[derive(Default)]
+pub(super) struct SessionLogRecorder {
+  /// Session logs in the order their sessions started, at most [MAX_SESSION_LOGS] of them.
+  sessions: Mutex<VecDeque<SessionLogEntry>>,
+}
+
+impl SessionLogRecorder {
+  /// Record an event for the given session, if it's connected.
+  pub fn record(&self, session_id: u64, timestamp: SystemTime, kind: SessionEventKind) {
+    let mut sessions = self.sessions.lock().expect("Lock poisoned");
+    if let Some(entry) = sessions
+      .iter_mut()
+      .find(|entry| entry.open && entry.session_id == session_id)
+    {
+      entry.push(timestamp, kind);
+    }
+  }
+
+  /// Record an event for every connected session.
+  fn record_all(&self, timestamp: SystemTime, kind: SessionEventKind) {
+    let mut sessions = self.sessions.lock().expect("Lock poisoned");
+    for entry in sessions.iter_mut().filter(|entry| entry.open) {
+      entry.push(timestamp, kind.clone());
+    }
+  }
+
+  /// Start a new log for the session, replacing any earlier log it had.
+  pub fn start_session(&self, session_id: u64, client_name: &str) {
+    let now = SystemTime::now();
+    let mut entry = SessionLogEntry {
+      session_id,
+      started_at: now,
+      events: VecDeque::new(),
+      dropped_events: 0,
+      open: true,
+    };
+    entry.push(
+      now,
+      SessionEventKind::ClientConnected {
+        client_name: client_name.to_owned(),
+      },
+    );
+    let mut sessions = self.sessions.lock().expect("Lock poisoned");
+    sessions.retain(|entry| entry.session_id != session_id);
+    if sessions.len() >= MAX_SESSION_LOGS {
+      sessions.pop_front();
+    }
+    sessions.push_back(entry);
+  }
+
+  /// Note that the session's client disconnected, and stop recording for it.
+  pub fn end_session(&self, session_id: u64) {
+    let mut sessions = self.sessions.lock().expect("Lock poisoned");
+    for entry in sessions
+      .iter_mut()
+      .filter(|entry| entry.open && entry.session_id == session_id)
+    {
+      entry.push(SystemTime::now(), SessionEventKind::ClientDisconnected);
+      entry.open = false;
+    }
+  }
+
+  /// Note that every connected client disconnected.
+  pub fn end_all_sessions(&self) {
+    let mut sessions = self.sessions.lock().expect("Lock poisoned");
+    for entry in sessions.iter_mut().filter(|entry| entry.open) {
+      entry.push(SystemTime::now(), SessionEventKind::ClientDisconnected);
+      entry.open = false;
+    }
+  }
+
+  /// Record device and error events from `events` for every connected session, until it ends.
+  pub async fn record_server_events(
+    self: Arc<Self>,
+    events: impl Stream<Item = ButtplugServerMessage>,
+  ) {
+    pin_mut!(events);
+    while let Some(event) = events.next().await {
+      let kind = match event {
+        ButtplugServerMessage::DeviceAdded(msg) => SessionEventKind::DeviceAdded {
+          device_index: msg.device_index(),
+          device_name: msg.device_name().clone(),
+        },
+        ButtplugServerMessage::DeviceRemoved(msg) => SessionEventKind::DeviceRemoved {
+          device_index: msg.device_index(),
+        },
+        ButtplugServerMessage::Error(error) => SessionEventKind::Error {
+          message: None,
+          error,
+        },
+        _ => continue,
+      };
+      self.record_all(SystemTime::now(), kind);
+    }
+  }
+
+  fn export(&self, session_id: Option<u64>) -> Option<SessionLog> {
+    let sessions = self.sessions.lock().expect("Lock poisoned");
+    match session_id {
+      Some(session_id) => sessions.iter().find(|entry| entry.session_id == session_id),
+      None => sessions.back(),
+    }
+    .map(|entry| entry.export())
+  }
+}
+
+impl ButtplugServer {
+  /// Log of everything that happened in the session that connected most recently, whether or not
+  /// it's still connected. Empty unless
+  /// [ButtplugServerBuilder::record_session](super::ButtplugServerBuilder::record_session) is on.
+  pub fn export_session_log(&self) -> SessionLog {
+    self
+      .session_log
+      .as_ref()
+      .and_then(|session_log| session_log.export(None))
+      .unwrap_or_else(SessionLog::empty)
+  }
+
+  /// Log of the remote client session with the given id, as reported by the
+  /// [ClientConnected](super::ButtplugRemoteServerEvent::ClientConnected) event. None if the
+  /// session isn't one of the last [MAX_SESSION_LOGS] to connect, or if session recording is off.
+  pub fn export_client_session_log(&self, session_id: u64) -> Option<SessionLog> {
+    self
+      .session_log
+      .as_ref()
+      .and_then(|session_log| session_log.export(Some(session_id)))
+  }
+}
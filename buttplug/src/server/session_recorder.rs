@@ -0,0 +1,204 @@
+// Buttplug Rust Source Code File - See https://buttplug.io for more info.
+//
+// Copyright 2016-2023 Nonpolynomial Labs LLC. All rights reserved.
+//
+// Licensed under the BSD 3-Clause license. See LICENSE file in the project root
+// for full license information.
+
+//! Recording of remote server sessions, and replaying recorded client messages against a server
+//! for testing without hardware.
+
+use super::ButtplugServer;
+use crate::core::{
+  errors::{ButtplugError, ButtplugUnknownError},
+  message::{
+    ButtplugClientMessage,
+    ButtplugCurrentSpecServerMessage,
+    ButtplugMessageFinalizer,
+    ButtplugServerMessage,
+  },
+};
+use getset::{CopyGetters, Getters};
+use serde::{Deserialize, Serialize};
+use std::{
+  fs,
+  io::{self, BufRead, BufReader, BufWriter, Write},
+  path::Path,
+  sync::{Arc, Mutex, Weak},
+  time::SystemTime,
+};
+
+/// Which way a [TimestampedMessage] was sent.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum MessageDirection {
+  ClientToServer,
+  ServerToClient,
+}
+
+/// Message recorded by a [SessionRecorder].
+#[derive(Debug, Clone, Getters, CopyGetters)]
+pub struct TimestampedMessage {
+  #[getset(get_copy = "pub")]
+  timestamp: SystemTime,
+  #[getset(get_copy = "pub")]
+  direction: MessageDirection,
+  /// The message as a Buttplug JSON packet, i.e. a JSON array holding the message.
+  #[getset(get = "pub")]
+  message: Vec<u8>,
+}
+
+/// Line format of saved transcripts. The message is embedded as JSON, rather than as a byte array,
+/// so transcripts stay readable.
+#[derive(Serialize, Deserialize)]
+struct TranscriptLine {
+  timestamp: SystemTime,
+  direction: MessageDirection,
+  message: serde_json::Value,
+}
+
+type Transcript = Mutex<Vec<TimestampedMessage>>;
+
+/// Transcript of the messages passed between a [ButtplugRemoteServer](super::ButtplugRemoteServer)
+/// and its clients, created with
+/// [ButtplugRemoteServer::record_session](super::ButtplugRemoteServer::record_session).
+///
+/// Recording stops once the recorder and all of its clones are dropped.
+#[derive(Clone, Default)]
+pub struct SessionRecorder {
+  transcript: Arc<Transcript>,
+}
+
+impl SessionRecorder {
+  /// Messages recorded so far, oldest first.
+  pub fn transcript(&self) -> Vec<TimestampedMessage> {
+    self.transcript.lock().expect("Lock poisoned").clone()
+  }
+
+  /// Write the transcript to `path` as newline delimited JSON, one message per line.
+  pub fn save(&self, path: &Path) -> io::Result<()> {
+    let mut writer = BufWriter::new(fs::File::create(path)?);
+    for msg in self.transcript() {
+      let line = TranscriptLine {
+        timestamp: msg.timestamp,
+        direction: msg.direction,
+        message: serde_json::from_slice(&msg.message)?,
+      };
+      serde_json::to_writer(&mut writer, &line)?;
+      writer.write_all(b"\n")?;
+    }
+    writer.flush()
+  }
+}
+
+/// Recorders attached to a remote server. Messages are only serialized while at least one
+/// recorder is alive.
+#[derive(Default)]
+pub(super) struct SessionRecorders {
+  recorders: Mutex<Vec<Weak<Transcript>>>,
+}
+
+impl SessionRecorders {
+  pub fn add(&self) -> SessionRecorder {
+    let recorder = SessionRecorder::default();
+    self
+      .recorders
+      .lock()
+      .expect("Lock poisoned")
+      .push(Arc::downgrade(&recorder.transcript));
+    recorder
+  }
+
+  fn record(&self, direction: MessageDirection, serialize: impl FnOnce() -> Vec<u8>) {
+    let mut recorders = self.recorders.lock().expect("Lock poisoned");
+    recorders.retain(|recorder| recorder.strong_count() > 0);
+    if recorders.is_empty() {
+      return;
+    }
+    let msg = TimestampedMessage {
+      timestamp: SystemTime::now(),
+      direction,
+      message: serialize(),
+    };
+    for transcript in recorders.iter().filter_map(Weak::upgrade) {
+      transcript.lock().expect("Lock poisoned").push(msg.clone());
+    }
+  }
+
+  pub fn record_client_message(&self, msg: &ButtplugClientMessage) {
+    self.record(MessageDirection::ClientToServer, || {
+      serde_json::to_vec(&[msg]).expect("Infallible serialization")
+    });
+  }
+
+  pub fn record_server_message(&self, msg: &ButtplugServerMessage) {
+    self.record(MessageDirection::ServerToClient, || {
+      serialize_server_message(msg)
+    });
+  }
+}
+
+/// Serialize a server message the way it's sent to current spec clients.
+fn serialize_server_message(msg: &ButtplugServerMessage) -> Vec<u8> {
+  let msg = match ButtplugCurrentSpecServerMessage::try_from(msg.clone()) {
+    Ok(msg) => msg,
+    Err(err) => ButtplugCurrentSpecServerMessage::Error(ButtplugError::from(err).into()),
+  };
+  serde_json::to_vec(&[msg]).expect("Infallible serialization")
+}
+
+fn transcript_error(err: impl std::fmt::Display) -> ButtplugError {
+  ButtplugUnknownError::TranscriptReadError(err.to_string()).into()
+}
+
+/// Send the client messages from a transcript saved with [SessionRecorder::save] to `server`, in
+/// order, returning the server's replies. Error replies are returned as
+/// [ButtplugServerMessage::Error], so one failed message doesn't stop the replay.
+pub async fn replay_transcript(
+  path: &Path,
+  server: &ButtplugServer,
+) -> Result<Vec<ButtplugServerMessage>, ButtplugError> {
+  let reader = BufReader::new(fs::File::open(path).map_err(transcript_error)?);
+  let mut client_msgs = vec![];
+  for line in reader.lines() {
+    let line = line.map_err(transcript_error)?;
+    if line.trim().is_empty() {
+      continue;
+    }
+    let line: TranscriptLine = serde_json::from_str(&line).map_err(transcript_error)?;
+    if line.direction == MessageDirection::ClientToServer {
+      let msgs: Vec<ButtplugClientMessage> =
+        serde_json::from_value(line.message).map_err(transcript_error)?;
+      client_msgs.extend(msgs);
+    }
+  }
+  let mut replies = vec![];
+  for mut msg in client_msgs {
+    msg.finalize();
+    replies.push(match server.parse_message(msg).await {
+      Ok(reply) => reply,
+      Err(err) => err.into(),
+    });
+  }
+  Ok(replies)
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+  use crate::core::message::Ping;
+
+  #[test]
+  fn test_session_recorders_stop_when_dropped() {
+    let recorders = SessionRecorders::default();
+    let recorder = recorders.add();
+    recorders.record_client_message(&Ping::default().into());
+    assert_eq!(recorder.transcript().len(), 1);
+    assert_eq!(
+      recorder.transcript()[0].direction(),
+      MessageDirection::ClientToServer
+    );
+    drop(recorder);
+    recorders.record_client_message(&Ping::default().into());
+    assert!(recorders.recorders.lock().unwrap().is_empty());
+  }
+}
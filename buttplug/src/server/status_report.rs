@@ -0,0 +1,198 @@
+// Buttplug Rust Source Code File - See https://buttplug.io for more info.
+//
+// Copyright 2016-2023 Nonpolynomial Labs LLC. All rights reserved.
+//
+// Licensed under the BSD 3-Clause license. See LICENSE file in the project root
+// for full license information.
+
+//! Human readable snapshot of server state, for diagnostic tools.
+
+use getset::{CopyGetters, Getters};
+use std::{fmt, time::Duration};
+
+include!(concat!(env!("OUT_DIR"), "/features.rs"));
+
+/// Connected device, as listed in a [StatusReport].
+#[derive(Debug, Clone, Getters, CopyGetters)]
+pub struct StatusReportDevice {
+  #[getset(get_copy = "pub")]
+  index: u32,
+  #[getset(get = "pub")]
+  name: String,
+  #[getset(get = "pub")]
+  address: String,
+  /// Time since the last successful command, if command statistics are tracked and one has been
+  /// sent.
+  #[getset(get_copy = "pub")]
+  last_command_age: Option<Duration>,
+}
+
+impl StatusReportDevice {
+  pub(super) fn new(
+    index: u32,
+    name: &str,
+    address: &str,
+    last_command_age: Option<Duration>,
+  ) -> Self {
+    Self {
+      index,
+      name: name.to_owned(),
+      address: address.to_owned(),
+      last_command_age,
+    }
+  }
+}
+
+/// Snapshot of server state, made with
+/// [ButtplugRemoteServer::status_report](super::ButtplugRemoteServer::status_report). Displays as
+/// a multi-line summary with a table of connected devices.
+#[derive(Debug, Clone, Getters, CopyGetters)]
+pub struct StatusReport {
+  #[getset(get = "pub")]
+  server_name: String,
+  #[getset(get_copy = "pub")]
+  uptime: Duration,
+  /// Name of the connected client, if any.
+  #[getset(get = "pub")]
+  client_name: Option<String>,
+  #[getset(get_copy = "pub")]
+  connection_duration: Duration,
+  #[getset(get = "pub")]
+  devices: Vec<StatusReportDevice>,
+  #[getset(get_copy = "pub")]
+  message_count: u64,
+  #[getset(get_copy = "pub")]
+  error_count: u64,
+  /// Crate features compiled in.
+  #[getset(get = "pub")]
+  features: Vec<&'static str>,
+}
+
+impl StatusReport {
+  pub(super) fn new(
+    server_name: &str,
+    uptime: Duration,
+    client_name: Option<String>,
+    connection_duration: Duration,
+    devices: Vec<StatusReportDevice>,
+    message_count: u64,
+    error_count: u64,
+  ) -> Self {
+    Self {
+      server_name: server_name.to_owned(),
+      uptime,
+      client_name,
+      connection_duration,
+      devices,
+      message_count,
+      error_count,
+      features: ENABLED_FEATURES.to_vec(),
+    }
+  }
+}
+
+/// Formats a duration as hours, minutes and seconds, e.g. "1h 02m 03s".
+fn format_duration(duration: Duration) -> String {
+  let seconds = duration.as_secs();
+  let (hours, minutes, seconds) = (seconds / 3600, (seconds / 60) % 60, seconds % 60);
+  if hours > 0 {
+    format!("{}h {:02}m {:02}s", hours, minutes, seconds)
+  } else if minutes > 0 {
+    format!("{}m {:02}s", minutes, seconds)
+  } else {
+    format!("{}s", seconds)
+  }
+}
+
+impl fmt::Display for StatusReport {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    writeln!(f, "{:<10}{}", "Server:", self.server_name)?;
+    writeln!(f, "{:<10}{}", "Uptime:", format_duration(self.uptime))?;
+    match &self.client_name {
+      Some(client_name) => writeln!(
+        f,
+        "{:<10}{} (connected {})",
+        "Client:",
+        client_name,
+        format_duration(self.connection_duration)
+      )?,
+      None => writeln!(f, "{:<10}none", "Client:")?,
+    }
+    writeln!(
+      f,
+      "{:<10}{} handled, {} errors",
+      "Messages:", self.message_count, self.error_count
+    )?;
+    let features = if self.features.is_empty() {
+      "none".to_owned()
+    } else {
+      self.features.join(", ")
+    };
+    writeln!(f, "{:<10}{}", "Features:", features)?;
+    writeln!(f)?;
+
+    let rows: Vec<[String; 4]> = self
+      .devices
+      .iter()
+      .map(|device| {
+        [
+          device.index.to_string(),
+          device.name.clone(),
+          device.address.clone(),
+          device.last_command_age.map_or("-".to_owned(), |age| {
+            format!("{} ago", format_duration(age))
+          }),
+        ]
+      })
+      .collect();
+    let header = ["Index", "Name", "Address", "Last Command"].map(str::to_owned);
+    let mut widths = header.clone().map(|column| column.len());
+    for row in &rows {
+      for (width, column) in widths.iter_mut().zip(row) {
+        *width = (*width).max(column.len());
+      }
+    }
+    for row in std::iter::once(&header).chain(&rows) {
+      let line: Vec<String> = row
+        .iter()
+        .zip(widths)
+        .map(|(column, width)| format!("{:<width$}", column, width = width))
+        .collect();
+      writeln!(f, "{}", line.join(" | ").trim_end())?;
+    }
+    if rows.is_empty() {
+      writeln!(f, "(no devices connected)")?;
+    }
+    Ok(())
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+
+  #[test]
+  fn test_status_report_display() {
+    let report = StatusReport::new(
+      "Test Server",
+      Duration::from_secs(3723),
+      Some("Test Client".to_owned()),
+      Duration::from_secs(65),
+      vec![StatusReportDevice::new(
+        0,
+        "Lovense Hush",
+        "AA:BB",
+        Some(Duration::from_secs(3)),
+      )],
+      12,
+      1,
+    );
+    let output = report.to_string();
+    assert!(output.contains("Server:   Test Server\n"));
+    assert!(output.contains("Uptime:   1h 02m 03s\n"));
+    assert!(output.contains("Client:   Test Client (connected 1m 05s)\n"));
+    assert!(output.contains("Messages: 12 handled, 1 errors\n"));
+    assert!(output.contains("Index | Name         | Address | Last Command\n"));
+    assert!(output.contains("0     | Lovense Hush | AA:BB   | 3s ago\n"));
+  }
+}
@@ -0,0 +1,247 @@
+// Buttplug Rust Source Code File - See https://buttplug.io for more info.
+//
+// Copyright 2016-2023 Nonpolynomial Labs LLC. All rights reserved.
+//
+// Licensed under the BSD 3-Clause license. See LICENSE file in the project root
+// for full license information.
+
+//! Telemetry that can be turned on and off while a [ButtplugRemoteServer] is running, see
+//! [ButtplugRemoteServer::enable_telemetry].
+
+#[cfg(feature = "http-info")]
+use super::http_info::serve_http;
+#[cfg(doc)]
+use super::ButtplugRemoteServer;
+use super::ButtplugServer;
+use getset::{CopyGetters, Getters};
+#[cfg(feature = "http-info")]
+use std::fmt::Write;
+use std::{
+  net::SocketAddr,
+  sync::{
+    atomic::{AtomicU64, AtomicUsize, Ordering},
+    Arc,
+    Mutex,
+  },
+};
+use thiserror::Error;
+use tokio_util::sync::CancellationToken;
+
+/// Settings for [ButtplugRemoteServer::enable_telemetry].
+#[derive(Debug, Clone, PartialEq, Getters, CopyGetters)]
+pub struct TelemetryConfig {
+  /// If set, serve metrics in the Prometheus text format on this port, on localhost. Port 0 picks
+  /// a free port, see [ButtplugRemoteServer::prometheus_address]. Needs the `http-info` feature.
+  #[getset(get_copy = "pub")]
+  prometheus_port: Option<u16>,
+  /// OTLP collector to export traces to. This build has no OTLP exporter, so setting this is an
+  /// error. Use a tracing subscriber with an OTLP layer instead.
+  #[getset(get = "pub")]
+  otlp_endpoint: Option<String>,
+  /// Fraction (0.0-1.0) of client messages and server events that get a tracing span.
+  #[getset(get_copy = "pub")]
+  trace_sampling_rate: f64,
+}
+
+impl Default for TelemetryConfig {
+  fn default() -> Self {
+    Self {
+      prometheus_port: None,
+      otlp_endpoint: None,
+      trace_sampling_rate: 1.0,
+    }
+  }
+}
+
+impl TelemetryConfig {
+  pub fn new(
+    prometheus_port: Option<u16>,
+    otlp_endpoint: Option<String>,
+    trace_sampling_rate: f64,
+  ) -> Self {
+    Self {
+      prometheus_port,
+      otlp_endpoint,
+      trace_sampling_rate,
+    }
+  }
+}
+
+/// Error returned by [ButtplugRemoteServer::enable_telemetry]. Telemetry settings are left as they
+/// were when this is returned.
+#[derive(Error, Debug)]
+pub enum TelemetryError {
+  #[error("Trace sampling rate must be between 0.0 and 1.0, got {0}")]
+  InvalidSamplingRate(f64),
+  #[error("OTLP export is not supported by this build, cannot export to {0}")]
+  OtlpUnsupported(String),
+  #[error("Serving Prometheus metrics requires the http-info feature")]
+  PrometheusUnsupported,
+  #[error("Cannot listen for Prometheus scrapes on port {port}: {message}")]
+  PrometheusBindFailed { port: u16, message: String },
+}
+
+/// Telemetry settings currently in effect for a remote server.
+pub(super) struct TelemetryState {
+  /// Address of the Prometheus endpoint and the token that stops it, while it's running.
+  prometheus: Mutex<Option<(SocketAddr, CancellationToken)>>,
+  /// Trace sampling rate, stored as f64 bits.
+  sampling_rate: AtomicU64,
+  /// Number of sampling decisions made, used to spread sampled spans out evenly.
+  sampling_count: AtomicU64,
+}
+
+impl Default for TelemetryState {
+  fn default() -> Self {
+    Self {
+      prometheus: Mutex::new(None),
+      sampling_rate: AtomicU64::new(1.0f64.to_bits()),
+      sampling_count: AtomicU64::new(0),
+    }
+  }
+}
+
+impl TelemetryState {
+  pub(super) fn enable(
+    &self,
+    config: TelemetryConfig,
+    server: Arc<ButtplugServer>,
+    active_sessions: Arc<AtomicUsize>,
+  ) -> Result<(), TelemetryError> {
+    let sampling_rate = config.trace_sampling_rate();
+    if !(0.0..=1.0).contains(&sampling_rate) {
+      return Err(TelemetryError::InvalidSamplingRate(sampling_rate));
+    }
+    if let Some(endpoint) = config.otlp_endpoint() {
+      return Err(TelemetryError::OtlpUnsupported(endpoint.clone()));
+    }
+    let mut prometheus = self.prometheus.lock().expect("Lock poisoned");
+    // A running endpoint is kept if it's already on the requested port, as that port can't be
+    // bound again while it's in use.
+    let keep_running = matches!(
+      (config.prometheus_port(), prometheus.as_ref()),
+      (Some(port), Some((address, _))) if port == 0 || port == address.port()
+    );
+    if !keep_running {
+      let started = match config.prometheus_port() {
+        Some(port) => Some(start_prometheus_endpoint(port, server, active_sessions)?),
+        None => None,
+      };
+      if let Some((_, token)) = std::mem::replace(&mut *prometheus, started) {
+        token.cancel();
+      }
+    }
+    self
+      .sampling_rate
+      .store(sampling_rate.to_bits(), Ordering::SeqCst);
+    Ok(())
+  }
+
+  pub(super) fn disable(&self) {
+    if let Some((_, token)) = self.prometheus.lock().expect("Lock poisoned").take() {
+      token.cancel();
+    }
+    self.sampling_rate.store(1.0f64.to_bits(), Ordering::SeqCst);
+  }
+
+  pub(super) fn prometheus_address(&self) -> Option<SocketAddr> {
+    self
+      .prometheus
+      .lock()
+      .expect("Lock poisoned")
+      .as_ref()
+      .map(|(address, _)| *address)
+  }
+
+  /// Whether the next message should get a tracing span, per the sampling rate.
+  pub(super) fn should_sample(&self) -> bool {
+    let sampling_rate = f64::from_bits(self.sampling_rate.load(Ordering::SeqCst));
+    if sampling_rate >= 1.0 {
+      return true;
+    }
+    // Sample whenever the running total of rate * messages passes a whole number, which spreads
+    // samples out evenly without needing a random number generator.
+    let count = self.sampling_count.fetch_add(1, Ordering::SeqCst) as f64;
+    ((count + 1.0) * sampling_rate).floor() > (count * sampling_rate).floor()
+  }
+}
+
+/// Current server metrics, in the Prometheus text exposition format.
+#[cfg(feature = "http-info")]
+pub(super) fn prometheus_metrics(server: &ButtplugServer, active_sessions: usize) -> String {
+  let metrics = [
+    (
+      "buttplug_server_messages_total",
+      "counter",
+      "Client messages handled by the server.",
+      server.message_count() as f64,
+    ),
+    (
+      "buttplug_server_errors_total",
+      "counter",
+      "Client messages that got an error reply.",
+      server.error_count() as f64,
+    ),
+    (
+      "buttplug_server_uptime_seconds",
+      "gauge",
+      "Time since the server was built.",
+      server.uptime().as_secs_f64(),
+    ),
+    (
+      "buttplug_server_devices",
+      "gauge",
+      "Devices currently connected to the server.",
+      server.device_manager().device_indexes().len() as f64,
+    ),
+    (
+      "buttplug_remote_server_sessions",
+      "gauge",
+      "Client sessions currently running.",
+      active_sessions as f64,
+    ),
+  ];
+  let mut output = String::new();
+  for (name, metric_type, help, value) in metrics {
+    let _ = write!(
+      output,
+      "# HELP {name} {help}\n# TYPE {name} {metric_type}\n{name} {value}\n"
+    );
+  }
+  output
+}
+
+#[cfg(feature = "http-info")]
+fn start_prometheus_endpoint(
+  port: u16,
+  server: Arc<ButtplugServer>,
+  active_sessions: Arc<AtomicUsize>,
+) -> Result<(SocketAddr, CancellationToken), TelemetryError> {
+  // Every path gets the metrics, so scrapers don't need to be told where to look.
+  serve_http(
+    ([127, 0, 0, 1], port).into(),
+    "Prometheus metrics",
+    move |_| {
+      hyper::Response::builder()
+        .header(hyper::header::CONTENT_TYPE, "text/plain; version=0.0.4")
+        .body(hyper::Body::from(prometheus_metrics(
+          &server,
+          active_sessions.load(Ordering::SeqCst),
+        )))
+        .expect("Response is valid")
+    },
+  )
+  .map_err(|err| TelemetryError::PrometheusBindFailed {
+    port,
+    message: err.to_string(),
+  })
+}
+
+#[cfg(not(feature = "http-info"))]
+fn start_prometheus_endpoint(
+  _port: u16,
+  _server: Arc<ButtplugServer>,
+  _active_sessions: Arc<AtomicUsize>,
+) -> Result<(SocketAddr, CancellationToken), TelemetryError> {
+  Err(TelemetryError::PrometheusUnsupported)
+}
@@ -0,0 +1,114 @@
+// Buttplug Rust Source Code File - See https://buttplug.io for more info.
+//
+// Copyright 2016-2023 Nonpolynomial Labs LLC. All rights reserved.
+//
+// Licensed under the BSD 3-Clause license. See LICENSE file in the project root
+// for full license information.
+
+//! Typed [ButtplugRemoteServerEvent]s, for subscribing to a single kind of event with
+//! [ButtplugRemoteServer::subscribe].
+
+use super::{ButtplugRemoteServer, ButtplugRemoteServerEvent};
+use futures::{future, Stream, StreamExt};
+use getset::{CopyGetters, Getters};
+
+mod sealed {
+  use super::ButtplugRemoteServerEvent;
+
+  pub trait Sealed: Sized {
+    /// The typed event, if `event` is of this kind.
+    fn from_remote_event(event: ButtplugRemoteServerEvent) -> Option<Self>;
+  }
+}
+
+/// Event that can be subscribed to with [ButtplugRemoteServer::subscribe]. Sealed, as events are
+/// only created by the server.
+pub trait ButtplugEvent: sealed::Sealed + Send + 'static {}
+
+/// Client finished its handshake, see [ButtplugRemoteServerEvent::ClientConnected].
+#[derive(Debug, Clone, PartialEq, Eq, Getters, CopyGetters)]
+pub struct ClientConnectedEvent {
+  /// Id of the session, which stays the same for the matching
+  /// [ButtplugRemoteServerEvent::ClientDisconnected].
+  #[getset(get_copy = "pub")]
+  session_id: u64,
+  #[getset(get = "pub")]
+  client_name: String,
+}
+
+/// Device was connected, see [ButtplugRemoteServerEvent::DeviceAdded].
+#[derive(Debug, Clone, PartialEq, Eq, Getters, CopyGetters)]
+pub struct DeviceAddedEvent {
+  #[getset(get_copy = "pub")]
+  device_index: u32,
+  #[getset(get = "pub")]
+  name: String,
+  #[getset(get = "pub")]
+  address: String,
+  #[getset(get = "pub")]
+  display_name: Option<String>,
+}
+
+/// Device was removed, see [ButtplugRemoteServerEvent::DeviceRemoved].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, CopyGetters)]
+pub struct DeviceRemovedEvent {
+  #[getset(get_copy = "pub")]
+  device_index: u32,
+}
+
+impl sealed::Sealed for ClientConnectedEvent {
+  fn from_remote_event(event: ButtplugRemoteServerEvent) -> Option<Self> {
+    match event {
+      ButtplugRemoteServerEvent::ClientConnected(session_id, client_name) => Some(Self {
+        session_id,
+        client_name,
+      }),
+      _ => None,
+    }
+  }
+}
+
+impl sealed::Sealed for DeviceAddedEvent {
+  fn from_remote_event(event: ButtplugRemoteServerEvent) -> Option<Self> {
+    match event {
+      ButtplugRemoteServerEvent::DeviceAdded(device_index, name, address, display_name) => {
+        Some(Self {
+          device_index,
+          name,
+          address,
+          display_name,
+        })
+      }
+      _ => None,
+    }
+  }
+}
+
+impl sealed::Sealed for DeviceRemovedEvent {
+  fn from_remote_event(event: ButtplugRemoteServerEvent) -> Option<Self> {
+    match event {
+      ButtplugRemoteServerEvent::DeviceRemoved(device_index) => Some(Self { device_index }),
+      _ => None,
+    }
+  }
+}
+
+impl ButtplugEvent for ClientConnectedEvent {
+}
+
+impl ButtplugEvent for DeviceAddedEvent {
+}
+
+impl ButtplugEvent for DeviceRemovedEvent {
+}
+
+impl ButtplugRemoteServer {
+  /// Events of a single kind, e.g. `subscribe::<DeviceAddedEvent>()`. Same as filtering
+  /// [ButtplugRemoteServer::event_stream], so buffering follows the
+  /// [EventBufferPolicy](super::EventBufferPolicy) there.
+  pub fn subscribe<E: ButtplugEvent>(&self) -> impl Stream<Item = E> {
+    self
+      .event_stream()
+      .filter_map(|event| future::ready(E::from_remote_event(event)))
+  }
+}
@@ -9,6 +9,7 @@ use futures::{
   future::{Future, RemoteHandle},
   task::{FutureObj, Spawn, SpawnError},
 };
+use std::time::Duration;
 
 #[derive(Default)]
 pub struct DummyAsyncManager {}
@@ -34,6 +35,10 @@ where
   unimplemented!("Dummy executor can't actually spawn!")
 }
 
+pub async fn sleep(_: Duration) {
+  unimplemented!("Dummy executor can't actually sleep!")
+}
+
 pub fn block_on<F>(_: F) -> <F as Future>::Output
 where
   F: Future,
@@ -9,6 +9,7 @@ use futures::{
   future::{Future, RemoteHandle},
   task::{FutureObj, Spawn, SpawnError, SpawnExt},
 };
+use std::time::Duration;
 use tokio;
 
 #[derive(Default)]
@@ -38,6 +39,10 @@ where
   TokioAsyncManager::default().spawn_with_handle(future)
 }
 
+pub async fn sleep(duration: Duration) {
+  tokio::time::sleep(duration).await
+}
+
 pub fn block_on<F>(f: F) -> <F as Future>::Output
 where
   F: Future,
@@ -9,6 +9,7 @@ use futures::{
   future::{Future, RemoteHandle},
   task::{FutureObj, Spawn, SpawnError, SpawnExt},
 };
+use std::time::Duration;
 
 use wasm_bindgen_futures::spawn_local;
 
@@ -37,6 +38,10 @@ where
   WasmBindgenAsyncManager::default().spawn_with_handle(future)
 }
 
+pub async fn sleep(duration: Duration) {
+  gloo_timers::future::sleep(duration).await
+}
+
 pub fn block_on<F>(_: F) -> <F as Future>::Output
 where
   F: Future,
@@ -31,7 +31,7 @@ use crate::{
 };
 use getset::{CopyGetters, Getters, MutGetters, Setters};
 use serde::{Deserialize, Serialize};
-use std::{collections::HashMap, fmt::Display, ops::RangeInclusive};
+use std::{collections::HashMap, fmt::Display, ops::RangeInclusive, time::Duration};
 
 pub static DEVICE_CONFIGURATION_JSON: &str =
   include_str!("../../buttplug-device-config/buttplug-device-config.json");
@@ -94,6 +94,10 @@ struct UserDeviceConfig {
   #[serde(skip_serializing_if = "Option::is_none")]
   #[serde(default)]
   index: Option<u32>,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  #[serde(default)]
+  #[serde(rename = "command-debounce-ms")]
+  command_debounce_ms: Option<u64>,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone, Default, Getters, Setters, MutGetters)]
@@ -310,13 +314,19 @@ fn add_user_configs_to_protocol(
       }
       let server_ident: ServerDeviceIdentifier = user_config.identifier.clone().into();
 
-      let config_attrs = ProtocolDeviceAttributes::new(
+      let mut config_attrs = ProtocolDeviceAttributes::new(
         server_ident.attributes_identifier().clone(),
         None,
         user_config.config().display_name.clone(),
         user_config.config().messages.clone().unwrap_or_default(),
         None,
       );
+      config_attrs.set_command_debounce(
+        user_config
+          .config()
+          .command_debounce_ms
+          .map(Duration::from_millis),
+      );
       info!("Adding user config for {:?}", server_ident);
       external_config
         .user_configs
@@ -403,6 +413,12 @@ fn load_protocol_config_from_json(
   }
 }
 
+/// Check that a device configuration file is valid, without loading it.
+#[cfg(feature = "http-config")]
+pub(crate) fn validate_protocol_config(config_str: &str) -> Result<(), ButtplugDeviceError> {
+  load_protocol_config_from_json(config_str, false).map(|_| ())
+}
+
 fn load_protocol_configs_internal(
   main_config_str: Option<String>,
   user_config_str: Option<String>,
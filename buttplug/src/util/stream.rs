@@ -9,16 +9,41 @@ use async_stream::stream;
 use futures::{FutureExt, Stream};
 use tokio::sync::{broadcast, mpsc};
 
+/// Stream values from a broadcast receiver. If the receiver falls behind, skipped values are
+/// logged and the stream carries on from the oldest value still held.
 pub fn convert_broadcast_receiver_to_stream<T>(
   receiver: broadcast::Receiver<T>,
 ) -> impl Stream<Item = T>
 where
   T: Unpin + Clone,
+{
+  convert_broadcast_receiver_to_stream_with_lag_marker(receiver, |_| None)
+}
+
+/// Same as [convert_broadcast_receiver_to_stream], but yields the value returned by `lag_marker`
+/// (if any) when the receiver falls behind, with the number of values skipped, so consumers can
+/// tell that something was missed.
+pub fn convert_broadcast_receiver_to_stream_with_lag_marker<T, F>(
+  receiver: broadcast::Receiver<T>,
+  lag_marker: F,
+) -> impl Stream<Item = T>
+where
+  T: Unpin + Clone,
+  F: Fn(u64) -> Option<T>,
 {
   stream! {
     pin_mut!(receiver);
-    while let Ok(val) = receiver.recv().await {
-      yield val;
+    loop {
+      match receiver.recv().await {
+        Ok(val) => yield val,
+        Err(broadcast::error::RecvError::Lagged(count)) => {
+          warn!("Broadcast stream receiver fell behind, skipped {} values.", count);
+          if let Some(marker) = lag_marker(count) {
+            yield marker;
+          }
+        }
+        Err(broadcast::error::RecvError::Closed) => break,
+      }
     }
   }
 }
@@ -30,3 +55,26 @@ pub fn recv_now<T>(receiver: &mut mpsc::Receiver<T>) -> Option<Option<T>> {
 pub fn iffy_is_empty_check<T>(receiver: &mut mpsc::Receiver<T>) -> bool {
   recv_now(receiver).is_none()
 }
+
+#[cfg(test)]
+mod test {
+  use super::*;
+  use crate::util::async_manager;
+  use futures::StreamExt;
+
+  #[test]
+  fn test_broadcast_stream_lag_marker() {
+    async_manager::block_on(async {
+      let (sender, receiver) = broadcast::channel(2);
+      let stream = convert_broadcast_receiver_to_stream_with_lag_marker(receiver, |count| {
+        Some(-(count as i64))
+      });
+      for val in 1..=5 {
+        sender.send(val).unwrap();
+      }
+      drop(sender);
+      // Only the last 2 values fit in the channel, the first 3 were skipped.
+      assert_eq!(stream.collect::<Vec<i64>>().await, vec![-3, 4, 5]);
+    });
+  }
+}
@@ -6,7 +6,12 @@
 // for full license information.
 
 mod util;
-use util::{test_client, test_client_with_delayed_device_manager, test_client_with_device};
+use util::{
+  test_client,
+  test_client_with_delayed_device_manager,
+  test_client_with_device,
+  ChannelClientTestHelper,
+};
 extern crate buttplug;
 extern crate tracing;
 
@@ -19,7 +24,7 @@ use buttplug::{
       ButtplugConnectorResultFuture,
       ButtplugInProcessClientConnectorBuilder,
     },
-    errors::{ButtplugDeviceError, ButtplugError},
+    errors::{ButtplugDeviceError, ButtplugError, ButtplugUnknownError},
     message::{ButtplugCurrentSpecClientMessage, ButtplugCurrentSpecServerMessage},
   },
   server::ButtplugServerBuilder,
@@ -27,7 +32,7 @@ use buttplug::{
 };
 
 use futures::{future::BoxFuture, StreamExt};
-use std::time::Duration;
+use std::{sync::Arc, time::Duration};
 use tokio::{sync::mpsc::Sender, time::sleep};
 
 #[derive(Default)]
@@ -190,6 +195,50 @@ fn test_client_ping() {
     assert!(client.ping().await.is_err());
   });
 }
+#[test]
+fn test_client_max_pending_messages() {
+  async_manager::block_on(async {
+    let helper = Arc::new(ChannelClientTestHelper::with_client(
+      ButtplugClient::new("Test Client").with_max_pending_messages(2),
+    ));
+    helper.simulate_successful_connect().await;
+    // The server never replies to these, so they stay pending.
+    for _ in 0..2 {
+      let helper_clone = helper.clone();
+      async_manager::spawn(async move {
+        let _ = helper_clone.client().start_scanning().await;
+      });
+      helper.next_client_message().await;
+    }
+    assert!(matches!(
+      helper.client().stop_scanning().await,
+      Err(ButtplugClientError::MessageSorterFullError(2))
+    ));
+  });
+}
+
+#[test]
+fn test_client_reply_timeout() {
+  async_manager::block_on(async {
+    let helper = Arc::new(ChannelClientTestHelper::with_client(
+      ButtplugClient::new("Test Client").with_reply_timeout(Duration::from_millis(100)),
+    ));
+    helper.simulate_successful_connect().await;
+    // The server never replies, so the message fails once the timeout passes.
+    let helper_clone = helper.clone();
+    let reply =
+      async_manager::spawn_with_handle(async move { helper_clone.client().start_scanning().await })
+        .expect("Test, assuming infallible.");
+    helper.next_client_message().await;
+    assert!(matches!(
+      reply.await,
+      Err(ButtplugClientError::ButtplugError(
+        ButtplugError::ButtplugUnknownError(ButtplugUnknownError::ReplyTimedOut(_))
+      ))
+    ));
+  });
+}
+
 /*
 // Tests both the stop all devices functionality, as well as both ends of the
 // command range for is_in_command_range message validation.
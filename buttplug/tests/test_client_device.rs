@@ -15,7 +15,13 @@ use buttplug::{
   },
   core::{
     errors::{ButtplugDeviceError, ButtplugError, ButtplugMessageError},
-    message::{self, ButtplugClientMessage, ClientDeviceMessageAttributes},
+    message::{
+      self,
+      ButtplugClientMessage,
+      ButtplugCurrentSpecServerMessage,
+      ClientDeviceMessageAttributes,
+      Endpoint,
+    },
   },
   util::async_manager,
 };
@@ -290,6 +296,56 @@ fn test_client_repeated_deviceremoved_message() {
   });
 }
 
+#[cfg(feature = "server")]
+#[test]
+fn test_client_reassembles_split_raw_readings() {
+  async_manager::block_on(async move {
+    let helper = Arc::new(util::ChannelClientTestHelper::new());
+    helper.simulate_successful_connect().await;
+    let mut event_stream = helper.client().event_stream();
+    helper
+      .send_client_incoming(
+        message::DeviceAdded::new(
+          1,
+          "Test Device",
+          &None,
+          &None,
+          &ClientDeviceMessageAttributes::default(),
+        )
+        .into(),
+      )
+      .await;
+    let device = match event_stream
+      .next()
+      .await
+      .expect("Test, assuming infallible.")
+    {
+      ButtplugClientEvent::DeviceAdded(device) => device,
+      event => panic!("Expected DeviceAdded, got {:?}", event),
+    };
+    let mut device_events = device.event_stream();
+
+    let mut first = message::RawReading::new(1, Endpoint::Tx, vec![1, 2]);
+    first.set_continued(true);
+    let mut second = message::RawReading::new(1, Endpoint::Tx, vec![3]);
+    second.set_continued(true);
+    helper.send_client_incoming(first.into()).await;
+    helper.send_client_incoming(second.into()).await;
+    helper
+      .send_client_incoming(message::RawReading::new(1, Endpoint::Tx, vec![4, 5]).into())
+      .await;
+    match device_events.next().await {
+      Some(ButtplugClientDeviceEvent::Message(ButtplugCurrentSpecServerMessage::RawReading(
+        reading,
+      ))) => {
+        assert_eq!(reading.data(), &vec![1, 2, 3, 4, 5]);
+        assert!(!reading.continued());
+      }
+      event => panic!("Expected a raw reading, got {:?}", event),
+    }
+  });
+}
+
 // TODO Test invalid messages to device
 // TODO Test invalid parameters in message
 // TODO Test device invalidation across client connections (i.e. a device shouldn't be allowed to reconnect even if index is the same)
@@ -0,0 +1,3197 @@
+// Buttplug Rust Source Code File - See https://buttplug.io for more info.
+//
+// Copyright 2016-2023 Nonpolynomial Labs LLC. All rights reserved.
+//
+// Licensed under the BSD 3-Clause license. See LICENSE file in the project root
+// for full license information.
+
+mod util;
+use util::{
+  test_device_manager::{check_test_recv_value, TestDeviceIdentifier, TestHardwareNotification},
+  test_server_with_device,
+  TestDeviceCommunicationManagerBuilder,
+  TestHardwareEvent,
+};
+
+use buttplug::{
+  core::{
+    connector::{ButtplugConnector, ButtplugConnectorError, ButtplugConnectorResultFuture},
+    errors::{ButtplugDeviceError, ButtplugError, ButtplugMessageError, ButtplugUnknownError},
+    message::{
+      self,
+      serializer::{
+        ButtplugMessageSerializer,
+        ButtplugSerializedMessage,
+        ButtplugServerJSONSerializer,
+        CodecType,
+      },
+      ButtplugClientMessage,
+      ButtplugCurrentSpecServerMessage,
+      ButtplugDeviceCommandMessageUnion,
+      ButtplugDeviceMessage,
+      ButtplugMessage,
+      ButtplugMessageSpecVersion,
+      ButtplugServerMessage,
+      Endpoint,
+      BUTTPLUG_CURRENT_MESSAGE_SPEC_VERSION,
+    },
+  },
+  server::{
+    device::{
+      hardware::{HardwareCommand, HardwareWriteCmd},
+      CommandConflictPolicy,
+    },
+    replay_transcript,
+    AnyButtplugEvent,
+    ButtplugConnectorRetryConfig,
+    ButtplugEventSink,
+    ButtplugRemoteServer,
+    ButtplugRemoteServerBuilder,
+    ButtplugRemoteServerEvent,
+    ButtplugRemoteServerEventStreamError,
+    ButtplugServer,
+    ButtplugServerBuilder,
+    ButtplugServerConnectorError,
+    ClientConnectedEvent,
+    ConnectionQualityConfig,
+    DeviceAddedEvent,
+    DeviceEvent,
+    DisconnectReason,
+    EventBufferPolicy,
+    IdleAction,
+    IdleShutdownPolicy,
+    MessageDirection,
+    OverflowPolicy,
+    RetryPolicy,
+    SessionEventKind,
+    TelemetryConfig,
+    TelemetryError,
+    EVENT_CHANNEL_RESIZE_INTERVAL,
+  },
+  util::async_manager,
+};
+use futures::{
+  future::{self, BoxFuture, RemoteHandle},
+  pin_mut,
+  FutureExt,
+  StreamExt,
+};
+use std::{
+  collections::HashMap,
+  sync::{
+    atomic::{AtomicBool, AtomicU32, Ordering},
+    Arc,
+    Mutex,
+  },
+  time::{Duration, Instant},
+};
+use tokio::sync::mpsc;
+#[cfg(feature = "http-info")]
+use tokio::{
+  io::{AsyncReadExt, AsyncWriteExt},
+  net::TcpStream,
+};
+
+type ClientSenderSlot = Arc<Mutex<Option<mpsc::Sender<ButtplugClientMessage>>>>;
+
+/// Connector that hands messages straight to/from the test, without any transport or
+/// serialization.
+#[derive(Clone)]
+struct TestServerConnector {
+  client_sender: ClientSenderSlot,
+  server_sender: mpsc::Sender<ButtplugServerMessage>,
+  max_message_size: Option<usize>,
+  /// How long each send takes, for keeping message handling busy.
+  send_delay: Option<Duration>,
+  /// Result of health checks.
+  healthy: bool,
+  /// Last timeout set with [ButtplugConnector::set_timeout].
+  timeout: Arc<Mutex<Option<Duration>>>,
+}
+
+impl ButtplugConnector<ButtplugServerMessage, ButtplugClientMessage> for TestServerConnector {
+  fn connect(
+    &mut self,
+    message_sender: mpsc::Sender<ButtplugClientMessage>,
+  ) -> BoxFuture<'static, Result<(), ButtplugConnectorError>> {
+    *self.client_sender.lock().unwrap() = Some(message_sender);
+    async { Ok(()) }.boxed()
+  }
+
+  fn disconnect(&self) -> ButtplugConnectorResultFuture {
+    async { Ok(()) }.boxed()
+  }
+
+  fn send(&self, msg: ButtplugServerMessage) -> ButtplugConnectorResultFuture {
+    let sender = self.server_sender.clone();
+    let send_delay = self.send_delay;
+    async move {
+      if let Some(send_delay) = send_delay {
+        tokio::time::sleep(send_delay).await;
+      }
+      sender
+        .send(msg)
+        .await
+        .map_err(|_| ButtplugConnectorError::ConnectorChannelClosed)
+    }
+    .boxed()
+  }
+
+  fn max_message_size(&self) -> Option<usize> {
+    self.max_message_size
+  }
+
+  fn check_health(&self) -> ButtplugConnectorResultFuture {
+    let healthy = self.healthy;
+    async move {
+      if healthy {
+        Ok(())
+      } else {
+        Err(ButtplugConnectorError::ConnectorChannelClosed)
+      }
+    }
+    .boxed()
+  }
+
+  fn set_timeout(&self, timeout: Duration) -> Result<(), ButtplugConnectorError> {
+    *self.timeout.lock().unwrap() = Some(timeout);
+    Ok(())
+  }
+}
+
+/// Connector that fails to connect while `remaining_failures` is above 0, then acts like a
+/// [TestServerConnector].
+struct FlakyServerConnector {
+  remaining_failures: Arc<AtomicU32>,
+  inner: TestServerConnector,
+}
+
+impl ButtplugConnector<ButtplugServerMessage, ButtplugClientMessage> for FlakyServerConnector {
+  fn connect(
+    &mut self,
+    message_sender: mpsc::Sender<ButtplugClientMessage>,
+  ) -> BoxFuture<'static, Result<(), ButtplugConnectorError>> {
+    if self
+      .remaining_failures
+      .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |count| {
+        count.checked_sub(1)
+      })
+      .is_ok()
+    {
+      return async { Err(ButtplugConnectorError::ConnectorNotConnected) }.boxed();
+    }
+    self.inner.connect(message_sender)
+  }
+
+  fn disconnect(&self) -> ButtplugConnectorResultFuture {
+    self.inner.disconnect()
+  }
+
+  fn send(&self, msg: ButtplugServerMessage) -> ButtplugConnectorResultFuture {
+    self.inner.send(msg)
+  }
+}
+
+/// Connector whose sends fail while `remaining_failures` is above 0, then acts like a
+/// [TestServerConnector].
+struct FlakySendConnector {
+  remaining_failures: Arc<AtomicU32>,
+  inner: TestServerConnector,
+}
+
+impl ButtplugConnector<ButtplugServerMessage, ButtplugClientMessage> for FlakySendConnector {
+  fn connect(
+    &mut self,
+    message_sender: mpsc::Sender<ButtplugClientMessage>,
+  ) -> BoxFuture<'static, Result<(), ButtplugConnectorError>> {
+    self.inner.connect(message_sender)
+  }
+
+  fn disconnect(&self) -> ButtplugConnectorResultFuture {
+    self.inner.disconnect()
+  }
+
+  fn send(&self, msg: ButtplugServerMessage) -> ButtplugConnectorResultFuture {
+    if self
+      .remaining_failures
+      .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |count| {
+        count.checked_sub(1)
+      })
+      .is_ok()
+    {
+      return async { Err(ButtplugConnectorError::ConnectorChannelClosed) }.boxed();
+    }
+    self.inner.send(msg)
+  }
+}
+
+/// Connector whose connect never finishes, like a listener that never gets a connection. Sets
+/// `connecting` once connect has been called.
+#[derive(Default)]
+struct PendingServerConnector {
+  connecting: Arc<AtomicBool>,
+}
+
+impl ButtplugConnector<ButtplugServerMessage, ButtplugClientMessage> for PendingServerConnector {
+  fn connect(
+    &mut self,
+    _message_sender: mpsc::Sender<ButtplugClientMessage>,
+  ) -> BoxFuture<'static, Result<(), ButtplugConnectorError>> {
+    self.connecting.store(true, Ordering::SeqCst);
+    future::pending().boxed()
+  }
+
+  fn disconnect(&self) -> ButtplugConnectorResultFuture {
+    async { Ok(()) }.boxed()
+  }
+
+  fn send(&self, _msg: ButtplugServerMessage) -> ButtplugConnectorResultFuture {
+    async { Err(ButtplugConnectorError::ConnectorNotConnected) }.boxed()
+  }
+}
+
+/// Returns the connector, a slot that holds the sender for client messages once the remote server
+/// has connected, and a receiver for everything the server sends back.
+fn test_server_connector() -> (
+  TestServerConnector,
+  ClientSenderSlot,
+  mpsc::Receiver<ButtplugServerMessage>,
+) {
+  let client_sender = Arc::new(Mutex::new(None));
+  let (server_sender, server_receiver) = mpsc::channel(256);
+  (
+    TestServerConnector {
+      client_sender: client_sender.clone(),
+      server_sender,
+      max_message_size: None,
+      send_delay: None,
+      healthy: true,
+      timeout: Arc::new(Mutex::new(None)),
+    },
+    client_sender,
+    server_receiver,
+  )
+}
+
+#[test]
+fn test_remote_server_client_idle_timeout() {
+  async_manager::block_on(async {
+    let server = ButtplugServerBuilder::default()
+      .client_idle_timeout(Duration::from_millis(100))
+      .finish()
+      .unwrap();
+    let remote_server = Arc::new(ButtplugRemoteServer::new(server));
+    let events = remote_server.event_stream();
+    pin_mut!(events);
+    assert!(remote_server.last_client_message_at().is_none());
+    assert!(remote_server.negotiated_config().is_none());
+    assert!(remote_server.connected_since().is_none());
+    assert_eq!(remote_server.connection_duration(), Duration::ZERO);
+    assert!(remote_server.connector_type_name().is_none());
+
+    let (server_task, _sender, _server_receiver) = start_test_session(&remote_server).await;
+    assert!(remote_server.last_client_message_at().is_some());
+    assert_eq!(remote_server.pending_client_message_count(), 0);
+    assert!(remote_server.connected_since().is_some());
+    let negotiated_config = remote_server
+      .negotiated_config()
+      .expect("Handshake has happened");
+    assert_eq!(
+      negotiated_config.client_spec_version(),
+      BUTTPLUG_CURRENT_MESSAGE_SPEC_VERSION as u32
+    );
+    assert_eq!(negotiated_config.codec(), CodecType::Unserialized);
+    assert_eq!(negotiated_config.ping_timeout(), Duration::ZERO);
+    assert!(remote_server
+      .connector_type_name()
+      .expect("Session is running")
+      .ends_with("TestServerConnector"));
+    assert!(matches!(
+      events.next().await,
+      Some(ButtplugRemoteServerEvent::ClientConnected(..))
+    ));
+    // Send nothing else, and the server should time us out.
+    assert!(matches!(
+      events.next().await,
+      Some(ButtplugRemoteServerEvent::ClientIdleTimeout)
+    ));
+    assert!(matches!(
+      events.next().await,
+      Some(ButtplugRemoteServerEvent::ClientDisconnected(
+        _,
+        Some(DisconnectReason::IdleTimeout)
+      ))
+    ));
+    server_task.await;
+    assert!(remote_server.connected_since().is_none());
+    assert_eq!(remote_server.connection_duration(), Duration::ZERO);
+    assert!(remote_server.connector_type_name().is_none());
+  });
+}
+
+#[cfg(feature = "websockets")]
+#[test]
+fn test_remote_server_connector_error_os_error_code() {
+  use buttplug::core::{
+    connector::{
+      ButtplugRemoteServerConnector,
+      ButtplugWebsocketServerTransport,
+      ButtplugWebsocketServerTransportBuilder,
+    },
+    message::serializer::ButtplugServerJSONSerializer,
+  };
+
+  async_manager::block_on(async {
+    // Hold the port so the websocket server can't bind to it.
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+    let connector = ButtplugRemoteServerConnector::<
+      ButtplugWebsocketServerTransport,
+      ButtplugServerJSONSerializer,
+    >::new(
+      ButtplugWebsocketServerTransportBuilder::default()
+        .port(port)
+        .finish(),
+    );
+    let err = ButtplugRemoteServer::default()
+      .start(connector)
+      .await
+      .expect_err("Port is already in use");
+    assert!(err.os_error_code().is_some());
+  });
+}
+
+#[cfg(feature = "websockets")]
+#[test]
+fn test_websocket_connector_peer_address() {
+  use buttplug::core::{
+    connector::{
+      ButtplugRemoteClientConnector,
+      ButtplugRemoteServerConnector,
+      ButtplugWebsocketClientTransport,
+      ButtplugWebsocketServerTransport,
+      ButtplugWebsocketServerTransportBuilder,
+    },
+    message::serializer::{ButtplugClientJSONSerializer, ButtplugServerJSONSerializer},
+  };
+
+  async_manager::block_on(async {
+    // Find a free port, then release it for the websocket server.
+    let port = std::net::TcpListener::bind("127.0.0.1:0")
+      .unwrap()
+      .local_addr()
+      .unwrap()
+      .port();
+    let mut server_connector = ButtplugRemoteServerConnector::<
+      ButtplugWebsocketServerTransport,
+      ButtplugServerJSONSerializer,
+    >::new(
+      ButtplugWebsocketServerTransportBuilder::default()
+        .port(port)
+        .finish(),
+    );
+    assert!(server_connector.peer_address().is_none());
+    let (server_sender, _server_receiver) = mpsc::channel(256);
+    let server_connect =
+      async_manager::spawn_with_handle(server_connector.connect(server_sender)).unwrap();
+
+    let mut client_connector = None;
+    for _ in 0..10u8 {
+      let mut connector = ButtplugRemoteClientConnector::<
+        ButtplugWebsocketClientTransport,
+        ButtplugClientJSONSerializer,
+      >::new(ButtplugWebsocketClientTransport::new_insecure_connector(
+        &format!("ws://127.0.0.1:{}", port),
+      ));
+      let (client_sender, _client_receiver) = mpsc::channel(256);
+      if connector.connect(client_sender).await.is_ok() {
+        client_connector = Some(connector);
+        break;
+      }
+      tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+    let client_connector = client_connector.expect("Client should connect");
+    server_connect.await.unwrap();
+    let server_peer = server_connector
+      .peer_address()
+      .expect("Server knows the client address");
+    let client_peer = client_connector
+      .peer_address()
+      .expect("Client knows the server address");
+    assert_eq!(client_peer.port(), port);
+    assert!(server_peer.ip().is_loopback());
+  });
+}
+
+#[cfg(feature = "websockets")]
+#[test]
+fn test_remote_server_tls_config() {
+  use buttplug::core::{
+    connector::{
+      ButtplugRemoteServerConnector,
+      ButtplugWebsocketServerTransport,
+      ButtplugWebsocketServerTransportBuilder,
+      TlsConfig,
+    },
+    message::serializer::ButtplugServerJSONSerializer,
+  };
+
+  async_manager::block_on(async {
+    let missing_dir = std::env::temp_dir().join("buttplug-tls-config-test-missing");
+    let tls_config = TlsConfig::new(missing_dir.join("cert.pem"), missing_dir.join("key.pem"));
+    for tls_config in [
+      tls_config.clone(),
+      tls_config.with_client_ca(missing_dir.join("ca.pem"), true),
+    ] {
+      let server = ButtplugServerBuilder::default()
+        .with_tls_config(tls_config.clone())
+        .finish()
+        .unwrap();
+      assert_eq!(server.tls_config(), Some(tls_config));
+      let remote_server = ButtplugRemoteServer::new(server);
+      // The server's TLS settings reach the transport, which fails to load them before
+      // listening.
+      let connector = ButtplugRemoteServerConnector::<
+        ButtplugWebsocketServerTransport,
+        ButtplugServerJSONSerializer,
+      >::new(
+        ButtplugWebsocketServerTransportBuilder::default()
+          .port(0)
+          .finish(),
+      );
+      assert!(remote_server.start(connector).await.is_err());
+    }
+  });
+}
+
+#[cfg(all(
+  feature = "websockets",
+  not(any(target_os = "windows", target_vendor = "apple"))
+))]
+#[test]
+fn test_remote_server_tls_requires_buttplug_alpn() {
+  use buttplug::{
+    client::ButtplugClient,
+    core::{
+      connector::{
+        ButtplugRemoteClientConnector,
+        ButtplugRemoteServerConnector,
+        ButtplugWebsocketClientTransport,
+        ButtplugWebsocketServerTransport,
+        ButtplugWebsocketServerTransportBuilder,
+        TlsConfig,
+      },
+      message::serializer::{ButtplugClientJSONSerializer, ButtplugServerJSONSerializer},
+    },
+  };
+  use std::path::PathBuf;
+
+  async_manager::block_on(async {
+    let tls_dir = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/util/tls");
+    let tls_config = TlsConfig::new(tls_dir.join("cert.pem"), tls_dir.join("key.pem"));
+    let start_tls_server = |allow_missing_alpn: bool| {
+      let port = std::net::TcpListener::bind("127.0.0.1:0")
+        .unwrap()
+        .local_addr()
+        .unwrap()
+        .port();
+      let remote_server = ButtplugRemoteServer::new(
+        ButtplugServerBuilder::default()
+          .with_tls_config(tls_config.clone())
+          .finish()
+          .unwrap(),
+      );
+      let connector = ButtplugRemoteServerConnector::<
+        ButtplugWebsocketServerTransport,
+        ButtplugServerJSONSerializer,
+      >::new(
+        ButtplugWebsocketServerTransportBuilder::default()
+          .port(port)
+          .allow_missing_alpn(allow_missing_alpn)
+          .finish(),
+      );
+      let server_task =
+        async_manager::spawn_with_handle(async move { remote_server.start(connector).await })
+          .unwrap();
+      (port, server_task)
+    };
+
+    // Connects a plain TLS client that doesn't ask for a Buttplug protocol, then hangs up.
+    let connect_plain_tls = |port: u16| async move {
+      let mut stream = None;
+      for _ in 0..10u8 {
+        if let Ok(tcp_stream) = tokio::net::TcpStream::connect(("127.0.0.1", port)).await {
+          stream = Some(tcp_stream);
+          break;
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+      }
+      let tls_connector = tokio_native_tls::TlsConnector::from(
+        native_tls::TlsConnector::builder()
+          .danger_accept_invalid_certs(true)
+          .build()
+          .unwrap(),
+      );
+      let _tls_stream = tls_connector
+        .connect("localhost", stream.expect("Server should be listening"))
+        .await;
+    };
+
+    // By default, the plain TLS client is turned away.
+    let (port, server_task) = start_tls_server(false);
+    connect_plain_tls(port).await;
+    let err = server_task.await.unwrap_err();
+    assert!(err.to_string().contains("ALPN"));
+
+    // With the check opted out of, it gets as far as the websocket handshake.
+    let (port, server_task) = start_tls_server(true);
+    connect_plain_tls(port).await;
+    let err = server_task.await.unwrap_err();
+    assert!(!err.to_string().contains("ALPN"));
+
+    // The Buttplug websocket client asks for buttplug-json, so it gets in.
+    let (port, _server_task) = start_tls_server(false);
+    let mut connected = false;
+    for _ in 0..10u8 {
+      let connector = ButtplugRemoteClientConnector::<
+        ButtplugWebsocketClientTransport,
+        ButtplugClientJSONSerializer,
+      >::new(ButtplugWebsocketClientTransport::new_secure_connector(
+        &format!("wss://127.0.0.1:{}", port),
+        true,
+      ));
+      let client = ButtplugClient::new("Test Client");
+      if client.connect(connector).await.is_ok() {
+        connected = true;
+        break;
+      }
+      tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+    assert!(connected);
+  });
+}
+
+#[test]
+fn test_remote_server_server_name() {
+  async_manager::block_on(async {
+    let server = ButtplugServerBuilder::default()
+      .name("Dashboard Server")
+      .finish()
+      .expect("Test, assuming infallible.");
+    let remote_server = ButtplugRemoteServer::new(server);
+    assert_eq!(remote_server.server_name(), "Dashboard Server");
+    remote_server.set_server_name("Renamed Server".to_owned());
+    assert_eq!(remote_server.server_name(), "Renamed Server");
+  });
+}
+
+#[cfg(feature = "testing")]
+#[test]
+fn test_remote_server_inject_server_event() {
+  async_manager::block_on(async {
+    let remote_server = ButtplugRemoteServer::default();
+    let events = remote_server.event_stream();
+    pin_mut!(events);
+    remote_server.inject_server_event(ButtplugRemoteServerEvent::DeviceAdded(
+      3,
+      "Test Device".to_owned(),
+      "test-address".to_owned(),
+      None,
+    ));
+    remote_server.inject_server_event(ButtplugRemoteServerEvent::DeviceRemoved(3));
+    assert!(matches!(
+      events.next().await,
+      Some(ButtplugRemoteServerEvent::DeviceAdded(3, _, _, None))
+    ));
+    assert!(matches!(
+      events.next().await,
+      Some(ButtplugRemoteServerEvent::DeviceRemoved(3))
+    ));
+  });
+}
+
+#[cfg(feature = "custom-events")]
+#[test]
+fn test_remote_server_emit_custom_event() {
+  async_manager::block_on(async {
+    let remote_server = ButtplugRemoteServer::default();
+    assert!(matches!(
+      remote_server.emit_custom_event(serde_json::json!({"token": "renewed"})),
+      Err(ButtplugError::ButtplugUnknownError(
+        ButtplugUnknownError::NoEventListeners
+      ))
+    ));
+    let events = remote_server.event_stream();
+    pin_mut!(events);
+    remote_server
+      .emit_custom_event(serde_json::json!({"token": "renewed"}))
+      .unwrap();
+    match events.next().await {
+      Some(ButtplugRemoteServerEvent::Custom(payload)) => assert_eq!(payload["token"], "renewed"),
+      event => panic!("Expected a custom event, got {:?}", event),
+    }
+  });
+}
+
+#[test]
+fn test_remote_server_shutdown_with_timeout() {
+  async_manager::block_on(async {
+    let remote_server = ButtplugRemoteServer::default();
+    remote_server
+      .shutdown_with_timeout(Duration::from_secs(5))
+      .await
+      .unwrap();
+  });
+}
+
+#[test]
+fn test_remote_server_shutdown_all_devices_then_disconnect() {
+  async_manager::block_on(async {
+    let (server, mut device) = test_server_with_device("Massage Demo", false).await;
+    let remote_server = Arc::new(ButtplugRemoteServer::new(server));
+    let (session, sender, mut server_receiver) = start_test_session(&remote_server).await;
+    connect_test_device(&sender, &mut server_receiver).await;
+    let mut vibrate = message::VibrateCmd::new(0, vec![message::VibrateSubcommand::new(0, 0.5)]);
+    vibrate.set_id(3);
+    sender.send(vibrate.into()).await.unwrap();
+    wait_for_reply(&mut server_receiver, 3).await;
+    check_test_recv_value(
+      &mut device,
+      HardwareCommand::Write(HardwareWriteCmd::new(Endpoint::Tx, vec![0xF1, 64], false)),
+    );
+
+    remote_server
+      .shutdown_all_devices_then_disconnect(Duration::from_secs(5))
+      .await
+      .unwrap();
+    check_test_recv_value(
+      &mut device,
+      HardwareCommand::Write(HardwareWriteCmd::new(Endpoint::Tx, vec![0xF1, 0], false)),
+    );
+    // The client was disconnected, so the session ends.
+    session.await;
+  });
+}
+
+#[test]
+fn test_remote_server_on_shutdown_callbacks() {
+  async_manager::block_on(async {
+    let calls = Arc::new(Mutex::new(vec![]));
+    let remote_server = ButtplugRemoteServer::default();
+    for id in 0..2 {
+      let calls = calls.clone();
+      remote_server.on_shutdown(move || calls.lock().unwrap().push(id));
+    }
+    remote_server.shutdown().await.unwrap();
+    assert_eq!(*calls.lock().unwrap(), vec![0, 1]);
+    // Callbacks only run once.
+    remote_server.shutdown().await.unwrap();
+    assert_eq!(calls.lock().unwrap().len(), 2);
+
+    let dropped_server = ButtplugRemoteServer::default();
+    let calls_clone = calls.clone();
+    dropped_server.on_shutdown(move || calls_clone.lock().unwrap().push(2));
+    drop(dropped_server);
+    assert_eq!(*calls.lock().unwrap(), vec![0, 1, 2]);
+  });
+}
+
+#[test]
+fn test_remote_server_client_rate_limit() {
+  async_manager::block_on(async {
+    let server = ButtplugServerBuilder::default()
+      .client_rate_limit(2, Duration::from_secs(60))
+      .finish()
+      .unwrap();
+    let remote_server = Arc::new(ButtplugRemoteServer::new(server));
+    let events = remote_server.event_stream();
+    pin_mut!(events);
+
+    let (_server_task, sender, mut server_receiver) = start_test_session(&remote_server).await;
+    for id in 2..5 {
+      let mut msg = message::RequestDeviceList::default();
+      msg.set_id(id);
+      sender.send(msg.into()).await.unwrap();
+    }
+    let mut rate_limited = 0;
+    for _ in 2..5 {
+      if let Some(ButtplugServerMessage::Error(err)) = server_receiver.recv().await {
+        assert_eq!(err.error_code(), message::ErrorCode::ErrorMessage);
+        rate_limited += 1;
+      }
+    }
+    assert_eq!(rate_limited, 2);
+    assert!(matches!(
+      events.next().await,
+      Some(ButtplugRemoteServerEvent::ClientConnected(..))
+    ));
+    // Only the first drop in the window is reported.
+    assert!(matches!(
+      events.next().await,
+      Some(ButtplugRemoteServerEvent::RateLimitExceeded {
+        message_id: 3,
+        drop_count: 1,
+        ..
+      })
+    ));
+    assert!(remote_server.disconnect().await.is_ok());
+  });
+}
+
+#[test]
+fn test_remote_server_connection_quality() {
+  async_manager::block_on(async {
+    let server = ButtplugServerBuilder::default()
+      .client_rate_limit(2, Duration::from_secs(60))
+      .finish()
+      .unwrap();
+    // Only count dropped messages, so the score doesn't depend on timing.
+    let remote_server = Arc::new(
+      ButtplugRemoteServerBuilder::default()
+        .server(server)
+        .connection_quality(ConnectionQualityConfig::new(
+          0.0,
+          0.0,
+          1.0,
+          Duration::from_secs(1),
+          0.6,
+        ))
+        .finish(),
+    );
+    let events = remote_server.event_stream();
+    pin_mut!(events);
+    assert_eq!(remote_server.connection_quality(), 1.0);
+
+    let (_server_task, sender, mut server_receiver) = start_test_session(&remote_server).await;
+    assert_eq!(remote_server.connection_quality(), 1.0);
+    // Replies and drops can be recorded in any order, so drop enough messages that the score ends
+    // up below the threshold whatever the order was.
+    for id in 2..8 {
+      let mut msg = message::RequestDeviceList::default();
+      msg.set_id(id);
+      sender.send(msg.into()).await.unwrap();
+    }
+    // Dropped messages are answered right away, so replies can arrive out of order.
+    let mut unanswered: Vec<u32> = (2..8).collect();
+    while !unanswered.is_empty() {
+      let reply = server_receiver
+        .recv()
+        .await
+        .expect("Test, assuming infallible.");
+      unanswered.retain(|id| *id != reply.id());
+    }
+    loop {
+      if let Some(ButtplugRemoteServerEvent::ConnectionQualityDegraded { score }) =
+        events.next().await
+      {
+        assert!(score < 0.6);
+        break;
+      }
+    }
+    assert!(remote_server.connection_quality() < 0.6);
+    assert!(remote_server.disconnect().await.is_ok());
+  });
+}
+
+#[test]
+fn test_remote_server_message_transformer() {
+  async_manager::block_on(async {
+    let server = ButtplugServerBuilder::default()
+      .message_transformer(message::ClampIntensityTransformer::default())
+      .finish()
+      .unwrap();
+    let remote_server = Arc::new(ButtplugRemoteServer::new(server));
+
+    let (_server_task, sender, mut server_receiver) = start_test_session(&remote_server).await;
+    // Out of range, but clamped instead of failing validation, so we get as far as the device
+    // lookup.
+    let mut msg = message::ScalarCmd::new(
+      0,
+      vec![message::ScalarSubcommand::new(
+        0,
+        2.0,
+        message::ActuatorType::Vibrate,
+      )],
+    );
+    msg.set_id(2);
+    sender.send(msg.into()).await.unwrap();
+    if let Some(ButtplugServerMessage::Error(err)) = server_receiver.recv().await {
+      assert_eq!(err.id(), 2);
+      assert_eq!(err.error_code(), message::ErrorCode::ErrorDevice);
+    } else {
+      panic!("Should get a device error for the missing device");
+    }
+    assert!(remote_server.disconnect().await.is_ok());
+  });
+}
+
+#[test]
+fn test_remote_server_outbound_message_hook() {
+  async_manager::block_on(async {
+    let outbound_messages = Arc::new(Mutex::new(vec![]));
+    let outbound_messages_clone = outbound_messages.clone();
+    let remote_server = Arc::new(
+      ButtplugRemoteServerBuilder::default()
+        .on_outbound_message(move |msg| outbound_messages_clone.lock().unwrap().push(msg.id()))
+        .finish(),
+    );
+
+    // The handshake reply has id 1.
+    let (_server_task, sender, mut server_receiver) = start_test_session(&remote_server).await;
+    // Invalid replies go through the hook too.
+    let mut msg: ButtplugClientMessage = message::Ping::default().into();
+    msg.set_id(0);
+    sender.send(msg).await.unwrap();
+    assert!(matches!(
+      server_receiver.recv().await,
+      Some(ButtplugServerMessage::Error(_))
+    ));
+    assert_eq!(*outbound_messages.lock().unwrap(), vec![1, 0]);
+    assert!(remote_server.disconnect().await.is_ok());
+  });
+}
+
+/// Start a session on the remote server and complete the handshake. Returns a handle to the
+/// server task, and the receiver for messages sent to the client.
+async fn start_test_session(
+  remote_server: &Arc<ButtplugRemoteServer>,
+) -> (
+  RemoteHandle<()>,
+  mpsc::Sender<ButtplugClientMessage>,
+  mpsc::Receiver<ButtplugServerMessage>,
+) {
+  let (connector, client_sender, server_receiver) = test_server_connector();
+  start_test_session_with_connector(remote_server, connector, client_sender, server_receiver).await
+}
+
+/// Like [start_test_session], over a connector from [test_server_connector] that the test has
+/// already set up.
+async fn start_test_session_with_connector(
+  remote_server: &Arc<ButtplugRemoteServer>,
+  connector: TestServerConnector,
+  client_sender: ClientSenderSlot,
+  mut server_receiver: mpsc::Receiver<ButtplugServerMessage>,
+) -> (
+  RemoteHandle<()>,
+  mpsc::Sender<ButtplugClientMessage>,
+  mpsc::Receiver<ButtplugServerMessage>,
+) {
+  let (server_task, sender) = spawn_test_session(remote_server, connector, client_sender).await;
+  sender
+    .send(
+      message::RequestServerInfo::new("Test Client", BUTTPLUG_CURRENT_MESSAGE_SPEC_VERSION).into(),
+    )
+    .await
+    .unwrap();
+  assert!(matches!(
+    server_receiver.recv().await,
+    Some(ButtplugServerMessage::ServerInfo(_))
+  ));
+  (server_task, sender, server_receiver)
+}
+
+/// Start a session on the remote server over `connector`, without doing the handshake. Returns a
+/// handle to the server task, and the sender for client messages once the session is running.
+async fn spawn_test_session(
+  remote_server: &Arc<ButtplugRemoteServer>,
+  connector: TestServerConnector,
+  client_sender: ClientSenderSlot,
+) -> (RemoteHandle<()>, mpsc::Sender<ButtplugClientMessage>) {
+  let remote_server_clone = remote_server.clone();
+  let server_task = async_manager::spawn_with_handle(async move {
+    remote_server_clone.start(connector).await.unwrap();
+  })
+  .unwrap();
+  while client_sender.lock().unwrap().is_none() {
+    tokio::task::yield_now().await;
+  }
+  let sender = client_sender.lock().unwrap().clone().unwrap();
+  (server_task, sender)
+}
+
+/// Start scanning in a session started by [start_test_session], and wait for the test device to be
+/// added. The StartScanning message has id 2.
+async fn connect_test_device(
+  sender: &mpsc::Sender<ButtplugClientMessage>,
+  server_receiver: &mut mpsc::Receiver<ButtplugServerMessage>,
+) {
+  let mut start_scanning = message::StartScanning::default();
+  start_scanning.set_id(2);
+  sender.send(start_scanning.into()).await.unwrap();
+  while !matches!(
+    server_receiver.recv().await,
+    Some(ButtplugServerMessage::DeviceAdded(_))
+  ) {}
+}
+
+#[test]
+fn test_remote_server_force_reconnect() {
+  async_manager::block_on(async {
+    let remote_server = Arc::new(ButtplugRemoteServer::default());
+    let events = remote_server.event_stream();
+    pin_mut!(events);
+
+    let (first_session, _first_sender, _first_receiver) = start_test_session(&remote_server).await;
+    assert!(matches!(
+      events.next().await,
+      Some(ButtplugRemoteServerEvent::ClientConnected(..))
+    ));
+    let remote_server_clone = remote_server.clone();
+    let reconnect =
+      async_manager::spawn_with_handle(async move { remote_server_clone.force_reconnect().await })
+        .unwrap();
+    assert!(matches!(
+      events.next().await,
+      Some(ButtplugRemoteServerEvent::ClientDisconnected(
+        _,
+        Some(DisconnectReason::ForcedReconnect)
+      ))
+    ));
+    first_session.await;
+
+    let (_second_session, _second_sender, _second_receiver) =
+      start_test_session(&remote_server).await;
+    assert!(matches!(
+      events.next().await,
+      Some(ButtplugRemoteServerEvent::ClientConnected(..))
+    ));
+    assert!(reconnect.await.is_ok());
+
+    // Nothing reconnects this time.
+    assert!(matches!(
+      remote_server
+        .force_reconnect_with_timeout(Duration::from_millis(50))
+        .await,
+      Err(ButtplugError::ButtplugUnknownError(
+        ButtplugUnknownError::ReconnectTimedOut(_)
+      ))
+    ));
+  });
+}
+
+#[test]
+fn test_remote_server_disconnect_client() {
+  async_manager::block_on(async {
+    let remote_server = Arc::new(ButtplugRemoteServer::default());
+    let events = remote_server.event_stream();
+    pin_mut!(events);
+
+    let (server_task, _sender, _server_receiver) = start_test_session(&remote_server).await;
+    assert!(matches!(
+      events.next().await,
+      Some(ButtplugRemoteServerEvent::ClientConnected(..))
+    ));
+    remote_server
+      .disconnect_client(DisconnectReason::AuthFailed)
+      .await;
+    assert!(matches!(
+      events.next().await,
+      Some(ButtplugRemoteServerEvent::ClientDisconnected(
+        _,
+        Some(DisconnectReason::AuthFailed)
+      ))
+    ));
+    server_task.await;
+  });
+}
+
+#[test]
+fn test_remote_server_subscribe_to_device() {
+  async_manager::block_on(async {
+    let (server, device) = test_server_with_device("Massage Demo", false).await;
+    let remote_server = Arc::new(ButtplugRemoteServer::new(server));
+    let device_events = remote_server.subscribe_to_device(0);
+    pin_mut!(device_events);
+    let (_session, sender, mut server_receiver) = start_test_session(&remote_server).await;
+    let mut start_scanning = message::StartScanning::default();
+    start_scanning.set_id(2);
+    sender.send(start_scanning.into()).await.unwrap();
+    assert!(matches!(
+      device_events.next().await,
+      Some(DeviceEvent::Connected)
+    ));
+
+    let mut vibrate = message::VibrateCmd::new(0, vec![message::VibrateSubcommand::new(0, 0.5)]);
+    vibrate.set_id(3);
+    sender.send(vibrate.into()).await.unwrap();
+    assert!(matches!(
+      device_events.next().await,
+      Some(DeviceEvent::CommandSent(ButtplugClientMessage::VibrateCmd(
+        _
+      )))
+    ));
+
+    // Massage Demo only has 2 vibrators.
+    let mut bad_vibrate =
+      message::VibrateCmd::new(0, vec![message::VibrateSubcommand::new(5, 0.5)]);
+    bad_vibrate.set_id(4);
+    sender.send(bad_vibrate.into()).await.unwrap();
+    assert!(matches!(
+      device_events.next().await,
+      Some(DeviceEvent::Error(_))
+    ));
+
+    device
+      .sender
+      .send(TestHardwareEvent::Disconnect)
+      .await
+      .unwrap();
+    assert!(matches!(
+      device_events.next().await,
+      Some(DeviceEvent::Disconnected)
+    ));
+    assert!(device_events.next().await.is_none());
+    server_receiver.close();
+  });
+}
+
+#[test]
+fn test_remote_server_device_unstable() {
+  async_manager::block_on(async {
+    let mut comm_manager = TestDeviceCommunicationManagerBuilder::default();
+    let device = comm_manager.add_test_device(&TestDeviceIdentifier::new("Massage Demo", None));
+    let server = ButtplugServerBuilder::default()
+      .comm_manager(comm_manager)
+      .device_error_threshold(1)
+      .finish()
+      .unwrap();
+    let remote_server = Arc::new(ButtplugRemoteServer::new(server));
+    let mut events = Box::pin(remote_server.event_stream());
+    let (_session, sender, mut server_receiver) = start_test_session(&remote_server).await;
+    connect_test_device(&sender, &mut server_receiver).await;
+
+    device
+      .sender
+      .send(TestHardwareEvent::FailWrites(true))
+      .await
+      .unwrap();
+    // Give the test device time to pick up the event.
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    for (id, speed) in [(3, 0.5), (4, 0.7)] {
+      let mut vibrate =
+        message::VibrateCmd::new(0, vec![message::VibrateSubcommand::new(0, speed)]);
+      vibrate.set_id(id);
+      sender.send(vibrate.into()).await.unwrap();
+      assert!(matches!(
+        wait_for_reply(&mut server_receiver, id).await,
+        ButtplugServerMessage::Error(_)
+      ));
+    }
+    assert_eq!(
+      remote_server.server().device_communication_error_count(0),
+      2
+    );
+    loop {
+      match events.next().await {
+        Some(ButtplugRemoteServerEvent::DeviceUnstable {
+          device_index,
+          error_count,
+        }) => {
+          assert_eq!(device_index, 0);
+          assert_eq!(error_count, 2);
+          break;
+        }
+        Some(_) => continue,
+        None => panic!("Event stream ended before DeviceUnstable"),
+      }
+    }
+    server_receiver.close();
+  });
+}
+
+#[test]
+fn test_remote_server_subscribe_typed_events() {
+  async_manager::block_on(async {
+    let (server, _device) = test_server_with_device("Massage Demo", false).await;
+    let remote_server = Arc::new(ButtplugRemoteServer::new(server));
+    let client_events = remote_server.subscribe::<ClientConnectedEvent>();
+    let added_events = remote_server.subscribe::<DeviceAddedEvent>();
+    pin_mut!(client_events);
+    pin_mut!(added_events);
+    let (_session, sender, mut server_receiver) = start_test_session(&remote_server).await;
+    let client_event = client_events
+      .next()
+      .await
+      .expect("Test, assuming infallible");
+    assert_eq!(client_event.client_name(), "Test Client");
+
+    let mut start_scanning = message::StartScanning::default();
+    start_scanning.set_id(2);
+    sender.send(start_scanning.into()).await.unwrap();
+    let added_event = added_events
+      .next()
+      .await
+      .expect("Test, assuming infallible");
+    assert_eq!(added_event.device_index(), 0);
+    server_receiver.close();
+  });
+}
+
+/// Wait for the reply to the message with the given id, skipping any events sent in between.
+async fn wait_for_reply(
+  server_receiver: &mut mpsc::Receiver<ButtplugServerMessage>,
+  id: u32,
+) -> ButtplugServerMessage {
+  loop {
+    let msg = server_receiver
+      .recv()
+      .await
+      .expect("Test, assuming infallible.");
+    if msg.id() == id {
+      return msg;
+    }
+  }
+}
+
+#[test]
+fn test_remote_server_device_command_event() {
+  async_manager::block_on(async {
+    let (server, _device) = test_server_with_device("Massage Demo", false).await;
+    let remote_server = Arc::new(ButtplugRemoteServer::new(server));
+    let mut events = Box::pin(remote_server.event_stream());
+    let (_session, sender, mut server_receiver) = start_test_session(&remote_server).await;
+    let mut start_scanning = message::StartScanning::default();
+    start_scanning.set_id(2);
+    sender.send(start_scanning.into()).await.unwrap();
+    while !matches!(
+      events.next().await,
+      Some(ButtplugRemoteServerEvent::DeviceAdded(0, ..))
+    ) {}
+
+    let mut device_list = message::RequestDeviceList::default();
+    device_list.set_id(3);
+    sender.send(device_list.into()).await.unwrap();
+    wait_for_reply(&mut server_receiver, 3).await;
+    let mut vibrate = message::VibrateCmd::new(0, vec![message::VibrateSubcommand::new(0, 0.5)]);
+    vibrate.set_id(4);
+    sender.send(vibrate.into()).await.unwrap();
+    wait_for_reply(&mut server_receiver, 4).await;
+    // The device list request shouldn't show up, so the first device command is the vibrate.
+    loop {
+      match events.next().await {
+        Some(ButtplugRemoteServerEvent::DeviceCommand(
+          ButtplugDeviceCommandMessageUnion::VibrateCmd(cmd),
+        )) => {
+          assert_eq!(cmd.device_index(), 0);
+          assert_eq!(cmd.speeds()[0].speed(), 0.5);
+          break;
+        }
+        Some(ButtplugRemoteServerEvent::DeviceCommand(cmd)) => {
+          panic!("Unexpected device command event: {:?}", cmd)
+        }
+        Some(_) => continue,
+        None => panic!("Event stream ended"),
+      }
+    }
+
+    // With nobody listening, device commands should still go through.
+    drop(events);
+    for id in 5..10 {
+      let mut vibrate = message::VibrateCmd::new(0, vec![message::VibrateSubcommand::new(0, 0.25)]);
+      vibrate.set_id(id);
+      sender.send(vibrate.into()).await.unwrap();
+      assert!(matches!(
+        wait_for_reply(&mut server_receiver, id).await,
+        ButtplugServerMessage::Ok(_)
+      ));
+    }
+  });
+}
+
+#[test]
+fn test_remote_server_max_intensity_for_session() {
+  async_manager::block_on(async {
+    let (server, mut device) = test_server_with_device("Massage Demo", false).await;
+    let remote_server = Arc::new(ButtplugRemoteServer::new(server));
+    let mut events = Box::pin(remote_server.event_stream());
+    let (_session, sender, mut server_receiver) = start_test_session(&remote_server).await;
+    let mut start_scanning = message::StartScanning::default();
+    start_scanning.set_id(2);
+    sender.send(start_scanning.into()).await.unwrap();
+    while !matches!(
+      events.next().await,
+      Some(ButtplugRemoteServerEvent::DeviceAdded(0, ..))
+    ) {}
+
+    remote_server.set_max_intensity_for_session(1.5);
+    assert_eq!(remote_server.max_intensity_for_session(), 1.0);
+    remote_server.set_max_intensity_for_session(0.5);
+    let mut vibrate = message::VibrateCmd::new(0, vec![message::VibrateSubcommand::new(0, 1.0)]);
+    vibrate.set_id(3);
+    sender.send(vibrate.into()).await.unwrap();
+    wait_for_reply(&mut server_receiver, 3).await;
+    check_test_recv_value(
+      &mut device,
+      HardwareCommand::Write(HardwareWriteCmd::new(Endpoint::Tx, vec![0xF1, 64], false)),
+    );
+  });
+}
+
+#[test]
+fn test_remote_server_bounded_event_stream_overflow() {
+  async_manager::block_on(async {
+    let (server, _device) = test_server_with_device("Massage Demo", false).await;
+    let remote_server = Arc::new(
+      ButtplugRemoteServerBuilder::default()
+        .server(server)
+        .event_channel_capacity(2)
+        .finish(),
+    );
+    // Neither of these are read until the server is done sending events.
+    let bounded_events = remote_server.bounded_event_stream();
+    pin_mut!(bounded_events);
+    let _stalled_events = remote_server.event_stream();
+    let (_session, sender, mut server_receiver) = start_test_session(&remote_server).await;
+    let mut start_scanning = message::StartScanning::default();
+    start_scanning.set_id(2);
+    sender.send(start_scanning.into()).await.unwrap();
+    while !matches!(
+      server_receiver.recv().await,
+      Some(ButtplugServerMessage::DeviceAdded(_))
+    ) {}
+    for id in 3..13 {
+      let mut vibrate = message::VibrateCmd::new(0, vec![message::VibrateSubcommand::new(0, 0.5)]);
+      vibrate.set_id(id);
+      sender.send(vibrate.into()).await.unwrap();
+      assert!(matches!(
+        wait_for_reply(&mut server_receiver, id).await,
+        ButtplugServerMessage::Ok(_)
+      ));
+    }
+
+    // The first 2 events fit, then the stream reports the overflow and ends.
+    assert!(matches!(bounded_events.next().await, Some(Ok(_))));
+    assert!(matches!(bounded_events.next().await, Some(Ok(_))));
+    assert!(matches!(
+      bounded_events.next().await,
+      Some(Err(ButtplugRemoteServerEventStreamError::Overflowed(2)))
+    ));
+    assert!(bounded_events.next().await.is_none());
+  });
+}
+
+#[test]
+fn test_remote_server_override_device_index() {
+  async_manager::block_on(async {
+    let mut comm_manager = TestDeviceCommunicationManagerBuilder::default();
+    let _device = comm_manager.add_test_device(&TestDeviceIdentifier::new(
+      "Massage Demo",
+      Some("kiosk-device".to_owned()),
+    ));
+    let server = ButtplugServerBuilder::default()
+      .comm_manager(comm_manager)
+      .finish()
+      .unwrap();
+    let remote_server = Arc::new(ButtplugRemoteServer::new(server));
+    remote_server
+      .override_device_index("kiosk-device", 5)
+      .expect("Test, assuming infallible.");
+    // Setting the same override again is fine, giving the index to another device isn't.
+    remote_server
+      .override_device_index("kiosk-device", 5)
+      .expect("Test, assuming infallible.");
+    assert!(matches!(
+      remote_server.override_device_index("other-device", 5),
+      Err(ButtplugError::ButtplugDeviceError(
+        ButtplugDeviceError::DeviceIndexConflict(5, address)
+      )) if address == "kiosk-device"
+    ));
+
+    let (_session, sender, mut server_receiver) = start_test_session(&remote_server).await;
+    let mut start_scanning = message::StartScanning::default();
+    start_scanning.set_id(2);
+    sender.send(start_scanning.into()).await.unwrap();
+    loop {
+      if let Some(ButtplugServerMessage::DeviceAdded(added)) = server_receiver.recv().await {
+        assert_eq!(added.device_index(), 5);
+        break;
+      }
+    }
+
+    // The device still holds the index while it's connected, even without the override.
+    assert!(remote_server.clear_device_index_override("kiosk-device"));
+    assert!(!remote_server.clear_device_index_override("kiosk-device"));
+    assert!(remote_server
+      .override_device_index("other-device", 5)
+      .is_err());
+    assert!(remote_server
+      .override_device_index("other-device", 6)
+      .is_ok());
+  });
+}
+
+#[test]
+fn test_remote_server_task_watchdog_timeout() {
+  async_manager::block_on(async {
+    let mut comm_manager = TestDeviceCommunicationManagerBuilder::default();
+    let _device = comm_manager.add_test_device(&TestDeviceIdentifier::new("Massage Demo", None));
+    let server = ButtplugServerBuilder::default()
+      .comm_manager(comm_manager)
+      .allow_raw_messages()
+      .task_watchdog_timeout(Duration::from_millis(5))
+      .finish()
+      .unwrap();
+    let remote_server = Arc::new(ButtplugRemoteServer::new(server));
+    let mut events = Box::pin(remote_server.event_stream());
+    let (_session, sender, mut server_receiver) = start_test_session(&remote_server).await;
+    let mut start_scanning = message::StartScanning::default();
+    start_scanning.set_id(2);
+    sender.send(start_scanning.into()).await.unwrap();
+    while !matches!(
+      server_receiver.recv().await,
+      Some(ButtplugServerMessage::DeviceAdded(_))
+    ) {}
+
+    // The test device never gets any data to read, so this would wait until it gives up.
+    let mut raw_read = message::RawReadCmd::new(0, message::Endpoint::Tx, 0, 0);
+    raw_read.set_id(3);
+    sender.send(raw_read.into()).await.unwrap();
+    match wait_for_reply(&mut server_receiver, 3).await {
+      ButtplugServerMessage::Error(err) => assert!(matches!(
+        err.original_error(),
+        ButtplugError::ButtplugUnknownError(ButtplugUnknownError::TaskWatchdogTriggered(_))
+      )),
+      msg => panic!("Expected an error, got {:?}", msg),
+    }
+    loop {
+      if let Some(ButtplugRemoteServerEvent::InternalError { context, .. }) = events.next().await {
+        assert_eq!(context, "task_watchdog_triggered");
+        break;
+      }
+    }
+  });
+}
+
+/// Returns a factory for [ButtplugRemoteServer::start_with_reconnect] whose connectors fail to
+/// connect `failures` times, along with the failure counter and the client/server channels of
+/// [test_server_connector].
+fn flaky_connector_factory(
+  failures: u32,
+) -> (
+  impl Fn() -> FlakyServerConnector,
+  Arc<AtomicU32>,
+  ClientSenderSlot,
+  mpsc::Receiver<ButtplugServerMessage>,
+) {
+  let (connector, client_sender, server_receiver) = test_server_connector();
+  let remaining_failures = Arc::new(AtomicU32::new(failures));
+  let remaining_failures_clone = remaining_failures.clone();
+  let factory = move || FlakyServerConnector {
+    remaining_failures: remaining_failures_clone.clone(),
+    inner: connector.clone(),
+  };
+  (factory, remaining_failures, client_sender, server_receiver)
+}
+
+#[test]
+fn test_remote_server_start_with_reconnect() {
+  async_manager::block_on(async {
+    let remote_server = Arc::new(ButtplugRemoteServer::default());
+    let (factory, remaining_failures, client_sender, mut server_receiver) =
+      flaky_connector_factory(1);
+    let remote_server_clone = remote_server.clone();
+    let server_task = async_manager::spawn_with_handle(async move {
+      remote_server_clone
+        .start_with_reconnect(
+          factory,
+          RetryPolicy::new(Some(3), Duration::from_millis(10)),
+        )
+        .await
+    })
+    .unwrap();
+    // The first attempt fails, the second connects.
+    while client_sender.lock().unwrap().is_none() {
+      tokio::task::yield_now().await;
+    }
+    assert_eq!(remaining_failures.load(Ordering::SeqCst), 0);
+    let sender = client_sender.lock().unwrap().clone().unwrap();
+    sender
+      .send(
+        message::RequestServerInfo::new("Test Client", BUTTPLUG_CURRENT_MESSAGE_SPEC_VERSION)
+          .into(),
+      )
+      .await
+      .unwrap();
+    assert!(matches!(
+      server_receiver.recv().await,
+      Some(ButtplugServerMessage::ServerInfo(_))
+    ));
+    remote_server.disconnect().await.unwrap();
+    assert!(server_task.await.is_ok());
+  });
+}
+
+#[test]
+fn test_remote_server_start_with_reconnect_max_attempts() {
+  async_manager::block_on(async {
+    let remote_server = ButtplugRemoteServer::default();
+    let (factory, _, client_sender, _server_receiver) = flaky_connector_factory(u32::MAX);
+    assert!(remote_server
+      .start_with_reconnect(factory, RetryPolicy::new(Some(1), Duration::ZERO))
+      .await
+      .is_err());
+    assert!(client_sender.lock().unwrap().is_none());
+  });
+}
+
+#[test]
+fn test_remote_server_start_with_reconnect_disconnect() {
+  async_manager::block_on(async {
+    let remote_server = Arc::new(ButtplugRemoteServer::default());
+    let (factory, remaining_failures, _, _server_receiver) = flaky_connector_factory(u32::MAX);
+    let remote_server_clone = remote_server.clone();
+    let server_task = async_manager::spawn_with_handle(async move {
+      remote_server_clone
+        .start_with_reconnect(factory, RetryPolicy::new(None, Duration::from_secs(3600)))
+        .await
+    })
+    .unwrap();
+    while remaining_failures.load(Ordering::SeqCst) == u32::MAX {
+      tokio::task::yield_now().await;
+    }
+    // Without disconnect, this would wait an hour before the next attempt.
+    remote_server.disconnect().await.unwrap();
+    assert!(server_task.await.is_ok());
+    assert_eq!(remaining_failures.load(Ordering::SeqCst), u32::MAX - 1);
+  });
+}
+
+#[test]
+fn test_remote_server_connector_retry() {
+  async_manager::block_on(async {
+    let remote_server = Arc::new(
+      ButtplugRemoteServerBuilder::default()
+        .connector_retry(ButtplugConnectorRetryConfig::new(
+          2,
+          Duration::from_millis(1),
+        ))
+        .finish(),
+    );
+    let (inner, client_sender, mut server_receiver) = test_server_connector();
+    let remaining_failures = Arc::new(AtomicU32::new(2));
+    let connector = FlakySendConnector {
+      remaining_failures: remaining_failures.clone(),
+      inner,
+    };
+    let remote_server_clone = remote_server.clone();
+    let _server_task = async_manager::spawn_with_handle(async move {
+      remote_server_clone.start(connector).await.unwrap();
+    })
+    .unwrap();
+    while client_sender.lock().unwrap().is_none() {
+      tokio::task::yield_now().await;
+    }
+    let sender = client_sender.lock().unwrap().clone().unwrap();
+    let mut request_server_info =
+      message::RequestServerInfo::new("Test Client", BUTTPLUG_CURRENT_MESSAGE_SPEC_VERSION);
+    request_server_info.set_id(1);
+    sender.send(request_server_info.into()).await.unwrap();
+    // The reply got through on the last retry.
+    assert!(matches!(
+      wait_for_reply(&mut server_receiver, 1).await,
+      ButtplugServerMessage::ServerInfo(_)
+    ));
+    assert_eq!(remaining_failures.load(Ordering::SeqCst), 0);
+  });
+}
+
+#[test]
+fn test_remote_server_preflight_check() {
+  async_manager::block_on(async {
+    let (connector, _client_sender, _server_receiver) = test_server_connector();
+    assert!(ButtplugRemoteServer::test_connector_health(&connector)
+      .await
+      .is_ok());
+
+    let remote_server = ButtplugRemoteServerBuilder::default()
+      .preflight_check(true)
+      .finish();
+    let (mut connector, client_sender, _server_receiver) = test_server_connector();
+    connector.healthy = false;
+    assert!(matches!(
+      remote_server.start(connector).await,
+      Err(ButtplugServerConnectorError::ConnectorError { .. })
+    ));
+    // The connector connected, but no session was started with it.
+    assert!(client_sender.lock().unwrap().is_some());
+    assert!(remote_server.connection_history().is_empty());
+  });
+}
+
+#[test]
+fn test_remote_server_set_connector_timeout() {
+  async_manager::block_on(async {
+    let remote_server = Arc::new(ButtplugRemoteServerBuilder::default().finish());
+    let (connector, client_sender, _server_receiver) = test_server_connector();
+    let timeout = connector.timeout.clone();
+    let remote_server_clone = remote_server.clone();
+    let _server_task = async_manager::spawn_with_handle(async move {
+      remote_server_clone.start(connector).await.unwrap();
+    })
+    .unwrap();
+    while client_sender.lock().unwrap().is_none() {
+      tokio::task::yield_now().await;
+    }
+    assert!(timeout.lock().unwrap().is_none());
+    // Running sessions pick the timeout up without reconnecting.
+    remote_server
+      .set_connector_timeout(Duration::from_millis(250))
+      .unwrap();
+    assert_eq!(*timeout.lock().unwrap(), Some(Duration::from_millis(250)));
+    remote_server
+      .set_connector_timeout(Duration::from_secs(2))
+      .unwrap();
+    assert_eq!(*timeout.lock().unwrap(), Some(Duration::from_secs(2)));
+    remote_server.disconnect().await.unwrap();
+
+    // Sessions started later get the last timeout set.
+    let (connector, _client_sender, _server_receiver) = test_server_connector();
+    let timeout = connector.timeout.clone();
+    let _server_task = async_manager::spawn_with_handle(async move {
+      remote_server.start(connector).await.unwrap();
+    })
+    .unwrap();
+    while timeout.lock().unwrap().is_none() {
+      tokio::task::yield_now().await;
+    }
+    assert_eq!(*timeout.lock().unwrap(), Some(Duration::from_secs(2)));
+  });
+}
+
+#[test]
+fn test_remote_server_max_message_size() {
+  async_manager::block_on(async {
+    let (server, device) = test_server_with_device("Massage Demo", true).await;
+    let remote_server = Arc::new(ButtplugRemoteServer::new(server));
+    let (mut connector, client_sender, server_receiver) = test_server_connector();
+    connector.max_message_size = Some(MAX_MESSAGE_SIZE);
+    let (_server_task, sender, mut server_receiver) =
+      start_test_session_with_connector(&remote_server, connector, client_sender, server_receiver)
+        .await;
+    assert_eq!(
+      remote_server
+        .negotiated_config()
+        .expect("Handshake has happened")
+        .max_message_size(),
+      Some(MAX_MESSAGE_SIZE)
+    );
+    connect_test_device(&sender, &mut server_receiver).await;
+    let mut subscribe = message::RawSubscribeCmd::new(0, Endpoint::Tx);
+    subscribe.set_id(3);
+    sender.send(subscribe.into()).await.unwrap();
+    assert!(matches!(
+      wait_for_reply(&mut server_receiver, 3).await,
+      ButtplugServerMessage::Ok(_)
+    ));
+
+    let data: Vec<u8> = (100..140).collect();
+    device
+      .sender
+      .send(TestHardwareEvent::Notifications(vec![
+        TestHardwareNotification::new(Endpoint::Tx, data.clone()),
+      ]))
+      .await
+      .unwrap();
+    assert_eq!(
+      receive_split_raw_reading(&mut server_receiver, 0).await,
+      data
+    );
+
+    // Replies to RawReadCmd are split the same way, every piece keeping the id.
+    device
+      .sender
+      .send(TestHardwareEvent::Reads(vec![
+        TestHardwareNotification::new(Endpoint::Tx, data.clone()),
+      ]))
+      .await
+      .unwrap();
+    let mut raw_read = message::RawReadCmd::new(0, Endpoint::Tx, 0, 0);
+    raw_read.set_id(4);
+    sender.send(raw_read.into()).await.unwrap();
+    assert_eq!(
+      receive_split_raw_reading(&mut server_receiver, 4).await,
+      data
+    );
+  });
+}
+
+const MAX_MESSAGE_SIZE: usize = 120;
+
+/// Receive the pieces of a raw reading split to fit [MAX_MESSAGE_SIZE], checking each fits and
+/// that all but the last are marked as continued, and return the data put back together.
+async fn receive_split_raw_reading(
+  server_receiver: &mut mpsc::Receiver<ButtplugServerMessage>,
+  id: u32,
+) -> Vec<u8> {
+  let mut pieces = 0;
+  let mut data = vec![];
+  loop {
+    match server_receiver.recv().await {
+      Some(ButtplugServerMessage::RawReading(reading)) => {
+        assert_eq!(reading.id(), id);
+        let serialized = serde_json::to_string(&[ButtplugCurrentSpecServerMessage::RawReading(
+          reading.clone(),
+        )])
+        .unwrap();
+        assert!(serialized.len() <= MAX_MESSAGE_SIZE);
+        pieces += 1;
+        data.extend_from_slice(reading.data());
+        if !reading.continued() {
+          break;
+        }
+      }
+      msg => panic!("Expected a raw reading, got {:?}", msg),
+    }
+  }
+  assert!(pieces > 1);
+  data
+}
+
+#[test]
+fn test_remote_server_start_with_timeout() {
+  async_manager::block_on(async {
+    let remote_server = ButtplugRemoteServer::default();
+    let started = Instant::now();
+    let result = remote_server
+      .start_with_timeout(PendingServerConnector::default(), Duration::from_millis(50))
+      .await;
+    assert!(
+      matches!(result, Err(ButtplugServerConnectorError::ConnectorError { message, .. }) if message.contains("timed out"))
+    );
+    assert!(started.elapsed() < Duration::from_secs(5));
+  });
+}
+
+#[test]
+fn test_remote_server_start_with_timeout_disconnect() {
+  async_manager::block_on(async {
+    let remote_server = Arc::new(ButtplugRemoteServer::default());
+    let connector = PendingServerConnector::default();
+    let connecting = connector.connecting.clone();
+    let remote_server_clone = remote_server.clone();
+    let server_task = async_manager::spawn_with_handle(async move {
+      remote_server_clone
+        .start_with_timeout(connector, Duration::from_secs(3600))
+        .await
+    })
+    .unwrap();
+    while !connecting.load(Ordering::SeqCst) {
+      tokio::task::yield_now().await;
+    }
+    remote_server.disconnect().await.unwrap();
+    assert!(server_task.await.is_err());
+  });
+}
+
+#[test]
+fn test_remote_server_reply_send_error_event() {
+  async_manager::block_on(async {
+    let remote_server = Arc::new(ButtplugRemoteServer::default());
+    let mut events = Box::pin(remote_server.event_stream());
+    let (_session, sender, server_receiver) = start_test_session(&remote_server).await;
+    // With nothing on the other end, replies can't be delivered.
+    drop(server_receiver);
+    let mut ping = message::Ping::default();
+    ping.set_id(2);
+    sender.send(ping.into()).await.unwrap();
+    loop {
+      if let Some(ButtplugRemoteServerEvent::Error { source, fatal }) = events.next().await {
+        assert_eq!(source, "send_reply_to_client");
+        assert!(!fatal);
+        break;
+      }
+    }
+  });
+}
+
+#[test]
+fn test_remote_server_event_send_error_event() {
+  async_manager::block_on(async {
+    let (server, _device) = test_server_with_device("Massage Demo", false).await;
+    let remote_server = Arc::new(ButtplugRemoteServer::new(server));
+    let mut events = Box::pin(remote_server.event_stream());
+    let (session, sender, server_receiver) = start_test_session(&remote_server).await;
+    drop(server_receiver);
+    let mut start_scanning = message::StartScanning::default();
+    start_scanning.set_id(2);
+    sender.send(start_scanning.into()).await.unwrap();
+    // The device being found can't be sent to the client either, which ends the session.
+    loop {
+      if let Some(ButtplugRemoteServerEvent::Error {
+        source,
+        fatal: true,
+      }) = events.next().await
+      {
+        assert_eq!(source, "send_event_to_client");
+        break;
+      }
+    }
+    session.await;
+  });
+}
+
+#[cfg(feature = "serialize-json")]
+#[test]
+fn test_remote_server_event_serde_round_trip() {
+  async_manager::block_on(async {
+    let (server, _device) = test_server_with_device("Massage Demo", false).await;
+    let recv = server.event_stream();
+    pin_mut!(recv);
+    server
+      .parse_message(
+        message::RequestServerInfo::new("Test Client", BUTTPLUG_CURRENT_MESSAGE_SPEC_VERSION)
+          .into(),
+      )
+      .await
+      .unwrap();
+    server
+      .parse_message(message::StartScanning::default().into())
+      .await
+      .unwrap();
+    while !matches!(
+      recv.next().await,
+      Some(ButtplugServerMessage::DeviceAdded(_))
+    ) {}
+    let device_info = server.device_manager().device_info(0).unwrap();
+
+    let vibrate = message::VibrateCmd::new(0, vec![message::VibrateSubcommand::new(0, 0.5)]);
+    let error: ButtplugError = ButtplugUnknownError::NoDeviceCommManagers.into();
+    let events = vec![
+      ButtplugRemoteServerEvent::ClientConnected(0, "Test Client".to_owned()),
+      ButtplugRemoteServerEvent::ClientDisconnected(1, None),
+      ButtplugRemoteServerEvent::ClientDisconnected(2, Some(DisconnectReason::IdleTimeout)),
+      ButtplugRemoteServerEvent::DeviceAdded(
+        1,
+        "Test Device".to_owned(),
+        "test-address".to_owned(),
+        Some("My Device".to_owned()),
+      ),
+      ButtplugRemoteServerEvent::DeviceRemoved(1),
+      ButtplugRemoteServerEvent::ClientIdleTimeout,
+      ButtplugRemoteServerEvent::DeviceUpdated(0, Box::new(device_info)),
+      ButtplugRemoteServerEvent::RateLimitExceeded {
+        message_id: 4,
+        message_type: "VibrateCmd".to_owned(),
+        drop_count: 2,
+      },
+      ButtplugRemoteServerEvent::DeviceReconnectFailed(1),
+      ButtplugRemoteServerEvent::DeviceCommand(vibrate.clone().into()),
+      ButtplugRemoteServerEvent::DeviceCommandSent(0, vibrate.into()),
+      ButtplugRemoteServerEvent::DeviceCommandFailed(0, error.clone()),
+      ButtplugRemoteServerEvent::InternalError {
+        context: "task_watchdog_triggered".to_owned(),
+        error,
+      },
+      ButtplugRemoteServerEvent::Error {
+        source: "send_reply_to_client".to_owned(),
+        fatal: false,
+      },
+    ];
+    #[cfg(feature = "custom-events")]
+    let events = [
+      events,
+      vec![ButtplugRemoteServerEvent::Custom(
+        serde_json::json!({"token": "renewed"}),
+      )],
+    ]
+    .concat();
+    for event in events {
+      let json = serde_json::to_value(&event).unwrap();
+      let round_tripped: ButtplugRemoteServerEvent = serde_json::from_value(json.clone()).unwrap();
+      assert_eq!(format!("{:?}", round_tripped), format!("{:?}", event));
+      assert_eq!(serde_json::to_value(&round_tripped).unwrap(), json);
+    }
+  });
+}
+
+#[cfg(feature = "serialize-json")]
+#[test]
+fn test_remote_server_event_serde_device_added_fields() {
+  let json = serde_json::to_value(ButtplugRemoteServerEvent::DeviceAdded(
+    3,
+    "Test Device".to_owned(),
+    "test-address".to_owned(),
+    None,
+  ))
+  .unwrap();
+  assert_eq!(
+    json,
+    serde_json::json!({
+      "type": "DeviceAdded",
+      "device_index": 3,
+      "name": "Test Device",
+      "address": "test-address",
+      "display_name": null,
+    })
+  );
+}
+
+#[test]
+fn test_remote_server_start_accepting() {
+  async_manager::block_on(async {
+    let mut comm_manager = TestDeviceCommunicationManagerBuilder::default();
+    let _device = comm_manager.add_test_device(&TestDeviceIdentifier::new("Massage Demo", None));
+    let server = ButtplugServerBuilder::default()
+      .comm_manager(comm_manager)
+      .finish()
+      .unwrap();
+    let remote_server = Arc::new(ButtplugRemoteServer::new(server));
+    let mut events = Box::pin(remote_server.event_stream());
+    let (first_connector, first_slot, mut first_receiver) = test_server_connector();
+    let (second_connector, second_slot, mut second_receiver) = test_server_connector();
+    let remote_server_clone = remote_server.clone();
+    let accepting = async_manager::spawn_with_handle(async move {
+      remote_server_clone
+        .start_accepting(futures::stream::iter(vec![
+          first_connector,
+          second_connector,
+        ]))
+        .await;
+    })
+    .unwrap();
+    while first_slot.lock().unwrap().is_none() || second_slot.lock().unwrap().is_none() {
+      tokio::task::yield_now().await;
+    }
+    let first_sender = first_slot.lock().unwrap().clone().unwrap();
+    let second_sender = second_slot.lock().unwrap().clone().unwrap();
+
+    // Both clients can handshake with the same server.
+    let mut session_ids = vec![];
+    for (sender, receiver) in [
+      (&first_sender, &mut first_receiver),
+      (&second_sender, &mut second_receiver),
+    ] {
+      sender
+        .send(
+          message::RequestServerInfo::new("Test Client", BUTTPLUG_CURRENT_MESSAGE_SPEC_VERSION)
+            .into(),
+        )
+        .await
+        .unwrap();
+      assert!(matches!(
+        receiver.recv().await,
+        Some(ButtplugServerMessage::ServerInfo(_))
+      ));
+      match events.next().await {
+        Some(ButtplugRemoteServerEvent::ClientConnected(session_id, _)) => {
+          session_ids.push(session_id)
+        }
+        event => panic!("Expected ClientConnected, got {:?}", event),
+      }
+    }
+    assert_ne!(session_ids[0], session_ids[1]);
+
+    // Devices found through one session are announced to both.
+    let mut start_scanning = message::StartScanning::default();
+    start_scanning.set_id(2);
+    first_sender.send(start_scanning.into()).await.unwrap();
+    for receiver in [&mut first_receiver, &mut second_receiver] {
+      while !matches!(
+        receiver.recv().await,
+        Some(ButtplugServerMessage::DeviceAdded(_))
+      ) {}
+    }
+
+    // Dropping the first client leaves the second one connected.
+    first_slot.lock().unwrap().take();
+    drop(first_sender);
+    loop {
+      match events.next().await {
+        Some(ButtplugRemoteServerEvent::ClientDisconnected(session_id, None)) => {
+          assert_eq!(session_id, session_ids[0]);
+          break;
+        }
+        Some(_) => continue,
+        None => panic!("Event stream ended early"),
+      }
+    }
+    let mut device_list = message::RequestDeviceList::default();
+    device_list.set_id(3);
+    second_sender.send(device_list.into()).await.unwrap();
+    match wait_for_reply(&mut second_receiver, 3).await {
+      ButtplugServerMessage::DeviceList(list) => assert_eq!(list.devices().len(), 1),
+      msg => panic!("Expected DeviceList, got {:?}", msg),
+    }
+
+    second_slot.lock().unwrap().take();
+    drop(second_sender);
+    accepting.await;
+  });
+}
+
+#[test]
+fn test_remote_server_command_conflict_between_sessions() {
+  async_manager::block_on(async {
+    let mut comm_manager = TestDeviceCommunicationManagerBuilder::default();
+    let mut device = comm_manager.add_test_device(&TestDeviceIdentifier::new("Massage Demo", None));
+    let server = ButtplugServerBuilder::default()
+      .comm_manager(comm_manager)
+      .command_conflict_policy(CommandConflictPolicy::MaxIntensity)
+      .finish()
+      .unwrap();
+    let remote_server = Arc::new(ButtplugRemoteServer::new(server));
+    let mut events = Box::pin(remote_server.event_stream());
+    let (first_connector, first_slot, mut first_receiver) = test_server_connector();
+    let (second_connector, second_slot, mut second_receiver) = test_server_connector();
+    let remote_server_clone = remote_server.clone();
+    let accepting = async_manager::spawn_with_handle(async move {
+      remote_server_clone
+        .start_accepting(futures::stream::iter(vec![
+          first_connector,
+          second_connector,
+        ]))
+        .await;
+    })
+    .unwrap();
+    while first_slot.lock().unwrap().is_none() || second_slot.lock().unwrap().is_none() {
+      tokio::task::yield_now().await;
+    }
+    let first_sender = first_slot.lock().unwrap().clone().unwrap();
+    let second_sender = second_slot.lock().unwrap().clone().unwrap();
+    for (sender, receiver) in [
+      (&first_sender, &mut first_receiver),
+      (&second_sender, &mut second_receiver),
+    ] {
+      sender
+        .send(
+          message::RequestServerInfo::new("Test Client", BUTTPLUG_CURRENT_MESSAGE_SPEC_VERSION)
+            .into(),
+        )
+        .await
+        .unwrap();
+      wait_for_reply(receiver, 1).await;
+    }
+    let mut start_scanning = message::StartScanning::default();
+    start_scanning.set_id(2);
+    first_sender.send(start_scanning.into()).await.unwrap();
+    while !matches!(
+      events.next().await,
+      Some(ButtplugRemoteServerEvent::DeviceAdded(0, ..))
+    ) {}
+
+    let mut next_id = 3;
+    let mut vibrate = |sender: &mpsc::Sender<ButtplugClientMessage>, speed: f64| {
+      let mut msg = message::VibrateCmd::new(0, vec![message::VibrateSubcommand::new(0, speed)]);
+      msg.set_id(next_id);
+      next_id += 1;
+      let sender = sender.clone();
+      async move { sender.send(msg.into()).await.unwrap() }
+    };
+    vibrate(&first_sender, 0.5).await;
+    wait_for_reply(&mut first_receiver, 3).await;
+    check_test_recv_value(
+      &mut device,
+      HardwareCommand::Write(HardwareWriteCmd::new(Endpoint::Tx, vec![0xF1, 64], false)),
+    );
+    vibrate(&second_sender, 0.75).await;
+    wait_for_reply(&mut second_receiver, 4).await;
+    check_test_recv_value(
+      &mut device,
+      HardwareCommand::Write(HardwareWriteCmd::new(Endpoint::Tx, vec![0xF1, 96], false)),
+    );
+    // The second session's command is still the highest, so nothing changes.
+    vibrate(&first_sender, 0.25).await;
+    wait_for_reply(&mut first_receiver, 5).await;
+    assert!(device.receiver.try_recv().is_err());
+
+    // Once the second session is gone, its command no longer counts.
+    second_slot.lock().unwrap().take();
+    drop(second_sender);
+    while !matches!(
+      events.next().await,
+      Some(ButtplugRemoteServerEvent::ClientDisconnected(..))
+    ) {}
+    vibrate(&first_sender, 0.3).await;
+    wait_for_reply(&mut first_receiver, 6).await;
+    check_test_recv_value(
+      &mut device,
+      HardwareCommand::Write(HardwareWriteCmd::new(Endpoint::Tx, vec![0xF1, 39], false)),
+    );
+
+    first_slot.lock().unwrap().take();
+    drop(first_sender);
+    accepting.await;
+  });
+}
+
+#[test]
+fn test_remote_server_sessions_keep_own_spec_version() {
+  async_manager::block_on(async {
+    let remote_server = Arc::new(ButtplugRemoteServer::default());
+    let (first_connector, first_slot, mut first_receiver) = test_server_connector();
+    let (second_connector, second_slot, mut second_receiver) = test_server_connector();
+    let remote_server_clone = remote_server.clone();
+    let accepting = async_manager::spawn_with_handle(async move {
+      remote_server_clone
+        .start_accepting(futures::stream::iter(vec![
+          first_connector,
+          second_connector,
+        ]))
+        .await;
+    })
+    .unwrap();
+    while first_slot.lock().unwrap().is_none() || second_slot.lock().unwrap().is_none() {
+      tokio::task::yield_now().await;
+    }
+    let first_sender = first_slot.lock().unwrap().clone().unwrap();
+    let second_sender = second_slot.lock().unwrap().clone().unwrap();
+    for (sender, receiver, version) in [
+      (
+        &first_sender,
+        &mut first_receiver,
+        ButtplugMessageSpecVersion::Version1,
+      ),
+      (
+        &second_sender,
+        &mut second_receiver,
+        BUTTPLUG_CURRENT_MESSAGE_SPEC_VERSION,
+      ),
+    ] {
+      sender
+        .send(message::RequestServerInfo::new("Test Client", version).into())
+        .await
+        .unwrap();
+      wait_for_reply(receiver, 1).await;
+    }
+
+    // ScalarCmd is a v3 message, so only the session that negotiated v3 gets past the version
+    // check, even though the other session joined the same server.
+    let mut scalar_cmd = message::ScalarCmd::new(0, vec![]);
+    scalar_cmd.set_id(2);
+    first_sender.send(scalar_cmd.clone().into()).await.unwrap();
+    let ButtplugServerMessage::Error(err) = wait_for_reply(&mut first_receiver, 2).await else {
+      panic!("Should've received an error");
+    };
+    assert!(matches!(
+      err.original_error(),
+      ButtplugError::ButtplugMessageError(ButtplugMessageError::VersionError(..))
+    ));
+    second_sender.send(scalar_cmd.into()).await.unwrap();
+    let ButtplugServerMessage::Error(err) = wait_for_reply(&mut second_receiver, 2).await else {
+      panic!("Should've received an error");
+    };
+    assert!(matches!(
+      err.original_error(),
+      ButtplugError::ButtplugDeviceError(ButtplugDeviceError::DeviceNotAvailable(_))
+    ));
+
+    first_slot.lock().unwrap().take();
+    second_slot.lock().unwrap().take();
+    drop(first_sender);
+    drop(second_sender);
+    accepting.await;
+  });
+}
+
+#[test]
+fn test_remote_server_sessions_keep_own_session_log() {
+  async_manager::block_on(async {
+    let server = ButtplugServerBuilder::default()
+      .record_session(true)
+      .finish()
+      .unwrap();
+    let remote_server = Arc::new(ButtplugRemoteServer::new(server));
+    let mut events = Box::pin(remote_server.event_stream());
+    let (first_connector, first_slot, mut first_receiver) = test_server_connector();
+    let (second_connector, second_slot, mut second_receiver) = test_server_connector();
+    let remote_server_clone = remote_server.clone();
+    let accepting = async_manager::spawn_with_handle(async move {
+      remote_server_clone
+        .start_accepting(futures::stream::iter(vec![
+          first_connector,
+          second_connector,
+        ]))
+        .await;
+    })
+    .unwrap();
+    while first_slot.lock().unwrap().is_none() || second_slot.lock().unwrap().is_none() {
+      tokio::task::yield_now().await;
+    }
+    let first_sender = first_slot.lock().unwrap().clone().unwrap();
+    let second_sender = second_slot.lock().unwrap().clone().unwrap();
+    let mut session_ids = HashMap::new();
+    for (sender, receiver, client_name) in [
+      (&first_sender, &mut first_receiver, "First Client"),
+      (&second_sender, &mut second_receiver, "Second Client"),
+    ] {
+      let msg = message::RequestServerInfo::new(client_name, BUTTPLUG_CURRENT_MESSAGE_SPEC_VERSION);
+      sender.send(msg.into()).await.unwrap();
+      wait_for_reply(receiver, 1).await;
+      while let Some(event) = events.next().await {
+        if let ButtplugRemoteServerEvent::ClientConnected(session_id, name) = event {
+          session_ids.insert(name, session_id);
+          break;
+        }
+      }
+    }
+    let mut msg: ButtplugClientMessage = message::RequestDeviceList::default().into();
+    msg.set_id(2);
+    first_sender.send(msg).await.unwrap();
+    wait_for_reply(&mut first_receiver, 2).await;
+
+    // The second client connecting didn't clear the first one's log, and each log only has its
+    // own session's messages.
+    let server = remote_server.server();
+    let log_has_device_list = |client_name: &str| {
+      server
+        .export_client_session_log(session_ids[client_name])
+        .unwrap()
+        .events()
+        .iter()
+        .any(|event| {
+          matches!(
+            event.kind(),
+            SessionEventKind::Command {
+              message: ButtplugClientMessage::RequestDeviceList(_),
+              ..
+            }
+          )
+        })
+    };
+    assert!(log_has_device_list("First Client"));
+    assert!(!log_has_device_list("Second Client"));
+    assert!(matches!(
+      server.export_session_log().events()[0].kind(),
+      SessionEventKind::ClientConnected { client_name } if client_name == "Second Client"
+    ));
+
+    first_slot.lock().unwrap().take();
+    second_slot.lock().unwrap().take();
+    drop(first_sender);
+    drop(second_sender);
+    accepting.await;
+  });
+}
+
+#[test]
+fn test_remote_server_concurrent_sessions_keep_own_activity() {
+  async_manager::block_on(async {
+    let remote_server = Arc::new(ButtplugRemoteServer::default());
+    let mut events = Box::pin(remote_server.event_stream());
+    let (connector, first_slot, mut first_receiver) = test_server_connector();
+    let remote_server_clone = remote_server.clone();
+    let first_session = async_manager::spawn_with_handle(async move {
+      remote_server_clone.start(connector).await.unwrap();
+    })
+    .unwrap();
+    while first_slot.lock().unwrap().is_none() {
+      tokio::task::yield_now().await;
+    }
+    let first_sender = first_slot.lock().unwrap().clone().unwrap();
+    for id in 1..=3 {
+      let mut msg: ButtplugClientMessage = if id == 1 {
+        message::RequestServerInfo::new("First Client", BUTTPLUG_CURRENT_MESSAGE_SPEC_VERSION)
+          .into()
+      } else {
+        message::RequestDeviceList::default().into()
+      };
+      msg.set_id(id);
+      first_sender.send(msg).await.unwrap();
+      wait_for_reply(&mut first_receiver, id).await;
+    }
+
+    // A second session starting doesn't touch the first one's counts.
+    let (_second_session, _second_sender, _second_receiver) =
+      start_test_session(&remote_server).await;
+    first_slot.lock().unwrap().take();
+    drop(first_sender);
+    while !matches!(
+      events.next().await,
+      Some(ButtplugRemoteServerEvent::ClientDisconnected(0, _))
+    ) {}
+    first_session.await;
+    let history = remote_server.connection_history();
+    assert_eq!(history.len(), 1);
+    assert_eq!(history[0].client_name().as_deref(), Some("First Client"));
+    assert_eq!(history[0].messages_in(), 3);
+    assert_eq!(history[0].messages_out(), 3);
+    // And the first session ending doesn't clear the second one's.
+    assert!(remote_server.connector_type_name().is_some());
+    assert_eq!(
+      remote_server.status_report().client_name().as_deref(),
+      Some("Test Client")
+    );
+  });
+}
+
+#[test]
+fn test_remote_server_session_disconnect_clears_own_command_history() {
+  async_manager::block_on(async {
+    let mut comm_manager = TestDeviceCommunicationManagerBuilder::default();
+    let _device = comm_manager.add_test_device(&TestDeviceIdentifier::new("Massage Demo", None));
+    let server = ButtplugServerBuilder::default()
+      .comm_manager(comm_manager)
+      .track_command_statistics(true)
+      .clear_history_on_disconnect(true)
+      .finish()
+      .unwrap();
+    let remote_server = Arc::new(ButtplugRemoteServer::new(server));
+    let mut events = Box::pin(remote_server.event_stream());
+    let (connector, first_slot, mut first_receiver) = test_server_connector();
+    let remote_server_clone = remote_server.clone();
+    let first_session = async_manager::spawn_with_handle(async move {
+      remote_server_clone.start(connector).await.unwrap();
+    })
+    .unwrap();
+    while first_slot.lock().unwrap().is_none() {
+      tokio::task::yield_now().await;
+    }
+    let first_sender = first_slot.lock().unwrap().clone().unwrap();
+    first_sender
+      .send(
+        message::RequestServerInfo::new("First Client", BUTTPLUG_CURRENT_MESSAGE_SPEC_VERSION)
+          .into(),
+      )
+      .await
+      .unwrap();
+    wait_for_reply(&mut first_receiver, 1).await;
+    let mut start_scanning = message::StartScanning::default();
+    start_scanning.set_id(2);
+    first_sender.send(start_scanning.into()).await.unwrap();
+    while !matches!(
+      events.next().await,
+      Some(ButtplugRemoteServerEvent::DeviceAdded(0, ..))
+    ) {}
+    let (_second_session, second_sender, mut second_receiver) =
+      start_test_session(&remote_server).await;
+    for (sender, receiver) in [
+      (&first_sender, &mut first_receiver),
+      (&second_sender, &mut second_receiver),
+    ] {
+      let mut vibrate = message::VibrateCmd::new(0, vec![message::VibrateSubcommand::new(0, 0.5)]);
+      vibrate.set_id(3);
+      sender.send(vibrate.into()).await.unwrap();
+      wait_for_reply(receiver, 3).await;
+    }
+    let server = remote_server.server();
+    assert_eq!(server.command_statistics(0).total_commands(), 2);
+
+    // Only the commands from the session that ended are forgotten.
+    first_slot.lock().unwrap().take();
+    drop(first_sender);
+    first_session.await;
+    assert_eq!(server.command_statistics(0).total_commands(), 1);
+    assert!(server.device_manager().device_average_latency(0).is_some());
+  });
+}
+
+#[test]
+fn test_remote_server_status_report() {
+  async_manager::block_on(async {
+    let mut comm_manager = TestDeviceCommunicationManagerBuilder::default();
+    let _device = comm_manager.add_test_device(&TestDeviceIdentifier::new(
+      "Massage Demo",
+      Some("status-report-addr".to_owned()),
+    ));
+    let server = ButtplugServerBuilder::default()
+      .name("Status Server")
+      .comm_manager(comm_manager)
+      .finish()
+      .unwrap();
+    let remote_server = Arc::new(ButtplugRemoteServer::new(server));
+    assert!(remote_server.status_report().client_name().is_none());
+    let (_session, sender, mut server_receiver) = start_test_session(&remote_server).await;
+    let mut start_scanning = message::StartScanning::default();
+    start_scanning.set_id(2);
+    sender.send(start_scanning.into()).await.unwrap();
+    while !matches!(
+      server_receiver.recv().await,
+      Some(ButtplugServerMessage::DeviceAdded(_))
+    ) {}
+
+    let report = remote_server.status_report();
+    assert_eq!(report.server_name(), "Status Server");
+    assert_eq!(report.client_name().as_deref(), Some("Test Client"));
+    assert_eq!(report.message_count(), 2);
+    assert_eq!(report.error_count(), 0);
+    assert_eq!(report.devices().len(), 1);
+    assert_eq!(report.devices()[0].address(), "status-report-addr");
+    assert!(report.to_string().contains("status-report-addr"));
+  });
+}
+
+/// Records the name of every span created while it's the default subscriber.
+#[derive(Clone, Default)]
+struct SpanNameRecorder {
+  names: Arc<Mutex<Vec<String>>>,
+}
+
+impl<S: tracing::Subscriber> tracing_subscriber::Layer<S> for SpanNameRecorder {
+  fn on_new_span(
+    &self,
+    attrs: &tracing::span::Attributes<'_>,
+    _id: &tracing::span::Id,
+    _ctx: tracing_subscriber::layer::Context<'_, S>,
+  ) {
+    self
+      .names
+      .lock()
+      .unwrap()
+      .push(attrs.metadata().name().to_owned());
+  }
+}
+
+#[test]
+fn test_remote_server_message_spans() {
+  use tracing_subscriber::layer::SubscriberExt;
+  let recorder = SpanNameRecorder::default();
+  let _subscriber =
+    tracing::subscriber::set_default(tracing_subscriber::registry().with(recorder.clone()));
+  async_manager::block_on(async {
+    let remote_server = ButtplugRemoteServer::default();
+    let (connector, client_sender, mut server_receiver) = test_server_connector();
+    // Run the server loop on this thread, so its spans go to the subscriber set above.
+    let client = async {
+      while client_sender.lock().unwrap().is_none() {
+        tokio::task::yield_now().await;
+      }
+      let sender = client_sender.lock().unwrap().take().unwrap();
+      sender
+        .send(
+          message::RequestServerInfo::new("Test Client", BUTTPLUG_CURRENT_MESSAGE_SPEC_VERSION)
+            .into(),
+        )
+        .await
+        .unwrap();
+      assert!(matches!(
+        server_receiver.recv().await,
+        Some(ButtplugServerMessage::ServerInfo(_))
+      ));
+    };
+    let (result, _) = future::join(remote_server.start(connector), client).await;
+    assert!(result.is_ok());
+  });
+  assert!(recorder
+    .names
+    .lock()
+    .unwrap()
+    .iter()
+    .any(|name| name == "handle_client_message"));
+}
+
+#[cfg(feature = "metrics")]
+#[test]
+fn test_remote_server_device_command_latency_histogram() {
+  async_manager::block_on(async {
+    let mut comm_manager = TestDeviceCommunicationManagerBuilder::default();
+    let _device = comm_manager.add_test_device(&TestDeviceIdentifier::new("Massage Demo", None));
+    let server = ButtplugServerBuilder::default()
+      .comm_manager(comm_manager)
+      .finish()
+      .unwrap();
+    let remote_server = Arc::new(ButtplugRemoteServer::new(server));
+    let (_session, sender, mut server_receiver) = start_test_session(&remote_server).await;
+    let mut start_scanning = message::StartScanning::default();
+    start_scanning.set_id(2);
+    sender.send(start_scanning.into()).await.unwrap();
+    while !matches!(
+      server_receiver.recv().await,
+      Some(ButtplugServerMessage::DeviceAdded(_))
+    ) {}
+    assert!(remote_server.device_command_latency_histogram(0).is_none());
+
+    let mut vibrate = message::VibrateCmd::new(0, vec![message::VibrateSubcommand::new(0, 0.5)]);
+    vibrate.set_id(3);
+    sender.send(vibrate.into()).await.unwrap();
+    assert!(matches!(
+      wait_for_reply(&mut server_receiver, 3).await,
+      ButtplugServerMessage::Ok(_)
+    ));
+    let histogram = remote_server.device_command_latency_histogram(0).unwrap();
+    assert_eq!(histogram.count(), 1);
+    assert!(histogram.percentile(50.0).is_some());
+  });
+}
+
+#[test]
+fn test_remote_server_server_accessor_and_is_running() {
+  async_manager::block_on(async {
+    let server = ButtplugServerBuilder::default()
+      .name("Accessor Server")
+      .finish()
+      .unwrap();
+    let remote_server = Arc::new(ButtplugRemoteServer::new(server));
+    assert_eq!(remote_server.server().server_name(), "Accessor Server");
+    assert!(Arc::ptr_eq(
+      &remote_server.server(),
+      &remote_server.server()
+    ));
+    assert!(!remote_server.is_running());
+
+    let (session, sender, _receiver) = start_test_session(&remote_server).await;
+    assert!(remote_server.is_running());
+    assert!(remote_server.server().connected());
+
+    remote_server.disconnect().await.unwrap();
+    session.await;
+    drop(sender);
+    assert!(!remote_server.is_running());
+  });
+}
+
+#[test]
+fn test_remote_server_global_event_stream() {
+  async_manager::block_on(async {
+    let mut comm_manager = TestDeviceCommunicationManagerBuilder::default();
+    let _device = comm_manager.add_test_device(&TestDeviceIdentifier::new("Massage Demo", None));
+    let server = ButtplugServerBuilder::default()
+      .comm_manager(comm_manager)
+      .finish()
+      .unwrap();
+    let remote_server = Arc::new(ButtplugRemoteServer::new(server));
+    let mut events = Box::pin(remote_server.global_event_stream());
+    let (_session, sender, _server_receiver) = start_test_session(&remote_server).await;
+    let mut start_scanning = message::StartScanning::default();
+    start_scanning.set_id(2);
+    sender.send(start_scanning.into()).await.unwrap();
+    let (mut remote_device_added, mut server_device_added) = (false, false);
+    while !(remote_device_added && server_device_added) {
+      match events.next().await {
+        Some(AnyButtplugEvent::Remote(ButtplugRemoteServerEvent::DeviceAdded(..))) => {
+          remote_device_added = true
+        }
+        Some(AnyButtplugEvent::Server(ButtplugServerMessage::DeviceAdded(_))) => {
+          server_device_added = true
+        }
+        Some(_) => continue,
+        None => panic!("Event stream ended early"),
+      }
+    }
+  });
+}
+
+/// Start a session whose replies each take `send_delay` to send, then send a message and wait
+/// until the server has started handling it.
+async fn start_slow_session_with_message_in_flight(
+  remote_server: &Arc<ButtplugRemoteServer>,
+  send_delay: Duration,
+) -> (RemoteHandle<()>, mpsc::Receiver<ButtplugServerMessage>) {
+  let (mut connector, client_sender, server_receiver) = test_server_connector();
+  connector.send_delay = Some(send_delay);
+  let (session, sender) = spawn_test_session(remote_server, connector, client_sender).await;
+  // Sent before the handshake, so the reply is an error, but it's still handled and replied to.
+  let mut ping = message::Ping::default();
+  ping.set_id(1);
+  sender.send(ping.into()).await.unwrap();
+  while remote_server.pending_client_message_count() > 0 {
+    tokio::task::yield_now().await;
+  }
+  tokio::time::sleep(Duration::from_millis(10)).await;
+  (session, server_receiver)
+}
+
+#[test]
+fn test_remote_server_shutdown_graceful_drains_messages() {
+  async_manager::block_on(async {
+    let remote_server = Arc::new(ButtplugRemoteServer::default());
+    let (session, mut server_receiver) =
+      start_slow_session_with_message_in_flight(&remote_server, Duration::from_millis(100)).await;
+    remote_server
+      .shutdown_graceful(Duration::from_secs(5))
+      .await
+      .unwrap();
+    // The reply went out before shutdown finished.
+    assert_eq!(server_receiver.try_recv().unwrap().id(), 1);
+    session.await;
+  });
+}
+
+#[test]
+fn test_remote_server_shutdown_graceful_timeout() {
+  async_manager::block_on(async {
+    let remote_server = Arc::new(ButtplugRemoteServer::default());
+    let (session, mut server_receiver) =
+      start_slow_session_with_message_in_flight(&remote_server, Duration::from_secs(5)).await;
+    let started = Instant::now();
+    remote_server
+      .shutdown_graceful(Duration::from_millis(50))
+      .await
+      .unwrap();
+    assert!(started.elapsed() < Duration::from_secs(2));
+    // The slow reply was abandoned.
+    assert!(server_receiver.try_recv().is_err());
+    session.await;
+  });
+}
+
+#[test]
+fn test_remote_server_connection_history() {
+  async_manager::block_on(async {
+    let remote_server = Arc::new(
+      ButtplugRemoteServerBuilder::default()
+        .connection_history_size(1)
+        .finish(),
+    );
+    assert!(remote_server.connection_history().is_empty());
+    assert_eq!(remote_server.sessions_completed(), 0);
+    for reason in [
+      DisconnectReason::AuthFailed,
+      DisconnectReason::ForcedReconnect,
+    ] {
+      let (session, _sender, _server_receiver) = start_test_session(&remote_server).await;
+      remote_server.disconnect_client(reason).await;
+      session.await;
+    }
+    // Only the latest session fits in the history, but both are counted.
+    assert_eq!(remote_server.sessions_completed(), 2);
+    let history = remote_server.connection_history();
+    assert_eq!(history.len(), 1);
+    let record = &history[0];
+    assert_eq!(record.client_name().as_deref(), Some("Test Client"));
+    assert_eq!(
+      record.disconnect_reason(),
+      Some(DisconnectReason::ForcedReconnect)
+    );
+    assert_eq!(record.messages_in(), 1);
+    assert_eq!(record.messages_out(), 1);
+    assert!(record.disconnected_at() >= record.connected_at());
+  });
+}
+
+#[test]
+fn test_remote_server_connection_history_disabled() {
+  async_manager::block_on(async {
+    let remote_server = Arc::new(
+      ButtplugRemoteServerBuilder::default()
+        .connection_history_size(0)
+        .finish(),
+    );
+    let (session, _sender, _server_receiver) = start_test_session(&remote_server).await;
+    remote_server
+      .disconnect_client(DisconnectReason::AuthFailed)
+      .await;
+    session.await;
+    assert!(remote_server.connection_history().is_empty());
+    assert_eq!(remote_server.sessions_completed(), 1);
+  });
+}
+
+#[test]
+fn test_remote_server_record_and_replay_session() {
+  async_manager::block_on(async {
+    let remote_server = Arc::new(ButtplugRemoteServer::default());
+    let recorder = remote_server.record_session();
+    let (session, sender, mut server_receiver) = start_test_session(&remote_server).await;
+    let mut ping = message::Ping::default();
+    ping.set_id(2);
+    sender.send(ping.into()).await.unwrap();
+    wait_for_reply(&mut server_receiver, 2).await;
+    let mut device_list = message::RequestDeviceList::default();
+    device_list.set_id(3);
+    sender.send(device_list.into()).await.unwrap();
+    wait_for_reply(&mut server_receiver, 3).await;
+    remote_server
+      .disconnect_client(DisconnectReason::AuthFailed)
+      .await;
+    session.await;
+
+    let transcript = recorder.transcript();
+    assert_eq!(transcript.len(), 6);
+    assert_eq!(transcript[0].direction(), MessageDirection::ClientToServer);
+    assert_eq!(transcript[1].direction(), MessageDirection::ServerToClient);
+    let path = std::env::temp_dir().join(format!(
+      "buttplug-session-transcript-{}.jsonl",
+      std::process::id()
+    ));
+    recorder.save(&path).unwrap();
+    let replies = replay_transcript(&path, &ButtplugServer::default())
+      .await
+      .unwrap();
+    let _ = std::fs::remove_file(&path);
+
+    // The replayed server answers exactly the way the recorded one did.
+    let serializer = ButtplugServerJSONSerializer::default();
+    serializer.force_message_version(&BUTTPLUG_CURRENT_MESSAGE_SPEC_VERSION);
+    let recorded: Vec<serde_json::Value> = transcript
+      .iter()
+      .filter(|msg| msg.direction() == MessageDirection::ServerToClient)
+      .map(|msg| serde_json::from_slice(msg.message()).unwrap())
+      .collect();
+    let replayed: Vec<serde_json::Value> = replies
+      .into_iter()
+      .map(|reply| match serializer.serialize(&[reply]) {
+        ButtplugSerializedMessage::Text(text) => serde_json::from_str(&text).unwrap(),
+        ButtplugSerializedMessage::Binary(_) => panic!("JSON serializer should output text"),
+      })
+      .collect();
+    assert_eq!(recorded, replayed);
+  });
+}
+
+#[test]
+fn test_remote_server_disable_device() {
+  async_manager::block_on(async {
+    let (server, _device) = test_server_with_device("Massage Demo", false).await;
+    let remote_server = Arc::new(ButtplugRemoteServer::new(server));
+    let mut events = Box::pin(remote_server.event_stream());
+    let (_session, sender, mut server_receiver) = start_test_session(&remote_server).await;
+    let mut start_scanning = message::StartScanning::default();
+    start_scanning.set_id(2);
+    sender.send(start_scanning.into()).await.unwrap();
+    while !matches!(
+      events.next().await,
+      Some(ButtplugRemoteServerEvent::DeviceAdded(0, ..))
+    ) {}
+    let mut device_list = message::RequestDeviceList::default();
+    device_list.set_id(3);
+    sender.send(device_list.into()).await.unwrap();
+    wait_for_reply(&mut server_receiver, 3).await;
+
+    assert!(remote_server.disable_device(1).await.is_err());
+    remote_server.disable_device(0).await.unwrap();
+    assert!(!remote_server
+      .server()
+      .device_manager()
+      .device_info(0)
+      .unwrap()
+      .enabled());
+    let mut vibrate = message::VibrateCmd::new(0, vec![message::VibrateSubcommand::new(0, 0.5)]);
+    vibrate.set_id(4);
+    sender.send(vibrate.clone().into()).await.unwrap();
+    if let ButtplugServerMessage::Error(err) = wait_for_reply(&mut server_receiver, 4).await {
+      assert!(matches!(
+        err.original_error(),
+        ButtplugError::ButtplugDeviceError(ButtplugDeviceError::DeviceDisabled(0))
+      ));
+    } else {
+      panic!("Commands to disabled devices should fail");
+    }
+    // Stopping is always allowed.
+    let mut stop = message::StopDeviceCmd::new(0);
+    stop.set_id(5);
+    sender.send(stop.into()).await.unwrap();
+    assert!(matches!(
+      wait_for_reply(&mut server_receiver, 5).await,
+      ButtplugServerMessage::Ok(_)
+    ));
+
+    remote_server.enable_device(0).unwrap();
+    vibrate.set_id(6);
+    sender.send(vibrate.into()).await.unwrap();
+    assert!(matches!(
+      wait_for_reply(&mut server_receiver, 6).await,
+      ButtplugServerMessage::Ok(_)
+    ));
+  });
+}
+
+#[test]
+fn test_remote_server_num_event_subscribers() {
+  async_manager::block_on(async {
+    let remote_server = ButtplugRemoteServer::default();
+    assert_eq!(remote_server.num_event_subscribers(), 0);
+    let events = remote_server.event_stream();
+    let bounded_events = remote_server.bounded_event_stream();
+    // Streams count even if they're never polled.
+    assert_eq!(remote_server.num_event_subscribers(), 2);
+    drop(events);
+    drop(bounded_events);
+    assert_eq!(remote_server.num_event_subscribers(), 0);
+  });
+}
+
+/// Sends a copy of each event it gets on to the test, or fails every event.
+struct TestEventSink(Option<mpsc::UnboundedSender<ButtplugRemoteServerEvent>>);
+
+#[async_trait::async_trait]
+impl ButtplugEventSink for TestEventSink {
+  async fn on_event(&self, event: &ButtplugRemoteServerEvent) -> Result<(), String> {
+    match &self.0 {
+      Some(sender) => sender.send(event.clone()).map_err(|err| err.to_string()),
+      None => Err("Test sink always fails".to_owned()),
+    }
+  }
+}
+
+#[test]
+fn test_remote_server_mirror_events_to() {
+  async_manager::block_on(async {
+    let remote_server = Arc::new(ButtplugRemoteServer::default());
+    let (sender, mut receiver) = mpsc::unbounded_channel();
+    remote_server.mirror_events_to(Arc::new(TestEventSink(None)));
+    remote_server.mirror_events_to(Arc::new(TestEventSink(Some(sender))));
+    assert_eq!(
+      remote_server.subscriber_names(),
+      vec!["event_sink".to_owned()]
+    );
+    assert_eq!(remote_server.num_event_subscribers(), 2);
+    let (_session, _sender, _server_receiver) = start_test_session(&remote_server).await;
+    // The failing sink doesn't stop the other one from getting events.
+    loop {
+      if let Some(ButtplugRemoteServerEvent::ClientConnected(_, client_name)) =
+        receiver.recv().await
+      {
+        assert_eq!(client_name, "Test Client");
+        break;
+      }
+    }
+    assert!(remote_server.disconnect().await.is_ok());
+  });
+}
+
+#[test]
+fn test_remote_server_event_stream_named() {
+  async_manager::block_on(async {
+    let remote_server = ButtplugRemoteServer::default();
+    assert!(remote_server.subscriber_names().is_empty());
+    let ui_events = remote_server.event_stream_named("ui");
+    let other_ui_events = remote_server.event_stream_named("ui");
+    let logger_events = remote_server.event_stream_named("logger");
+    let unnamed_events = remote_server.event_stream();
+    assert_eq!(
+      remote_server.subscriber_names(),
+      vec!["logger".to_owned(), "ui".to_owned()]
+    );
+    assert_eq!(remote_server.num_event_subscribers(), 4);
+    drop(ui_events);
+    drop(logger_events);
+    assert_eq!(remote_server.subscriber_names(), vec!["ui".to_owned()]);
+    drop(other_ui_events);
+    assert!(remote_server.subscriber_names().is_empty());
+    assert_eq!(remote_server.num_event_subscribers(), 1);
+    drop(unnamed_events);
+  });
+}
+
+#[test]
+fn test_remote_server_announce_device_list_to_client() {
+  async_manager::block_on(async {
+    let mut comm_manager = TestDeviceCommunicationManagerBuilder::default();
+    let _device = comm_manager.add_test_device(&TestDeviceIdentifier::new("Massage Demo", None));
+    let server = ButtplugServerBuilder::default()
+      .comm_manager(comm_manager)
+      .finish()
+      .unwrap();
+    let remote_server = Arc::new(ButtplugRemoteServer::new(server));
+    let (_session, sender, mut server_receiver) = start_test_session(&remote_server).await;
+    let mut start_scanning = message::StartScanning::default();
+    start_scanning.set_id(2);
+    sender.send(start_scanning.into()).await.unwrap();
+    while !matches!(
+      server_receiver.recv().await,
+      Some(ButtplugServerMessage::DeviceAdded(_))
+    ) {}
+    let mut ping = message::Ping::default();
+    ping.set_id(3);
+    sender.send(ping.into()).await.unwrap();
+    wait_for_reply(&mut server_receiver, 3).await;
+
+    remote_server.announce_device_list_to_client();
+    loop {
+      match server_receiver.recv().await {
+        Some(ButtplugServerMessage::DeviceAdded(device_added)) => {
+          assert_eq!(device_added.device_index(), 0);
+          assert_eq!(device_added.device_name(), "Aneros Vivi");
+          break;
+        }
+        Some(_) => continue,
+        None => panic!("Session should still be running"),
+      }
+    }
+  });
+}
+
+#[test]
+fn test_remote_server_close_after() {
+  async_manager::block_on(async {
+    let remote_server = Arc::new(ButtplugRemoteServer::default());
+    let events = remote_server.event_stream();
+    pin_mut!(events);
+    let (session, _sender, _server_receiver) = start_test_session(&remote_server).await;
+    assert!(matches!(
+      events.next().await,
+      Some(ButtplugRemoteServerEvent::ClientConnected(..))
+    ));
+
+    // Dropping the timer cancels it.
+    let cancelled =
+      async_manager::spawn_with_handle(remote_server.close_after(Duration::from_millis(10)))
+        .unwrap();
+    drop(cancelled);
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    assert!(remote_server.is_running());
+
+    remote_server.close_after(Duration::from_millis(10)).await;
+    assert!(matches!(
+      events.next().await,
+      Some(ButtplugRemoteServerEvent::SessionExpired)
+    ));
+    assert!(matches!(
+      events.next().await,
+      Some(ButtplugRemoteServerEvent::ClientDisconnected(
+        _,
+        Some(DisconnectReason::SessionExpired)
+      ))
+    ));
+    session.await;
+    assert!(!remote_server.is_running());
+  });
+}
+
+#[test]
+fn test_remote_server_set_event_buffer_policy() {
+  async_manager::block_on(async {
+    let (server, _device) = test_server_with_device("Massage Demo", false).await;
+    let remote_server = Arc::new(ButtplugRemoteServer::new(server));
+    let events = remote_server.event_stream();
+    pin_mut!(events);
+    let (_session, sender, mut server_receiver) = start_test_session(&remote_server).await;
+    let mut start_scanning = message::StartScanning::default();
+    start_scanning.set_id(2);
+    sender.send(start_scanning.into()).await.unwrap();
+    while !matches!(
+      server_receiver.recv().await,
+      Some(ButtplugServerMessage::DeviceAdded(_))
+    ) {}
+
+    let policy = EventBufferPolicy::new(16, OverflowPolicy::DropOldest, true);
+    remote_server.set_event_buffer_policy(policy);
+    assert_eq!(remote_server.event_buffer_policy(), policy);
+    let mut vibrate = message::VibrateCmd::new(0, vec![message::VibrateSubcommand::new(0, 0.5)]);
+    vibrate.set_id(3);
+    sender.send(vibrate.clone().into()).await.unwrap();
+    wait_for_reply(&mut server_receiver, 3).await;
+
+    // The existing subscriber reads what was sent before the change, then carries on with the
+    // new buffer.
+    let mut saw_device_added = false;
+    loop {
+      match events.next().await.expect("Test, assuming infallible.") {
+        ButtplugRemoteServerEvent::DeviceAdded(..) => saw_device_added = true,
+        ButtplugRemoteServerEvent::DeviceCommandSent(0, msg) => {
+          assert_eq!(msg, vibrate.clone().into());
+          break;
+        }
+        _ => {}
+      }
+    }
+    assert!(saw_device_added);
+
+    // Late subscribers get events sent since the change replayed.
+    let late_events = remote_server.event_stream();
+    pin_mut!(late_events);
+    assert!(matches!(
+      late_events.next().await,
+      Some(ButtplugRemoteServerEvent::DeviceCommand(_))
+    ));
+    assert!(matches!(
+      late_events.next().await,
+      Some(ButtplugRemoteServerEvent::DeviceCommandSent(0, _))
+    ));
+
+    // Subscribers that fall behind can be closed instead of skipping events.
+    remote_server.set_event_buffer_policy(EventBufferPolicy::new(
+      1,
+      OverflowPolicy::CloseSubscriber,
+      false,
+    ));
+    let stalled_events = remote_server.event_stream();
+    pin_mut!(stalled_events);
+    for id in 4..7 {
+      vibrate.set_id(id);
+      sender.send(vibrate.clone().into()).await.unwrap();
+      wait_for_reply(&mut server_receiver, id).await;
+    }
+    assert!(stalled_events.next().await.is_none());
+  });
+}
+
+#[test]
+fn test_remote_server_event_channel_auto_resize() {
+  async_manager::block_on(async {
+    let (server, _device) = test_server_with_device("Massage Demo", false).await;
+    let remote_server = Arc::new(
+      ButtplugRemoteServerBuilder::default()
+        .server(server)
+        .event_channel_capacity(1)
+        .event_channel_auto_resize(2)
+        .finish(),
+    );
+    let events = remote_server.event_stream();
+    pin_mut!(events);
+    let (_session, sender, mut server_receiver) = start_test_session(&remote_server).await;
+    connect_test_device(&sender, &mut server_receiver).await;
+    let mut vibrate = message::VibrateCmd::new(0, vec![message::VibrateSubcommand::new(0, 0.5)]);
+    for id in 3..6 {
+      vibrate.set_id(id);
+      sender.send(vibrate.clone().into()).await.unwrap();
+      wait_for_reply(&mut server_receiver, id).await;
+    }
+    assert_eq!(remote_server.events_discarded_count(), 0);
+    assert!(events.next().await.is_some());
+    assert!(remote_server.events_discarded_count() > 0);
+    assert_eq!(remote_server.event_buffer_policy().capacity(), 1);
+
+    // The first event sent after the resize interval picks up the skipped events and grows the
+    // channel.
+    tokio::time::sleep(EVENT_CHANNEL_RESIZE_INTERVAL).await;
+    vibrate.set_id(6);
+    sender.send(vibrate.clone().into()).await.unwrap();
+    wait_for_reply(&mut server_receiver, 6).await;
+    assert_eq!(remote_server.event_buffer_policy().capacity(), 2);
+  });
+}
+
+#[test]
+fn test_remote_server_event_transform() {
+  async_manager::block_on(async {
+    let (server, _device) = test_server_with_device("Massage Demo", false).await;
+    let remote_server = Arc::new(
+      ButtplugRemoteServerBuilder::default()
+        .server(server)
+        .with_event_transform(|event| match event {
+          ButtplugRemoteServerEvent::ClientConnected(id, _) => Some(
+            ButtplugRemoteServerEvent::ClientConnected(id, "Redacted".to_owned()),
+          ),
+          ButtplugRemoteServerEvent::DeviceCommand(_) => None,
+          event => Some(event),
+        })
+        .finish(),
+    );
+    let events = remote_server.event_stream();
+    pin_mut!(events);
+    let (_session, sender, mut server_receiver) = start_test_session(&remote_server).await;
+    match events.next().await {
+      Some(ButtplugRemoteServerEvent::ClientConnected(_, name)) => assert_eq!(name, "Redacted"),
+      event => panic!("Expected ClientConnected, got {:?}", event),
+    }
+    let mut start_scanning = message::StartScanning::default();
+    start_scanning.set_id(2);
+    sender.send(start_scanning.into()).await.unwrap();
+    while !matches!(
+      server_receiver.recv().await,
+      Some(ButtplugServerMessage::DeviceAdded(_))
+    ) {}
+    assert!(matches!(
+      events.next().await,
+      Some(ButtplugRemoteServerEvent::DeviceAdded(..))
+    ));
+    let mut vibrate = message::VibrateCmd::new(0, vec![message::VibrateSubcommand::new(0, 0.5)]);
+    vibrate.set_id(3);
+    sender.send(vibrate.into()).await.unwrap();
+    wait_for_reply(&mut server_receiver, 3).await;
+    // The DeviceCommand event is dropped, so the next one is the result.
+    assert!(matches!(
+      events.next().await,
+      Some(ButtplugRemoteServerEvent::DeviceCommandSent(..))
+    ));
+  });
+}
+
+#[test]
+fn test_remote_server_build_info() {
+  async_manager::block_on(async {
+    let remote_server = ButtplugRemoteServer::default();
+    let build_info = remote_server.server_build_info();
+    assert_eq!(build_info.crate_version(), env!("CARGO_PKG_VERSION"));
+    assert_eq!(
+      build_info.spec_version(),
+      BUTTPLUG_CURRENT_MESSAGE_SPEC_VERSION as u32
+    );
+    assert!(build_info.features().contains(&"server".to_owned()));
+    // Features turned on by other features are listed too.
+    assert!(build_info.features().contains(&"std".to_owned()));
+    // Optional dependencies aren't features.
+    assert!(!build_info.features().contains(&"tokio".to_owned()));
+  });
+}
+
+#[test]
+fn test_remote_server_telemetry() {
+  async_manager::block_on(async {
+    let remote_server = ButtplugRemoteServer::default();
+    assert!(matches!(
+      remote_server.enable_telemetry(TelemetryConfig::new(None, None, 2.0)),
+      Err(TelemetryError::InvalidSamplingRate(_))
+    ));
+    assert!(matches!(
+      remote_server.enable_telemetry(TelemetryConfig::new(
+        None,
+        Some("http://localhost:4317".to_owned()),
+        1.0
+      )),
+      Err(TelemetryError::OtlpUnsupported(_))
+    ));
+    assert!(remote_server.prometheus_address().is_none());
+
+    #[cfg(not(feature = "http-info"))]
+    assert!(matches!(
+      remote_server.enable_telemetry(TelemetryConfig::new(Some(0), None, 0.5)),
+      Err(TelemetryError::PrometheusUnsupported)
+    ));
+    #[cfg(feature = "http-info")]
+    {
+      remote_server
+        .enable_telemetry(TelemetryConfig::new(Some(0), None, 0.5))
+        .expect("Test, assuming infallible.");
+      let address = remote_server
+        .prometheus_address()
+        .expect("Test, assuming infallible.");
+      // Changing other settings keeps the endpoint on the port it's already using.
+      remote_server
+        .enable_telemetry(TelemetryConfig::new(Some(address.port()), None, 1.0))
+        .expect("Test, assuming infallible.");
+      assert_eq!(remote_server.prometheus_address(), Some(address));
+      let mut stream = TcpStream::connect(address)
+        .await
+        .expect("Test, assuming infallible.");
+      stream
+        .write_all(b"GET /metrics HTTP/1.1\r\nConnection: close\r\n\r\n")
+        .await
+        .expect("Test, assuming infallible.");
+      let mut response = String::new();
+      stream
+        .read_to_string(&mut response)
+        .await
+        .expect("Test, assuming infallible.");
+      assert!(response.starts_with("HTTP/1.1 200 OK"));
+      assert!(response.contains("buttplug_server_messages_total 0"));
+      assert!(response.contains("buttplug_remote_server_sessions 0"));
+    }
+
+    remote_server.disable_telemetry();
+    assert!(remote_server.prometheus_address().is_none());
+  });
+}
+
+#[cfg(feature = "http-info")]
+#[test]
+fn test_remote_server_http_info_endpoint() {
+  async_manager::block_on(async {
+    let remote_server = ButtplugRemoteServer::default();
+    let handle = remote_server
+      .serve_http_info_endpoint(([127, 0, 0, 1], 0).into())
+      .expect("Test, assuming infallible.");
+    let address = handle.address();
+    let get = |path: &'static str| async move {
+      let mut stream = TcpStream::connect(address)
+        .await
+        .expect("Test, assuming infallible.");
+      stream
+        .write_all(format!("GET {} HTTP/1.1\r\nConnection: close\r\n\r\n", path).as_bytes())
+        .await
+        .expect("Test, assuming infallible.");
+      let mut response = String::new();
+      stream
+        .read_to_string(&mut response)
+        .await
+        .expect("Test, assuming infallible.");
+      response
+    };
+    let response = get("/info").await;
+    assert!(response.starts_with("HTTP/1.1 200 OK"));
+    let body: serde_json::Value = serde_json::from_str(
+      response
+        .split("\r\n\r\n")
+        .nth(1)
+        .expect("Test, assuming infallible."),
+    )
+    .expect("Test, assuming infallible.");
+    assert_eq!(body["server_name"], "Buttplug Server");
+    assert_eq!(body["devices"], serde_json::json!([]));
+    assert!(body["client_name"].is_null());
+    assert!(get("/missing").await.starts_with("HTTP/1.1 404"));
+    #[cfg(feature = "metrics")]
+    assert!(get("/metrics")
+      .await
+      .contains("buttplug_server_messages_total 0"));
+
+    handle.stop();
+    assert!(handle.is_stopped());
+  });
+}
+
+#[test]
+fn test_remote_server_idle_device_shutdown() {
+  async_manager::block_on(async {
+    let (server, mut device) = test_server_with_device("Massage Demo", false).await;
+    let remote_server = Arc::new(ButtplugRemoteServer::with_idle_device_shutdown(
+      server,
+      IdleShutdownPolicy::new(Duration::from_millis(100), IdleAction::Stop),
+    ));
+    let mut events = Box::pin(remote_server.event_stream());
+    let (_session, sender, mut server_receiver) = start_test_session(&remote_server).await;
+    let mut start_scanning = message::StartScanning::default();
+    start_scanning.set_id(2);
+    sender.send(start_scanning.into()).await.unwrap();
+    while !matches!(
+      events.next().await,
+      Some(ButtplugRemoteServerEvent::DeviceAdded(0, ..))
+    ) {}
+    let device_manager = remote_server.server().device_manager();
+    assert!(device_manager.device_last_actuator_command_at(0).is_none());
+    // Only actuator commands count towards keeping a device active.
+    let mut stop = message::StopDeviceCmd::new(0);
+    stop.set_id(3);
+    sender.send(stop.into()).await.unwrap();
+    wait_for_reply(&mut server_receiver, 3).await;
+    while device.receiver.try_recv().is_ok() {}
+    assert!(device_manager.device_last_command_at(0).is_some());
+    assert!(device_manager.device_last_actuator_command_at(0).is_none());
+    let mut vibrate = message::VibrateCmd::new(0, vec![message::VibrateSubcommand::new(0, 0.5)]);
+    vibrate.set_id(4);
+    sender.send(vibrate.into()).await.unwrap();
+    wait_for_reply(&mut server_receiver, 4).await;
+    check_test_recv_value(
+      &mut device,
+      HardwareCommand::Write(HardwareWriteCmd::new(Endpoint::Tx, vec![0xF1, 64], false)),
+    );
+    assert!(device_manager.device_last_actuator_command_at(0).is_some());
+
+    // Idle devices are stopped once, and left alone until they get another command.
+    tokio::time::sleep(Duration::from_millis(400)).await;
+    check_test_recv_value(
+      &mut device,
+      HardwareCommand::Write(HardwareWriteCmd::new(Endpoint::Tx, vec![0xF1, 0], false)),
+    );
+    assert!(device_manager.device_last_actuator_command_at(0).is_none());
+    assert!(device.receiver.try_recv().is_err());
+  });
+}
+
+#[test]
+fn test_remote_server_reset_metrics() {
+  async_manager::block_on(async {
+    let (server, _device) = test_server_with_device("Massage Demo", false).await;
+    let remote_server = Arc::new(
+      ButtplugRemoteServerBuilder::default()
+        .server(server)
+        .event_channel_capacity(1)
+        .finish(),
+    );
+    let events = remote_server.event_stream();
+    pin_mut!(events);
+    let (_session, sender, mut server_receiver) = start_test_session(&remote_server).await;
+    let mut start_scanning = message::StartScanning::default();
+    start_scanning.set_id(2);
+    sender.send(start_scanning.into()).await.unwrap();
+    while !matches!(
+      server_receiver.recv().await,
+      Some(ButtplugServerMessage::DeviceAdded(_))
+    ) {}
+    let mut vibrate = message::VibrateCmd::new(5, vec![message::VibrateSubcommand::new(0, 0.5)]);
+    vibrate.set_id(3);
+    sender.send(vibrate.into()).await.unwrap();
+    wait_for_reply(&mut server_receiver, 3).await;
+    assert!(events.next().await.is_some());
+    let server = remote_server.server();
+    assert!(server.message_count() > 0);
+    assert!(server.error_count() > 0);
+    assert!(remote_server.events_discarded_count() > 0);
+    tokio::time::sleep(Duration::from_millis(50)).await;
+
+    remote_server.reset_metrics();
+    assert_eq!(server.message_count(), 0);
+    assert_eq!(server.error_count(), 0);
+    assert!(server.uptime() < Duration::from_millis(50));
+    assert_eq!(remote_server.events_discarded_count(), 0);
+    // The client stays connected, and counting starts over.
+    let mut request_device_list = message::RequestDeviceList::default();
+    request_device_list.set_id(4);
+    sender.send(request_device_list.into()).await.unwrap();
+    wait_for_reply(&mut server_receiver, 4).await;
+    assert_eq!(server.message_count(), 1);
+    assert_eq!(server.error_count(), 0);
+    assert!(server.device_uptime(0).is_some());
+  });
+}
@@ -7,19 +7,29 @@
 
 mod util;
 pub use util::{
+  start_test_server_with_connected_device,
   test_device_manager::{
     check_test_recv_value,
     TestDeviceCommunicationManagerBuilder,
     TestDeviceIdentifier,
+    TestHardwareEvent,
   },
   test_server_with_device,
+  DelayDeviceCommunicationManagerBuilder,
 };
 
 use buttplug::{
   core::{
-    errors::{ButtplugDeviceError, ButtplugError, ButtplugHandshakeError},
+    errors::{
+      ButtplugDeviceError,
+      ButtplugError,
+      ButtplugHandshakeError,
+      ButtplugMessageError,
+      ButtplugUnknownError,
+    },
     message::{
       self,
+      ActuatorType,
       ButtplugMessageSpecVersion,
       ButtplugServerMessage,
       Endpoint,
@@ -27,14 +37,28 @@ use buttplug::{
     },
   },
   server::{
-    device::hardware::{HardwareCommand, HardwareWriteCmd},
+    device::{
+      configuration::{ServerDeviceMessageAttributesBuilder, ServerGenericDeviceMessageAttributes},
+      hardware::{HardwareCommand, HardwareWriteCmd},
+      protocol::ProtocolCapabilityFlags,
+      CommandConflictPolicy,
+      VirtualDeviceConfig,
+    },
     ButtplugServer,
     ButtplugServerBuilder,
+    ButtplugServerError,
+    PairingEvent,
+    SessionEventKind,
+    MAX_SESSION_LOG_EVENTS,
   },
   util::async_manager,
 };
 use futures::{pin_mut, Stream, StreamExt};
-use std::time::Duration;
+use std::{
+  ops::RangeInclusive,
+  sync::Arc,
+  time::{Duration, Instant},
+};
 use tokio::time::sleep;
 
 async fn setup_test_server(
@@ -104,6 +128,31 @@ fn test_client_version_older_than_server() {
   });
 }
 
+#[test]
+fn test_message_newer_than_client_version() {
+  let msg =
+    message::RequestServerInfo::new("Test Client", ButtplugMessageSpecVersion::Version1).into();
+  async_manager::block_on(async {
+    let server = ButtplugServer::default();
+    assert!(server.parse_message(msg).await.is_ok());
+    let scalar_msg: message::ButtplugClientMessage = message::ScalarCmd::new(0, vec![]).into();
+    assert_eq!(scalar_msg.schema_version(), 3);
+    let reply = server.parse_message(scalar_msg).await;
+    assert!(matches!(
+      reply.unwrap_err().original_error(),
+      ButtplugError::ButtplugMessageError(ButtplugMessageError::VersionError(..))
+    ));
+    // Messages from the client's own spec version still make it to the device manager.
+    let vibrate_msg: message::ButtplugClientMessage = message::VibrateCmd::new(0, vec![]).into();
+    assert_eq!(vibrate_msg.schema_version(), 1);
+    let reply = server.parse_message(vibrate_msg).await;
+    assert!(matches!(
+      reply.unwrap_err().original_error(),
+      ButtplugError::ButtplugDeviceError(ButtplugDeviceError::DeviceNotAvailable(_))
+    ));
+  });
+}
+
 #[test]
 #[ignore = "Needs to be rewritten to send in via the JSON parser, otherwise we're type bound due to the enum and can't fail"]
 fn test_server_version_older_than_client() {
@@ -220,6 +269,75 @@ fn test_device_stop_on_ping_timeout() {
   });
 }
 
+#[test]
+fn test_stop_all_devices_safe_stop() {
+  async_manager::block_on(async {
+    let (server, mut device) = test_server_with_device("PROSTATE VIBE", false).await;
+    let recv = server.event_stream();
+    pin_mut!(recv);
+    let msg = message::RequestServerInfo::new("Test Client", BUTTPLUG_CURRENT_MESSAGE_SPEC_VERSION);
+    assert!(server.parse_message(msg.into()).await.is_ok());
+    assert!(server
+      .parse_message(message::StartScanning::default().into())
+      .await
+      .is_ok());
+    let mut device_index = 100;
+    while let Some(msg) = recv.next().await {
+      if let ButtplugServerMessage::DeviceAdded(da) = msg {
+        device_index = da.device_index();
+        break;
+      }
+    }
+    let device_info = server
+      .device_manager()
+      .device_info(device_index)
+      .expect("Device was just added");
+    assert!(device_info
+      .capability_flags()
+      .contains(ProtocolCapabilityFlags::SAFE_STOP));
+
+    let vibrate_msg =
+      message::VibrateCmd::new(device_index, vec![message::VibrateSubcommand::new(0, 0.5)]);
+    server
+      .parse_message(vibrate_msg.clone().into())
+      .await
+      .expect("Test, assuming infallible.");
+    check_test_recv_value(
+      &mut device,
+      HardwareCommand::Write(HardwareWriteCmd::new(
+        Endpoint::Tx,
+        vec![0xF3, 1, 0x40],
+        true,
+      )),
+    );
+    check_test_recv_value(
+      &mut device,
+      HardwareCommand::Write(HardwareWriteCmd::new(Endpoint::Tx, vec![0xF3, 2, 0], true)),
+    );
+    server
+      .parse_message(message::StopAllDevices::default().into())
+      .await
+      .expect("Test, assuming infallible.");
+    check_test_recv_value(
+      &mut device,
+      HardwareCommand::Write(HardwareWriteCmd::new(Endpoint::Tx, vec![0xF3, 0, 0], true)),
+    );
+    // The device is known to be stopped, so the same command is sent again.
+    server
+      .parse_message(vibrate_msg.into())
+      .await
+      .expect("Test, assuming infallible.");
+    check_test_recv_value(
+      &mut device,
+      HardwareCommand::Write(HardwareWriteCmd::new(
+        Endpoint::Tx,
+        vec![0xF3, 1, 0x40],
+        true,
+      )),
+    );
+  });
+}
+
 #[test]
 fn test_repeated_handshake() {
   let msg = message::RequestServerInfo::new("Test Client", ButtplugMessageSpecVersion::Version3);
@@ -250,6 +368,31 @@ fn test_invalid_device_index() {
   });
 }
 
+#[test]
+fn test_server_on_error() {
+  async_manager::block_on(async {
+    let msg = message::RequestServerInfo::new("Test Client", BUTTPLUG_CURRENT_MESSAGE_SPEC_VERSION);
+    let (server, _) = setup_test_server(msg.clone().into()).await;
+    let (error_sender, mut error_receiver) = tokio::sync::mpsc::unbounded_channel();
+    server.on_error(move |err| {
+      let _ = error_sender.send(err);
+    });
+    // Server level errors aren't device errors, so they shouldn't reach the callback.
+    assert!(server.parse_message(msg.into()).await.is_err());
+    assert!(server
+      .parse_message(message::VibrateCmd::new(10, vec![]).into())
+      .await
+      .is_err());
+    assert!(matches!(
+      error_receiver.recv().await,
+      Some(ButtplugError::ButtplugDeviceError(
+        ButtplugDeviceError::DeviceNotAvailable(10)
+      ))
+    ));
+    assert!(error_receiver.try_recv().is_err());
+  });
+}
+
 #[test]
 fn test_device_index_generation() {
   async_manager::block_on(async {
@@ -342,6 +485,253 @@ fn test_server_scanning_finished() {
   });
 }
 
+#[test]
+fn test_server_scan_results() {
+  async_manager::block_on(async {
+    let mut builder = TestDeviceCommunicationManagerBuilder::default();
+    let mut _device1 = builder.add_test_device(&TestDeviceIdentifier::new("Massage Demo", None));
+    let mut _device2 = builder.add_test_device(&TestDeviceIdentifier::new(
+      "Massage Demo",
+      Some("denied-address".to_owned()),
+    ));
+
+    let mut server_builder = ButtplugServerBuilder::default();
+    server_builder
+      .comm_manager(builder)
+      .denied_address("denied-address");
+    let server = server_builder.finish().unwrap();
+    assert!(server.scan_results().is_empty());
+    assert_eq!(server.pending_scan_results(), 0);
+
+    let recv = server.event_stream();
+    pin_mut!(recv);
+    assert!(server
+      .parse_message(
+        message::RequestServerInfo::new("Test Client", BUTTPLUG_CURRENT_MESSAGE_SPEC_VERSION)
+          .into()
+      )
+      .await
+      .is_ok());
+    assert!(server
+      .parse_message(message::StartScanning::default().into())
+      .await
+      .is_ok());
+    let mut device_added = false;
+    let mut finish_received = false;
+    while let Some(msg) = recv.next().await {
+      match msg {
+        ButtplugServerMessage::DeviceAdded(_) => device_added = true,
+        ButtplugServerMessage::ScanningFinished(_) => finish_received = true,
+        _ => panic!("Unexpected message: {:?}", msg),
+      }
+      if device_added && finish_received {
+        break;
+      }
+    }
+    // The connected device is no longer a scan result, but the denied one still shows up.
+    let results = server.scan_results();
+    assert_eq!(results.len(), 1);
+    assert_eq!(server.pending_scan_results(), 1);
+    assert_eq!(results[0].address(), "denied-address");
+    assert_eq!(results[0].advertisement_name(), "Massage Demo");
+    assert_eq!(*results[0].rssi(), None);
+    assert_eq!(results[0].protocol_guess(), &Some("aneros".to_owned()));
+  });
+}
+
+#[test]
+fn test_server_active_command_count() {
+  async_manager::block_on(async {
+    let server = ButtplugServer::default();
+    assert_eq!(server.active_command_count(), 0);
+    let handshake = server.parse_message(
+      message::RequestServerInfo::new("Test Client", BUTTPLUG_CURRENT_MESSAGE_SPEC_VERSION).into(),
+    );
+    assert_eq!(server.active_command_count(), 1);
+    assert!(handshake.await.is_ok());
+    assert_eq!(server.active_command_count(), 0);
+
+    let stop = server.parse_message(message::StopAllDevices::default().into());
+    let device_list = server.parse_message(message::RequestDeviceList::default().into());
+    assert_eq!(server.active_command_count(), 2);
+    assert!(stop.await.is_ok());
+    assert_eq!(server.active_command_count(), 1);
+    // Dropping an unfinished command future should also remove it from the count.
+    drop(device_list);
+    assert_eq!(server.active_command_count(), 0);
+  });
+}
+
+#[test]
+fn test_server_wait_for_idle() {
+  async_manager::block_on(async {
+    let server = ButtplugServer::default();
+    assert!(server
+      .wait_for_idle(Duration::from_millis(50))
+      .await
+      .is_ok());
+    assert!(server
+      .parse_message(
+        message::RequestServerInfo::new("Test Client", BUTTPLUG_CURRENT_MESSAGE_SPEC_VERSION)
+          .into()
+      )
+      .await
+      .is_ok());
+    let device_list = server.parse_message(message::RequestDeviceList::default().into());
+    assert!(matches!(
+      server.wait_for_idle(Duration::from_millis(50)).await,
+      Err(ButtplugError::ButtplugUnknownError(
+        ButtplugUnknownError::IdleTimedOut(_)
+      ))
+    ));
+    drop(device_list);
+    assert_eq!(server.pending_command_count(), 0);
+    assert!(server
+      .wait_for_idle(Duration::from_millis(50))
+      .await
+      .is_ok());
+  });
+}
+
+#[test]
+fn test_server_comm_manager_init_timeout() {
+  async_manager::block_on(async {
+    let mut builder = ButtplugServerBuilder::default();
+    builder
+      .comm_manager(DelayDeviceCommunicationManagerBuilder::with_init_delay(
+        Duration::from_secs(5),
+      ))
+      .comm_manager_init_timeout(Duration::from_millis(100));
+    let start = std::time::Instant::now();
+    let server = builder.finish().expect("Test, assuming infallible.");
+    assert!(start.elapsed() < Duration::from_secs(2));
+    assert_eq!(
+      *server.export_config().comm_manager_init_timeout(),
+      Some(Duration::from_millis(100))
+    );
+  });
+}
+
+#[test]
+fn test_server_add_comm_manager_init_timeout_does_not_block() {
+  async_manager::block_on(async {
+    let mut builder = ButtplugServerBuilder::default();
+    builder.comm_manager_init_timeout(Duration::from_millis(500));
+    let server = builder.finish().expect("Test, assuming infallible.");
+    let start = Instant::now();
+    // The timer only fires on time if adding the comm manager leaves the executor free.
+    let (added, timer_elapsed) = futures::join!(
+      server.add_comm_manager(DelayDeviceCommunicationManagerBuilder::with_init_delay(
+        Duration::from_secs(5)
+      )),
+      async {
+        sleep(Duration::from_millis(50)).await;
+        start.elapsed()
+      }
+    );
+    assert!(matches!(
+      added,
+      Err(ButtplugServerError::DeviceCommunicationManagerInitTimedOut)
+    ));
+    assert!(timer_elapsed < Duration::from_millis(400));
+  });
+}
+
+#[test]
+fn test_server_add_duplicate_comm_manager_is_not_built() {
+  async_manager::block_on(async {
+    let mut builder = ButtplugServerBuilder::default();
+    builder.comm_manager(DelayDeviceCommunicationManagerBuilder::default());
+    let server = builder.finish().expect("Test, assuming infallible.");
+    let start = Instant::now();
+    assert!(matches!(
+      server
+        .add_comm_manager(DelayDeviceCommunicationManagerBuilder::with_init_delay(
+          Duration::from_secs(5)
+        ))
+        .await,
+      Err(ButtplugServerError::DeviceCommunicationManagerTypeAlreadyAdded(_))
+    ));
+    assert!(start.elapsed() < Duration::from_secs(2));
+  });
+}
+
+#[test]
+fn test_server_set_server_name() {
+  async_manager::block_on(async {
+    let server = ButtplugServer::default();
+    server.set_server_name("Renamed Server");
+    assert_eq!(server.server_name(), "Renamed Server");
+    match server
+      .parse_message(
+        message::RequestServerInfo::new("Test Client", BUTTPLUG_CURRENT_MESSAGE_SPEC_VERSION)
+          .into(),
+      )
+      .await
+      .expect("Test, assuming infallible.")
+    {
+      ButtplugServerMessage::ServerInfo(s) => assert_eq!(s.server_name(), "Renamed Server"),
+      _ => panic!("Should've received ServerInfo"),
+    }
+  });
+}
+
+#[test]
+fn test_server_export_config() {
+  async_manager::block_on(async {
+    let server = ButtplugServerBuilder::default()
+      .name("Exported Server")
+      .max_ping_time(1000)
+      .allowed_address("allowed-addr")
+      .denied_address("denied-addr")
+      .allow_raw_messages()
+      .device_command_debounce(Duration::from_millis(50))
+      .finish()
+      .expect("Test, assuming infallible.");
+    server.set_server_name("Renamed Server");
+    let config = server.export_config();
+    assert_eq!(config.name(), "Renamed Server");
+    assert_eq!(*config.max_ping_time(), Some(1000));
+    assert_eq!(config.allowed_addresses(), &vec!["allowed-addr".to_owned()]);
+    assert_eq!(config.denied_addresses(), &vec!["denied-addr".to_owned()]);
+    assert!(*config.allow_raw_messages());
+    assert!(!*config.skip_default_protocols());
+    assert!(config.custom_device_configuration_json().is_none());
+    assert_eq!(
+      *config.device_command_debounce(),
+      Some(Duration::from_millis(50))
+    );
+    let config_json = serde_json::to_value(&config).expect("Config should serialize.");
+    assert_eq!(config_json["name"], "Renamed Server");
+    assert_eq!(config_json["max_ping_time"], 1000);
+  });
+}
+
+#[test]
+fn test_server_list_protocols() {
+  async_manager::block_on(async {
+    let server = ButtplugServer::default();
+    let protocols = server.list_protocols();
+    let lovense = protocols
+      .iter()
+      .find(|protocol| protocol.name() == "lovense")
+      .expect("Lovense is a default protocol");
+    assert_eq!(lovense.manufacturer(), "Lovense");
+    assert!(lovense.device_names().contains(&"Lovense Edge".to_owned()));
+    assert!(lovense
+      .supported_messages()
+      .contains(&"ScalarCmd".to_owned()));
+    assert!(lovense
+      .supported_messages()
+      .contains(&"RotateCmd".to_owned()));
+    let server = ButtplugServerBuilder::default()
+      .skip_default_protocols()
+      .finish()
+      .unwrap();
+    assert!(server.list_protocols().is_empty());
+  });
+}
+
 #[test]
 fn test_server_builder_null_device_config() {
   async_manager::block_on(async {
@@ -510,3 +900,945 @@ fn test_server_builder_user_device_config_old_config_version() {
 // TODO Test scan with no comm managers
 // TODO Test message with no RequestServerInfo first
 // TODO Test sending device command for device that doesn't exist (in server)
+
+#[cfg(feature = "http-config")]
+#[tokio::test]
+async fn test_server_device_config_url_with_cache() {
+  use buttplug::util::device_configuration::DEVICE_CONFIGURATION_JSON;
+  use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::TcpListener,
+  };
+
+  let listener = TcpListener::bind("127.0.0.1:0")
+    .await
+    .expect("Test, assuming infallible.");
+  let url = format!(
+    "http://{}/buttplug-device-config.json",
+    listener.local_addr().expect("Test, assuming infallible.")
+  );
+  async_manager::spawn(async move {
+    let (mut socket, _) = listener.accept().await.expect("Test, assuming infallible.");
+    let mut request = [0u8; 1024];
+    let _ = socket.read(&mut request).await;
+    let response = format!(
+      "HTTP/1.1 200 OK\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+      DEVICE_CONFIGURATION_JSON.len(),
+      DEVICE_CONFIGURATION_JSON
+    );
+    socket
+      .write_all(response.as_bytes())
+      .await
+      .expect("Test, assuming infallible.");
+  });
+
+  let cache_path = std::env::temp_dir().join(format!(
+    "buttplug-device-config-cache-{}.json",
+    std::process::id()
+  ));
+  let _ = std::fs::remove_file(&cache_path);
+  let mut builder = ButtplugServerBuilder::default();
+  builder.device_config_cache_path(cache_path.clone());
+  builder
+    .with_device_config_url(&url)
+    .await
+    .expect("Test, assuming infallible.");
+  assert_eq!(
+    std::fs::read_to_string(&cache_path).expect("Test, assuming infallible."),
+    DEVICE_CONFIGURATION_JSON
+  );
+
+  // The listener only answers once, so this falls back to the cached copy.
+  let mut builder = ButtplugServerBuilder::default();
+  builder.device_config_cache_path(cache_path.clone());
+  builder
+    .with_device_config_url(&url)
+    .await
+    .expect("Test, assuming infallible.");
+  assert!(builder.finish().is_ok());
+
+  // Without a cache to fall back to, the download error is returned.
+  assert!(ButtplugServerBuilder::default()
+    .with_device_config_url(&url)
+    .await
+    .is_err());
+  let _ = std::fs::remove_file(&cache_path);
+}
+
+#[test]
+fn test_server_add_comm_manager_while_scanning() {
+  async_manager::block_on(async {
+    let server = ButtplugServer::default();
+    let recv = server.event_stream();
+    pin_mut!(recv);
+    let msg = message::RequestServerInfo::new("Test Client", BUTTPLUG_CURRENT_MESSAGE_SPEC_VERSION);
+    assert!(server.parse_message(msg.into()).await.is_ok());
+    assert!(server
+      .parse_message(message::StartScanning::default().into())
+      .await
+      .is_ok());
+
+    let mut builder = TestDeviceCommunicationManagerBuilder::default();
+    let _device = builder.add_test_device(&TestDeviceIdentifier::new("Massage Demo", None));
+    server
+      .add_comm_manager(builder)
+      .await
+      .expect("First comm manager of this type");
+    loop {
+      let msg = recv.next().await.expect("Event stream shouldn't end");
+      if let ButtplugServerMessage::DeviceAdded(_) = msg {
+        break;
+      }
+    }
+
+    assert!(matches!(
+      server
+        .add_comm_manager(TestDeviceCommunicationManagerBuilder::default())
+        .await,
+      Err(ButtplugServerError::DeviceCommunicationManagerTypeAlreadyAdded(_))
+    ));
+  });
+}
+
+#[test]
+fn test_server_actuator_shortcuts() {
+  async_manager::block_on(async {
+    let (server, mut device) = start_test_server_with_connected_device(
+      &mut ButtplugServerBuilder::default(),
+      "PROSTATE VIBE",
+    )
+    .await;
+    let device_index = 0;
+    server
+      .vibrate(device_index, 0.5)
+      .await
+      .expect("Test, assuming infallible.");
+    check_test_recv_value(
+      &mut device,
+      HardwareCommand::Write(HardwareWriteCmd::new(
+        Endpoint::Tx,
+        vec![0xF3, 0, 0x40],
+        true,
+      )),
+    );
+    assert!(matches!(
+      server.vibrate(device_index, 1.5).await,
+      Err(ButtplugError::ButtplugMessageError(_))
+    ));
+    assert!(matches!(
+      server.rotate(device_index, 0.5, true).await,
+      Err(ButtplugError::ButtplugDeviceError(
+        ButtplugDeviceError::MessageNotSupported(_)
+      ))
+    ));
+    assert!(matches!(
+      server.linear(device_index + 1, 500, 0.5).await,
+      Err(ButtplugError::ButtplugDeviceError(
+        ButtplugDeviceError::DeviceNotAvailable(_)
+      ))
+    ));
+  });
+}
+
+#[test]
+fn test_server_bulk_vibrate() {
+  async_manager::block_on(async {
+    let (server, mut device) = start_test_server_with_connected_device(
+      &mut ButtplugServerBuilder::default(),
+      "PROSTATE VIBE",
+    )
+    .await;
+    let device_index = 0;
+    let results = server
+      .bulk_vibrate(&[(device_index, 0.5), (device_index + 1, 0.5)])
+      .await;
+    assert_eq!(results.len(), 2);
+    assert!(results[0].is_ok());
+    assert!(results[1].is_err());
+    check_test_recv_value(
+      &mut device,
+      HardwareCommand::Write(HardwareWriteCmd::new(
+        Endpoint::Tx,
+        vec![0xF3, 0, 0x40],
+        true,
+      )),
+    );
+  });
+}
+
+#[test]
+fn test_server_start_pairing() {
+  async_manager::block_on(async {
+    let (server, _device) = test_server_with_device("Massage Demo", false).await;
+    let failed: Vec<PairingEvent> = server.start_pairing("not-a-protocol").collect().await;
+    assert!(matches!(failed[..], [PairingEvent::Failure(_)]));
+
+    let events: Vec<PairingEvent> = server.start_pairing("aneros").collect().await;
+    assert!(matches!(events[0], PairingEvent::Waiting));
+    if let Some(PairingEvent::Success(info)) = events.last() {
+      assert_eq!(info.name(), "Aneros Vivi");
+    } else {
+      panic!("Pairing should end with success, got {:?}", events);
+    }
+  });
+}
+
+#[test]
+fn test_server_event_stream_since() {
+  async_manager::block_on(async {
+    let mut builder = TestDeviceCommunicationManagerBuilder::default();
+    let _device = builder.add_test_device(&TestDeviceIdentifier::new("Massage Demo", None));
+    let server = ButtplugServerBuilder::default()
+      .comm_manager(builder)
+      .event_buffer_size(10)
+      .finish()
+      .unwrap();
+    let started_at = Instant::now();
+    let msg = message::RequestServerInfo::new("Test Client", BUTTPLUG_CURRENT_MESSAGE_SPEC_VERSION);
+    assert!(server.parse_message(msg.into()).await.is_ok());
+    let recv = server.event_stream();
+    pin_mut!(recv);
+    assert!(server
+      .parse_message(message::StartScanning::default().into())
+      .await
+      .is_ok());
+    while !matches!(
+      recv.next().await,
+      Some(ButtplugServerMessage::DeviceAdded(_))
+    ) {}
+
+    // A subscriber that starts late still sees the device being added.
+    let late_recv = server.event_stream_since(started_at);
+    pin_mut!(late_recv);
+    while !matches!(
+      late_recv.next().await,
+      Some(ButtplugServerMessage::DeviceAdded(_))
+    ) {}
+  });
+}
+
+#[cfg(feature = "testing")]
+#[test]
+fn test_server_simulate_device_event() {
+  async_manager::block_on(async {
+    let server = ButtplugServer::default();
+    let recv = server.event_stream();
+    pin_mut!(recv);
+    server.simulate_device_event(message::DeviceRemoved::new(3).into());
+    server.simulate_device_event(message::ScanningFinished::default().into());
+    assert!(matches!(
+      recv.next().await,
+      Some(ButtplugServerMessage::DeviceRemoved(removed)) if removed.device_index() == 3
+    ));
+    assert!(matches!(
+      recv.next().await,
+      Some(ButtplugServerMessage::ScanningFinished(_))
+    ));
+  });
+}
+
+#[test]
+fn test_server_device_supports_message() {
+  async_manager::block_on(async {
+    let (server, _device) = start_test_server_with_connected_device(
+      &mut ButtplugServerBuilder::default(),
+      "Massage Demo",
+    )
+    .await;
+    let device_index = 0;
+    let vibrate: message::ButtplugClientMessage =
+      message::VibrateCmd::new(device_index, vec![message::VibrateSubcommand::new(0, 0.5)]).into();
+    let rotate: message::ButtplugClientMessage = message::RotateCmd::new(
+      device_index,
+      vec![message::RotationSubcommand::new(0, 0.5, true)],
+    )
+    .into();
+    assert!(server.device_supports_message(device_index, &vibrate));
+    assert!(!server.device_supports_message(device_index, &rotate));
+    assert!(!server.device_supports_message(device_index + 1, &vibrate));
+    assert!(!server.device_supports_message(device_index, &message::Ping::default().into()));
+  });
+}
+
+#[test]
+fn test_server_flush_device_queue() {
+  async_manager::block_on(async {
+    let (server, mut device) = start_test_server_with_connected_device(
+      &mut ButtplugServerBuilder::default(),
+      "Massage Demo",
+    )
+    .await;
+    let device_index = 0;
+    let fut = server.device_manager().parse_message(
+      message::VibrateCmd::new(device_index, vec![message::VibrateSubcommand::new(0, 0.5)]).into(),
+    );
+    assert_eq!(server.flush_device_queue(device_index), 1);
+    assert_eq!(server.flush_device_queue(device_index), 0);
+    assert!(matches!(
+      fut.await,
+      Err(ButtplugError::ButtplugDeviceError(
+        ButtplugDeviceError::DeviceCommandFlushed
+      ))
+    ));
+    assert!(device.receiver.try_recv().is_err());
+    assert_eq!(server.flush_device_queue(device_index + 1), 0);
+
+    // Stops can't be flushed.
+    let fut = server
+      .device_manager()
+      .parse_message(message::StopDeviceCmd::new(device_index).into());
+    assert_eq!(server.flush_device_queue(device_index), 0);
+    assert!(fut.await.is_ok());
+    assert!(device.receiver.try_recv().is_ok());
+  });
+}
+
+#[test]
+fn test_server_device_is_busy() {
+  async_manager::block_on(async {
+    let (server, _device) = test_server_with_device("Massage Demo", false).await;
+    let recv = server.event_stream();
+    pin_mut!(recv);
+    let msg = message::RequestServerInfo::new("Test Client", BUTTPLUG_CURRENT_MESSAGE_SPEC_VERSION);
+    assert!(server.parse_message(msg.into()).await.is_ok());
+    assert!(server
+      .parse_message(message::StartScanning::default().into())
+      .await
+      .is_ok());
+    let mut device_index = 100;
+    while let Some(msg) = recv.next().await {
+      if let ButtplugServerMessage::DeviceAdded(da) = msg {
+        device_index = da.device_index();
+        break;
+      }
+    }
+    assert!(!server.device_is_busy(device_index));
+    let vibrate = || -> message::ButtplugClientMessage {
+      message::VibrateCmd::new(device_index, vec![message::VibrateSubcommand::new(1, 0.5)]).into()
+    };
+    let fut = server.device_manager().parse_message(vibrate());
+    assert!(server.device_is_busy(device_index));
+    assert!(!server.device_is_busy(device_index + 1));
+    assert!(fut.await.is_ok());
+    assert!(!server.device_is_busy(device_index));
+    // Abandoned commands don't leave the device busy.
+    drop(server.device_manager().parse_message(vibrate()));
+    assert!(!server.device_is_busy(device_index));
+  });
+}
+
+#[test]
+fn test_server_auto_start_scanning() {
+  async_manager::block_on(async {
+    let mut builder = TestDeviceCommunicationManagerBuilder::default();
+    let _device = builder.add_test_device(&TestDeviceIdentifier::new("Massage Demo", None));
+    let server = ButtplugServerBuilder::default()
+      .comm_manager(builder)
+      .auto_start_scanning(true)
+      .auto_scan_duration(Duration::from_millis(50))
+      .finish()
+      .expect("Test, assuming infallible.");
+    let recv = server.event_stream();
+    pin_mut!(recv);
+    let msg = message::RequestServerInfo::new("Test Client", BUTTPLUG_CURRENT_MESSAGE_SPEC_VERSION);
+    assert!(server.parse_message(msg.into()).await.is_ok());
+    // No StartScanning sent, the device should still show up.
+    while let Some(msg) = recv.next().await {
+      if matches!(msg, ButtplugServerMessage::DeviceAdded(_)) {
+        break;
+      }
+    }
+    assert_eq!(
+      server.export_config().auto_scan_duration(),
+      &Some(Duration::from_millis(50))
+    );
+  });
+}
+
+#[test]
+fn test_server_auto_scan_left_to_client_scan() {
+  async_manager::block_on(async {
+    let server = ButtplugServerBuilder::default()
+      .comm_manager(DelayDeviceCommunicationManagerBuilder::default())
+      .auto_start_scanning(true)
+      .auto_scan_duration(Duration::from_millis(50))
+      .finish()
+      .expect("Test, assuming infallible.");
+    let recv = server.event_stream();
+    pin_mut!(recv);
+    let rsi = message::RequestServerInfo::new("Test Client", BUTTPLUG_CURRENT_MESSAGE_SPEC_VERSION);
+    assert!(server.parse_message(rsi.clone().into()).await.is_ok());
+    // The client starting its own scan takes over the automatic one, so the timer mustn't stop it.
+    assert!(server
+      .parse_message(message::StartScanning::default().into())
+      .await
+      .is_ok());
+    sleep(Duration::from_millis(150)).await;
+    assert!(server.active_scan_duration().is_some());
+
+    assert!(server.disconnect().await.is_ok());
+    while let Some(msg) = recv.next().await {
+      if matches!(msg, ButtplugServerMessage::ScanningFinished(_)) {
+        break;
+      }
+    }
+    // Reconnecting doesn't start another automatic scan.
+    assert!(server.parse_message(rsi.into()).await.is_ok());
+    sleep(Duration::from_millis(50)).await;
+    assert!(server.active_scan_duration().is_none());
+  });
+}
+
+#[test]
+fn test_server_add_virtual_device() {
+  async_manager::block_on(async {
+    let server = ButtplugServer::default();
+    let recv = server.event_stream();
+    pin_mut!(recv);
+    let vibrator = ServerGenericDeviceMessageAttributes::new(
+      "Vibrator",
+      &RangeInclusive::new(0, 20),
+      ActuatorType::Vibrate,
+    );
+    let capabilities = ServerDeviceMessageAttributesBuilder::default()
+      .scalar_cmd(&[vibrator])
+      .finish();
+    let config = VirtualDeviceConfig::new("Virtual Vibe", capabilities, Duration::from_millis(50));
+    let first_index = server
+      .add_virtual_device(config.clone())
+      .expect("Test, assuming infallible.");
+    let second_index = server
+      .add_virtual_device(config)
+      .expect("Test, assuming infallible.");
+    assert_ne!(first_index, second_index);
+    while let Some(msg) = recv.next().await {
+      if let ButtplugServerMessage::DeviceAdded(da) = msg {
+        assert_eq!(da.device_index(), first_index);
+        assert_eq!(da.device_name(), "Virtual Vibe");
+        break;
+      }
+    }
+    let started_at = Instant::now();
+    assert!(matches!(
+      server
+        .device_manager()
+        .parse_message(
+          message::ScalarCmd::new(
+            first_index,
+            vec![message::ScalarSubcommand::new(
+              0,
+              0.5,
+              ActuatorType::Vibrate
+            )]
+          )
+          .into()
+        )
+        .await,
+      Ok(ButtplugServerMessage::Ok(_))
+    ));
+    assert!(started_at.elapsed() >= Duration::from_millis(50));
+    // Only advertised capabilities are accepted.
+    assert!(server
+      .device_manager()
+      .parse_message(
+        message::RotateCmd::new(
+          first_index,
+          vec![message::RotationSubcommand::new(0, 0.5, true)]
+        )
+        .into()
+      )
+      .await
+      .is_err());
+  });
+}
+
+#[test]
+fn test_server_comm_manager_status() {
+  async_manager::block_on(async {
+    let (server, device) = start_test_server_with_connected_device(
+      &mut ButtplugServerBuilder::default(),
+      "Massage Demo",
+    )
+    .await;
+    let recv = server.event_stream();
+    pin_mut!(recv);
+    let statuses = server
+      .comm_manager_status()
+      .await
+      .expect("Test, assuming infallible.");
+    assert_eq!(statuses.len(), 1);
+    assert!(statuses[0].active());
+    assert!(statuses[0].error().is_none());
+    assert_eq!(statuses[0].device_count(), 1);
+
+    device
+      .sender
+      .send(TestHardwareEvent::Disconnect)
+      .await
+      .expect("Test, assuming infallible.");
+    while let Some(msg) = recv.next().await {
+      if matches!(msg, ButtplugServerMessage::DeviceRemoved(_)) {
+        break;
+      }
+    }
+    let statuses = server
+      .comm_manager_status()
+      .await
+      .expect("Test, assuming infallible.");
+    assert_eq!(statuses[0].device_count(), 0);
+  });
+}
+
+#[test]
+fn test_server_command_conflict_policy() {
+  async_manager::block_on(async {
+    let server = ButtplugServerBuilder::default()
+      .command_conflict_policy(CommandConflictPolicy::MaxIntensity)
+      .finish()
+      .expect("Test, assuming infallible.");
+    assert_eq!(
+      *server.export_config().command_conflict_policy(),
+      CommandConflictPolicy::MaxIntensity
+    );
+    let vibrator = ServerGenericDeviceMessageAttributes::new(
+      "Vibrator",
+      &RangeInclusive::new(0, 20),
+      ActuatorType::Vibrate,
+    );
+    let capabilities = ServerDeviceMessageAttributesBuilder::default()
+      .scalar_cmd(&[vibrator])
+      .finish();
+    let recv = server.event_stream();
+    pin_mut!(recv);
+    let index = server
+      .add_virtual_device(VirtualDeviceConfig::new(
+        "Virtual Vibe",
+        capabilities,
+        Duration::ZERO,
+      ))
+      .expect("Test, assuming infallible.");
+    while let Some(msg) = recv.next().await {
+      if matches!(msg, ButtplugServerMessage::DeviceAdded(_)) {
+        break;
+      }
+    }
+    // With a single client, its own commands always win, and VibrateCmd is resolved like
+    // ScalarCmd.
+    for msg in [
+      message::ScalarCmd::new(
+        index,
+        vec![message::ScalarSubcommand::new(
+          0,
+          0.5,
+          ActuatorType::Vibrate,
+        )],
+      )
+      .into(),
+      message::VibrateCmd::new(index, vec![message::VibrateSubcommand::new(0, 0.25)]).into(),
+      message::StopDeviceCmd::new(index).into(),
+    ] {
+      assert!(matches!(
+        server.device_manager().parse_message(msg).await,
+        Ok(ButtplugServerMessage::Ok(_))
+      ));
+    }
+  });
+}
+
+#[test]
+fn test_server_export_session_log() {
+  async_manager::block_on(async {
+    let server = ButtplugServerBuilder::default()
+      .record_session(true)
+      .finish()
+      .expect("Test, assuming infallible.");
+    let recv = server.event_stream();
+    pin_mut!(recv);
+    server
+      .parse_message(
+        message::RequestServerInfo::new("Test Client", BUTTPLUG_CURRENT_MESSAGE_SPEC_VERSION)
+          .into(),
+      )
+      .await
+      .expect("Test, assuming infallible.");
+    let vibrator = ServerGenericDeviceMessageAttributes::new(
+      "Vibrator",
+      &RangeInclusive::new(0, 20),
+      ActuatorType::Vibrate,
+    );
+    let capabilities = ServerDeviceMessageAttributesBuilder::default()
+      .scalar_cmd(&[vibrator])
+      .finish();
+    let index = server
+      .add_virtual_device(VirtualDeviceConfig::new(
+        "Virtual Vibe",
+        capabilities,
+        Duration::ZERO,
+      ))
+      .expect("Test, assuming infallible.");
+    while let Some(msg) = recv.next().await {
+      if matches!(msg, ButtplugServerMessage::DeviceAdded(_)) {
+        break;
+      }
+    }
+    assert!(server
+      .parse_message(
+        message::ScalarCmd::new(
+          index,
+          vec![message::ScalarSubcommand::new(
+            0,
+            0.5,
+            ActuatorType::Vibrate
+          )]
+        )
+        .into()
+      )
+      .await
+      .is_ok());
+    assert!(server
+      .parse_message(message::StopDeviceCmd::new(index + 1).into())
+      .await
+      .is_err());
+    server
+      .disconnect()
+      .await
+      .expect("Test, assuming infallible.");
+
+    let log = server.export_session_log();
+    let events = log.events();
+    assert!(events
+      .windows(2)
+      .all(|pair| pair[0].timestamp() <= pair[1].timestamp()));
+    assert!(events.iter().any(|event| matches!(
+      event.kind(),
+      SessionEventKind::ClientConnected { client_name } if client_name == "Test Client"
+    )));
+    assert!(events.iter().any(|event| matches!(
+      event.kind(),
+      SessionEventKind::DeviceAdded { device_index, .. } if *device_index == index
+    )));
+    assert!(events.iter().any(|event| matches!(
+      event.kind(),
+      SessionEventKind::Command {
+        message: message::ButtplugClientMessage::ScalarCmd(_),
+        ..
+      }
+    )));
+    assert!(events.iter().any(|event| matches!(
+      event.kind(),
+      SessionEventKind::Error {
+        message: Some(message::ButtplugClientMessage::StopDeviceCmd(_)),
+        ..
+      }
+    )));
+    assert!(events
+      .iter()
+      .any(|event| matches!(event.kind(), SessionEventKind::ClientDisconnected)));
+    assert!(serde_json::to_value(&log).is_ok());
+
+    // The log starts over when the next client connects.
+    server
+      .parse_message(
+        message::RequestServerInfo::new("Next Client", BUTTPLUG_CURRENT_MESSAGE_SPEC_VERSION)
+          .into(),
+      )
+      .await
+      .expect("Test, assuming infallible.");
+    assert!(server
+      .export_session_log()
+      .events()
+      .iter()
+      .all(|event| !matches!(event.kind(), SessionEventKind::DeviceAdded { .. })));
+  });
+}
+
+#[test]
+fn test_server_session_log_is_bounded() {
+  async_manager::block_on(async {
+    let server = ButtplugServerBuilder::default()
+      .record_session(true)
+      .finish()
+      .expect("Test, assuming infallible.");
+    server
+      .parse_message(
+        message::RequestServerInfo::new("Test Client", BUTTPLUG_CURRENT_MESSAGE_SPEC_VERSION)
+          .into(),
+      )
+      .await
+      .expect("Test, assuming infallible.");
+    for _ in 0..MAX_SESSION_LOG_EVENTS {
+      server
+        .parse_message(message::RequestDeviceList::default().into())
+        .await
+        .expect("Test, assuming infallible.");
+    }
+    // The connection event and handshake reply are the oldest, so they're the ones dropped.
+    let log = server.export_session_log();
+    assert_eq!(log.events().len(), MAX_SESSION_LOG_EVENTS);
+    assert_eq!(log.dropped_events(), 2);
+    assert!(log.events().iter().all(|event| matches!(
+      event.kind(),
+      SessionEventKind::Command {
+        message: message::ButtplugClientMessage::RequestDeviceList(_),
+        ..
+      }
+    )));
+  });
+}
+
+#[test]
+fn test_server_on_device_command() {
+  async_manager::block_on(async {
+    let msg = message::RequestServerInfo::new("Test Client", BUTTPLUG_CURRENT_MESSAGE_SPEC_VERSION);
+    let (server, recv) = setup_test_server(msg.into()).await;
+    pin_mut!(recv);
+    let (command_sender, mut command_receiver) = tokio::sync::mpsc::unbounded_channel();
+    server.on_device_command(move |index, msg| {
+      let _ = command_sender.send((index, msg.clone()));
+    });
+    let vibrator = ServerGenericDeviceMessageAttributes::new(
+      "Vibrator",
+      &RangeInclusive::new(0, 20),
+      ActuatorType::Vibrate,
+    );
+    let capabilities = ServerDeviceMessageAttributesBuilder::default()
+      .scalar_cmd(&[vibrator])
+      .finish();
+    let index = server
+      .add_virtual_device(VirtualDeviceConfig::new(
+        "Virtual Vibe",
+        capabilities,
+        Duration::ZERO,
+      ))
+      .expect("Test, assuming infallible.");
+    while let Some(msg) = recv.next().await {
+      if matches!(msg, ButtplugServerMessage::DeviceAdded(_)) {
+        break;
+      }
+    }
+    let vibrate: message::ButtplugClientMessage =
+      message::VibrateCmd::new(index, vec![message::VibrateSubcommand::new(0, 0.5)]).into();
+    assert!(server.parse_message(vibrate.clone()).await.is_ok());
+    assert_eq!(
+      command_receiver.recv().await,
+      Some((index, vibrate.clone()))
+    );
+    // Commands the device doesn't accept, or for devices that don't exist, aren't observed.
+    assert!(server
+      .parse_message(
+        message::RotateCmd::new(index, vec![message::RotationSubcommand::new(0, 0.5, true)]).into()
+      )
+      .await
+      .is_err());
+    assert!(server
+      .parse_message(message::StopDeviceCmd::new(index + 1).into())
+      .await
+      .is_err());
+    // Including ones that only fail once the device looks at the subcommands.
+    assert!(server
+      .parse_message(
+        message::VibrateCmd::new(index, vec![message::VibrateSubcommand::new(5, 0.5)]).into()
+      )
+      .await
+      .is_err());
+    assert!(command_receiver.try_recv().is_err());
+
+    // Callbacks can register more callbacks without deadlocking.
+    let device_manager = server.device_manager();
+    let (nested_sender, mut nested_receiver) = tokio::sync::mpsc::unbounded_channel();
+    server.on_device_command(move |_, _| {
+      let nested_sender = nested_sender.clone();
+      device_manager.on_device_command(move |index, _| {
+        let _ = nested_sender.send(index);
+      });
+    });
+    assert!(server.parse_message(vibrate.clone()).await.is_ok());
+    assert!(server.parse_message(vibrate).await.is_ok());
+    assert_eq!(nested_receiver.recv().await, Some(index));
+  });
+}
+
+#[test]
+fn test_server_scan_advertisements() {
+  async_manager::block_on(async {
+    let mut builder = TestDeviceCommunicationManagerBuilder::default();
+    let _device = builder.add_test_device(&TestDeviceIdentifier::new(
+      "Massage Demo",
+      Some("advertising-device".to_owned()),
+    ));
+    let mut server_builder = ButtplugServerBuilder::default();
+    server_builder.comm_manager(builder);
+    let server = server_builder.finish().expect("Test, assuming infallible.");
+
+    let advertisements = server.scan_advertisements(Duration::from_secs(60));
+    pin_mut!(advertisements);
+    let record = advertisements
+      .next()
+      .await
+      .expect("Test, assuming infallible.");
+    assert_eq!(record.name(), "Massage Demo");
+    assert_eq!(record.address(), "advertising-device");
+    // The device is found, but not connected.
+    sleep(Duration::from_millis(100)).await;
+    assert_eq!(server.scan_results().len(), 1);
+    assert!(server.device_manager().device_indexes().is_empty());
+    // StopScanning ends the stream.
+    server
+      .device_manager()
+      .parse_message(message::StopScanning::default().into())
+      .await
+      .expect("Test, assuming infallible.");
+    assert!(advertisements.next().await.is_none());
+
+    // Scans also end once their duration runs out.
+    let advertisements = server.scan_advertisements(Duration::from_millis(50));
+    pin_mut!(advertisements);
+    assert!(advertisements.next().await.is_none());
+  });
+}
+
+#[test]
+fn test_server_active_scan_duration() {
+  async_manager::block_on(async {
+    let mut server_builder = ButtplugServerBuilder::default();
+    server_builder.comm_manager(DelayDeviceCommunicationManagerBuilder::default());
+    let server = server_builder.finish().unwrap();
+    assert!(server.active_scan_duration().is_none());
+
+    let recv = server.event_stream();
+    pin_mut!(recv);
+    assert!(server
+      .parse_message(
+        message::RequestServerInfo::new("Test Client", BUTTPLUG_CURRENT_MESSAGE_SPEC_VERSION)
+          .into()
+      )
+      .await
+      .is_ok());
+    assert!(server
+      .parse_message(message::StartScanning::default().into())
+      .await
+      .is_ok());
+    sleep(Duration::from_millis(50)).await;
+    let duration = server
+      .active_scan_duration()
+      .expect("Scan should be running");
+    assert!(duration >= Duration::from_millis(50));
+
+    assert!(server
+      .parse_message(message::StopScanning::default().into())
+      .await
+      .is_ok());
+    while let Some(msg) = recv.next().await {
+      if matches!(msg, ButtplugServerMessage::ScanningFinished(_)) {
+        break;
+      }
+    }
+    assert!(server.active_scan_duration().is_none());
+  });
+}
+
+#[test]
+fn test_server_stop_skips_pending_writes() {
+  async_manager::block_on(async {
+    let server = Arc::new(ButtplugServer::default());
+    let recv = server.event_stream();
+    pin_mut!(recv);
+    let vibrator = ServerGenericDeviceMessageAttributes::new(
+      "Vibrator",
+      &RangeInclusive::new(0, 20),
+      ActuatorType::Vibrate,
+    );
+    let capabilities = ServerDeviceMessageAttributesBuilder::default()
+      .scalar_cmd(&[vibrator])
+      .finish();
+    let config = VirtualDeviceConfig::new("Virtual Vibe", capabilities, Duration::from_millis(300));
+    let index = server
+      .add_virtual_device(config)
+      .expect("Test, assuming infallible.");
+    while let Some(msg) = recv.next().await {
+      if matches!(msg, ButtplugServerMessage::DeviceAdded(_)) {
+        break;
+      }
+    }
+    // Start a slow write, and queue another one behind it.
+    for speed in [0.5, 0.75] {
+      let server = server.clone();
+      async_manager::spawn(async move {
+        let _ = server
+          .device_manager()
+          .parse_message(
+            message::ScalarCmd::new(
+              index,
+              vec![message::ScalarSubcommand::new(
+                0,
+                speed,
+                ActuatorType::Vibrate,
+              )],
+            )
+            .into(),
+          )
+          .await;
+      });
+    }
+    sleep(Duration::from_millis(20)).await;
+    // The stop doesn't wait for either write, so it only takes as long as its own.
+    let started_at = Instant::now();
+    assert!(server
+      .device_manager()
+      .parse_message(message::StopDeviceCmd::new(index).into())
+      .await
+      .is_ok());
+    assert!(started_at.elapsed() < Duration::from_millis(450));
+  });
+}
+
+#[test]
+fn test_server_force_stop_all_devices_timeout() {
+  async_manager::block_on(async {
+    let server = ButtplugServer::default();
+    let recv = server.event_stream();
+    pin_mut!(recv);
+    let vibrator = ServerGenericDeviceMessageAttributes::new(
+      "Vibrator",
+      &RangeInclusive::new(0, 20),
+      ActuatorType::Vibrate,
+    );
+    let capabilities = ServerDeviceMessageAttributesBuilder::default()
+      .scalar_cmd(&[vibrator])
+      .finish();
+    server
+      .add_virtual_device(VirtualDeviceConfig::new(
+        "Responsive Vibe",
+        capabilities.clone(),
+        Duration::ZERO,
+      ))
+      .expect("Test, assuming infallible.");
+    let slow_index = server
+      .add_virtual_device(VirtualDeviceConfig::new(
+        "Slow Vibe",
+        capabilities,
+        Duration::from_secs(5),
+      ))
+      .expect("Test, assuming infallible.");
+    let mut added = 0;
+    while let Some(msg) = recv.next().await {
+      if matches!(msg, ButtplugServerMessage::DeviceAdded(_)) {
+        added += 1;
+        if added == 2 {
+          break;
+        }
+      }
+    }
+    let started_at = Instant::now();
+    let result = server
+      .force_stop_all_devices(Duration::from_millis(100))
+      .await;
+    assert!(started_at.elapsed() < Duration::from_secs(1));
+    assert_eq!(
+      result,
+      Err(ButtplugError::ButtplugDeviceError(
+        ButtplugDeviceError::DevicesStopTimedOut(vec![slow_index])
+      ))
+    );
+  });
+}
@@ -9,14 +9,42 @@ mod util;
 use buttplug::{
   core::{
     errors::{ButtplugDeviceError, ButtplugError},
-    message::{self, ButtplugServerMessage, Endpoint, BUTTPLUG_CURRENT_MESSAGE_SPEC_VERSION},
+    message::{
+      self,
+      ActuatorType,
+      ButtplugServerMessage,
+      CommandPriority,
+      Endpoint,
+      ScalarSubcommand,
+      BUTTPLUG_CURRENT_MESSAGE_SPEC_VERSION,
+    },
   },
-  util::async_manager,
+  server::{
+    device::{
+      hardware::{HardwareCommand, HardwareWriteCmd},
+      ServerDeviceInfo,
+      MAX_RAW_STREAM_SIZE,
+    },
+    ButtplugServer,
+    ButtplugServerBuilder,
+    DeviceEventHandler,
+  },
+  util::{async_manager, stream::recv_now},
 };
 use futures::{pin_mut, StreamExt};
-use std::matches;
+use std::{collections::HashMap, matches, sync::Arc, time::Duration};
 pub use util::test_device_manager::TestDeviceCommunicationManagerBuilder;
-use util::test_server_with_device;
+use util::{
+  start_test_server_with_connected_device,
+  test_device_manager::{
+    check_test_recv_value,
+    TestDeviceIdentifier,
+    TestHardwareEvent,
+    TestHardwareNotification,
+  },
+  test_server_with_device,
+  DelayDeviceCommunicationManagerBuilder,
+};
 
 // Test devices that have protocols that support movements not all devices do.
 // For instance, the Onyx+ is part of a protocol that supports vibration, but
@@ -259,3 +287,1205 @@ fn test_repeated_address_additions() {
   });
 }
 */
+
+async fn send_vibrate(server: &ButtplugServer, scalars: &[(u32, f64)]) {
+  let scalars = scalars
+    .iter()
+    .map(|(index, scalar)| ScalarSubcommand::new(*index, *scalar, ActuatorType::Vibrate))
+    .collect();
+  server
+    .parse_message(message::ScalarCmd::new(0, scalars).into())
+    .await
+    .expect("Debounced commands should still succeed.");
+}
+
+fn vibrate_write(data: Vec<u8>) -> HardwareCommand {
+  HardwareCommand::Write(HardwareWriteCmd::new(Endpoint::Tx, data, false))
+}
+
+#[test]
+fn test_server_device_command_debounce() {
+  async_manager::block_on(async {
+    let (server, mut device) = start_test_server_with_connected_device(
+      ButtplugServerBuilder::default().device_command_debounce(Duration::from_secs(60)),
+      "Massage Demo",
+    )
+    .await;
+    send_vibrate(&server, &[(0, 0.5)]).await;
+    check_test_recv_value(&mut device, vibrate_write(vec![0xF1, 64]));
+    // The first actuator is still inside its debounce window, the second hasn't been used yet.
+    send_vibrate(&server, &[(0, 1.0), (1, 0.5)]).await;
+    check_test_recv_value(&mut device, vibrate_write(vec![0xF2, 64]));
+    assert!(recv_now(&mut device.receiver).is_none());
+    // Stop commands are never debounced.
+    server
+      .parse_message(message::StopDeviceCmd::new(0).into())
+      .await
+      .expect("Test, assuming infallible.");
+    check_test_recv_value(&mut device, vibrate_write(vec![0xF1, 0]));
+    check_test_recv_value(&mut device, vibrate_write(vec![0xF2, 0]));
+  });
+}
+
+#[test]
+fn test_server_device_command_debounce_trailing_value() {
+  async_manager::block_on(async {
+    let (server, mut device) = start_test_server_with_connected_device(
+      ButtplugServerBuilder::default().device_command_debounce(Duration::from_millis(200)),
+      "Massage Demo",
+    )
+    .await;
+    send_vibrate(&server, &[(0, 0.5)]).await;
+    check_test_recv_value(&mut device, vibrate_write(vec![0xF1, 64]));
+    // Values inside the window are held back, and only the last one is sent once it closes.
+    send_vibrate(&server, &[(0, 0.25)]).await;
+    send_vibrate(&server, &[(0, 1.0)]).await;
+    assert!(recv_now(&mut device.receiver).is_none());
+    tokio::time::sleep(Duration::from_millis(300)).await;
+    check_test_recv_value(&mut device, vibrate_write(vec![0xF1, 127]));
+    assert!(recv_now(&mut device.receiver).is_none());
+    // Stop values always go through, and drop anything held back.
+    send_vibrate(&server, &[(0, 0.5)]).await;
+    send_vibrate(&server, &[(0, 0.0)]).await;
+    check_test_recv_value(&mut device, vibrate_write(vec![0xF1, 0]));
+    tokio::time::sleep(Duration::from_millis(300)).await;
+    assert!(recv_now(&mut device.receiver).is_none());
+  });
+}
+
+#[test]
+fn test_server_device_command_debounce_user_config() {
+  let user_config_json = r#"
+  {
+    "version": {
+      "major": 2,
+      "minor": 999
+    },
+    "user-configs": {
+      "devices": [
+        {
+          "identifier": {
+            "address": "debounce-test-addr",
+            "protocol": "svakom",
+            "identifier": "Aogu SCB"
+          },
+          "config": {
+            "command-debounce-ms": 60000
+          }
+        }
+      ]
+    }
+  }
+  "#;
+  async_manager::block_on(async {
+    let (server, mut device) = start_test_server_with_connected_device(
+      ButtplugServerBuilder::default()
+        .user_device_configuration_json(Some(user_config_json.to_owned())),
+      "Aogu SCB",
+    )
+    .await;
+    send_vibrate(&server, &[(0, 0.5)]).await;
+    assert!(recv_now(&mut device.receiver).is_some());
+    send_vibrate(&server, &[(0, 1.0)]).await;
+    assert!(recv_now(&mut device.receiver).is_none());
+  });
+}
+
+#[test]
+fn test_server_query_device() {
+  async_manager::block_on(async {
+    let (server, _device) = start_test_server_with_connected_device(
+      &mut ButtplugServerBuilder::default(),
+      "Massage Demo",
+    )
+    .await;
+    let updates = server.device_update_stream();
+    pin_mut!(updates);
+    let events = server.event_stream();
+    pin_mut!(events);
+    let device_info = server.query_device(0).await.expect("Device is connected.");
+    assert_eq!(device_info.name(), "Aneros Vivi");
+    assert_eq!(
+      device_info
+        .message_attributes()
+        .scalar_cmd()
+        .as_ref()
+        .expect("Device has scalar attributes")
+        .len(),
+      2
+    );
+    let (index, updated_info) = updates.next().await.expect("Update should be emitted.");
+    assert_eq!(index, 0);
+    assert_eq!(updated_info.identifier(), device_info.identifier());
+    // Clients are told about the new attributes by the device being removed and added again.
+    assert!(matches!(
+      events.next().await,
+      Some(ButtplugServerMessage::DeviceRemoved(removed)) if removed.device_index() == 0
+    ));
+    let Some(ButtplugServerMessage::DeviceAdded(added)) = events.next().await else {
+      panic!("Device should be added again.");
+    };
+    assert_eq!(added.device_index(), 0);
+    assert_eq!(added.device_name(), "Aneros Vivi");
+    // The device should still be usable after being requeried.
+    send_vibrate(&server, &[(0, 0.5)]).await;
+    assert!(matches!(
+      server.query_device(1).await,
+      Err(ButtplugError::ButtplugDeviceError(
+        ButtplugDeviceError::DeviceNotAvailable(1)
+      ))
+    ));
+  });
+}
+
+#[test]
+fn test_server_raw_stream_cmd() {
+  async_manager::block_on(async {
+    let (server, mut device) = start_test_server_with_connected_device(
+      ButtplugServerBuilder::default().allow_raw_messages(),
+      "Massage Demo",
+    )
+    .await;
+    let chunks: [&[u8]; 3] = [&[0x01, 0x02], &[0x03], &[0x04, 0x05]];
+    for (chunk_index, chunk) in chunks.iter().enumerate() {
+      server
+        .parse_message(
+          message::RawStreamCmd::new(
+            0,
+            Endpoint::Tx,
+            chunk_index as u32,
+            chunk_index == chunks.len() - 1,
+            chunk,
+          )
+          .into(),
+        )
+        .await
+        .expect("Test, assuming infallible.");
+      if chunk_index != chunks.len() - 1 {
+        // Nothing is written until the last chunk arrives.
+        assert!(recv_now(&mut device.receiver).is_none());
+      }
+    }
+    check_test_recv_value(
+      &mut device,
+      HardwareCommand::Write(HardwareWriteCmd::new(
+        Endpoint::Tx,
+        vec![0x01, 0x02, 0x03, 0x04, 0x05],
+        true,
+      )),
+    );
+
+    // Skipping a chunk cancels the transfer.
+    server
+      .parse_message(message::RawStreamCmd::new(0, Endpoint::Tx, 0, false, &[0x01]).into())
+      .await
+      .expect("Test, assuming infallible.");
+    let err = server
+      .parse_message(message::RawStreamCmd::new(0, Endpoint::Tx, 2, true, &[0x03]).into())
+      .await
+      .expect_err("Out of order chunk should fail");
+    assert!(matches!(
+      err.original_error(),
+      ButtplugError::ButtplugDeviceError(ButtplugDeviceError::DeviceRawStreamChunkOutOfOrder(1, 2))
+    ));
+    assert!(server
+      .parse_message(message::RawStreamCmd::new(0, Endpoint::Tx, 1, true, &[0x02]).into())
+      .await
+      .is_err());
+    assert!(recv_now(&mut device.receiver).is_none());
+
+    // Going over the size limit drops the transfer.
+    server
+      .parse_message(
+        message::RawStreamCmd::new(0, Endpoint::Tx, 0, false, &vec![0; MAX_RAW_STREAM_SIZE]).into(),
+      )
+      .await
+      .expect("Test, assuming infallible.");
+    let err = server
+      .parse_message(message::RawStreamCmd::new(0, Endpoint::Tx, 1, true, &[0x01]).into())
+      .await
+      .expect_err("Oversized transfer should fail");
+    assert!(matches!(
+      err.original_error(),
+      ButtplugError::ButtplugDeviceError(ButtplugDeviceError::DeviceRawStreamTooLarge(
+        MAX_RAW_STREAM_SIZE
+      ))
+    ));
+    assert!(server
+      .parse_message(message::RawStreamCmd::new(0, Endpoint::Tx, 2, true, &[0x02]).into())
+      .await
+      .is_err());
+    assert!(recv_now(&mut device.receiver).is_none());
+  });
+}
+
+#[test]
+fn test_server_command_statistics() {
+  async_manager::block_on(async {
+    let (server, device) = start_test_server_with_connected_device(
+      ButtplugServerBuilder::default().track_command_statistics(true),
+      "Massage Demo",
+    )
+    .await;
+    assert_eq!(server.command_statistics(0).total_commands(), 0);
+    send_vibrate(&server, &[(0, 0.5), (1, 1.0)]).await;
+    send_vibrate(&server, &[(0, 0.0)]).await;
+    server
+      .parse_message(message::StopDeviceCmd::new(0).into())
+      .await
+      .expect("Test, assuming infallible.");
+    let stats = server.command_statistics(0);
+    assert_eq!(stats.total_commands(), 3);
+    assert_eq!(stats.commands_by_type().get("ScalarCmd"), Some(&2));
+    assert_eq!(stats.commands_by_type().get("StopDeviceCmd"), Some(&1));
+    assert!((stats.average_intensity() - 0.5).abs() < f64::EPSILON);
+    assert!(stats.last_command_at().is_some());
+
+    // Statistics are dropped when the device disconnects.
+    let recv = server.event_stream();
+    pin_mut!(recv);
+    device
+      .sender
+      .send(TestHardwareEvent::Disconnect)
+      .await
+      .expect("Test, assuming infallible.");
+    while let Some(msg) = recv.next().await {
+      if let ButtplugServerMessage::DeviceRemoved(_) = msg {
+        break;
+      }
+    }
+    assert_eq!(server.command_statistics(0).total_commands(), 0);
+  });
+}
+
+#[test]
+fn test_server_clear_command_history() {
+  async_manager::block_on(async {
+    let (server, _device) = start_test_server_with_connected_device(
+      ButtplugServerBuilder::default()
+        .track_command_statistics(true)
+        .clear_history_on_disconnect(true),
+      "Massage Demo",
+    )
+    .await;
+    send_vibrate(&server, &[(0, 0.5)]).await;
+    assert_eq!(server.command_statistics(0).total_commands(), 1);
+    assert!(server.device_manager().device_average_latency(0).is_some());
+    server.clear_command_history(0);
+    assert_eq!(server.command_statistics(0).total_commands(), 0);
+    assert!(server.device_manager().device_average_latency(0).is_none());
+
+    // Disconnecting the client clears everything.
+    send_vibrate(&server, &[(0, 0.5)]).await;
+    assert_eq!(server.command_statistics(0).total_commands(), 1);
+    server
+      .disconnect()
+      .await
+      .expect("Test, assuming infallible.");
+    assert_eq!(server.command_statistics(0).total_commands(), 0);
+  });
+}
+
+#[test]
+fn test_server_device_callbacks() {
+  async_manager::block_on(async {
+    let mut builder = TestDeviceCommunicationManagerBuilder::default();
+    let device = builder.add_test_device(&TestDeviceIdentifier::new(
+      "Massage Demo",
+      Some("callback-test-addr".to_owned()),
+    ));
+    let server = ButtplugServerBuilder::default()
+      .comm_manager(builder)
+      .finish()
+      .unwrap();
+    let (added_sender, mut added_receiver) = tokio::sync::mpsc::unbounded_channel();
+    server.on_device_added(move |info| {
+      let _ = added_sender.send(info.name().clone());
+    });
+    let (removed_sender, mut removed_receiver) = tokio::sync::mpsc::unbounded_channel();
+    server.on_device_removed(move |index| {
+      let _ = removed_sender.send(index);
+    });
+    server
+      .parse_message(
+        message::RequestServerInfo::new("Test Client", BUTTPLUG_CURRENT_MESSAGE_SPEC_VERSION)
+          .into(),
+      )
+      .await
+      .expect("Test, assuming infallible.");
+    server
+      .parse_message(message::StartScanning::default().into())
+      .await
+      .expect("Test, assuming infallible.");
+    assert_eq!(added_receiver.recv().await, Some("Aneros Vivi".to_owned()));
+    device
+      .sender
+      .send(TestHardwareEvent::Disconnect)
+      .await
+      .expect("Test, assuming infallible.");
+    assert_eq!(removed_receiver.recv().await, Some(0));
+  });
+}
+
+#[derive(Debug, PartialEq)]
+enum HandledDeviceEvent {
+  Added(String),
+  Removed(u32),
+  Error(u32),
+}
+
+struct TestDeviceEventHandler(tokio::sync::mpsc::UnboundedSender<HandledDeviceEvent>);
+
+impl DeviceEventHandler for TestDeviceEventHandler {
+  fn on_added(&self, info: ServerDeviceInfo) {
+    let _ = self.0.send(HandledDeviceEvent::Added(info.name().clone()));
+  }
+
+  fn on_removed(&self, index: u32) {
+    let _ = self.0.send(HandledDeviceEvent::Removed(index));
+  }
+
+  fn on_error(&self, index: u32, _err: ButtplugError) {
+    let _ = self.0.send(HandledDeviceEvent::Error(index));
+  }
+}
+
+#[test]
+fn test_server_device_event_handler() {
+  async_manager::block_on(async {
+    let mut builder = TestDeviceCommunicationManagerBuilder::default();
+    let device = builder.add_test_device(&TestDeviceIdentifier::new(
+      "Massage Demo",
+      Some("handler-test-addr".to_owned()),
+    ));
+    let server = ButtplugServerBuilder::default()
+      .comm_manager(builder)
+      .finish()
+      .unwrap();
+    // Every registered handler gets every event.
+    let (first_sender, mut first_receiver) = tokio::sync::mpsc::unbounded_channel();
+    server.register_device_event_handler(Arc::new(TestDeviceEventHandler(first_sender)));
+    let (second_sender, mut second_receiver) = tokio::sync::mpsc::unbounded_channel();
+    server.register_device_event_handler(Arc::new(TestDeviceEventHandler(second_sender)));
+    server
+      .parse_message(
+        message::RequestServerInfo::new("Test Client", BUTTPLUG_CURRENT_MESSAGE_SPEC_VERSION)
+          .into(),
+      )
+      .await
+      .expect("Test, assuming infallible.");
+    server
+      .parse_message(message::StartScanning::default().into())
+      .await
+      .expect("Test, assuming infallible.");
+    for receiver in [&mut first_receiver, &mut second_receiver] {
+      assert_eq!(
+        receiver.recv().await,
+        Some(HandledDeviceEvent::Added("Aneros Vivi".to_owned()))
+      );
+    }
+    assert!(server
+      .parse_message(
+        message::ScalarCmd::new(
+          0,
+          vec![ScalarSubcommand::new(5, 0.5, ActuatorType::Vibrate)]
+        )
+        .into()
+      )
+      .await
+      .is_err());
+    for receiver in [&mut first_receiver, &mut second_receiver] {
+      assert_eq!(receiver.recv().await, Some(HandledDeviceEvent::Error(0)));
+    }
+    device
+      .sender
+      .send(TestHardwareEvent::Disconnect)
+      .await
+      .expect("Test, assuming infallible.");
+    for receiver in [&mut first_receiver, &mut second_receiver] {
+      assert_eq!(receiver.recv().await, Some(HandledDeviceEvent::Removed(0)));
+    }
+  });
+}
+
+#[test]
+fn test_server_device_reconnect_failed() {
+  async_manager::block_on(async {
+    let (server, device) = start_test_server_with_connected_device(
+      ButtplugServerBuilder::default()
+        .device_reconnect_delay(Duration::from_millis(10))
+        .device_max_reconnect_attempts(2),
+      "Massage Demo",
+    )
+    .await;
+    let reconnect_failed = server.device_reconnect_failed_stream();
+    pin_mut!(reconnect_failed);
+    device
+      .sender
+      .send(TestHardwareEvent::Disconnect)
+      .await
+      .expect("Test, assuming infallible.");
+    // The test comm manager only hands out its devices once, so rescanning never finds it again.
+    assert_eq!(reconnect_failed.next().await, Some(0));
+  });
+}
+
+#[test]
+fn test_server_reconnect_all_devices() {
+  async_manager::block_on(async {
+    let (server, device) = start_test_server_with_connected_device(
+      ButtplugServerBuilder::default()
+        .device_reconnect_delay(Duration::from_millis(10))
+        .device_max_reconnect_attempts(2),
+      "Massage Demo",
+    )
+    .await;
+    assert!(server.reconnect_all_devices().await.is_empty());
+    let recv = server.event_stream();
+    pin_mut!(recv);
+    device
+      .sender
+      .send(TestHardwareEvent::Disconnect)
+      .await
+      .expect("Test, assuming infallible.");
+    while let Some(msg) = recv.next().await {
+      if let ButtplugServerMessage::DeviceRemoved(removed) = msg {
+        assert_eq!(removed.device_index(), 0);
+        break;
+      }
+    }
+    // The device is never found again, so every attempt fails.
+    let results = server.reconnect_all_devices().await;
+    assert_eq!(results.len(), 1);
+    assert_eq!(results[0].0, 0);
+    assert!(matches!(
+      results[0].1,
+      Err(ButtplugError::ButtplugDeviceError(
+        ButtplugDeviceError::DeviceConnectionError(_)
+      ))
+    ));
+  });
+}
+
+#[test]
+fn test_server_reset() {
+  async_manager::block_on(async {
+    let (server, _device) = start_test_server_with_connected_device(
+      ButtplugServerBuilder::default()
+        .track_command_statistics(true)
+        .device_reconnect_delay(Duration::from_millis(10)),
+      "Massage Demo",
+    )
+    .await;
+    send_vibrate(&server, &[(0, 0.5)]).await;
+    assert_eq!(server.command_statistics(0).total_commands(), 1);
+    let recv = server.event_stream();
+    pin_mut!(recv);
+    server.reset().await.expect("Test, assuming infallible.");
+    assert!(!server.connected());
+    assert!(server.device_manager().device_info(0).is_none());
+    assert_eq!(server.command_statistics(0).total_commands(), 0);
+    assert!(server.scan_results().is_empty());
+    while let Some(msg) = recv.next().await {
+      if let ButtplugServerMessage::DeviceRemoved(removed) = msg {
+        assert_eq!(removed.device_index(), 0);
+        break;
+      }
+    }
+    // The server can be handed to a new client straight away.
+    server
+      .parse_message(
+        message::RequestServerInfo::new("Test Client", BUTTPLUG_CURRENT_MESSAGE_SPEC_VERSION)
+          .into(),
+      )
+      .await
+      .expect("Test, assuming infallible.");
+    assert!(server.connected());
+  });
+}
+
+#[test]
+fn test_server_device_last_seen() {
+  async_manager::block_on(async {
+    let (server, _device) = start_test_server_with_connected_device(
+      &mut ButtplugServerBuilder::default(),
+      "Massage Demo",
+    )
+    .await;
+    assert!(server.device_last_seen(1).is_none());
+    let connected_at = server
+      .device_last_seen(0)
+      .expect("Test, assuming infallible.");
+    tokio::time::sleep(Duration::from_millis(10)).await;
+    send_vibrate(&server, &[(0, 0.5)]).await;
+    assert!(
+      server
+        .device_last_seen(0)
+        .expect("Test, assuming infallible.")
+        > connected_at
+    );
+  });
+}
+
+#[test]
+fn test_server_device_average_latency() {
+  async_manager::block_on(async {
+    let (server, mut device) = start_test_server_with_connected_device(
+      ButtplugServerBuilder::default().device_command_debounce(Duration::from_millis(200)),
+      "Massage Demo",
+    )
+    .await;
+    assert!(server.device_average_latency(0).is_none());
+    send_vibrate(&server, &[(0, 0.5)]).await;
+    check_test_recv_value(&mut device, vibrate_write(vec![0xF1, 64]));
+    // Held back by debouncing, then written once the window closes. Only the write is timed, so
+    // the time spent held back doesn't count.
+    send_vibrate(&server, &[(0, 0.25)]).await;
+    tokio::time::sleep(Duration::from_millis(300)).await;
+    check_test_recv_value(&mut device, vibrate_write(vec![0xF1, 32]));
+    let latency = server
+      .device_average_latency(0)
+      .expect("Test, assuming infallible.");
+    assert!(latency < Duration::from_millis(100));
+    assert!(server.device_average_latency(1).is_none());
+  });
+}
+
+#[test]
+fn test_server_max_actuator_value() {
+  async_manager::block_on(async {
+    let (server, _device) = start_test_server_with_connected_device(
+      &mut ButtplugServerBuilder::default(),
+      "Massage Demo",
+    )
+    .await;
+    assert_eq!(server.max_actuator_value(0, 0), Some(1.0));
+    assert!(server.max_actuator_value(0, 100).is_none());
+    assert!(server.max_actuator_value(1, 0).is_none());
+  });
+}
+
+#[test]
+fn test_server_device_protocol_version() {
+  async_manager::block_on(async {
+    let (server, _device) = start_test_server_with_connected_device(
+      &mut ButtplugServerBuilder::default(),
+      "Massage Demo",
+    )
+    .await;
+    assert_eq!(
+      server.device_protocol_version(0),
+      Some("aneros/1.0".to_owned())
+    );
+    assert_eq!(
+      server
+        .device_manager()
+        .device_info(0)
+        .expect("Test, assuming infallible.")
+        .protocol_version(),
+      "aneros/1.0"
+    );
+    assert!(server.device_protocol_version(1).is_none());
+  });
+}
+
+#[test]
+fn test_server_on_scan_complete() {
+  async_manager::block_on(async {
+    let mut builder = TestDeviceCommunicationManagerBuilder::default();
+    let _device = builder.add_test_device(&TestDeviceIdentifier::new("Massage Demo", None));
+    // The test comm manager finishes scanning as soon as it's found the device, which can be
+    // before the device is done connecting, so hold the scan open until it's connected.
+    let server = ButtplugServerBuilder::default()
+      .comm_manager(builder)
+      .comm_manager(DelayDeviceCommunicationManagerBuilder::default())
+      .finish()
+      .expect("Test, assuming infallible.");
+    let recv = server.event_stream();
+    pin_mut!(recv);
+    let (sender, receiver) = tokio::sync::oneshot::channel();
+    server.on_scan_complete(move |devices| {
+      let _ = sender.send(devices);
+    });
+    while let Some(msg) = recv.next().await {
+      if matches!(msg, ButtplugServerMessage::DeviceAdded(_)) {
+        break;
+      }
+    }
+    server
+      .device_manager()
+      .parse_message(message::StopScanning::default().into())
+      .await
+      .expect("Test, assuming infallible.");
+    let devices = receiver.await.expect("Test, assuming infallible.");
+    assert_eq!(devices.len(), 1);
+    assert_eq!(devices[0].name(), "Aneros Vivi");
+  });
+}
+
+#[test]
+fn test_server_on_scan_complete_dropped_on_shutdown() {
+  async_manager::block_on(async {
+    let server = ButtplugServerBuilder::default()
+      .finish()
+      .expect("Test, assuming infallible.");
+    server.shutdown().await.expect("Test, assuming infallible.");
+    let (sender, receiver) = tokio::sync::oneshot::channel::<Vec<ServerDeviceInfo>>();
+    server.on_scan_complete(move |devices| {
+      let _ = sender.send(devices);
+    });
+    assert!(receiver.await.is_err());
+  });
+}
+
+#[test]
+fn test_server_device_uptime() {
+  async_manager::block_on(async {
+    let (server, _device) = start_test_server_with_connected_device(
+      &mut ButtplugServerBuilder::default(),
+      "Massage Demo",
+    )
+    .await;
+    let uptime = server.device_uptime(0).expect("Test, assuming infallible.");
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    assert!(
+      server.device_uptime(0).expect("Test, assuming infallible.")
+        >= uptime + Duration::from_millis(50)
+    );
+    assert!(server.device_uptime(1).is_none());
+    let info = server
+      .device_manager()
+      .device_info(0)
+      .expect("Test, assuming infallible.");
+    assert_eq!(info.uptime_seconds(), 0);
+  });
+}
+
+#[test]
+fn test_server_device_name() {
+  async_manager::block_on(async {
+    let (server, _device) = start_test_server_with_connected_device(
+      &mut ButtplugServerBuilder::default(),
+      "Massage Demo",
+    )
+    .await;
+    assert_eq!(server.device_name(0), Some("Aneros Vivi".to_owned()));
+    assert!(server.device_name(1).is_none());
+    assert_eq!(server.device_display_name_or_fallback(0), "Aneros Vivi");
+    assert_eq!(server.device_display_name_or_fallback(1), "Unknown Device");
+  });
+}
+
+#[test]
+fn test_server_battery_level_cache() {
+  async_manager::block_on(async {
+    let (server, device) = start_test_server_with_connected_device(
+      ButtplugServerBuilder::default().battery_cache_ttl(Duration::from_secs(60)),
+      "Flamingo",
+    )
+    .await;
+    assert!(server.device_battery_level_cache(0).is_none());
+    device
+      .sender
+      .send(TestHardwareEvent::Reads(vec![
+        TestHardwareNotification::new(Endpoint::RxBLEBattery, vec![50]),
+      ]))
+      .await
+      .expect("Test, assuming infallible.");
+    assert_eq!(server.battery_level(0).await.unwrap(), 0.5);
+    let (cached_level, read_at) = server
+      .device_battery_level_cache(0)
+      .expect("Test, assuming infallible.");
+    assert_eq!(cached_level, 0.5);
+    // Answered from the cache, there's no reading queued on the device for a second query.
+    assert_eq!(server.battery_level(0).await.unwrap(), 0.5);
+    assert_eq!(server.device_battery_level_cache(0), Some((0.5, read_at)));
+    assert!(server.battery_level(1).await.is_err());
+  });
+}
+
+#[test]
+fn test_server_device_actuator_counts() {
+  async_manager::block_on(async {
+    let (server, _device) = start_test_server_with_connected_device(
+      &mut ButtplugServerBuilder::default(),
+      "Massage Demo",
+    )
+    .await;
+    assert_eq!(server.device_actuator_count(0), Some(2));
+    assert_eq!(server.rotation_actuator_count(0), Some(0));
+    assert_eq!(server.linear_actuator_count(0), Some(0));
+    assert_eq!(server.sensor_count(0), Some(0));
+    assert!(server.device_actuator_count(1).is_none());
+    assert!(server.rotation_actuator_count(1).is_none());
+    assert!(server.linear_actuator_count(1).is_none());
+    assert!(server.sensor_count(1).is_none());
+  });
+}
+
+#[test]
+fn test_server_device_tags() {
+  async_manager::block_on(async {
+    let (server, _device) = start_test_server_with_connected_device(
+      &mut ButtplugServerBuilder::default(),
+      "Massage Demo",
+    )
+    .await;
+    let updates = server.device_update_stream();
+    pin_mut!(updates);
+    assert_eq!(server.device_tags(0), Some(HashMap::new()));
+    assert!(server.device_tags(1).is_none());
+    assert!(server
+      .set_device_tag(1, "owner".to_owned(), "alice".to_owned())
+      .is_err());
+
+    server
+      .set_device_tag(0, "owner".to_owned(), "alice".to_owned())
+      .expect("Test, assuming infallible.");
+    server
+      .set_device_tag(0, "owner".to_owned(), "bob".to_owned())
+      .expect("Test, assuming infallible.");
+    let expected = HashMap::from([("owner".to_owned(), "bob".to_owned())]);
+    assert_eq!(server.device_tags(0), Some(expected.clone()));
+    assert_eq!(
+      server
+        .device_manager()
+        .device_info(0)
+        .expect("Test, assuming infallible.")
+        .tags(),
+      &expected
+    );
+    let (index, info) = updates.next().await.expect("Test, assuming infallible.");
+    assert_eq!(index, 0);
+    assert_eq!(info.tags().get("owner"), Some(&"alice".to_owned()));
+  });
+}
+
+#[test]
+fn test_server_pause_all_devices() {
+  async_manager::block_on(async {
+    let (server, mut device) = start_test_server_with_connected_device(
+      &mut ButtplugServerBuilder::default(),
+      "Massage Demo",
+    )
+    .await;
+    assert!(server.pause_all_devices().await.device_indexes().is_empty());
+
+    send_vibrate(&server, &[(0, 0.5)]).await;
+    check_test_recv_value(&mut device, vibrate_write(vec![0xF1, 64]));
+    let pause = server.pause_all_devices().await;
+    assert_eq!(pause.device_indexes(), vec![0]);
+    check_test_recv_value(&mut device, vibrate_write(vec![0xF1, 0]));
+    assert!(device.receiver.try_recv().is_err());
+
+    pause.resume().await.expect("Test, assuming infallible.");
+    check_test_recv_value(&mut device, vibrate_write(vec![0xF1, 64]));
+    assert!(device.receiver.try_recv().is_err());
+
+    // Devices stopped or sent new values while paused aren't resumed.
+    let pause = server.pause_all_devices().await;
+    check_test_recv_value(&mut device, vibrate_write(vec![0xF1, 0]));
+    server
+      .parse_message(message::StopDeviceCmd::new(0).into())
+      .await
+      .expect("Test, assuming infallible.");
+    while device.receiver.try_recv().is_ok() {}
+    pause.resume().await.expect("Test, assuming infallible.");
+    assert!(device.receiver.try_recv().is_err());
+
+    send_vibrate(&server, &[(0, 0.5)]).await;
+    check_test_recv_value(&mut device, vibrate_write(vec![0xF1, 64]));
+    let pause = server.pause_all_devices().await;
+    check_test_recv_value(&mut device, vibrate_write(vec![0xF1, 0]));
+    send_vibrate(&server, &[(0, 0.25)]).await;
+    check_test_recv_value(&mut device, vibrate_write(vec![0xF1, 32]));
+    pause.resume().await.expect("Test, assuming infallible.");
+    assert!(device.receiver.try_recv().is_err());
+  });
+}
+
+#[test]
+fn test_server_device_index_for_address() {
+  async_manager::block_on(async {
+    let (server, _device) = start_test_server_with_connected_device(
+      &mut ButtplugServerBuilder::default(),
+      "Massage Demo",
+    )
+    .await;
+    assert_eq!(
+      server.device_index_for_address("debounce-test-addr"),
+      Some(0)
+    );
+    assert!(server.device_index_for_address("not-a-device").is_none());
+  });
+}
+
+#[test]
+fn test_server_device_stale_timeout() {
+  async_manager::block_on(async {
+    let (server, _device) = start_test_server_with_connected_device(
+      ButtplugServerBuilder::default().device_stale_timeout(Duration::from_millis(50)),
+      "Massage Demo",
+    )
+    .await;
+    let recv = server.event_stream();
+    pin_mut!(recv);
+    while let Some(msg) = recv.next().await {
+      if let ButtplugServerMessage::DeviceRemoved(removed) = msg {
+        assert_eq!(removed.device_index(), 0);
+        break;
+      }
+    }
+    assert!(server.device_last_seen(0).is_none());
+  });
+}
+
+#[test]
+fn test_server_stop_device() {
+  async_manager::block_on(async {
+    let (server, mut device) = start_test_server_with_connected_device(
+      &mut ButtplugServerBuilder::default(),
+      "Massage Demo",
+    )
+    .await;
+    send_vibrate(&server, &[(0, 0.5)]).await;
+    check_test_recv_value(&mut device, vibrate_write(vec![0xF1, 64]));
+    server
+      .stop_device(0)
+      .await
+      .expect("Test, assuming infallible.");
+    check_test_recv_value(&mut device, vibrate_write(vec![0xF1, 0]));
+    assert!(matches!(
+      server.stop_device(1).await,
+      Err(ButtplugError::ButtplugDeviceError(
+        ButtplugDeviceError::DeviceNotAvailable(1)
+      ))
+    ));
+  });
+}
+
+#[test]
+fn test_server_calibrate_device() {
+  async_manager::block_on(async {
+    let (server, _device) = start_test_server_with_connected_device(
+      &mut ButtplugServerBuilder::default(),
+      "Massage Demo",
+    )
+    .await;
+    // Aneros has no calibration sequence.
+    assert!(matches!(
+      server.calibrate_device(0).await,
+      Err(ButtplugError::ButtplugDeviceError(
+        ButtplugDeviceError::UnhandledCommand(_)
+      ))
+    ));
+    assert!(matches!(
+      server.calibrate_device(1).await,
+      Err(ButtplugError::ButtplugDeviceError(
+        ButtplugDeviceError::DeviceNotAvailable(1)
+      ))
+    ));
+  });
+}
+
+#[test]
+fn test_server_batch_stop() {
+  async_manager::block_on(async {
+    let (server, mut device) = start_test_server_with_connected_device(
+      &mut ButtplugServerBuilder::default(),
+      "Massage Demo",
+    )
+    .await;
+    send_vibrate(&server, &[(0, 0.5)]).await;
+    check_test_recv_value(&mut device, vibrate_write(vec![0xF1, 64]));
+    server
+      .batch_stop(&[0])
+      .await
+      .expect("Test, assuming infallible.");
+    check_test_recv_value(&mut device, vibrate_write(vec![0xF1, 0]));
+
+    // Devices that exist are still stopped if another index fails.
+    send_vibrate(&server, &[(0, 0.5)]).await;
+    check_test_recv_value(&mut device, vibrate_write(vec![0xF1, 64]));
+    assert!(matches!(
+      server.batch_stop(&[1, 0]).await,
+      Err(ButtplugError::ButtplugDeviceError(
+        ButtplugDeviceError::DeviceNotAvailable(1)
+      ))
+    ));
+    check_test_recv_value(&mut device, vibrate_write(vec![0xF1, 0]));
+  });
+}
+
+#[test]
+fn test_server_max_devices() {
+  async_manager::block_on(async {
+    let mut builder = TestDeviceCommunicationManagerBuilder::default();
+    let _first = builder.add_test_device(&TestDeviceIdentifier::new(
+      "Massage Demo",
+      Some("limit-test-addr-1".to_owned()),
+    ));
+    let _second = builder.add_test_device(&TestDeviceIdentifier::new(
+      "Massage Demo",
+      Some("limit-test-addr-2".to_owned()),
+    ));
+    let server = ButtplugServerBuilder::default()
+      .max_devices(1)
+      .comm_manager(builder)
+      .finish()
+      .unwrap();
+    let recv = server.event_stream();
+    pin_mut!(recv);
+    let limit_reached = server.device_limit_reached_stream();
+    pin_mut!(limit_reached);
+    server
+      .parse_message(
+        message::RequestServerInfo::new("Test Client", BUTTPLUG_CURRENT_MESSAGE_SPEC_VERSION)
+          .into(),
+      )
+      .await
+      .expect("Test, assuming infallible.");
+    server
+      .parse_message(message::StartScanning::default().into())
+      .await
+      .expect("Test, assuming infallible.");
+    assert_eq!(limit_reached.next().await, Some(()));
+    while let Some(msg) = recv.next().await {
+      if let ButtplugServerMessage::DeviceAdded(_) = msg {
+        break;
+      }
+    }
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    let device_list = server
+      .parse_message(message::RequestDeviceList::default().into())
+      .await
+      .expect("Test, assuming infallible.");
+    if let ButtplugServerMessage::DeviceList(list) = device_list {
+      assert_eq!(list.devices().len(), 1);
+    } else {
+      panic!("Expected a DeviceList reply.");
+    }
+  });
+}
+
+#[test]
+fn test_server_prioritized_scalar_cmd() {
+  async_manager::block_on(async {
+    let (server, mut device) = start_test_server_with_connected_device(
+      &mut ButtplugServerBuilder::default(),
+      "Massage Demo",
+    )
+    .await;
+    // Fill the test device's 256 command buffer, so the next write blocks while holding the
+    // device's turn, and everything after it has to queue.
+    for i in 0..256 {
+      send_vibrate(&server, &[(0, if i % 2 == 0 { 0.5 } else { 0.0 })]).await;
+    }
+    let blocked = async_manager::spawn_with_handle(
+      server.parse_message(
+        message::ScalarCmd::new(
+          0,
+          vec![ScalarSubcommand::new(1, 0.5, ActuatorType::Vibrate)],
+        )
+        .into(),
+      ),
+    )
+    .expect("Test, assuming infallible.");
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    let prioritized_cmd = |index: u32, priority: CommandPriority| {
+      message::PrioritizedScalarCmd::new(
+        0,
+        priority,
+        vec![ScalarSubcommand::new(index, 1.0, ActuatorType::Vibrate)],
+      )
+    };
+    let low = async_manager::spawn_with_handle(
+      server.parse_message(prioritized_cmd(1, CommandPriority::Low).into()),
+    )
+    .expect("Test, assuming infallible.");
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    let high = async_manager::spawn_with_handle(
+      server.parse_message(prioritized_cmd(0, CommandPriority::High).into()),
+    )
+    .expect("Test, assuming infallible.");
+    tokio::time::sleep(Duration::from_millis(50)).await;
+
+    for i in 0..256 {
+      assert_eq!(
+        device.receiver.recv().await,
+        Some(vibrate_write(vec![0xF1, if i % 2 == 0 { 64 } else { 0 }]))
+      );
+    }
+    for handle in [blocked, low, high] {
+      handle.await.expect("Test, assuming infallible.");
+    }
+    // The high priority command arrived last, but goes out ahead of the low priority one.
+    check_test_recv_value(&mut device, vibrate_write(vec![0xF2, 64]));
+    check_test_recv_value(&mut device, vibrate_write(vec![0xF1, 127]));
+    check_test_recv_value(&mut device, vibrate_write(vec![0xF2, 127]));
+  });
+}
+
+#[test]
+fn test_server_prioritized_scalar_cmd_critical_is_clamped() {
+  async_manager::block_on(async {
+    let (server, mut device) = start_test_server_with_connected_device(
+      &mut ButtplugServerBuilder::default(),
+      "Massage Demo",
+    )
+    .await;
+    for i in 0..256 {
+      send_vibrate(&server, &[(0, if i % 2 == 0 { 0.5 } else { 0.0 })]).await;
+    }
+    let blocked = async_manager::spawn_with_handle(
+      server.parse_message(
+        message::ScalarCmd::new(
+          0,
+          vec![ScalarSubcommand::new(1, 0.5, ActuatorType::Vibrate)],
+        )
+        .into(),
+      ),
+    )
+    .expect("Test, assuming infallible.");
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    // Wait for each command to be queued, rather than guessing how long that takes.
+    let queued = server.pending_command_count();
+    let critical = async_manager::spawn_with_handle(
+      server.parse_message(
+        message::PrioritizedScalarCmd::new(
+          0,
+          CommandPriority::Critical,
+          vec![ScalarSubcommand::new(0, 1.0, ActuatorType::Vibrate)],
+        )
+        .into(),
+      ),
+    )
+    .expect("Test, assuming infallible.");
+    while server.pending_command_count() <= queued {
+      tokio::time::sleep(Duration::from_millis(1)).await;
+    }
+    let stop =
+      async_manager::spawn_with_handle(server.parse_message(message::StopDeviceCmd::new(0).into()))
+        .expect("Test, assuming infallible.");
+    while server.pending_command_count() <= queued + 1 {
+      tokio::time::sleep(Duration::from_millis(1)).await;
+    }
+
+    for _ in 0..256 {
+      assert!(device.receiver.recv().await.is_some());
+    }
+    for handle in [blocked, critical, stop] {
+      handle.await.expect("Test, assuming infallible.");
+    }
+    // The client's critical command arrived first, but the stop still goes out ahead of it.
+    let mut writes = vec![];
+    while let Ok(write) = device.receiver.try_recv() {
+      writes.push(write);
+    }
+    let position = |data: Vec<u8>| {
+      writes
+        .iter()
+        .position(|write| *write == vibrate_write(data.clone()))
+        .expect("Test, assuming infallible.")
+    };
+    assert!(position(vec![0xF1, 0]) < position(vec![0xF1, 127]));
+  });
+}
+
+#[test]
+fn test_server_global_stop() {
+  async_manager::block_on(async {
+    // The delay comm manager keeps scanning until it's stopped, so the scan is still running when
+    // the global stop comes in.
+    let (server, mut device) = start_test_server_with_connected_device(
+      ButtplugServerBuilder::default()
+        .comm_manager(DelayDeviceCommunicationManagerBuilder::default()),
+      "Massage Demo",
+    )
+    .await;
+    assert!(server.active_scan_duration().is_some());
+    let recv = server.event_stream();
+    pin_mut!(recv);
+    send_vibrate(&server, &[(0, 0.5), (1, 0.5)]).await;
+    check_test_recv_value(&mut device, vibrate_write(vec![0xF1, 64]));
+    check_test_recv_value(&mut device, vibrate_write(vec![0xF2, 64]));
+    server
+      .global_stop()
+      .await
+      .expect("Test, assuming infallible.");
+    check_test_recv_value(&mut device, vibrate_write(vec![0xF1, 0]));
+    check_test_recv_value(&mut device, vibrate_write(vec![0xF2, 0]));
+    while let Some(msg) = recv.next().await {
+      if matches!(msg, ButtplugServerMessage::ScanningFinished(_)) {
+        break;
+      }
+    }
+    assert!(server.active_scan_duration().is_none());
+    // Nothing left to stop, so stopping again is fine.
+    server
+      .global_stop()
+      .await
+      .expect("Test, assuming infallible.");
+  });
+}
+
+#[test]
+fn test_server_force_stop_all_devices() {
+  async_manager::block_on(async {
+    let (server, mut device) = start_test_server_with_connected_device(
+      &mut ButtplugServerBuilder::default(),
+      "Massage Demo",
+    )
+    .await;
+    // Block the device on a full command buffer, with more commands queued behind it.
+    for i in 0..256 {
+      send_vibrate(&server, &[(0, if i % 2 == 0 { 0.5 } else { 0.0 })]).await;
+    }
+    let blocked = async_manager::spawn_with_handle(
+      server.parse_message(
+        message::ScalarCmd::new(
+          0,
+          vec![ScalarSubcommand::new(1, 0.5, ActuatorType::Vibrate)],
+        )
+        .into(),
+      ),
+    )
+    .expect("Test, assuming infallible.");
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    let queued = async_manager::spawn_with_handle(
+      server.parse_message(
+        message::ScalarCmd::new(
+          0,
+          vec![ScalarSubcommand::new(0, 1.0, ActuatorType::Vibrate)],
+        )
+        .into(),
+      ),
+    )
+    .expect("Test, assuming infallible.");
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    let server = Arc::new(server);
+    let server_clone = server.clone();
+    let stop = async_manager::spawn_with_handle(async move {
+      server_clone
+        .force_stop_all_devices(Duration::from_secs(5))
+        .await
+    })
+    .expect("Test, assuming infallible.");
+    tokio::time::sleep(Duration::from_millis(50)).await;
+
+    for _ in 0..256 {
+      assert!(device.receiver.recv().await.is_some());
+    }
+    assert!(blocked.await.is_ok());
+    assert!(matches!(
+      queued.await.unwrap_err().original_error(),
+      ButtplugError::ButtplugDeviceError(ButtplugDeviceError::DeviceCommandFlushed)
+    ));
+    assert!(stop.await.is_ok());
+    // The write in flight finishes, then the stop goes out, and the queued command never does.
+    check_test_recv_value(&mut device, vibrate_write(vec![0xF2, 64]));
+    check_test_recv_value(&mut device, vibrate_write(vec![0xF1, 0]));
+    check_test_recv_value(&mut device, vibrate_write(vec![0xF2, 0]));
+    assert!(device.receiver.try_recv().is_err());
+  });
+}
@@ -0,0 +1,354 @@
+// Buttplug Rust Source Code File - See https://buttplug.io for more info.
+//
+// Copyright 2016-2023 Nonpolynomial Labs LLC. All rights reserved.
+//
+// Licensed under the BSD 3-Clause license. See LICENSE file in the project root
+// for full license information.
+
+use buttplug::{
+  client::ButtplugClient,
+  core::{
+    connector::{
+      transport::{
+        ButtplugConnectorTransport,
+        ButtplugFramer,
+        ButtplugStreamTransport,
+        ButtplugTransportIncomingMessage,
+        ByteOrder,
+        FrameTooLarge,
+        LengthPrefixFramer,
+        NewlineFramer,
+        DEFAULT_MAX_FRAME_SIZE,
+      },
+      ButtplugCompressor,
+      ButtplugConnectorError,
+      ButtplugRemoteClientConnector,
+      ButtplugRemoteServerConnector,
+    },
+    message::{
+      serializer::{ButtplugClientJSONSerializer, ButtplugServerJSONSerializer},
+      CompressionAlgorithm,
+    },
+  },
+  server::ButtplugRemoteServer,
+  util::async_manager,
+};
+use bytes::BytesMut;
+use std::{
+  sync::{
+    atomic::{AtomicUsize, Ordering},
+    Arc,
+  },
+  time::Duration,
+};
+use test_case::test_case;
+use tokio::{
+  io::{AsyncReadExt, AsyncWriteExt},
+  sync::mpsc,
+};
+
+#[test_case(NewlineFramer ; "newline")]
+#[test_case(LengthPrefixFramer(ByteOrder::LittleEndian) ; "length prefix")]
+fn test_client_server_stream_transport<F: ButtplugFramer + Clone>(framer: F) {
+  async_manager::block_on(async move {
+    let (client_stream, server_stream) = tokio::io::duplex(4096);
+    let server = Arc::new(ButtplugRemoteServer::default());
+    let server_clone = server.clone();
+    let server_framer = framer.clone();
+    async_manager::spawn(async move {
+      let connector = ButtplugRemoteServerConnector::<_, ButtplugServerJSONSerializer>::new(
+        ButtplugStreamTransport::new(server_stream, server_framer),
+      );
+      server_clone
+        .start(connector)
+        .await
+        .expect("Test, assuming infallible.");
+    });
+    let connector = ButtplugRemoteClientConnector::<_, ButtplugClientJSONSerializer>::new(
+      ButtplugStreamTransport::new(client_stream, framer),
+    );
+    let client = ButtplugClient::new("Test Client");
+    client
+      .connect(connector)
+      .await
+      .expect("Test, assuming infallible.");
+    assert!(client.connected());
+    assert_eq!(client.server_name(), Some("Buttplug Server".to_owned()));
+  });
+}
+
+#[test]
+fn test_length_prefix_framer_partial_frames() {
+  let framer = LengthPrefixFramer(ByteOrder::BigEndian);
+  let mut frames = BytesMut::new();
+  frames.extend_from_slice(&framer.encode(b"first"));
+  frames.extend_from_slice(&framer.encode(b"second"));
+  assert_eq!(&frames[..4], &[0, 0, 0, 5]);
+
+  // Feed the data in one byte at a time, as if it was trickling in from a socket.
+  let mut buffer = BytesMut::new();
+  let mut decoded = vec![];
+  for byte in frames.iter() {
+    buffer.extend_from_slice(&[*byte]);
+    if let Some(frame) = framer
+      .decode(&mut buffer, DEFAULT_MAX_FRAME_SIZE)
+      .expect("Test, assuming infallible.")
+    {
+      decoded.push(frame);
+    }
+  }
+  assert_eq!(decoded, vec!["first".as_bytes(), "second".as_bytes()]);
+  assert!(buffer.is_empty());
+}
+
+#[test]
+fn test_framers_reject_oversized_frames() {
+  // A length prefix over the limit fails before any room is made for the frame.
+  let framer = LengthPrefixFramer(ByteOrder::BigEndian);
+  let mut buffer = BytesMut::from(&[0xFF, 0xFF, 0xFF, 0xFF][..]);
+  assert_eq!(
+    framer.decode(&mut buffer, 1024),
+    Err(FrameTooLarge {
+      size: u32::MAX as usize,
+      max_size: 1024
+    })
+  );
+  assert!(buffer.capacity() < 1024);
+  let mut buffer = BytesMut::from(&framer.encode(&[0; 16])[..]);
+  assert!(framer.decode(&mut buffer, 16).unwrap().is_some());
+
+  // Newline framing fails once more than the limit is buffered without a newline.
+  let mut buffer = BytesMut::from(&[b'a'; 16][..]);
+  assert_eq!(NewlineFramer.decode(&mut buffer, 16), Ok(None));
+  buffer.extend_from_slice(b"a");
+  assert_eq!(
+    NewlineFramer.decode(&mut buffer, 16),
+    Err(FrameTooLarge {
+      size: 17,
+      max_size: 16
+    })
+  );
+}
+
+#[test]
+fn test_stream_transport_closes_on_oversized_frame() {
+  async_manager::block_on(async move {
+    let (mut remote_stream, stream) = tokio::io::duplex(4096);
+    let transport = ButtplugStreamTransport::new(stream, NewlineFramer).with_max_frame_size(64);
+    assert_eq!(transport.max_message_size(), Some(64));
+    let (_outgoing_sender, outgoing_receiver) = mpsc::channel(1);
+    let (incoming_sender, mut incoming_receiver) = mpsc::channel(1);
+    transport
+      .connect(outgoing_receiver, incoming_sender)
+      .await
+      .expect("Test, assuming infallible.");
+    remote_stream
+      .write_all(&[b'a'; 128])
+      .await
+      .expect("Test, assuming infallible.");
+    assert!(matches!(
+      incoming_receiver.recv().await,
+      Some(ButtplugTransportIncomingMessage::Close(_))
+    ));
+    // The other end sees the connection close.
+    let mut buf = vec![];
+    assert_eq!(
+      remote_stream
+        .read_to_end(&mut buf)
+        .await
+        .expect("Test, assuming infallible."),
+      0
+    );
+  });
+}
+
+/// Stands in for a real compression library. Flipping every bit is enough to make sure both sides
+/// agree on when compression starts.
+#[derive(Default)]
+struct TestCompressor {
+  algorithm: CompressionAlgorithm,
+  compressed: AtomicUsize,
+  decompressed: AtomicUsize,
+  fail_decompress: bool,
+}
+
+impl TestCompressor {
+  fn new(algorithm: CompressionAlgorithm) -> Arc<Self> {
+    Arc::new(Self {
+      algorithm,
+      ..Default::default()
+    })
+  }
+}
+
+impl ButtplugCompressor for TestCompressor {
+  fn algorithm(&self) -> CompressionAlgorithm {
+    self.algorithm
+  }
+
+  fn compress(&self, data: &[u8]) -> Result<Vec<u8>, ButtplugConnectorError> {
+    self.compressed.fetch_add(1, Ordering::SeqCst);
+    Ok(data.iter().map(|byte| !byte).collect())
+  }
+
+  fn decompress(&self, data: &[u8]) -> Result<Vec<u8>, ButtplugConnectorError> {
+    self.decompressed.fetch_add(1, Ordering::SeqCst);
+    if self.fail_decompress {
+      return Err(ButtplugConnectorError::ConnectorGenericError(
+        "Corrupt data".to_owned(),
+      ));
+    }
+    Ok(data.iter().map(|byte| !byte).collect())
+  }
+}
+
+#[test]
+fn test_client_server_compression_negotiation() {
+  async_manager::block_on(async move {
+    let (client_stream, server_stream) = tokio::io::duplex(4096);
+    let client_lz4 = TestCompressor::new(CompressionAlgorithm::Lz4);
+    let client_zstd = TestCompressor::new(CompressionAlgorithm::Zstd { level: 1 });
+    let server_zstd = TestCompressor::new(CompressionAlgorithm::Zstd { level: 3 });
+    let server = Arc::new(ButtplugRemoteServer::default());
+    let server_clone = server.clone();
+    let server_compressor: Arc<dyn ButtplugCompressor> = server_zstd.clone();
+    async_manager::spawn(async move {
+      let connector = ButtplugRemoteServerConnector::<_, ButtplugServerJSONSerializer>::new(
+        ButtplugStreamTransport::new(server_stream, LengthPrefixFramer::default()),
+      )
+      .with_compression(vec![server_compressor]);
+      server_clone
+        .start(connector)
+        .await
+        .expect("Test, assuming infallible.");
+    });
+    let connector = ButtplugRemoteClientConnector::<_, ButtplugClientJSONSerializer>::new(
+      ButtplugStreamTransport::new(client_stream, LengthPrefixFramer::default()),
+    )
+    .with_compression(vec![client_lz4.clone(), client_zstd.clone()]);
+    let client = ButtplugClient::new("Test Client");
+    client
+      .connect(connector)
+      .await
+      .expect("Test, assuming infallible.");
+    // Connecting requests the device list after the handshake, which should be compressed both
+    // ways with zstd, the most preferred algorithm both sides have.
+    assert!(client.connected());
+    assert!(client.devices().is_empty());
+    assert!(client_zstd.compressed.load(Ordering::SeqCst) > 0);
+    assert!(client_zstd.decompressed.load(Ordering::SeqCst) > 0);
+    assert!(server_zstd.compressed.load(Ordering::SeqCst) > 0);
+    assert!(server_zstd.decompressed.load(Ordering::SeqCst) > 0);
+    assert_eq!(client_lz4.compressed.load(Ordering::SeqCst), 0);
+  });
+}
+
+#[test]
+fn test_client_compression_without_server_support() {
+  async_manager::block_on(async move {
+    let (client_stream, server_stream) = tokio::io::duplex(4096);
+    let server = Arc::new(ButtplugRemoteServer::default());
+    let server_clone = server.clone();
+    async_manager::spawn(async move {
+      let connector = ButtplugRemoteServerConnector::<_, ButtplugServerJSONSerializer>::new(
+        ButtplugStreamTransport::new(server_stream, LengthPrefixFramer::default()),
+      );
+      server_clone
+        .start(connector)
+        .await
+        .expect("Test, assuming infallible.");
+    });
+    let client_lz4 = TestCompressor::new(CompressionAlgorithm::Lz4);
+    let connector = ButtplugRemoteClientConnector::<_, ButtplugClientJSONSerializer>::new(
+      ButtplugStreamTransport::new(client_stream, LengthPrefixFramer::default()),
+    )
+    .with_compression(vec![client_lz4.clone()]);
+    let client = ButtplugClient::new("Test Client");
+    client
+      .connect(connector)
+      .await
+      .expect("Test, assuming infallible.");
+    assert!(client.connected());
+    assert_eq!(client_lz4.compressed.load(Ordering::SeqCst), 0);
+  });
+}
+
+#[test]
+fn test_client_compression_rejects_newline_framing() {
+  async_manager::block_on(async move {
+    let (client_stream, _server_stream) = tokio::io::duplex(4096);
+    let connector = ButtplugRemoteClientConnector::<_, ButtplugClientJSONSerializer>::new(
+      ButtplugStreamTransport::new(client_stream, NewlineFramer),
+    )
+    .with_compression(vec![TestCompressor::new(CompressionAlgorithm::Lz4)]);
+    let client = ButtplugClient::new("Test Client");
+    assert!(client.connect(connector).await.is_err());
+    assert!(!client.connected());
+  });
+}
+
+#[test]
+fn test_client_compression_closes_connection_on_decompress_failure() {
+  async_manager::block_on(async move {
+    let (client_stream, server_stream) = tokio::io::duplex(4096);
+    let server = Arc::new(ButtplugRemoteServer::default());
+    let server_clone = server.clone();
+    async_manager::spawn(async move {
+      let connector = ButtplugRemoteServerConnector::<_, ButtplugServerJSONSerializer>::new(
+        ButtplugStreamTransport::new(server_stream, LengthPrefixFramer::default()),
+      )
+      .with_compression(vec![TestCompressor::new(CompressionAlgorithm::Lz4)]);
+      // The server may see the connection drop mid handshake.
+      let _ = server_clone.start(connector).await;
+    });
+    let client_lz4 = Arc::new(TestCompressor {
+      algorithm: CompressionAlgorithm::Lz4,
+      fail_decompress: true,
+      ..Default::default()
+    });
+    let connector = ButtplugRemoteClientConnector::<_, ButtplugClientJSONSerializer>::new(
+      ButtplugStreamTransport::new(client_stream, LengthPrefixFramer::default()),
+    )
+    .with_compression(vec![client_lz4.clone()]);
+    let client = ButtplugClient::new("Test Client");
+    // The device list reply is the first compressed message, so connecting can fail there, or the
+    // client can find out it's been disconnected right after.
+    let _ = client.connect(connector).await;
+    for _ in 0..100 {
+      if !client.connected() {
+        break;
+      }
+      tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+    assert!(client_lz4.decompressed.load(Ordering::SeqCst) > 0);
+    assert!(!client.connected());
+  });
+}
+
+#[cfg(all(feature = "unix", unix))]
+#[test]
+fn test_client_server_unix_stream_connector() {
+  use buttplug::core::connector::ButtplugUnixStreamServerConnector;
+  async_manager::block_on(async move {
+    let (client_stream, server_stream) =
+      tokio::net::UnixStream::pair().expect("Test, assuming infallible.");
+    let server = Arc::new(ButtplugRemoteServer::default());
+    let server_clone = server.clone();
+    async_manager::spawn(async move {
+      server_clone
+        .start(ButtplugUnixStreamServerConnector::from(server_stream))
+        .await
+        .expect("Test, assuming infallible.");
+    });
+    let connector = ButtplugRemoteClientConnector::<_, ButtplugClientJSONSerializer>::new(
+      ButtplugStreamTransport::new(client_stream, NewlineFramer),
+    );
+    let client = ButtplugClient::new("Test Client");
+    client
+      .connect(connector)
+      .await
+      .expect("Test, assuming infallible.");
+    assert!(client.connected());
+    assert_eq!(client.server_name(), Some("Buttplug Server".to_owned()));
+  });
+}
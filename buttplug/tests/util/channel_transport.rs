@@ -126,7 +126,11 @@ pub struct ChannelClientTestHelper {
 
 impl ChannelClientTestHelper {
   pub fn new() -> Self {
-    let client = Arc::new(ButtplugClient::new("test client"));
+    Self::with_client(ButtplugClient::new("test client"))
+  }
+
+  pub fn with_client(client: ButtplugClient) -> Self {
+    let client = Arc::new(client);
     let (incoming_sender, incoming_receiver) = channel(256);
     let (outgoing_sender, outgoing_receiver) = channel(256);
     let connector = Arc::new(Mutex::new(Some(ButtplugRemoteClientConnector::<
@@ -179,6 +183,8 @@ impl ChannelClientTestHelper {
       .expect("Test, assuming infallible");
     let finish_notifier = Arc::new(Notify::new());
     let finish_notifier_clone = finish_notifier.clone();
+    // Created up front, since notify_waiters only wakes futures that exist when it's called.
+    let finished = finish_notifier.notified();
     async_manager::spawn(async move {
       if let Err(e) = client_clone.connect(connector).await {
         assert!(false, "Error connecting to client: {:?}", e);
@@ -209,7 +215,7 @@ impl ChannelClientTestHelper {
     let mut dl = message::DeviceList::new(vec![]);
     dl.set_id(2);
     self.send_client_incoming(dl.into()).await;
-    finish_notifier.notified().await;
+    finished.await;
   }
 
   pub async fn next_client_message(&self) -> ButtplugClientMessage {
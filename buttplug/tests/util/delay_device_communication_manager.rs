@@ -14,20 +14,38 @@ use buttplug::{
   },
 };
 use futures::FutureExt;
-use std::sync::{
-  atomic::{AtomicBool, Ordering},
-  Arc,
+use std::{
+  sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc,
+  },
+  time::Duration,
 };
 use tokio::sync::mpsc::Sender;
 
 #[derive(Default)]
-pub struct DelayDeviceCommunicationManagerBuilder {}
+pub struct DelayDeviceCommunicationManagerBuilder {
+  init_delay: Option<Duration>,
+}
+
+impl DelayDeviceCommunicationManagerBuilder {
+  /// Block for this long while building the comm manager, to simulate slow hardware.
+  #[allow(dead_code)]
+  pub fn with_init_delay(init_delay: Duration) -> Self {
+    Self {
+      init_delay: Some(init_delay),
+    }
+  }
+}
 
 impl HardwareCommunicationManagerBuilder for DelayDeviceCommunicationManagerBuilder {
   fn finish(
     &mut self,
     sender: Sender<HardwareCommunicationManagerEvent>,
   ) -> Box<dyn HardwareCommunicationManager> {
+    if let Some(delay) = self.init_delay {
+      std::thread::sleep(delay);
+    }
     Box::new(DelayDeviceCommunicationManager::new(sender))
   }
 }
@@ -13,10 +13,14 @@ pub use delay_device_communication_manager::DelayDeviceCommunicationManagerBuild
 mod channel_transport;
 use buttplug::{
   client::ButtplugClient,
-  core::connector::ButtplugInProcessClientConnectorBuilder,
+  core::{
+    connector::ButtplugInProcessClientConnectorBuilder,
+    message::{self, ButtplugServerMessage, BUTTPLUG_CURRENT_MESSAGE_SPEC_VERSION},
+  },
   server::{ButtplugServer, ButtplugServerBuilder},
 };
 pub use channel_transport::*;
+use futures::{pin_mut, StreamExt};
 pub use test_device_manager::{
   TestDeviceChannelHost,
   TestDeviceCommunicationManagerBuilder,
@@ -89,6 +93,39 @@ pub async fn test_client_with_delayed_device_manager() -> ButtplugClient {
   client
 }
 
+/// Build a server with a test device, then complete the handshake and scan until the device has
+/// connected, at index 0.
+#[allow(dead_code)]
+pub async fn start_test_server_with_connected_device(
+  server_builder: &mut ButtplugServerBuilder,
+  device_name: &str,
+) -> (ButtplugServer, TestDeviceChannelHost) {
+  let mut builder = TestDeviceCommunicationManagerBuilder::default();
+  let device = builder.add_test_device(&TestDeviceIdentifier::new(
+    device_name,
+    Some("debounce-test-addr".to_owned()),
+  ));
+  let server = server_builder.comm_manager(builder).finish().unwrap();
+  let recv = server.event_stream();
+  pin_mut!(recv);
+  server
+    .parse_message(
+      message::RequestServerInfo::new("Test Client", BUTTPLUG_CURRENT_MESSAGE_SPEC_VERSION).into(),
+    )
+    .await
+    .expect("Test, assuming infallible.");
+  server
+    .parse_message(message::StartScanning::default().into())
+    .await
+    .expect("Test, assuming infallible.");
+  while let Some(msg) = recv.next().await {
+    if let ButtplugServerMessage::DeviceAdded(_) = msg {
+      break;
+    }
+  }
+  (server, device)
+}
+
 #[allow(dead_code)]
 pub async fn test_server_with_device(
   device_type: &str,
@@ -33,7 +33,10 @@ use serde::{Deserialize, Serialize};
 use std::{
   collections::{HashSet, VecDeque},
   fmt::{self, Debug},
-  sync::Arc,
+  sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc,
+  },
 };
 use tokio::sync::{broadcast, mpsc, Mutex};
 
@@ -43,6 +46,13 @@ pub struct TestHardwareNotification {
   data: Vec<u8>,
 }
 
+impl TestHardwareNotification {
+  #[allow(dead_code)]
+  pub fn new(endpoint: Endpoint, data: Vec<u8>) -> Self {
+    Self { endpoint, data }
+  }
+}
+
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub enum TestHardwareEvent {
   // Values to be emitted from subscriptions
@@ -50,6 +60,8 @@ pub enum TestHardwareEvent {
   // Values to be emitted when calls to ReadValue happen
   Reads(Vec<TestHardwareNotification>),
   Disconnect,
+  // Make writes fail (or succeed again), as if the connection was flaky
+  FailWrites(bool),
 }
 
 pub struct TestHardwareConnector {
@@ -162,6 +174,7 @@ pub struct TestDevice {
   event_sender: broadcast::Sender<HardwareEvent>,
   subscribed_endpoints: Arc<DashSet<Endpoint>>,
   read_data: Arc<Mutex<VecDeque<HardwareReading>>>,
+  fail_writes: Arc<AtomicBool>,
 }
 
 impl TestDevice {
@@ -176,6 +189,8 @@ impl TestDevice {
     let subscribed_endpoints_clone = subscribed_endpoints.clone();
     let read_data = Arc::new(Mutex::new(VecDeque::new()));
     let read_data_clone = read_data.clone();
+    let fail_writes = Arc::new(AtomicBool::new(false));
+    let fail_writes_clone = fail_writes.clone();
     async_manager::spawn(async move {
       while let Some(event) = receiver.recv().await {
         match event {
@@ -203,6 +218,7 @@ impl TestDevice {
               guard.push_front(HardwareReading::new(read.endpoint, &read.data));
             }
           }
+          TestHardwareEvent::FailWrites(fail) => fail_writes_clone.store(fail, Ordering::SeqCst),
         }
       }
     });
@@ -215,6 +231,7 @@ impl TestDevice {
       event_sender,
       subscribed_endpoints,
       read_data,
+      fail_writes,
     }
   }
 
@@ -301,6 +318,12 @@ impl HardwareInternal for TestDevice {
     if !self.endpoints.contains(&msg.endpoint()) {
       return future::ready(Err(ButtplugDeviceError::InvalidEndpoint(msg.endpoint()))).boxed();
     }
+    if self.fail_writes.load(Ordering::SeqCst) {
+      return future::ready(Err(ButtplugDeviceError::DeviceCommunicationError(
+        "Test write failure".to_owned(),
+      )))
+      .boxed();
+    }
     self.send_command(msg.clone().into())
   }
 
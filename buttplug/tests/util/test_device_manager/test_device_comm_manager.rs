@@ -18,6 +18,7 @@ use buttplug::{
   core::ButtplugResultFuture,
   server::device::configuration::{BluetoothLESpecifier, ProtocolCommunicationSpecifier},
   server::device::hardware::communication::{
+    AdvertisementRecord,
     HardwareCommunicationManager,
     HardwareCommunicationManagerBuilder,
     HardwareCommunicationManagerEvent,
@@ -121,6 +122,7 @@ pub struct TestDeviceCommunicationManager {
   device_sender: Sender<HardwareCommunicationManagerEvent>,
   devices: Vec<(TestDeviceIdentifier, TestDeviceChannelDevice)>,
   is_scanning: Arc<AtomicBool>,
+  reporting_advertisements: bool,
 }
 
 impl TestDeviceCommunicationManager {
@@ -132,6 +134,7 @@ impl TestDeviceCommunicationManager {
       device_sender,
       devices,
       is_scanning: Arc::new(AtomicBool::new(false)),
+      reporting_advertisements: false,
     }
   }
 }
@@ -151,9 +154,15 @@ impl HardwareCommunicationManager for TestDeviceCommunicationManager {
     while let Some((device, test_channel)) = self.devices.pop() {
       let device_creator = new_uninitialized_ble_test_device(&device, test_channel);
 
+      if self.reporting_advertisements {
+        events.push(HardwareCommunicationManagerEvent::Advertisement(
+          AdvertisementRecord::new(&device.address, &device.name, None, &[], &[]),
+        ));
+      }
       events.push(HardwareCommunicationManagerEvent::DeviceFound {
         name: device.name.clone(),
         address: device.address,
+        rssi: None,
         creator: Box::new(device_creator),
       });
     }
@@ -193,4 +202,8 @@ impl HardwareCommunicationManager for TestDeviceCommunicationManager {
   fn scanning_status(&self) -> bool {
     self.is_scanning.load(Ordering::SeqCst)
   }
+
+  fn set_reporting_advertisements(&mut self, reporting: bool) {
+    self.reporting_advertisements = reporting;
+  }
 }